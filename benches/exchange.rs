@@ -0,0 +1,148 @@
+//! Hot-path benchmark of the [`BasicExchange`].
+//!
+//! Reports the wall-clock time and the number of heap allocations per processed order,
+//! including the matching of the orders and the replies to their submitter.
+//! Run with `cargo bench --features concrete --bench exchange`.
+
+// Wall-clock time is what the benchmark measures
+#![allow(clippy::disallowed_methods)]
+
+use {
+    rand::{rngs::StdRng, SeedableRng},
+    std::{
+        alloc::{GlobalAlloc, Layout, System},
+        hint::black_box,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Instant,
+    },
+    trading_backtester::{
+        concrete::{
+            exchange::BasicExchange,
+            message_protocol::replay::request::{BasicReplayRequest, BasicReplayToExchange},
+            order::{LimitOrderPlacingRequest, MarketOrderPlacingRequest},
+            traded_pair::{Base, settlement::concrete::SpotSettlement, TradedPair},
+            types::{Direction, Lots, OrderID, Tick, TickSize},
+        },
+        interface::exchange::Exchange,
+        types::{Date, TimeSync},
+        utils::queue::{LessElementBinaryHeap, MessageReceiver},
+    },
+};
+
+/// Global allocator that counts the number of allocations.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const NUM_ORDERS: u64 = 200_000;
+const NUM_LEVELS: u64 = 8;
+
+type BenchExchange = BasicExchange<&'static str, u8, &'static str, SpotSettlement>;
+
+fn pair() -> TradedPair<&'static str, SpotSettlement> {
+    TradedPair {
+        quoted_asset: Base::new("USD").into(),
+        settlement_asset: Base::new("RUB").into(),
+        settlement_determinant: SpotSettlement,
+    }
+}
+
+/// Processes the replay request, discarding the actions of the exchange.
+fn process(
+    exchange: &mut BenchExchange,
+    queue: &mut LessElementBinaryHeap<usize>,
+    rng: &mut StdRng,
+    content: BasicReplayRequest<&'static str, SpotSettlement>)
+{
+    exchange.process_replay_request(
+        MessageReceiver::new(queue),
+        |action, _| {
+            black_box(action);
+            0
+        },
+        BasicReplayToExchange { exchange_id: "MOEX", content },
+        rng,
+    );
+    queue.0.clear()
+}
+
+/// Places limit orders at rotating price levels
+/// and sweeps every level with a market order right after it is filled,
+/// so that price levels are constantly created and emptied.
+fn place_and_sweep(
+    exchange: &mut BenchExchange,
+    queue: &mut LessElementBinaryHeap<usize>,
+    rng: &mut StdRng,
+    first_order_id: u64)
+{
+    for i in first_order_id..first_order_id + NUM_ORDERS {
+        let limit_order = LimitOrderPlacingRequest {
+            traded_pair: pair(),
+            order_id: OrderID(2 * i),
+            direction: Direction::Sell,
+            price: Tick(100 + (i % NUM_LEVELS) as i64),
+            size: Lots(10),
+            dummy: false,
+            time_in_force: Default::default(),
+        };
+        process(exchange, queue, rng, BasicReplayRequest::PlaceLimitOrder(limit_order));
+        if i % 2 == 1 {
+            let market_order = MarketOrderPlacingRequest {
+                traded_pair: pair(),
+                order_id: OrderID(2 * i + 1),
+                direction: Direction::Buy,
+                size: Lots(20),
+                dummy: false,
+            };
+            process(exchange, queue, rng, BasicReplayRequest::PlaceMarketOrder(market_order))
+        }
+    }
+}
+
+fn main() {
+    let mut exchange = BenchExchange::new("MOEX");
+    let mut queue = LessElementBinaryHeap::default();
+    let mut rng = StdRng::seed_from_u64(0);
+    *exchange.current_datetime_mut() = Date::from_ymd_opt(2022, 1, 3).unwrap()
+        .and_hms_opt(10, 0, 0).unwrap();
+    process(&mut exchange, &mut queue, &mut rng, BasicReplayRequest::ExchangeOpen);
+    process(
+        &mut exchange,
+        &mut queue,
+        &mut rng,
+        BasicReplayRequest::StartTrades { traded_pair: pair(), price_step: TickSize(0.01) },
+    );
+    // Warm up internal buffers
+    place_and_sweep(&mut exchange, &mut queue, &mut rng, 0);
+
+    let allocations_before = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    place_and_sweep(&mut exchange, &mut queue, &mut rng, NUM_ORDERS);
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations_before;
+
+    println!(
+        "exchange/place_and_sweep: {:.1} ns/order, {:.3} allocations/order",
+        elapsed.as_nanos() as f64 / NUM_ORDERS as f64,
+        allocations as f64 / NUM_ORDERS as f64,
+    )
+}
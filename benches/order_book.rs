@@ -0,0 +1,86 @@
+//! Hot-path benchmark of the [`OrderBook`].
+//!
+//! Reports the wall-clock time and the number of heap allocations per processed order.
+//! Run with `cargo bench --features concrete --bench order_book`.
+
+// Wall-clock time is what the benchmark measures
+#![allow(clippy::disallowed_methods)]
+
+use {
+    std::{
+        alloc::{GlobalAlloc, Layout, System},
+        hint::black_box,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Instant,
+    },
+    trading_backtester::{
+        concrete::{
+            order_book::OrderBook,
+            types::{Lots, OrderID, Tick},
+        },
+        types::Date,
+    },
+};
+
+/// Global allocator that counts the number of allocations.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const NUM_ORDERS: u64 = 1_000_000;
+const NUM_LEVELS: u64 = 8;
+
+/// Places limit orders at rotating price levels
+/// and sweeps every level with a market order right after it is filled,
+/// so that price levels are constantly created and emptied.
+fn place_and_sweep(order_book: &mut OrderBook, first_order_id: u64) {
+    let dt = Date::from_ymd_opt(1970, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    for i in first_order_id..first_order_id + NUM_ORDERS {
+        let price = Tick(100 + (i % NUM_LEVELS) as i64);
+        order_book.insert_limit_order::<_, false, false>(
+            dt, OrderID(i), price, Lots(10), |event| { black_box(event); },
+        );
+        if i % 2 == 1 {
+            order_book.insert_market_order::<_, false, true>(
+                Lots(20), |event| { black_box(event); },
+            )
+        }
+    }
+}
+
+fn main() {
+    let mut order_book = OrderBook::new();
+    // Warm up internal buffers
+    place_and_sweep(&mut order_book, 0);
+
+    let allocations_before = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    place_and_sweep(&mut order_book, NUM_ORDERS);
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations_before;
+
+    println!(
+        "order_book/place_and_sweep: {:.1} ns/order, {:.3} allocations/order",
+        elapsed.as_nanos() as f64 / NUM_ORDERS as f64,
+        allocations as f64 / NUM_ORDERS as f64,
+    )
+}
@@ -3,7 +3,10 @@ use {
     quote::quote,
     std::str::FromStr,
     syn::{
-        {Data, DeriveInput, Field, Ident, parse_macro_input},
+        {
+            Attribute, Data, DataStruct, DeriveInput, Field, Fields, Ident, Lit, Meta,
+            MetaNameValue, NestedMeta, parse_macro_input, Type, TypePath,
+        },
         __private::TokenStream2,
     },
 };
@@ -84,6 +87,14 @@ pub fn derive_trader(input: TokenStream) -> TokenStream
         TokenStream2::new()
     );
 
+    let latency_generator_name = TokenStream2::from_str(&format!("{name}LatencyGenerator"))
+        .unwrap();
+    let (mut update_latency_generator, mut save_state, mut restore_state) = (
+        TokenStream2::new(),
+        TokenStream2::new(),
+        TokenStream2::new()
+    );
+
     let process_variant = |(variant_name, variant_field): (&Ident, &Field)| {
         let as_trait = quote! {<#variant_field as Latent>};
         latency_generator.extend(quote! {#variant_name(#as_trait::LatencyGenerator),});
@@ -96,6 +107,13 @@ pub fn derive_trader(input: TokenStream) -> TokenStream
         let match_arm = quote! {Self::#variant_name(v) => v};
         outgoing_latency.extend(quote! {#match_arm.outgoing_latency(outer_id, event_dt, rng),});
         incoming_latency.extend(quote! {#match_arm.incoming_latency(outer_id, event_dt, rng),});
+        save_state.extend(quote! {#match_arm.save_state(),});
+        restore_state.extend(quote! {#match_arm.restore_state(state),});
+        update_latency_generator.extend(
+            quote! {(Self::#variant_name(v), #latency_generator_name::#variant_name(g)) =>
+                v.update_latency_generator(g),
+            }
+        );
 
         time_sync.extend(quote! {#match_arm.current_datetime_mut(),});
         get_latency.extend(quote! {#match_arm.get_latency_generator(),});
@@ -119,8 +137,6 @@ pub fn derive_trader(input: TokenStream) -> TokenStream
     idents.into_iter().zip(field_types.into_iter()).for_each(process_variant);
 
     let vis = ast.vis;
-    let latency_generator_name = TokenStream2::from_str(&format!("{name}LatencyGenerator"))
-        .unwrap();
 
     let tokens = quote! {
         #[derive(Copy, Clone)]
@@ -142,7 +158,7 @@ pub fn derive_trader(input: TokenStream) -> TokenStream
                 &mut self,
                 outer_id: Self::OuterID,
                 event_dt: DateTime,
-                rng: &mut impl Rng) -> u64
+                rng: &mut impl Rng) -> NanoDuration
             {
                 match self { #outgoing_latency }
             }
@@ -152,10 +168,20 @@ pub fn derive_trader(input: TokenStream) -> TokenStream
                 &mut self,
                 outer_id: Self::OuterID,
                 event_dt: DateTime,
-                rng: &mut impl Rng) -> u64
+                rng: &mut impl Rng) -> NanoDuration
             {
                 match self { #incoming_latency }
             }
+
+            #[inline]
+            fn save_state(&self) -> Option<Vec<u8>> {
+                match self { #save_state }
+            }
+
+            #[inline]
+            fn restore_state(&mut self, state: &[u8]) {
+                match self { #restore_state }
+            }
         }
 
         impl #impl_generics Trader
@@ -217,6 +243,15 @@ pub fn derive_trader(input: TokenStream) -> TokenStream
             fn get_latency_generator(&self) -> Self::LatencyGenerator {
                 match self { #get_latency_generator }
             }
+
+            #[inline]
+            #[allow(unreachable_patterns)]
+            fn update_latency_generator(&mut self, latency_generator: Self::LatencyGenerator) {
+                match (self, latency_generator) {
+                    #update_latency_generator
+                    _ => unreachable!("Latency generator does not match the agent variant")
+                }
+            }
         }
 
         impl #impl_generics Named<#trader_id>
@@ -310,7 +345,10 @@ pub fn derive_broker(input: TokenStream) -> TokenStream
         mut get_latency, mut latency_generator, mut get_latency_generator,
         mut outgoing_latency, mut incoming_latency,
         mut named, mut wakeup, mut process_trader_request, mut process_exchange_reply,
-        mut process_replay_request, mut upon_connection_to_exchange, mut register_trader) = (
+        mut process_replay_request, mut process_broker_message, mut get_b2b_recipient,
+        mut upon_connection_to_exchange, mut register_trader) = (
+        TokenStream2::new(),
+        TokenStream2::new(),
         TokenStream2::new(),
         TokenStream2::new(),
         TokenStream2::new(),
@@ -326,6 +364,14 @@ pub fn derive_broker(input: TokenStream) -> TokenStream
         TokenStream2::new()
     );
 
+    let latency_generator_name = TokenStream2::from_str(&format!("{name}LatencyGenerator"))
+        .unwrap();
+    let (mut update_latency_generator, mut save_state, mut restore_state) = (
+        TokenStream2::new(),
+        TokenStream2::new(),
+        TokenStream2::new()
+    );
+
     let process_variant = |(variant_name, variant_field): (&Ident, &Field)| {
         let as_trait = quote! {<#variant_field as Latent>};
         latency_generator.extend(quote! {#variant_name(#as_trait::LatencyGenerator),});
@@ -338,6 +384,13 @@ pub fn derive_broker(input: TokenStream) -> TokenStream
         let match_arm = quote! {Self::#variant_name(v) => v};
         outgoing_latency.extend(quote! {#match_arm.outgoing_latency(outer_id, event_dt, rng),});
         incoming_latency.extend(quote! {#match_arm.incoming_latency(outer_id, event_dt, rng),});
+        save_state.extend(quote! {#match_arm.save_state(),});
+        restore_state.extend(quote! {#match_arm.restore_state(state),});
+        update_latency_generator.extend(
+            quote! {(Self::#variant_name(v), #latency_generator_name::#variant_name(g)) =>
+                v.update_latency_generator(g),
+            }
+        );
 
         time_sync.extend(quote! {#match_arm.current_datetime_mut(),});
         get_latency.extend(quote! {#match_arm.get_latency_generator(),});
@@ -367,6 +420,14 @@ pub fn derive_broker(input: TokenStream) -> TokenStream
                 ),
             }
         );
+        process_broker_message.extend(
+            quote! {
+                #match_arm.process_broker_message(
+                    message_receiver, action_processor, message, broker_id, rng
+                ),
+            }
+        );
+        get_b2b_recipient.extend(quote! {#match_arm.get_b2b_recipient(message),});
         upon_connection_to_exchange.extend(
             quote! {#match_arm.upon_connection_to_exchange(exchange_id),}
         );
@@ -376,8 +437,6 @@ pub fn derive_broker(input: TokenStream) -> TokenStream
     idents.into_iter().zip(field_types.into_iter()).for_each(process_variant);
 
     let vis = ast.vis;
-    let latency_generator_name = TokenStream2::from_str(&format!("{name}LatencyGenerator"))
-        .unwrap();
 
     let tokens = quote! {
         #[derive(Copy, Clone)]
@@ -399,7 +458,7 @@ pub fn derive_broker(input: TokenStream) -> TokenStream
                 &mut self,
                 outer_id: Self::OuterID,
                 event_dt: DateTime,
-                rng: &mut impl Rng) -> u64
+                rng: &mut impl Rng) -> NanoDuration
             {
                 match self { #outgoing_latency }
             }
@@ -409,10 +468,20 @@ pub fn derive_broker(input: TokenStream) -> TokenStream
                 &mut self,
                 outer_id: Self::OuterID,
                 event_dt: DateTime,
-                rng: &mut impl Rng) -> u64
+                rng: &mut impl Rng) -> NanoDuration
             {
                 match self { #incoming_latency }
             }
+
+            #[inline]
+            fn save_state(&self) -> Option<Vec<u8>> {
+                match self { #save_state }
+            }
+
+            #[inline]
+            fn restore_state(&mut self, state: &[u8]) {
+                match self { #restore_state }
+            }
         }
 
         impl #impl_generics Broker
@@ -478,6 +547,23 @@ pub fn derive_broker(input: TokenStream) -> TokenStream
                 match self { #process_replay_request }
             }
 
+            #[inline]
+            fn process_broker_message<KerMsg: Ord>(
+                &mut self,
+                message_receiver: MessageReceiver<KerMsg>,
+                action_processor: impl LatentActionProcessor<Self::Action, Self::ExchangeID, KerMsg=KerMsg>,
+                message: Self::B2B,
+                broker_id: Self::BrokerID,
+                rng: &mut impl Rng,
+            ) {
+                match self { #process_broker_message }
+            }
+
+            #[inline]
+            fn get_b2b_recipient(&self, message: &Self::B2B) -> Self::BrokerID {
+                match self { #get_b2b_recipient }
+            }
+
             #[inline]
             fn upon_connection_to_exchange(&mut self, exchange_id: Self::ExchangeID) {
                 match self { #upon_connection_to_exchange }
@@ -512,6 +598,15 @@ pub fn derive_broker(input: TokenStream) -> TokenStream
             fn get_latency_generator(&self) -> Self::LatencyGenerator {
                 match self { #get_latency_generator }
             }
+
+            #[inline]
+            #[allow(unreachable_patterns)]
+            fn update_latency_generator(&mut self, latency_generator: Self::LatencyGenerator) {
+                match (self, latency_generator) {
+                    #update_latency_generator
+                    _ => unreachable!("Latency generator does not match the agent variant")
+                }
+            }
         }
 
         impl #impl_generics Named<#broker_id>
@@ -916,7 +1011,9 @@ pub fn derive_latency_generator(input: TokenStream) -> TokenStream
     let outer_id = get_associated_types(&first_field_type);
 
 
-    let (mut outgoing_latency, mut incoming_latency) = (
+    let (mut outgoing_latency, mut incoming_latency, mut save_state, mut restore_state) = (
+        TokenStream2::new(),
+        TokenStream2::new(),
         TokenStream2::new(),
         TokenStream2::new()
     );
@@ -925,7 +1022,9 @@ pub fn derive_latency_generator(input: TokenStream) -> TokenStream
         let match_arm = quote! {Self::#variant_name(v) => v};
 
         outgoing_latency.extend(quote! { #match_arm.outgoing_latency(outer_id, event_dt, rng), });
-        incoming_latency.extend(quote! { #match_arm.incoming_latency(outer_id, event_dt, rng), })
+        incoming_latency.extend(quote! { #match_arm.incoming_latency(outer_id, event_dt, rng), });
+        save_state.extend(quote! { #match_arm.save_state(), });
+        restore_state.extend(quote! { #match_arm.restore_state(state), })
     };
 
     idents.into_iter().for_each(process_variant);
@@ -942,7 +1041,7 @@ pub fn derive_latency_generator(input: TokenStream) -> TokenStream
                 &mut self,
                 outer_id: Self::OuterID,
                 event_dt: DateTime,
-                rng: &mut impl Rng) -> u64
+                rng: &mut impl Rng) -> NanoDuration
             {
                 match self { #outgoing_latency }
             }
@@ -952,10 +1051,20 @@ pub fn derive_latency_generator(input: TokenStream) -> TokenStream
                 &mut self,
                 outer_id: Self::OuterID,
                 event_dt: DateTime,
-                rng: &mut impl Rng) -> u64
+                rng: &mut impl Rng) -> NanoDuration
             {
                 match self { #incoming_latency }
             }
+
+            #[inline]
+            fn save_state(&self) -> Option<Vec<u8>> {
+                match self { #save_state }
+            }
+
+            #[inline]
+            fn restore_state(&mut self, state: &[u8]) {
+                match self { #restore_state }
+            }
         }
 
         #into_impls
@@ -1007,7 +1116,7 @@ pub fn derive_get_settlement_lag(input: TokenStream) -> TokenStream
         #where_clause
         {
             #[inline]
-            fn get_settlement_lag(&self, transaction_dt: DateTime) -> u64 {
+            fn get_settlement_lag(&self, transaction_dt: DateTime) -> NanoDuration {
                 match self { #get_settlement_lag }
             }
         }
@@ -1015,4 +1124,218 @@ pub fn derive_get_settlement_lag(input: TokenStream) -> TokenStream
         #into_impls
     };
     tokens.into()
-}
\ No newline at end of file
+}
+
+#[proc_macro_derive(Tunable, attributes(tunable))]
+pub fn derive_tunable(input: TokenStream) -> TokenStream
+{
+    let ast = parse_macro_input!(input as DeriveInput);
+    let data = ast.data;
+    let fields = if let Data::Struct(DataStruct { fields: Fields::Named(fields), .. }) = data {
+        fields.named
+    } else {
+        panic!("Struct with named fields expected. Got {data:?}")
+    };
+
+    let name = ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+
+    let (mut param_specs, mut get_param, mut set_param) = (
+        TokenStream2::new(),
+        TokenStream2::new(),
+        TokenStream2::new()
+    );
+    for field in &fields {
+        let attr = if let Some(attr) = field.attrs.iter().find(|attr| attr.path.is_ident("tunable")) {
+            attr
+        } else {
+            continue;
+        };
+        let ident = field.ident.as_ref().expect("Named field expected");
+        let param_name = ident.to_string();
+        let field_type = &field.ty;
+        let kind = get_tunable_kind(field_type);
+        let (min, max, choices) = parse_tunable_attr(attr, &param_name);
+
+        let range = match kind {
+            TunableKind::Integer => {
+                if !choices.is_empty() {
+                    panic!("Integer parameter {param_name} cannot have choices")
+                }
+                let parse = |bound: Option<String>| bound.map_or_else(
+                    || quote! {None},
+                    |bound| {
+                        let bound = i64::from_str(&bound).unwrap_or_else(
+                            |err| panic!("Bad bound {bound} of the parameter {param_name}: {err}")
+                        );
+                        quote! {Some(#bound)}
+                    },
+                );
+                let (min, max) = (parse(min), parse(max));
+                quote! {ParamRange::Integer { min: #min, max: #max }}
+            }
+            TunableKind::Real => {
+                if !choices.is_empty() {
+                    panic!("Floating-point parameter {param_name} cannot have choices")
+                }
+                let parse = |bound: Option<String>| bound.map_or_else(
+                    || quote! {None},
+                    |bound| {
+                        let bound = f64::from_str(&bound).unwrap_or_else(
+                            |err| panic!("Bad bound {bound} of the parameter {param_name}: {err}")
+                        );
+                        quote! {Some(#bound)}
+                    },
+                );
+                let (min, max) = (parse(min), parse(max));
+                quote! {ParamRange::Real { min: #min, max: #max }}
+            }
+            TunableKind::String => {
+                if min.is_some() || max.is_some() {
+                    panic!("String parameter {param_name} cannot have bounds")
+                }
+                quote! {ParamRange::String { choices: [#(#choices),*].into() }}
+            }
+        };
+        let spec = quote! {ParamSpec { name: #param_name, range: #range }};
+        param_specs.extend(quote! { #spec, });
+
+        let (getter, setter) = match kind {
+            TunableKind::Integer => (
+                quote! {
+                    i64::try_from(self.#ident).ok().map(ParamValue::Integer)
+                },
+                quote! {
+                    if let ParamValue::Integer(value) = (#spec).check(value)? {
+                        self.#ident = <#field_type>::try_from(value).map_err(
+                            |_| ParamError::OutOfRange {
+                                name: #param_name,
+                                value: ParamValue::Integer(value),
+                            }
+                        )?
+                    }
+                }
+            ),
+            TunableKind::Real => (
+                quote! {
+                    Some(ParamValue::Real(self.#ident as f64))
+                },
+                quote! {
+                    if let ParamValue::Real(value) = (#spec).check(value)? {
+                        self.#ident = value as #field_type
+                    }
+                }
+            ),
+            TunableKind::String => (
+                quote! {
+                    Some(ParamValue::String(self.#ident.clone()))
+                },
+                quote! {
+                    if let ParamValue::String(value) = (#spec).check(value)? {
+                        self.#ident = value
+                    }
+                }
+            )
+        };
+        get_param.extend(quote! { #param_name => #getter, });
+        set_param.extend(quote! { #param_name => { #setter } });
+    }
+
+    let tokens = quote! {
+        impl #impl_generics Tunable
+        for #name #ty_generics
+        #where_clause
+        {
+            fn get_param_specs() -> Vec<ParamSpec> {
+                [#param_specs].into()
+            }
+
+            fn get_param(&self, name: &str) -> Option<ParamValue> {
+                match name {
+                    #get_param
+                    _ => None
+                }
+            }
+
+            fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError> {
+                match name {
+                    #set_param
+                    _ => return Err(ParamError::Unknown(name.into()))
+                }
+                Ok(())
+            }
+        }
+    };
+    tokens.into()
+}
+
+enum TunableKind {
+    Integer,
+    Real,
+    String,
+}
+
+fn get_tunable_kind(field_type: &Type) -> TunableKind {
+    let type_name = if let Type::Path(TypePath { path, .. }) = field_type {
+        path.segments.last().map(|segment| segment.ident.to_string())
+    } else {
+        None
+    };
+    match type_name.as_deref() {
+        Some("i8" | "i16" | "i32" | "i64" | "isize" | "u8" | "u16" | "u32" | "u64" | "usize") => {
+            TunableKind::Integer
+        }
+        Some("f32" | "f64") => TunableKind::Real,
+        Some("String") => TunableKind::String,
+        _ => panic!(
+            "Tunable parameter should be of the integer, floating-point or String type. \
+            Got {}",
+            quote! {#field_type}
+        )
+    }
+}
+
+/// Returns the bounds and the choices of the `#[tunable(...)]` attribute.
+fn parse_tunable_attr(attr: &Attribute, param_name: &str)
+    -> (Option<String>, Option<String>, Vec<String>)
+{
+    let (mut min, mut max, mut choices) = (None, None, Vec::new());
+    let list = match attr.parse_meta() {
+        Ok(Meta::Path(_)) => return (min, max, choices),
+        Ok(Meta::List(list)) => list.nested,
+        other => panic!("Bad tunable attribute of the parameter {param_name}: {other:?}")
+    };
+    let lit_to_string = |lit: &Lit| match lit {
+        Lit::Int(lit) => lit.base10_digits().to_string(),
+        Lit::Float(lit) => lit.base10_digits().to_string(),
+        Lit::Str(lit) => lit.value(),
+        _ => panic!("Bad literal in the tunable attribute of the parameter {param_name}")
+    };
+    for nested in list {
+        match nested {
+            NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, lit, .. })) => {
+                if path.is_ident("min") {
+                    min = Some(lit_to_string(&lit))
+                } else if path.is_ident("max") {
+                    max = Some(lit_to_string(&lit))
+                } else {
+                    panic!("Unknown key in the tunable attribute of the parameter {param_name}")
+                }
+            }
+            NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("choices") => {
+                choices = list.nested.iter()
+                    .map(
+                        |choice| match choice {
+                            NestedMeta::Lit(Lit::Str(choice)) => choice.value(),
+                            _ => panic!(
+                                "Choices of the parameter {param_name} should be string literals"
+                            )
+                        }
+                    )
+                    .collect()
+            }
+            _ => panic!("Bad tunable attribute of the parameter {param_name}")
+        }
+    }
+    (min, max, choices)
+}
@@ -0,0 +1,667 @@
+//! Command-line front-end running the simulations configured by the YAML-config.
+//!
+//! ```text
+//! backtester-cli validate <CONFIG> [--set <PATH>=<VALUE>]...
+//! backtester-cli run <CONFIG> [--seed <SEED>] [--set <PATH>=<VALUE>]...
+//! backtester-cli sweep <CONFIG> --seeds <FROM>..<TO> [--threads <NUM>] [--set <PATH>=<VALUE>]...
+//! backtester-cli watch <CONFIG> [--seed <SEED>] [--set <PATH>=<VALUE>]...
+//! ```
+//!
+//! Every trader is connected to the single broker and subscribed to the OB-snapshots
+//! of all the traded pairs listed in the config. Only "Base :: Spot" traded pairs are supported.
+//! The optional "Broker" section sets the `name` of the broker, `Broker` by default,
+//! and the `ob_snapshot_period` in seconds within its `params`, 1 by default.
+//!
+//! Supported trader types and their `params`:
+//!
+//! * `SpreadWriter` — `price_step` and `file`, the path to the csv-file
+//!   relative to the directory of the config.
+//! * `QuoteRecorder` — `price_step` and `file`, the path template of the files
+//!   relative to the directory of the config with the `{exchange}` and `{pair}` placeholders.
+//!   Optional `fields`, the list of the column names, e.g. `[BID_PRICE, ASK_PRICE, SPREAD]`,
+//!   `depth`, `precision` and `format`, either `csv` or `tsv`.
+//!
+//! The summary reports the number of rows and the mean spread of the output files
+//! of every trader, reading either the `SPREAD` column
+//! or the difference of the `ASK_PRICE` and `BID_PRICE` ones.
+//!
+//! `sweep` runs one simulation per seed, in parallel if the `multithread` feature is enabled,
+//! suffixing the output files of the traders with the seed.
+//!
+//! `watch` parses the market data once and re-runs the simulation whenever the config changes,
+//! reporting the delta in the summary metrics against the previous run.
+//! Only the "Traders" section is reloaded, changes to the other ones require a restart.
+//!
+//! `--set` overrides the single parameter of the config, e.g. `--set Traders.0.params.price_step=0.001`.
+//! Overrides listed in the `BACKTESTER_OVERRIDES` environment variable, separated by `;`,
+//! are applied before the ones of the command line.
+
+// Wall-clock time is used only to report the duration of the runs
+#![allow(clippy::disallowed_methods)]
+
+use {
+    rand::{Rng, rngs::StdRng},
+    std::{
+        collections::BTreeMap,
+        fmt::{Debug, Display, Formatter},
+        fs::{metadata, read_to_string},
+        num::NonZeroU64,
+        panic::{AssertUnwindSafe, catch_unwind},
+        path::{Path, PathBuf},
+        process::exit,
+        str::FromStr,
+        sync::Mutex,
+        thread::sleep,
+        time::{Duration, Instant},
+    },
+    trading_backtester::prelude::{
+        broker_examples::BasicBroker,
+        derive::Trader,
+        exchange_example::BasicExchange,
+        replay_examples::{GetNextObSnapshotDelay, OneTickReplay},
+        settlement_examples::SpotSettlement,
+        traded_pair_parser_examples::SpotBaseTradedPairParser,
+        trader_examples::{
+            quote_recorder::{QuoteField, QuoteFormat, QuoteRecorder},
+            SpreadWriter,
+        },
+        *,
+    },
+    yaml_rust::Yaml,
+};
+
+const USAGE: &str = "\
+Usage:
+    backtester-cli validate <CONFIG> [--set <PATH>=<VALUE>]...
+    backtester-cli run <CONFIG> [--seed <SEED>] [--set <PATH>=<VALUE>]...
+    backtester-cli sweep <CONFIG> --seeds <FROM>..<TO> [--threads <NUM>] [--set <PATH>=<VALUE>]...
+    backtester-cli watch <CONFIG> [--seed <SEED>] [--set <PATH>=<VALUE>]...
+
+Environment:
+    BACKTESTER_OVERRIDES    <PATH>=<VALUE> overrides separated by ';'";
+
+const DEFAULT_SEED: u64 = 3344;
+
+const OVERRIDES_VAR: &str = "BACKTESTER_OVERRIDES";
+
+const WATCH_POLL_PERIOD: Duration = Duration::from_millis(500);
+
+const DEFAULT_BROKER_NAME: &str = "Broker";
+
+const DEFAULT_OB_SNAPSHOT_PERIOD: f64 = 1.0;
+
+/// Interned names, indexed by the [`Name`].
+static NAMES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+#[derive(Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd)]
+/// Exchange, broker or symbol name read from the config.
+/// Interned, so that the names re-read by the `watch` do not grow the memory.
+struct Name(usize);
+
+impl Name {
+    fn new(name: &str) -> Self {
+        let mut names = NAMES.lock().unwrap();
+        let index = names.iter().position(|interned| interned == name).unwrap_or_else(
+            || {
+                names.push(name.into());
+                names.len() - 1
+            }
+        );
+        Self(index)
+    }
+}
+
+impl FromStr for Name {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::new(s))
+    }
+}
+
+impl Display for Name {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&NAMES.lock().unwrap()[self.0], f)
+    }
+}
+
+impl Debug for Name {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+#[derive(Clone, Copy)]
+/// Requests an OB-snapshot with the constant period.
+struct PeriodicSnapshots(NonZeroU64);
+
+impl GetNextObSnapshotDelay<Name, Name, SpotSettlement> for PeriodicSnapshots {
+    fn get_ob_snapshot_delay(
+        &mut self,
+        _: Name,
+        _: TradedPair<Name, SpotSettlement>,
+        _: &mut impl Rng,
+        _: DateTime) -> Option<(NonZeroU64, usize)>
+    {
+        Some((self.0, 1))
+    }
+}
+
+type TraderID = u64;
+type ReplayConfig = OneTickReplayConfig<Name, Name, PeriodicSnapshots, SpotSettlement>;
+
+#[derive(Trader)]
+enum CliTrader {
+    SpreadWriter(SpreadWriter<TraderID, Name, Name, Name, SpotSettlement>),
+    QuoteRecorder(QuoteRecorder<TraderID, Name, Name, Name, SpotSettlement>),
+}
+
+impl CliTrader {
+    fn get_output_files(&self) -> Vec<PathBuf> {
+        match self {
+            Self::SpreadWriter(trader) => vec![trader.get_path().to_path_buf()],
+            Self::QuoteRecorder(trader) => trader.get_paths().map(Path::to_path_buf).collect()
+        }
+    }
+}
+
+enum Command {
+    Validate,
+    Run { seed: u64 },
+    Sweep { seeds: Vec<u64>, num_threads: usize },
+    Watch { seed: u64 },
+}
+
+#[derive(Default)]
+/// Summary metrics of the single simulation run.
+struct RunSummary {
+    elapsed: f64,
+    num_rows: BTreeMap<TraderID, usize>,
+    mean_spreads: BTreeMap<TraderID, f64>,
+}
+
+impl RunSummary {
+    fn rows(&self) -> Vec<(String, f64)> {
+        let num_rows = self.num_rows.iter()
+            .map(|(trader_id, num_rows)| (format!("{trader_id} :: rows"), *num_rows as f64));
+        let mean_spreads = self.mean_spreads.iter()
+            .map(|(trader_id, spread)| (format!("{trader_id} :: mean spread"), *spread));
+        [("elapsed, s".to_string(), self.elapsed)].into_iter()
+            .chain(num_rows)
+            .chain(mean_spreads)
+            .collect()
+    }
+
+    fn print(&self) {
+        for (name, value) in self.rows() {
+            println!("{name:<32}{value:>16.4}")
+        }
+    }
+
+    fn print_delta(&self, previous: &Self) {
+        let previous: BTreeMap<_, _> = previous.rows().into_iter().collect();
+        println!("{:<32}{:>16}{:>16}{:>16}", "", "previous", "current", "diff");
+        for (name, value) in self.rows() {
+            if let Some(previous) = previous.get(&name) {
+                println!("{name:<32}{previous:>16.4}{value:>16.4}{:>16.4}", value - previous)
+            } else {
+                println!("{name:<32}{:>16}{value:>16.4}{:>16}", "-", "-")
+            }
+        }
+    }
+}
+
+struct Config {
+    path: PathBuf,
+    overrides: Vec<ConfigOverride>,
+    exchange_names: Vec<Name>,
+    replay_config: ReplayConfig,
+    date_range: (DateTime, DateTime),
+    broker_id: Name,
+    ob_snapshot_period: NonZeroU64,
+    traders: Vec<YamlTraderConfig<TraderID>>,
+}
+
+impl Config {
+    fn load(path: &Path, overrides: Vec<ConfigOverride>) -> Self {
+        let broker = parse_broker_yaml_with_overrides(path, &overrides);
+        let broker_id = broker.as_ref().map_or_else(
+            || Name::new(DEFAULT_BROKER_NAME),
+            |broker| broker.name,
+        );
+        let ob_snapshot_period = get_ob_snapshot_period(
+            broker.as_ref().map_or(&Yaml::BadValue, |broker| &broker.params)
+        );
+        let (exchange_names, replay_config, start_dt, end_dt) = parse_yaml_with_overrides(
+            path,
+            &overrides,
+            SpotBaseTradedPairParser,
+            PeriodicSnapshots(ob_snapshot_period),
+        );
+        Self {
+            path: path.to_path_buf(),
+            traders: parse_traders_yaml_with_overrides(path, &overrides),
+            overrides,
+            exchange_names,
+            replay_config,
+            date_range: (start_dt, end_dt),
+            broker_id,
+            ob_snapshot_period,
+        }
+    }
+
+    fn print_summary(&self) {
+        let (start_dt, end_dt) = self.date_range;
+        println!("Config: {:?}", self.path);
+        println!("Simulation time: {start_dt} — {end_dt}");
+        println!("Exchanges: {:?}", self.exchange_names);
+        println!(
+            "Broker: {} :: OB-snapshot period {}s",
+            self.broker_id,
+            self.ob_snapshot_period.get() as f64 / 1e9
+        );
+        println!("Traded pairs:");
+        for config in &self.replay_config.traded_pair_configs {
+            let traded_pair = config.traded_pair;
+            println!(
+                "    {} :: {:?}/{:?}",
+                config.exchange_id, traded_pair.quoted_asset, traded_pair.settlement_asset
+            )
+        }
+        println!("Traders:");
+        for trader in &self.traders {
+            if trader.tags.is_empty() {
+                println!("    {} :: {}", trader.name, trader.kind)
+            } else {
+                println!("    {} :: {} [{}]", trader.name, trader.kind, trader.tags.join(", "))
+            }
+        }
+    }
+
+    fn create_registry(&self, suffix: Option<u64>) -> TraderRegistry<TraderID, CliTrader> {
+        let config_dir = self.path.parent().unwrap_or_else(
+            || panic!("Cannot get parent directory of the {:?}", self.path)
+        ).to_path_buf();
+        let quote_recorder_dir = config_dir.clone();
+        TraderRegistry::new()
+            .with_factory(
+                "SpreadWriter",
+                move |name, params: &Yaml| {
+                    let price_step = get_price_step(name, params);
+                    let file = get_output_file(&config_dir, name, params, suffix);
+                    CliTrader::SpreadWriter(SpreadWriter::new(name, price_step, file))
+                },
+            )
+            .with_factory(
+                "QuoteRecorder",
+                move |name, params: &Yaml| {
+                    let price_step = get_price_step(name, params);
+                    let file = get_output_file(&quote_recorder_dir, name, params, suffix);
+                    let mut trader = QuoteRecorder::new(name, price_step, file.to_string_lossy());
+                    if let Some(fields) = get_quote_fields(name, params) {
+                        trader = trader.with_fields(fields)
+                    }
+                    if let Some(depth) = get_usize_param(name, params, "depth") {
+                        trader = trader.with_depth(depth)
+                    }
+                    if let Some(precision) = get_usize_param(name, params, "precision") {
+                        trader = trader.with_precision(precision)
+                    }
+                    if let Some(format) = get_quote_format(name, params) {
+                        trader = trader.with_format(format)
+                    }
+                    CliTrader::QuoteRecorder(trader)
+                },
+            )
+    }
+
+    fn reload_traders(&mut self) {
+        self.traders = parse_traders_yaml_with_overrides(&self.path, &self.overrides);
+        self.validate()
+    }
+
+    fn summarize(elapsed: f64, output_files: BTreeMap<TraderID, Vec<PathBuf>>) -> RunSummary {
+        let mut summary = RunSummary { elapsed, ..Default::default() };
+        for (trader_id, files) in output_files {
+            let spreads: Vec<f64> = files.iter().flat_map(|file| read_spreads(file)).collect();
+            summary.num_rows.insert(trader_id, spreads.len());
+            if !spreads.is_empty() {
+                let mean_spread = spreads.iter().sum::<f64>() / spreads.len() as f64;
+                summary.mean_spreads.insert(trader_id, mean_spread);
+            }
+        }
+        summary
+    }
+
+    fn validate(&self) {
+        if self.traders.is_empty() {
+            panic!("There are no traders in the config {:?}", self.path)
+        }
+        let registry = self.create_registry(None);
+        for trader in &self.traders {
+            if !registry.is_registered(&trader.kind) {
+                panic!("Trader {} has unsupported type \"{}\"", trader.name, trader.kind)
+            }
+        }
+    }
+
+    fn run(&self, seed: u64, suffix: Option<u64>) -> RunSummary {
+        let broker_id = self.broker_id;
+        let registry = self.create_registry(suffix);
+        let subscriptions: Vec<_> = self.replay_config.traded_pair_configs.iter()
+            .map(
+                |config| SubscriptionConfig::new(
+                    config.exchange_id,
+                    config.traded_pair,
+                    SubscriptionList::subscribe().to_ob_snapshots(),
+                )
+            )
+            .collect();
+        let traders = self.traders.iter().map(
+            |config| (registry.create_trader(config), [(broker_id, subscriptions.clone())])
+        );
+        let exchanges = self.exchange_names.iter().map(BasicExchange::from);
+        let brokers = [(BasicBroker::new(broker_id), self.exchange_names.clone())];
+        let replay = OneTickReplay::from(&self.replay_config);
+
+        let started = Instant::now();
+        println!("Seed {seed} :: started");
+        let agents = KernelBuilder::new(exchanges, brokers, traders, replay, self.date_range)
+            .with_seed(seed)
+            .with_rng::<StdRng>()
+            .build()
+            .run_simulation_into_agents();
+        let elapsed = started.elapsed().as_secs_f64();
+        println!("Seed {seed} :: finished in {elapsed:.3}s");
+        // Traders are dropped before their output files are read
+        let output_files = agents.traders.into_iter()
+            .map(|(trader_id, trader)| (trader_id, trader.get_output_files()))
+            .collect();
+        Self::summarize(elapsed, output_files)
+    }
+
+    fn watch(mut self, seed: u64) {
+        let get_modified = |path: &Path| metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .unwrap_or_else(|err| panic!("Cannot get modification time of {path:?}. Error: {err}"));
+        self.replay_config = self.replay_config.with_shared_event_tables();
+        let mut modified = get_modified(&self.path);
+        let mut previous = self.run(seed, None);
+        previous.print();
+        loop {
+            println!("Watching {:?} for changes", self.path);
+            modified = loop {
+                sleep(WATCH_POLL_PERIOD);
+                let last_modified = get_modified(&self.path);
+                if last_modified != modified {
+                    break last_modified;
+                }
+            };
+            println!("Config changed. Re-running the simulation");
+            let result = catch_unwind(
+                AssertUnwindSafe(|| {
+                    self.reload_traders();
+                    self.run(seed, None)
+                })
+            );
+            match result {
+                Ok(summary) => {
+                    summary.print_delta(&previous);
+                    previous = summary
+                }
+                Err(_) => eprintln!("Run failed. Fix the config to re-run the simulation")
+            }
+        }
+    }
+}
+
+fn get_ob_snapshot_period(params: &Yaml) -> NonZeroU64 {
+    let period = match &params["ob_snapshot_period"] {
+        Yaml::BadValue => DEFAULT_OB_SNAPSHOT_PERIOD,
+        Yaml::Integer(period) => *period as f64,
+        period => period.as_f64().unwrap_or_else(
+            || panic!("Broker :: params :: ob_snapshot_period should be a number. Got {period:?}")
+        )
+    };
+    NonZeroU64::new((period * 1e9).round() as u64).unwrap_or_else(
+        || panic!("Broker :: params :: ob_snapshot_period should be positive. Got {period}")
+    )
+}
+
+fn get_price_step(name: TraderID, params: &Yaml) -> f64 {
+    params["price_step"].as_f64().unwrap_or_else(
+        || panic!("Trader {name} :: params :: price_step should be a float")
+    )
+}
+
+fn get_usize_param(name: TraderID, params: &Yaml, param: &str) -> Option<usize> {
+    match &params[param] {
+        Yaml::BadValue => None,
+        value => Some(
+            value.as_i64().and_then(|value| value.try_into().ok()).unwrap_or_else(
+                || panic!("Trader {name} :: params :: {param} should be a non-negative integer")
+            )
+        )
+    }
+}
+
+fn get_quote_fields(name: TraderID, params: &Yaml) -> Option<Vec<QuoteField>> {
+    let fields = match &params["fields"] {
+        Yaml::BadValue => return None,
+        Yaml::Array(fields) => fields,
+        _ => panic!("Trader {name} :: params :: fields should be a list of the column names")
+    };
+    let fields = fields.iter().map(
+        |field| match field.as_str() {
+            Some("BID_PRICE") => QuoteField::BidPrice,
+            Some("BID_SIZE") => QuoteField::BidSize,
+            Some("ASK_PRICE") => QuoteField::AskPrice,
+            Some("ASK_SIZE") => QuoteField::AskSize,
+            Some("MID_PRICE") => QuoteField::MidPrice,
+            Some("SPREAD") => QuoteField::Spread,
+            Some("MICROPRICE") => QuoteField::Microprice,
+            Some("IMBALANCE") => QuoteField::Imbalance,
+            _ => panic!("Trader {name} :: params :: fields has unknown column {field:?}")
+        }
+    );
+    Some(fields.collect())
+}
+
+fn get_quote_format(name: TraderID, params: &Yaml) -> Option<QuoteFormat> {
+    match &params["format"] {
+        Yaml::BadValue => None,
+        format => match format.as_str() {
+            Some("csv") => Some(QuoteFormat::Csv),
+            Some("tsv") => Some(QuoteFormat::Tsv),
+            _ => panic!("Trader {name} :: params :: format should be either csv or tsv")
+        }
+    }
+}
+
+/// Reads the spreads from the csv- or tsv-file,
+/// locating the columns by the header that follows the metadata comment lines.
+fn read_spreads(file: &Path) -> Vec<f64> {
+    let output = read_to_string(file).unwrap_or_else(
+        |err| panic!("Cannot read the following file: {file:?}. Error: {err}")
+    );
+    let mut lines = output.lines().filter(|line| !line.starts_with('#'));
+    let Some(header) = lines.next() else {
+        return Vec::new();
+    };
+    let sep = if header.contains('\t') { '\t' } else { ',' };
+    let columns: Vec<_> = header.split(sep).collect();
+    let get_column = |name| columns.iter().position(|column| *column == name);
+    let get_spread: Box<dyn Fn(&[&str]) -> Option<f64>> = match (
+        get_column("SPREAD"),
+        get_column("BID_PRICE"),
+        get_column("ASK_PRICE")
+    ) {
+        (Some(spread), _, _) => Box::new(move |fields| fields.get(spread)?.parse().ok()),
+        (None, Some(bid_price), Some(ask_price)) => Box::new(
+            move |fields| {
+                let bid_price: f64 = fields.get(bid_price)?.parse().ok()?;
+                let ask_price: f64 = fields.get(ask_price)?.parse().ok()?;
+                Some(ask_price - bid_price)
+            }
+        ),
+        _ => panic!(
+            "{file:?} should have either the SPREAD or the BID_PRICE and ASK_PRICE columns. \
+            Got header: {header}"
+        )
+    };
+    lines
+        .filter_map(|line| get_spread(&line.split(sep).collect::<Vec<_>>()))
+        .collect()
+}
+
+fn get_output_file(
+    config_dir: &Path,
+    name: TraderID,
+    params: &Yaml,
+    suffix: Option<u64>) -> PathBuf
+{
+    let file = params["file"].as_str().unwrap_or_else(
+        || panic!("Trader {name} :: params :: file should be a string")
+    );
+    let mut file = config_dir.join(file);
+    if let Some(suffix) = suffix {
+        let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+        let file_name = match file.extension() {
+            Some(ext) => format!("{stem}_{suffix}.{}", ext.to_string_lossy()),
+            None => format!("{stem}_{suffix}")
+        };
+        file.set_file_name(file_name)
+    }
+    file
+}
+
+fn parse_args(args: &[String]) -> Result<(Command, PathBuf, Vec<ConfigOverride>), String> {
+    let (command, path, options) = match args {
+        [command, path, options @ ..] => (command.as_str(), PathBuf::from(path), options),
+        _ => return Err("Expected a subcommand and a path to the config".into())
+    };
+    let mut seed = None;
+    let mut seeds = None;
+    let mut num_threads = 0;
+    let mut overrides = Vec::new();
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        let value = options.next().ok_or_else(|| format!("Missing value of the {option}"))?;
+        let bad_value = || format!("Bad value of the {option}: {value}");
+        match option.as_str() {
+            "--seed" => seed = Some(value.parse().map_err(|_| bad_value())?),
+            "--seeds" => {
+                let (from, to) = value.split_once("..").ok_or_else(bad_value)?;
+                let from: u64 = from.parse().map_err(|_| bad_value())?;
+                let to: u64 = to.parse().map_err(|_| bad_value())?;
+                if from >= to {
+                    return Err(bad_value());
+                }
+                seeds = Some((from..to).collect())
+            }
+            "--threads" => num_threads = value.parse().map_err(|_| bad_value())?,
+            "--set" => overrides.push(value.parse()?),
+            _ => return Err(format!("Unknown option: {option}"))
+        }
+    }
+    let command = match (command, seed, seeds) {
+        ("validate", None, None) => Command::Validate,
+        ("run", seed, None) => Command::Run { seed: seed.unwrap_or(DEFAULT_SEED) },
+        ("sweep", None, Some(seeds)) => Command::Sweep { seeds, num_threads },
+        ("sweep", _, None) => return Err("sweep requires the --seeds option".into()),
+        ("watch", seed, None) => Command::Watch { seed: seed.unwrap_or(DEFAULT_SEED) },
+        ("validate" | "run" | "sweep" | "watch", ..) => {
+            return Err(format!("Unexpected options for the {command}"));
+        }
+        _ => return Err(format!("Unknown subcommand: {command}"))
+    };
+    Ok((command, path, overrides))
+}
+
+fn parse_env_overrides() -> Result<Vec<ConfigOverride>, String> {
+    let Ok(overrides) = std::env::var(OVERRIDES_VAR) else {
+        return Ok(Vec::new());
+    };
+    overrides.split(';')
+        .map(str::trim)
+        .filter(|config_override| !config_override.is_empty())
+        .map(
+            |config_override| config_override.parse()
+                .map_err(|err| format!("{OVERRIDES_VAR}: {err}"))
+        )
+        .collect()
+}
+
+#[cfg(feature = "multithread")]
+fn sweep(config: &Config, seeds: Vec<u64>, num_threads: usize) -> Vec<(u64, RunSummary)> {
+    use rayon::{iter::{IntoParallelIterator, ParallelIterator}, ThreadPoolBuilder};
+
+    let job = || seeds.into_par_iter()
+        .map(|seed| (seed, config.run(seed, Some(seed))))
+        .collect();
+    if num_threads == 0 {
+        job()
+    } else {
+        ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .build()
+            .unwrap_or_else(
+                |err| panic!(
+                    "Cannot build ThreadPool \
+                    with the following number of threads to use: {num_threads}. \
+                    Error: {err}"
+                )
+            )
+            .install(job)
+    }
+}
+
+#[cfg(not(feature = "multithread"))]
+fn sweep(config: &Config, seeds: Vec<u64>, num_threads: usize) -> Vec<(u64, RunSummary)> {
+    if num_threads > 1 {
+        eprintln!("The multithread feature is disabled. Running the sweep in a single thread")
+    }
+    seeds.into_iter().map(|seed| (seed, config.run(seed, Some(seed)))).collect()
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (command, path, overrides) = parse_env_overrides()
+        .and_then(
+            |env_overrides| {
+                let (command, path, overrides) = parse_args(&args)?;
+                Ok((command, path, env_overrides.into_iter().chain(overrides).collect()))
+            }
+        )
+        .unwrap_or_else(
+            |err| {
+                eprintln!("{err}\n\n{USAGE}");
+                exit(2)
+            }
+        );
+    let config = Config::load(&path, overrides);
+    config.validate();
+    let started = Instant::now();
+    match command {
+        Command::Validate => {
+            config.print_summary();
+            println!("Config is valid")
+        }
+        Command::Run { seed } => {
+            let summary = config.run(seed, None);
+            config.print_summary();
+            summary.print()
+        }
+        Command::Sweep { seeds, num_threads } => {
+            let summaries = sweep(&config, seeds, num_threads);
+            config.print_summary();
+            for (seed, summary) in &summaries {
+                println!("Seed {seed}:");
+                summary.print()
+            }
+            println!("Runs: {}", summaries.len())
+        }
+        Command::Watch { seed } => config.watch(seed)
+    }
+    println!("Total time: {:.3}s", started.elapsed().as_secs_f64())
+}
@@ -1,19 +1,31 @@
+/// End-of-run statistics computed over the columnar trade logs.
+pub mod analytics;
+/// Concrete implementors of the [`Auxiliary`](crate::interface::auxiliary::Auxiliary).
+pub mod auxiliary;
 /// Concrete implementors of the [`Broker`](crate::interface::broker::Broker).
 pub mod broker;
+/// Central counterparty novating trades and maintaining margin accounts.
+pub mod clearing;
 /// Concrete implementors of the [`Exchange`](crate::interface::exchange::Exchange).
 pub mod exchange;
+/// Keep-alive messages and detection of the missing peers.
+pub mod heartbeat;
 /// Input parsers and initializer utilities.
 pub mod input;
 /// Concrete implementors related to the [`latency`](crate::interface::latency).
 pub mod latency;
 /// Concrete implementors related to the [`message_protocol`](crate::interface::message).
 pub mod message_protocol;
+/// User-defined hooks of the concrete brokers and exchanges.
+pub mod middleware;
 /// Order types for the [`message_protocol`].
 pub mod order;
 /// Simple order book struct.
 pub mod order_book;
 /// Concrete implementors of the [`Replay`](crate::interface::replay::Replay).
 pub mod replay;
+/// Top-level facade running the simulations configured by the YAML-config.
+pub mod simulation;
 /// Traded pair and financial instruments.
 pub mod traded_pair;
 /// Concrete implementors of the [`Trader`](crate::interface::trader::Trader).
@@ -0,0 +1,488 @@
+use {
+    crate::{
+        concrete::types::{Direction, Lots, OrderID, Tick},
+        types::{DateTime, Duration, Id},
+        utils::collections::HashMap,
+    },
+    std::{cell::RefCell, hash::Hash, rc::Rc},
+};
+
+/// [`TradeLog`] shared between the brokers of the single simulation thread.
+pub type SharedTradeLog<TraderID, Instrument> = Rc<RefCell<TradeLog<TraderID, Instrument>>>;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+/// End-of-run statistics of the single trader, in ticks times lots.
+pub struct RunStatistics {
+    /// Marked-to-market P&L at the end of the run.
+    pub pnl: f64,
+    /// Largest decline of the marked-to-market P&L from its running peak.
+    pub max_drawdown: f64,
+    /// Mean of the period P&L changes divided by their standard deviation.
+    /// `None` if there are less than two periods or the P&L does not vary.
+    pub sharpe_ratio: Option<f64>,
+    /// Total absolute notional of the fills.
+    pub turnover: f64,
+    /// Number of the fills.
+    pub num_fills: usize,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+/// Statistics of the limit orders of the single trader in the single instrument.
+pub struct MakerStatistics {
+    /// Number of the limit orders.
+    pub num_orders: usize,
+    /// Number of the limit orders filled at least partially.
+    pub num_filled_orders: usize,
+    /// Total size of the limit orders.
+    pub submitted_size: i64,
+    /// Total filled size of the limit orders.
+    pub filled_size: i64,
+    /// Times between the submissions of the filled limit orders and their first fills,
+    /// in ascending order.
+    pub times_to_fill: Vec<Duration>,
+    /// Size-weighted mean markouts of the fills, in ticks, at the requested horizons.
+    /// Markout is the change of the mark price since the fill in the direction of the fill,
+    /// so it is negative if the fills are adversely selected.
+    /// `None` if no fill is followed by the horizon within the log.
+    pub markouts: Vec<(Duration, Option<f64>)>,
+}
+
+impl MakerStatistics {
+    /// Returns the filled size divided by the submitted size.
+    /// `None` if no limit orders are submitted.
+    pub fn get_fill_ratio(&self) -> Option<f64> {
+        if self.submitted_size != 0 {
+            Some(self.filled_size as f64 / self.submitted_size as f64)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the quantile of the time-to-fill distribution, using the nearest-rank method.
+    /// `None` if no limit orders are filled.
+    ///
+    /// # Arguments
+    ///
+    /// * `q` — Quantile level within `[0, 1]`.
+    pub fn get_time_to_fill_quantile(&self, q: f64) -> Option<Duration> {
+        if !(0.0..=1.0).contains(&q) {
+            panic!("Quantile level should be within [0, 1]. Got {q}")
+        }
+        let last = self.times_to_fill.len().checked_sub(1)?;
+        self.times_to_fill.get((q * last as f64).round() as usize).copied()
+    }
+}
+
+/// Limit order recorded to the [`TradeLog`].
+struct LimitOrderRecord<TraderID: Id> {
+    datetime: DateTime,
+    trader_id: TraderID,
+    instrument: u32,
+    size: i64,
+    /// Rows of the fills of the order.
+    fill_rows: Vec<usize>,
+}
+
+/// Columnar log of the fills of the traders and of the marks of the instruments.
+///
+/// Rows are kept in the struct-of-arrays layout in the order they are recorded,
+/// which should be chronological. Fills also mark their instruments.
+/// Instruments are interned, so that the statistics are computed in a single pass
+/// over the contiguous columns with the per-instrument state kept in the flat arrays.
+///
+/// # Examples
+///
+/// ```
+/// use {
+///     chrono::NaiveDate,
+///     chrono::Duration,
+///     trading_backtester::concrete::{
+///         analytics::TradeLog,
+///         types::{Direction, Lots, Tick},
+///     },
+/// };
+///
+/// let dt = |hour| NaiveDate::from_ymd_opt(2024, 1, 2).unwrap()
+///     .and_hms_opt(hour, 0, 0)
+///     .unwrap();
+/// let mut log = TradeLog::new();
+/// log.record_fill(dt(10), 1_u8, "AAPL", Direction::Buy, Tick(100), Lots(2));
+/// log.record_mark(dt(11), "AAPL", Tick(90));
+/// log.record_mark(dt(12), "AAPL", Tick(105));
+///
+/// let stats = log.compute_statistics(1, Duration::hours(1));
+/// assert_eq!(stats.pnl, 10.0);
+/// assert_eq!(stats.max_drawdown, 20.0);
+/// assert_eq!(stats.turnover, 200.0);
+/// assert_eq!(stats.num_fills, 1);
+/// ```
+pub struct TradeLog<TraderID: Id, Instrument: Copy + Eq + Hash> {
+    datetimes: Vec<DateTime>,
+    /// Filling trader. `None` for the marks.
+    traders: Vec<Option<TraderID>>,
+    instruments: Vec<u32>,
+    prices: Vec<i64>,
+    /// Signed fill sizes. Positive for the buys and zero for the marks.
+    sizes: Vec<i64>,
+    instrument_ids: HashMap<Instrument, u32>,
+    num_instruments: u32,
+    limit_orders: Vec<LimitOrderRecord<TraderID>>,
+    limit_order_ids: HashMap<(TraderID, OrderID), usize>,
+}
+
+impl<TraderID: Id, Instrument: Copy + Eq + Hash> Default for TradeLog<TraderID, Instrument> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<TraderID: Id, Instrument: Copy + Eq + Hash> TradeLog<TraderID, Instrument> {
+    /// Creates a new instance of the `TradeLog`.
+    pub fn new() -> Self {
+        Self {
+            datetimes: vec![],
+            traders: vec![],
+            instruments: vec![],
+            prices: vec![],
+            sizes: vec![],
+            instrument_ids: Default::default(),
+            num_instruments: 0,
+            limit_orders: vec![],
+            limit_order_ids: Default::default(),
+        }
+    }
+
+    /// Returns the number of the recorded rows.
+    pub fn len(&self) -> usize {
+        self.datetimes.len()
+    }
+
+    /// Returns whether nothing is recorded.
+    pub fn is_empty(&self) -> bool {
+        self.datetimes.is_empty()
+    }
+
+    fn intern(&mut self, instrument: Instrument) -> u32 {
+        let next_id = &mut self.num_instruments;
+        *self.instrument_ids.entry(instrument).or_insert_with(
+            || {
+                *next_id += 1;
+                *next_id - 1
+            }
+        )
+    }
+
+    fn push(
+        &mut self,
+        datetime: DateTime,
+        trader_id: Option<TraderID>,
+        instrument: Instrument,
+        price: Tick,
+        size: i64)
+    {
+        let instrument = self.intern(instrument);
+        self.datetimes.push(datetime);
+        self.traders.push(trader_id);
+        self.instruments.push(instrument);
+        self.prices.push(price.0);
+        self.sizes.push(size)
+    }
+
+    /// Records the fill of the trader order.
+    ///
+    /// # Arguments
+    ///
+    /// * `datetime` — Datetime of the fill.
+    /// * `trader_id` — Owner of the order.
+    /// * `instrument` — Filled instrument.
+    /// * `direction` — Order direction.
+    /// * `price` — Fill price.
+    /// * `size` — Fill size.
+    pub fn record_fill(
+        &mut self,
+        datetime: DateTime,
+        trader_id: TraderID,
+        instrument: Instrument,
+        direction: Direction,
+        price: Tick,
+        size: Lots)
+    {
+        let size = match direction {
+            Direction::Buy => size.0,
+            Direction::Sell => -size.0
+        };
+        self.push(datetime, Some(trader_id), instrument, price, size)
+    }
+
+    /// Records the submission of the limit order of the trader,
+    /// so that its fills are accounted by the [`compute_maker_statistics`](Self::compute_maker_statistics).
+    /// Does nothing if the order with the same ID is already recorded for the trader.
+    ///
+    /// # Arguments
+    ///
+    /// * `datetime` — Datetime of the submission.
+    /// * `trader_id` — Owner of the order.
+    /// * `instrument` — Instrument of the order.
+    /// * `order_id` — ID of the order, unique per trader.
+    /// * `size` — Order size.
+    pub fn record_limit_order(
+        &mut self,
+        datetime: DateTime,
+        trader_id: TraderID,
+        instrument: Instrument,
+        order_id: OrderID,
+        size: Lots)
+    {
+        if self.limit_order_ids.contains_key(&(trader_id, order_id)) {
+            return;
+        }
+        let instrument = self.intern(instrument);
+        self.limit_order_ids.insert((trader_id, order_id), self.limit_orders.len());
+        self.limit_orders.push(
+            LimitOrderRecord { datetime, trader_id, instrument, size: size.0, fill_rows: vec![] }
+        )
+    }
+
+    /// Records the fill of the trader order, attributing it to the limit order
+    /// recorded with the [`record_limit_order`](Self::record_limit_order), if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `datetime` — Datetime of the fill.
+    /// * `trader_id` — Owner of the order.
+    /// * `instrument` — Filled instrument.
+    /// * `order_id` — ID of the order.
+    /// * `direction` — Order direction.
+    /// * `price` — Fill price.
+    /// * `size` — Fill size.
+    #[allow(clippy::too_many_arguments)]
+    pub fn record_order_fill(
+        &mut self,
+        datetime: DateTime,
+        trader_id: TraderID,
+        instrument: Instrument,
+        order_id: OrderID,
+        direction: Direction,
+        price: Tick,
+        size: Lots)
+    {
+        if let Some(&i) = self.limit_order_ids.get(&(trader_id, order_id)) {
+            let row = self.len();
+            self.limit_orders[i].fill_rows.push(row)
+        }
+        self.record_fill(datetime, trader_id, instrument, direction, price, size)
+    }
+
+    /// Records the price the positions in the instrument are marked to,
+    /// e.g. the last trade price or the closing price.
+    ///
+    /// # Arguments
+    ///
+    /// * `datetime` — Datetime of the mark.
+    /// * `instrument` — Instrument.
+    /// * `price` — Mark price.
+    pub fn record_mark(&mut self, datetime: DateTime, instrument: Instrument, price: Tick) {
+        self.push(datetime, None, instrument, price, 0)
+    }
+
+    /// Computes the [`RunStatistics`] of the trader.
+    ///
+    /// P&L is marked to the market after every row. Period P&L changes are taken
+    /// between the last rows of the consecutive periods, counted from the first row.
+    /// Periods without rows are skipped, e.g. the overnight breaks.
+    ///
+    /// # Arguments
+    ///
+    /// * `trader_id` — Trader.
+    /// * `period` — Length of the periods the Sharpe ratio is computed over.
+    pub fn compute_statistics(&self, trader_id: TraderID, period: Duration) -> RunStatistics {
+        if period <= Duration::zero() {
+            panic!("Statistics period should be positive. Got {period}")
+        }
+        let period_nanos = period.num_nanoseconds().unwrap_or(i64::MAX);
+        let mut positions = vec![0_i64; self.num_instruments as usize];
+        let mut marks = vec![0_i64; self.num_instruments as usize];
+        // Cash plus the marked value of the positions
+        let mut equity = 0_i128;
+        let mut peak = 0_i128;
+        let mut max_drawdown = 0_i128;
+        let mut turnover = 0_i128;
+        let mut num_fills = 0;
+        let mut period_changes = Vec::new();
+        let mut period_end = None;
+        let mut period_start_equity = 0_i128;
+
+        let rows = self.datetimes.iter()
+            .zip(&self.traders)
+            .zip(&self.instruments)
+            .zip(&self.prices)
+            .zip(&self.sizes);
+        for ((((&datetime, &trader), &instrument), &price), &size) in rows {
+            // Fills of the other traders only mark their instruments
+            let is_own_fill = trader == Some(trader_id);
+            let instrument = instrument as usize;
+            match period_end {
+                None => period_end = Some(datetime + period),
+                Some(end) if datetime >= end => {
+                    period_changes.push((equity - period_start_equity) as f64);
+                    period_start_equity = equity;
+                    let elapsed = (datetime - end).num_nanoseconds().unwrap_or(i64::MAX);
+                    let skipped = elapsed / period_nanos;
+                    period_end = Some(end + Duration::nanoseconds((skipped + 1) * period_nanos))
+                }
+                Some(_) => {}
+            }
+            let position = positions[instrument] as i128;
+            equity += position * (price - marks[instrument]) as i128;
+            marks[instrument] = price;
+            if is_own_fill {
+                // Fill at the mark price does not change the equity
+                positions[instrument] += size;
+                turnover += (size as i128 * price as i128).abs();
+                num_fills += 1
+            }
+            peak = peak.max(equity);
+            max_drawdown = max_drawdown.max(peak - equity);
+        }
+        if period_end.is_some() {
+            period_changes.push((equity - period_start_equity) as f64)
+        }
+        RunStatistics {
+            pnl: equity as f64,
+            max_drawdown: max_drawdown as f64,
+            sharpe_ratio: sharpe_ratio(&period_changes),
+            turnover: turnover as f64,
+            num_fills,
+        }
+    }
+
+    /// Computes the [`MakerStatistics`] of the trader per instrument,
+    /// in the order the instruments first appear in the log.
+    /// Instruments without the limit orders of the trader are omitted.
+    ///
+    /// Markouts are taken against the last mark of the instrument at or before
+    /// the fill datetime plus the horizon. Fills followed by less than the horizon
+    /// until the last row of the log are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `trader_id` — Trader.
+    /// * `horizons` — Markout horizons, e.g. 100 milliseconds, 1 second and 10 seconds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use {
+    ///     chrono::NaiveDate,
+    ///     chrono::Duration,
+    ///     trading_backtester::concrete::{
+    ///         analytics::TradeLog,
+    ///         types::{Direction, Lots, OrderID, Tick},
+    ///     },
+    /// };
+    ///
+    /// let dt = |second| NaiveDate::from_ymd_opt(2024, 1, 2).unwrap()
+    ///     .and_hms_opt(10, 0, second)
+    ///     .unwrap();
+    /// let mut log = TradeLog::new();
+    /// log.record_limit_order(dt(0), 1_u8, "AAPL", OrderID(0), Lots(4));
+    /// log.record_order_fill(dt(2), 1, "AAPL", OrderID(0), Direction::Buy, Tick(100), Lots(1));
+    /// log.record_mark(dt(3), "AAPL", Tick(98));
+    ///
+    /// let stats = log.compute_maker_statistics(1, &[Duration::seconds(1)]);
+    /// let (instrument, stats) = &stats[0];
+    /// assert_eq!(*instrument, "AAPL");
+    /// assert_eq!(stats.get_fill_ratio(), Some(0.25));
+    /// assert_eq!(stats.times_to_fill, vec![Duration::seconds(2)]);
+    /// assert_eq!(stats.markouts, vec![(Duration::seconds(1), Some(-2.0))]);
+    /// ```
+    pub fn compute_maker_statistics(
+        &self,
+        trader_id: TraderID,
+        horizons: &[Duration]) -> Vec<(Instrument, MakerStatistics)>
+    {
+        let mut statistics: Vec<Option<MakerStatistics>> = vec![None; self.num_instruments as usize];
+        // Sums of the size-weighted markouts and of the weights per instrument and horizon
+        let mut markout_sums = vec![vec![(0_i128, 0_i128); horizons.len()]; statistics.len()];
+        let mut marks = vec![vec![]; statistics.len()];
+        for ((&datetime, &instrument), &price) in self.datetimes.iter()
+            .zip(&self.instruments)
+            .zip(&self.prices)
+        {
+            marks[instrument as usize].push((datetime, price))
+        }
+        let last_dt = self.datetimes.last().copied();
+
+        for order in self.limit_orders.iter().filter(|order| order.trader_id == trader_id) {
+            let instrument = order.instrument as usize;
+            let stats = statistics[instrument].get_or_insert_with(Default::default);
+            stats.num_orders += 1;
+            stats.submitted_size += order.size;
+            if let Some(&first_row) = order.fill_rows.first() {
+                stats.num_filled_orders += 1;
+                stats.times_to_fill.push(self.datetimes[first_row] - order.datetime)
+            }
+            for &row in &order.fill_rows {
+                let (datetime, price, size) = (self.datetimes[row], self.prices[row], self.sizes[row]);
+                stats.filled_size += size.abs();
+                let marks = &marks[instrument];
+                for (horizon, (sum, weight)) in horizons.iter()
+                    .zip(&mut markout_sums[instrument])
+                {
+                    let markout_dt = datetime + *horizon;
+                    if last_dt.is_none_or(|last_dt| last_dt < markout_dt) {
+                        continue;
+                    }
+                    let i = marks.partition_point(|(datetime, _)| *datetime <= markout_dt);
+                    let (_, mark) = marks[i - 1];
+                    *sum += (mark - price) as i128 * size as i128;
+                    *weight += size.abs() as i128
+                }
+            }
+        }
+        let mut instruments = vec![None; statistics.len()];
+        for (instrument, &i) in &self.instrument_ids {
+            instruments[i as usize] = Some(*instrument)
+        }
+        statistics.into_iter()
+            .zip(instruments)
+            .zip(markout_sums)
+            .filter_map(
+                |((stats, instrument), markout_sums)| {
+                    let mut stats = stats?;
+                    stats.times_to_fill.sort_unstable();
+                    stats.markouts = horizons.iter()
+                        .zip(markout_sums)
+                        .map(
+                            |(horizon, (sum, weight))| (
+                                *horizon,
+                                if weight != 0 { Some(sum as f64 / weight as f64) } else { None }
+                            )
+                        )
+                        .collect();
+                    Some((instrument?, stats))
+                }
+            )
+            .collect()
+    }
+}
+
+/// Computes the mean of the P&L changes divided by their sample standard deviation.
+/// Returns `None` if there are less than two changes or they do not vary.
+///
+/// # Arguments
+///
+/// * `changes` — P&L changes.
+pub fn sharpe_ratio(changes: &[f64]) -> Option<f64> {
+    if changes.len() < 2 {
+        return None;
+    }
+    let n = changes.len() as f64;
+    let mean = changes.iter().sum::<f64>() / n;
+    let variance = changes.iter().map(|change| (change - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    if variance > 0.0 {
+        Some(mean / variance.sqrt())
+    } else {
+        None
+    }
+}
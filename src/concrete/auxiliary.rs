@@ -0,0 +1,343 @@
+use {
+    crate::{
+        interface::{
+            auxiliary::{Auxiliary, AuxiliaryAction, AuxiliaryActionKind},
+            broker::Broker,
+            latency::LatencyGenerator,
+            message::AuxiliaryToAuxiliary,
+            trader::Trader,
+        },
+        types::{Agent, Date, DateTime, Id, NanoDuration, Named, TimeSync},
+        utils::queue::MessageReceiver,
+    },
+    csv::ReaderBuilder,
+    rand::Rng,
+    std::{
+        cmp::Ordering,
+        collections::{BTreeMap, BTreeSet, VecDeque},
+        marker::PhantomData,
+        path::Path,
+        rc::Rc,
+    },
+};
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Value of the [`NewsItem`].
+/// Scalars are compared by their [total order](f64::total_cmp), so that they can be messages.
+pub enum NewsContent {
+    /// Numeric value, e.g. the macro print.
+    Scalar(f64),
+    /// Text, e.g. the headline.
+    Text(String),
+}
+
+impl PartialEq for NewsContent {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for NewsContent {}
+
+impl PartialOrd for NewsContent {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for NewsContent {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Scalar(lhs), Self::Scalar(rhs)) => lhs.total_cmp(rhs),
+            (Self::Scalar(_), Self::Text(_)) => Ordering::Less,
+            (Self::Text(_), Self::Scalar(_)) => Ordering::Greater,
+            (Self::Text(lhs), Self::Text(rhs)) => lhs.cmp(rhs)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Timestamped event delivered by the [`NewsReplay`], e.g. the earnings or the macro print.
+pub struct NewsItem {
+    /// Datetime of the publication.
+    pub datetime: DateTime,
+    /// Topic the traders subscribe to, e.g. the ticker or the indicator name.
+    pub topic: String,
+    /// Value of the event.
+    pub content: NewsContent,
+}
+
+/// Reads the [`NewsItem`]s from the CSV-file with the `datetime`, `topic` and `value` columns.
+/// Values parsed as `f64` become [scalars](NewsContent::Scalar),
+/// the rest become [texts](NewsContent::Text).
+///
+/// # Arguments
+///
+/// * `path` — Path to the CSV-file.
+/// * `datetime_format` — Format of the `datetime` column.
+pub fn read_news_csv(path: impl AsRef<Path>, datetime_format: &str) -> Vec<NewsItem> {
+    let path = path.as_ref();
+    let mut reader = ReaderBuilder::new().from_path(path).unwrap_or_else(
+        |err| panic!("Cannot read the following file: {path:?}. Error: {err}")
+    );
+    let headers = reader.headers().unwrap_or_else(
+        |err| panic!("Cannot parse the header of the file: {path:?}. Error: {err}")
+    );
+    let get_column_idx = |column: &str| headers.iter()
+        .position(|header| header == column)
+        .unwrap_or_else(|| panic!("Cannot find the {column:?} column in the file: {path:?}"));
+    let datetime_idx = get_column_idx("datetime");
+    let topic_idx = get_column_idx("topic");
+    let value_idx = get_column_idx("value");
+    reader.records()
+        .zip(2..)
+        .map(
+            |(record, row_n)| {
+                let record = record.unwrap_or_else(
+                    |err| panic!(
+                        "Cannot parse {row_n}-th CSV-record for the file: {path:?}. Error: {err}"
+                    )
+                );
+                let datetime = &record[datetime_idx];
+                let value = &record[value_idx];
+                NewsItem {
+                    datetime: DateTime::parse_from_str(datetime, datetime_format)
+                        .unwrap_or_else(
+                            |err| panic!(
+                                "Cannot parse to NaiveDateTime: {datetime}. \
+                                Datetime format used: {datetime_format}. Error: {err}"
+                            )
+                        ),
+                    topic: record[topic_idx].to_string(),
+                    content: value.parse()
+                        .map(NewsContent::Scalar)
+                        .unwrap_or_else(|_| NewsContent::Text(value.to_string())),
+                }
+            }
+        )
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+/// Wakeup of the [`NewsReplay`] at the datetime of its next [`NewsItem`].
+pub struct NewsReplayWakeUp<AuxiliaryID: Id> {
+    /// Recipient.
+    pub news_replay_id: AuxiliaryID,
+}
+
+impl<AuxiliaryID: Id> AuxiliaryToAuxiliary for NewsReplayWakeUp<AuxiliaryID> {
+    type AuxiliaryID = AuxiliaryID;
+    fn get_auxiliary_id(&self) -> Self::AuxiliaryID {
+        self.news_replay_id
+    }
+}
+
+/// [`Auxiliary`] agent delivering the [`NewsItem`]s to the subscribed traders
+/// as their own wakeups, delayed by the latency of each trader.
+/// Traders receive the shared items converted into their `T2T` messages.
+///
+/// Items published before the start of the simulation are not delivered.
+/// Items of the same datetime are delivered in the order they are added.
+pub struct NewsReplay<AuxiliaryID, T, B, L>
+    where AuxiliaryID: Id,
+          T: Trader,
+          B: Broker,
+          L: LatencyGenerator<OuterID=T::TraderID>
+{
+    current_dt: DateTime,
+    name: AuxiliaryID,
+    latency_generator: L,
+    /// Items not yet delivered, sorted by datetime.
+    items: VecDeque<Rc<NewsItem>>,
+    /// Topics of the subscribers. `None` if the trader is subscribed to every topic.
+    subscribers: BTreeMap<T::TraderID, Option<BTreeSet<String>>>,
+    phantoms: PhantomData<fn() -> B>,
+}
+
+impl<AuxiliaryID, T, B, L> NewsReplay<AuxiliaryID, T, B, L>
+    where AuxiliaryID: Id,
+          T: Trader,
+          B: Broker,
+          L: LatencyGenerator<OuterID=T::TraderID>
+{
+    /// Creates a new instance of the `NewsReplay` without items and subscribers.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` — ID of the `NewsReplay`.
+    /// * `latency_generator` — Latency of the delivery to the traders,
+    ///   sampled as the outgoing one against the trader.
+    pub fn new(name: AuxiliaryID, latency_generator: L) -> Self {
+        Self {
+            current_dt: Date::from_ymd_opt(1970, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+            name,
+            latency_generator,
+            items: Default::default(),
+            subscribers: Default::default(),
+            phantoms: Default::default(),
+        }
+    }
+
+    /// Adds the items to deliver, e.g. the ones read with the [`read_news_csv`].
+    ///
+    /// # Arguments
+    ///
+    /// * `items` — Items in any order.
+    pub fn with_items(mut self, items: impl IntoIterator<Item=NewsItem>) -> Self {
+        let mut items: Vec<_> = self.items.drain(..)
+            .chain(items.into_iter().map(Rc::new))
+            .collect();
+        items.sort_by_key(|item| item.datetime);
+        self.items = items.into();
+        self
+    }
+
+    /// Subscribes the trader to every topic.
+    ///
+    /// # Arguments
+    ///
+    /// * `trader_id` — Subscriber.
+    pub fn with_subscriber(mut self, trader_id: T::TraderID) -> Self {
+        self.subscribers.insert(trader_id, None);
+        self
+    }
+
+    /// Subscribes the trader to the topics, in addition to the ones it is already subscribed to.
+    ///
+    /// # Arguments
+    ///
+    /// * `trader_id` — Subscriber.
+    /// * `topics` — Topics to subscribe to.
+    pub fn with_topic_subscriber(
+        mut self,
+        trader_id: T::TraderID,
+        topics: impl IntoIterator<Item=impl Into<String>>) -> Self
+    {
+        let subscription = self.subscribers.entry(trader_id).or_insert(Some(Default::default()));
+        if let Some(subscribed_topics) = subscription {
+            subscribed_topics.extend(topics.into_iter().map(Into::into))
+        }
+        self
+    }
+
+    /// Returns the number of items not yet delivered.
+    pub fn get_num_pending_items(&self) -> usize {
+        self.items.len()
+    }
+
+    fn schedule_next_wakeup<KerMsg: Ord, RNG: Rng>(
+        &self,
+        mut message_receiver: MessageReceiver<KerMsg>,
+        mut process_action: impl FnMut(<Self as Agent>::Action, &mut RNG) -> KerMsg,
+        rng: &mut RNG)
+    {
+        let next_dt = if let Some(item) = self.items.front() {
+            item.datetime
+        } else {
+            return;
+        };
+        let delay = NanoDuration::from_duration(next_dt - self.current_dt).unwrap_or_else(
+            || panic!("Cannot express the delay until {next_dt} in nanoseconds")
+        );
+        let action = AuxiliaryAction {
+            delay,
+            content: AuxiliaryActionKind::AuxiliaryToAuxiliary(
+                NewsReplayWakeUp { news_replay_id: self.name }
+            ),
+        };
+        message_receiver.push(process_action(action, rng))
+    }
+}
+
+impl<AuxiliaryID, T, B, L> TimeSync for NewsReplay<AuxiliaryID, T, B, L>
+    where AuxiliaryID: Id,
+          T: Trader,
+          B: Broker,
+          L: LatencyGenerator<OuterID=T::TraderID>
+{
+    fn current_datetime_mut(&mut self) -> &mut DateTime {
+        &mut self.current_dt
+    }
+}
+
+impl<AuxiliaryID, T, B, L> Named<AuxiliaryID> for NewsReplay<AuxiliaryID, T, B, L>
+    where AuxiliaryID: Id,
+          T: Trader,
+          B: Broker,
+          L: LatencyGenerator<OuterID=T::TraderID>
+{
+    fn get_name(&self) -> AuxiliaryID {
+        self.name
+    }
+}
+
+impl<AuxiliaryID, T, B, L> Agent for NewsReplay<AuxiliaryID, T, B, L>
+    where AuxiliaryID: Id,
+          T: Trader,
+          B: Broker,
+          L: LatencyGenerator<OuterID=T::TraderID>
+{
+    type Action = AuxiliaryAction<
+        NewsReplayWakeUp<AuxiliaryID>, T::TraderID, T::T2T, B::BrokerID, B::B2B
+    >;
+}
+
+impl<AuxiliaryID, T, B, L> Auxiliary for NewsReplay<AuxiliaryID, T, B, L>
+    where AuxiliaryID: Id,
+          T: Trader,
+          T::T2T: From<Rc<NewsItem>>,
+          B: Broker,
+          L: LatencyGenerator<OuterID=T::TraderID>
+{
+    type AuxiliaryID = AuxiliaryID;
+    type TraderID = T::TraderID;
+    type BrokerID = B::BrokerID;
+    type A2A = NewsReplayWakeUp<AuxiliaryID>;
+    type T2T = T::T2T;
+    type B2B = B::B2B;
+
+    fn initialize<KerMsg: Ord, RNG: Rng>(
+        &mut self,
+        message_receiver: MessageReceiver<KerMsg>,
+        process_action: impl FnMut(Self::Action, &mut RNG) -> KerMsg,
+        rng: &mut RNG)
+    {
+        let current_dt = self.current_dt;
+        self.items.retain(|item| item.datetime >= current_dt);
+        self.schedule_next_wakeup(message_receiver, process_action, rng)
+    }
+
+    fn process_auxiliary_message<KerMsg: Ord, RNG: Rng>(
+        &mut self,
+        mut message_receiver: MessageReceiver<KerMsg>,
+        mut process_action: impl FnMut(Self::Action, &mut RNG) -> KerMsg,
+        _: Self::A2A,
+        _: Self::AuxiliaryID,
+        rng: &mut RNG)
+    {
+        while let Some(item) = self.items.front() {
+            if item.datetime > self.current_dt {
+                break;
+            }
+            let item = self.items.pop_front().unwrap_or_else(|| unreachable!("Item is present"));
+            for (trader_id, topics) in &self.subscribers {
+                if topics.as_ref().is_some_and(|topics| !topics.contains(&item.topic)) {
+                    continue;
+                }
+                let action = AuxiliaryAction {
+                    delay: self.latency_generator.outgoing_latency(
+                        *trader_id, self.current_dt, rng,
+                    ),
+                    content: AuxiliaryActionKind::AuxiliaryToTrader {
+                        trader_id: *trader_id,
+                        t2t: Rc::clone(&item).into(),
+                    },
+                };
+                message_receiver.push(process_action(action, rng))
+            }
+        }
+        self.schedule_next_wakeup(message_receiver, process_action, rng)
+    }
+}
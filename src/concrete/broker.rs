@@ -1,20 +1,34 @@
 use {
     crate::{
         concrete::{
+            analytics::SharedTradeLog,
+            heartbeat::{HeartbeatConfig, HeartbeatMonitor, HeartbeatPoll},
             latency::ConstantLatency,
+            middleware::{BrokerMiddleware, Verdict},
             message_protocol::{
                 broker::{
                     reply::{
                         BasicBrokerReply,
                         BasicBrokerToTrader,
+                        BorrowRecalled,
+                        BuyIn,
                         CancellationReason,
                         CannotCancelOrder,
+                        FirmQuote,
                         InabilityToCancelReason,
                         OrderCancelled,
+                        OrderCarriedOver,
                         OrderPlacementDiscarded,
                         PlacementDiscardingReason,
+                        PositionClosedOut,
+                        QuoteRejected,
+                        QuoteRejectionReason,
+                        QuoteTraded,
+                        SubscriptionRejected,
+                        SubscriptionRejectionReason,
                     },
                     request::{BasicBrokerRequest, BasicBrokerToExchange},
+                    wakeup::{BasicBrokerToItself, GiveUp},
                 },
                 exchange::{
                     reply::{
@@ -22,17 +36,34 @@ use {
                         BasicExchangeToBrokerReply,
                         CancellationReason as ExchangeCancellationReason,
                         ExchangeEventNotification,
+                        LegExecuted,
                         MarketOrderNotFullyExecuted,
                         OrderAccepted,
                         OrderExecuted,
                         OrderPartiallyExecuted,
+                        ObSnapshot,
+                        OrderPriceAdjusted,
+                        OrderSizeReduced,
+                        OrderState,
+                        OrderStatus,
+                        RecoverySnapshot,
+                        StopOrderTriggered,
+                        TradeBusted,
                     }
                 },
+                replay::request::{BasicReplayToBroker, BasicReplayToBrokerRequest},
                 trader::request::{BasicTraderRequest, BasicTraderToBroker},
             },
+            order::{
+                LimitOrderCancelRequest,
+                LimitOrderPlacingRequest,
+                MarketOrderPlacingRequest,
+                QuoteAcceptance,
+                QuoteRequest,
+            },
             traded_pair::{settlement::GetSettlementLag, TradedPair},
-            trader::subscriptions::{SubscriptionConfig, SubscriptionList},
-            types::OrderID,
+            trader::subscriptions::{SubscriptionConfig, SubscriptionList, SubscriptionRequest},
+            types::{AccountID, Direction, Lots, OrderID, Tick},
         },
         interface::{
             broker::{Broker, BrokerAction, BrokerActionKind},
@@ -48,13 +79,109 @@ use {
             },
         },
         kernel::LatentActionProcessor,
-        types::{Agent, Date, DateTime, Id, Named, NeverType, Nothing, TimeSync},
-        utils::queue::MessageReceiver,
+        types::{Agent, Date, DateTime, Id, NanoDuration, Named, Nothing, TimeSync},
+        utils::{collections::{HashMap, HashSet}, queue::MessageReceiver},
+    },
+    accounts::{AccountLedger, OrderFill},
+    audit::{
+        AuditRecord,
+        AuditStage,
+        describe_broker_request,
+        describe_reply,
+        describe_trader_request,
+        SharedOrderAuditTrail,
     },
+    entitlements::Entitlements,
+    faults::{FaultConfig, FaultInjector, InjectedFaults, ReplyFault},
+    kill_switch::KillSwitch,
+    portfolio::Portfolio,
     rand::Rng,
-    std::{collections::{HashMap, HashSet}, marker::PhantomData, rc::Rc},
+    statements::StatementGenerator,
+    smallvec::SmallVec,
+    std::{
+        collections::{BTreeMap, VecDeque},
+        marker::PhantomData,
+        rc::Rc,
+    },
 };
 
+/// Accounts of the traders and allocation of their fills.
+pub mod accounts;
+/// Per-order lifecycle records of the routed orders.
+pub mod audit;
+/// Broker that fills the orders of the traders from its own quotes.
+pub mod dealer;
+/// Trading permissions of the traders.
+pub mod entitlements;
+/// Simulated operational errors of the order flow.
+pub mod faults;
+/// Blocking of the order flow of the traders breaching their risk limits.
+pub mod kill_switch;
+/// Positions and profits of the traders on the average cost basis.
+pub mod portfolio;
+/// Daily broker statements of the traders.
+pub mod statements;
+#[cfg(test)]
+mod tests;
+
+/// Subscribers to the single traded pair.
+/// Usually there are only few of them, so they are stored inline.
+type Subscribers<TraderID> = SmallVec<[(TraderID, SubscriptionList); 4]>;
+
+/// Order book snapshot feeds of the traders, by trader, exchange and traded pair.
+type ConflatedFeeds<TraderID, ExchangeID, Symbol, Settlement> = BTreeMap<
+    (TraderID, ExchangeID, TradedPair<Symbol, Settlement>),
+    ConflatedFeed<Symbol, Settlement>
+>;
+
+/// Log of the fills and the marks keyed by the exchange and the traded pair.
+type BrokerTradeLog<TraderID, ExchangeID, Symbol, Settlement> = SharedTradeLog<
+    TraderID,
+    (ExchangeID, TradedPair<Symbol, Settlement>)
+>;
+
+/// Conflation state of the order book snapshot feed of the single trader.
+struct ConflatedFeed<Symbol: Id, Settlement: GetSettlementLag> {
+    /// Datetime the last snapshot was forwarded at.
+    last_forward_dt: Option<DateTime>,
+    /// Latest snapshot held back along with its exchange datetime.
+    pending: Option<(DateTime, Rc<ObSnapshot<Symbol, Settlement>>)>,
+    /// Datetime of the scheduled [`BasicBrokerToItself::SnapshotFlush`].
+    flush_dt: Option<DateTime>,
+}
+
+#[derive(Clone, Copy)]
+/// Good-till-cancelled or good-till-date limit order tracked by the broker across the sessions.
+struct GtdOrder<ExchangeID: Id, Symbol: Id, Settlement: GetSettlementLag> {
+    exchange_id: ExchangeID,
+    /// Placing request with the internal ID and the remaining size.
+    request: LimitOrderPlacingRequest<Symbol, Settlement>,
+    /// Whether the order has been cancelled at the close and waits for the next session.
+    dormant: bool,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+/// Quoting parameters of the [`BasicBroker`] acting as a dealer
+/// in the request-for-quote workflow.
+pub struct RfqConfig {
+    /// Distance between the quote and the last trade price of the traded pair.
+    pub half_spread: Tick,
+    /// Time the quote is valid for since it is issued.
+    pub validity: NanoDuration,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+/// Clearing broker the [`BasicBroker`] gives the fills of the trader up to.
+pub struct GiveUpConfig<BrokerID: Id, TraderID: Id> {
+    /// Clearing broker.
+    pub clearing_broker_id: BrokerID,
+    /// Trader at the clearing broker the fills are booked to.
+    pub trader_id: TraderID,
+    /// Account of the trader at the clearing broker.
+    /// If absent, the allocation rule of the clearing broker is applied.
+    pub account: Option<AccountID>,
+}
+
 /// [`Broker`] that supports basic operations.
 pub struct BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
     where BrokerID: Id,
@@ -75,7 +202,13 @@ pub struct BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
     /// and Traders that are subscribed to the corresponding pairs
     traded_pairs_info: HashMap<
         (ExchangeID, TradedPair<Symbol, Settlement>),
-        Vec<(TraderID, SubscriptionList)>,
+        Subscribers<TraderID>,
+    >,
+    /// Traders waiting for the recovery snapshots of the traded pairs
+    /// along with the subscriptions to add once the snapshots arrive.
+    pending_recoveries: HashMap<
+        (ExchangeID, TradedPair<Symbol, Settlement>),
+        Subscribers<TraderID>,
     >,
 
     /// Submitted to Internal Order ID map
@@ -85,6 +218,81 @@ pub struct BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
 
     registered_exchanges: HashSet<ExchangeID>,
     next_internal_order_id: OrderID,
+
+    /// Quoting parameters. If absent, all quote requests are rejected.
+    rfq_config: Option<RfqConfig>,
+    /// Last trade prices observed at the exchanges.
+    last_trade_prices: HashMap<(ExchangeID, TradedPair<Symbol, Settlement>), Tick>,
+    /// Outstanding firm quotes.
+    firm_quotes: HashMap<(TraderID, OrderID), (ExchangeID, FirmQuote<Symbol, Settlement>)>,
+
+    /// Trading permissions. Traders without the entry are permitted everything.
+    entitlements: HashMap<TraderID, Entitlements<ExchangeID, Symbol, Settlement>>,
+
+    /// Ledger of the trader accounts. If absent, fills are not booked.
+    ledger: Option<AccountLedger<TraderID, ExchangeID, Symbol, Settlement>>,
+    /// Account tags and directions of the orders with outstanding fills, by internal ID.
+    order_accounts: HashMap<OrderID, (Option<AccountID>, Direction)>,
+    /// Owners and account tags of the filled orders, by internal ID. Used to unwind busted fills.
+    filled_orders: HashMap<OrderID, (TraderID, Option<AccountID>)>,
+    /// Clearing brokers the fills of the traders are given up to, by trader.
+    give_ups: HashMap<TraderID, GiveUpConfig<BrokerID, TraderID>>,
+    /// Fills given up to the clearing brokers that are not sent yet.
+    pending_give_ups: Vec<GiveUp<BrokerID, TraderID, ExchangeID, Symbol, Settlement>>,
+    /// Portfolios of the traders. If absent, positions are not tracked.
+    portfolios: Option<HashMap<TraderID, Portfolio<ExchangeID, Symbol, Settlement>>>,
+    /// Time, in nanoseconds, between the portfolio snapshots sent to the traders.
+    /// If absent, snapshots are not sent.
+    portfolio_snapshot_period: Option<NanoDuration>,
+    /// Datetime the last portfolio snapshots were sent at.
+    last_portfolio_snapshot_dt: Option<DateTime>,
+
+    /// Time, in nanoseconds, the trader is considered busy after a snapshot is forwarded to it.
+    /// If absent, snapshots are not conflated.
+    conflation_window: Option<NanoDuration>,
+    /// Order book snapshot feeds subject to the conflation.
+    conflated_feeds: ConflatedFeeds<TraderID, ExchangeID, Symbol, Settlement>,
+    /// Number of snapshots dropped due to the conflation.
+    num_conflated_snapshots: u64,
+
+    /// Generator of the daily statements. If absent, statements are not generated.
+    statements: Option<StatementGenerator<TraderID>>,
+    /// Log of the fills and the marks for the end-of-run statistics.
+    /// If absent, nothing is logged.
+    trade_log: Option<BrokerTradeLog<TraderID, ExchangeID, Symbol, Settlement>>,
+    /// Lifecycle records of the trader orders.
+    audit_trail: Option<SharedOrderAuditTrail<TraderID>>,
+
+    /// Scheduled outages of the session with the exchanges that have not ended yet,
+    /// sorted by their starts.
+    session_outages: VecDeque<(DateTime, DateTime)>,
+    /// Whether the session with the exchanges is down.
+    session_down: bool,
+    /// Datetime of the scheduled [`BasicBrokerToItself::SessionCheck`].
+    session_check_dt: Option<DateTime>,
+    /// Heartbeats sent to the exchanges. If absent, heartbeats are not sent.
+    heartbeats: Option<HeartbeatMonitor<ExchangeID>>,
+    /// Datetime of the last scheduled [`BasicBrokerToItself::HeartbeatCheck`].
+    heartbeat_check_dt: Option<DateTime>,
+    /// Exchanges and traded pairs of the limit orders that may be resting, by internal ID.
+    open_limit_orders: HashMap<OrderID, (ExchangeID, TradedPair<Symbol, Settlement>)>,
+    /// Reasons of the cancellations initiated by the broker itself, by internal ID.
+    broker_cancels: HashMap<OrderID, CancellationReason>,
+    /// Limit orders outliving the sessions that have not expired yet, by internal ID.
+    gtd_orders: HashMap<OrderID, GtdOrder<ExchangeID, Symbol, Settlement>>,
+    /// Directions, prices and remaining sizes of the limit orders that may be resting,
+    /// by internal ID. If absent, order status requests are forwarded to the exchanges.
+    order_cache: Option<HashMap<OrderID, (Direction, Tick, Lots)>>,
+    /// Owners and sizes of the forced buy-to-cover orders, by internal ID.
+    buy_ins: HashMap<OrderID, (TraderID, Lots)>,
+
+    /// Risk monitor blocking the order flow of the traders. If absent, nothing is blocked.
+    kill_switch: Option<KillSwitch<TraderID>>,
+    /// Simulated operational errors of the order flow. If absent, no errors are injected.
+    faults: Option<FaultInjector>,
+
+    /// User-defined hooks, in the order they are called.
+    middleware: Vec<Box<dyn BrokerMiddleware<BrokerID, TraderID, ExchangeID, Symbol, Settlement>>>,
 }
 
 impl<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
@@ -128,7 +336,7 @@ for BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
         Nothing,
         BasicBrokerToExchange<ExchangeID, Symbol, Settlement>,
         BasicBrokerToTrader<TraderID, ExchangeID, Symbol, Settlement>,
-        Nothing
+        BasicBrokerToItself<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
     >;
 }
 
@@ -162,36 +370,139 @@ for BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
     type TraderID = TraderID;
     type ExchangeID = ExchangeID;
 
-    type R2B = NeverType<BrokerID>;
+    type R2B = BasicReplayToBroker<BrokerID, ExchangeID, Symbol, Settlement>;
     type E2B = BasicExchangeToBroker<BrokerID, Symbol, Settlement>;
     type T2B = BasicTraderToBroker<BrokerID, ExchangeID, Symbol, Settlement>;
     type B2R = Nothing;
     type B2E = BasicBrokerToExchange<ExchangeID, Symbol, Settlement>;
     type B2T = BasicBrokerToTrader<TraderID, ExchangeID, Symbol, Settlement>;
-    type B2B = Nothing;
+    type B2B = BasicBrokerToItself<BrokerID, TraderID, ExchangeID, Symbol, Settlement>;
     type SubCfg = SubscriptionConfig<ExchangeID, Symbol, Settlement>;
 
+    // Wakeups make the BasicBroker observe the datetime they are scheduled at,
+    // so they are processed as any other event
     fn wakeup<KerMsg: Ord>(
         &mut self,
-        _: MessageReceiver<KerMsg>,
-        _: impl LatentActionProcessor<Self::Action, Self::ExchangeID, KerMsg=KerMsg>,
-        _: Nothing,
-        _: &mut impl Rng,
+        mut message_receiver: MessageReceiver<KerMsg>,
+        mut action_processor: impl LatentActionProcessor<Self::Action, Self::ExchangeID, KerMsg=KerMsg>,
+        scheduled_action: BasicBrokerToItself<BrokerID, TraderID, ExchangeID, Symbol, Settlement>,
+        rng: &mut impl Rng,
     ) {
-        unreachable!("{} :: Broker wakeups are not planned", self.current_dt)
+        self.sync_state(&mut message_receiver, &mut action_processor, rng);
+        if let (BasicBrokerToItself::SnapshotFlush, Some(window)) = (
+            scheduled_action, self.conflation_window
+        ) {
+            let due_snapshots = self.flush_conflated_snapshots(window, |_, _| false);
+            let mut latency_generator = self.get_latency_generator();
+            message_receiver.extend(
+                due_snapshots.into_iter().map(
+                    |action| action_processor.process_action(action, &mut latency_generator, rng)
+                )
+            );
+            self.update_latency_generator(latency_generator)
+        }
     }
 
     fn process_trader_request<KerMsg: Ord>(
         &mut self,
         mut message_receiver: MessageReceiver<KerMsg>,
         mut action_processor: impl LatentActionProcessor<Self::Action, Self::ExchangeID, KerMsg=KerMsg>,
-        request: BasicTraderToBroker<BrokerID, ExchangeID, Symbol, Settlement>,
+        mut request: BasicTraderToBroker<BrokerID, ExchangeID, Symbol, Settlement>,
         trader_id: TraderID,
         rng: &mut impl Rng,
     ) {
+        self.sync_state(&mut message_receiver, &mut action_processor, rng);
+        match self.apply_trader_request_middleware(trader_id, &mut request) {
+            Verdict::Pass => {}
+            Verdict::Suppress => return,
+            Verdict::Reject(reply) => {
+                let action = Self::create_broker_reply(
+                    trader_id,
+                    request.content.get_exchange_id(),
+                    self.current_dt,
+                    reply,
+                );
+                let mut latency_generator = self.get_latency_generator();
+                message_receiver.push(
+                    action_processor.process_action(action, &mut latency_generator, rng)
+                );
+                self.update_latency_generator(latency_generator);
+                return;
+            }
+        }
+        if let Some(audit_trail) = &self.audit_trail {
+            let record = describe_trader_request(AuditStage::Received, &request.content);
+            if let Some((order_id, record)) = record {
+                audit_trail.borrow_mut().record(
+                    trader_id,
+                    order_id,
+                    AuditRecord { broker_dt: Some(self.current_dt), ..record },
+                )
+            }
+        }
+        let account = request.account;
+        let quote_traded = matches!(request.content, BasicTraderRequest::AcceptQuote(..));
         let action = match request.content {
+            BasicTraderRequest::CancelLimitOrder(request, exchange_id)
+            if self.is_dormant(trader_id, request.order_id) => {
+                let order_id = self.submitted_to_internal[&(trader_id, request.order_id)];
+                self.gtd_orders.remove(&order_id);
+                self.order_accounts.remove(&order_id);
+                Self::create_broker_reply(
+                    trader_id,
+                    exchange_id,
+                    self.current_dt,
+                    BasicBrokerReply::OrderCancelled(
+                        OrderCancelled {
+                            traded_pair: request.traded_pair,
+                            order_id: request.order_id,
+                            reason: CancellationReason::TraderRequested,
+                        }
+                    ),
+                )
+            }
+            BasicTraderRequest::ReduceOrderSize(request, exchange_id)
+            if self.is_dormant(trader_id, request.order_id) => {
+                let order_id = self.submitted_to_internal[&(trader_id, request.order_id)];
+                let order = &mut self.gtd_orders.get_mut(&order_id).unwrap().request;
+                let reply = if request.size < order.size {
+                    order.size -= request.size;
+                    BasicBrokerReply::OrderSizeReduced(
+                        OrderSizeReduced {
+                            traded_pair: request.traded_pair,
+                            order_id: request.order_id,
+                            size: request.size,
+                            remaining_size: order.size,
+                        }
+                    )
+                } else {
+                    self.gtd_orders.remove(&order_id);
+                    self.order_accounts.remove(&order_id);
+                    BasicBrokerReply::OrderCancelled(
+                        OrderCancelled {
+                            traded_pair: request.traded_pair,
+                            order_id: request.order_id,
+                            reason: CancellationReason::TraderRequested,
+                        }
+                    )
+                };
+                Self::create_broker_reply(trader_id, exchange_id, self.current_dt, reply)
+            }
             BasicTraderRequest::CancelLimitOrder(mut request, exchange_id) => {
-                if self.registered_exchanges.contains(&exchange_id) {
+                if self.session_down {
+                    Self::create_broker_reply(
+                        trader_id,
+                        exchange_id,
+                        self.current_dt,
+                        BasicBrokerReply::CannotCancelOrder(
+                            CannotCancelOrder {
+                                traded_pair: request.traded_pair,
+                                order_id: request.order_id,
+                                reason: InabilityToCancelReason::BrokerSessionDown,
+                            }
+                        ),
+                    )
+                } else if self.registered_exchanges.contains(&exchange_id) {
                     if let Some(order_id) = self.submitted_to_internal.get(
                         &(trader_id, request.order_id)
                     ) {
@@ -229,24 +540,108 @@ for BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
                     )
                 }
             }
+            BasicTraderRequest::ReduceOrderSize(mut request, exchange_id) => {
+                let internal_order_id = if self.session_down {
+                    Err(InabilityToCancelReason::BrokerSessionDown)
+                } else if !self.registered_exchanges.contains(&exchange_id) {
+                    Err(InabilityToCancelReason::BrokerNotConnectedToExchange)
+                } else {
+                    self.submitted_to_internal.get(&(trader_id, request.order_id))
+                        .copied()
+                        .ok_or(InabilityToCancelReason::OrderHasNotBeenSubmitted)
+                };
+                match internal_order_id {
+                    Ok(order_id) => {
+                        request.order_id = order_id;
+                        Self::create_broker_request(
+                            exchange_id,
+                            BasicBrokerRequest::ReduceOrderSize(request),
+                        )
+                    }
+                    Err(reason) => {
+                        Self::create_broker_reply(
+                            trader_id,
+                            exchange_id,
+                            self.current_dt,
+                            BasicBrokerReply::CannotReduceOrderSize(
+                                CannotCancelOrder {
+                                    traded_pair: request.traded_pair,
+                                    order_id: request.order_id,
+                                    reason,
+                                }
+                            ),
+                        )
+                    }
+                }
+            }
             BasicTraderRequest::PlaceLimitOrder(mut request, exchange_id) => {
-                if self.registered_exchanges.contains(&exchange_id) {
-                    self.internal_to_submitted.insert(
-                        self.next_internal_order_id,
-                        (trader_id, request.order_id),
-                    );
-                    self.submitted_to_internal.insert(
-                        (trader_id, request.order_id),
-                        self.next_internal_order_id,
-                    );
-                    request.order_id = self.next_internal_order_id;
-                    self.next_internal_order_id += OrderID(1);
-                    Self::create_broker_request(
+                match self.register_order::<true>(
+                    trader_id,
+                    account,
+                    exchange_id,
+                    request.traded_pair,
+                    request.order_id,
+                    request.direction,
+                ) {
+                    Ok(order_id) => {
+                        if let Some(trade_log) = &self.trade_log {
+                            trade_log.borrow_mut().record_limit_order(
+                                self.current_dt,
+                                trader_id,
+                                (exchange_id, request.traded_pair),
+                                request.order_id,
+                                request.size,
+                            )
+                        }
+                        if let Some(order_cache) = &mut self.order_cache {
+                            order_cache.insert(
+                                order_id,
+                                (request.direction, request.price, request.size),
+                            );
+                        }
+                        request.order_id = order_id;
+                        if request.time_in_force.is_carried_over() {
+                            self.gtd_orders.insert(
+                                order_id,
+                                GtdOrder { exchange_id, request, dormant: false },
+                            );
+                        }
+                        Self::create_broker_request(
+                            exchange_id,
+                            BasicBrokerRequest::PlaceLimitOrder(request),
+                        )
+                    }
+                    Err(reason) => Self::create_broker_reply(
+                        trader_id,
                         exchange_id,
-                        BasicBrokerRequest::PlaceLimitOrder(request),
+                        self.current_dt,
+                        BasicBrokerReply::OrderPlacementDiscarded(
+                            OrderPlacementDiscarded {
+                                traded_pair: request.traded_pair,
+                                order_id: request.order_id,
+                                reason,
+                            }
+                        ),
                     )
-                } else {
-                    Self::create_broker_reply(
+                }
+            }
+            BasicTraderRequest::PlaceMarketOrder(mut request, exchange_id) => {
+                match self.register_order::<false>(
+                    trader_id,
+                    account,
+                    exchange_id,
+                    request.traded_pair,
+                    request.order_id,
+                    request.direction,
+                ) {
+                    Ok(order_id) => {
+                        request.order_id = order_id;
+                        Self::create_broker_request(
+                            exchange_id,
+                            BasicBrokerRequest::PlaceMarketOrder(request),
+                        )
+                    }
+                    Err(reason) => Self::create_broker_reply(
                         trader_id,
                         exchange_id,
                         self.current_dt,
@@ -254,30 +649,104 @@ for BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
                             OrderPlacementDiscarded {
                                 traded_pair: request.traded_pair,
                                 order_id: request.order_id,
-                                reason: PlacementDiscardingReason::BrokerNotConnectedToExchange,
+                                reason,
                             }
                         ),
                     )
                 }
             }
-            BasicTraderRequest::PlaceMarketOrder(mut request, exchange_id) => {
-                if self.registered_exchanges.contains(&exchange_id) {
-                    self.internal_to_submitted.insert(
-                        self.next_internal_order_id,
-                        (trader_id, request.order_id),
-                    );
-                    self.submitted_to_internal.insert(
-                        (trader_id, request.order_id),
-                        self.next_internal_order_id,
-                    );
-                    request.order_id = self.next_internal_order_id;
-                    self.next_internal_order_id += OrderID(1);
-                    Self::create_broker_request(
+            BasicTraderRequest::PlaceIcebergOrder(mut request, exchange_id) => {
+                match self.register_order::<true>(
+                    trader_id,
+                    account,
+                    exchange_id,
+                    request.traded_pair,
+                    request.order_id,
+                    request.direction,
+                ) {
+                    Ok(order_id) => {
+                        if let Some(trade_log) = &self.trade_log {
+                            trade_log.borrow_mut().record_limit_order(
+                                self.current_dt,
+                                trader_id,
+                                (exchange_id, request.traded_pair),
+                                request.order_id,
+                                request.size,
+                            )
+                        }
+                        if let Some(order_cache) = &mut self.order_cache {
+                            order_cache.insert(
+                                order_id,
+                                (request.direction, request.price, request.size),
+                            );
+                        }
+                        request.order_id = order_id;
+                        Self::create_broker_request(
+                            exchange_id,
+                            BasicBrokerRequest::PlaceIcebergOrder(request),
+                        )
+                    }
+                    Err(reason) => Self::create_broker_reply(
+                        trader_id,
                         exchange_id,
-                        BasicBrokerRequest::PlaceMarketOrder(request),
+                        self.current_dt,
+                        BasicBrokerReply::OrderPlacementDiscarded(
+                            OrderPlacementDiscarded {
+                                traded_pair: request.traded_pair,
+                                order_id: request.order_id,
+                                reason,
+                            }
+                        ),
                     )
-                } else {
-                    Self::create_broker_reply(
+                }
+            }
+            BasicTraderRequest::PlaceStopOrder(mut request, exchange_id) => {
+                match self.register_order::<true>(
+                    trader_id,
+                    account,
+                    exchange_id,
+                    request.traded_pair,
+                    request.order_id,
+                    request.direction,
+                ) {
+                    Ok(order_id) => {
+                        request.order_id = order_id;
+                        Self::create_broker_request(
+                            exchange_id,
+                            BasicBrokerRequest::PlaceStopOrder(request),
+                        )
+                    }
+                    Err(reason) => Self::create_broker_reply(
+                        trader_id,
+                        exchange_id,
+                        self.current_dt,
+                        BasicBrokerReply::OrderPlacementDiscarded(
+                            OrderPlacementDiscarded {
+                                traded_pair: request.traded_pair,
+                                order_id: request.order_id,
+                                reason,
+                            }
+                        ),
+                    )
+                }
+            }
+            BasicTraderRequest::PlaceStopLimitOrder(mut request, exchange_id) => {
+                match self.register_order::<true>(
+                    trader_id,
+                    account,
+                    exchange_id,
+                    request.traded_pair,
+                    request.order_id,
+                    request.direction,
+                ) {
+                    Ok(order_id) => {
+                        request.order_id = order_id;
+                        Self::create_broker_request(
+                            exchange_id,
+                            BasicBrokerRequest::PlaceStopLimitOrder(request),
+                        )
+                    }
+                    Err(reason) => Self::create_broker_reply(
                         trader_id,
                         exchange_id,
                         self.current_dt,
@@ -285,26 +754,161 @@ for BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
                             OrderPlacementDiscarded {
                                 traded_pair: request.traded_pair,
                                 order_id: request.order_id,
-                                reason: PlacementDiscardingReason::BrokerNotConnectedToExchange,
+                                reason,
+                            }
+                        ),
+                    )
+                }
+            }
+            BasicTraderRequest::QueryOrderStatus(mut request, exchange_id) => {
+                let internal_order_id = self.submitted_to_internal
+                    .get(&(trader_id, request.order_id))
+                    .copied();
+                let state = match internal_order_id {
+                    None => Some(OrderState::Unknown),
+                    Some(order_id)
+                    if self.order_cache.is_some() || self.is_dormant(trader_id, request.order_id) => {
+                        Some(self.get_cached_order_state(order_id))
+                    }
+                    Some(_) if self.session_down
+                        || !self.registered_exchanges.contains(&exchange_id) => {
+                        Some(OrderState::Unknown)
+                    }
+                    Some(_) => None
+                };
+                if let Some(state) = state {
+                    Self::create_broker_reply(
+                        trader_id,
+                        exchange_id,
+                        self.current_dt,
+                        BasicBrokerReply::OrderStatus(
+                            OrderStatus {
+                                traded_pair: request.traded_pair,
+                                order_id: request.order_id,
+                                state,
                             }
                         ),
                     )
+                } else {
+                    request.order_id = internal_order_id.unwrap_or_else(
+                        || unreachable!("Order {} should have been submitted", request.order_id)
+                    );
+                    Self::create_broker_request(
+                        exchange_id,
+                        BasicBrokerRequest::QueryOrderStatus(request),
+                    )
+                }
+            }
+            BasicTraderRequest::Subscribe(request, exchange_id) => {
+                match self.await_recovery(trader_id, exchange_id, request) {
+                    Ok(()) => Self::create_broker_request(
+                        exchange_id,
+                        BasicBrokerRequest::RequestRecoverySnapshot(request.traded_pair),
+                    ),
+                    Err(reason) => Self::create_broker_reply(
+                        trader_id,
+                        exchange_id,
+                        self.current_dt,
+                        BasicBrokerReply::SubscriptionRejected(
+                            SubscriptionRejected { traded_pair: request.traded_pair, reason }
+                        ),
+                    )
                 }
             }
+            BasicTraderRequest::Heartbeat(heartbeat, exchange_id) => {
+                Self::create_broker_reply(
+                    trader_id,
+                    exchange_id,
+                    self.current_dt,
+                    BasicBrokerReply::Heartbeat(heartbeat),
+                )
+            }
+            BasicTraderRequest::CloseOutPosition(traded_pair, exchange_id) => {
+                let account = account.unwrap_or_default();
+                let size = self.ledger.as_mut().map_or(
+                    Lots(0),
+                    |ledger| ledger.close_out(trader_id, account, exchange_id, traded_pair),
+                );
+                Self::create_broker_reply(
+                    trader_id,
+                    exchange_id,
+                    self.current_dt,
+                    BasicBrokerReply::PositionClosedOut(
+                        PositionClosedOut { traded_pair, account, size }
+                    ),
+                )
+            }
+            BasicTraderRequest::RequestQuote(request, exchange_id) => {
+                Self::create_broker_reply(
+                    trader_id,
+                    exchange_id,
+                    self.current_dt,
+                    self.process_quote_request(trader_id, exchange_id, request),
+                )
+            }
+            BasicTraderRequest::AcceptQuote(acceptance, exchange_id) => {
+                Self::create_broker_reply(
+                    trader_id,
+                    exchange_id,
+                    self.current_dt,
+                    self.process_quote_acceptance(trader_id, exchange_id, account, acceptance),
+                )
+            }
         };
-        message_receiver.push(
-            action_processor.process_action(action, self.get_latency_generator(), rng)
-        )
+        self.push_order_flow_action(
+            &mut message_receiver,
+            &mut action_processor,
+            action,
+            None,
+            rng,
+        );
+        if quote_traded {
+            self.send_give_ups(&mut message_receiver, &mut action_processor, rng);
+            self.run_kill_switch(&mut message_receiver, &mut action_processor, rng)
+        }
     }
 
     fn process_exchange_reply<KerMsg: Ord>(
         &mut self,
         mut message_receiver: MessageReceiver<KerMsg>,
         mut action_processor: impl LatentActionProcessor<Self::Action, Self::ExchangeID, KerMsg=KerMsg>,
-        reply: BasicExchangeToBroker<BrokerID, Symbol, Settlement>,
+        mut reply: BasicExchangeToBroker<BrokerID, Symbol, Settlement>,
         exchange_id: ExchangeID,
         rng: &mut impl Rng,
     ) {
+        self.sync_state(&mut message_receiver, &mut action_processor, rng);
+        let current_dt = self.current_dt;
+        let passed = self.middleware.iter_mut().all(
+            |middleware| middleware.on_exchange_reply(current_dt, exchange_id, &mut reply)
+        );
+        if !passed {
+            return;
+        }
+        // Spread fills are booked as the fills of their legs
+        let legs = if let BasicExchangeToBrokerReply::SpreadOrderExecuted(spread) = &reply.content {
+            let spread = Rc::clone(spread);
+            reply.content = spread.execution.clone();
+            spread.legs.clone()
+        } else {
+            vec![]
+        };
+        let buy_in = self.process_buy_in_reply(
+            &mut message_receiver,
+            &mut action_processor,
+            &reply,
+            exchange_id,
+            rng,
+        );
+        if buy_in {
+            self.send_give_ups(&mut message_receiver, &mut action_processor, rng);
+            return;
+        }
+        self.update_order_cache(&reply.content);
+        let filled = matches!(
+            reply.content,
+            BasicExchangeToBrokerReply::OrderPartiallyExecuted(_)
+            | BasicExchangeToBrokerReply::OrderExecuted(_)
+        );
         let message = match reply.content {
             BasicExchangeToBrokerReply::OrderAccepted(accepted) => {
                 if let Some((trader_id, order_id)) = self.internal_to_submitted.get(
@@ -315,10 +919,7 @@ for BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
                         exchange_id,
                         reply.exchange_dt,
                         BasicBrokerReply::OrderAccepted(
-                            OrderAccepted {
-                                traded_pair: accepted.traded_pair,
-                                order_id: *order_id,
-                            }
+                            OrderAccepted { order_id: *order_id, ..accepted }
                         ),
                     )
                 } else {
@@ -329,6 +930,9 @@ for BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
                 }
             }
             BasicExchangeToBrokerReply::OrderPlacementDiscarded(discarded) => {
+                self.order_accounts.remove(&discarded.order_id);
+                self.open_limit_orders.remove(&discarded.order_id);
+                self.gtd_orders.remove(&discarded.order_id);
                 if let Some((trader_id, order_id)) = self.internal_to_submitted.get(
                     &discarded.order_id
                 ) {
@@ -351,31 +955,46 @@ for BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
                     )
                 }
             }
-            BasicExchangeToBrokerReply::OrderPartiallyExecuted(executed) => {
+            BasicExchangeToBrokerReply::OrderPriceAdjusted(adjusted) => {
+                if let Some(order) = self.gtd_orders.get_mut(&adjusted.order_id) {
+                    order.request.price = adjusted.price
+                }
                 if let Some((trader_id, order_id)) = self.internal_to_submitted.get(
-                    &executed.order_id
+                    &adjusted.order_id
                 ) {
                     Self::create_broker_reply(
                         *trader_id,
                         exchange_id,
                         reply.exchange_dt,
-                        BasicBrokerReply::OrderPartiallyExecuted(
-                            OrderPartiallyExecuted {
-                                traded_pair: executed.traded_pair,
+                        BasicBrokerReply::OrderPriceAdjusted(
+                            OrderPriceAdjusted {
+                                traded_pair: adjusted.traded_pair,
                                 order_id: *order_id,
-                                price: executed.price,
-                                size: executed.size,
+                                original_price: adjusted.original_price,
+                                price: adjusted.price,
                             }
                         ),
                     )
                 } else {
                     panic!(
                         "Cannot find a corresponding submitted order id \
-                        for the internal order id {}", executed.order_id
+                        for the internal order id {}", adjusted.order_id
                     )
                 }
             }
-            BasicExchangeToBrokerReply::OrderExecuted(executed) => {
+            BasicExchangeToBrokerReply::OrderPartiallyExecuted(executed) => {
+                if let Some(order) = self.gtd_orders.get_mut(&executed.order_id) {
+                    order.request.size -= executed.size
+                }
+                self.book_order_fill(
+                    exchange_id,
+                    reply.exchange_dt,
+                    executed.order_id,
+                    executed.traded_pair,
+                    executed.price,
+                    executed.size,
+                    &legs,
+                );
                 if let Some((trader_id, order_id)) = self.internal_to_submitted.get(
                     &executed.order_id
                 ) {
@@ -383,13 +1002,8 @@ for BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
                         *trader_id,
                         exchange_id,
                         reply.exchange_dt,
-                        BasicBrokerReply::OrderExecuted(
-                            OrderExecuted {
-                                traded_pair: executed.traded_pair,
-                                order_id: *order_id,
-                                price: executed.price,
-                                size: executed.size,
-                            }
+                        BasicBrokerReply::OrderPartiallyExecuted(
+                            OrderPartiallyExecuted { order_id: *order_id, ..executed }
                         ),
                     )
                 } else {
@@ -399,30 +1013,202 @@ for BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
                     )
                 }
             }
-            BasicExchangeToBrokerReply::MarketOrderNotFullyExecuted(not_fully_exec) => {
+            BasicExchangeToBrokerReply::OrderExecuted(executed) => {
+                self.book_order_fill(
+                    exchange_id,
+                    reply.exchange_dt,
+                    executed.order_id,
+                    executed.traded_pair,
+                    executed.price,
+                    executed.size,
+                    &legs,
+                );
+                self.order_accounts.remove(&executed.order_id);
+                self.open_limit_orders.remove(&executed.order_id);
+                self.gtd_orders.remove(&executed.order_id);
                 if let Some((trader_id, order_id)) = self.internal_to_submitted.get(
-                    &not_fully_exec.order_id
+                    &executed.order_id
                 ) {
                     Self::create_broker_reply(
                         *trader_id,
                         exchange_id,
                         reply.exchange_dt,
-                        BasicBrokerReply::MarketOrderNotFullyExecuted(
-                            MarketOrderNotFullyExecuted {
-                                traded_pair: not_fully_exec.traded_pair,
-                                order_id: *order_id,
-                                remaining_size: not_fully_exec.remaining_size,
-                            }
+                        BasicBrokerReply::OrderExecuted(
+                            OrderExecuted { order_id: *order_id, ..executed }
                         ),
                     )
                 } else {
                     panic!(
                         "Cannot find a corresponding submitted order id \
-                        for the internal order id {}", not_fully_exec.order_id
+                        for the internal order id {}", executed.order_id
                     )
                 }
             }
-            BasicExchangeToBrokerReply::OrderCancelled(order_cancelled) => {
+            BasicExchangeToBrokerReply::SpreadOrderExecuted(_) => {
+                unreachable!("{} :: Spread order execution is not unwrapped", self.current_dt)
+            }
+            BasicExchangeToBrokerReply::ShadowOrderPartiallyExecuted(executed) => {
+                if let Some(order) = self.gtd_orders.get_mut(&executed.order_id) {
+                    order.request.size -= executed.size
+                }
+                if let Some((trader_id, order_id)) = self.internal_to_submitted.get(
+                    &executed.order_id
+                ) {
+                    Self::create_broker_reply(
+                        *trader_id,
+                        exchange_id,
+                        reply.exchange_dt,
+                        BasicBrokerReply::ShadowOrderPartiallyExecuted(
+                            OrderPartiallyExecuted { order_id: *order_id, ..executed }
+                        ),
+                    )
+                } else {
+                    panic!(
+                        "Cannot find a corresponding submitted order id \
+                        for the internal order id {}", executed.order_id
+                    )
+                }
+            }
+            BasicExchangeToBrokerReply::ShadowOrderExecuted(executed) => {
+                self.order_accounts.remove(&executed.order_id);
+                self.open_limit_orders.remove(&executed.order_id);
+                self.gtd_orders.remove(&executed.order_id);
+                if let Some((trader_id, order_id)) = self.internal_to_submitted.get(
+                    &executed.order_id
+                ) {
+                    Self::create_broker_reply(
+                        *trader_id,
+                        exchange_id,
+                        reply.exchange_dt,
+                        BasicBrokerReply::ShadowOrderExecuted(
+                            OrderExecuted { order_id: *order_id, ..executed }
+                        ),
+                    )
+                } else {
+                    panic!(
+                        "Cannot find a corresponding submitted order id \
+                        for the internal order id {}", executed.order_id
+                    )
+                }
+            }
+            BasicExchangeToBrokerReply::GhostOrderPartiallyExecuted(executed) => {
+                if let Some(order) = self.gtd_orders.get_mut(&executed.order_id) {
+                    order.request.size -= executed.size
+                }
+                if let Some((trader_id, order_id)) = self.internal_to_submitted.get(
+                    &executed.order_id
+                ) {
+                    Self::create_broker_reply(
+                        *trader_id,
+                        exchange_id,
+                        reply.exchange_dt,
+                        BasicBrokerReply::GhostOrderPartiallyExecuted(
+                            OrderPartiallyExecuted { order_id: *order_id, ..executed }
+                        ),
+                    )
+                } else {
+                    panic!(
+                        "Cannot find a corresponding submitted order id \
+                        for the internal order id {}", executed.order_id
+                    )
+                }
+            }
+            BasicExchangeToBrokerReply::GhostOrderExecuted(executed) => {
+                self.order_accounts.remove(&executed.order_id);
+                self.open_limit_orders.remove(&executed.order_id);
+                self.gtd_orders.remove(&executed.order_id);
+                if let Some((trader_id, order_id)) = self.internal_to_submitted.get(
+                    &executed.order_id
+                ) {
+                    Self::create_broker_reply(
+                        *trader_id,
+                        exchange_id,
+                        reply.exchange_dt,
+                        BasicBrokerReply::GhostOrderExecuted(
+                            OrderExecuted { order_id: *order_id, ..executed }
+                        ),
+                    )
+                } else {
+                    panic!(
+                        "Cannot find a corresponding submitted order id \
+                        for the internal order id {}", executed.order_id
+                    )
+                }
+            }
+            BasicExchangeToBrokerReply::StopOrderTriggered(triggered) => {
+                // Triggered stop orders are executed as the market ones at once
+                if triggered.limit_price.is_none() {
+                    self.open_limit_orders.remove(&triggered.order_id);
+                }
+                if let Some((trader_id, order_id)) = self.internal_to_submitted.get(
+                    &triggered.order_id
+                ) {
+                    Self::create_broker_reply(
+                        *trader_id,
+                        exchange_id,
+                        reply.exchange_dt,
+                        BasicBrokerReply::StopOrderTriggered(
+                            StopOrderTriggered { order_id: *order_id, ..triggered }
+                        ),
+                    )
+                } else {
+                    panic!(
+                        "Cannot find a corresponding submitted order id \
+                        for the internal order id {}", triggered.order_id
+                    )
+                }
+            }
+            BasicExchangeToBrokerReply::MarketOrderNotFullyExecuted(not_fully_exec) => {
+                self.order_accounts.remove(&not_fully_exec.order_id);
+                if let Some((trader_id, order_id)) = self.internal_to_submitted.get(
+                    &not_fully_exec.order_id
+                ) {
+                    Self::create_broker_reply(
+                        *trader_id,
+                        exchange_id,
+                        reply.exchange_dt,
+                        BasicBrokerReply::MarketOrderNotFullyExecuted(
+                            MarketOrderNotFullyExecuted {
+                                traded_pair: not_fully_exec.traded_pair,
+                                order_id: *order_id,
+                                remaining_size: not_fully_exec.remaining_size,
+                            }
+                        ),
+                    )
+                } else {
+                    panic!(
+                        "Cannot find a corresponding submitted order id \
+                        for the internal order id {}", not_fully_exec.order_id
+                    )
+                }
+            }
+            // Orders outliving the session are resubmitted once trades restart
+            BasicExchangeToBrokerReply::OrderCancelled(order_cancelled)
+            if order_cancelled.reason == ExchangeCancellationReason::ExchangeClosed
+                && self.is_carried_over(order_cancelled.order_id, reply.exchange_dt) => {
+                self.open_limit_orders.remove(&order_cancelled.order_id);
+                let order = self.gtd_orders.get_mut(&order_cancelled.order_id).unwrap();
+                order.dormant = true;
+                let expiration = order.request.time_in_force.get_expiration();
+                let (trader_id, order_id) = self.internal_to_submitted[&order_cancelled.order_id];
+                Self::create_broker_reply(
+                    trader_id,
+                    exchange_id,
+                    reply.exchange_dt,
+                    BasicBrokerReply::OrderCarriedOver(
+                        OrderCarriedOver {
+                            traded_pair: order_cancelled.traded_pair,
+                            order_id,
+                            expiration,
+                        }
+                    ),
+                )
+            }
+            BasicExchangeToBrokerReply::OrderCancelled(order_cancelled) => {
+                self.order_accounts.remove(&order_cancelled.order_id);
+                self.open_limit_orders.remove(&order_cancelled.order_id);
+                let broker_cancel = self.broker_cancels.remove(&order_cancelled.order_id);
+                let expired = self.gtd_orders.remove(&order_cancelled.order_id).is_some();
                 if let Some((trader_id, order_id)) = self.internal_to_submitted.get(
                     &order_cancelled.order_id
                 ) {
@@ -436,7 +1222,10 @@ for BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
                                 order_id: *order_id,
                                 reason: match order_cancelled.reason {
                                     ExchangeCancellationReason::BrokerRequested => {
-                                        CancellationReason::TraderRequested
+                                        broker_cancel.unwrap_or(CancellationReason::TraderRequested)
+                                    }
+                                    ExchangeCancellationReason::ExchangeClosed if expired => {
+                                        CancellationReason::Expired
                                     }
                                     ExchangeCancellationReason::ExchangeClosed => {
                                         CancellationReason::ExchangeClosed
@@ -444,6 +1233,21 @@ for BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
                                     ExchangeCancellationReason::TradesStopped => {
                                         CancellationReason::TradesStopped
                                     }
+                                    ExchangeCancellationReason::OffTickPrice => {
+                                        CancellationReason::OffTickPrice
+                                    }
+                                    ExchangeCancellationReason::OutsidePriceBand => {
+                                        CancellationReason::OutsidePriceBand
+                                    }
+                                    ExchangeCancellationReason::ExchangeDown => {
+                                        CancellationReason::ExchangeDown
+                                    }
+                                    ExchangeCancellationReason::Expired => {
+                                        CancellationReason::Expired
+                                    }
+                                    ExchangeCancellationReason::NotImmediatelyExecuted => {
+                                        CancellationReason::NotImmediatelyExecuted
+                                    }
                                 },
                             }
                         ),
@@ -455,111 +1259,2051 @@ for BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
                     )
                 }
             }
-            BasicExchangeToBrokerReply::CannotCancelOrder(cannot_cancel) => {
-                if let Some((trader_id, order_id)) = self.internal_to_submitted.get(
-                    &cannot_cancel.order_id
-                ) {
+            BasicExchangeToBrokerReply::CannotCancelOrder(cannot_cancel) => {
+                if let Some((trader_id, order_id)) = self.internal_to_submitted.get(
+                    &cannot_cancel.order_id
+                ) {
+                    Self::create_broker_reply(
+                        *trader_id,
+                        exchange_id,
+                        reply.exchange_dt,
+                        BasicBrokerReply::CannotCancelOrder(
+                            CannotCancelOrder {
+                                traded_pair: cannot_cancel.traded_pair,
+                                order_id: *order_id,
+                                reason: cannot_cancel.reason.into(),
+                            }
+                        ),
+                    )
+                } else {
+                    panic!(
+                        "Cannot find a corresponding submitted order id \
+                        for the internal order id {}", cannot_cancel.order_id
+                    )
+                }
+            }
+            BasicExchangeToBrokerReply::OrderSizeReduced(reduced) => {
+                if let Some(order) = self.gtd_orders.get_mut(&reduced.order_id) {
+                    order.request.size = reduced.remaining_size
+                }
+                if let Some((trader_id, order_id)) = self.internal_to_submitted.get(
+                    &reduced.order_id
+                ) {
+                    Self::create_broker_reply(
+                        *trader_id,
+                        exchange_id,
+                        reply.exchange_dt,
+                        BasicBrokerReply::OrderSizeReduced(
+                            OrderSizeReduced { order_id: *order_id, ..reduced }
+                        ),
+                    )
+                } else {
+                    panic!(
+                        "Cannot find a corresponding submitted order id \
+                        for the internal order id {}", reduced.order_id
+                    )
+                }
+            }
+            BasicExchangeToBrokerReply::CannotReduceOrderSize(cannot_reduce) => {
+                if let Some((trader_id, order_id)) = self.internal_to_submitted.get(
+                    &cannot_reduce.order_id
+                ) {
+                    Self::create_broker_reply(
+                        *trader_id,
+                        exchange_id,
+                        reply.exchange_dt,
+                        BasicBrokerReply::CannotReduceOrderSize(
+                            CannotCancelOrder {
+                                traded_pair: cannot_reduce.traded_pair,
+                                order_id: *order_id,
+                                reason: cannot_reduce.reason.into(),
+                            }
+                        ),
+                    )
+                } else {
+                    panic!(
+                        "Cannot find a corresponding submitted order id \
+                        for the internal order id {}", cannot_reduce.order_id
+                    )
+                }
+            }
+            BasicExchangeToBrokerReply::OrderStatus(status) => {
+                if let Some((trader_id, order_id)) = self.internal_to_submitted.get(
+                    &status.order_id
+                ) {
+                    Self::create_broker_reply(
+                        *trader_id,
+                        exchange_id,
+                        reply.exchange_dt,
+                        BasicBrokerReply::OrderStatus(
+                            OrderStatus { order_id: *order_id, ..status }
+                        ),
+                    )
+                } else {
+                    panic!(
+                        "Cannot find a corresponding submitted order id \
+                        for the internal order id {}", status.order_id
+                    )
+                }
+            }
+            BasicExchangeToBrokerReply::Heartbeat(heartbeat) => {
+                let restored = self.heartbeats.as_mut().is_some_and(
+                    |heartbeats| heartbeats.receive(exchange_id, heartbeat, self.current_dt)
+                );
+                if restored {
+                    self.notify_traders(
+                        &mut message_receiver,
+                        &mut action_processor,
+                        exchange_id,
+                        BasicBrokerReply::HeartbeatRestored,
+                        rng,
+                    )
+                }
+                return;
+            }
+            BasicExchangeToBrokerReply::RecoverySnapshot(snapshot) => {
+                self.complete_recoveries(
+                    &mut message_receiver,
+                    &mut action_processor,
+                    exchange_id,
+                    reply.exchange_dt,
+                    snapshot,
+                    rng,
+                );
+                return;
+            }
+            BasicExchangeToBrokerReply::TradeBusted(busted) => {
+                let reversed_direction = match busted.direction {
+                    Direction::Buy => Direction::Sell,
+                    Direction::Sell => Direction::Buy
+                };
+                let give_up = self.filled_orders.get(&busted.order_id)
+                    .and_then(|(trader_id, _)| self.give_ups.get(trader_id));
+                if let Some(config) = give_up {
+                    self.pending_give_ups.push(
+                        GiveUp {
+                            clearing_broker_id: config.clearing_broker_id,
+                            trader_id: config.trader_id,
+                            account: config.account,
+                            exchange_id,
+                            traded_pair: busted.traded_pair,
+                            direction: reversed_direction,
+                            price: busted.price,
+                            size: busted.size,
+                            executed_dt: reply.exchange_dt,
+                        }
+                    )
+                } else {
+                    if let Some(ledger) = &mut self.ledger {
+                        let (trader_id, account) = self.filled_orders.get(&busted.order_id)
+                            .unwrap_or_else(
+                                || panic!(
+                                    "Cannot find owner of the busted fill \
+                                    of the internal order id {}", busted.order_id
+                                )
+                            );
+                        ledger.unwind_fill(
+                            OrderFill {
+                                datetime: reply.exchange_dt,
+                                trader_id: *trader_id,
+                                account: *account,
+                                order_id: Some(busted.order_id),
+                                exchange_id,
+                                traded_pair: busted.traded_pair,
+                                direction: busted.direction,
+                                price: busted.price,
+                                size: busted.size,
+                            }
+                        )
+                    }
+                    if let Some(portfolios) = &mut self.portfolios {
+                        let (trader_id, _) = self.filled_orders.get(&busted.order_id)
+                            .unwrap_or_else(
+                                || panic!(
+                                    "Cannot find owner of the busted fill \
+                                    of the internal order id {}", busted.order_id
+                                )
+                            );
+                        portfolios.entry(*trader_id).or_default().book_fill(
+                            exchange_id,
+                            busted.traded_pair,
+                            reversed_direction,
+                            busted.price,
+                            busted.size,
+                        )
+                    }
+                }
+                // Buy-ins are reported to the traders by their internal IDs
+                let (trader_id, order_id) = self.internal_to_submitted.get(&busted.order_id)
+                    .copied()
+                    .or_else(
+                        || self.filled_orders.get(&busted.order_id)
+                            .map(|(trader_id, _)| (*trader_id, busted.order_id))
+                    )
+                    .unwrap_or_else(
+                        || panic!(
+                            "Cannot find a corresponding submitted order id \
+                            for the internal order id {}", busted.order_id
+                        )
+                    );
+                Self::create_broker_reply(
+                    trader_id,
+                    exchange_id,
+                    reply.exchange_dt,
+                    BasicBrokerReply::TradeBusted(TradeBusted { order_id, ..busted }),
+                )
+            }
+            BasicExchangeToBrokerReply::ExchangeEventNotification(notification) => {
+                match &notification {
+                    ExchangeEventNotification::TradeExecuted(trade)
+                    if self.kill_switch.is_some() => {
+                        let key = (exchange_id, trade.traded_pair);
+                        self.last_trade_prices.insert(key, trade.price);
+                        self.run_kill_switch(&mut message_receiver, &mut action_processor, rng)
+                    }
+                    ExchangeEventNotification::TradesStarted { traded_pair, .. } => {
+                        self.resume_gtd_orders(
+                            exchange_id,
+                            *traded_pair,
+                            &mut message_receiver,
+                            &mut action_processor,
+                            rng,
+                        )
+                    }
+                    // Positions are marked to the official closing prices once published
+                    ExchangeEventNotification::ClosingPrice { traded_pair, price } => {
+                        self.last_trade_prices.insert((exchange_id, *traded_pair), *price);
+                        self.run_kill_switch(&mut message_receiver, &mut action_processor, rng)
+                    }
+                    _ => {}
+                }
+                self.handle_exchange_notification(
+                    message_receiver,
+                    action_processor,
+                    notification,
+                    exchange_id,
+                    reply.exchange_dt,
+                    rng,
+                );
+                return;
+            }
+        };
+        self.push_order_flow_action(
+            &mut message_receiver,
+            &mut action_processor,
+            message,
+            Some(reply.exchange_dt),
+            rng,
+        );
+        self.send_give_ups(&mut message_receiver, &mut action_processor, rng);
+        if filled {
+            self.run_kill_switch(&mut message_receiver, &mut action_processor, rng)
+        }
+    }
+
+    fn process_replay_request<KerMsg: Ord>(
+        &mut self,
+        mut message_receiver: MessageReceiver<KerMsg>,
+        mut action_processor: impl LatentActionProcessor<Self::Action, Self::ExchangeID, KerMsg=KerMsg>,
+        request: Self::R2B,
+        rng: &mut impl Rng,
+    ) {
+        self.sync_state(&mut message_receiver, &mut action_processor, rng);
+        let exchange_id = request.exchange_id;
+        let actions = match request.content {
+            BasicReplayToBrokerRequest::RecallBorrow { traded_pair, deadline } => {
+                let mut shorts: BTreeMap<TraderID, Lots> = BTreeMap::new();
+                for (trader_id, _, size) in self.get_short_positions(exchange_id, traded_pair) {
+                    *shorts.entry(trader_id).or_insert(Lots(0)) += size
+                }
+                shorts.into_iter()
+                    .map(
+                        |(trader_id, size)| Self::create_broker_reply(
+                            trader_id,
+                            exchange_id,
+                            self.current_dt,
+                            BasicBrokerReply::BorrowRecalled(
+                                BorrowRecalled { traded_pair, size, deadline }
+                            ),
+                        )
+                    )
+                    .collect()
+            }
+            BasicReplayToBrokerRequest::BuyIn(traded_pair) => {
+                self.buy_in(exchange_id, traded_pair)
+            }
+        };
+        let mut latency_generator = self.get_latency_generator();
+        message_receiver.extend(
+            actions.into_iter().map(
+                |action| action_processor.process_action(action, &mut latency_generator, rng)
+            )
+        );
+        self.update_latency_generator(latency_generator)
+    }
+
+    fn process_broker_message<KerMsg: Ord>(
+        &mut self,
+        _: MessageReceiver<KerMsg>,
+        _: impl LatentActionProcessor<Self::Action, Self::ExchangeID, KerMsg=KerMsg>,
+        message: Self::B2B,
+        broker_id: BrokerID,
+        _: &mut impl Rng,
+    ) {
+        self.roll_accounts();
+        if let BasicBrokerToItself::GiveUp(give_up) = message {
+            self.book_give_up(give_up)
+        } else {
+            unreachable!(
+                "{} :: Broker {} got an unexpected message {message:?} from Broker {broker_id}",
+                self.current_dt, self.name
+            )
+        }
+    }
+
+    fn get_b2b_recipient(&self, message: &Self::B2B) -> BrokerID {
+        if let BasicBrokerToItself::GiveUp(give_up) = message {
+            give_up.clearing_broker_id
+        } else {
+            unreachable!("Broker {} does not send {message:?} to other brokers", self.name)
+        }
+    }
+
+    fn upon_connection_to_exchange(&mut self, exchange_id: ExchangeID) {
+        self.registered_exchanges.insert(exchange_id);
+    }
+
+    fn register_trader(
+        &mut self,
+        trader_id: TraderID,
+        sub_cfgs: impl IntoIterator<Item=SubscriptionConfig<ExchangeID, Symbol, Settlement>>,
+    ) {
+        self.trader_configs.insert(
+            trader_id,
+            sub_cfgs.into_iter()
+                .inspect(
+                    |SubscriptionConfig { exchange, traded_pair, subscription }| {
+                        if !self.registered_exchanges.contains(&exchange) {
+                            panic!("Broker {} is not connected to Exchange {exchange}", self.name)
+                        };
+                        let entitled = self.entitlements.get(&trader_id).is_none_or(
+                            |entitlements| entitlements.is_permitted(*exchange, *traded_pair)
+                        );
+                        if !entitled {
+                            panic!(
+                                "Trader {trader_id} is not entitled to subscribe to \
+                                {traded_pair:?} at Exchange {exchange}"
+                            )
+                        };
+                        self.traded_pairs_info
+                            .entry((*exchange, *traded_pair))
+                            .or_default()
+                            .push((trader_id, *subscription))
+                    }
+                )
+                .map(
+                    |SubscriptionConfig { exchange, traded_pair, subscription }|
+                        ((exchange, traded_pair), subscription)
+                ).collect(),
+        );
+    }
+}
+
+impl<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
+BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
+    where BrokerID: Id,
+          TraderID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    /// Creates a new instance of the `BasicBroker`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` — ID of the `BasicBroker`.
+    pub fn new(name: BrokerID) -> Self {
+        BasicBroker {
+            current_dt: Date::from_ymd(1970, 01, 01).and_hms(0, 0, 0),
+            name,
+            trader_configs: Default::default(),
+            traded_pairs_info: Default::default(),
+            pending_recoveries: Default::default(),
+            submitted_to_internal: Default::default(),
+            internal_to_submitted: Default::default(),
+            registered_exchanges: Default::default(),
+            next_internal_order_id: OrderID(0),
+            rfq_config: None,
+            last_trade_prices: Default::default(),
+            firm_quotes: Default::default(),
+            entitlements: Default::default(),
+            ledger: None,
+            order_accounts: Default::default(),
+            filled_orders: Default::default(),
+            give_ups: Default::default(),
+            pending_give_ups: vec![],
+            portfolios: None,
+            portfolio_snapshot_period: None,
+            last_portfolio_snapshot_dt: None,
+            conflation_window: None,
+            conflated_feeds: Default::default(),
+            num_conflated_snapshots: 0,
+            statements: None,
+            trade_log: None,
+            audit_trail: None,
+            session_outages: Default::default(),
+            session_down: false,
+            session_check_dt: None,
+            heartbeats: None,
+            heartbeat_check_dt: None,
+            open_limit_orders: Default::default(),
+            broker_cancels: Default::default(),
+            gtd_orders: Default::default(),
+            order_cache: None,
+            buy_ins: Default::default(),
+            kill_switch: None,
+            faults: None,
+            middleware: vec![],
+        }
+    }
+
+    /// Restricts the trader to the venues and the traded pairs it is entitled to.
+    /// Requests concerning other ones are rejected.
+    /// Should be called before the trader is registered at the `BasicBroker`.
+    ///
+    /// # Arguments
+    ///
+    /// * `trader_id` — Trader to restrict.
+    /// * `entitlements` — Trading permissions of the trader.
+    pub fn with_entitlements(
+        mut self,
+        trader_id: TraderID,
+        entitlements: Entitlements<ExchangeID, Symbol, Settlement>) -> Self
+    {
+        self.entitlements.insert(trader_id, entitlements);
+        self
+    }
+
+    /// Makes the `BasicBroker` book the fills of the traders to their accounts.
+    /// Fills of the orders tagged with the account are booked to it,
+    /// while the ones of the untagged orders are split according to the allocation rules.
+    ///
+    /// # Arguments
+    ///
+    /// * `ledger` — Ledger with the allocation rules of the traders.
+    pub fn with_account_ledger(
+        mut self,
+        ledger: AccountLedger<TraderID, ExchangeID, Symbol, Settlement>) -> Self
+    {
+        self.ledger = Some(ledger);
+        self
+    }
+
+    /// Returns the ledger of the trader accounts, if it is enabled.
+    pub fn get_account_ledger(
+        &self) -> Option<&AccountLedger<TraderID, ExchangeID, Symbol, Settlement>>
+    {
+        self.ledger.as_ref()
+    }
+
+    /// Makes the `BasicBroker` generate the daily statements of the traders.
+    /// The statement of the day is written once the first event of the next day is processed
+    /// or, for the last day, when the `BasicBroker` is dropped.
+    /// Enables the account ledger with the default allocation rules unless it is set
+    /// by the [`with_account_ledger`](Self::with_account_ledger).
+    ///
+    /// # Arguments
+    ///
+    /// * `statements` — Statement generator.
+    pub fn with_statements(mut self, statements: StatementGenerator<TraderID>) -> Self {
+        self.ledger.get_or_insert_with(AccountLedger::new);
+        self.statements = Some(statements);
+        self
+    }
+
+    /// Returns the statement generator, if it is enabled.
+    pub fn get_statements(&self) -> Option<&StatementGenerator<TraderID>> {
+        self.statements.as_ref()
+    }
+
+    /// Makes the `BasicBroker` log the fills and the limit orders of the traders
+    /// and the last trade and closing prices for the end-of-run statistics.
+    /// The log may be shared with the other brokers of the simulation thread.
+    /// Busted fills are not removed from the log.
+    ///
+    /// # Arguments
+    ///
+    /// * `trade_log` — Log to record to.
+    pub fn with_trade_log(
+        mut self,
+        trade_log: BrokerTradeLog<TraderID, ExchangeID, Symbol, Settlement>) -> Self
+    {
+        self.trade_log = Some(trade_log);
+        self
+    }
+
+    /// Makes the `BasicBroker` record the lifecycles of the trader orders,
+    /// i.e. the requests it receives and routes to the exchanges and the replies it sends.
+    /// The trail may be shared with the other brokers and the traders of the simulation thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `audit_trail` — Trail to record to.
+    pub fn with_audit_trail(mut self, audit_trail: SharedOrderAuditTrail<TraderID>) -> Self {
+        self.audit_trail = Some(audit_trail);
+        self
+    }
+
+    /// Schedules outages of the session with the exchanges.
+    /// The `BasicBroker` wakes itself up at the start and at the end of each outage,
+    /// beginning with the first event it processes, so outages ending before it are ignored.
+    /// At the start of the outage the resting limit orders are cancelled
+    /// and all traders are notified with the [`BasicBrokerReply::SessionDown`].
+    /// While the session is down, order placements and cancellations are rejected.
+    /// Once it is restored, the traders are notified with the [`BasicBrokerReply::SessionUp`].
+    ///
+    /// # Arguments
+    ///
+    /// * `outages` — Non-overlapping outage intervals, with inclusive starts and exclusive ends.
+    pub fn with_session_outages(
+        mut self,
+        outages: impl IntoIterator<Item=(DateTime, DateTime)>) -> Self
+    {
+        let mut outages: Vec<_> = self.session_outages.drain(..).chain(outages).collect();
+        outages.sort_unstable();
+        if let Some((start, end)) = outages.iter().find(|(start, end)| start >= end) {
+            panic!("Session outage should end after it starts. Got {start} .. {end}")
+        }
+        if let Some(pair) = outages.windows(2).find(|pair| pair[0].1 > pair[1].0) {
+            panic!("Session outages overlap: {:?} and {:?}", pair[0], pair[1])
+        }
+        self.session_outages = outages.into();
+        self
+    }
+
+    /// Returns whether the session with the exchanges is down.
+    pub fn is_session_down(&self) -> bool {
+        self.session_down
+    }
+
+    /// Makes the `BasicBroker` send the [`BasicBrokerRequest::Heartbeat`] to the exchanges
+    /// and notify the traders with the [`BasicBrokerReply::HeartbeatMissed`]
+    /// once the exchange does not echo them within the timeout.
+    /// Once the heartbeat is echoed again, the traders are notified
+    /// with the [`BasicBrokerReply::HeartbeatRestored`].
+    /// While the session with the exchanges is down, heartbeats are not sent.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` — Heartbeat interval and timeout.
+    pub fn with_heartbeats(mut self, config: HeartbeatConfig) -> Self {
+        self.heartbeats = Some(HeartbeatMonitor::new(config));
+        self
+    }
+
+    /// Returns the monitor of the heartbeats sent to the exchanges, if they are enabled.
+    pub fn get_heartbeats(&self) -> Option<&HeartbeatMonitor<ExchangeID>> {
+        self.heartbeats.as_ref()
+    }
+
+    fn update_session<KerMsg: Ord>(
+        &mut self,
+        message_receiver: &mut MessageReceiver<KerMsg>,
+        action_processor: &mut impl LatentActionProcessor<
+            <Self as Agent>::Action, ExchangeID, KerMsg=KerMsg
+        >,
+        rng: &mut impl Rng)
+    {
+        while matches!(self.session_outages.front(), Some((_, end)) if *end <= self.current_dt) {
+            self.session_outages.pop_front();
+        }
+        let session_down = matches!(
+            self.session_outages.front(), Some((start, _)) if *start <= self.current_dt
+        );
+        let mut actions = Vec::new();
+        let next_check_dt = self.session_outages.front().map(
+            |(start, end)| if session_down { *end } else { *start }
+        );
+        if let Some(check_dt) = next_check_dt.filter(|dt| Some(*dt) != self.session_check_dt) {
+            self.session_check_dt = Some(check_dt);
+            actions.push(self.schedule_wakeup(check_dt, BasicBrokerToItself::SessionCheck))
+        }
+        if session_down != self.session_down {
+            self.session_down = session_down;
+            self.cancel_on_disconnect(session_down, &mut actions)
+        }
+        let mut latency_generator = self.get_latency_generator();
+        message_receiver.extend(
+            actions.into_iter().map(
+                |action| action_processor.process_action(action, &mut latency_generator, rng)
+            )
+        );
+        self.update_latency_generator(latency_generator)
+    }
+
+    /// Cancels the resting limit orders if the session is down
+    /// and notifies the traders about the change of the session state.
+    fn cancel_on_disconnect(
+        &mut self,
+        session_down: bool,
+        actions: &mut Vec<<Self as Agent>::Action>)
+    {
+        if session_down {
+            // Cancel-on-disconnect. Sorted to keep the latency sampling reproducible
+            let mut open_orders: Vec<_> = self.open_limit_orders.iter().collect();
+            open_orders.sort_unstable();
+            for (order_id, (exchange_id, traded_pair)) in open_orders {
+                self.broker_cancels.insert(*order_id, CancellationReason::SessionDown);
+                actions.push(
+                    Self::create_broker_request(
+                        *exchange_id,
+                        BasicBrokerRequest::CancelLimitOrder(
+                            LimitOrderCancelRequest {
+                                traded_pair: *traded_pair,
+                                order_id: *order_id,
+                            }
+                        ),
+                    )
+                )
+            }
+        }
+        let mut traders: Vec<_> = self.trader_configs.keys().copied().collect();
+        traders.sort_unstable();
+        let mut exchanges: Vec<_> = self.registered_exchanges.iter().copied().collect();
+        exchanges.sort_unstable();
+        for trader_id in traders {
+            for exchange_id in &exchanges {
+                actions.push(
+                    Self::create_broker_reply(
+                        trader_id,
+                        *exchange_id,
+                        self.current_dt,
+                        if session_down {
+                            BasicBrokerReply::SessionDown
+                        } else {
+                            BasicBrokerReply::SessionUp
+                        },
+                    )
+                )
+            }
+        }
+    }
+
+    /// Creates the wakeup of the `BasicBroker` at the datetime.
+    ///
+    /// # Arguments
+    ///
+    /// * `wakeup_dt` — Datetime to wake up at. Should not precede the current datetime.
+    /// * `wakeup` — Wakeup to schedule.
+    fn schedule_wakeup(
+        &self,
+        wakeup_dt: DateTime,
+        wakeup: BasicBrokerToItself<BrokerID, TraderID, ExchangeID, Symbol, Settlement>,
+    ) -> <Self as Agent>::Action
+    {
+        BrokerAction {
+            delay: NanoDuration::from_duration(wakeup_dt - self.current_dt).unwrap_or_else(
+                || panic!("{} :: Cannot schedule the wakeup at {wakeup_dt}", self.current_dt)
+            ),
+            content: BrokerActionKind::BrokerToItself(wakeup),
+        }
+    }
+
+    fn send_give_ups<KerMsg: Ord>(
+        &mut self,
+        message_receiver: &mut MessageReceiver<KerMsg>,
+        action_processor: &mut impl LatentActionProcessor<
+            <Self as Agent>::Action, ExchangeID, KerMsg=KerMsg
+        >,
+        rng: &mut impl Rng)
+    {
+        if self.pending_give_ups.is_empty() {
+            return;
+        }
+        let mut latency_generator = self.get_latency_generator();
+        message_receiver.extend(
+            self.pending_give_ups.drain(..).map(
+                |give_up| action_processor.process_action(
+                    BrokerAction {
+                        delay: NanoDuration::ZERO,
+                        content: BrokerActionKind::BrokerToBroker(give_up.into()),
+                    },
+                    &mut latency_generator,
+                    rng,
+                )
+            )
+        );
+        self.update_latency_generator(latency_generator)
+    }
+
+    /// Books the fill given up by another broker to the trader.
+    fn book_give_up(&mut self, give_up: GiveUp<BrokerID, TraderID, ExchangeID, Symbol, Settlement>) {
+        let GiveUp {
+            trader_id, account, exchange_id, traded_pair, direction, price, size, executed_dt, ..
+        } = give_up;
+        if self.kill_switch.is_some() {
+            self.last_trade_prices.insert((exchange_id, traded_pair), price);
+        }
+        if let Some(trade_log) = &self.trade_log {
+            trade_log.borrow_mut().record_fill(
+                self.current_dt, trader_id, (exchange_id, traded_pair), direction, price, size,
+            )
+        }
+        if self.portfolios.is_some() {
+            self.mark_portfolios(exchange_id, traded_pair, price);
+        }
+        if let Some(portfolios) = &mut self.portfolios {
+            portfolios.entry(trader_id)
+                .or_default()
+                .book_fill(exchange_id, traded_pair, direction, price, size)
+        }
+        if let Some(ledger) = &mut self.ledger {
+            ledger.book_fill(
+                OrderFill {
+                    datetime: executed_dt,
+                    trader_id,
+                    account,
+                    order_id: None,
+                    exchange_id,
+                    traded_pair,
+                    direction,
+                    price,
+                    size,
+                }
+            )
+        }
+    }
+
+    fn update_heartbeats<KerMsg: Ord>(
+        &mut self,
+        message_receiver: &mut MessageReceiver<KerMsg>,
+        action_processor: &mut impl LatentActionProcessor<
+            <Self as Agent>::Action, ExchangeID, KerMsg=KerMsg
+        >,
+        rng: &mut impl Rng)
+    {
+        let heartbeats = if let Some(heartbeats) = &mut self.heartbeats {
+            heartbeats
+        } else {
+            return;
+        };
+        for exchange_id in &self.registered_exchanges {
+            heartbeats.add_peer(*exchange_id, self.current_dt)
+        }
+        let HeartbeatPoll { due, missed } = heartbeats.poll(self.current_dt);
+        // Missing exchanges are detected at the deadlines even if nothing else happens
+        let deadline = heartbeats.get_next_deadline().filter(
+            |deadline| self.heartbeat_check_dt.is_none_or(
+                |check_dt| check_dt <= self.current_dt || check_dt > *deadline
+            )
+        );
+        if let Some(deadline) = deadline {
+            self.heartbeat_check_dt = Some(deadline);
+            let action = self.schedule_wakeup(deadline, BasicBrokerToItself::HeartbeatCheck);
+            let mut latency_generator = self.get_latency_generator();
+            message_receiver.push(
+                action_processor.process_action(action, &mut latency_generator, rng)
+            );
+            self.update_latency_generator(latency_generator)
+        }
+        if !self.session_down {
+            let mut latency_generator = self.get_latency_generator();
+            message_receiver.extend(
+                due.into_iter().map(
+                    |(exchange_id, heartbeat, delay)| {
+                        let action = BrokerAction {
+                            delay,
+                            content: BrokerActionKind::BrokerToExchange(
+                                BasicBrokerToExchange {
+                                    exchange_id,
+                                    content: BasicBrokerRequest::Heartbeat(heartbeat),
+                                }
+                            ),
+                        };
+                        action_processor.process_action(action, &mut latency_generator, rng)
+                    }
+                )
+            );
+            self.update_latency_generator(latency_generator)
+        }
+        for exchange_id in missed {
+            self.notify_traders(
+                message_receiver,
+                action_processor,
+                exchange_id,
+                BasicBrokerReply::HeartbeatMissed,
+                rng,
+            )
+        }
+    }
+
+    /// Sends the reply concerning the exchange to all traders.
+    fn notify_traders<KerMsg: Ord>(
+        &mut self,
+        message_receiver: &mut MessageReceiver<KerMsg>,
+        action_processor: &mut impl LatentActionProcessor<
+            <Self as Agent>::Action, ExchangeID, KerMsg=KerMsg
+        >,
+        exchange_id: ExchangeID,
+        content: BasicBrokerReply<Symbol, Settlement>,
+        rng: &mut impl Rng)
+    {
+        let mut traders: Vec<_> = self.trader_configs.keys().copied().collect();
+        traders.sort_unstable();
+        let mut latency_generator = self.get_latency_generator();
+        message_receiver.extend(
+            traders.into_iter().map(
+                |trader_id| {
+                    let action = Self::create_broker_reply(
+                        trader_id,
+                        exchange_id,
+                        self.current_dt,
+                        content.clone(),
+                    );
+                    action_processor.process_action(action, &mut latency_generator, rng)
+                }
+            )
+        );
+        self.update_latency_generator(latency_generator)
+    }
+
+    fn is_dormant(&self, trader_id: TraderID, order_id: OrderID) -> bool {
+        self.submitted_to_internal.get(&(trader_id, order_id)).is_some_and(
+            |order_id| self.gtd_orders.get(order_id).is_some_and(|order| order.dormant)
+        )
+    }
+
+    fn is_carried_over(&self, order_id: OrderID, close_dt: DateTime) -> bool {
+        self.gtd_orders.get(&order_id).is_some_and(
+            |order| order.request.time_in_force.get_expiration().is_none_or(
+                |expiration| expiration > close_dt
+            )
+        )
+    }
+
+    /// Cancels the dormant good-till-date orders that have expired by the current datetime.
+    /// Resting orders are cancelled by the exchange itself once they expire.
+    fn expire_gtd_orders<KerMsg: Ord>(
+        &mut self,
+        message_receiver: &mut MessageReceiver<KerMsg>,
+        action_processor: &mut impl LatentActionProcessor<
+            <Self as Agent>::Action, ExchangeID, KerMsg=KerMsg
+        >,
+        rng: &mut impl Rng)
+    {
+        let current_dt = self.current_dt;
+        let mut expired: Vec<_> = self.gtd_orders.iter()
+            .filter(
+                |(_, order)| order.dormant && order.request.time_in_force.get_expiration()
+                    .is_some_and(|expiration| expiration <= current_dt)
+            )
+            .map(|(order_id, order)| (*order_id, *order))
+            .collect();
+        if expired.is_empty() {
+            return;
+        }
+        // Sorted to keep the latency sampling reproducible
+        expired.sort_unstable_by_key(|(order_id, _)| *order_id);
+        let mut actions = Vec::with_capacity(expired.len());
+        for (order_id, GtdOrder { exchange_id, request, .. }) in expired {
+            self.gtd_orders.remove(&order_id);
+            self.order_accounts.remove(&order_id);
+            let (trader_id, submitted_id) = self.internal_to_submitted[&order_id];
+            actions.push(
+                Self::create_broker_reply(
+                    trader_id,
+                    exchange_id,
+                    current_dt,
+                    BasicBrokerReply::OrderCancelled(
+                        OrderCancelled {
+                            traded_pair: request.traded_pair,
+                            order_id: submitted_id,
+                            reason: CancellationReason::Expired,
+                        }
+                    ),
+                )
+            )
+        }
+        let mut latency_generator = self.get_latency_generator();
+        message_receiver.extend(
+            actions.into_iter().map(
+                |action| action_processor.process_action(action, &mut latency_generator, rng)
+            )
+        );
+        self.update_latency_generator(latency_generator)
+    }
+
+    /// Resubmits the dormant orders once trades start at the exchange.
+    /// While the session is down, the orders stay dormant.
+    fn resume_gtd_orders<KerMsg: Ord>(
+        &mut self,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        message_receiver: &mut MessageReceiver<KerMsg>,
+        action_processor: &mut impl LatentActionProcessor<
+            <Self as Agent>::Action, ExchangeID, KerMsg=KerMsg
+        >,
+        rng: &mut impl Rng)
+    {
+        if self.session_down {
+            return;
+        }
+        let mut resumed: Vec<_> = self.gtd_orders.iter_mut()
+            .filter(
+                |(_, order)| order.dormant
+                    && order.exchange_id == exchange_id
+                    && order.request.traded_pair == traded_pair
+            )
+            .map(
+                |(order_id, order)| {
+                    order.dormant = false;
+                    (*order_id, order.request)
+                }
+            )
+            .collect();
+        resumed.sort_unstable_by_key(|(order_id, _)| *order_id);
+        let mut actions = Vec::with_capacity(resumed.len());
+        for (order_id, request) in resumed {
+            self.open_limit_orders.insert(order_id, (exchange_id, traded_pair));
+            actions.push(
+                Self::create_broker_request(
+                    exchange_id,
+                    BasicBrokerRequest::PlaceLimitOrder(request),
+                )
+            )
+        }
+        let mut latency_generator = self.get_latency_generator();
+        message_receiver.extend(
+            actions.into_iter().map(
+                |action| action_processor.process_action(action, &mut latency_generator, rng)
+            )
+        );
+        self.update_latency_generator(latency_generator)
+    }
+
+    /// Makes the `BasicBroker` answer the order status requests of the traders
+    /// from its own cache instead of forwarding them to the exchanges.
+    /// The cache is updated by the exchange replies, so the answers lag behind
+    /// the exchange state by the latency, e.g. the orders in flight are reported as resting.
+    pub fn with_order_status_cache(mut self) -> Self {
+        self.order_cache = Some(Default::default());
+        self
+    }
+
+    /// Returns the state of the order as known to the `BasicBroker`.
+    /// Orders outliving the sessions are resting until they expire, even if carried over.
+    fn get_cached_order_state(&self, internal_order_id: OrderID) -> OrderState {
+        if let Some(order) = self.gtd_orders.get(&internal_order_id) {
+            return OrderState::Resting {
+                direction: order.request.direction,
+                price: order.request.price,
+                remaining_size: order.request.size,
+            };
+        }
+        self.order_cache.as_ref()
+            .and_then(|order_cache| order_cache.get(&internal_order_id))
+            .map_or(
+                OrderState::Closed,
+                |(direction, price, size)| OrderState::Resting {
+                    direction: *direction,
+                    price: *price,
+                    remaining_size: *size,
+                },
+            )
+    }
+
+    fn update_order_cache(&mut self, content: &BasicExchangeToBrokerReply<Symbol, Settlement>) {
+        let order_cache = if let Some(order_cache) = &mut self.order_cache {
+            order_cache
+        } else {
+            return;
+        };
+        match content {
+            BasicExchangeToBrokerReply::OrderPriceAdjusted(adjusted) => {
+                if let Some((_, price, _)) = order_cache.get_mut(&adjusted.order_id) {
+                    *price = adjusted.price
+                }
+            }
+            BasicExchangeToBrokerReply::OrderPartiallyExecuted(executed)
+            | BasicExchangeToBrokerReply::ShadowOrderPartiallyExecuted(executed)
+            | BasicExchangeToBrokerReply::GhostOrderPartiallyExecuted(executed) => {
+                if let Some((_, _, size)) = order_cache.get_mut(&executed.order_id) {
+                    *size -= executed.size
+                }
+            }
+            BasicExchangeToBrokerReply::OrderSizeReduced(reduced) => {
+                if let Some((_, _, size)) = order_cache.get_mut(&reduced.order_id) {
+                    *size = reduced.remaining_size
+                }
+            }
+            BasicExchangeToBrokerReply::OrderPlacementDiscarded(discarded) => {
+                order_cache.remove(&discarded.order_id);
+            }
+            BasicExchangeToBrokerReply::OrderExecuted(executed)
+            | BasicExchangeToBrokerReply::ShadowOrderExecuted(executed)
+            | BasicExchangeToBrokerReply::GhostOrderExecuted(executed) => {
+                order_cache.remove(&executed.order_id);
+            }
+            BasicExchangeToBrokerReply::OrderCancelled(cancelled) => {
+                order_cache.remove(&cancelled.order_id);
+            }
+            _ => {}
+        }
+    }
+
+    /// Makes the `BasicBroker` monitor the risk metrics of the traders
+    /// with the [`KillSwitch`]. P&L is marked to the last trade prices.
+    /// Once the kill switch trips for the trader, its resting limit orders are cancelled
+    /// and its further order placements and quote acceptances are rejected.
+    /// Enables the account ledger with the default allocation rules unless it is set
+    /// by the [`with_account_ledger`](Self::with_account_ledger).
+    ///
+    /// # Arguments
+    ///
+    /// * `kill_switch` — Risk monitor.
+    pub fn with_kill_switch(mut self, kill_switch: KillSwitch<TraderID>) -> Self {
+        self.ledger.get_or_insert_with(AccountLedger::new);
+        self.kill_switch = Some(kill_switch);
+        self
+    }
+
+    /// Returns the kill switch, if it is enabled.
+    pub fn get_kill_switch(&self) -> Option<&KillSwitch<TraderID>> {
+        self.kill_switch.as_ref()
+    }
+
+    /// Makes the `BasicBroker` simulate the operational errors of the order flow:
+    /// lost requests to the exchanges as well as lost, duplicate and delayed replies
+    /// to the traders. Allows validating the reconciliation logic of the traders.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` — Probabilities of the errors.
+    pub fn with_fault_injection(mut self, config: FaultConfig) -> Self {
+        self.faults = Some(FaultInjector::new(config));
+        self
+    }
+
+    /// Returns the numbers of the injected operational errors, if the injection is enabled.
+    pub fn get_injected_faults(&self) -> Option<InjectedFaults> {
+        self.faults.as_ref().map(FaultInjector::get_injected_faults)
+    }
+
+    /// Pushes the action of the order flow, injecting the operational errors if enabled.
+    fn push_order_flow_action<KerMsg: Ord>(
+        &mut self,
+        message_receiver: &mut MessageReceiver<KerMsg>,
+        action_processor: &mut impl LatentActionProcessor<
+            <Self as Agent>::Action, ExchangeID, KerMsg=KerMsg
+        >,
+        mut action: <Self as Agent>::Action,
+        exchange_dt: Option<DateTime>,
+        rng: &mut impl Rng)
+    {
+        self.audit_action(&action, exchange_dt);
+        let duplicate = match (&mut self.faults, &action.content) {
+            (Some(faults), BrokerActionKind::BrokerToExchange(_)) => {
+                if faults.is_request_lost(rng) {
+                    return;
+                }
+                None
+            }
+            (Some(faults), BrokerActionKind::BrokerToTrader(reply)) => {
+                match faults.get_reply_fault(rng) {
+                    ReplyFault::None => None,
+                    ReplyFault::Lost => return,
+                    ReplyFault::Duplicated => Some(reply.clone()),
+                    ReplyFault::Delayed(delay) => {
+                        action.delay += delay;
+                        None
+                    }
+                }
+            }
+            _ => None
+        };
+        let mut latency_generator = self.get_latency_generator();
+        message_receiver.push(
+            action_processor.process_action(action, &mut latency_generator, rng)
+        );
+        if let Some(reply) = duplicate {
+            let action = BrokerAction {
+                delay: NanoDuration::ZERO,
+                content: BrokerActionKind::BrokerToTrader(reply),
+            };
+            message_receiver.push(
+                action_processor.process_action(action, &mut latency_generator, rng)
+            )
+        }
+        self.update_latency_generator(latency_generator)
+    }
+
+    /// Records the request routed to the exchange or the reply sent to the trader
+    /// if the audit trail is enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `action` — Action to record.
+    /// * `exchange_dt` — Datetime of the exchange reply the action is caused by, if any.
+    fn audit_action(&self, action: &<Self as Agent>::Action, exchange_dt: Option<DateTime>) {
+        let audit_trail = if let Some(audit_trail) = &self.audit_trail {
+            audit_trail
+        } else {
+            return;
+        };
+        let record = match &action.content {
+            BrokerActionKind::BrokerToExchange(request) => {
+                describe_broker_request(&request.content).and_then(
+                    |(internal_order_id, record)| {
+                        let (trader_id, order_id) = self.internal_to_submitted
+                            .get(&internal_order_id)?;
+                        Some((*trader_id, *order_id, record))
+                    }
+                )
+            }
+            BrokerActionKind::BrokerToTrader(reply) => {
+                describe_reply(AuditStage::Replied, &reply.content)
+                    .map(|(order_id, record)| (reply.trader_id, order_id, record))
+            }
+            _ => None
+        };
+        if let Some((trader_id, order_id, record)) = record {
+            audit_trail.borrow_mut().record(
+                trader_id,
+                order_id,
+                AuditRecord { broker_dt: Some(self.current_dt), exchange_dt, ..record },
+            )
+        }
+    }
+
+    /// Adds the user-defined hook observing the incoming messages before they are processed.
+    /// Hooks are called in the order they are added.
+    ///
+    /// # Arguments
+    ///
+    /// * `middleware` — Hook to add.
+    pub fn with_middleware<M>(mut self, middleware: M) -> Self
+        where M: BrokerMiddleware<BrokerID, TraderID, ExchangeID, Symbol, Settlement> + 'static
+    {
+        self.middleware.push(Box::new(middleware));
+        self
+    }
+
+    fn apply_trader_request_middleware(
+        &mut self,
+        trader_id: TraderID,
+        request: &mut BasicTraderToBroker<BrokerID, ExchangeID, Symbol, Settlement>,
+    ) -> Verdict<BasicBrokerReply<Symbol, Settlement>>
+    {
+        for middleware in &mut self.middleware {
+            match middleware.on_trader_request(self.current_dt, trader_id, request) {
+                Verdict::Pass => {}
+                verdict => return verdict
+            }
+        }
+        Verdict::Pass
+    }
+
+    /// Checks whether the trader may place the order and, if so,
+    /// maps its submitted ID to the new internal one and returns the latter.
+    ///
+    /// # Parameters
+    ///
+    /// * `RESTING` — Whether the order may rest at the exchange, i.e. is not a market one.
+    ///   Such orders are cancelled on disconnect and by the kill switch.
+    fn register_order<const RESTING: bool>(
+        &mut self,
+        trader_id: TraderID,
+        account: Option<AccountID>,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        order_id: OrderID,
+        direction: Direction) -> Result<OrderID, PlacementDiscardingReason>
+    {
+        if !self.is_entitled(trader_id, exchange_id, traded_pair) {
+            return Err(PlacementDiscardingReason::TraderNotEntitled);
+        }
+        if self.is_blocked(trader_id) {
+            return Err(PlacementDiscardingReason::KillSwitchTripped);
+        }
+        if self.session_down {
+            return Err(PlacementDiscardingReason::BrokerSessionDown);
+        }
+        if !self.registered_exchanges.contains(&exchange_id) {
+            return Err(PlacementDiscardingReason::BrokerNotConnectedToExchange);
+        }
+        let internal_order_id = self.next_internal_order_id;
+        self.next_internal_order_id += OrderID(1);
+        self.internal_to_submitted.insert(internal_order_id, (trader_id, order_id));
+        self.submitted_to_internal.insert((trader_id, order_id), internal_order_id);
+        if self.is_booking_fills() {
+            self.order_accounts.insert(internal_order_id, (account, direction));
+        }
+        if RESTING {
+            self.open_limit_orders.insert(internal_order_id, (exchange_id, traded_pair));
+        }
+        Ok(internal_order_id)
+    }
+
+    /// Makes the trader wait for the recovery snapshot of the traded pair
+    /// before it is subscribed to the events.
+    fn await_recovery(
+        &mut self,
+        trader_id: TraderID,
+        exchange_id: ExchangeID,
+        request: SubscriptionRequest<Symbol, Settlement>) -> Result<(), SubscriptionRejectionReason>
+    {
+        if !self.trader_configs.contains_key(&trader_id) {
+            return Err(SubscriptionRejectionReason::TraderNotRegistered);
+        }
+        if !self.registered_exchanges.contains(&exchange_id) {
+            return Err(SubscriptionRejectionReason::BrokerNotConnectedToExchange);
+        }
+        if !self.is_entitled(trader_id, exchange_id, request.traded_pair) {
+            return Err(SubscriptionRejectionReason::TraderNotEntitled);
+        }
+        if self.session_down {
+            return Err(SubscriptionRejectionReason::BrokerSessionDown);
+        }
+        self.pending_recoveries
+            .entry((exchange_id, request.traded_pair))
+            .or_default()
+            .push((trader_id, request.subscription));
+        Ok(())
+    }
+
+    /// Subscribes the traders waiting for the recovery snapshot and sends it to them,
+    /// so that they receive only the events following the snapshot.
+    fn complete_recoveries<KerMsg: Ord>(
+        &mut self,
+        message_receiver: &mut MessageReceiver<KerMsg>,
+        action_processor: &mut impl LatentActionProcessor<
+            <Self as Agent>::Action, ExchangeID, KerMsg=KerMsg
+        >,
+        exchange_id: ExchangeID,
+        exchange_dt: DateTime,
+        snapshot: Rc<RecoverySnapshot<Symbol, Settlement>>,
+        rng: &mut impl Rng)
+    {
+        let key = (exchange_id, snapshot.traded_pair);
+        // Snapshot requested by several traders is sent to all of them at once
+        let waiting = if let Some(waiting) = self.pending_recoveries.remove(&key) {
+            waiting
+        } else {
+            return;
+        };
+        let mut latency_generator = self.get_latency_generator();
+        for (trader_id, subscription) in waiting {
+            *self.trader_configs.entry(trader_id).or_default()
+                .entry(key)
+                .or_insert(SubscriptionList::empty()) |= subscription;
+            let subscribers = self.traded_pairs_info.entry(key).or_default();
+            let subscribed = subscribers.iter_mut()
+                .find(|(subscriber, _)| *subscriber == trader_id);
+            if let Some((_, subscribed)) = subscribed {
+                *subscribed |= subscription
+            } else {
+                subscribers.push((trader_id, subscription))
+            }
+            let action = Self::create_broker_reply(
+                trader_id,
+                exchange_id,
+                exchange_dt,
+                BasicBrokerReply::RecoverySnapshot(Rc::clone(&snapshot)),
+            );
+            message_receiver.push(
+                action_processor.process_action(action, &mut latency_generator, rng)
+            )
+        }
+        self.update_latency_generator(latency_generator)
+    }
+
+    fn is_blocked(&self, trader_id: TraderID) -> bool {
+        self.kill_switch.as_ref().is_some_and(|kill_switch| kill_switch.is_tripped(trader_id))
+    }
+
+    fn run_kill_switch<KerMsg: Ord>(
+        &mut self,
+        message_receiver: &mut MessageReceiver<KerMsg>,
+        action_processor: &mut impl LatentActionProcessor<
+            <Self as Agent>::Action, ExchangeID, KerMsg=KerMsg
+        >,
+        rng: &mut impl Rng)
+    {
+        let (kill_switch, ledger) = if let (Some(kill_switch), Some(ledger)) = (
+            &mut self.kill_switch, &self.ledger
+        ) {
+            (kill_switch, ledger)
+        } else {
+            return;
+        };
+        let mut pnls: BTreeMap<TraderID, f64> = BTreeMap::new();
+        let mut positions: HashMap<_, Lots> = HashMap::default();
+        for ((trader_id, _, exchange_id, traded_pair), position) in ledger.iter_positions() {
+            if kill_switch.is_tripped(trader_id) {
+                continue;
+            }
+            let key = (exchange_id, traded_pair);
+            let mark = self.last_trade_prices.get(&key).map_or(0, |price| price.0);
+            let pnl = position.cash_flow + position.size.0 * mark;
+            *pnls.entry(trader_id).or_default() += pnl as f64;
+            *positions.entry((trader_id, key)).or_insert(Lots(0)) += position.size;
+        }
+        let mut max_positions: HashMap<TraderID, Lots> = HashMap::default();
+        for ((trader_id, _), size) in positions {
+            let max_position = max_positions.entry(trader_id).or_insert(Lots(0));
+            *max_position = (*max_position).max(Lots(size.0.abs()))
+        }
+        let tripped: Vec<_> = pnls.into_iter()
+            .filter_map(
+                |(trader_id, pnl)| {
+                    let max_position = max_positions[&trader_id];
+                    kill_switch.check(self.current_dt, trader_id, pnl, max_position)?;
+                    Some(trader_id)
+                }
+            )
+            .collect();
+        if tripped.is_empty() {
+            return;
+        }
+        let mut open_orders: Vec<_> = self.open_limit_orders.iter()
+            .filter(
+                |(order_id, _)| self.internal_to_submitted.get(order_id).is_some_and(
+                    |(trader_id, _)| tripped.contains(trader_id)
+                )
+            )
+            .collect();
+        open_orders.sort_unstable();
+        let mut actions = Vec::with_capacity(open_orders.len());
+        for (order_id, (exchange_id, traded_pair)) in open_orders {
+            self.broker_cancels.insert(*order_id, CancellationReason::KillSwitchTripped);
+            actions.push(
+                Self::create_broker_request(
+                    *exchange_id,
+                    BasicBrokerRequest::CancelLimitOrder(
+                        LimitOrderCancelRequest {
+                            traded_pair: *traded_pair,
+                            order_id: *order_id,
+                        }
+                    ),
+                )
+            )
+        }
+        let mut latency_generator = self.get_latency_generator();
+        message_receiver.extend(
+            actions.into_iter().map(
+                |action| action_processor.process_action(action, &mut latency_generator, rng)
+            )
+        );
+        self.update_latency_generator(latency_generator)
+    }
+
+    /// Returns the net short positions in the traded pair, sorted by the trader and the account.
+    fn get_short_positions(
+        &self,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>) -> Vec<(TraderID, AccountID, Lots)>
+    {
+        let ledger = self.ledger.as_ref().unwrap_or_else(
+            || panic!("Broker {} cannot recall borrows without the account ledger", self.name)
+        );
+        let mut shorts: Vec<_> = ledger.iter_positions()
+            .filter_map(
+                |((trader_id, account, pos_exchange_id, pos_traded_pair), position)| {
+                    if pos_exchange_id == exchange_id
+                        && pos_traded_pair == traded_pair
+                        && position.size < Lots(0) {
+                        Some((trader_id, account, Lots(-position.size.0)))
+                    } else {
+                        None
+                    }
+                }
+            )
+            .collect();
+        shorts.sort_unstable();
+        shorts
+    }
+
+    /// Places the forced market orders buying to cover the short positions in the traded pair.
+    /// If the exchange cannot be reached, the buy-ins are reported as not executed at all.
+    fn buy_in(
+        &mut self,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>) -> Vec<<Self as Agent>::Action>
+    {
+        let shorts = self.get_short_positions(exchange_id, traded_pair);
+        let reachable = !self.session_down && self.registered_exchanges.contains(&exchange_id);
+        let mut actions = Vec::with_capacity(2 * shorts.len());
+        for (trader_id, account, size) in shorts {
+            let order_id = self.next_internal_order_id;
+            self.next_internal_order_id += OrderID(1);
+            actions.push(
+                Self::create_broker_reply(
+                    trader_id,
+                    exchange_id,
+                    self.current_dt,
+                    BasicBrokerReply::BuyInPlaced(
+                        BuyIn { traded_pair, order_id, account, size }
+                    ),
+                )
+            );
+            if !reachable {
+                actions.push(
+                    Self::create_broker_reply(
+                        trader_id,
+                        exchange_id,
+                        self.current_dt,
+                        BasicBrokerReply::BuyInNotFullyExecuted(
+                            MarketOrderNotFullyExecuted {
+                                traded_pair,
+                                order_id,
+                                remaining_size: size,
+                            }
+                        ),
+                    )
+                );
+                continue;
+            }
+            self.buy_ins.insert(order_id, (trader_id, size));
+            self.order_accounts.insert(order_id, (Some(account), Direction::Buy));
+            actions.push(
+                Self::create_broker_request(
+                    exchange_id,
+                    BasicBrokerRequest::PlaceMarketOrder(
+                        MarketOrderPlacingRequest {
+                            traded_pair,
+                            order_id,
+                            direction: Direction::Buy,
+                            size,
+                            dummy: false,
+                        }
+                    ),
+                )
+            )
+        }
+        actions
+    }
+
+    /// Processes the exchange reply concerning the forced buy-to-cover order.
+    /// Returns `false` if the reply does not concern any.
+    fn process_buy_in_reply<KerMsg: Ord>(
+        &mut self,
+        message_receiver: &mut MessageReceiver<KerMsg>,
+        action_processor: &mut impl LatentActionProcessor<
+            <Self as Agent>::Action, ExchangeID, KerMsg=KerMsg
+        >,
+        reply: &BasicExchangeToBroker<BrokerID, Symbol, Settlement>,
+        exchange_id: ExchangeID,
+        rng: &mut impl Rng) -> bool
+    {
+        let order_id = match &reply.content {
+            BasicExchangeToBrokerReply::OrderAccepted(accepted) => accepted.order_id,
+            BasicExchangeToBrokerReply::OrderPlacementDiscarded(discarded) => discarded.order_id,
+            BasicExchangeToBrokerReply::OrderPartiallyExecuted(executed) => executed.order_id,
+            BasicExchangeToBrokerReply::OrderExecuted(executed) => executed.order_id,
+            BasicExchangeToBrokerReply::MarketOrderNotFullyExecuted(not_fully_exec) => {
+                not_fully_exec.order_id
+            }
+            _ => return false
+        };
+        let (trader_id, size) = if let Some(buy_in) = self.buy_ins.get(&order_id) {
+            *buy_in
+        } else {
+            return false;
+        };
+        let content = match reply.content {
+            BasicExchangeToBrokerReply::OrderAccepted(_) => return true,
+            BasicExchangeToBrokerReply::OrderPlacementDiscarded(discarded) => {
+                self.buy_ins.remove(&order_id);
+                self.order_accounts.remove(&order_id);
+                BasicBrokerReply::BuyInNotFullyExecuted(
+                    MarketOrderNotFullyExecuted {
+                        traded_pair: discarded.traded_pair,
+                        order_id,
+                        remaining_size: size,
+                    }
+                )
+            }
+            BasicExchangeToBrokerReply::OrderPartiallyExecuted(executed) => {
+                self.book_order_fill(
+                    exchange_id,
+                    reply.exchange_dt,
+                    order_id,
+                    executed.traded_pair,
+                    executed.price,
+                    executed.size,
+                    &[],
+                );
+                BasicBrokerReply::BuyInExecuted(executed)
+            }
+            BasicExchangeToBrokerReply::OrderExecuted(executed) => {
+                self.book_order_fill(
+                    exchange_id,
+                    reply.exchange_dt,
+                    order_id,
+                    executed.traded_pair,
+                    executed.price,
+                    executed.size,
+                    &[],
+                );
+                self.buy_ins.remove(&order_id);
+                self.order_accounts.remove(&order_id);
+                BasicBrokerReply::BuyInExecuted(
+                    OrderPartiallyExecuted {
+                        traded_pair: executed.traded_pair,
+                        order_id,
+                        broker_order_id: executed.broker_order_id,
+                        exchange_order_id: executed.exchange_order_id,
+                        exec_id: executed.exec_id,
+                        trade_id: executed.trade_id,
+                        price: executed.price,
+                        size: executed.size,
+                        fee: executed.fee,
+                    }
+                )
+            }
+            BasicExchangeToBrokerReply::MarketOrderNotFullyExecuted(not_fully_exec) => {
+                self.buy_ins.remove(&order_id);
+                self.order_accounts.remove(&order_id);
+                BasicBrokerReply::BuyInNotFullyExecuted(not_fully_exec)
+            }
+            _ => unreachable!("{} :: Unexpected buy-in reply {reply:?}", self.current_dt)
+        };
+        let filled = matches!(content, BasicBrokerReply::BuyInExecuted(_));
+        let action = Self::create_broker_reply(trader_id, exchange_id, reply.exchange_dt, content);
+        let mut latency_generator = self.get_latency_generator();
+        message_receiver.push(
+            action_processor.process_action(action, &mut latency_generator, rng)
+        );
+        self.update_latency_generator(latency_generator);
+        if filled {
+            self.run_kill_switch(message_receiver, action_processor, rng)
+        }
+        true
+    }
+
+    /// Brings the state of the `BasicBroker` up to the current datetime
+    /// before it processes the next message.
+    fn sync_state<KerMsg: Ord>(
+        &mut self,
+        message_receiver: &mut MessageReceiver<KerMsg>,
+        action_processor: &mut impl LatentActionProcessor<
+            <Self as Agent>::Action, ExchangeID, KerMsg=KerMsg
+        >,
+        rng: &mut impl Rng)
+    {
+        self.roll_accounts();
+        self.update_session(message_receiver, action_processor, rng);
+        self.update_heartbeats(message_receiver, action_processor, rng);
+        self.update_portfolio_snapshots(message_receiver, action_processor, rng);
+        self.expire_gtd_orders(message_receiver, action_processor, rng)
+    }
+
+    fn roll_accounts(&mut self) {
+        if let Some(ledger) = &mut self.ledger {
+            ledger.accrue_interest(self.current_dt)
+        }
+        if let (Some(statements), Some(ledger)) = (&mut self.statements, &self.ledger) {
+            statements.roll(self.current_dt, ledger, self.trader_configs.keys().copied())
+        }
+    }
+
+    /// Checks whether the fills of the orders are booked anywhere,
+    /// so that the accounts and the directions of the orders should be kept.
+    fn is_booking_fills(&self) -> bool {
+        self.ledger.is_some()
+            || self.portfolios.is_some()
+            || self.trade_log.is_some()
+            || !self.give_ups.is_empty()
+    }
+
+    /// Books the fill of the order or, if the order is a spread, the fills of its `legs`.
+    #[allow(clippy::too_many_arguments)]
+    fn book_order_fill(
+        &mut self,
+        exchange_id: ExchangeID,
+        exchange_dt: DateTime,
+        internal_order_id: OrderID,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        price: Tick,
+        size: Lots,
+        legs: &[LegExecuted<Symbol, Settlement>])
+    {
+        if self.kill_switch.is_some() {
+            self.last_trade_prices.insert((exchange_id, traded_pair), price);
+        }
+        if !self.is_booking_fills() {
+            return;
+        }
+        let (account, direction) = *self.order_accounts.get(&internal_order_id).unwrap_or_else(
+            || panic!("Cannot find account of the internal order id {internal_order_id}")
+        );
+        let (trader_id, order_id) = self.internal_to_submitted.get(&internal_order_id)
+            .map(|(trader_id, order_id)| (*trader_id, Some(*order_id)))
+            .or_else(
+                || self.buy_ins.get(&internal_order_id).map(|(trader_id, _)| (*trader_id, None))
+            )
+            .unwrap_or_else(
+                || panic!(
+                    "Cannot find a corresponding submitted order id \
+                    for the internal order id {internal_order_id}"
+                )
+            );
+        self.filled_orders.insert(internal_order_id, (trader_id, account));
+        let fill = [LegExecuted { traded_pair, direction, price, size }];
+        let fills = if legs.is_empty() { &fill[..] } else { legs };
+        self.book_fills(
+            exchange_id, exchange_dt, trader_id, account, order_id, Some(internal_order_id), fills,
+        )
+    }
+
+    /// Books the fills of the trader to the trade log, the portfolios and the ledger
+    /// or gives them up to the clearing broker.
+    ///
+    /// # Arguments
+    ///
+    /// * `order_id` — Submitted ID of the filled order, if it is the trader order.
+    /// * `internal_order_id` — Internal ID of the filled order, if any.
+    #[allow(clippy::too_many_arguments)]
+    fn book_fills(
+        &mut self,
+        exchange_id: ExchangeID,
+        exchange_dt: DateTime,
+        trader_id: TraderID,
+        account: Option<AccountID>,
+        order_id: Option<OrderID>,
+        internal_order_id: Option<OrderID>,
+        fills: &[LegExecuted<Symbol, Settlement>])
+    {
+        for &LegExecuted { traded_pair, direction, price, size } in fills {
+            if let Some(trade_log) = &self.trade_log {
+                let mut trade_log = trade_log.borrow_mut();
+                let instrument = (exchange_id, traded_pair);
+                if let Some(order_id) = order_id {
+                    trade_log.record_order_fill(
+                        self.current_dt, trader_id, instrument, order_id, direction, price, size,
+                    )
+                } else {
+                    trade_log.record_fill(
+                        self.current_dt, trader_id, instrument, direction, price, size,
+                    )
+                }
+            }
+            if self.portfolios.is_some() {
+                self.mark_portfolios(exchange_id, traded_pair, price);
+            }
+            if let Some(config) = self.give_ups.get(&trader_id) {
+                self.pending_give_ups.push(
+                    GiveUp {
+                        clearing_broker_id: config.clearing_broker_id,
+                        trader_id: config.trader_id,
+                        account: config.account,
+                        exchange_id,
+                        traded_pair,
+                        direction,
+                        price,
+                        size,
+                        executed_dt: exchange_dt,
+                    }
+                );
+                continue;
+            }
+            if let Some(portfolios) = &mut self.portfolios {
+                portfolios.entry(trader_id)
+                    .or_default()
+                    .book_fill(exchange_id, traded_pair, direction, price, size)
+            }
+            if let Some(ledger) = &mut self.ledger {
+                ledger.book_fill(
+                    OrderFill {
+                        datetime: exchange_dt,
+                        trader_id,
+                        account,
+                        order_id: internal_order_id,
+                        exchange_id,
+                        traded_pair,
+                        direction,
+                        price,
+                        size,
+                    }
+                )
+            }
+        }
+    }
+
+    /// Makes the `BasicBroker` track the positions, the average entry prices
+    /// and the realized and unrealized profits of the traders from the fills of their orders.
+    pub fn with_portfolio_tracking(mut self) -> Self {
+        self.portfolios = Some(Default::default());
+        self
+    }
+
+    /// Makes the `BasicBroker` give the fills of the trader up to the clearing broker,
+    /// which books them to its own ledger and portfolios.
+    /// The given-up fills are not booked to the ledger and the portfolios of the `BasicBroker`,
+    /// and the busted ones are given up once more in the reversed direction.
+    ///
+    /// # Arguments
+    ///
+    /// * `trader_id` — Trader whose fills are given up.
+    /// * `config` — Clearing broker to give the fills up to.
+    pub fn with_give_up(
+        mut self,
+        trader_id: TraderID,
+        config: GiveUpConfig<BrokerID, TraderID>) -> Self
+    {
+        self.give_ups.insert(trader_id, config);
+        self
+    }
+
+    /// Makes the `BasicBroker` track the portfolios of the traders
+    /// and send them the [`PortfolioSnapshots`](portfolio::PortfolioSnapshot)
+    /// of every exchange periodically.
+    /// The first snapshots are sent with the first event the `BasicBroker` processes,
+    /// then the `BasicBroker` wakes itself up each time the `period` elapses.
+    ///
+    /// # Arguments
+    ///
+    /// * `period` — Time between the snapshots. Should be positive.
+    pub fn with_portfolio_snapshots(mut self, period: NanoDuration) -> Self {
+        if period.is_zero() {
+            panic!("Portfolio snapshot period of Broker {} should be positive", self.name)
+        }
+        self.portfolio_snapshot_period = Some(period);
+        self.with_portfolio_tracking()
+    }
+
+    /// Returns the portfolio of the trader
+    /// or `None` if the tracking is disabled or the trader has no fills.
+    ///
+    /// # Arguments
+    ///
+    /// * `trader_id` — Trader.
+    pub fn get_portfolio(
+        &self,
+        trader_id: TraderID) -> Option<&Portfolio<ExchangeID, Symbol, Settlement>>
+    {
+        self.portfolios.as_ref()?.get(&trader_id)
+    }
+
+    fn mark_portfolios(
+        &mut self,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        price: Tick)
+    {
+        for portfolio in self.portfolios.iter_mut().flat_map(HashMap::values_mut) {
+            portfolio.mark(exchange_id, traded_pair, price)
+        }
+    }
+
+    fn update_portfolio_snapshots<KerMsg: Ord>(
+        &mut self,
+        message_receiver: &mut MessageReceiver<KerMsg>,
+        action_processor: &mut impl LatentActionProcessor<
+            <Self as Agent>::Action, ExchangeID, KerMsg=KerMsg
+        >,
+        rng: &mut impl Rng)
+    {
+        let (period, portfolios) = if let (Some(period), Some(portfolios)) = (
+            self.portfolio_snapshot_period, &self.portfolios
+        ) {
+            (period, portfolios)
+        } else {
+            return;
+        };
+        let is_due = self.last_portfolio_snapshot_dt.is_none_or(
+            |dt| dt + period <= self.current_dt
+        );
+        if !is_due {
+            return;
+        }
+        self.last_portfolio_snapshot_dt = Some(self.current_dt);
+        let mut traders: Vec<_> = self.trader_configs.keys().copied().collect();
+        traders.sort_unstable();
+        let mut exchanges: Vec<_> = self.registered_exchanges.iter().copied().collect();
+        exchanges.sort_unstable();
+        let mut actions = Vec::with_capacity(traders.len() * exchanges.len() + 1);
+        actions.push(
+            self.schedule_wakeup(
+                self.current_dt + period,
+                BasicBrokerToItself::PortfolioSnapshot,
+            )
+        );
+        for trader_id in traders {
+            let portfolio = portfolios.get(&trader_id).cloned().unwrap_or_default();
+            for exchange_id in &exchanges {
+                actions.push(
+                    Self::create_broker_reply(
+                        trader_id,
+                        *exchange_id,
+                        self.current_dt,
+                        BasicBrokerReply::PortfolioSnapshot(
+                            Rc::new(portfolio.get_snapshot(*exchange_id))
+                        ),
+                    )
+                )
+            }
+        }
+        let mut latency_generator = self.get_latency_generator();
+        message_receiver.extend(
+            actions.into_iter().map(
+                |action| action_processor.process_action(action, &mut latency_generator, rng)
+            )
+        );
+        self.update_latency_generator(latency_generator)
+    }
+
+    /// Makes the `BasicBroker` conflate the order book snapshots,
+    /// modelling a conflating feed handler.
+    /// After a snapshot is forwarded to the trader, the trader is considered busy
+    /// for the `window`. Snapshots of the same traded pair arriving meanwhile are not queued:
+    /// only the latest of them is kept and forwarded once the `window` elapses.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` — Time the trader is considered busy for.
+    pub fn with_snapshot_conflation(mut self, window: NanoDuration) -> Self {
+        self.conflation_window = Some(window);
+        self
+    }
+
+    /// Returns the number of order book snapshots dropped due to the conflation.
+    pub fn get_num_conflated_snapshots(&self) -> u64 {
+        self.num_conflated_snapshots
+    }
+
+    fn conflate_snapshots(
+        &mut self,
+        window: NanoDuration,
+        notification: &ExchangeEventNotification<Symbol, Settlement>,
+        exchange_id: ExchangeID,
+        exchange_dt: DateTime) -> Vec<<Self as Agent>::Action>
+    {
+        if let ExchangeEventNotification::ObSnapshot(ob_snapshot) = notification {
+            let subscribers = self.traded_pairs_info
+                .get(&(exchange_id, ob_snapshot.traded_pair))
+                .map(|subscribers| subscribers.as_slice())
+                .unwrap_or_default();
+            for (trader_id, subscription) in subscribers {
+                if !subscription.contains(SubscriptionList::OB_SNAPSHOTS) {
+                    continue;
+                }
+                let feed = self.conflated_feeds
+                    .entry((*trader_id, exchange_id, ob_snapshot.traded_pair))
+                    .or_insert(ConflatedFeed { last_forward_dt: None, pending: None, flush_dt: None });
+                if feed.pending.replace((exchange_dt, Rc::clone(ob_snapshot))).is_some() {
+                    self.num_conflated_snapshots += 1
+                }
+            }
+        }
+        self.flush_conflated_snapshots(
+            window,
+            |feed_exchange_id, traded_pair| match notification {
+                ExchangeEventNotification::ExchangeClosed => feed_exchange_id == exchange_id,
+                ExchangeEventNotification::TradesStopped(stopped_pair) => {
+                    feed_exchange_id == exchange_id && traded_pair == *stopped_pair
+                }
+                _ => false
+            },
+        )
+    }
+
+    /// Forwards the held back snapshots once the traders are not busy anymore
+    /// and schedules the [`BasicBrokerToItself::SnapshotFlush`] for the rest of them.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` — Time the trader is considered busy for.
+    /// * `is_forced` — Whether the snapshot of the traded pair at the exchange
+    ///                 is forwarded even if the trader is busy.
+    fn flush_conflated_snapshots(
+        &mut self,
+        window: NanoDuration,
+        is_forced: impl Fn(ExchangeID, TradedPair<Symbol, Settlement>) -> bool,
+    ) -> Vec<<Self as Agent>::Action>
+    {
+        let current_dt = self.current_dt;
+        let mut actions = Vec::new();
+        let mut flush_dts = Vec::new();
+        for ((trader_id, feed_exchange_id, traded_pair), feed) in &mut self.conflated_feeds {
+            let due_dt = feed.last_forward_dt.map(|dt| dt + window);
+            let is_due = due_dt.is_none_or(|dt| dt <= current_dt)
+                || is_forced(*feed_exchange_id, *traded_pair);
+            if !is_due {
+                if feed.pending.is_some() && feed.flush_dt != due_dt {
+                    feed.flush_dt = due_dt;
+                    flush_dts.extend(due_dt)
+                }
+                continue;
+            }
+            if let Some((event_dt, ob_snapshot)) = feed.pending.take() {
+                feed.last_forward_dt = Some(current_dt);
+                actions.push(
                     Self::create_broker_reply(
                         *trader_id,
-                        exchange_id,
-                        reply.exchange_dt,
-                        BasicBrokerReply::CannotCancelOrder(
-                            CannotCancelOrder {
-                                traded_pair: cannot_cancel.traded_pair,
-                                order_id: *order_id,
-                                reason: cannot_cancel.reason.into(),
-                            }
+                        *feed_exchange_id,
+                        event_dt,
+                        BasicBrokerReply::ExchangeEventNotification(
+                            ExchangeEventNotification::ObSnapshot(ob_snapshot)
                         ),
                     )
-                } else {
-                    panic!(
-                        "Cannot find a corresponding submitted order id \
-                        for the internal order id {}", cannot_cancel.order_id
-                    )
-                }
-            }
-            BasicExchangeToBrokerReply::ExchangeEventNotification(notification) => {
-                self.handle_exchange_notification(
-                    message_receiver,
-                    action_processor,
-                    notification,
-                    exchange_id,
-                    reply.exchange_dt,
-                    rng,
-                );
-                return;
+                )
             }
-        };
-        message_receiver.push(
-            action_processor.process_action(message, self.get_latency_generator(), rng)
-        )
+        }
+        // Feeds forwarded at the same datetime are flushed by the single wakeup
+        flush_dts.sort_unstable();
+        flush_dts.dedup();
+        actions.extend(
+            flush_dts.into_iter().map(
+                |flush_dt| self.schedule_wakeup(flush_dt, BasicBrokerToItself::SnapshotFlush)
+            )
+        );
+        actions
     }
 
-    fn process_replay_request<KerMsg: Ord>(
-        &mut self,
-        _: MessageReceiver<KerMsg>,
-        _: impl LatentActionProcessor<Self::Action, Self::ExchangeID, KerMsg=KerMsg>,
-        _: Self::R2B,
-        _: &mut impl Rng,
-    ) {
-        unreachable!("{} :: Did not plan to communicate with brokers", self.current_dt)
+    fn is_entitled(
+        &self,
+        trader_id: TraderID,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>) -> bool
+    {
+        self.entitlements.get(&trader_id)
+            .is_none_or(|entitlements| entitlements.is_permitted(exchange_id, traded_pair))
     }
 
-    fn upon_connection_to_exchange(&mut self, exchange_id: ExchangeID) {
-        self.registered_exchanges.insert(exchange_id);
+    /// Makes the `BasicBroker` respond to the quote requests of the traders
+    /// with firm quotes around the last trade prices and trade at them as a dealer.
+    ///
+    /// # Arguments
+    ///
+    /// * `rfq_config` — Quoting parameters.
+    pub fn with_rfq_quoting(mut self, rfq_config: RfqConfig) -> Self {
+        self.rfq_config = Some(rfq_config);
+        self
     }
 
-    fn register_trader(
+    fn process_quote_request(
         &mut self,
         trader_id: TraderID,
-        sub_cfgs: impl IntoIterator<Item=SubscriptionConfig<ExchangeID, Symbol, Settlement>>,
-    ) {
-        self.trader_configs.insert(
-            trader_id,
-            sub_cfgs.into_iter()
-                .inspect(
-                    |SubscriptionConfig { exchange, traded_pair, subscription }| {
-                        if !self.registered_exchanges.contains(&exchange) {
-                            panic!("Broker {} is not connected to Exchange {exchange}", self.name)
-                        };
-                        self.traded_pairs_info
-                            .entry((*exchange, *traded_pair))
-                            .or_default()
-                            .push((trader_id, *subscription))
-                    }
-                )
-                .map(
-                    |SubscriptionConfig { exchange, traded_pair, subscription }|
-                        ((exchange, traded_pair), subscription)
-                ).collect(),
+        exchange_id: ExchangeID,
+        request: QuoteRequest<Symbol, Settlement>) -> BasicBrokerReply<Symbol, Settlement>
+    {
+        let reject = |reason| BasicBrokerReply::QuoteRejected(
+            QuoteRejected {
+                traded_pair: request.traded_pair,
+                request_id: request.request_id,
+                reason,
+            }
         );
+        let rfq_config = if let Some(rfq_config) = self.rfq_config {
+            rfq_config
+        } else {
+            return reject(QuoteRejectionReason::QuotingDisabled);
+        };
+        if self.session_down {
+            return reject(QuoteRejectionReason::BrokerSessionDown);
+        }
+        if !self.registered_exchanges.contains(&exchange_id) {
+            return reject(QuoteRejectionReason::BrokerNotConnectedToExchange);
+        }
+        if !self.is_entitled(trader_id, exchange_id, request.traded_pair) {
+            return reject(QuoteRejectionReason::TraderNotEntitled);
+        }
+        if request.size <= Lots(0) {
+            return reject(QuoteRejectionReason::ZeroSize);
+        }
+        let key = (trader_id, request.request_id);
+        if matches!(
+            self.firm_quotes.get(&key),
+            Some((_, quote)) if quote.valid_until >= self.current_dt
+        ) {
+            return reject(QuoteRejectionReason::RequestWithSuchIDAlreadySubmitted);
+        }
+        let last_price = self.last_trade_prices.get(&(exchange_id, request.traded_pair));
+        let last_price = if let Some(last_price) = last_price {
+            *last_price
+        } else {
+            return reject(QuoteRejectionReason::NoReferencePrice);
+        };
+        let quote = FirmQuote {
+            traded_pair: request.traded_pair,
+            request_id: request.request_id,
+            direction: request.direction,
+            price: match request.direction {
+                Direction::Buy => last_price + rfq_config.half_spread,
+                Direction::Sell => last_price - rfq_config.half_spread
+            },
+            size: request.size,
+            valid_until: self.current_dt + rfq_config.validity,
+        };
+        self.firm_quotes.insert(key, (exchange_id, quote));
+        BasicBrokerReply::FirmQuote(quote)
     }
-}
 
-impl<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
-BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
-    where BrokerID: Id,
-          TraderID: Id,
-          ExchangeID: Id,
-          Symbol: Id,
-          Settlement: GetSettlementLag
-{
-    /// Creates a new instance of the `BasicBroker`.
-    ///
-    /// # Arguments
-    ///
-    /// * `name` — ID of the `BasicBroker`.
-    pub fn new(name: BrokerID) -> Self {
-        BasicBroker {
-            current_dt: Date::from_ymd(1970, 01, 01).and_hms(0, 0, 0),
-            name,
-            trader_configs: Default::default(),
-            traded_pairs_info: Default::default(),
-            submitted_to_internal: Default::default(),
-            internal_to_submitted: Default::default(),
-            registered_exchanges: Default::default(),
-            next_internal_order_id: OrderID(0),
+    fn process_quote_acceptance(
+        &mut self,
+        trader_id: TraderID,
+        exchange_id: ExchangeID,
+        account: Option<AccountID>,
+        acceptance: QuoteAcceptance<Symbol, Settlement>) -> BasicBrokerReply<Symbol, Settlement>
+    {
+        let reject = |reason| BasicBrokerReply::QuoteRejected(
+            QuoteRejected {
+                traded_pair: acceptance.traded_pair,
+                request_id: acceptance.request_id,
+                reason,
+            }
+        );
+        if !self.is_entitled(trader_id, exchange_id, acceptance.traded_pair) {
+            return reject(QuoteRejectionReason::TraderNotEntitled);
+        }
+        if self.is_blocked(trader_id) {
+            return reject(QuoteRejectionReason::KillSwitchTripped);
+        }
+        if self.session_down {
+            return reject(QuoteRejectionReason::BrokerSessionDown);
+        }
+        if !self.registered_exchanges.contains(&exchange_id) {
+            return reject(QuoteRejectionReason::BrokerNotConnectedToExchange);
+        }
+        let quote = match self.firm_quotes.remove(&(trader_id, acceptance.request_id)) {
+            Some((quote_exchange_id, quote))
+            if quote_exchange_id == exchange_id && quote.traded_pair == acceptance.traded_pair => {
+                quote
+            }
+            _ => return reject(QuoteRejectionReason::NoSuchQuote)
+        };
+        if quote.valid_until < self.current_dt {
+            return reject(QuoteRejectionReason::QuoteExpired);
+        }
+        if self.kill_switch.is_some() {
+            self.last_trade_prices.insert((exchange_id, quote.traded_pair), quote.price);
         }
+        self.book_fills(
+            exchange_id,
+            self.current_dt,
+            trader_id,
+            account,
+            None,
+            None,
+            &[
+                LegExecuted {
+                    traded_pair: quote.traded_pair,
+                    direction: quote.direction,
+                    price: quote.price,
+                    size: quote.size,
+                }
+            ],
+        );
+        BasicBrokerReply::QuoteTraded(
+            QuoteTraded {
+                traded_pair: quote.traded_pair,
+                request_id: quote.request_id,
+                direction: quote.direction,
+                price: quote.price,
+                size: quote.size,
+            }
+        )
     }
 
     fn handle_exchange_notification<KerMsg: Ord, RNG: Rng>(
@@ -571,152 +3315,143 @@ BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
         exchange_dt: DateTime,
         rng: &mut RNG,
     ) {
-        let process_action = |action|
-            action_processor.process_action(
-                action,
-                self.get_latency_generator(),
-                rng,
-            );
-        match notification {
-            ExchangeEventNotification::ExchangeOpen => {
-                let action_iterator = self.trader_configs.keys().map(
-                    |trader_id| Self::create_broker_reply(
-                        *trader_id,
-                        exchange_id,
-                        exchange_dt,
-                        BasicBrokerReply::ExchangeEventNotification(
-                            ExchangeEventNotification::ExchangeOpen
-                        ),
-                    )
-                );
-                message_receiver.extend(action_iterator.map(process_action))
+        if let ExchangeEventNotification::TradeExecuted(trade) = &notification {
+            if self.rfq_config.is_some() {
+                self.last_trade_prices.insert((exchange_id, trade.traded_pair), trade.price);
             }
-            ExchangeEventNotification::TradesStarted { traded_pair, price_step } => {
-                let action_iterator = self.trader_configs.keys().map(
-                    |trader_id| Self::create_broker_reply(
-                        *trader_id,
-                        exchange_id,
-                        exchange_dt,
-                        BasicBrokerReply::ExchangeEventNotification(
-                            ExchangeEventNotification::TradesStarted { traded_pair, price_step }
-                        ),
+        }
+        match &notification {
+            ExchangeEventNotification::TradeExecuted(trade) => {
+                self.mark_portfolios(exchange_id, trade.traded_pair, trade.price)
+            }
+            ExchangeEventNotification::ClosingPrice { traded_pair, price } => {
+                self.mark_portfolios(exchange_id, *traded_pair, *price)
+            }
+            _ => {}
+        }
+        if let Some(trade_log) = &self.trade_log {
+            match &notification {
+                ExchangeEventNotification::TradeExecuted(trade) => {
+                    trade_log.borrow_mut().record_mark(
+                        self.current_dt, (exchange_id, trade.traded_pair), trade.price,
                     )
-                );
-                message_receiver.extend(action_iterator.map(process_action))
-            }
-            ExchangeEventNotification::OrderCancelled(cancelled) => {
-                let action_iterator = self.trader_configs.iter().filter_map(
-                    |(trader_id, configs)| {
-                        if let Some(config) = configs.get(&(exchange_id, cancelled.traded_pair)) {
-                            if config.contains(SubscriptionList::CANCELLED_LIMIT_ORDERS) {
-                                let notification = Self::create_broker_reply(
-                                    *trader_id,
-                                    exchange_id,
-                                    exchange_dt,
-                                    BasicBrokerReply::ExchangeEventNotification(
-                                        ExchangeEventNotification::OrderCancelled(cancelled)
-                                    ),
-                                );
-                                return Some(notification);
-                            }
-                        }
-                        None
-                    }
-                );
-                message_receiver.extend(action_iterator.map(process_action))
-            }
-            ExchangeEventNotification::OrderPlaced(placed) => {
-                let action_iterator = self.trader_configs.iter().filter_map(
-                    |(trader_id, configs)| {
-                        if let Some(config) = configs.get(&(exchange_id, placed.traded_pair)) {
-                            if config.contains(SubscriptionList::NEW_LIMIT_ORDERS) {
-                                let notification = Self::create_broker_reply(
-                                    *trader_id,
-                                    exchange_id,
-                                    exchange_dt,
-                                    BasicBrokerReply::ExchangeEventNotification(
-                                        ExchangeEventNotification::OrderPlaced(placed)
-                                    ),
-                                );
-                                return Some(notification);
-                            }
-                        }
-                        None
-                    }
-                );
-                message_receiver.extend(action_iterator.map(process_action))
+                }
+                ExchangeEventNotification::ClosingPrice { traded_pair, price } => {
+                    trade_log.borrow_mut().record_mark(
+                        self.current_dt, (exchange_id, *traded_pair), *price,
+                    )
+                }
+                _ => {}
+            }
+        }
+        if let Some(window) = self.conflation_window {
+            let due_snapshots = self.conflate_snapshots(
+                window,
+                &notification,
+                exchange_id,
+                exchange_dt,
+            );
+            let mut latency_generator = self.get_latency_generator();
+            message_receiver.extend(
+                due_snapshots.into_iter().map(
+                    |action| action_processor.process_action(action, &mut latency_generator, rng)
+                )
+            );
+            self.update_latency_generator(latency_generator);
+            if let ExchangeEventNotification::ObSnapshot(_) = notification {
+                return;
+            }
+        }
+        // Order book events are sent to the subscribers only, whereas the rest to every trader
+        let subscription = match &notification {
+            ExchangeEventNotification::OrderCancelled(order)
+            | ExchangeEventNotification::OrderSizeReduced(order) => {
+                Some((order.traded_pair, SubscriptionList::CANCELLED_LIMIT_ORDERS))
+            }
+            ExchangeEventNotification::OrderPlaced(order) => {
+                Some((order.traded_pair, SubscriptionList::NEW_LIMIT_ORDERS))
             }
             ExchangeEventNotification::TradeExecuted(trade) => {
-                let action_iterator = self.trader_configs.iter().filter_map(
-                    |(trader_id, configs)| {
-                        if let Some(config) = configs.get(&(exchange_id, trade.traded_pair)) {
-                            if config.contains(SubscriptionList::TRADES) {
-                                let notification = Self::create_broker_reply(
-                                    *trader_id,
-                                    exchange_id,
-                                    exchange_dt,
-                                    BasicBrokerReply::ExchangeEventNotification(
-                                        ExchangeEventNotification::TradeExecuted(trade)
-                                    ),
-                                );
-                                return Some(notification);
-                            }
-                        }
-                        None
-                    }
-                );
-                message_receiver.extend(action_iterator.map(process_action))
+                Some((trade.traded_pair, SubscriptionList::TRADES))
+            }
+            // Delayed prints are not used as the last trade prices,
+            // since the market may have moved since their execution
+            ExchangeEventNotification::OffBookTradeReported(trade) => {
+                Some((trade.traded_pair, SubscriptionList::TRADES))
             }
             ExchangeEventNotification::ObSnapshot(ob_snapshot) => {
-                let action_iterator = self.trader_configs.iter().filter_map(
-                    |(trader_id, configs)| {
-                        if let Some(config) = configs.get(&(exchange_id, ob_snapshot.traded_pair)) {
-                            if config.contains(SubscriptionList::OB_SNAPSHOTS) {
-                                let ob_snapshot = Self::create_broker_reply(
-                                    *trader_id,
-                                    exchange_id,
-                                    exchange_dt,
-                                    BasicBrokerReply::ExchangeEventNotification(
-                                        ExchangeEventNotification::ObSnapshot(
-                                            Rc::clone(&ob_snapshot)
-                                        )
-                                    ),
-                                );
-                                return Some(ob_snapshot);
-                            }
-                        }
-                        None
-                    }
-                );
-                message_receiver.extend(action_iterator.map(process_action))
+                Some((ob_snapshot.traded_pair, SubscriptionList::OB_SNAPSHOTS))
             }
-            ExchangeEventNotification::TradesStopped(traded_pair) => {
-                let action_iterator = self.trader_configs.keys().map(
-                    |trader_id| Self::create_broker_reply(
-                        *trader_id,
-                        exchange_id,
-                        exchange_dt,
-                        BasicBrokerReply::ExchangeEventNotification(
-                            ExchangeEventNotification::TradesStopped(traded_pair)
-                        ),
-                    )
-                );
-                message_receiver.extend(action_iterator.map(process_action))
+            ExchangeEventNotification::ObDiff(ob_diff) => {
+                Some((ob_diff.traded_pair, SubscriptionList::OB_DIFFS))
             }
-            ExchangeEventNotification::ExchangeClosed => {
-                let action_iterator = self.trader_configs.keys().map(
-                    |trader_id| Self::create_broker_reply(
-                        *trader_id,
-                        exchange_id,
-                        exchange_dt,
-                        BasicBrokerReply::ExchangeEventNotification(
-                            ExchangeEventNotification::ExchangeClosed
-                        ),
-                    )
-                );
-                message_receiver.extend(action_iterator.map(process_action))
+            ExchangeEventNotification::ThroughputStatistics(statistics) => {
+                Some((statistics.traded_pair, SubscriptionList::THROUGHPUT_STATISTICS))
+            }
+            ExchangeEventNotification::VolatilityEstimate(estimate) => {
+                Some((estimate.traded_pair, SubscriptionList::VOLATILITY_ESTIMATES))
             }
+            ExchangeEventNotification::ExchangeOpen
+            | ExchangeEventNotification::TradesStarted { .. }
+            | ExchangeEventNotification::TradesStopped(_)
+            | ExchangeEventNotification::AdminActionApplied(_)
+            | ExchangeEventNotification::ClosingPrice { .. }
+            | ExchangeEventNotification::DowntimeStarted
+            | ExchangeEventNotification::DowntimeEnded
+            | ExchangeEventNotification::TradingPhaseChanged(_)
+            | ExchangeEventNotification::AuctionPriceDetermined(_)
+            | ExchangeEventNotification::ExchangeClosed => None
+        };
+        let mut latency_generator = self.get_latency_generator();
+        let process_action = |action|
+            action_processor.process_action(action, &mut latency_generator, rng);
+        if let Some((traded_pair, subscription)) = subscription {
+            let action_iterator = self.notify_subscribers(
+                exchange_id,
+                exchange_dt,
+                traded_pair,
+                subscription,
+                notification,
+            );
+            message_receiver.extend(action_iterator.map(process_action))
+        } else {
+            let action_iterator = self.trader_configs.keys().map(
+                |trader_id| Self::create_broker_reply(
+                    *trader_id,
+                    exchange_id,
+                    exchange_dt,
+                    BasicBrokerReply::ExchangeEventNotification(notification.clone()),
+                )
+            );
+            message_receiver.extend(action_iterator.map(process_action))
         }
+        self.update_latency_generator(latency_generator)
+    }
+
+    /// Returns the notifications of the traders having the subscription
+    /// to the events of the traded pair.
+    fn notify_subscribers(
+        &self,
+        exchange_id: ExchangeID,
+        exchange_dt: DateTime,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        subscription: SubscriptionList,
+        notification: ExchangeEventNotification<Symbol, Settlement>,
+    ) -> impl Iterator<Item=<Self as Agent>::Action> + '_
+    {
+        self.traded_pairs_info
+            .get(&(exchange_id, traded_pair))
+            .into_iter()
+            .flatten()
+            .filter(move |(_, subscribed)| subscribed.contains(subscription))
+            .map(
+                move |(trader_id, _)| Self::create_broker_reply(
+                    *trader_id,
+                    exchange_id,
+                    exchange_dt,
+                    BasicBrokerReply::ExchangeEventNotification(notification.clone()),
+                )
+            )
     }
 
     fn create_broker_reply(
@@ -726,7 +3461,7 @@ BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
         content: BasicBrokerReply<Symbol, Settlement>) -> <Self as Agent>::Action
     {
         BrokerAction {
-            delay: 0,
+            delay: NanoDuration::ZERO,
             content: BrokerActionKind::BrokerToTrader(
                 BasicBrokerToTrader {
                     trader_id,
@@ -743,7 +3478,7 @@ BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
         content: BasicBrokerRequest<Symbol, Settlement>) -> <Self as Agent>::Action
     {
         BrokerAction {
-            delay: 0,
+            delay: NanoDuration::ZERO,
             content: BrokerActionKind::BrokerToExchange(
                 BasicBrokerToExchange {
                     exchange_id,
@@ -754,6 +3489,25 @@ BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
     }
 }
 
+impl<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
+Drop
+for BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
+    where BrokerID: Id,
+          TraderID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            return;
+        }
+        if let (Some(statements), Some(ledger)) = (&mut self.statements, &self.ledger) {
+            statements.write_statements(ledger, self.trader_configs.keys().copied())
+        }
+    }
+}
+
 /// [`Broker`] that is doing nothing.
 pub struct VoidBroker<BrokerID, TraderID, ExchangeID, R2B, E2B, T2B, B2R, B2E, B2T, B2B, SubCfg>
     where BrokerID: Id,
@@ -948,12 +3702,12 @@ for VoidBroker<BrokerID, TraderID, ExchangeID, R2B, E2B, T2B, B2R, B2E, B2T, B2B
 /// [`message_protocol`](crate::concrete::message_protocol).
 pub type BasicVoidBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement> = VoidBroker<
     BrokerID, TraderID, ExchangeID,
-    NeverType<BrokerID>,
+    BasicReplayToBroker<BrokerID, ExchangeID, Symbol, Settlement>,
     BasicExchangeToBroker<BrokerID, Symbol, Settlement>,
     BasicTraderToBroker<BrokerID, ExchangeID, Symbol, Settlement>,
     Nothing,
     BasicBrokerToExchange<ExchangeID, Symbol, Settlement>,
     BasicBrokerToTrader<TraderID, ExchangeID, Symbol, Settlement>,
-    Nothing,
+    BasicBrokerToItself<BrokerID, TraderID, ExchangeID, Symbol, Settlement>,
     SubscriptionConfig<ExchangeID, Symbol, Settlement>
 >;
\ No newline at end of file
@@ -0,0 +1,758 @@
+use {
+    crate::{
+        concrete::{
+            traded_pair::{settlement::GetSettlementLag, TradedPair},
+            types::{AccountID, Direction, Lots, OrderID, Tick},
+        },
+        types::{Date, DateTime, Id},
+        utils::collections::HashMap,
+    },
+    std::{fs::File, io::Write, path::Path},
+};
+
+/// Owner, account, exchange and traded pair of the account position.
+type PositionKey<TraderID, ExchangeID, Symbol, Settlement> =
+    (TraderID, AccountID, ExchangeID, TradedPair<Symbol, Settlement>);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+/// Bookkeeping mode of the account positions.
+pub enum PositionMode {
+    /// Single net position per instrument. Opposite fills close the position first.
+    #[default]
+    Netting,
+    /// Long and short positions held simultaneously, as some FX brokers do.
+    /// Every fill opens the position of its side until the legs are closed out
+    /// against each other with the [`AccountLedger::close_out`].
+    Hedging,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+/// Annual interest rates applied daily, on the ACT/360 basis, to the account cash balances.
+pub struct InterestRates {
+    /// Rate credited to the positive balances.
+    pub credit_rate: f64,
+    /// Rate charged on the negative balances.
+    pub debit_rate: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Rule of splitting the fills of the untagged orders between the accounts of the trader.
+pub enum AllocationRule {
+    /// Book all fills to the single account.
+    Single(AccountID),
+    /// Split the filled size of every order pro-rata to the weights of the accounts.
+    /// Fills are allocated against the cumulative filled size of the order,
+    /// so that fractional lots are carried over to its subsequent fills
+    /// and are assigned to the accounts lagging most behind their shares.
+    ProRata(Vec<(AccountID, f64)>),
+}
+
+impl Default for AllocationRule {
+    fn default() -> Self {
+        Self::Single(AccountID::default())
+    }
+}
+
+impl AllocationRule {
+    /// Splits the fill of the order between the accounts. Accounts getting zero lots are omitted.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` — Fill size.
+    /// * `allocated` — Sizes allocated to the accounts of the rule from the previous fills
+    ///   of the same order, in the order of the accounts. Empty for the first fill.
+    ///   Is updated by the fill.
+    pub fn allocate(&self, size: Lots, allocated: &mut Vec<Lots>) -> Vec<(AccountID, Lots)> {
+        self.split::<true>(size, allocated)
+    }
+
+    /// Splits the busted fill of the order between the accounts the way opposite to the
+    /// [`allocate`](Self::allocate), so that the remaining allocations stay pro-rata.
+    /// Accounts losing zero lots are omitted.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` — Busted fill size.
+    /// * `allocated` — Sizes allocated to the accounts of the rule from the fills
+    ///   of the same order, in the order of the accounts. Is updated by the bust.
+    pub fn deallocate(&self, size: Lots, allocated: &mut Vec<Lots>) -> Vec<(AccountID, Lots)> {
+        self.split::<false>(size, allocated)
+    }
+
+    fn split<const ALLOCATE: bool>(
+        &self,
+        size: Lots,
+        allocated: &mut Vec<Lots>) -> Vec<(AccountID, Lots)>
+    {
+        let weights = match self {
+            Self::Single(account) => return vec![(*account, size)],
+            Self::ProRata(weights) => weights
+        };
+        allocated.resize(weights.len(), Lots(0));
+        let cumulative = allocated.iter().map(|size| size.0).sum::<i64>()
+            + if ALLOCATE { size.0 } else { -size.0 };
+        let total_weight: f64 = weights.iter().map(|(_, weight)| weight).sum();
+        // Lots each account lacks to reach its share, or has in excess when deallocating
+        let mut gaps: Vec<_> = weights.iter()
+            .zip(allocated.iter())
+            .map(|((_, weight), allocated)| {
+                let gap = cumulative as f64 * weight / total_weight - allocated.0 as f64;
+                if ALLOCATE { gap } else { -gap }
+            })
+            .collect();
+        let mut sizes = vec![0; weights.len()];
+        let mut unallocated = size.0;
+        for (gap, size) in gaps.iter_mut().zip(&mut sizes) {
+            let whole = (gap.floor().max(0.0) as i64).min(unallocated);
+            *size = whole;
+            *gap -= whole as f64;
+            unallocated -= whole
+        }
+        let mut remainders: Vec<_> = (0..gaps.len()).collect();
+        remainders.sort_by(|i, j| gaps[*j].total_cmp(&gaps[*i]));
+        for i in remainders.into_iter().take(unallocated as usize) {
+            sizes[i] += 1
+        }
+        for (allocated, size) in allocated.iter_mut().zip(&sizes) {
+            if ALLOCATE { *allocated += Lots(*size) } else { *allocated -= Lots(*size) }
+        }
+        weights.iter()
+            .zip(sizes)
+            .filter(|(_, size)| *size != 0)
+            .map(|((account, _), size)| (*account, Lots(size)))
+            .collect()
+    }
+
+    fn validate(&self) {
+        if let Self::ProRata(weights) = self {
+            if weights.is_empty() {
+                panic!("Pro-rata allocation rule should list at least one account")
+            }
+            let invalid = weights.iter().find(|(_, weight)| weight.is_nan() || *weight <= 0.0);
+            if let Some((account, weight)) = invalid {
+                panic!("Allocation weight should be positive. Got {weight} for account {account}")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Fill of the trader order to book to the accounts.
+pub struct OrderFill<TraderID, ExchangeID, Symbol, Settlement>
+    where TraderID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    /// Datetime of the fill.
+    pub datetime: DateTime,
+    /// Owner of the order.
+    pub trader_id: TraderID,
+    /// Account tag of the order. If absent, the allocation rule is applied.
+    pub account: Option<AccountID>,
+    /// ID of the order the fills of which are allocated together.
+    /// `None` for the standalone fills, e.g. the quote trades.
+    pub order_id: Option<OrderID>,
+    /// Exchange the fill happened at.
+    pub exchange_id: ExchangeID,
+    /// Traded pair.
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    /// Direction of the order.
+    pub direction: Direction,
+    /// Fill price.
+    pub price: Tick,
+    /// Fill size.
+    pub size: Lots,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Fill booked to the single account.
+pub struct Allocation<TraderID, ExchangeID, Symbol, Settlement>
+    where TraderID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    /// Datetime of the fill.
+    pub datetime: DateTime,
+    /// Owner of the account.
+    pub trader_id: TraderID,
+    /// Account the fill is booked to.
+    pub account: AccountID,
+    /// Exchange the fill happened at.
+    pub exchange_id: ExchangeID,
+    /// Traded pair.
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    /// Direction of the trader.
+    pub direction: Direction,
+    /// Fill price.
+    pub price: Tick,
+    /// Allocated size.
+    pub size: Lots,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Position of the account in the single instrument.
+pub struct AccountPosition {
+    /// Signed net position size. Positive for long positions.
+    pub size: Lots,
+    /// Size of the long leg. Equals the net size of the long netting position.
+    pub long: Lots,
+    /// Size of the short leg. Equals the negated net size of the short netting position.
+    pub short: Lots,
+    /// Signed sum of the fill notionals, in ticks times lots. Positive for net sales.
+    pub cash_flow: i64,
+}
+
+impl Default for AccountPosition {
+    fn default() -> Self {
+        Self { size: Lots(0), long: Lots(0), short: Lots(0), cash_flow: 0 }
+    }
+}
+
+impl AccountPosition {
+    /// Returns the size subject to the margin requirements, that is the sum of the legs.
+    /// For the netting position it is the absolute net size.
+    pub fn get_gross_size(&self) -> Lots {
+        self.long + self.short
+    }
+
+    /// Closes out the long and short legs against each other, leaving the net size intact.
+    /// Returns the closed size.
+    pub fn close_out(&mut self) -> Lots {
+        let closed = self.long.min(self.short);
+        self.long -= closed;
+        self.short -= closed;
+        closed
+    }
+
+    fn apply_fill(&mut self, mode: PositionMode, direction: Direction, price: Tick, size: Lots) {
+        match direction {
+            Direction::Buy => self.cash_flow -= price.0 * size.0,
+            Direction::Sell => self.cash_flow += price.0 * size.0
+        }
+        self.add_size(mode, direction, size)
+    }
+
+    /// Updates the net size and the legs by the fill, leaving the cash flow intact.
+    pub(crate) fn add_size(&mut self, mode: PositionMode, direction: Direction, size: Lots) {
+        let (same, opposite) = match direction {
+            Direction::Buy => {
+                self.size += size;
+                (&mut self.long, &mut self.short)
+            }
+            Direction::Sell => {
+                self.size -= size;
+                (&mut self.short, &mut self.long)
+            }
+        };
+        let closed = match mode {
+            PositionMode::Netting => size.min(*opposite),
+            PositionMode::Hedging => Lots(0)
+        };
+        *opposite -= closed;
+        *same += size - closed
+    }
+
+    fn unwind_fill(&mut self, mode: PositionMode, direction: Direction, price: Tick, size: Lots) {
+        let (same, opposite) = match direction {
+            Direction::Buy => {
+                self.size -= size;
+                self.cash_flow += price.0 * size.0;
+                (&mut self.long, &mut self.short)
+            }
+            Direction::Sell => {
+                self.size += size;
+                self.cash_flow -= price.0 * size.0;
+                (&mut self.short, &mut self.long)
+            }
+        };
+        match mode {
+            PositionMode::Netting => {
+                self.long = Lots(self.size.0.max(0));
+                self.short = Lots((-self.size.0).max(0))
+            }
+            // If the leg has been closed out, the rest of the fill is unwound
+            // by opening the opposite one
+            PositionMode::Hedging => {
+                let unwound = size.min(*same);
+                *same -= unwound;
+                *opposite += size - unwound
+            }
+        }
+    }
+}
+
+/// Books fills to the accounts of the traders according to the order-level account tags
+/// and the allocation rules of the traders.
+pub struct AccountLedger<TraderID, ExchangeID, Symbol, Settlement>
+    where TraderID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    rules: HashMap<TraderID, AllocationRule>,
+    modes: HashMap<(TraderID, AccountID), PositionMode>,
+    positions: HashMap<PositionKey<TraderID, ExchangeID, Symbol, Settlement>, AccountPosition>,
+    allocations: Vec<Allocation<TraderID, ExchangeID, Symbol, Settlement>>,
+    /// Sizes allocated from the untagged orders to the accounts of the allocation rule.
+    order_allocations: HashMap<(TraderID, OrderID), Vec<Lots>>,
+    interest_rates: Option<InterestRates>,
+    /// Date the interest has been accrued up to.
+    interest_date: Option<Date>,
+    accrued_interest: HashMap<(TraderID, AccountID), f64>,
+}
+
+impl<TraderID, ExchangeID, Symbol, Settlement> Default
+for AccountLedger<TraderID, ExchangeID, Symbol, Settlement>
+    where TraderID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    fn default() -> Self {
+        Self {
+            rules: Default::default(),
+            modes: Default::default(),
+            positions: Default::default(),
+            allocations: vec![],
+            order_allocations: Default::default(),
+            interest_rates: None,
+            interest_date: None,
+            accrued_interest: Default::default(),
+        }
+    }
+}
+
+impl<TraderID, ExchangeID, Symbol, Settlement>
+AccountLedger<TraderID, ExchangeID, Symbol, Settlement>
+    where TraderID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    /// Creates a new instance of the `AccountLedger`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the allocation rule for the untagged orders of the trader.
+    /// By default, they are booked to the [`AccountID::default`].
+    ///
+    /// # Arguments
+    ///
+    /// * `trader_id` — Trader.
+    /// * `rule` — Allocation rule.
+    pub fn with_allocation_rule(mut self, trader_id: TraderID, rule: AllocationRule) -> Self {
+        rule.validate();
+        self.rules.insert(trader_id, rule);
+        self
+    }
+
+    /// Sets the bookkeeping mode of the account positions.
+    /// By default, the accounts are in the [`PositionMode::Netting`].
+    /// Should be called before any fill is booked to the account.
+    ///
+    /// # Arguments
+    ///
+    /// * `trader_id` — Owner of the account.
+    /// * `account` — Account.
+    /// * `mode` — Position mode.
+    pub fn with_position_mode(
+        mut self,
+        trader_id: TraderID,
+        account: AccountID,
+        mode: PositionMode) -> Self
+    {
+        self.modes.insert((trader_id, account), mode);
+        self
+    }
+
+    /// Makes the `AccountLedger` accrue the interest on the account cash balances.
+    ///
+    /// # Arguments
+    ///
+    /// * `rates` — Credit and debit interest rates.
+    pub fn with_interest_rates(mut self, rates: InterestRates) -> Self {
+        self.interest_rates = Some(rates);
+        self
+    }
+
+    /// Returns the bookkeeping mode of the account positions.
+    ///
+    /// # Arguments
+    ///
+    /// * `trader_id` — Owner of the account.
+    /// * `account` — Account.
+    pub fn get_position_mode(&self, trader_id: TraderID, account: AccountID) -> PositionMode {
+        self.modes.get(&(trader_id, account)).copied().unwrap_or_default()
+    }
+
+    /// Books the fill to the accounts.
+    ///
+    /// # Arguments
+    ///
+    /// * `fill` — Fill of the trader order.
+    pub fn book_fill(&mut self, fill: OrderFill<TraderID, ExchangeID, Symbol, Settlement>) {
+        let OrderFill {
+            datetime, trader_id, account, order_id, exchange_id, traded_pair, direction, price, size
+        } = fill;
+        for (account, size) in self.split_fill::<true>(trader_id, account, order_id, size) {
+            let mode = self.get_position_mode(trader_id, account);
+            self.positions
+                .entry((trader_id, account, exchange_id, traded_pair))
+                .or_default()
+                .apply_fill(mode, direction, price, size);
+            self.allocations.push(
+                Allocation {
+                    datetime,
+                    trader_id,
+                    account,
+                    exchange_id,
+                    traded_pair,
+                    direction,
+                    price,
+                    size,
+                }
+            )
+        }
+    }
+
+    /// Unwinds the busted fill previously booked by the [`book_fill`](Self::book_fill),
+    /// splitting it among the accounts the same way.
+    /// Reversing allocations are recorded in the opposite direction at the fill price.
+    ///
+    /// # Arguments
+    ///
+    /// * `fill` — Busted fill. Its datetime is the datetime of the bust.
+    pub fn unwind_fill(&mut self, fill: OrderFill<TraderID, ExchangeID, Symbol, Settlement>) {
+        let OrderFill {
+            datetime, trader_id, account, order_id, exchange_id, traded_pair, direction, price, size
+        } = fill;
+        let reversed_direction = match direction {
+            Direction::Buy => Direction::Sell,
+            Direction::Sell => Direction::Buy
+        };
+        for (account, size) in self.split_fill::<false>(trader_id, account, order_id, size) {
+            let mode = self.get_position_mode(trader_id, account);
+            self.positions
+                .entry((trader_id, account, exchange_id, traded_pair))
+                .or_default()
+                .unwind_fill(mode, direction, price, size);
+            self.allocations.push(
+                Allocation {
+                    datetime,
+                    trader_id,
+                    account,
+                    exchange_id,
+                    traded_pair,
+                    direction: reversed_direction,
+                    price,
+                    size,
+                }
+            )
+        }
+    }
+
+    fn split_fill<const BOOK: bool>(
+        &mut self,
+        trader_id: TraderID,
+        account: Option<AccountID>,
+        order_id: Option<OrderID>,
+        size: Lots) -> Vec<(AccountID, Lots)>
+    {
+        if let Some(account) = account {
+            return vec![(account, size)];
+        }
+        let rule = match self.rules.get(&trader_id) {
+            Some(rule) => rule,
+            None => return AllocationRule::default().allocate(size, &mut Vec::new())
+        };
+        let mut standalone = Vec::new();
+        let allocated = match order_id {
+            Some(order_id) => self.order_allocations.entry((trader_id, order_id)).or_default(),
+            None => &mut standalone
+        };
+        if BOOK {
+            rule.allocate(size, allocated)
+        } else {
+            rule.deallocate(size, allocated)
+        }
+    }
+
+    /// Returns the position of the account.
+    ///
+    /// # Arguments
+    ///
+    /// * `trader_id` — Owner of the account.
+    /// * `account` — Account.
+    /// * `exchange_id` — Exchange the instrument is listed at.
+    /// * `traded_pair` — Traded pair.
+    pub fn get_position(
+        &self,
+        trader_id: TraderID,
+        account: AccountID,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>) -> AccountPosition
+    {
+        self.positions.get(&(trader_id, account, exchange_id, traded_pair))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Closes out the long and short legs of the hedging position against each other,
+    /// leaving the net position intact. Returns the closed size.
+    /// Netting positions have nothing to close out.
+    ///
+    /// # Arguments
+    ///
+    /// * `trader_id` — Owner of the account.
+    /// * `account` — Account.
+    /// * `exchange_id` — Exchange the instrument is listed at.
+    /// * `traded_pair` — Traded pair.
+    pub fn close_out(
+        &mut self,
+        trader_id: TraderID,
+        account: AccountID,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>) -> Lots
+    {
+        let position = if let Some(position) = self.positions.get_mut(
+            &(trader_id, account, exchange_id, traded_pair)
+        ) {
+            position
+        } else {
+            return Lots(0);
+        };
+        position.close_out()
+    }
+
+    /// Accrues the interest for every day elapsed since the previous call,
+    /// including the non-trading ones. Interest is compounded daily
+    /// and the balances of the elapsed days do not include the fills of the current one.
+    /// Does nothing unless the interest rates are set.
+    ///
+    /// # Arguments
+    ///
+    /// * `current_dt` — Current datetime.
+    pub fn accrue_interest(&mut self, current_dt: DateTime) {
+        let rates = if let Some(rates) = self.interest_rates {
+            rates
+        } else {
+            return;
+        };
+        let current_date = current_dt.date();
+        let num_days = match self.interest_date {
+            Some(date) if date < current_date => (current_date - date).num_days(),
+            Some(_) => return,
+            None => {
+                self.interest_date = Some(current_date);
+                return;
+            }
+        };
+        self.interest_date = Some(current_date);
+        let mut balances: HashMap<(TraderID, AccountID), f64> = HashMap::default();
+        for ((trader_id, account, _, _), position) in &self.positions {
+            *balances.entry((*trader_id, *account)).or_default() += position.cash_flow as f64
+        }
+        for (key, cash) in balances {
+            let interest = self.accrued_interest.entry(key).or_default();
+            for _ in 0..num_days {
+                let balance = cash + *interest;
+                let rate = if balance > 0.0 { rates.credit_rate } else { rates.debit_rate };
+                *interest += balance * rate / 360.0
+            }
+        }
+    }
+
+    /// Returns the total interest accrued on the account cash balance.
+    /// Negative if it has been charged.
+    ///
+    /// # Arguments
+    ///
+    /// * `trader_id` — Owner of the account.
+    /// * `account` — Account.
+    pub fn get_accrued_interest(&self, trader_id: TraderID, account: AccountID) -> f64 {
+        self.accrued_interest.get(&(trader_id, account)).copied().unwrap_or_default()
+    }
+
+    /// Returns the cash balance of the account, in ticks times lots,
+    /// that is the sum of the fill notionals and the accrued interest.
+    ///
+    /// # Arguments
+    ///
+    /// * `trader_id` — Owner of the account.
+    /// * `account` — Account.
+    pub fn get_cash_balance(&self, trader_id: TraderID, account: AccountID) -> f64 {
+        let cash_flow: i64 = self.positions.iter()
+            .filter(|((owner, position_account, _, _), _)| {
+                *owner == trader_id && *position_account == account
+            })
+            .map(|(_, position)| position.cash_flow)
+            .sum();
+        cash_flow as f64 + self.get_accrued_interest(trader_id, account)
+    }
+
+    /// Yields the positions of all accounts in arbitrary order.
+    pub fn iter_positions(
+        &self) -> impl Iterator<
+            Item=(PositionKey<TraderID, ExchangeID, Symbol, Settlement>, AccountPosition)
+        > + '_
+    {
+        self.positions.iter().map(|(key, position)| (*key, *position))
+    }
+
+    /// Returns all booked fills in the order of booking.
+    pub fn get_allocations(&self) -> &[Allocation<TraderID, ExchangeID, Symbol, Settlement>] {
+        &self.allocations
+    }
+
+    /// Writes all positions to the csv-file, sorted by the trader and the account.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` — Path to the csv-file to create.
+    pub fn write_positions(&self, file: impl AsRef<Path>) {
+        let file = file.as_ref();
+        let mut writer = File::create(file).unwrap_or_else(
+            |err| panic!("Cannot create file {file:?}. Error: {err}")
+        );
+        let mut positions: Vec<_> = self.positions.iter().collect();
+        positions.sort_by_key(|((trader_id, account, exchange_id, traded_pair), _)| {
+            (*trader_id, *account, *exchange_id, *traded_pair)
+        });
+        let mut write = |line: String| writeln!(writer, "{line}").unwrap_or_else(
+            |err| panic!("Cannot write to file {file:?}. Error: {err}")
+        );
+        write("TRADER,ACCOUNT,EXCHANGE,TRADED_PAIR,SIZE,LONG,SHORT,CASH_FLOW".into());
+        for ((trader_id, account, exchange_id, traded_pair), position) in positions {
+            write(
+                format!(
+                    "{trader_id},{account},{exchange_id},{:?},{},{},{},{}",
+                    traded_pair.quoted_asset,
+                    position.size,
+                    position.long,
+                    position.short,
+                    position.cash_flow
+                )
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            concrete::traded_pair::settlement::concrete::SpotSettlement,
+            test_utils::{dt, pair},
+        },
+    };
+
+    type Ledger = AccountLedger<u8, u8, &'static str, SpotSettlement>;
+
+    fn fill(
+        account: Option<AccountID>,
+        order_id: Option<u64>,
+        direction: Direction,
+        size: i64) -> OrderFill<u8, u8, &'static str, SpotSettlement>
+    {
+        OrderFill {
+            datetime: dt(0),
+            trader_id: 1,
+            account,
+            order_id: order_id.map(OrderID),
+            exchange_id: 2,
+            traded_pair: pair(),
+            direction,
+            price: Tick(100),
+            size: Lots(size),
+        }
+    }
+
+    fn pro_rata() -> AllocationRule {
+        AllocationRule::ProRata(vec![(AccountID(1), 0.5), (AccountID(2), 0.3), (AccountID(3), 0.2)])
+    }
+
+    fn get_sizes(ledger: &Ledger) -> [i64; 3] {
+        [1, 2, 3].map(|account| ledger.get_position(1, AccountID(account), 2, pair()).size.0)
+    }
+
+    #[test]
+    fn test_largest_remainder() {
+        let mut allocated = Vec::new();
+        assert_eq!(
+            pro_rata().allocate(Lots(7), &mut allocated),
+            [(AccountID(1), Lots(4)), (AccountID(2), Lots(2)), (AccountID(3), Lots(1))]
+        );
+        assert_eq!(allocated, [Lots(4), Lots(2), Lots(1)]);
+        assert_eq!(
+            AllocationRule::Single(AccountID(5)).allocate(Lots(7), &mut Vec::new()),
+            [(AccountID(5), Lots(7))]
+        )
+    }
+
+    #[test]
+    fn test_partial_fills_are_pro_rata() {
+        let mut ledger = Ledger::new().with_allocation_rule(1, pro_rata());
+        let mut booked = Vec::new();
+        for _ in 0..10 {
+            ledger.book_fill(fill(None, Some(7), Direction::Buy, 1));
+            booked.push(get_sizes(&ledger))
+        }
+        assert_eq!(get_sizes(&ledger), [5, 3, 2]);
+        // Every account stays within a lot of its share of the filled size
+        for (filled, sizes) in (1..).zip(booked) {
+            for (size, weight) in sizes.into_iter().zip([0.5, 0.3, 0.2]) {
+                assert!((size as f64 - filled as f64 * weight).abs() < 1.0, "{filled}: {sizes:?}")
+            }
+        }
+
+        // Fills of the other orders are allocated independently
+        ledger.book_fill(fill(None, Some(8), Direction::Sell, 1));
+        assert_eq!(get_sizes(&ledger), [4, 3, 2]);
+        // Tagged fills bypass the rule
+        ledger.book_fill(fill(Some(AccountID(3)), Some(9), Direction::Buy, 2));
+        assert_eq!(get_sizes(&ledger), [4, 3, 4])
+    }
+
+    #[test]
+    fn test_bust_keeps_allocation_pro_rata() {
+        let mut ledger = Ledger::new().with_allocation_rule(1, pro_rata());
+        for _ in 0..10 {
+            ledger.book_fill(fill(None, Some(7), Direction::Buy, 1))
+        }
+        ledger.unwind_fill(fill(None, Some(7), Direction::Buy, 4));
+        assert_eq!(get_sizes(&ledger), [3, 2, 1]);
+        let reversals: Lots = ledger.get_allocations()[10..].iter()
+            .inspect(|allocation| assert_eq!(allocation.direction, Direction::Sell))
+            .map(|allocation| allocation.size)
+            .fold(Lots(0), |total, size| total + size);
+        assert_eq!(reversals, Lots(4));
+
+        // Subsequent fills of the order are allocated against the remaining size
+        ledger.book_fill(fill(None, Some(7), Direction::Buy, 4));
+        assert_eq!(get_sizes(&ledger), [5, 3, 2])
+    }
+
+    #[test]
+    fn test_position_modes() {
+        let mut ledger = Ledger::new()
+            .with_position_mode(1, AccountID(2), PositionMode::Hedging);
+        for account in [1, 2] {
+            ledger.book_fill(fill(Some(AccountID(account)), None, Direction::Buy, 3));
+            ledger.book_fill(fill(Some(AccountID(account)), None, Direction::Sell, 2));
+        }
+        let netting = ledger.get_position(1, AccountID(1), 2, pair());
+        assert_eq!((netting.size, netting.long, netting.short), (Lots(1), Lots(1), Lots(0)));
+        let hedging = ledger.get_position(1, AccountID(2), 2, pair());
+        assert_eq!((hedging.size, hedging.long, hedging.short), (Lots(1), Lots(3), Lots(2)));
+        assert_eq!(hedging.cash_flow, -100);
+
+        assert_eq!(ledger.close_out(1, AccountID(2), 2, pair()), Lots(2));
+        let hedging = ledger.get_position(1, AccountID(2), 2, pair());
+        assert_eq!((hedging.size, hedging.long, hedging.short), (Lots(1), Lots(1), Lots(0)));
+        assert_eq!(ledger.close_out(1, AccountID(1), 2, pair()), Lots(0))
+    }
+}
@@ -0,0 +1,796 @@
+use {
+    crate::{
+        concrete::{
+            message_protocol::{
+                broker::{
+                    reply::{BasicBrokerReply, BasicBrokerToTrader},
+                    request::BasicBrokerRequest,
+                },
+                exchange::reply::OrderState,
+                trader::request::BasicTraderRequest,
+            },
+            traded_pair::settlement::GetSettlementLag,
+            types::{ExecID, Lots, OrderID, Tick, TradeID},
+        },
+        types::{DateTime, Id},
+        utils::collections::HashSet,
+    },
+    std::{
+        cell::RefCell,
+        collections::BTreeMap,
+        fmt::Display,
+        fs::{create_dir_all, File},
+        io::Write,
+        path::Path,
+        rc::Rc,
+    },
+};
+
+/// [`OrderAuditTrail`] shared between the brokers and the traders of the single simulation thread.
+pub type SharedOrderAuditTrail<TraderID> = Rc<RefCell<OrderAuditTrail<TraderID>>>;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+/// Stage of the order routing the [`AuditRecord`] is made at.
+pub enum AuditStage {
+    /// Request is sent by the trader.
+    Sent,
+    /// Request is received by the broker.
+    Received,
+    /// Request is routed by the broker to the exchange.
+    Routed,
+    /// Reply is sent by the broker to the trader.
+    Replied,
+    /// Reply is delivered to the trader.
+    Delivered,
+}
+
+impl AuditStage {
+    fn as_str(self) -> &'static str {
+        match self {
+            AuditStage::Sent => "SENT",
+            AuditStage::Received => "RECEIVED",
+            AuditStage::Routed => "ROUTED",
+            AuditStage::Replied => "REPLIED",
+            AuditStage::Delivered => "DELIVERED"
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// State transition of the order along with the clocks of the agents it is observed by.
+pub struct AuditRecord {
+    /// Stage of the order routing.
+    pub stage: AuditStage,
+    /// Kind of the request or the reply, e.g. `PLACE_LIMIT_ORDER` or `ORDER_ACCEPTED`.
+    pub kind: &'static str,
+    /// Datetime of the trader the transition is observed at.
+    pub trader_dt: Option<DateTime>,
+    /// Datetime of the broker the transition is observed at.
+    pub broker_dt: Option<DateTime>,
+    /// Datetime of the exchange the reply is sent at.
+    pub exchange_dt: Option<DateTime>,
+    /// Price of the order or the fill.
+    pub price: Option<Tick>,
+    /// Size of the order or the fill.
+    pub size: Option<Lots>,
+    /// ID of the order assigned by the broker, i.e. the one routed to the exchange.
+    pub broker_order_id: Option<OrderID>,
+    /// ID of the order assigned by the exchange.
+    pub exchange_order_id: Option<OrderID>,
+    /// ID of the fill.
+    pub exec_id: Option<ExecID>,
+    /// ID of the trade printed to the tape.
+    pub trade_id: Option<TradeID>,
+    /// Reason of the rejection or the cancellation, or other details.
+    pub details: Option<String>,
+}
+
+/// Lifecycle records of the trader orders, keyed by the traders and the submitted order IDs,
+/// e.g. to debug the latencies and the rejections of the specific orders after the run.
+///
+/// The [`BasicBroker`](crate::concrete::broker::BasicBroker) records the requests it receives
+/// and routes and the replies it sends, at its own clock and at the clock of the exchange.
+/// Traders may add their own records at their clocks
+/// with the [`record_sent`](Self::record_sent) and [`record_delivered`](Self::record_delivered),
+/// so that the whole round trip is seen.
+/// Records of every order are kept in the order they are made.
+pub struct OrderAuditTrail<TraderID: Id> {
+    /// If set, only these orders are recorded.
+    orders: Option<HashSet<(TraderID, OrderID)>>,
+    records: BTreeMap<(TraderID, OrderID), Vec<AuditRecord>>,
+}
+
+impl<TraderID: Id> Default for OrderAuditTrail<TraderID> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<TraderID: Id> OrderAuditTrail<TraderID> {
+    /// Creates a new instance of the `OrderAuditTrail` recording all orders.
+    pub fn new() -> Self {
+        Self { orders: None, records: Default::default() }
+    }
+
+    /// Restricts the recording to the orders.
+    ///
+    /// # Arguments
+    ///
+    /// * `orders` — Traders along with the submitted IDs of their orders.
+    pub fn with_orders(mut self, orders: impl IntoIterator<Item=(TraderID, OrderID)>) -> Self {
+        self.orders = Some(orders.into_iter().collect());
+        self
+    }
+
+    /// Adds the record to the lifecycle of the order, unless it is not recorded.
+    ///
+    /// # Arguments
+    ///
+    /// * `trader_id` — Owner of the order.
+    /// * `order_id` — Submitted ID of the order.
+    /// * `record` — Record to add.
+    pub fn record(&mut self, trader_id: TraderID, order_id: OrderID, record: AuditRecord) {
+        if self.orders.as_ref().is_none_or(|orders| orders.contains(&(trader_id, order_id))) {
+            self.records.entry((trader_id, order_id)).or_default().push(record)
+        }
+    }
+
+    /// Records the request the trader sends, if it concerns an order.
+    ///
+    /// # Arguments
+    ///
+    /// * `trader_id` — Trader.
+    /// * `request` — Request sent.
+    /// * `trader_dt` — Current datetime of the trader.
+    pub fn record_sent<ExchangeID: Id, Symbol: Id, Settlement: GetSettlementLag>(
+        &mut self,
+        trader_id: TraderID,
+        request: &BasicTraderRequest<ExchangeID, Symbol, Settlement>,
+        trader_dt: DateTime)
+    {
+        if let Some((order_id, record)) = describe_trader_request(AuditStage::Sent, request) {
+            self.record(trader_id, order_id, AuditRecord { trader_dt: Some(trader_dt), ..record })
+        }
+    }
+
+    /// Records the reply delivered to the trader, if it concerns an order.
+    ///
+    /// # Arguments
+    ///
+    /// * `reply` — Reply delivered.
+    /// * `trader_dt` — Current datetime of the trader.
+    pub fn record_delivered<ExchangeID: Id, Symbol: Id, Settlement: GetSettlementLag>(
+        &mut self,
+        reply: &BasicBrokerToTrader<TraderID, ExchangeID, Symbol, Settlement>,
+        trader_dt: DateTime)
+    {
+        if let Some((order_id, record)) = describe_reply(AuditStage::Delivered, &reply.content) {
+            self.record(
+                reply.trader_id,
+                order_id,
+                AuditRecord { trader_dt: Some(trader_dt), ..record },
+            )
+        }
+    }
+
+    /// Returns the records of the order in the order they are made.
+    ///
+    /// # Arguments
+    ///
+    /// * `trader_id` — Owner of the order.
+    /// * `order_id` — Submitted ID of the order.
+    pub fn get_records(&self, trader_id: TraderID, order_id: OrderID) -> &[AuditRecord] {
+        self.records.get(&(trader_id, order_id)).map_or(&[], Vec::as_slice)
+    }
+
+    /// Writes the lifecycle of the order to the csv-file.
+    ///
+    /// # Arguments
+    ///
+    /// * `trader_id` — Owner of the order.
+    /// * `order_id` — Submitted ID of the order.
+    /// * `file` — Path to the csv-file to create.
+    pub fn write_order(&self, trader_id: TraderID, order_id: OrderID, file: impl AsRef<Path>) {
+        let file = file.as_ref();
+        let mut writer = File::create(file).unwrap_or_else(
+            |err| panic!("Cannot create file {file:?}. Error: {err}")
+        );
+        let mut write = |line: String| writeln!(writer, "{line}").unwrap_or_else(
+            |err| panic!("Cannot write to file {file:?}. Error: {err}")
+        );
+        write(
+            "STAGE,KIND,TRADER_DT,BROKER_DT,EXCHANGE_DT,PRICE,SIZE,\
+            BROKER_ORDER_ID,EXCHANGE_ORDER_ID,EXEC_ID,TRADE_ID,DETAILS".into()
+        );
+        for record in self.get_records(trader_id, order_id) {
+            write(
+                format!(
+                    "{},{},{},{},{},{},{},{},{},{},{},{}",
+                    record.stage.as_str(),
+                    record.kind,
+                    format_optional(record.trader_dt),
+                    format_optional(record.broker_dt),
+                    format_optional(record.exchange_dt),
+                    format_optional(record.price),
+                    format_optional(record.size),
+                    format_optional(record.broker_order_id),
+                    format_optional(record.exchange_order_id),
+                    format_optional(record.exec_id),
+                    format_optional(record.trade_id),
+                    format_optional(record.details.as_deref()),
+                )
+            )
+        }
+    }
+
+    /// Writes the lifecycle of every recorded order
+    /// to the csv-file named `{trader_id}_{order_id}.csv` within the directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `directory` — Directory to create the csv-files in. Is created if absent.
+    pub fn write_all(&self, directory: impl AsRef<Path>) {
+        let directory = directory.as_ref();
+        create_dir_all(directory).unwrap_or_else(
+            |err| panic!("Cannot create directory {directory:?}. Error: {err}")
+        );
+        for (trader_id, order_id) in self.records.keys() {
+            self.write_order(
+                *trader_id,
+                *order_id,
+                directory.join(format!("{trader_id}_{order_id}.csv")),
+            )
+        }
+    }
+}
+
+fn format_optional(value: Option<impl Display>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+fn create_record(
+    stage: AuditStage,
+    kind: &'static str,
+    price: Option<Tick>,
+    size: Option<Lots>,
+    details: Option<String>) -> AuditRecord
+{
+    AuditRecord {
+        stage,
+        kind,
+        trader_dt: None,
+        broker_dt: None,
+        exchange_dt: None,
+        price,
+        size,
+        broker_order_id: None,
+        exchange_order_id: None,
+        exec_id: None,
+        trade_id: None,
+        details,
+    }
+}
+
+/// Returns the submitted ID of the order the trader request concerns
+/// along with the record without the datetimes.
+pub(crate) fn describe_trader_request<ExchangeID: Id, Symbol: Id, Settlement: GetSettlementLag>(
+    stage: AuditStage,
+    request: &BasicTraderRequest<ExchangeID, Symbol, Settlement>) -> Option<(OrderID, AuditRecord)>
+{
+    let (order_id, record) = match request {
+        BasicTraderRequest::PlaceLimitOrder(request, exchange_id) => (
+            request.order_id,
+            create_record(
+                stage,
+                "PLACE_LIMIT_ORDER",
+                Some(request.price),
+                Some(request.size),
+                Some(format!("{:?} at exchange {exchange_id}", request.direction)),
+            )
+        ),
+        BasicTraderRequest::PlaceIcebergOrder(request, exchange_id) => (
+            request.order_id,
+            create_record(
+                stage,
+                "PLACE_ICEBERG_ORDER",
+                Some(request.price),
+                Some(request.size),
+                Some(
+                    format!(
+                        "{:?} at exchange {exchange_id}, display size {}",
+                        request.direction, request.display_size
+                    )
+                ),
+            )
+        ),
+        BasicTraderRequest::PlaceMarketOrder(request, exchange_id) => (
+            request.order_id,
+            create_record(
+                stage,
+                "PLACE_MARKET_ORDER",
+                None,
+                Some(request.size),
+                Some(format!("{:?} at exchange {exchange_id}", request.direction)),
+            )
+        ),
+        BasicTraderRequest::PlaceStopOrder(request, exchange_id) => (
+            request.order_id,
+            create_record(
+                stage,
+                "PLACE_STOP_ORDER",
+                None,
+                Some(request.size),
+                Some(
+                    format!(
+                        "{:?} at exchange {exchange_id}, stop price {}",
+                        request.direction, request.stop_price
+                    )
+                ),
+            )
+        ),
+        BasicTraderRequest::PlaceStopLimitOrder(request, exchange_id) => (
+            request.order_id,
+            create_record(
+                stage,
+                "PLACE_STOP_LIMIT_ORDER",
+                Some(request.price),
+                Some(request.size),
+                Some(
+                    format!(
+                        "{:?} at exchange {exchange_id}, stop price {}",
+                        request.direction, request.stop_price
+                    )
+                ),
+            )
+        ),
+        BasicTraderRequest::CancelLimitOrder(request, _) => (
+            request.order_id,
+            create_record(stage, "CANCEL_LIMIT_ORDER", None, None, None)
+        ),
+        BasicTraderRequest::ReduceOrderSize(request, _) => (
+            request.order_id,
+            create_record(stage, "REDUCE_ORDER_SIZE", None, Some(request.size), None)
+        ),
+        BasicTraderRequest::QueryOrderStatus(request, _) => (
+            request.order_id,
+            create_record(stage, "QUERY_ORDER_STATUS", None, None, None)
+        ),
+        BasicTraderRequest::RequestQuote(..)
+        | BasicTraderRequest::AcceptQuote(..)
+        | BasicTraderRequest::Subscribe(..)
+        | BasicTraderRequest::Heartbeat(..)
+        | BasicTraderRequest::CloseOutPosition(..) => return None
+    };
+    Some((order_id, record))
+}
+
+/// Returns the internal ID of the order the broker request concerns
+/// along with the record without the datetimes.
+pub(crate) fn describe_broker_request<Symbol: Id, Settlement: GetSettlementLag>(
+    request: &BasicBrokerRequest<Symbol, Settlement>) -> Option<(OrderID, AuditRecord)>
+{
+    let stage = AuditStage::Routed;
+    let (order_id, mut record) = match request {
+        BasicBrokerRequest::PlaceLimitOrder(request) => (
+            request.order_id,
+            create_record(stage, "PLACE_LIMIT_ORDER", Some(request.price), Some(request.size), None)
+        ),
+        BasicBrokerRequest::PlaceIcebergOrder(request) => (
+            request.order_id,
+            create_record(
+                stage,
+                "PLACE_ICEBERG_ORDER",
+                Some(request.price),
+                Some(request.size),
+                Some(format!("Display size {}", request.display_size)),
+            )
+        ),
+        BasicBrokerRequest::PlaceMarketOrder(request) => (
+            request.order_id,
+            create_record(stage, "PLACE_MARKET_ORDER", None, Some(request.size), None)
+        ),
+        BasicBrokerRequest::PlaceStopOrder(request) => (
+            request.order_id,
+            create_record(
+                stage,
+                "PLACE_STOP_ORDER",
+                None,
+                Some(request.size),
+                Some(format!("Stop price {}", request.stop_price)),
+            )
+        ),
+        BasicBrokerRequest::PlaceStopLimitOrder(request) => (
+            request.order_id,
+            create_record(
+                stage,
+                "PLACE_STOP_LIMIT_ORDER",
+                Some(request.price),
+                Some(request.size),
+                Some(format!("Stop price {}", request.stop_price)),
+            )
+        ),
+        BasicBrokerRequest::CancelLimitOrder(request) => (
+            request.order_id,
+            create_record(stage, "CANCEL_LIMIT_ORDER", None, None, None)
+        ),
+        BasicBrokerRequest::ReduceOrderSize(request) => (
+            request.order_id,
+            create_record(stage, "REDUCE_ORDER_SIZE", None, Some(request.size), None)
+        ),
+        BasicBrokerRequest::QueryOrderStatus(request) => (
+            request.order_id,
+            create_record(stage, "QUERY_ORDER_STATUS", None, None, None)
+        ),
+        BasicBrokerRequest::RequestRecoverySnapshot(_)
+        | BasicBrokerRequest::Heartbeat(_)
+        | BasicBrokerRequest::ReportOffBookTrade(_) => return None
+    };
+    record.broker_order_id = Some(order_id);
+    Some((order_id, record))
+}
+
+/// Returns the submitted ID of the order the broker reply concerns
+/// along with the record without the datetimes.
+pub(crate) fn describe_reply<Symbol: Id, Settlement: GetSettlementLag>(
+    stage: AuditStage,
+    reply: &BasicBrokerReply<Symbol, Settlement>) -> Option<(OrderID, AuditRecord)>
+{
+    let (order_id, mut record) = match reply {
+        BasicBrokerReply::OrderAccepted(accepted) => (
+            accepted.order_id,
+            create_record(stage, "ORDER_ACCEPTED", None, None, None)
+        ),
+        BasicBrokerReply::OrderPlacementDiscarded(discarded) => (
+            discarded.order_id,
+            create_record(
+                stage,
+                "ORDER_PLACEMENT_DISCARDED",
+                None,
+                None,
+                Some(format!("{:?}", discarded.reason)),
+            )
+        ),
+        BasicBrokerReply::OrderPriceAdjusted(adjusted) => (
+            adjusted.order_id,
+            create_record(
+                stage,
+                "ORDER_PRICE_ADJUSTED",
+                Some(adjusted.price),
+                None,
+                Some(format!("Original price {}", adjusted.original_price)),
+            )
+        ),
+        BasicBrokerReply::OrderPartiallyExecuted(executed) => (
+            executed.order_id,
+            create_record(
+                stage,
+                "ORDER_PARTIALLY_EXECUTED",
+                Some(executed.price),
+                Some(executed.size),
+                None,
+            )
+        ),
+        BasicBrokerReply::OrderExecuted(executed) => (
+            executed.order_id,
+            create_record(stage, "ORDER_EXECUTED", Some(executed.price), Some(executed.size), None)
+        ),
+        BasicBrokerReply::ShadowOrderPartiallyExecuted(executed) => (
+            executed.order_id,
+            create_record(
+                stage,
+                "SHADOW_ORDER_PARTIALLY_EXECUTED",
+                Some(executed.price),
+                Some(executed.size),
+                None,
+            )
+        ),
+        BasicBrokerReply::ShadowOrderExecuted(executed) => (
+            executed.order_id,
+            create_record(
+                stage,
+                "SHADOW_ORDER_EXECUTED",
+                Some(executed.price),
+                Some(executed.size),
+                None,
+            )
+        ),
+        BasicBrokerReply::GhostOrderPartiallyExecuted(executed) => (
+            executed.order_id,
+            create_record(
+                stage,
+                "GHOST_ORDER_PARTIALLY_EXECUTED",
+                Some(executed.price),
+                Some(executed.size),
+                None,
+            )
+        ),
+        BasicBrokerReply::GhostOrderExecuted(executed) => (
+            executed.order_id,
+            create_record(
+                stage,
+                "GHOST_ORDER_EXECUTED",
+                Some(executed.price),
+                Some(executed.size),
+                None,
+            )
+        ),
+        BasicBrokerReply::MarketOrderNotFullyExecuted(not_fully_executed) => (
+            not_fully_executed.order_id,
+            create_record(
+                stage,
+                "MARKET_ORDER_NOT_FULLY_EXECUTED",
+                None,
+                Some(not_fully_executed.remaining_size),
+                None,
+            )
+        ),
+        BasicBrokerReply::StopOrderTriggered(triggered) => (
+            triggered.order_id,
+            create_record(
+                stage,
+                "STOP_ORDER_TRIGGERED",
+                triggered.limit_price,
+                None,
+                Some(format!("Trigger price {}", triggered.trigger_price)),
+            )
+        ),
+        BasicBrokerReply::OrderCancelled(cancelled) => (
+            cancelled.order_id,
+            create_record(
+                stage,
+                "ORDER_CANCELLED",
+                None,
+                None,
+                Some(format!("{:?}", cancelled.reason)),
+            )
+        ),
+        BasicBrokerReply::OrderCarriedOver(carried_over) => (
+            carried_over.order_id,
+            create_record(
+                stage,
+                "ORDER_CARRIED_OVER",
+                None,
+                None,
+                carried_over.expiration.map(|expiration| format!("Expires at {expiration}")),
+            )
+        ),
+        BasicBrokerReply::CannotCancelOrder(cannot_cancel) => (
+            cannot_cancel.order_id,
+            create_record(
+                stage,
+                "CANNOT_CANCEL_ORDER",
+                None,
+                None,
+                Some(format!("{:?}", cannot_cancel.reason)),
+            )
+        ),
+        BasicBrokerReply::OrderSizeReduced(reduced) => (
+            reduced.order_id,
+            create_record(
+                stage,
+                "ORDER_SIZE_REDUCED",
+                None,
+                Some(reduced.size),
+                Some(format!("Remaining size {}", reduced.remaining_size)),
+            )
+        ),
+        BasicBrokerReply::CannotReduceOrderSize(cannot_reduce) => (
+            cannot_reduce.order_id,
+            create_record(
+                stage,
+                "CANNOT_REDUCE_ORDER_SIZE",
+                None,
+                None,
+                Some(format!("{:?}", cannot_reduce.reason)),
+            )
+        ),
+        BasicBrokerReply::TradeBusted(busted) => (
+            busted.order_id,
+            create_record(stage, "TRADE_BUSTED", Some(busted.price), Some(busted.size), None)
+        ),
+        BasicBrokerReply::OrderStatus(status) => {
+            let record = match status.state {
+                OrderState::Resting { direction, price, remaining_size } => create_record(
+                    stage,
+                    "ORDER_STATUS",
+                    Some(price),
+                    Some(remaining_size),
+                    Some(format!("Resting {direction:?}")),
+                ),
+                OrderState::PendingStop { direction, stop_price, remaining_size } => {
+                    create_record(
+                        stage,
+                        "ORDER_STATUS",
+                        None,
+                        Some(remaining_size),
+                        Some(format!("Pending {direction:?} stop at {stop_price}")),
+                    )
+                }
+                OrderState::Closed => {
+                    create_record(stage, "ORDER_STATUS", None, None, Some("Closed".into()))
+                }
+                OrderState::Unknown => {
+                    create_record(stage, "ORDER_STATUS", None, None, Some("Unknown".into()))
+                }
+            };
+            (status.order_id, record)
+        }
+        // Buy-ins are reported by the internal IDs
+        BasicBrokerReply::BuyInPlaced(_)
+        | BasicBrokerReply::BuyInExecuted(_)
+        | BasicBrokerReply::BuyInNotFullyExecuted(_)
+        | BasicBrokerReply::BorrowRecalled(_)
+        | BasicBrokerReply::ExchangeEventNotification(_)
+        | BasicBrokerReply::FirmQuote(_)
+        | BasicBrokerReply::QuoteTraded(_)
+        | BasicBrokerReply::QuoteRejected(_)
+        | BasicBrokerReply::RecoverySnapshot(_)
+        | BasicBrokerReply::SubscriptionRejected(_)
+        | BasicBrokerReply::SessionDown
+        | BasicBrokerReply::SessionUp
+        | BasicBrokerReply::Heartbeat(_)
+        | BasicBrokerReply::HeartbeatMissed
+        | BasicBrokerReply::HeartbeatRestored
+        | BasicBrokerReply::PortfolioSnapshot(_)
+        | BasicBrokerReply::PositionClosedOut(_) => return None
+    };
+    match reply {
+        BasicBrokerReply::OrderAccepted(accepted) => {
+            record.broker_order_id = Some(accepted.broker_order_id);
+            record.exchange_order_id = accepted.exchange_order_id
+        }
+        BasicBrokerReply::OrderPartiallyExecuted(executed)
+        | BasicBrokerReply::ShadowOrderPartiallyExecuted(executed)
+        | BasicBrokerReply::GhostOrderPartiallyExecuted(executed) => {
+            record.broker_order_id = Some(executed.broker_order_id);
+            record.exchange_order_id = Some(executed.exchange_order_id);
+            record.exec_id = Some(executed.exec_id);
+            record.trade_id = Some(executed.trade_id)
+        }
+        BasicBrokerReply::OrderExecuted(executed)
+        | BasicBrokerReply::ShadowOrderExecuted(executed)
+        | BasicBrokerReply::GhostOrderExecuted(executed) => {
+            record.broker_order_id = Some(executed.broker_order_id);
+            record.exchange_order_id = Some(executed.exchange_order_id);
+            record.exec_id = Some(executed.exec_id);
+            record.trade_id = Some(executed.trade_id)
+        }
+        BasicBrokerReply::TradeBusted(busted) => {
+            record.exec_id = Some(busted.exec_id);
+            record.trade_id = Some(busted.trade_id)
+        }
+        _ => {}
+    }
+    Some((order_id, record))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            concrete::{
+                heartbeat::Heartbeat,
+                message_protocol::exchange::reply::OrderAccepted,
+                order::{LimitOrderPlacingRequest, TimeInForce},
+                traded_pair::settlement::concrete::SpotSettlement,
+                types::Direction,
+            },
+            test_utils::{dt, pair},
+        },
+        std::{env::temp_dir, fs::read_to_string},
+    };
+
+    type Trail = OrderAuditTrail<u8>;
+    type Request = BasicTraderRequest<u8, &'static str, SpotSettlement>;
+
+    fn place(order_id: u64) -> Request {
+        BasicTraderRequest::PlaceLimitOrder(
+            LimitOrderPlacingRequest {
+                traded_pair: pair(),
+                order_id: OrderID(order_id),
+                direction: Direction::Buy,
+                price: Tick(100),
+                size: Lots(3),
+                dummy: false,
+                time_in_force: TimeInForce::Day,
+            },
+            0,
+        )
+    }
+
+    fn accepted(trader_id: u8, order_id: u64)
+        -> BasicBrokerToTrader<u8, u8, &'static str, SpotSettlement>
+    {
+        BasicBrokerToTrader {
+            trader_id,
+            exchange_id: 0,
+            event_dt: dt(1),
+            content: BasicBrokerReply::OrderAccepted(
+                OrderAccepted {
+                    traded_pair: pair(),
+                    order_id: OrderID(order_id),
+                    broker_order_id: OrderID(10),
+                    exchange_order_id: Some(OrderID(20)),
+                }
+            ),
+        }
+    }
+
+    #[test]
+    fn test_round_trip_is_recorded_in_order() {
+        let mut trail = Trail::new();
+        trail.record_sent(1, &place(5), dt(0));
+        trail.record(
+            1,
+            OrderID(5),
+            AuditRecord {
+                broker_dt: Some(dt(1)),
+                ..create_record(AuditStage::Received, "PLACE_LIMIT_ORDER", None, None, None)
+            },
+        );
+        trail.record_delivered(&accepted(1, 5), dt(2));
+
+        let records = trail.get_records(1, OrderID(5));
+        let stages: Vec<_> = records.iter().map(|record| record.stage).collect();
+        assert_eq!(stages, [AuditStage::Sent, AuditStage::Received, AuditStage::Delivered]);
+
+        let sent = &records[0];
+        assert_eq!(sent.kind, "PLACE_LIMIT_ORDER");
+        assert_eq!((sent.trader_dt, sent.broker_dt), (Some(dt(0)), None));
+        assert_eq!((sent.price, sent.size), (Some(Tick(100)), Some(Lots(3))));
+        assert_eq!(sent.details.as_deref(), Some("Buy at exchange 0"));
+
+        let delivered = &records[2];
+        assert_eq!(delivered.kind, "ORDER_ACCEPTED");
+        assert_eq!(delivered.trader_dt, Some(dt(2)));
+        assert_eq!(delivered.broker_order_id, Some(OrderID(10)));
+        assert_eq!(delivered.exchange_order_id, Some(OrderID(20)))
+    }
+
+    #[test]
+    fn test_only_selected_orders_are_recorded() {
+        let mut trail = Trail::new().with_orders([(1, OrderID(5))]);
+        trail.record_sent(1, &place(5), dt(0));
+        trail.record_sent(1, &place(6), dt(0));
+        trail.record_sent(2, &place(5), dt(0));
+        trail.record_delivered(&accepted(2, 5), dt(1));
+
+        assert_eq!(trail.get_records(1, OrderID(5)).len(), 1);
+        assert!(trail.get_records(1, OrderID(6)).is_empty());
+        assert!(trail.get_records(2, OrderID(5)).is_empty())
+    }
+
+    #[test]
+    fn test_requests_without_orders_are_skipped() {
+        let mut trail = Trail::new();
+        let heartbeat = Request::Heartbeat(Heartbeat { sent_dt: dt(0) }, 0);
+        trail.record_sent(1, &heartbeat, dt(0));
+        assert!(trail.records.is_empty())
+    }
+
+    #[test]
+    fn test_write_all() {
+        let mut trail = Trail::new();
+        trail.record_sent(1, &place(5), dt(0));
+        trail.record_delivered(&accepted(1, 5), dt(2));
+        trail.record_sent(2, &place(7), dt(1));
+
+        let dir = temp_dir().join(format!("trading_backtester_audit_{}", std::process::id()));
+        trail.write_all(&dir);
+
+        let content = read_to_string(dir.join("1_5.csv")).unwrap();
+        let lines: Vec<_> = content.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "STAGE,KIND,TRADER_DT,BROKER_DT,EXCHANGE_DT,PRICE,SIZE,\
+                BROKER_ORDER_ID,EXCHANGE_ORDER_ID,EXEC_ID,TRADE_ID,DETAILS",
+                "SENT,PLACE_LIMIT_ORDER,2022-01-03 10:00:00,,,100,3,,,,,Buy at exchange 0",
+                "DELIVERED,ORDER_ACCEPTED,2022-01-03 10:00:02,,,,,10,20,,,",
+            ]
+        );
+        assert_eq!(read_to_string(dir.join("2_7.csv")).unwrap().lines().count(), 2);
+        assert!(!dir.join("2_5.csv").exists())
+    }
+}
@@ -0,0 +1,1038 @@
+use {
+    crate::{
+        concrete::{
+            latency::ConstantLatency,
+            message_protocol::{
+                broker::{
+                    reply::{
+                        BasicBrokerReply,
+                        BasicBrokerToTrader,
+                        CancellationReason,
+                        CannotCancelOrder,
+                        FirmQuote,
+                        InabilityToCancelReason,
+                        OrderCancelled,
+                        OrderPlacementDiscarded,
+                        PlacementDiscardingReason,
+                        PositionClosedOut,
+                        QuoteRejected,
+                        QuoteRejectionReason,
+                        QuoteTraded,
+                        SubscriptionRejected,
+                        SubscriptionRejectionReason,
+                    },
+                    request::{BasicBrokerRequest, BasicBrokerToExchange},
+                    wakeup::BasicBrokerToItself,
+                },
+                exchange::reply::{
+                    BasicExchangeToBroker,
+                    BasicExchangeToBrokerReply,
+                    ExchangeEventNotification,
+                    OffBookTradeInfo,
+                    OrderAccepted,
+                    OrderExecuted,
+                    OrderSizeReduced,
+                    OrderState,
+                    OrderStatus,
+                },
+                replay::request::BasicReplayToBroker,
+                trader::request::{BasicTraderRequest, BasicTraderToBroker},
+            },
+            order::LimitOrderPlacingRequest,
+            traded_pair::{settlement::GetSettlementLag, TradedPair},
+            trader::subscriptions::{SubscriptionConfig, SubscriptionList},
+            types::{Direction, ExecID, Fee, Lots, OrderID, Tick, TradeID},
+        },
+        interface::{
+            broker::{Broker, BrokerAction, BrokerActionKind},
+            latency::Latent,
+        },
+        kernel::LatentActionProcessor,
+        types::{Agent, Date, DateTime, Id, NanoDuration, Named, Nothing, TimeSync},
+        utils::{collections::{HashMap, HashSet}, queue::MessageReceiver},
+    },
+    rand::Rng,
+    std::cmp::Reverse,
+};
+
+/// Instrument quoted by the [`DealerBroker`].
+type Instrument<ExchangeID, Symbol, Settlement> = (ExchangeID, TradedPair<Symbol, Settlement>);
+
+/// Limit order resting at the [`DealerBroker`] along with its arrival sequence number.
+type RestingOrder<ExchangeID, Symbol, Settlement> = (
+    ExchangeID,
+    LimitOrderPlacingRequest<Symbol, Settlement>,
+    u64
+);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Pricing parameters of the [`DealerBroker`].
+pub struct DealerConfig {
+    /// Distance between the reference price and each side of the dealer quote.
+    pub half_spread: Tick,
+    /// Shift of the dealer quote, in ticks, per lot of the dealer inventory.
+    /// Positive values make the long dealer quote lower and the short one higher.
+    pub skew_per_lot: f64,
+    /// Maximum absolute inventory of the dealer per instrument. Unlimited if `None`.
+    pub max_inventory: Option<Lots>,
+    /// Time the firm quote is valid for since it is issued.
+    pub quote_validity: NanoDuration,
+}
+
+/// [`Broker`] that fills the orders of the traders from its own quotes
+/// instead of routing them to the exchanges.
+///
+/// The quotes are built around the reference price of the instrument, i.e. the mid-price
+/// of the last order book snapshot or the last trade price, whichever is received later.
+/// Marketable orders are filled at once at the dealer quote.
+/// Other limit orders rest at the dealer until its quote crosses them.
+pub struct DealerBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
+    where BrokerID: Id,
+          TraderID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    current_dt: DateTime,
+    name: BrokerID,
+    config: DealerConfig,
+
+    registered_traders: HashSet<TraderID>,
+    registered_exchanges: HashSet<ExchangeID>,
+    /// Traders subscribed to the market data of the instruments.
+    subscribers: HashMap<
+        Instrument<ExchangeID, Symbol, Settlement>,
+        Vec<(TraderID, SubscriptionList)>
+    >,
+
+    reference_prices: HashMap<Instrument<ExchangeID, Symbol, Settlement>, Tick>,
+    inventory: HashMap<Instrument<ExchangeID, Symbol, Settlement>, Lots>,
+    /// Limit orders resting at the dealer.
+    resting_orders: HashMap<(TraderID, OrderID), RestingOrder<ExchangeID, Symbol, Settlement>>,
+    /// Arrival sequence number of the next resting order.
+    next_arrival: u64,
+    firm_quotes: HashMap<(TraderID, OrderID), (ExchangeID, FirmQuote<Symbol, Settlement>)>,
+    /// ID of the next dealer trade. Each trade fills the single order,
+    /// so it also serves as the ID of the fill.
+    next_trade_id: TradeID,
+
+    /// Delay of reporting the dealer trades to the tapes of the exchanges.
+    tape_delay: Option<NanoDuration>,
+    /// Dealer trades that are not reported to the exchanges yet.
+    pending_prints: Vec<(ExchangeID, OffBookTradeInfo<Symbol, Settlement>)>,
+}
+
+impl<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
+TimeSync
+for DealerBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
+    where BrokerID: Id,
+          TraderID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    fn current_datetime_mut(&mut self) -> &mut DateTime {
+        &mut self.current_dt
+    }
+}
+
+impl<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
+Named<BrokerID>
+for DealerBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
+    where BrokerID: Id,
+          TraderID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    fn get_name(&self) -> BrokerID {
+        self.name
+    }
+}
+
+impl<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
+Agent
+for DealerBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
+    where BrokerID: Id,
+          TraderID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    type Action = BrokerAction<
+        Nothing,
+        BasicBrokerToExchange<ExchangeID, Symbol, Settlement>,
+        BasicBrokerToTrader<TraderID, ExchangeID, Symbol, Settlement>,
+        BasicBrokerToItself<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
+    >;
+}
+
+impl<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
+Latent
+for DealerBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
+    where BrokerID: Id,
+          TraderID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    type OuterID = ExchangeID;
+    type LatencyGenerator = ConstantLatency<ExchangeID, 0, 0>;
+
+    fn get_latency_generator(&self) -> Self::LatencyGenerator {
+        ConstantLatency::<ExchangeID, 0, 0>::new()
+    }
+}
+
+impl<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
+Broker
+for DealerBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
+    where BrokerID: Id,
+          TraderID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    type BrokerID = BrokerID;
+    type TraderID = TraderID;
+    type ExchangeID = ExchangeID;
+
+    type R2B = BasicReplayToBroker<BrokerID, ExchangeID, Symbol, Settlement>;
+    type E2B = BasicExchangeToBroker<BrokerID, Symbol, Settlement>;
+    type T2B = BasicTraderToBroker<BrokerID, ExchangeID, Symbol, Settlement>;
+    type B2R = Nothing;
+    type B2E = BasicBrokerToExchange<ExchangeID, Symbol, Settlement>;
+    type B2T = BasicBrokerToTrader<TraderID, ExchangeID, Symbol, Settlement>;
+    type B2B = BasicBrokerToItself<BrokerID, TraderID, ExchangeID, Symbol, Settlement>;
+    type SubCfg = SubscriptionConfig<ExchangeID, Symbol, Settlement>;
+
+    fn wakeup<KerMsg: Ord>(
+        &mut self,
+        _: MessageReceiver<KerMsg>,
+        _: impl LatentActionProcessor<Self::Action, Self::ExchangeID, KerMsg=KerMsg>,
+        _: BasicBrokerToItself<BrokerID, TraderID, ExchangeID, Symbol, Settlement>,
+        _: &mut impl Rng,
+    ) {
+        unreachable!("{} :: Broker wakeups are not planned", self.current_dt)
+    }
+
+    fn process_trader_request<KerMsg: Ord>(
+        &mut self,
+        mut message_receiver: MessageReceiver<KerMsg>,
+        mut action_processor: impl LatentActionProcessor<Self::Action, Self::ExchangeID, KerMsg=KerMsg>,
+        request: BasicTraderToBroker<BrokerID, ExchangeID, Symbol, Settlement>,
+        trader_id: TraderID,
+        rng: &mut impl Rng,
+    ) {
+        let current_dt = self.current_dt;
+        let account = request.account;
+        let (exchange_id, replies) = match request.content {
+            BasicTraderRequest::CancelLimitOrder(request, exchange_id) => {
+                let resting_order = self.resting_orders.remove(&(trader_id, request.order_id));
+                let reply = if resting_order.is_some() {
+                    BasicBrokerReply::OrderCancelled(
+                        OrderCancelled {
+                            traded_pair: request.traded_pair,
+                            order_id: request.order_id,
+                            reason: CancellationReason::TraderRequested,
+                        }
+                    )
+                } else {
+                    BasicBrokerReply::CannotCancelOrder(
+                        CannotCancelOrder {
+                            traded_pair: request.traded_pair,
+                            order_id: request.order_id,
+                            reason: InabilityToCancelReason::OrderHasNotBeenSubmitted,
+                        }
+                    )
+                };
+                (exchange_id, vec![reply])
+            }
+            BasicTraderRequest::ReduceOrderSize(request, exchange_id) => {
+                let key = (trader_id, request.order_id);
+                let reply = match self.resting_orders.get_mut(&key) {
+                    Some((_, order, _)) if request.size < order.size => {
+                        order.size -= request.size;
+                        BasicBrokerReply::OrderSizeReduced(
+                            OrderSizeReduced {
+                                traded_pair: request.traded_pair,
+                                order_id: request.order_id,
+                                size: request.size,
+                                remaining_size: order.size,
+                            }
+                        )
+                    }
+                    Some(_) => {
+                        self.resting_orders.remove(&key);
+                        BasicBrokerReply::OrderCancelled(
+                            OrderCancelled {
+                                traded_pair: request.traded_pair,
+                                order_id: request.order_id,
+                                reason: CancellationReason::TraderRequested,
+                            }
+                        )
+                    }
+                    None => BasicBrokerReply::CannotReduceOrderSize(
+                        CannotCancelOrder {
+                            traded_pair: request.traded_pair,
+                            order_id: request.order_id,
+                            reason: InabilityToCancelReason::OrderHasNotBeenSubmitted,
+                        }
+                    )
+                };
+                (exchange_id, vec![reply])
+            }
+            // Closed orders are not kept, so they are reported as unknown
+            BasicTraderRequest::QueryOrderStatus(request, exchange_id) => {
+                let state = self.resting_orders.get(&(trader_id, request.order_id)).map_or(
+                    OrderState::Unknown,
+                    |(_, order, _)| OrderState::Resting {
+                        direction: order.direction,
+                        price: order.price,
+                        remaining_size: order.size,
+                    },
+                );
+                let reply = BasicBrokerReply::OrderStatus(
+                    OrderStatus {
+                        traded_pair: request.traded_pair,
+                        order_id: request.order_id,
+                        state,
+                    }
+                );
+                (exchange_id, vec![reply])
+            }
+            BasicTraderRequest::Heartbeat(heartbeat, exchange_id) => {
+                (exchange_id, vec![BasicBrokerReply::Heartbeat(heartbeat)])
+            }
+            // Dealer books every trade as the netting position, so there is nothing to close out
+            BasicTraderRequest::CloseOutPosition(traded_pair, exchange_id) => {
+                let reply = BasicBrokerReply::PositionClosedOut(
+                    PositionClosedOut {
+                        traded_pair,
+                        account: account.unwrap_or_default(),
+                        size: Lots(0),
+                    }
+                );
+                (exchange_id, vec![reply])
+            }
+            // Dealer does not request the order books, so it cannot recover the late subscribers
+            BasicTraderRequest::Subscribe(request, exchange_id) => {
+                let reply = BasicBrokerReply::SubscriptionRejected(
+                    SubscriptionRejected {
+                        traded_pair: request.traded_pair,
+                        reason: SubscriptionRejectionReason::NotSupported,
+                    }
+                );
+                (exchange_id, vec![reply])
+            }
+            BasicTraderRequest::PlaceLimitOrder(request, exchange_id) => {
+                (exchange_id, self.place_limit_order(trader_id, exchange_id, request))
+            }
+            // Dealer does not display the resting orders, so the icebergs rest as the limit ones
+            BasicTraderRequest::PlaceIcebergOrder(request, exchange_id) => {
+                (exchange_id, self.place_limit_order(trader_id, exchange_id, request.into()))
+            }
+            BasicTraderRequest::PlaceMarketOrder(request, exchange_id) => {
+                let replies = self.place_order(
+                    exchange_id,
+                    request.traded_pair,
+                    request.order_id,
+                    request.direction,
+                    request.size,
+                    None,
+                );
+                (exchange_id, replies)
+            }
+            // Dealer fills the orders at once, so it has no trades to trigger the stops
+            BasicTraderRequest::PlaceStopOrder(request, exchange_id) => {
+                let reply = BasicBrokerReply::OrderPlacementDiscarded(
+                    OrderPlacementDiscarded {
+                        traded_pair: request.traded_pair,
+                        order_id: request.order_id,
+                        reason: PlacementDiscardingReason::OrderTypeNotSupported,
+                    }
+                );
+                (exchange_id, vec![reply])
+            }
+            BasicTraderRequest::PlaceStopLimitOrder(request, exchange_id) => {
+                let reply = BasicBrokerReply::OrderPlacementDiscarded(
+                    OrderPlacementDiscarded {
+                        traded_pair: request.traded_pair,
+                        order_id: request.order_id,
+                        reason: PlacementDiscardingReason::OrderTypeNotSupported,
+                    }
+                );
+                (exchange_id, vec![reply])
+            }
+            BasicTraderRequest::RequestQuote(request, exchange_id) => {
+                let reject = |reason| BasicBrokerReply::QuoteRejected(
+                    QuoteRejected {
+                        traded_pair: request.traded_pair,
+                        request_id: request.request_id,
+                        reason,
+                    }
+                );
+                let key = (trader_id, request.request_id);
+                let reply = if !self.registered_exchanges.contains(&exchange_id) {
+                    reject(QuoteRejectionReason::BrokerNotConnectedToExchange)
+                } else if request.size <= Lots(0) {
+                    reject(QuoteRejectionReason::ZeroSize)
+                } else if matches!(
+                    self.firm_quotes.get(&key),
+                    Some((_, quote)) if quote.valid_until >= current_dt
+                ) {
+                    reject(QuoteRejectionReason::RequestWithSuchIDAlreadySubmitted)
+                } else if let Some((bid, ask)) = self.get_quote(exchange_id, request.traded_pair) {
+                    let quote = FirmQuote {
+                        traded_pair: request.traded_pair,
+                        request_id: request.request_id,
+                        direction: request.direction,
+                        price: match request.direction {
+                            Direction::Buy => ask,
+                            Direction::Sell => bid
+                        },
+                        size: request.size,
+                        valid_until: current_dt + self.config.quote_validity,
+                    };
+                    self.firm_quotes.insert(key, (exchange_id, quote));
+                    BasicBrokerReply::FirmQuote(quote)
+                } else {
+                    reject(QuoteRejectionReason::NoReferencePrice)
+                };
+                (exchange_id, vec![reply])
+            }
+            BasicTraderRequest::AcceptQuote(acceptance, exchange_id) => {
+                let reject = |reason| BasicBrokerReply::QuoteRejected(
+                    QuoteRejected {
+                        traded_pair: acceptance.traded_pair,
+                        request_id: acceptance.request_id,
+                        reason,
+                    }
+                );
+                let reply = match self.firm_quotes.remove(&(trader_id, acceptance.request_id)) {
+                    Some((quote_exchange_id, quote))
+                    if quote_exchange_id == exchange_id
+                        && quote.traded_pair == acceptance.traded_pair => {
+                        if quote.valid_until < current_dt {
+                            reject(QuoteRejectionReason::QuoteExpired)
+                        } else {
+                            self.book_trade(
+                                (exchange_id, quote.traded_pair),
+                                quote.direction,
+                                quote.price,
+                                quote.size,
+                            );
+                            BasicBrokerReply::QuoteTraded(
+                                QuoteTraded {
+                                    traded_pair: quote.traded_pair,
+                                    request_id: quote.request_id,
+                                    direction: quote.direction,
+                                    price: quote.price,
+                                    size: quote.size,
+                                }
+                            )
+                        }
+                    }
+                    _ => reject(QuoteRejectionReason::NoSuchQuote)
+                };
+                (exchange_id, vec![reply])
+            }
+        };
+        let actions = replies.into_iter()
+            .map(|reply| Self::create_broker_reply(trader_id, exchange_id, current_dt, reply))
+            .chain(self.report_pending_prints());
+        let mut latency_generator = self.get_latency_generator();
+        message_receiver.extend(
+            actions.map(|action| action_processor.process_action(action, &mut latency_generator, rng))
+        );
+        self.update_latency_generator(latency_generator)
+    }
+
+    fn process_exchange_reply<KerMsg: Ord>(
+        &mut self,
+        mut message_receiver: MessageReceiver<KerMsg>,
+        mut action_processor: impl LatentActionProcessor<Self::Action, Self::ExchangeID, KerMsg=KerMsg>,
+        reply: BasicExchangeToBroker<BrokerID, Symbol, Settlement>,
+        exchange_id: ExchangeID,
+        rng: &mut impl Rng,
+    ) {
+        let notification = if let BasicExchangeToBrokerReply::ExchangeEventNotification(
+            notification
+        ) = reply.content {
+            notification
+        } else {
+            unreachable!(
+                "{} :: DealerBroker does not send orders to the exchanges. Got: {:?}",
+                self.current_dt, reply.content
+            )
+        };
+        let mut actions = Vec::new();
+        let traded_pair = match &notification {
+            ExchangeEventNotification::TradeExecuted(trade) => {
+                self.reference_prices.insert((exchange_id, trade.traded_pair), trade.price);
+                Some(trade.traded_pair)
+            }
+            ExchangeEventNotification::ObSnapshot(snapshot) => {
+                let best_bid = snapshot.state.bids.first().map(|(price, _)| *price);
+                let best_ask = snapshot.state.asks.first().map(|(price, _)| *price);
+                let reference = match (best_bid, best_ask) {
+                    (Some(bid), Some(ask)) => Some(Tick((bid.0 + ask.0).div_euclid(2))),
+                    (bid, ask) => bid.or(ask)
+                };
+                if let Some(reference) = reference {
+                    self.reference_prices.insert((exchange_id, snapshot.traded_pair), reference);
+                }
+                Some(snapshot.traded_pair)
+            }
+            _ => None
+        };
+        if let Some(traded_pair) = traded_pair {
+            actions.extend(self.fill_resting_orders(exchange_id, traded_pair, reply.exchange_dt));
+            actions.extend(self.report_pending_prints())
+        }
+
+        let subscription = match &notification {
+            ExchangeEventNotification::OrderCancelled(cancelled) => {
+                Some((cancelled.traded_pair, SubscriptionList::CANCELLED_LIMIT_ORDERS))
+            }
+            ExchangeEventNotification::OrderSizeReduced(reduced) => {
+                Some((reduced.traded_pair, SubscriptionList::CANCELLED_LIMIT_ORDERS))
+            }
+            ExchangeEventNotification::OrderPlaced(placed) => {
+                Some((placed.traded_pair, SubscriptionList::NEW_LIMIT_ORDERS))
+            }
+            ExchangeEventNotification::TradeExecuted(trade) => {
+                Some((trade.traded_pair, SubscriptionList::TRADES))
+            }
+            ExchangeEventNotification::OffBookTradeReported(trade) => {
+                Some((trade.traded_pair, SubscriptionList::TRADES))
+            }
+            ExchangeEventNotification::ObSnapshot(snapshot) => {
+                Some((snapshot.traded_pair, SubscriptionList::OB_SNAPSHOTS))
+            }
+            ExchangeEventNotification::ObDiff(diff) => {
+                Some((diff.traded_pair, SubscriptionList::OB_DIFFS))
+            }
+            ExchangeEventNotification::ThroughputStatistics(statistics) => {
+                Some((statistics.traded_pair, SubscriptionList::THROUGHPUT_STATISTICS))
+            }
+            ExchangeEventNotification::VolatilityEstimate(estimate) => {
+                Some((estimate.traded_pair, SubscriptionList::VOLATILITY_ESTIMATES))
+            }
+            _ => None
+        };
+        let recipients: Vec<_> = if let Some((traded_pair, flag)) = subscription {
+            self.subscribers.get(&(exchange_id, traded_pair))
+                .map(|subscribers| subscribers.as_slice())
+                .unwrap_or_default()
+                .iter()
+                .filter(|(_, subscription)| subscription.contains(flag))
+                .map(|(trader_id, _)| *trader_id)
+                .collect()
+        } else {
+            self.registered_traders.iter().copied().collect()
+        };
+        actions.extend(
+            recipients.into_iter().map(
+                |trader_id| Self::create_broker_reply(
+                    trader_id,
+                    exchange_id,
+                    reply.exchange_dt,
+                    BasicBrokerReply::ExchangeEventNotification(notification.clone()),
+                )
+            )
+        );
+        let mut latency_generator = self.get_latency_generator();
+        message_receiver.extend(
+            actions.into_iter().map(
+                |action| action_processor.process_action(action, &mut latency_generator, rng)
+            )
+        );
+        self.update_latency_generator(latency_generator)
+    }
+
+    fn process_replay_request<KerMsg: Ord>(
+        &mut self,
+        _: MessageReceiver<KerMsg>,
+        _: impl LatentActionProcessor<Self::Action, Self::ExchangeID, KerMsg=KerMsg>,
+        request: Self::R2B,
+        _: &mut impl Rng,
+    ) {
+        panic!("{} :: DealerBroker does not support replay requests: {request:?}", self.current_dt)
+    }
+
+    fn upon_connection_to_exchange(&mut self, exchange_id: ExchangeID) {
+        self.registered_exchanges.insert(exchange_id);
+    }
+
+    fn register_trader(
+        &mut self,
+        trader_id: TraderID,
+        sub_cfgs: impl IntoIterator<Item=SubscriptionConfig<ExchangeID, Symbol, Settlement>>,
+    ) {
+        self.registered_traders.insert(trader_id);
+        for SubscriptionConfig { exchange, traded_pair, subscription } in sub_cfgs {
+            if !self.registered_exchanges.contains(&exchange) {
+                panic!("Broker {} is not connected to Exchange {exchange}", self.name)
+            }
+            self.subscribers
+                .entry((exchange, traded_pair))
+                .or_default()
+                .push((trader_id, subscription))
+        }
+    }
+}
+
+impl<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
+DealerBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
+    where BrokerID: Id,
+          TraderID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    /// Creates a new instance of the `DealerBroker`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` — ID of the `DealerBroker`.
+    /// * `config` — Pricing parameters.
+    pub fn new(name: BrokerID, config: DealerConfig) -> Self {
+        if config.half_spread < Tick(0) {
+            panic!("Half-spread should be non-negative. Got: {}", config.half_spread)
+        }
+        if matches!(config.max_inventory, Some(max_inventory) if max_inventory < Lots(0)) {
+            panic!("Maximum inventory should be non-negative")
+        }
+        DealerBroker {
+            current_dt: Date::from_ymd_opt(1970, 1, 1)
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+                .unwrap_or_else(|| unreachable!("Unix epoch should be a valid datetime")),
+            name,
+            config,
+            registered_traders: Default::default(),
+            registered_exchanges: Default::default(),
+            subscribers: Default::default(),
+            reference_prices: Default::default(),
+            inventory: Default::default(),
+            resting_orders: Default::default(),
+            next_arrival: 0,
+            firm_quotes: Default::default(),
+            next_trade_id: TradeID(0),
+            tape_delay: None,
+            pending_prints: vec![],
+        }
+    }
+
+    /// Makes the `DealerBroker` report its trades to the tapes of the exchanges
+    /// the instruments are listed at, so that they are published to all their brokers.
+    /// Reports are sent with the delay, as the off-book trades are usually printed late.
+    ///
+    /// # Arguments
+    ///
+    /// * `delay` — Time between the trade and its report.
+    pub fn with_tape_reporting(mut self, delay: NanoDuration) -> Self {
+        self.tape_delay = Some(delay);
+        self
+    }
+
+    /// Returns the current bid and ask of the dealer.
+    /// `None` if the reference price of the instrument is not known yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `exchange_id` — Exchange the instrument is listed at.
+    /// * `traded_pair` — Traded pair.
+    pub fn get_quote(
+        &self,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>) -> Option<(Tick, Tick)>
+    {
+        let instrument = (exchange_id, traded_pair);
+        let reference = *self.reference_prices.get(&instrument)?;
+        let inventory = self.get_inventory(exchange_id, traded_pair);
+        let skew = Tick((self.config.skew_per_lot * inventory.0 as f64).round() as i64);
+        Some(
+            (
+                reference - self.config.half_spread - skew,
+                reference + self.config.half_spread - skew
+            )
+        )
+    }
+
+    /// Returns the signed inventory of the dealer. Positive if the dealer is long.
+    ///
+    /// # Arguments
+    ///
+    /// * `exchange_id` — Exchange the instrument is listed at.
+    /// * `traded_pair` — Traded pair.
+    pub fn get_inventory(
+        &self,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>) -> Lots
+    {
+        self.inventory.get(&(exchange_id, traded_pair)).copied().unwrap_or(Lots(0))
+    }
+
+    fn place_limit_order(
+        &mut self,
+        trader_id: TraderID,
+        exchange_id: ExchangeID,
+        request: LimitOrderPlacingRequest<Symbol, Settlement>,
+    ) -> Vec<BasicBrokerReply<Symbol, Settlement>>
+    {
+        let key = (trader_id, request.order_id);
+        if self.resting_orders.contains_key(&key) {
+            return vec![
+                Self::discard(
+                    request.traded_pair,
+                    request.order_id,
+                    PlacementDiscardingReason::OrderWithSuchIDAlreadySubmitted,
+                )
+            ];
+        }
+        let replies = self.place_order(
+            exchange_id,
+            request.traded_pair,
+            request.order_id,
+            request.direction,
+            request.size,
+            Some(request.price),
+        );
+        if let [BasicBrokerReply::OrderAccepted(_)] = replies.as_slice() {
+            self.resting_orders.insert(key, (exchange_id, request, self.next_arrival));
+            self.next_arrival += 1;
+        }
+        replies
+    }
+
+    /// Fills the order at the dealer quote if it is marketable.
+    /// Returns the replies to the trader.
+    /// Only `OrderAccepted` is returned if the limit order should rest at the dealer.
+    fn place_order(
+        &mut self,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        order_id: OrderID,
+        direction: Direction,
+        size: Lots,
+        limit_price: Option<Tick>,
+    ) -> Vec<BasicBrokerReply<Symbol, Settlement>>
+    {
+        let discard = |reason| vec![Self::discard(traded_pair, order_id, reason)];
+        if !self.registered_exchanges.contains(&exchange_id) {
+            return discard(PlacementDiscardingReason::BrokerNotConnectedToExchange);
+        }
+        if size <= Lots(0) {
+            return discard(PlacementDiscardingReason::ZeroSize);
+        }
+        // Orders are not routed, so they keep the IDs assigned by the traders
+        let accepted = BasicBrokerReply::OrderAccepted(
+            OrderAccepted {
+                traded_pair,
+                order_id,
+                broker_order_id: order_id,
+                exchange_order_id: Some(order_id),
+            }
+        );
+        let quote = self.get_quote(exchange_id, traded_pair);
+        let price = match (quote, direction) {
+            (Some((_, ask)), Direction::Buy) => ask,
+            (Some((bid, _)), Direction::Sell) => bid,
+            (None, _) => {
+                return if limit_price.is_some() {
+                    vec![accepted]
+                } else {
+                    discard(PlacementDiscardingReason::NoDealerQuote)
+                };
+            }
+        };
+        let marketable = match (limit_price, direction) {
+            (Some(limit_price), Direction::Buy) => price <= limit_price,
+            (Some(limit_price), Direction::Sell) => price >= limit_price,
+            (None, _) => true
+        };
+        if !marketable {
+            return vec![accepted];
+        }
+        if !self.can_trade((exchange_id, traded_pair), direction, size) {
+            return if limit_price.is_some() {
+                vec![accepted]
+            } else {
+                discard(PlacementDiscardingReason::DealerInventoryLimitExceeded)
+            };
+        }
+        let trade_id = self.book_trade((exchange_id, traded_pair), direction, price, size);
+        vec![
+            accepted,
+            BasicBrokerReply::OrderExecuted(
+                OrderExecuted {
+                    traded_pair,
+                    order_id,
+                    broker_order_id: order_id,
+                    exchange_order_id: order_id,
+                    exec_id: ExecID(trade_id.0),
+                    trade_id,
+                    price,
+                    size,
+                    fee: Fee(0),
+                }
+            ),
+        ]
+    }
+
+    /// Fills the resting orders crossed by the dealer quote.
+    /// The quote is recomputed after each fill, as the fill moves the dealer inventory.
+    /// Orders are filled in the order of their prices, the most aggressive first,
+    /// and then in the order of their arrival.
+    fn fill_resting_orders(
+        &mut self,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        event_dt: DateTime) -> Vec<<Self as Agent>::Action>
+    {
+        let instrument = (exchange_id, traded_pair);
+        let mut actions = Vec::new();
+        while let Some((bid, ask)) = self.get_quote(exchange_id, traded_pair) {
+            let next_fill = self.resting_orders.iter()
+                .filter_map(
+                    |(key, (order_exchange_id, order, arrival))| {
+                        if *order_exchange_id != exchange_id || order.traded_pair != traded_pair {
+                            return None;
+                        }
+                        let (price, improvement) = match order.direction {
+                            Direction::Buy => (ask, order.price - ask),
+                            Direction::Sell => (bid, bid - order.price)
+                        };
+                        if improvement < Tick(0)
+                            || !self.can_trade(instrument, order.direction, order.size) {
+                            return None;
+                        }
+                        Some(((Reverse(improvement), *arrival), *key, price))
+                    }
+                )
+                .min_by_key(|(priority, _, _)| *priority);
+            let (key @ (trader_id, order_id), price) = if let Some((_, key, price)) = next_fill {
+                (key, price)
+            } else {
+                break;
+            };
+            let (_, order, _) = self.resting_orders.remove(&key).unwrap_or_else(
+                || unreachable!("Resting order {key:?} should exist")
+            );
+            let trade_id = self.book_trade(instrument, order.direction, price, order.size);
+            actions.push(
+                Self::create_broker_reply(
+                    trader_id,
+                    exchange_id,
+                    event_dt,
+                    BasicBrokerReply::OrderExecuted(
+                        OrderExecuted {
+                            traded_pair,
+                            order_id,
+                            broker_order_id: order_id,
+                            exchange_order_id: order_id,
+                            exec_id: ExecID(trade_id.0),
+                            trade_id,
+                            price,
+                            size: order.size,
+                            fee: Fee(0),
+                        }
+                    ),
+                )
+            )
+        }
+        actions
+    }
+
+    fn can_trade(
+        &self,
+        instrument: Instrument<ExchangeID, Symbol, Settlement>,
+        trader_direction: Direction,
+        size: Lots) -> bool
+    {
+        let max_inventory = if let Some(max_inventory) = self.config.max_inventory {
+            max_inventory
+        } else {
+            return true;
+        };
+        let inventory = self.inventory.get(&instrument).copied().unwrap_or(Lots(0));
+        let new_inventory = match trader_direction {
+            Direction::Buy => inventory - size,
+            Direction::Sell => inventory + size
+        };
+        new_inventory.0.abs() <= max_inventory.0
+    }
+
+    fn book_trade(
+        &mut self,
+        instrument: Instrument<ExchangeID, Symbol, Settlement>,
+        trader_direction: Direction,
+        price: Tick,
+        size: Lots) -> TradeID
+    {
+        let trade_id = self.next_trade_id;
+        self.next_trade_id.0 += 1;
+        let inventory = self.inventory.entry(instrument).or_insert(Lots(0));
+        match trader_direction {
+            Direction::Buy => *inventory -= size,
+            Direction::Sell => *inventory += size
+        }
+        if self.tape_delay.is_some() {
+            let (exchange_id, traded_pair) = instrument;
+            self.pending_prints.push(
+                (
+                    exchange_id,
+                    OffBookTradeInfo {
+                        traded_pair,
+                        trade_id,
+                        direction: trader_direction,
+                        price,
+                        size,
+                        executed_dt: self.current_dt,
+                    }
+                )
+            )
+        }
+        trade_id
+    }
+
+    fn report_pending_prints(&mut self) -> Vec<<Self as Agent>::Action> {
+        let delay = if let Some(delay) = self.tape_delay {
+            delay
+        } else {
+            return vec![];
+        };
+        self.pending_prints.drain(..).map(
+            |(exchange_id, trade)| BrokerAction {
+                delay,
+                content: BrokerActionKind::BrokerToExchange(
+                    BasicBrokerToExchange {
+                        exchange_id,
+                        content: BasicBrokerRequest::ReportOffBookTrade(trade),
+                    }
+                ),
+            }
+        ).collect()
+    }
+
+    fn discard(
+        traded_pair: TradedPair<Symbol, Settlement>,
+        order_id: OrderID,
+        reason: PlacementDiscardingReason) -> BasicBrokerReply<Symbol, Settlement>
+    {
+        BasicBrokerReply::OrderPlacementDiscarded(
+            OrderPlacementDiscarded { traded_pair, order_id, reason }
+        )
+    }
+
+    fn create_broker_reply(
+        trader_id: TraderID,
+        exchange_id: ExchangeID,
+        event_dt: DateTime,
+        content: BasicBrokerReply<Symbol, Settlement>) -> <Self as Agent>::Action
+    {
+        BrokerAction {
+            delay: NanoDuration::ZERO,
+            content: BrokerActionKind::BrokerToTrader(
+                BasicBrokerToTrader {
+                    trader_id,
+                    exchange_id,
+                    event_dt,
+                    content,
+                }
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            concrete::{order::TimeInForce, traded_pair::settlement::concrete::SpotSettlement},
+            test_utils::{dt, pair},
+        },
+    };
+
+    type Dealer = DealerBroker<u8, u8, u8, &'static str, SpotSettlement>;
+
+    const EXCHANGE: u8 = 0;
+
+    fn dealer(skew_per_lot: f64, max_inventory: Option<Lots>) -> Dealer {
+        let mut dealer = Dealer::new(
+            0,
+            DealerConfig {
+                half_spread: Tick(1),
+                skew_per_lot,
+                max_inventory,
+                quote_validity: NanoDuration::ZERO,
+            },
+        );
+        dealer.upon_connection_to_exchange(EXCHANGE);
+        dealer.reference_prices.insert((EXCHANGE, pair()), Tick(100));
+        dealer
+    }
+
+    fn rest(dealer: &mut Dealer, trader_id: u8, order_id: u64, price: i64, size: i64) {
+        let replies = dealer.place_limit_order(
+            trader_id,
+            EXCHANGE,
+            LimitOrderPlacingRequest {
+                traded_pair: pair(),
+                order_id: OrderID(order_id),
+                direction: Direction::Buy,
+                price: Tick(price),
+                size: Lots(size),
+                dummy: false,
+                time_in_force: TimeInForce::Day,
+            },
+        );
+        assert!(matches!(replies.as_slice(), [BasicBrokerReply::OrderAccepted(_)]))
+    }
+
+    fn fill_resting_orders(dealer: &mut Dealer, reference: i64) -> Vec<(u8, OrderID, Tick)> {
+        dealer.reference_prices.insert((EXCHANGE, pair()), Tick(reference));
+        let dt = dt(0);
+        dealer.fill_resting_orders(EXCHANGE, pair(), dt).into_iter().map(
+            |action| match action.content {
+                BrokerActionKind::BrokerToTrader(
+                    BasicBrokerToTrader {
+                        trader_id,
+                        content: BasicBrokerReply::OrderExecuted(executed),
+                        ..
+                    }
+                ) => (trader_id, executed.order_id, executed.price),
+                content => panic!("Unexpected action: {content:?}")
+            }
+        ).collect()
+    }
+
+    #[test]
+    fn test_quote_moves_after_each_fill() {
+        let mut dealer = dealer(1.0, None);
+        rest(&mut dealer, 1, 0, 95, 2);
+        rest(&mut dealer, 2, 0, 95, 1);
+        // Ask is 96 before the fills, so nothing crosses
+        assert_eq!(fill_resting_orders(&mut dealer, 95), vec![]);
+        // Ask is 95, after the first fill of 2 lots it is 97
+        assert_eq!(fill_resting_orders(&mut dealer, 94), vec![(1, OrderID(0), Tick(95))]);
+        assert_eq!(dealer.get_inventory(EXCHANGE, pair()), Lots(-2));
+        assert_eq!(dealer.get_quote(EXCHANGE, pair()), Some((Tick(95), Tick(97))));
+        // Second order is still resting
+        assert_eq!(fill_resting_orders(&mut dealer, 92), vec![(2, OrderID(0), Tick(95))]);
+    }
+
+    #[test]
+    fn test_price_time_priority() {
+        let mut dealer = dealer(0.0, Some(Lots(2)));
+        dealer.reference_prices.insert((EXCHANGE, pair()), Tick(110));
+        rest(&mut dealer, 2, 0, 101, 2);
+        rest(&mut dealer, 1, 1, 105, 2);
+        rest(&mut dealer, 1, 0, 105, 2);
+        // All orders cross the ask of 101, but the inventory limit binds after the first fill
+        assert_eq!(fill_resting_orders(&mut dealer, 100), vec![(1, OrderID(1), Tick(101))]);
+        assert_eq!(dealer.resting_orders.len(), 2)
+    }
+}
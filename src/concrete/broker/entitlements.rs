@@ -0,0 +1,116 @@
+use {
+    crate::{
+        concrete::traded_pair::{settlement::GetSettlementLag, TradedPair},
+        types::Id,
+        utils::collections::HashSet,
+    },
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Venues and traded pairs the trader is permitted to trade and to subscribe to.
+pub struct Entitlements<ExchangeID, Symbol, Settlement>
+    where ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    venues: HashSet<ExchangeID>,
+    traded_pairs: HashSet<(ExchangeID, TradedPair<Symbol, Settlement>)>,
+}
+
+impl<ExchangeID, Symbol, Settlement> Default for Entitlements<ExchangeID, Symbol, Settlement>
+    where ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    fn default() -> Self {
+        Self {
+            venues: Default::default(),
+            traded_pairs: Default::default(),
+        }
+    }
+}
+
+impl<ExchangeID, Symbol, Settlement> Entitlements<ExchangeID, Symbol, Settlement>
+    where ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    /// Creates a new instance of the `Entitlements` that permits nothing.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Permits all traded pairs of the exchange.
+    ///
+    /// # Arguments
+    ///
+    /// * `exchange_id` — Exchange to permit.
+    pub fn with_venue(mut self, exchange_id: ExchangeID) -> Self {
+        self.venues.insert(exchange_id);
+        self
+    }
+
+    /// Permits the traded pair of the exchange.
+    ///
+    /// # Arguments
+    ///
+    /// * `exchange_id` — Exchange the traded pair is listed at.
+    /// * `traded_pair` — Traded pair to permit.
+    pub fn with_traded_pair(
+        mut self,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>) -> Self
+    {
+        self.traded_pairs.insert((exchange_id, traded_pair));
+        self
+    }
+
+    /// Checks whether the traded pair of the exchange is permitted.
+    ///
+    /// # Arguments
+    ///
+    /// * `exchange_id` — Exchange the traded pair is listed at.
+    /// * `traded_pair` — Traded pair to check.
+    pub fn is_permitted(
+        &self,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>) -> bool
+    {
+        self.venues.contains(&exchange_id)
+            || self.traded_pairs.contains(&(exchange_id, traded_pair))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            concrete::traded_pair::settlement::concrete::SpotSettlement,
+            test_utils::{pair, symbol_pair},
+        },
+    };
+
+    type TestEntitlements = Entitlements<&'static str, &'static str, SpotSettlement>;
+
+    #[test]
+    fn test_nothing_is_permitted_by_default() {
+        assert!(!TestEntitlements::new().is_permitted("MOEX", pair()))
+    }
+
+    #[test]
+    fn test_venue_permits_all_its_traded_pairs() {
+        let entitlements = TestEntitlements::new().with_venue("MOEX");
+        assert!(entitlements.is_permitted("MOEX", pair()));
+        assert!(entitlements.is_permitted("MOEX", symbol_pair("EUR")));
+        assert!(!entitlements.is_permitted("SPB", pair()))
+    }
+
+    #[test]
+    fn test_traded_pair_is_permitted_at_its_exchange_only() {
+        let entitlements = TestEntitlements::new().with_traded_pair("MOEX", pair());
+        assert!(entitlements.is_permitted("MOEX", pair()));
+        assert!(!entitlements.is_permitted("MOEX", symbol_pair("EUR")));
+        assert!(!entitlements.is_permitted("SPB", pair()))
+    }
+}
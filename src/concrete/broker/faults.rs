@@ -0,0 +1,174 @@
+use {crate::types::NanoDuration, rand::Rng};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+/// Probabilities of the operational errors simulated by the
+/// [`BasicBroker`](crate::concrete::broker::BasicBroker).
+/// Errors concern only the order flow, i.e. the requests forwarded to the exchanges
+/// and the replies to the traders concerning their requests. Market data is not affected.
+pub struct FaultConfig {
+    /// Probability that the request forwarded to the exchange is lost.
+    pub request_lost: f64,
+    /// Probability that the reply to the trader is lost.
+    pub reply_lost: f64,
+    /// Probability that the reply to the trader is sent twice.
+    pub reply_duplicated: f64,
+    /// Probability that the reply to the trader is delayed by the `reply_delay`.
+    pub reply_delayed: f64,
+    /// Extra delay of the delayed replies.
+    pub reply_delay: NanoDuration,
+}
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Hash)]
+/// Numbers of the operational errors injected by the [`FaultInjector`].
+pub struct InjectedFaults {
+    /// Number of the requests lost on the way to the exchanges.
+    pub requests_lost: u64,
+    /// Number of the replies lost on the way to the traders.
+    pub replies_lost: u64,
+    /// Number of the replies sent twice.
+    pub replies_duplicated: u64,
+    /// Number of the delayed replies.
+    pub replies_delayed: u64,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+/// Operational error of the single reply to the trader.
+pub enum ReplyFault {
+    /// Reply is delivered as usual.
+    None,
+    /// Reply is not delivered.
+    Lost,
+    /// Reply is delivered twice.
+    Duplicated,
+    /// Reply is delivered with the extra delay.
+    Delayed(NanoDuration),
+}
+
+/// Draws the operational errors of the order flow according to the [`FaultConfig`]
+/// and counts them. At most one error is injected into the single message.
+pub struct FaultInjector {
+    config: FaultConfig,
+    injected: InjectedFaults,
+}
+
+impl FaultInjector {
+    /// Creates a new instance of the `FaultInjector`.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` — Probabilities of the errors.
+    pub fn new(config: FaultConfig) -> Self {
+        let FaultConfig { request_lost, reply_lost, reply_duplicated, reply_delayed, .. } = config;
+        for probability in [request_lost, reply_lost, reply_duplicated, reply_delayed] {
+            if !(0.0..=1.0).contains(&probability) {
+                panic!("Fault probability should be within [0, 1]. Got: {probability}")
+            }
+        }
+        Self { config, injected: Default::default() }
+    }
+
+    /// Returns the probabilities of the errors.
+    pub fn get_config(&self) -> FaultConfig {
+        self.config
+    }
+
+    /// Returns the numbers of the errors injected so far.
+    pub fn get_injected_faults(&self) -> InjectedFaults {
+        self.injected
+    }
+
+    /// Decides whether the request forwarded to the exchange is lost.
+    ///
+    /// # Arguments
+    ///
+    /// * `rng` — Random number generator.
+    pub fn is_request_lost(&mut self, rng: &mut impl Rng) -> bool {
+        let lost = draw(self.config.request_lost, rng);
+        if lost {
+            self.injected.requests_lost += 1
+        }
+        lost
+    }
+
+    /// Decides on the operational error of the reply to the trader.
+    ///
+    /// # Arguments
+    ///
+    /// * `rng` — Random number generator.
+    pub fn get_reply_fault(&mut self, rng: &mut impl Rng) -> ReplyFault {
+        if draw(self.config.reply_lost, rng) {
+            self.injected.replies_lost += 1;
+            ReplyFault::Lost
+        } else if draw(self.config.reply_duplicated, rng) {
+            self.injected.replies_duplicated += 1;
+            ReplyFault::Duplicated
+        } else if draw(self.config.reply_delayed, rng) {
+            self.injected.replies_delayed += 1;
+            ReplyFault::Delayed(self.config.reply_delay)
+        } else {
+            ReplyFault::None
+        }
+    }
+}
+
+/// Draws the event of the given probability.
+/// Does not touch the random number generator if the event is impossible,
+/// so that disabled errors do not affect the rest of the simulation.
+fn draw(probability: f64, rng: &mut impl Rng) -> bool {
+    probability != 0.0 && rng.gen_bool(probability)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, rand::{rngs::StdRng, SeedableRng}};
+
+    #[test]
+    fn test_certain_faults() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let reply_delay = NanoDuration(5);
+        let mut injector = FaultInjector::new(
+            FaultConfig { request_lost: 1.0, reply_delayed: 1.0, reply_delay, ..Default::default() }
+        );
+        assert!(injector.is_request_lost(&mut rng));
+        assert_eq!(injector.get_reply_fault(&mut rng), ReplyFault::Delayed(reply_delay));
+        assert_eq!(injector.get_reply_fault(&mut rng), ReplyFault::Delayed(reply_delay));
+        assert_eq!(
+            injector.get_injected_faults(),
+            InjectedFaults { requests_lost: 1, replies_delayed: 2, ..Default::default() }
+        )
+    }
+
+    #[test]
+    fn test_single_fault_per_reply() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut injector = FaultInjector::new(
+            FaultConfig {
+                reply_lost: 1.0,
+                reply_duplicated: 1.0,
+                reply_delayed: 1.0,
+                ..Default::default()
+            }
+        );
+        assert_eq!(injector.get_reply_fault(&mut rng), ReplyFault::Lost);
+        assert_eq!(
+            injector.get_injected_faults(),
+            InjectedFaults { replies_lost: 1, ..Default::default() }
+        )
+    }
+
+    #[test]
+    fn test_disabled_faults_do_not_draw() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut injector = FaultInjector::new(Default::default());
+        assert!(!injector.is_request_lost(&mut rng));
+        assert_eq!(injector.get_reply_fault(&mut rng), ReplyFault::None);
+        assert_eq!(injector.get_injected_faults(), Default::default());
+        assert_eq!(rng.gen::<u64>(), StdRng::seed_from_u64(0).gen::<u64>())
+    }
+
+    #[test]
+    #[should_panic(expected = "Fault probability should be within [0, 1]. Got: 1.5")]
+    fn test_invalid_probability() {
+        FaultInjector::new(FaultConfig { reply_duplicated: 1.5, ..Default::default() });
+    }
+}
@@ -0,0 +1,243 @@
+use {
+    crate::{
+        concrete::types::Lots,
+        types::{DateTime, Id},
+        utils::collections::{HashMap, HashSet},
+    },
+    std::{fs::File, io::Write, path::Path},
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+/// Risk limits of the single trader. Absent limits are not checked.
+pub struct RiskLimits {
+    /// Maximum loss, in ticks times lots.
+    pub max_loss: Option<f64>,
+    /// Maximum decline of the P&L from its peak, in ticks times lots.
+    pub max_drawdown: Option<f64>,
+    /// Maximum absolute net position per instrument, summed over the accounts of the trader.
+    pub max_position: Option<Lots>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Breach of the [`RiskLimits`].
+pub enum RiskLimitBreach {
+    /// Loss limit breached. Contains the P&L.
+    Loss(f64),
+    /// Drawdown limit breached. Contains the drawdown.
+    Drawdown(f64),
+    /// Position limit breached. Contains the absolute net position.
+    Position(Lots),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Trip of the [`KillSwitch`].
+pub struct KillSwitchEvent<TraderID: Id> {
+    /// Datetime the kill switch tripped at.
+    pub datetime: DateTime,
+    /// Trader which order flow is blocked.
+    pub trader_id: TraderID,
+    /// Breached limit.
+    pub breach: RiskLimitBreach,
+}
+
+/// Monitors the P&L, the drawdown and the positions of the traders.
+/// Once any of the [`RiskLimits`] of the trader is breached, the kill switch trips:
+/// the trader stays blocked until the end of the simulation
+/// and the event is recorded to the summary of the run.
+pub struct KillSwitch<TraderID: Id> {
+    default_limits: RiskLimits,
+    limits: HashMap<TraderID, RiskLimits>,
+    peak_pnls: HashMap<TraderID, f64>,
+    tripped: HashSet<TraderID>,
+    events: Vec<KillSwitchEvent<TraderID>>,
+}
+
+impl<TraderID: Id> KillSwitch<TraderID> {
+    /// Creates a new instance of the `KillSwitch`.
+    ///
+    /// # Arguments
+    ///
+    /// * `default_limits` — Risk limits of the traders without the individual ones.
+    pub fn new(default_limits: RiskLimits) -> Self {
+        Self {
+            default_limits,
+            limits: Default::default(),
+            peak_pnls: Default::default(),
+            tripped: Default::default(),
+            events: vec![],
+        }
+    }
+
+    /// Sets the individual risk limits of the trader.
+    ///
+    /// # Arguments
+    ///
+    /// * `trader_id` — Trader.
+    /// * `limits` — Risk limits.
+    pub fn with_limits(mut self, trader_id: TraderID, limits: RiskLimits) -> Self {
+        self.limits.insert(trader_id, limits);
+        self
+    }
+
+    /// Returns whether the kill switch has tripped for the trader.
+    ///
+    /// # Arguments
+    ///
+    /// * `trader_id` — Trader.
+    pub fn is_tripped(&self, trader_id: TraderID) -> bool {
+        self.tripped.contains(&trader_id)
+    }
+
+    /// Returns all trips in the order they happened.
+    pub fn get_events(&self) -> &[KillSwitchEvent<TraderID>] {
+        &self.events
+    }
+
+    /// Checks the risk metrics of the trader against its limits.
+    /// Trips the kill switch and returns the breach if any limit is breached.
+    ///
+    /// # Arguments
+    ///
+    /// * `datetime` — Current datetime.
+    /// * `trader_id` — Trader.
+    /// * `pnl` — Marked-to-market P&L of the trader, in ticks times lots.
+    /// * `max_position` — Largest absolute net position of the trader among the instruments.
+    pub fn check(
+        &mut self,
+        datetime: DateTime,
+        trader_id: TraderID,
+        pnl: f64,
+        max_position: Lots) -> Option<RiskLimitBreach>
+    {
+        if self.tripped.contains(&trader_id) {
+            return None;
+        }
+        let limits = self.limits.get(&trader_id).unwrap_or(&self.default_limits);
+        let peak_pnl = self.peak_pnls.entry(trader_id).or_insert(0.0);
+        *peak_pnl = peak_pnl.max(pnl);
+        let drawdown = *peak_pnl - pnl;
+        let breach = if limits.max_loss.is_some_and(|max_loss| -pnl > max_loss) {
+            RiskLimitBreach::Loss(pnl)
+        } else if limits.max_drawdown.is_some_and(|max_drawdown| drawdown > max_drawdown) {
+            RiskLimitBreach::Drawdown(drawdown)
+        } else if limits.max_position.is_some_and(|limit| max_position > limit) {
+            RiskLimitBreach::Position(max_position)
+        } else {
+            return None;
+        };
+        self.tripped.insert(trader_id);
+        self.events.push(KillSwitchEvent { datetime, trader_id, breach });
+        Some(breach)
+    }
+
+    /// Writes all trips to the csv-file in the order they happened.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` — Path to the csv-file to create.
+    pub fn write_events(&self, file: impl AsRef<Path>) {
+        let file = file.as_ref();
+        let mut writer = File::create(file).unwrap_or_else(
+            |err| panic!("Cannot create file {file:?}. Error: {err}")
+        );
+        let mut write = |line: String| writeln!(writer, "{line}").unwrap_or_else(
+            |err| panic!("Cannot write to file {file:?}. Error: {err}")
+        );
+        write("DATETIME,TRADER,BREACH,VALUE".into());
+        for KillSwitchEvent { datetime, trader_id, breach } in &self.events {
+            let (breach, value) = match breach {
+                RiskLimitBreach::Loss(pnl) => ("LOSS", pnl.to_string()),
+                RiskLimitBreach::Drawdown(drawdown) => ("DRAWDOWN", drawdown.to_string()),
+                RiskLimitBreach::Position(position) => ("POSITION", position.to_string())
+            };
+            write(format!("{datetime},{trader_id},{breach},{value}"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::test_utils::dt,
+        std::{env::temp_dir, fs::read_to_string},
+    };
+
+    #[test]
+    fn test_loss_breach_trips_once() {
+        let breach = RiskLimitBreach::Loss(-150.0);
+        let mut kill_switch = KillSwitch::new(
+            RiskLimits { max_loss: Some(100.0), ..Default::default() }
+        );
+        assert_eq!(kill_switch.check(dt(0), 1, -50.0, Lots(0)), None);
+        assert!(!kill_switch.is_tripped(1));
+        assert_eq!(kill_switch.check(dt(1), 1, -150.0, Lots(0)), Some(breach));
+        assert!(kill_switch.is_tripped(1));
+        // Tripped trader is not checked anymore
+        assert_eq!(kill_switch.check(dt(2), 1, -500.0, Lots(0)), None);
+        assert_eq!(
+            kill_switch.get_events(),
+            [KillSwitchEvent { datetime: dt(1), trader_id: 1, breach }]
+        )
+    }
+
+    #[test]
+    fn test_drawdown_breach() {
+        let mut kill_switch = KillSwitch::new(
+            RiskLimits { max_drawdown: Some(30.0), ..Default::default() }
+        );
+        assert_eq!(kill_switch.check(dt(0), 1, 50.0, Lots(0)), None);
+        assert_eq!(kill_switch.check(dt(1), 1, 30.0, Lots(0)), None);
+        // Drawdown is measured from the peak P&L rather than from the previous one
+        assert_eq!(
+            kill_switch.check(dt(2), 1, 10.0, Lots(0)),
+            Some(RiskLimitBreach::Drawdown(40.0))
+        )
+    }
+
+    #[test]
+    fn test_position_breach_with_individual_limits() {
+        let default_limits = RiskLimits {
+            max_loss: Some(10.0),
+            max_position: Some(Lots(5)),
+            ..Default::default()
+        };
+        let individual_limits = RiskLimits { max_position: Some(Lots(10)), ..Default::default() };
+        let mut kill_switch = KillSwitch::new(default_limits).with_limits(2, individual_limits);
+
+        assert_eq!(
+            kill_switch.check(dt(0), 1, 0.0, Lots(6)),
+            Some(RiskLimitBreach::Position(Lots(6)))
+        );
+        assert_eq!(kill_switch.check(dt(0), 2, 0.0, Lots(6)), None);
+        // Individual limits replace the default ones as a whole
+        assert_eq!(kill_switch.check(dt(0), 2, -100.0, Lots(10)), None);
+        assert_eq!(
+            kill_switch.check(dt(0), 2, -100.0, Lots(11)),
+            Some(RiskLimitBreach::Position(Lots(11)))
+        );
+        assert!(kill_switch.is_tripped(1) && kill_switch.is_tripped(2))
+    }
+
+    #[test]
+    fn test_write_events() {
+        let mut kill_switch = KillSwitch::new(
+            RiskLimits { max_loss: Some(100.0), max_position: Some(Lots(5)), max_drawdown: None }
+        );
+        kill_switch.check(dt(1), 2, -150.5, Lots(0));
+        kill_switch.check(dt(2), 1, 0.0, Lots(7));
+
+        let file = temp_dir().join(
+            format!("trading_backtester_kill_switch_{}.csv", std::process::id())
+        );
+        kill_switch.write_events(&file);
+        assert_eq!(
+            read_to_string(&file).unwrap().lines().collect::<Vec<_>>(),
+            [
+                "DATETIME,TRADER,BREACH,VALUE",
+                "2022-01-03 10:00:01,2,LOSS,-150.5",
+                "2022-01-03 10:00:02,1,POSITION,7",
+            ]
+        )
+    }
+}
@@ -0,0 +1,184 @@
+use {
+    crate::{
+        concrete::{
+            traded_pair::{settlement::GetSettlementLag, TradedPair},
+            types::{Direction, Lots, Tick},
+        },
+        types::Id,
+    },
+    std::collections::BTreeMap,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Position of the trader in the single instrument, kept on the average cost basis.
+/// Amounts are in ticks times lots.
+pub struct PortfolioPosition {
+    /// Signed net position size. Positive for long positions.
+    pub size: Lots,
+    /// Signed cost of the open position. Positive for long positions.
+    pub cost_basis: i64,
+    /// Profit realized by closing the position.
+    pub realized_pnl: i64,
+    /// Signed sum of the fill notionals. Positive for net sales.
+    pub cash_flow: i64,
+    /// Last known trade or closing price of the instrument. `None` if there has been none.
+    pub mark_price: Option<Tick>,
+}
+
+impl Default for PortfolioPosition {
+    fn default() -> Self {
+        Self { size: Lots(0), cost_basis: 0, realized_pnl: 0, cash_flow: 0, mark_price: None }
+    }
+}
+
+impl PortfolioPosition {
+    /// Returns the average entry price, in ticks, or `None` if the position is flat.
+    pub fn get_average_entry_price(&self) -> Option<f64> {
+        if self.size == Lots(0) {
+            None
+        } else {
+            Some(self.cost_basis as f64 / self.size.0 as f64)
+        }
+    }
+
+    /// Returns the profit of the open position marked to the [`mark_price`](Self::mark_price).
+    /// Zero if the position is flat or there is no mark price.
+    pub fn get_unrealized_pnl(&self) -> i64 {
+        self.mark_price.map_or(0, |mark| mark.0 * self.size.0 - self.cost_basis)
+    }
+
+    fn apply_fill(&mut self, direction: Direction, price: Tick, size: Lots) {
+        let delta = match direction {
+            Direction::Buy => size.0,
+            Direction::Sell => -size.0
+        };
+        self.cash_flow -= price.0 * delta;
+        // Opposite fill closes the position first, releasing its cost pro-rata
+        let closed = if self.size.0.signum() == -delta.signum() {
+            delta.signum() * delta.abs().min(self.size.0.abs())
+        } else {
+            0
+        };
+        if closed != 0 {
+            let released = (self.cost_basis as i128 * closed.abs() as i128
+                / self.size.0.abs() as i128) as i64;
+            self.realized_pnl += -price.0 * closed - released;
+            self.cost_basis -= released;
+            self.size += Lots(closed)
+        }
+        let opened = delta - closed;
+        self.cost_basis += price.0 * opened;
+        self.size += Lots(opened)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Positions of the trader at the single exchange,
+/// periodically sent by the [`BasicBroker`](crate::concrete::broker::BasicBroker).
+pub struct PortfolioSnapshot<Symbol: Id, Settlement: GetSettlementLag> {
+    /// Positions by traded pair, ordered by the traded pairs.
+    pub positions: Vec<(TradedPair<Symbol, Settlement>, PortfolioPosition)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Positions of the single trader across the exchanges and the traded pairs,
+/// tracked by the [`BasicBroker`](crate::concrete::broker::BasicBroker)
+/// from the fills of the trader orders.
+///
+/// Positions are marked to the last trade prices the broker observes,
+/// that is to the fills of its own traders, the trades the traders are subscribed to
+/// and the official closing prices.
+/// Busted fills are reversed at their prices.
+pub struct Portfolio<ExchangeID: Id, Symbol: Id, Settlement: GetSettlementLag> {
+    positions: BTreeMap<(ExchangeID, TradedPair<Symbol, Settlement>), PortfolioPosition>,
+}
+
+impl<ExchangeID: Id, Symbol: Id, Settlement: GetSettlementLag> Default
+for Portfolio<ExchangeID, Symbol, Settlement>
+{
+    fn default() -> Self {
+        Self { positions: Default::default() }
+    }
+}
+
+impl<ExchangeID: Id, Symbol: Id, Settlement: GetSettlementLag>
+Portfolio<ExchangeID, Symbol, Settlement>
+{
+    /// Returns the position in the instrument.
+    ///
+    /// # Arguments
+    ///
+    /// * `exchange_id` — Exchange the instrument is listed at.
+    /// * `traded_pair` — Traded pair.
+    pub fn get_position(
+        &self,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>) -> PortfolioPosition
+    {
+        self.positions.get(&(exchange_id, traded_pair)).copied().unwrap_or_default()
+    }
+
+    /// Yields the positions ordered by the exchanges and the traded pairs.
+    pub fn iter_positions(
+        &self,
+    ) -> impl Iterator<Item=(ExchangeID, TradedPair<Symbol, Settlement>, PortfolioPosition)> + '_
+    {
+        self.positions.iter()
+            .map(|((exchange_id, traded_pair), position)| (*exchange_id, *traded_pair, *position))
+    }
+
+    /// Returns the total realized profit, in ticks times lots.
+    pub fn get_realized_pnl(&self) -> i64 {
+        self.positions.values().map(|position| position.realized_pnl).sum()
+    }
+
+    /// Returns the total unrealized profit, in ticks times lots.
+    pub fn get_unrealized_pnl(&self) -> i64 {
+        self.positions.values().map(PortfolioPosition::get_unrealized_pnl).sum()
+    }
+
+    /// Returns the total cash balance, in ticks times lots, that is the sum of the fill notionals.
+    pub fn get_cash_balance(&self) -> i64 {
+        self.positions.values().map(|position| position.cash_flow).sum()
+    }
+
+    /// Returns the positions at the exchange.
+    ///
+    /// # Arguments
+    ///
+    /// * `exchange_id` — Exchange.
+    pub fn get_snapshot(&self, exchange_id: ExchangeID) -> PortfolioSnapshot<Symbol, Settlement> {
+        PortfolioSnapshot {
+            positions: self.positions.iter()
+                .filter(|((position_exchange_id, _), _)| *position_exchange_id == exchange_id)
+                .map(|((_, traded_pair), position)| (*traded_pair, *position))
+                .collect()
+        }
+    }
+
+    pub(crate) fn book_fill(
+        &mut self,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        direction: Direction,
+        price: Tick,
+        size: Lots)
+    {
+        let position = self.positions.entry((exchange_id, traded_pair)).or_default();
+        position.apply_fill(direction, price, size);
+        position.mark_price = Some(price)
+    }
+
+    pub(crate) fn mark(
+        &mut self,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        price: Tick)
+    {
+        if let Some(position) = self.positions.get_mut(&(exchange_id, traded_pair)) {
+            position.mark_price = Some(price)
+        }
+    }
+}
@@ -0,0 +1,290 @@
+use {
+    crate::{
+        concrete::{
+            broker::accounts::AccountLedger,
+            traded_pair::settlement::GetSettlementLag,
+            types::{AccountID, Direction},
+        },
+        types::{Date, DateTime, Id},
+        utils::collections::HashMap,
+    },
+    std::{fs::File, io::Write, path::PathBuf},
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+/// Charges applied to the accounts in the broker statements.
+pub struct StatementConfig {
+    /// Commission per fill, in basis points of the fill notional.
+    pub commission_bps: f64,
+    /// Annual financing rate applied daily, on the ACT/360 basis, to the cash balance.
+    /// Negative balances are charged and positive ones are credited.
+    pub financing_rate: f64,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+/// Cumulative cash state of the single account, in ticks times lots.
+pub struct AccountBalance {
+    /// Cash balance including the fill notionals, the commissions and the financing.
+    pub cash: f64,
+    /// Total commissions charged.
+    pub commissions: f64,
+    /// Total financing credited. Negative if it has been charged.
+    pub financing: f64,
+}
+
+/// Generates daily broker statements of the traders from the [`AccountLedger`].
+///
+/// Statement lists the trades, the commissions and the financing of the day,
+/// as well as the ending positions and cash balances of the accounts of the trader.
+/// It is written to the csv-file named `<TRADER>_<DATE>.csv` in the output directory.
+pub struct StatementGenerator<TraderID: Id> {
+    directory: PathBuf,
+    config: StatementConfig,
+    date: Option<Date>,
+    next_allocation: usize,
+    balances: HashMap<(TraderID, AccountID), AccountBalance>,
+}
+
+impl<TraderID: Id> StatementGenerator<TraderID> {
+    /// Creates a new instance of the `StatementGenerator`.
+    ///
+    /// # Arguments
+    ///
+    /// * `directory` — Existing directory to write the statements to.
+    /// * `config` — Charges applied to the accounts.
+    pub fn new(directory: impl Into<PathBuf>, config: StatementConfig) -> Self {
+        Self {
+            directory: directory.into(),
+            config,
+            date: None,
+            next_allocation: 0,
+            balances: Default::default(),
+        }
+    }
+
+    /// Returns the cumulative cash state of the account as of the last written statement.
+    ///
+    /// # Arguments
+    ///
+    /// * `trader_id` — Owner of the account.
+    /// * `account` — Account.
+    pub fn get_balance(&self, trader_id: TraderID, account: AccountID) -> AccountBalance {
+        self.balances.get(&(trader_id, account)).copied().unwrap_or_default()
+    }
+
+    /// Writes the statements of the current day if the `current_dt` belongs to the next one.
+    ///
+    /// # Arguments
+    ///
+    /// * `current_dt` — Current datetime of the broker.
+    /// * `ledger` — Ledger of the trader accounts.
+    /// * `traders` — Traders to write the statements for.
+    pub fn roll<ExchangeID: Id, Symbol: Id, Settlement: GetSettlementLag>(
+        &mut self,
+        current_dt: DateTime,
+        ledger: &AccountLedger<TraderID, ExchangeID, Symbol, Settlement>,
+        traders: impl IntoIterator<Item=TraderID>)
+    {
+        let current_date = current_dt.date();
+        match self.date {
+            Some(date) if date < current_date => {
+                self.write_statements(ledger, traders);
+                self.date = Some(current_date)
+            }
+            Some(_) => {}
+            None => self.date = Some(current_date)
+        }
+    }
+
+    /// Writes the statements of the current day.
+    ///
+    /// # Arguments
+    ///
+    /// * `ledger` — Ledger of the trader accounts.
+    /// * `traders` — Traders to write the statements for.
+    pub fn write_statements<ExchangeID: Id, Symbol: Id, Settlement: GetSettlementLag>(
+        &mut self,
+        ledger: &AccountLedger<TraderID, ExchangeID, Symbol, Settlement>,
+        traders: impl IntoIterator<Item=TraderID>)
+    {
+        let date = if let Some(date) = self.date {
+            date
+        } else {
+            return;
+        };
+        let mut records: HashMap<TraderID, Vec<String>> = traders.into_iter()
+            .map(|trader_id| (trader_id, Vec::new()))
+            .collect();
+        let allocations = &ledger.get_allocations()[self.next_allocation..];
+        self.next_allocation += allocations.len();
+        for fill in allocations {
+            let notional = (fill.price.0 * fill.size.0) as f64;
+            let amount = match fill.direction {
+                Direction::Buy => -notional,
+                Direction::Sell => notional
+            };
+            let commission = notional.abs() * self.config.commission_bps / 10_000.0;
+            let balance = self.balances.entry((fill.trader_id, fill.account)).or_default();
+            balance.cash += amount - commission;
+            balance.commissions += commission;
+            let trader_records = records.entry(fill.trader_id).or_default();
+            let (account, dt, exchange_id) = (fill.account, fill.datetime, fill.exchange_id);
+            let asset = format!("{:?}", fill.traded_pair.quoted_asset);
+            trader_records.push(
+                format!(
+                    "TRADE,{account},{dt},{exchange_id},{asset},{:?},{},{},{amount}",
+                    fill.direction, fill.price, fill.size
+                )
+            );
+            if commission != 0.0 {
+                trader_records.push(
+                    format!("COMMISSION,{account},{dt},{exchange_id},{asset},,,,{}", -commission)
+                )
+            }
+        }
+        for ((trader_id, account, exchange_id, traded_pair), position) in ledger.iter_positions() {
+            if let Some(trader_records) = records.get_mut(&trader_id) {
+                trader_records.push(
+                    format!(
+                        "POSITION,{account},,{exchange_id},{:?},,,{},",
+                        traded_pair.quoted_asset, position.size
+                    )
+                );
+                self.balances.entry((trader_id, account)).or_default();
+            }
+        }
+        for ((trader_id, account), balance) in self.balances.iter_mut() {
+            if let Some(trader_records) = records.get_mut(trader_id) {
+                let financing = balance.cash * self.config.financing_rate / 360.0;
+                if financing != 0.0 {
+                    balance.cash += financing;
+                    balance.financing += financing;
+                    trader_records.push(format!("FINANCING,{account},,,,,,,{financing}"))
+                }
+                trader_records.push(format!("BALANCE,{account},,,,,,,{}", balance.cash))
+            }
+        }
+        for (trader_id, mut trader_records) in records {
+            // Records of every kind are sorted by the account
+            trader_records.sort_by_key(
+                |record| {
+                    let mut fields = record.split(',');
+                    let kind = match fields.next() {
+                        Some("TRADE") | Some("COMMISSION") => 0,
+                        Some("POSITION") => 1,
+                        Some("FINANCING") => 2,
+                        _ => 3
+                    };
+                    (kind, fields.next().and_then(|account| account.parse::<u64>().ok()))
+                }
+            );
+            let file = self.directory.join(format!("{trader_id}_{date}.csv"));
+            let mut writer = File::create(&file).unwrap_or_else(
+                |err| panic!("Cannot create file {file:?}. Error: {err}")
+            );
+            let mut write = |line: &str| writeln!(writer, "{line}").unwrap_or_else(
+                |err| panic!("Cannot write to file {file:?}. Error: {err}")
+            );
+            write(&format!("# Statement of trader {trader_id} for {date}"));
+            write("RECORD,ACCOUNT,DATETIME,EXCHANGE,TRADED_PAIR,DIRECTION,PRICE,SIZE,AMOUNT");
+            trader_records.iter().for_each(|record| write(record))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            concrete::{
+                broker::accounts::OrderFill,
+                traded_pair::settlement::concrete::SpotSettlement,
+                types::{Lots, OrderID, Tick},
+            },
+            test_utils::{dt, pair},
+            types::Duration,
+        },
+        std::{env::temp_dir, fs::{create_dir_all, read_to_string}},
+    };
+
+    type Ledger = AccountLedger<u8, u8, &'static str, SpotSettlement>;
+
+    fn fill(
+        datetime: DateTime,
+        direction: Direction,
+        price: i64,
+        size: i64) -> OrderFill<u8, u8, &'static str, SpotSettlement>
+    {
+        OrderFill {
+            datetime,
+            trader_id: 1,
+            account: Some(AccountID(1)),
+            order_id: Some(OrderID(7)),
+            exchange_id: 2,
+            traded_pair: pair(),
+            direction,
+            price: Tick(price),
+            size: Lots(size),
+        }
+    }
+
+    fn read_lines(file: PathBuf) -> Vec<String> {
+        read_to_string(&file).unwrap_or_else(|err| panic!("Cannot read {file:?}. Error: {err}"))
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn test_daily_statements() {
+        let directory = temp_dir().join(
+            format!("trading_backtester_statements_{}", std::process::id())
+        );
+        create_dir_all(&directory).unwrap();
+        // 1% commission and 25% daily financing
+        let config = StatementConfig { commission_bps: 100.0, financing_rate: 90.0 };
+        let mut statements = StatementGenerator::new(&directory, config);
+        let mut ledger = Ledger::new();
+
+        statements.roll(dt(0), &ledger, [1, 2]);
+        ledger.book_fill(fill(dt(0), Direction::Buy, 100, 2));
+        ledger.book_fill(fill(dt(1), Direction::Sell, 200, 1));
+        statements.roll(dt(2), &ledger, [1, 2]);
+        assert!(!directory.join("1_2022-01-03.csv").exists());
+
+        statements.roll(dt(0) + Duration::days(1), &ledger, [1, 2]);
+        let asset = format!("{:?}", pair().quoted_asset);
+        let header = [
+            "# Statement of trader 1 for 2022-01-03".to_string(),
+            "RECORD,ACCOUNT,DATETIME,EXCHANGE,TRADED_PAIR,DIRECTION,PRICE,SIZE,AMOUNT".into(),
+        ];
+        let position = format!("POSITION,1,,2,{asset},,,1,");
+        assert_eq!(
+            read_lines(directory.join("1_2022-01-03.csv")),
+            header.iter().cloned().chain(
+                [
+                    format!("TRADE,1,2022-01-03 10:00:00,2,{asset},Buy,100,2,-200"),
+                    format!("COMMISSION,1,2022-01-03 10:00:00,2,{asset},,,,-2"),
+                    format!("TRADE,1,2022-01-03 10:00:01,2,{asset},Sell,200,1,200"),
+                    format!("COMMISSION,1,2022-01-03 10:00:01,2,{asset},,,,-2"),
+                    position.clone(),
+                    "FINANCING,1,,,,,,,-1".into(),
+                    "BALANCE,1,,,,,,,-5".into(),
+                ]
+            ).collect::<Vec<_>>()
+        );
+        assert_eq!(read_lines(directory.join("2_2022-01-03.csv")).len(), 2);
+        assert_eq!(
+            statements.get_balance(1, AccountID(1)),
+            AccountBalance { cash: -5.0, commissions: 4.0, financing: -1.0 }
+        );
+
+        // Trades are listed in the statement of their day only
+        statements.roll(dt(0) + Duration::days(2), &ledger, [1]);
+        assert_eq!(
+            read_lines(directory.join("1_2022-01-04.csv"))[2..],
+            [position, "FINANCING,1,,,,,,,-1.25".into(), "BALANCE,1,,,,,,,-6.25".into()]
+        )
+    }
+}
@@ -0,0 +1,1226 @@
+use {
+    crate::{
+        concrete::{
+            analytics::TradeLog,
+            broker::{
+                accounts::AccountLedger,
+                audit::{AuditStage, OrderAuditTrail},
+                BasicBroker,
+                entitlements::Entitlements,
+                faults::{FaultConfig, InjectedFaults},
+                GiveUpConfig,
+                kill_switch::{KillSwitch, RiskLimitBreach, RiskLimits},
+                RfqConfig,
+                statements::StatementGenerator,
+            },
+            heartbeat::HeartbeatConfig,
+            message_protocol::{
+                broker::{
+                    reply::{
+                        BasicBrokerReply,
+                        BasicBrokerToTrader,
+                        BorrowRecalled,
+                        BuyIn,
+                        CancellationReason,
+                        OrderCancelled,
+                        OrderCarriedOver,
+                        OrderPlacementDiscarded,
+                        PlacementDiscardingReason,
+                        QuoteRejected,
+                        QuoteRejectionReason,
+                        QuoteTraded,
+                        SubscriptionRejectionReason,
+                    },
+                    request::{BasicBrokerRequest, BasicBrokerToExchange},
+                    wakeup::{BasicBrokerToItself, GiveUp},
+                },
+                exchange::reply::{
+                    BasicExchangeToBroker,
+                    BasicExchangeToBrokerReply,
+                    CancellationReason as ExchangeCancellationReason,
+                    ExchangeEventNotification,
+                    LegExecuted,
+                    MarketOrderEventInfo,
+                    ObSnapshot,
+                    OrderCancelled as ExchangeOrderCancelled,
+                    OrderExecuted,
+                    RecoverySnapshot,
+                    SpreadOrderExecuted,
+                    TradeBusted,
+                },
+                replay::request::{BasicReplayToBroker, BasicReplayToBrokerRequest},
+                trader::request::{BasicTraderRequest, BasicTraderToBroker},
+            },
+            middleware::{BrokerMiddleware, Verdict},
+            order::{LimitOrderPlacingRequest, QuoteAcceptance, QuoteRequest, TimeInForce},
+            traded_pair::{settlement::concrete::SpotSettlement, TradedPair},
+            trader::subscriptions::{SubscriptionConfig, SubscriptionList, SubscriptionRequest},
+            types::{
+                AccountID,
+                Direction,
+                ExecID,
+                Fee,
+                Lots,
+                ObState,
+                OrderID,
+                Tick,
+                TickSize,
+                TradeID,
+            },
+        },
+        interface::{
+            broker::{Broker, BrokerAction, BrokerActionKind},
+            latency::LatencyGenerator,
+        },
+        kernel::LatentActionProcessor,
+        test_utils::{dt, pair, symbol_pair},
+        types::{Agent, DateTime, Duration, NanoDuration, TimeSync},
+        utils::queue::{LessElementBinaryHeap, MessageReceiver},
+    },
+    rand::{rngs::StdRng, Rng, SeedableRng},
+    std::{cell::{Cell, RefCell}, env::temp_dir, fs::create_dir_all, rc::Rc},
+};
+
+type TestBroker = BasicBroker<u8, u8, &'static str, &'static str, SpotSettlement>;
+type Action = <TestBroker as Agent>::Action;
+type Reply = BasicBrokerReply<&'static str, SpotSettlement>;
+type Wakeup = <TestBroker as Broker>::B2B;
+
+const EXCHANGE: &str = "MOEX";
+const BROKER: u8 = 0;
+const TRADER: u8 = 1;
+
+/// Collects the actions instead of pushing them into the kernel queue.
+struct ActionCollector<'a>(&'a mut Vec<Action>);
+
+impl LatentActionProcessor<Action, &'static str> for ActionCollector<'_> {
+    type KerMsg = usize;
+
+    fn process_action(
+        &mut self,
+        action: Action,
+        _: &mut impl LatencyGenerator<OuterID=&'static str>,
+        _: &mut impl Rng) -> usize
+    {
+        self.0.push(action);
+        self.0.len()
+    }
+}
+
+fn seconds(seconds: u64) -> NanoDuration {
+    NanoDuration::from_nanos(seconds * 1_000_000_000)
+}
+
+/// Connects the broker to the [`EXCHANGE`] and registers the [`TRADER`].
+fn connect(mut broker: TestBroker) -> TestBroker {
+    broker.upon_connection_to_exchange(EXCHANGE);
+    broker.register_trader(
+        TRADER,
+        [
+            SubscriptionConfig {
+                exchange: EXCHANGE,
+                traded_pair: pair(),
+                subscription: SubscriptionList::OB_SNAPSHOTS,
+            }
+        ],
+    );
+    broker
+}
+
+fn exchange(
+    broker: &mut TestBroker,
+    current_dt: DateTime,
+    content: BasicExchangeToBrokerReply<&'static str, SpotSettlement>) -> Vec<Action>
+{
+    *broker.current_datetime_mut() = current_dt;
+    let mut queue = LessElementBinaryHeap::default();
+    let mut actions = Vec::new();
+    broker.process_exchange_reply(
+        MessageReceiver::new(&mut queue),
+        ActionCollector(&mut actions),
+        BasicExchangeToBroker { broker_id: BROKER, exchange_dt: current_dt, content },
+        EXCHANGE,
+        &mut StdRng::seed_from_u64(0),
+    );
+    actions
+}
+
+fn trader(
+    broker: &mut TestBroker,
+    current_dt: DateTime,
+    content: BasicTraderRequest<&'static str, &'static str, SpotSettlement>) -> Vec<Action>
+{
+    *broker.current_datetime_mut() = current_dt;
+    let mut queue = LessElementBinaryHeap::default();
+    let mut actions = Vec::new();
+    broker.process_trader_request(
+        MessageReceiver::new(&mut queue),
+        ActionCollector(&mut actions),
+        BasicTraderToBroker { broker_id: BROKER, account: None, content },
+        TRADER,
+        &mut StdRng::seed_from_u64(0),
+    );
+    actions
+}
+
+fn wakeup(
+    broker: &mut TestBroker,
+    current_dt: DateTime,
+    scheduled_action: Wakeup) -> Vec<Action>
+{
+    *broker.current_datetime_mut() = current_dt;
+    let mut queue = LessElementBinaryHeap::default();
+    let mut actions = Vec::new();
+    broker.wakeup(
+        MessageReceiver::new(&mut queue),
+        ActionCollector(&mut actions),
+        scheduled_action,
+        &mut StdRng::seed_from_u64(0),
+    );
+    actions
+}
+
+/// Creates the day limit order to the [`EXCHANGE`].
+fn limit_order(
+    traded_pair: TradedPair<&'static str, SpotSettlement>,
+    order_id: u64,
+    direction: Direction,
+    price: i64,
+    size: i64) -> BasicTraderRequest<&'static str, &'static str, SpotSettlement>
+{
+    BasicTraderRequest::PlaceLimitOrder(
+        LimitOrderPlacingRequest {
+            traded_pair,
+            order_id: OrderID(order_id),
+            direction,
+            price: Tick(price),
+            size: Lots(size),
+            dummy: false,
+            time_in_force: TimeInForce::Day,
+        },
+        EXCHANGE,
+    )
+}
+
+/// Places the limit order of the [`TRADER`] and returns the ID it is routed to the exchange with.
+fn place(
+    broker: &mut TestBroker,
+    current_dt: DateTime,
+    order_id: u64,
+    direction: Direction,
+    price: i64,
+    size: i64) -> OrderID
+{
+    let request = limit_order(pair(), order_id, direction, price, size);
+    let actions = trader(broker, current_dt, request);
+    match get_requests(&actions).as_slice() {
+        [BasicBrokerRequest::PlaceLimitOrder(request)] => request.order_id,
+        requests => panic!("Unexpected requests: {requests:?}")
+    }
+}
+
+/// Executes the order routed to the exchange with the given ID.
+fn execute(
+    broker: &mut TestBroker,
+    current_dt: DateTime,
+    order_id: OrderID,
+    price: i64,
+    size: i64) -> Vec<Action>
+{
+    exchange(
+        broker,
+        current_dt,
+        BasicExchangeToBrokerReply::OrderExecuted(
+            OrderExecuted {
+                traded_pair: pair(),
+                order_id,
+                broker_order_id: order_id,
+                exchange_order_id: OrderID(0),
+                exec_id: ExecID(0),
+                trade_id: TradeID(0),
+                price: Tick(price),
+                size: Lots(size),
+                fee: Fee(0),
+            }
+        ),
+    )
+}
+
+/// Returns the wakeups the broker has scheduled along with their delays.
+fn get_wakeups(actions: &[Action]) -> Vec<(NanoDuration, Wakeup)> {
+    actions.iter()
+        .filter_map(
+            |action| match action.content {
+                BrokerActionKind::BrokerToItself(wakeup) => Some((action.delay, wakeup)),
+                _ => None
+            }
+        )
+        .collect()
+}
+
+/// Returns the replies received by the [`TRADER`].
+fn get_replies(actions: &[Action]) -> Vec<Reply> {
+    actions.iter()
+        .filter_map(
+            |action| match &action.content {
+                BrokerActionKind::BrokerToTrader(BasicBrokerToTrader { content, .. }) => {
+                    Some(content.clone())
+                }
+                _ => None
+            }
+        )
+        .collect()
+}
+
+/// Returns the fills given up to the other brokers.
+fn get_give_ups(actions: &[Action]) -> Vec<GiveUp<u8, u8, &'static str, &'static str, SpotSettlement>> {
+    actions.iter()
+        .filter_map(
+            |action| match action.content {
+                BrokerActionKind::BrokerToBroker(BasicBrokerToItself::GiveUp(give_up)) => {
+                    Some(give_up)
+                }
+                _ => None
+            }
+        )
+        .collect()
+}
+
+/// Returns the requests sent to the [`EXCHANGE`].
+fn get_requests(actions: &[Action]) -> Vec<BasicBrokerRequest<&'static str, SpotSettlement>> {
+    actions.iter()
+        .filter_map(
+            |action| match &action.content {
+                BrokerActionKind::BrokerToExchange(BasicBrokerToExchange { content, .. }) => {
+                    Some(*content)
+                }
+                _ => None
+            }
+        )
+        .collect()
+}
+
+#[test]
+fn test_session_outage_between_events() {
+    let mut broker = connect(TestBroker::new(BROKER).with_session_outages([(dt(10), dt(20))]));
+    let actions = trader(
+        &mut broker,
+        dt(0),
+        BasicTraderRequest::PlaceLimitOrder(
+            LimitOrderPlacingRequest {
+                traded_pair: pair(),
+                order_id: OrderID(7),
+                direction: Direction::Buy,
+                price: Tick(100),
+                size: Lots(1),
+                dummy: false,
+                time_in_force: TimeInForce::Day,
+            },
+            EXCHANGE,
+        ),
+    );
+    assert_eq!(get_wakeups(&actions), [(seconds(10), BasicBrokerToItself::SessionCheck)]);
+    let order_id = match get_requests(&actions).as_slice() {
+        [BasicBrokerRequest::PlaceLimitOrder(request)] => request.order_id,
+        requests => panic!("Unexpected requests: {requests:?}")
+    };
+
+    // Nothing else happens before the outage starts
+    let actions = wakeup(&mut broker, dt(10), BasicBrokerToItself::SessionCheck);
+    assert!(broker.is_session_down());
+    assert_eq!(get_wakeups(&actions), [(seconds(10), BasicBrokerToItself::SessionCheck)]);
+    assert!(
+        matches!(
+            get_requests(&actions).as_slice(),
+            [BasicBrokerRequest::CancelLimitOrder(request)] if request.order_id == order_id
+        )
+    );
+    assert_eq!(get_replies(&actions), [Reply::SessionDown]);
+
+    let actions = wakeup(&mut broker, dt(20), BasicBrokerToItself::SessionCheck);
+    assert!(!broker.is_session_down());
+    assert_eq!(get_wakeups(&actions), []);
+    assert_eq!(get_replies(&actions), [Reply::SessionUp]);
+}
+
+#[test]
+fn test_heartbeat_missed_between_events() {
+    let mut broker = connect(
+        TestBroker::new(BROKER).with_heartbeats(
+            HeartbeatConfig { interval: seconds(1), timeout: seconds(3) }
+        )
+    );
+    let actions = exchange(
+        &mut broker,
+        dt(0),
+        BasicExchangeToBrokerReply::ExchangeEventNotification(
+            ExchangeEventNotification::ExchangeOpen
+        ),
+    );
+    assert_eq!(get_wakeups(&actions), [(seconds(3), BasicBrokerToItself::HeartbeatCheck)]);
+    assert!(matches!(get_requests(&actions).as_slice(), [BasicBrokerRequest::Heartbeat(_)]));
+
+    // Exchange does not echo the heartbeat
+    let actions = wakeup(&mut broker, dt(3), BasicBrokerToItself::HeartbeatCheck);
+    assert!(broker.get_heartbeats().unwrap().is_missing(EXCHANGE));
+    assert_eq!(get_replies(&actions), [Reply::HeartbeatMissed]);
+    assert_eq!(get_wakeups(&actions), [])
+}
+
+#[test]
+fn test_periodic_portfolio_snapshots() {
+    let mut broker = connect(TestBroker::new(BROKER).with_portfolio_snapshots(seconds(5)));
+    let actions = exchange(
+        &mut broker,
+        dt(0),
+        BasicExchangeToBrokerReply::ExchangeEventNotification(
+            ExchangeEventNotification::ExchangeOpen
+        ),
+    );
+    assert_eq!(get_wakeups(&actions), [(seconds(5), BasicBrokerToItself::PortfolioSnapshot)]);
+    assert!(matches!(get_replies(&actions).first(), Some(Reply::PortfolioSnapshot(_))));
+
+    let actions = wakeup(&mut broker, dt(5), BasicBrokerToItself::PortfolioSnapshot);
+    assert_eq!(get_wakeups(&actions), [(seconds(5), BasicBrokerToItself::PortfolioSnapshot)]);
+    assert!(matches!(get_replies(&actions).as_slice(), [Reply::PortfolioSnapshot(_)]))
+}
+
+#[test]
+fn test_conflated_snapshot_flush() {
+    let mut broker = connect(TestBroker::new(BROKER).with_snapshot_conflation(seconds(5)));
+    let mut snapshot = |current_dt, best_bid| exchange(
+        &mut broker,
+        current_dt,
+        BasicExchangeToBrokerReply::ExchangeEventNotification(
+            ExchangeEventNotification::ObSnapshot(
+                Rc::new(
+                    ObSnapshot {
+                        traded_pair: pair(),
+                        state: ObState {
+                            bids: vec![(Tick(best_bid), vec![(Lots(1), current_dt)])],
+                            asks: vec![],
+                        },
+                    }
+                )
+            )
+        ),
+    );
+    let get_best_bids = |actions: &[Action]| -> Vec<_> {
+        get_replies(actions).into_iter()
+            .map(
+                |reply| match reply {
+                    Reply::ExchangeEventNotification(
+                        ExchangeEventNotification::ObSnapshot(snapshot)
+                    ) => snapshot.state.bids[0].0,
+                    reply => panic!("Unexpected reply: {reply:?}")
+                }
+            )
+            .collect()
+    };
+
+    let actions = snapshot(dt(0), 100);
+    assert_eq!(get_best_bids(&actions), [Tick(100)]);
+    assert_eq!(get_wakeups(&actions), []);
+
+    // Trader is busy, so the snapshots are held back until the window elapses
+    let actions = snapshot(dt(1), 101);
+    assert_eq!(get_best_bids(&actions), []);
+    assert_eq!(get_wakeups(&actions), [(seconds(4), BasicBrokerToItself::SnapshotFlush)]);
+    let actions = snapshot(dt(2), 102);
+    assert_eq!(get_best_bids(&actions), []);
+    assert_eq!(get_wakeups(&actions), []);
+
+    // Nothing else happens before the window elapses
+    let actions = wakeup(&mut broker, dt(5), BasicBrokerToItself::SnapshotFlush);
+    assert_eq!(get_best_bids(&actions), [Tick(102)]);
+    assert_eq!(get_wakeups(&actions), []);
+    assert_eq!(broker.get_num_conflated_snapshots(), 1)
+}
+
+#[test]
+fn test_give_up() {
+    const CLEARING_BROKER: u8 = 2;
+    const CLEARING_TRADER: u8 = 5;
+    let config = GiveUpConfig {
+        clearing_broker_id: CLEARING_BROKER,
+        trader_id: CLEARING_TRADER,
+        account: None,
+    };
+    let mut broker = connect(
+        TestBroker::new(BROKER).with_portfolio_tracking().with_give_up(TRADER, config)
+    );
+    let mut clearing_broker = TestBroker::new(CLEARING_BROKER).with_portfolio_tracking();
+    let actions = trader(
+        &mut broker,
+        dt(0),
+        BasicTraderRequest::PlaceLimitOrder(
+            LimitOrderPlacingRequest {
+                traded_pair: pair(),
+                order_id: OrderID(7),
+                direction: Direction::Buy,
+                price: Tick(100),
+                size: Lots(2),
+                dummy: false,
+                time_in_force: TimeInForce::Day,
+            },
+            EXCHANGE,
+        ),
+    );
+    let order_id = match get_requests(&actions).as_slice() {
+        [BasicBrokerRequest::PlaceLimitOrder(request)] => request.order_id,
+        requests => panic!("Unexpected requests: {requests:?}")
+    };
+    let actions = exchange(
+        &mut broker,
+        dt(1),
+        BasicExchangeToBrokerReply::OrderExecuted(
+            OrderExecuted {
+                traded_pair: pair(),
+                order_id,
+                broker_order_id: order_id,
+                exchange_order_id: OrderID(0),
+                exec_id: ExecID(0),
+                trade_id: TradeID(0),
+                price: Tick(100),
+                size: Lots(2),
+                fee: Fee(0),
+            }
+        ),
+    );
+    let give_up = GiveUp {
+        clearing_broker_id: CLEARING_BROKER,
+        trader_id: CLEARING_TRADER,
+        account: None,
+        exchange_id: EXCHANGE,
+        traded_pair: pair(),
+        direction: Direction::Buy,
+        price: Tick(100),
+        size: Lots(2),
+        executed_dt: dt(1),
+    };
+    assert_eq!(get_give_ups(&actions), [give_up]);
+    assert_eq!(broker.get_b2b_recipient(&give_up.into()), CLEARING_BROKER);
+    assert!(broker.get_portfolio(TRADER).is_none());
+
+    let mut give_up_to_clearing_broker = |give_up: GiveUp<_, _, _, _, _>| {
+        let mut queue = LessElementBinaryHeap::default();
+        clearing_broker.process_broker_message(
+            MessageReceiver::new(&mut queue),
+            ActionCollector(&mut vec![]),
+            give_up.into(),
+            BROKER,
+            &mut StdRng::seed_from_u64(0),
+        );
+        clearing_broker.get_portfolio(CLEARING_TRADER).unwrap().get_position(EXCHANGE, pair()).size
+    };
+    assert_eq!(give_up_to_clearing_broker(give_up), Lots(2));
+
+    // Busted fills are given up in the reversed direction
+    let actions = exchange(
+        &mut broker,
+        dt(2),
+        BasicExchangeToBrokerReply::TradeBusted(
+            TradeBusted {
+                traded_pair: pair(),
+                order_id,
+                exec_id: ExecID(0),
+                trade_id: TradeID(0),
+                direction: Direction::Buy,
+                price: Tick(100),
+                size: Lots(2),
+            }
+        ),
+    );
+    let reversed = GiveUp { direction: Direction::Sell, executed_dt: dt(2), ..give_up };
+    assert_eq!(get_give_ups(&actions), [reversed]);
+    assert_eq!(give_up_to_clearing_broker(reversed), Lots(0))
+}
+
+#[test]
+fn test_spread_fill_books_legs() {
+    let (near, far) = (symbol_pair("SiZ"), symbol_pair("SiH"));
+    let mut broker = connect(
+        TestBroker::new(BROKER)
+            .with_portfolio_tracking()
+            .with_account_ledger(AccountLedger::new())
+    );
+    let actions = trader(
+        &mut broker,
+        dt(0),
+        BasicTraderRequest::PlaceLimitOrder(
+            LimitOrderPlacingRequest {
+                traded_pair: pair(),
+                order_id: OrderID(7),
+                direction: Direction::Sell,
+                price: Tick(6),
+                size: Lots(2),
+                dummy: false,
+                time_in_force: TimeInForce::Day,
+            },
+            EXCHANGE,
+        ),
+    );
+    let order_id = match get_requests(&actions).as_slice() {
+        [BasicBrokerRequest::PlaceLimitOrder(request)] => request.order_id,
+        requests => panic!("Unexpected requests: {requests:?}")
+    };
+    let executed = OrderExecuted {
+        traded_pair: pair(),
+        order_id,
+        broker_order_id: order_id,
+        exchange_order_id: OrderID(0),
+        exec_id: ExecID(0),
+        trade_id: TradeID(0),
+        price: Tick(6),
+        size: Lots(2),
+        fee: Fee(0),
+    };
+    let actions = exchange(
+        &mut broker,
+        dt(1),
+        BasicExchangeToBrokerReply::SpreadOrderExecuted(
+            Rc::new(
+                SpreadOrderExecuted {
+                    execution: BasicExchangeToBrokerReply::OrderExecuted(executed),
+                    legs: vec![
+                        LegExecuted {
+                            traded_pair: near,
+                            direction: Direction::Sell,
+                            price: Tick(103),
+                            size: Lots(2),
+                        },
+                        LegExecuted {
+                            traded_pair: far,
+                            direction: Direction::Buy,
+                            price: Tick(97),
+                            size: Lots(2),
+                        },
+                    ],
+                }
+            )
+        ),
+    );
+
+    // Trader is notified of the spread fill, whereas the positions are held in the legs
+    assert!(
+        matches!(
+            get_replies(&actions).as_slice(),
+            [Reply::OrderExecuted(OrderExecuted { order_id: OrderID(7), size: Lots(2), .. })]
+        )
+    );
+    let portfolio = broker.get_portfolio(TRADER).unwrap();
+    let ledger = broker.get_account_ledger().unwrap();
+    for (traded_pair, size) in [(pair(), Lots(0)), (near, Lots(-2)), (far, Lots(2))] {
+        assert_eq!(portfolio.get_position(EXCHANGE, traded_pair).size, size);
+        assert_eq!(
+            ledger.get_position(TRADER, AccountID::default(), EXCHANGE, traded_pair).size,
+            size
+        )
+    }
+}
+
+#[test]
+fn test_trade_log_without_ledger() {
+    let trade_log = Rc::new(RefCell::new(TradeLog::new()));
+    let mut broker = connect(TestBroker::new(BROKER).with_trade_log(Rc::clone(&trade_log)));
+    let order_id = place(&mut broker, dt(0), 7, Direction::Buy, 100, 2);
+    execute(&mut broker, dt(1), order_id, 100, 2);
+    assert_eq!(trade_log.borrow().len(), 1);
+    assert!(broker.get_account_ledger().is_none())
+}
+
+#[test]
+fn test_trade_bust_unwinds_positions() {
+    let mut broker = connect(
+        TestBroker::new(BROKER)
+            .with_portfolio_tracking()
+            .with_account_ledger(AccountLedger::new())
+    );
+    let order_id = place(&mut broker, dt(0), 7, Direction::Buy, 100, 2);
+    execute(&mut broker, dt(1), order_id, 100, 2);
+    let position_size = |broker: &TestBroker| (
+        broker.get_portfolio(TRADER).unwrap().get_position(EXCHANGE, pair()).size,
+        broker.get_account_ledger().unwrap()
+            .get_position(TRADER, AccountID::default(), EXCHANGE, pair())
+            .size
+    );
+    assert_eq!(position_size(&broker), (Lots(2), Lots(2)));
+
+    let actions = exchange(
+        &mut broker,
+        dt(2),
+        BasicExchangeToBrokerReply::TradeBusted(
+            TradeBusted {
+                traded_pair: pair(),
+                order_id,
+                exec_id: ExecID(0),
+                trade_id: TradeID(0),
+                direction: Direction::Buy,
+                price: Tick(100),
+                size: Lots(2),
+            }
+        ),
+    );
+    assert!(
+        matches!(
+            get_replies(&actions).as_slice(),
+            [Reply::TradeBusted(TradeBusted { order_id: OrderID(7), size: Lots(2), .. })]
+        )
+    );
+    assert_eq!(position_size(&broker), (Lots(0), Lots(0)))
+}
+
+#[test]
+fn test_gtd_orders_are_carried_over() {
+    let mut broker = connect(TestBroker::new(BROKER));
+    let place_gtd = |broker: &mut TestBroker, order_id, expiration| {
+        let mut request = limit_order(pair(), order_id, Direction::Buy, 100, 1);
+        if let BasicTraderRequest::PlaceLimitOrder(request, _) = &mut request {
+            request.time_in_force = TimeInForce::GoodTillDate(expiration)
+        }
+        match get_requests(&trader(broker, dt(0), request)).as_slice() {
+            [BasicBrokerRequest::PlaceLimitOrder(request)] => request.order_id,
+            requests => panic!("Unexpected requests: {requests:?}")
+        }
+    };
+    let carried_over = place_gtd(&mut broker, 7, dt(30));
+    let expiring = place_gtd(&mut broker, 8, dt(10));
+    let close = |broker: &mut TestBroker, current_dt, order_id| get_replies(
+        &exchange(
+            broker,
+            current_dt,
+            BasicExchangeToBrokerReply::OrderCancelled(
+                ExchangeOrderCancelled {
+                    traded_pair: pair(),
+                    order_id,
+                    reason: ExchangeCancellationReason::ExchangeClosed,
+                }
+            ),
+        )
+    );
+    let trades_started = |broker: &mut TestBroker, current_dt| exchange(
+        broker,
+        current_dt,
+        BasicExchangeToBrokerReply::ExchangeEventNotification(
+            ExchangeEventNotification::TradesStarted {
+                traded_pair: pair(),
+                price_step: TickSize(0.01),
+            }
+        ),
+    );
+    let carried_over_reply = Reply::OrderCarriedOver(
+        OrderCarriedOver { traded_pair: pair(), order_id: OrderID(7), expiration: Some(dt(30)) }
+    );
+    let expired_reply = Reply::OrderCancelled(
+        OrderCancelled {
+            traded_pair: pair(),
+            order_id: OrderID(8),
+            reason: CancellationReason::Expired,
+        }
+    );
+
+    // Orders expiring by the close are cancelled, whereas the rest are kept dormant
+    assert_eq!(close(&mut broker, dt(10), carried_over), [carried_over_reply.clone()]);
+    assert_eq!(close(&mut broker, dt(10), expiring), [expired_reply]);
+    assert!(
+        matches!(
+            get_requests(&trades_started(&mut broker, dt(20))).as_slice(),
+            [BasicBrokerRequest::PlaceLimitOrder(request)] if request.order_id == carried_over
+        )
+    );
+
+    // Dormant orders expire on the next event the broker processes
+    assert_eq!(close(&mut broker, dt(25), carried_over), [carried_over_reply]);
+    let actions = trades_started(&mut broker, dt(30));
+    assert!(get_requests(&actions).is_empty());
+    assert!(
+        get_replies(&actions).contains(
+            &Reply::OrderCancelled(
+                OrderCancelled {
+                    traded_pair: pair(),
+                    order_id: OrderID(7),
+                    reason: CancellationReason::Expired,
+                }
+            )
+        )
+    )
+}
+
+#[test]
+fn test_borrow_recall_buys_in_short_positions() {
+    let mut broker = connect(TestBroker::new(BROKER).with_account_ledger(AccountLedger::new()));
+    let order_id = place(&mut broker, dt(0), 7, Direction::Sell, 100, 3);
+    execute(&mut broker, dt(1), order_id, 100, 3);
+    let replay = |broker: &mut TestBroker, current_dt, content| {
+        *broker.current_datetime_mut() = current_dt;
+        let mut queue = LessElementBinaryHeap::default();
+        let mut actions = Vec::new();
+        broker.process_replay_request(
+            MessageReceiver::new(&mut queue),
+            ActionCollector(&mut actions),
+            BasicReplayToBroker { broker_id: BROKER, exchange_id: EXCHANGE, content },
+            &mut StdRng::seed_from_u64(0),
+        );
+        actions
+    };
+
+    let actions = replay(
+        &mut broker,
+        dt(2),
+        BasicReplayToBrokerRequest::RecallBorrow { traded_pair: pair(), deadline: dt(10) },
+    );
+    assert_eq!(
+        get_replies(&actions),
+        [
+            Reply::BorrowRecalled(
+                BorrowRecalled { traded_pair: pair(), size: Lots(3), deadline: dt(10) }
+            )
+        ]
+    );
+    assert!(get_requests(&actions).is_empty());
+
+    let actions = replay(&mut broker, dt(10), BasicReplayToBrokerRequest::BuyIn(pair()));
+    let buy_in_id = match get_replies(&actions).as_slice() {
+        [Reply::BuyInPlaced(BuyIn { order_id, account, size: Lots(3), .. })] => {
+            assert_eq!(*account, AccountID::default());
+            *order_id
+        }
+        replies => panic!("Unexpected replies: {replies:?}")
+    };
+    assert!(
+        matches!(
+            get_requests(&actions).as_slice(),
+            [BasicBrokerRequest::PlaceMarketOrder(request)] if request.order_id == buy_in_id
+                && request.direction == Direction::Buy
+                && request.size == Lots(3)
+        )
+    );
+
+    let actions = execute(&mut broker, dt(11), buy_in_id, 105, 3);
+    assert!(
+        matches!(
+            get_replies(&actions).as_slice(),
+            [Reply::BuyInExecuted(executed)] if executed.order_id == buy_in_id
+        )
+    );
+    let ledger = broker.get_account_ledger().unwrap();
+    assert_eq!(ledger.get_position(TRADER, AccountID::default(), EXCHANGE, pair()).size, Lots(0));
+
+    // Nothing is left to buy in
+    let actions = replay(&mut broker, dt(12), BasicReplayToBrokerRequest::BuyIn(pair()));
+    assert!(actions.is_empty())
+}
+
+#[test]
+fn test_audit_trail_records_order_lifecycle() {
+    let audit_trail = Rc::new(RefCell::new(OrderAuditTrail::new()));
+    let mut broker = connect(TestBroker::new(BROKER).with_audit_trail(Rc::clone(&audit_trail)));
+    let broker_order_id = place(&mut broker, dt(0), 7, Direction::Buy, 100, 2);
+    execute(&mut broker, dt(1), broker_order_id, 100, 2);
+
+    let audit_trail = audit_trail.borrow();
+    let records = audit_trail.get_records(TRADER, OrderID(7));
+    let stages: Vec<_> = records.iter()
+        .map(|record| (record.stage, record.kind, record.broker_dt, record.exchange_dt))
+        .collect();
+    assert_eq!(
+        stages,
+        [
+            (AuditStage::Received, "PLACE_LIMIT_ORDER", Some(dt(0)), None),
+            (AuditStage::Routed, "PLACE_LIMIT_ORDER", Some(dt(0)), None),
+            (AuditStage::Replied, "ORDER_EXECUTED", Some(dt(1)), Some(dt(1))),
+        ]
+    );
+    assert_eq!(records[1].broker_order_id, Some(broker_order_id));
+    assert_eq!(records[2].size, Some(Lots(2)))
+}
+
+#[test]
+fn test_kill_switch_blocks_trader_on_loss() {
+    let limits = RiskLimits { max_loss: Some(50.0), ..Default::default() };
+    let mut broker = connect(TestBroker::new(BROKER).with_kill_switch(KillSwitch::new(limits)));
+    let filled_order_id = place(&mut broker, dt(0), 7, Direction::Buy, 100, 2);
+    execute(&mut broker, dt(1), filled_order_id, 100, 2);
+    let resting_order_id = place(&mut broker, dt(1), 8, Direction::Buy, 90, 1);
+
+    // Long position of 2 lots marked from 100 to 70 loses 60
+    let actions = exchange(
+        &mut broker,
+        dt(2),
+        BasicExchangeToBrokerReply::ExchangeEventNotification(
+            ExchangeEventNotification::TradeExecuted(
+                MarketOrderEventInfo {
+                    traded_pair: pair(),
+                    trade_id: TradeID(1),
+                    direction: Direction::Sell,
+                    price: Tick(70),
+                    size: Lots(1),
+                }
+            )
+        ),
+    );
+    assert!(
+        matches!(
+            get_requests(&actions).as_slice(),
+            [BasicBrokerRequest::CancelLimitOrder(request)] if request.order_id == resting_order_id
+        )
+    );
+    let kill_switch = broker.get_kill_switch().unwrap();
+    assert!(kill_switch.is_tripped(TRADER));
+    assert_eq!(kill_switch.get_events()[0].breach, RiskLimitBreach::Loss(-60.0));
+
+    let actions = trader(&mut broker, dt(3), limit_order(pair(), 9, Direction::Sell, 70, 2));
+    assert!(get_requests(&actions).is_empty());
+    assert!(
+        matches!(
+            get_replies(&actions).as_slice(),
+            [Reply::OrderPlacementDiscarded(discarded)]
+            if discarded.reason == PlacementDiscardingReason::KillSwitchTripped
+        )
+    )
+}
+
+#[test]
+fn test_entitlements_gate_orders_and_subscriptions() {
+    let entitlements = Entitlements::new().with_traded_pair(EXCHANGE, pair());
+    let mut broker = connect(TestBroker::new(BROKER).with_entitlements(TRADER, entitlements));
+    place(&mut broker, dt(0), 7, Direction::Buy, 100, 1);
+
+    let actions = trader(
+        &mut broker,
+        dt(1),
+        limit_order(symbol_pair("EUR"), 8, Direction::Buy, 100, 1),
+    );
+    assert!(get_requests(&actions).is_empty());
+    assert!(
+        matches!(
+            get_replies(&actions).as_slice(),
+            [Reply::OrderPlacementDiscarded(discarded)]
+            if discarded.reason == PlacementDiscardingReason::TraderNotEntitled
+        )
+    );
+
+    let actions = trader(
+        &mut broker,
+        dt(2),
+        BasicTraderRequest::Subscribe(
+            SubscriptionRequest {
+                traded_pair: symbol_pair("EUR"),
+                subscription: SubscriptionList::OB_SNAPSHOTS,
+            },
+            EXCHANGE,
+        ),
+    );
+    assert!(get_requests(&actions).is_empty());
+    assert!(
+        matches!(
+            get_replies(&actions).as_slice(),
+            [Reply::SubscriptionRejected(rejected)]
+            if rejected.reason == SubscriptionRejectionReason::TraderNotEntitled
+        )
+    )
+}
+
+#[test]
+fn test_subscription_starts_with_recovery_snapshot() {
+    let mut broker = connect(TestBroker::new(BROKER));
+    let traded_pair = symbol_pair("EUR");
+    let subscription = SubscriptionRequest { traded_pair, subscription: SubscriptionList::TRADES };
+    let actions = trader(&mut broker, dt(0), BasicTraderRequest::Subscribe(subscription, EXCHANGE));
+    assert!(get_replies(&actions).is_empty());
+    assert!(
+        matches!(
+            get_requests(&actions).as_slice(),
+            [BasicBrokerRequest::RequestRecoverySnapshot(requested)] if *requested == traded_pair
+        )
+    );
+    let trade = |broker: &mut TestBroker, current_dt| get_replies(
+        &exchange(
+            broker,
+            current_dt,
+            BasicExchangeToBrokerReply::ExchangeEventNotification(
+                ExchangeEventNotification::TradeExecuted(
+                    MarketOrderEventInfo {
+                        traded_pair,
+                        trade_id: TradeID(0),
+                        direction: Direction::Buy,
+                        price: Tick(100),
+                        size: Lots(1),
+                    }
+                )
+            ),
+        )
+    );
+    let snapshot = Rc::new(
+        RecoverySnapshot {
+            traded_pair,
+            state: ObState { bids: vec![], asks: vec![] },
+            recent_trades: vec![],
+        }
+    );
+    let recover = |broker: &mut TestBroker, current_dt| get_replies(
+        &exchange(
+            broker,
+            current_dt,
+            BasicExchangeToBrokerReply::RecoverySnapshot(Rc::clone(&snapshot)),
+        )
+    );
+
+    // Events preceding the snapshot are not forwarded
+    assert!(trade(&mut broker, dt(1)).is_empty());
+    assert_eq!(recover(&mut broker, dt(2)), [Reply::RecoverySnapshot(Rc::clone(&snapshot))]);
+    assert!(
+        matches!(
+            trade(&mut broker, dt(3)).as_slice(),
+            [Reply::ExchangeEventNotification(ExchangeEventNotification::TradeExecuted(_))]
+        )
+    );
+    // Snapshot is not resent to the traders already recovered
+    assert!(recover(&mut broker, dt(4)).is_empty())
+}
+
+#[test]
+#[should_panic(expected = "Trader 1 is not entitled to subscribe")]
+fn test_initial_subscription_requires_entitlement() {
+    let entitlements = Entitlements::new().with_traded_pair(EXCHANGE, symbol_pair("EUR"));
+    connect(TestBroker::new(BROKER).with_entitlements(TRADER, entitlements));
+}
+
+#[test]
+fn test_fault_injection_loses_requests() {
+    let config = FaultConfig { request_lost: 1.0, ..Default::default() };
+    let mut broker = connect(TestBroker::new(BROKER).with_fault_injection(config));
+    let actions = trader(&mut broker, dt(0), limit_order(pair(), 7, Direction::Buy, 100, 1));
+    assert!(actions.is_empty());
+    assert_eq!(broker.get_injected_faults().unwrap().requests_lost, 1)
+}
+
+#[test]
+fn test_fault_injection_duplicates_and_delays_replies() {
+    let config = FaultConfig { reply_duplicated: 1.0, ..Default::default() };
+    let mut broker = connect(TestBroker::new(BROKER).with_fault_injection(config));
+    let order_id = place(&mut broker, dt(0), 7, Direction::Buy, 100, 1);
+    let replies = get_replies(&execute(&mut broker, dt(1), order_id, 100, 1));
+    assert!(matches!(replies.as_slice(), [Reply::OrderExecuted(_), Reply::OrderExecuted(_)]));
+    assert_eq!(replies[0], replies[1]);
+
+    let reply_delay = seconds(1);
+    let config = FaultConfig { reply_delayed: 1.0, reply_delay, ..Default::default() };
+    let mut broker = connect(TestBroker::new(BROKER).with_fault_injection(config));
+    let order_id = place(&mut broker, dt(0), 7, Direction::Buy, 100, 1);
+    let actions = execute(&mut broker, dt(1), order_id, 100, 1);
+    assert!(
+        matches!(
+            actions.as_slice(),
+            [BrokerAction { delay, content: BrokerActionKind::BrokerToTrader(_) }]
+            if *delay == reply_delay
+        )
+    );
+    assert_eq!(
+        broker.get_injected_faults(),
+        Some(InjectedFaults { replies_delayed: 1, ..Default::default() })
+    )
+}
+
+#[test]
+fn test_statements_are_written_on_the_next_day() {
+    let directory = temp_dir().join(
+        format!("trading_backtester_broker_statements_{}", std::process::id())
+    );
+    create_dir_all(&directory).unwrap();
+    let statements = StatementGenerator::new(&directory, Default::default());
+    let mut broker = connect(TestBroker::new(BROKER).with_statements(statements));
+    let order_id = place(&mut broker, dt(0), 7, Direction::Buy, 100, 2);
+    execute(&mut broker, dt(1), order_id, 100, 2);
+    let file = directory.join(format!("{TRADER}_2022-01-03.csv"));
+    assert!(!file.exists());
+
+    place(&mut broker, dt(0) + Duration::days(1), 8, Direction::Sell, 100, 2);
+    assert!(file.exists());
+    let balance = broker.get_statements().unwrap().get_balance(TRADER, AccountID(0));
+    assert_eq!(balance.cash, -200.0)
+}
+
+/// Caps the limit prices at 105, rejects the order 8, suppresses the order 9
+/// and the execution reports, and counts the trader requests it is called for.
+struct OrderGate {
+    calls: Rc<Cell<usize>>,
+}
+
+impl BrokerMiddleware<u8, u8, &'static str, &'static str, SpotSettlement> for OrderGate {
+    fn on_trader_request(
+        &mut self,
+        _: DateTime,
+        _: u8,
+        request: &mut BasicTraderToBroker<u8, &'static str, &'static str, SpotSettlement>,
+    ) -> Verdict<Reply>
+    {
+        self.calls.set(self.calls.get() + 1);
+        if let BasicTraderRequest::PlaceLimitOrder(order, _) = &mut request.content {
+            match order.order_id {
+                OrderID(8) => {
+                    return Verdict::Reject(
+                        Reply::OrderPlacementDiscarded(
+                            OrderPlacementDiscarded {
+                                traded_pair: order.traded_pair,
+                                order_id: order.order_id,
+                                reason: PlacementDiscardingReason::OrderTypeNotSupported,
+                            }
+                        )
+                    );
+                }
+                OrderID(9) => return Verdict::Suppress,
+                _ => order.price = order.price.min(Tick(105))
+            }
+        }
+        Verdict::Pass
+    }
+
+    fn on_exchange_reply(
+        &mut self,
+        _: DateTime,
+        _: &'static str,
+        reply: &mut BasicExchangeToBroker<u8, &'static str, SpotSettlement>) -> bool
+    {
+        !matches!(reply.content, BasicExchangeToBrokerReply::OrderExecuted(_))
+    }
+}
+
+#[test]
+fn test_middleware() {
+    let (first_calls, second_calls) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(0)));
+    let mut broker = connect(
+        TestBroker::new(BROKER)
+            .with_middleware(OrderGate { calls: Rc::clone(&first_calls) })
+            .with_middleware(OrderGate { calls: Rc::clone(&second_calls) })
+    );
+
+    let actions = trader(&mut broker, dt(0), limit_order(pair(), 7, Direction::Buy, 110, 1));
+    let order_id = match get_requests(&actions).as_slice() {
+        [BasicBrokerRequest::PlaceLimitOrder(request)] if request.price == Tick(105) => {
+            request.order_id
+        }
+        requests => panic!("Unexpected requests: {requests:?}")
+    };
+
+    let actions = trader(&mut broker, dt(1), limit_order(pair(), 8, Direction::Buy, 100, 1));
+    assert!(get_requests(&actions).is_empty());
+    assert!(
+        matches!(
+            get_replies(&actions).as_slice(),
+            [Reply::OrderPlacementDiscarded(discarded)]
+            if discarded.reason == PlacementDiscardingReason::OrderTypeNotSupported
+        )
+    );
+    assert!(trader(&mut broker, dt(2), limit_order(pair(), 9, Direction::Buy, 100, 1)).is_empty());
+    // Hooks after the suppressing or rejecting one are not called
+    assert_eq!((first_calls.get(), second_calls.get()), (3, 1));
+
+    assert!(execute(&mut broker, dt(3), order_id, 105, 1).is_empty())
+}
+
+/// Publishes the trade at the [`EXCHANGE`] that the firm quotes of the broker refer to.
+fn quote_reference_trade(broker: &mut TestBroker, current_dt: DateTime, price: i64) {
+    exchange(
+        broker,
+        current_dt,
+        BasicExchangeToBrokerReply::ExchangeEventNotification(
+            ExchangeEventNotification::TradeExecuted(
+                MarketOrderEventInfo {
+                    traded_pair: pair(),
+                    trade_id: TradeID(0),
+                    direction: Direction::Buy,
+                    price: Tick(price),
+                    size: Lots(1),
+                }
+            )
+        ),
+    );
+}
+
+/// Requests the firm quote to buy 2 lots and accepts it at the `accept_dt`.
+fn trade_quote(
+    broker: &mut TestBroker,
+    request_dt: DateTime,
+    accept_dt: DateTime,
+    request_id: u64) -> Vec<Action>
+{
+    let request_id = OrderID(request_id);
+    trader(
+        broker,
+        request_dt,
+        BasicTraderRequest::RequestQuote(
+            QuoteRequest { traded_pair: pair(), request_id, direction: Direction::Buy, size: Lots(2) },
+            EXCHANGE,
+        ),
+    );
+    trader(
+        broker,
+        accept_dt,
+        BasicTraderRequest::AcceptQuote(
+            QuoteAcceptance { traded_pair: pair(), request_id },
+            EXCHANGE,
+        ),
+    )
+}
+
+#[test]
+fn test_quote_acceptance() {
+    let rfq_config = RfqConfig { half_spread: Tick(1), validity: seconds(10) };
+    let mut broker = connect(
+        TestBroker::new(BROKER)
+            .with_portfolio_tracking()
+            .with_rfq_quoting(rfq_config)
+            .with_session_outages([(dt(5), dt(10))])
+    );
+    quote_reference_trade(&mut broker, dt(0), 100);
+    let actions = trade_quote(&mut broker, dt(1), dt(2), 0);
+    let traded = QuoteTraded {
+        traded_pair: pair(),
+        request_id: OrderID(0),
+        direction: Direction::Buy,
+        price: Tick(101),
+        size: Lots(2),
+    };
+    assert_eq!(get_replies(&actions), [Reply::QuoteTraded(traded)]);
+    let position = broker.get_portfolio(TRADER).unwrap().get_position(EXCHANGE, pair());
+    assert_eq!((position.size, position.cost_basis), (Lots(2), 202));
+
+    // Acceptances are rejected while the session is down
+    let actions = trade_quote(&mut broker, dt(4), dt(6), 1);
+    let rejected = QuoteRejected {
+        traded_pair: pair(),
+        request_id: OrderID(1),
+        reason: QuoteRejectionReason::BrokerSessionDown,
+    };
+    assert_eq!(get_replies(&actions).last(), Some(&Reply::QuoteRejected(rejected)));
+    assert_eq!(broker.get_portfolio(TRADER).unwrap().get_position(EXCHANGE, pair()).size, Lots(2))
+}
+
+#[test]
+fn test_quote_acceptance_give_up() {
+    const CLEARING_BROKER: u8 = 2;
+    let config = GiveUpConfig { clearing_broker_id: CLEARING_BROKER, trader_id: TRADER, account: None };
+    let rfq_config = RfqConfig { half_spread: Tick(1), validity: seconds(10) };
+    let mut broker = connect(
+        TestBroker::new(BROKER).with_rfq_quoting(rfq_config).with_give_up(TRADER, config)
+    );
+    quote_reference_trade(&mut broker, dt(0), 100);
+    let actions = trade_quote(&mut broker, dt(1), dt(2), 0);
+    assert_eq!(
+        get_give_ups(&actions),
+        [
+            GiveUp {
+                clearing_broker_id: CLEARING_BROKER,
+                trader_id: TRADER,
+                account: None,
+                exchange_id: EXCHANGE,
+                traded_pair: pair(),
+                direction: Direction::Buy,
+                price: Tick(101),
+                size: Lots(2),
+                executed_dt: dt(2),
+            }
+        ]
+    )
+}
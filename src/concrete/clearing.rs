@@ -0,0 +1,541 @@
+use {
+    crate::{
+        concrete::{
+            broker::accounts::{AccountPosition, PositionMode},
+            traded_pair::{settlement::GetSettlementLag, TradedPair},
+            types::{Direction, Lots, Tick},
+        },
+        types::{DateTime, Id},
+        utils::collections::{HashMap, HashSet},
+    },
+    std::{cell::RefCell, rc::Rc},
+};
+
+/// [`ClearingHouse`] shared between the exchanges of the single simulation thread.
+pub type SharedClearingHouse<BrokerID, ExchangeID, Symbol, Settlement> = Rc<
+    RefCell<ClearingHouse<BrokerID, ExchangeID, Symbol, Settlement>>
+>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Trade novated by the [`ClearingHouse`].
+/// The clearing house becomes the buyer to the seller and the seller to the buyer.
+pub struct NovatedTrade<BrokerID, ExchangeID, Symbol, Settlement>
+    where BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    /// Datetime of the trade.
+    pub datetime: DateTime,
+    /// Exchange the trade happened at.
+    pub exchange_id: ExchangeID,
+    /// Traded pair.
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    /// Buyer. `None` if the order came from the replay.
+    pub buyer: Option<BrokerID>,
+    /// Seller. `None` if the order came from the replay.
+    pub seller: Option<BrokerID>,
+    /// Trade price.
+    pub price: Tick,
+    /// Trade size.
+    pub size: Lots,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Variation margin call issued by the [`ClearingHouse`].
+pub struct MarginCall<BrokerID: Id> {
+    /// Datetime of the settlement the call is issued at.
+    pub datetime: DateTime,
+    /// Broker that should deposit the collateral.
+    pub broker_id: BrokerID,
+    /// Amount of the collateral to deposit.
+    pub amount: f64,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+/// Position of the clearing member in a single instrument.
+pub struct Position {
+    /// Signed position size. Positive for long positions.
+    pub size: i64,
+    /// Long and short legs of the position, according to the position mode of the member.
+    /// Margin is required for their gross size. The cash flow of the legs is not tracked.
+    pub legs: AccountPosition,
+    /// Value of the position as of the last settlement
+    /// plus the value of the trades made since then, in ticks times lots.
+    marked_value: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Margin account of the clearing member.
+pub struct MarginAccount<ExchangeID, Symbol, Settlement>
+    where ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    /// Deposited collateral including the accrued variation margin.
+    pub collateral: f64,
+    /// Open positions.
+    pub positions: HashMap<(ExchangeID, TradedPair<Symbol, Settlement>), Position>,
+    /// Outstanding margin call, if any.
+    pub margin_call: Option<f64>,
+}
+
+impl<ExchangeID, Symbol, Settlement> Default for MarginAccount<ExchangeID, Symbol, Settlement>
+    where ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    fn default() -> Self {
+        Self {
+            collateral: 0.0,
+            positions: Default::default(),
+            margin_call: None,
+        }
+    }
+}
+
+/// Central counterparty that novates trades reported by the
+/// [`BasicExchange`](crate::concrete::exchange::BasicExchange)s,
+/// maintains per-broker margin accounts and issues variation margin calls at settlement times.
+/// Collateral, margins and margin calls are measured in ticks times lots,
+/// like the cash flows of the [`AccountLedger`](crate::concrete::broker::accounts::AccountLedger).
+///
+/// Usually shared between the exchanges of the single simulation thread
+/// as the [`SharedClearingHouse`].
+pub struct ClearingHouse<BrokerID, ExchangeID, Symbol, Settlement>
+    where BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    initial_margin_rate: f64,
+    modes: HashMap<BrokerID, PositionMode>,
+    accounts: HashMap<BrokerID, MarginAccount<ExchangeID, Symbol, Settlement>>,
+    last_prices: HashMap<(ExchangeID, TradedPair<Symbol, Settlement>), Tick>,
+    defaulted: HashSet<BrokerID>,
+    default_fund_deficit: f64,
+    margin_calls: Vec<MarginCall<BrokerID>>,
+    trades: Vec<NovatedTrade<BrokerID, ExchangeID, Symbol, Settlement>>,
+    keep_trades: bool,
+}
+
+impl<BrokerID, ExchangeID, Symbol, Settlement>
+ClearingHouse<BrokerID, ExchangeID, Symbol, Settlement>
+    where BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    /// Creates a new instance of the `ClearingHouse`.
+    ///
+    /// # Arguments
+    ///
+    /// * `initial_margin_rate` — Share of the open positions notional
+    ///                           that should be covered by the collateral.
+    pub fn new(initial_margin_rate: f64) -> Self {
+        if !(0.0..=1.0).contains(&initial_margin_rate) {
+            panic!("Initial margin rate should be in [0, 1]. Got: {initial_margin_rate}")
+        }
+        Self {
+            initial_margin_rate,
+            modes: Default::default(),
+            accounts: Default::default(),
+            last_prices: Default::default(),
+            defaulted: Default::default(),
+            default_fund_deficit: 0.0,
+            margin_calls: vec![],
+            trades: vec![],
+            keep_trades: false,
+        }
+    }
+
+    /// Sets whether to keep all the novated trades.
+    ///
+    /// # Arguments
+    ///
+    /// * `keep_trades` — Whether to keep all the novated trades.
+    pub fn with_trade_log(mut self, keep_trades: bool) -> Self {
+        self.keep_trades = keep_trades;
+        self
+    }
+
+    /// Sets the bookkeeping mode of the positions of the clearing member.
+    /// Opposite trades of the member in the [`PositionMode::Hedging`] open the opposite legs
+    /// instead of reducing the position, so that the margin is required for both legs
+    /// until they are closed out with the [`close_out`](Self::close_out).
+    /// By default, the positions are in the [`PositionMode::Netting`].
+    /// Should be called before any trade of the member is novated.
+    ///
+    /// # Arguments
+    ///
+    /// * `broker_id` — Clearing member.
+    /// * `mode` — Position mode.
+    pub fn with_position_mode(mut self, broker_id: BrokerID, mode: PositionMode) -> Self {
+        self.modes.insert(broker_id, mode);
+        self
+    }
+
+    /// Deposits the collateral to the margin account of the broker.
+    /// Outstanding margin call is considered met if the account is sufficiently collateralized.
+    ///
+    /// # Arguments
+    ///
+    /// * `broker_id` — Clearing member.
+    /// * `amount` — Amount of the collateral.
+    pub fn deposit(&mut self, broker_id: BrokerID, amount: f64) {
+        let required_margin = self.required_margin(broker_id);
+        let account = self.accounts.entry(broker_id).or_default();
+        account.collateral += amount;
+        if account.collateral >= required_margin {
+            account.margin_call = None
+        }
+    }
+
+    /// Novates the trade, updating the positions of the counterparties.
+    /// Trades of the defaulted brokers are not novated.
+    ///
+    /// # Arguments
+    ///
+    /// * `trade` — Trade to novate.
+    pub fn novate(&mut self, trade: NovatedTrade<BrokerID, ExchangeID, Symbol, Settlement>) {
+        let instrument = (trade.exchange_id, trade.traded_pair);
+        self.last_prices.insert(instrument, trade.price);
+        let counterparties = [(trade.buyer, Direction::Buy), (trade.seller, Direction::Sell)];
+        for (broker_id, direction) in counterparties {
+            if let Some(broker_id) = broker_id {
+                if self.defaulted.contains(&broker_id) {
+                    continue;
+                }
+                let mode = self.modes.get(&broker_id).copied().unwrap_or_default();
+                let position = self.accounts.entry(broker_id)
+                    .or_default()
+                    .positions
+                    .entry(instrument)
+                    .or_default();
+                let size = match direction {
+                    Direction::Buy => trade.size.0,
+                    Direction::Sell => -trade.size.0
+                };
+                position.size += size;
+                position.legs.add_size(mode, direction, trade.size);
+                position.marked_value += size * trade.price.0;
+            }
+        }
+        if self.keep_trades {
+            self.trades.push(trade)
+        }
+    }
+
+    /// Settles the positions of the instruments traded at the exchange
+    /// at the official settlement prices published by the exchange.
+    /// Instruments without the published price are settled at their last trade prices.
+    ///
+    /// # Arguments
+    ///
+    /// * `datetime` — Settlement datetime.
+    /// * `exchange_id` — Exchange which instruments to settle.
+    /// * `settlement_prices` — Settlement prices published by the exchange.
+    pub fn settle_exchange(
+        &mut self,
+        datetime: DateTime,
+        exchange_id: ExchangeID,
+        settlement_prices: impl IntoIterator<Item=(TradedPair<Symbol, Settlement>, Tick)>)
+    {
+        let mut prices: HashMap<_, _> = self.last_prices.iter()
+            .filter(|((instrument_exchange_id, _), _)| *instrument_exchange_id == exchange_id)
+            .map(|(instrument, price)| (*instrument, *price))
+            .collect();
+        prices.extend(
+            settlement_prices.into_iter().map(
+                |(traded_pair, price)| ((exchange_id, traded_pair), price)
+            )
+        );
+        self.settle(datetime, prices)
+    }
+
+    /// Accrues the variation margin, declares default of the brokers
+    /// that have not met their previous margin calls and issues new margin calls.
+    ///
+    /// # Arguments
+    ///
+    /// * `datetime` — Settlement datetime.
+    /// * `settlement_prices` — Settlement prices of the instruments.
+    pub fn settle(
+        &mut self,
+        datetime: DateTime,
+        settlement_prices: impl IntoIterator<
+            Item=((ExchangeID, TradedPair<Symbol, Settlement>), Tick)
+        >)
+    {
+        for (instrument, price) in settlement_prices {
+            self.last_prices.insert(instrument, price);
+            for account in self.accounts.values_mut() {
+                if let Some(position) = account.positions.get_mut(&instrument) {
+                    let value = position.size * price.0;
+                    account.collateral += (value - position.marked_value) as f64;
+                    position.marked_value = value
+                }
+            }
+        }
+        let mut brokers: Vec<_> = self.accounts.keys()
+            .filter(|broker_id| !self.defaulted.contains(broker_id))
+            .copied()
+            .collect();
+        brokers.sort();
+        for broker_id in brokers {
+            let required_margin = self.required_margin(broker_id);
+            let account = self.accounts.get_mut(&broker_id).unwrap_or_else(
+                || unreachable!("Cannot find margin account of the Broker {broker_id}")
+            );
+            if account.collateral >= required_margin {
+                account.margin_call = None
+            } else if account.margin_call.is_some() {
+                self.declare_default(broker_id)
+            } else {
+                let amount = required_margin - account.collateral;
+                account.margin_call = Some(amount);
+                self.margin_calls.push(MarginCall { datetime, broker_id, amount })
+            }
+        }
+    }
+
+    /// Declares default of the broker. Its positions are closed out at the last prices
+    /// and the uncovered loss is charged to the default fund.
+    ///
+    /// # Arguments
+    ///
+    /// * `broker_id` — Defaulted clearing member.
+    pub fn declare_default(&mut self, broker_id: BrokerID) {
+        if !self.defaulted.insert(broker_id) {
+            return;
+        }
+        if let Some(account) = self.accounts.get_mut(&broker_id) {
+            for (instrument, position) in account.positions.drain() {
+                if let Some(price) = self.last_prices.get(&instrument) {
+                    account.collateral += (position.size * price.0 - position.marked_value) as f64
+                }
+            }
+            if account.collateral < 0.0 {
+                self.default_fund_deficit -= account.collateral;
+                account.collateral = 0.0
+            }
+            account.margin_call = None
+        }
+    }
+
+    /// Closes out the long and short legs of the hedging position of the broker
+    /// against each other, leaving the net position intact. Returns the closed size.
+    /// Netting positions have nothing to close out.
+    ///
+    /// # Arguments
+    ///
+    /// * `broker_id` — Clearing member.
+    /// * `exchange_id` — Exchange the instrument is listed at.
+    /// * `traded_pair` — Traded pair.
+    pub fn close_out(
+        &mut self,
+        broker_id: BrokerID,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>) -> Lots
+    {
+        let position = self.accounts.get_mut(&broker_id)
+            .and_then(|account| account.positions.get_mut(&(exchange_id, traded_pair)));
+        position.map_or(Lots(0), |position| position.legs.close_out())
+    }
+
+    /// Returns the collateral required to cover the open positions of the broker,
+    /// that is the initial margin rate of the notional of their gross sizes.
+    ///
+    /// # Arguments
+    ///
+    /// * `broker_id` — Clearing member.
+    pub fn required_margin(&self, broker_id: BrokerID) -> f64 {
+        if let Some(account) = self.accounts.get(&broker_id) {
+            account.positions.iter()
+                .map(
+                    |(instrument, position)| {
+                        let price = self.last_prices.get(instrument).map_or(0, |price| price.0);
+                        (position.legs.get_gross_size().0 * price).abs() as f64
+                    }
+                )
+                .sum::<f64>() * self.initial_margin_rate
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns the margin account of the broker.
+    ///
+    /// # Arguments
+    ///
+    /// * `broker_id` — Clearing member.
+    pub fn get_account(
+        &self,
+        broker_id: BrokerID) -> Option<&MarginAccount<ExchangeID, Symbol, Settlement>>
+    {
+        self.accounts.get(&broker_id)
+    }
+
+    /// Checks whether the broker has been declared defaulted.
+    ///
+    /// # Arguments
+    ///
+    /// * `broker_id` — Clearing member.
+    pub fn is_defaulted(&self, broker_id: BrokerID) -> bool {
+        self.defaulted.contains(&broker_id)
+    }
+
+    /// Returns the total loss of the defaulted brokers not covered by their collateral.
+    pub fn get_default_fund_deficit(&self) -> f64 {
+        self.default_fund_deficit
+    }
+
+    /// Takes all the margin calls issued so far.
+    pub fn drain_margin_calls(&mut self) -> Vec<MarginCall<BrokerID>> {
+        std::mem::take(&mut self.margin_calls)
+    }
+
+    /// Returns all the novated trades if the trade log is enabled.
+    pub fn get_trades(&self) -> &[NovatedTrade<BrokerID, ExchangeID, Symbol, Settlement>] {
+        &self.trades
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            concrete::traded_pair::settlement::concrete::SpotSettlement,
+            test_utils::{dt, pair, symbol_pair},
+        },
+    };
+
+    type House = ClearingHouse<u8, &'static str, &'static str, SpotSettlement>;
+
+    fn trade(
+        exchange_id: &'static str,
+        traded_pair: TradedPair<&'static str, SpotSettlement>,
+        buyer: u8,
+        seller: u8,
+        price: i64,
+        size: i64) -> NovatedTrade<u8, &'static str, &'static str, SpotSettlement>
+    {
+        NovatedTrade {
+            datetime: dt(0),
+            exchange_id,
+            traded_pair,
+            buyer: Some(buyer),
+            seller: Some(seller),
+            price: Tick(price),
+            size: Lots(size),
+        }
+    }
+
+    /// Returns the clearing house where the Broker 1 is long 10 lots at 100
+    /// against the Broker 2, both having just enough collateral at the 10% initial margin,
+    /// and the price then drops to 90.
+    fn margin_called() -> House {
+        let mut clearing_house = House::new(0.1);
+        clearing_house.novate(trade("MOEX", pair(), 1, 2, 100, 10));
+        clearing_house.deposit(1, 100.0);
+        clearing_house.deposit(2, 100.0);
+        clearing_house.settle(dt(1), [(("MOEX", pair()), Tick(90))]);
+        clearing_house
+    }
+
+    #[test]
+    fn test_hedging_margin() {
+        let traded_pair = pair();
+        let dt = dt(0);
+        let mut clearing_house = ClearingHouse::new(0.5)
+            .with_position_mode(2, PositionMode::Hedging);
+        let trade = |buyer, seller, size| NovatedTrade {
+            datetime: dt,
+            exchange_id: "MOEX",
+            traded_pair,
+            buyer: Some(buyer),
+            seller: Some(seller),
+            price: Tick(10),
+            size: Lots(size),
+        };
+        clearing_house.novate(trade(1, 2, 3));
+        clearing_house.novate(trade(2, 1, 2));
+        // Netting member is margined for the net size, the hedging one for both legs
+        assert_eq!(clearing_house.required_margin(1), 5.0);
+        assert_eq!(clearing_house.required_margin(2), 25.0);
+        assert_eq!(clearing_house.close_out(1, "MOEX", traded_pair), Lots(0));
+        assert_eq!(clearing_house.close_out(2, "MOEX", traded_pair), Lots(2));
+        assert_eq!(clearing_house.required_margin(2), 5.0);
+        let account = clearing_house.get_account(2).unwrap();
+        assert_eq!(account.positions[&("MOEX", traded_pair)].size, -1)
+    }
+
+    #[test]
+    fn test_margin_call() {
+        let mut clearing_house = margin_called();
+        let account = clearing_house.get_account(1).unwrap();
+        assert_eq!((account.collateral, account.margin_call), (0.0, Some(90.0)));
+        let account = clearing_house.get_account(2).unwrap();
+        assert_eq!((account.collateral, account.margin_call), (200.0, None));
+        assert_eq!(
+            clearing_house.drain_margin_calls(),
+            [MarginCall { datetime: dt(1), broker_id: 1, amount: 90.0 }]
+        );
+        assert!(clearing_house.drain_margin_calls().is_empty())
+    }
+
+    #[test]
+    fn test_deposit_meets_margin_call() {
+        let mut clearing_house = margin_called();
+        clearing_house.deposit(1, 90.0);
+        assert_eq!(clearing_house.get_account(1).unwrap().margin_call, None);
+        clearing_house.settle(dt(2), [(("MOEX", pair()), Tick(90))]);
+        assert!(!clearing_house.is_defaulted(1));
+        // Only the met call is issued
+        assert_eq!(clearing_house.drain_margin_calls().len(), 1)
+    }
+
+    #[test]
+    fn test_unmet_margin_call_defaults() {
+        let mut clearing_house = margin_called();
+        // Loss of 100 exceeds the remaining collateral of the Broker 1
+        clearing_house.settle(dt(2), [(("MOEX", pair()), Tick(80))]);
+        assert!(clearing_house.is_defaulted(1));
+        assert!(!clearing_house.is_defaulted(2));
+        assert_eq!(clearing_house.get_default_fund_deficit(), 100.0);
+        let account = clearing_house.get_account(1).unwrap();
+        assert!(account.positions.is_empty());
+        assert_eq!((account.collateral, account.margin_call), (0.0, None));
+        assert_eq!(clearing_house.required_margin(1), 0.0);
+        assert_eq!(clearing_house.get_account(2).unwrap().collateral, 300.0);
+
+        // Trades of the defaulted broker are not novated
+        clearing_house.novate(trade("MOEX", pair(), 1, 2, 80, 1));
+        assert!(clearing_house.get_account(1).unwrap().positions.is_empty());
+        assert_eq!(clearing_house.get_account(2).unwrap().positions[&("MOEX", pair())].size, -11);
+
+        // Default is declared once
+        clearing_house.declare_default(1);
+        assert_eq!(clearing_house.get_default_fund_deficit(), 100.0)
+    }
+
+    #[test]
+    fn test_settle_exchange_at_last_prices() {
+        let (usd, eur) = (pair(), symbol_pair("EUR"));
+        let mut clearing_house = House::new(0.0);
+        clearing_house.novate(trade("MOEX", usd, 1, 2, 100, 1));
+        clearing_house.novate(trade("MOEX", eur, 1, 2, 100, 1));
+        clearing_house.novate(trade("SPB", usd, 1, 2, 100, 1));
+        clearing_house.novate(trade("MOEX", eur, 3, 4, 110, 1));
+        // EUR is settled at its last trade price, SPB is not settled at all
+        clearing_house.settle_exchange(dt(1), "MOEX", [(usd, Tick(105))]);
+        assert_eq!(clearing_house.get_account(1).unwrap().collateral, 15.0);
+        assert_eq!(clearing_house.get_account(3).unwrap().collateral, 0.0);
+        assert_eq!(
+            clearing_house.drain_margin_calls(),
+            [MarginCall { datetime: dt(1), broker_id: 2, amount: 15.0 }]
+        )
+    }
+}
@@ -1,14 +1,17 @@
 use {
     crate::{
         concrete::{
+            middleware::{ExchangeMiddleware, Verdict},
             message_protocol::{
                 broker::request::{BasicBrokerRequest, BasicBrokerToExchange},
                 exchange::reply::{
+                    AuctionInfo,
                     BasicExchangeToBroker,
                     BasicExchangeToBrokerReply,
                     BasicExchangeToReplay,
                     BasicExchangeToReplayReply,
                     CancellationReason,
+                    CannotApplyAdminAction,
                     CannotBroadcastObState,
                     CannotCancelOrder,
                     CannotCloseExchange,
@@ -16,6 +19,7 @@ use {
                     CannotStartTrades,
                     CannotStopTrades,
                     ExchangeEventNotification,
+                    InabilityToApplyAdminAction,
                     InabilityToBroadcastObState,
                     InabilityToCancelReason,
                     InabilityToCloseExchangeReason,
@@ -25,20 +29,56 @@ use {
                     LimitOrderEventInfo,
                     MarketOrderEventInfo,
                     MarketOrderNotFullyExecuted,
+                    ObDiff,
                     ObSnapshot,
                     OrderAccepted,
                     OrderCancelled,
                     OrderExecuted,
                     OrderPartiallyExecuted,
+                    SpreadOrderExecuted,
                     OrderPlacementDiscarded,
+                    OrderPriceAdjusted,
+                    OrderSizeReduced,
+                    OrderState,
+                    OrderStatus,
                     PlacementDiscardingReason,
+                    RecoverySnapshot,
+                    StopOrderTriggered,
+                    ThroughputCounts,
+                    ThroughputStatistics,
+                    TradeBusted,
                 },
+                exchange::wakeup::{BasicExchangeToItself, OrderExpiration, PhaseTransition},
                 replay::request::{BasicReplayRequest, BasicReplayToExchange},
             },
-            order::{LimitOrderCancelRequest, LimitOrderPlacingRequest, MarketOrderPlacingRequest},
-            order_book::{OrderBook, OrderBookEvent, OrderBookEventKind},
+            order::{
+                LimitOrderCancelRequest,
+                LimitOrderPlacingRequest,
+                LimitOrderReductionRequest,
+                MarketOrderPlacingRequest,
+                TimeInForce,
+            },
+            clearing::{ClearingHouse, NovatedTrade, SharedClearingHouse},
+            order_book::{
+                OffGridOrderPolicy,
+                OrderBook,
+                OrderBookEvent,
+                OrderBookEventKind,
+                TickSizeChangeEvent,
+            },
             traded_pair::{settlement::GetSettlementLag, TradedPair},
-            types::{Direction, Lots, OrderID, TickSize},
+            types::{
+                ACCEPTABLE_PRECISION_ERROR,
+                Direction,
+                ExecID,
+                Fee,
+                Lots,
+                ObState,
+                OrderID,
+                Tick,
+                TickSize,
+                TradeID,
+            },
         },
         interface::{
             exchange::{Exchange, ExchangeAction, ExchangeActionKind},
@@ -54,22 +94,189 @@ use {
             Agent,
             Date,
             DateTime,
+            Duration,
             Id,
+            NanoDuration,
             Named,
-            Nothing,
             TimeSync,
         },
-        utils::queue::MessageReceiver,
+        utils::{collections::{HashMap, HashSet}, queue::MessageReceiver},
     },
+    admin::{AdminAction, FeeSchedule, PriceBand},
+    auction::{SessionCalendar, SessionSchedule, TradingPhase},
+    book_validation::BookValidator,
+    closing::{ClosingPriceMethod, TradeTape},
+    fees::{ChargeableFill, FeeModel, Liquidity},
+    protection::{SharedConsolidatedQuotes, TradeThroughPolicy},
     rand::Rng,
+    smallvec::SmallVec,
+    spread::{LegReference, split_spread_fill, SpreadDefinition},
+    stops::{PendingStop, StopBook},
+    throughput::ThroughputMonitor,
+    volatility::{VolatilityBand, VolatilityEstimator, VolatilityMonitor},
     std::{
-        collections::{hash_map::Entry::*, HashMap},
+        cell::RefCell,
+        collections::hash_map::Entry::*,
         iter::{once, once_with},
         marker::PhantomData,
         rc::Rc,
     },
 };
 
+/// Administrative actions changing the trading rules mid-simulation.
+pub mod admin;
+/// Trading phases of the session and the auctions ending the call phases.
+pub mod auction;
+/// Running several strategy variants against one market replay in isolation.
+pub mod bracketed;
+/// Validation of the reconstructed order books against reference snapshots.
+pub mod book_validation;
+/// Official closing prices of the traded pairs.
+pub mod closing;
+/// Trading fees charged for the fills of the broker orders.
+pub mod fees;
+/// Trade-through protection against the best prices of the other exchanges.
+pub mod protection;
+/// Exchange-listed multi-leg instruments.
+pub mod spread;
+/// Order event counts published over the regular intervals.
+pub mod throughput;
+/// Rolling realized volatility of the traded pairs.
+pub mod volatility;
+/// Stop and stop-limit orders waiting for their triggers.
+mod stops;
+#[cfg(test)]
+mod tests;
+
+/// Clearing house to report trades to along with the exchange ID.
+type ClearingLink<'a, BrokerID, ExchangeID, Symbol, Settlement> = (
+    &'a RefCell<ClearingHouse<BrokerID, ExchangeID, Symbol, Settlement>>,
+    ExchangeID,
+);
+
+/// Legs of the spread along with their reference prices. Kept inline for the usual spreads.
+type SpreadLegReferences<Symbol, Settlement> = SmallVec<[LegReference<Symbol, Settlement>; 4]>;
+
+/// [Submitted Order ID -> Internal Order ID] of the orders of the single submitter.
+type SubmittedToInternal<Symbol, Settlement> = HashMap<
+    (TradedPair<Symbol, Settlement>, OrderID),
+    OrderID
+>;
+
+/// Exchange state the order book events of the single placement or auction are interpreted
+/// against. Is built once per placement by the [`BasicExchange::get_match_context`].
+struct MatchContext<'a, ExchangeID, BrokerID, Symbol, Settlement>
+    where ExchangeID: Id,
+          BrokerID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    current_dt: DateTime,
+    traded_pair: TradedPair<Symbol, Settlement>,
+    price_step: TickSize,
+    /// Legs of the traded pair along with their reference prices if it is a spread.
+    spread_legs: &'a [LegReference<Symbol, Settlement>],
+    internal_to_submitted: &'a HashMap<OrderID, (OrderID, Option<BrokerID>)>,
+    shadow_orders: &'a mut HashSet<OrderID>,
+    order_expirations: &'a mut HashMap<OrderID, DateTime>,
+    broker_to_order_id: &'a HashMap<BrokerID, SubmittedToInternal<Symbol, Settlement>>,
+    clearing: Option<ClearingLink<'a, BrokerID, ExchangeID, Symbol, Settlement>>,
+    fee_model: Option<&'a mut (dyn FeeModel<BrokerID, Symbol, Settlement> + 'static)>,
+    trade_tape: &'a mut TradeTape,
+    volatility: Option<&'a mut VolatilityEstimator>,
+    session_fills: &'a mut Vec<SessionFill<BrokerID>>,
+    /// Number of the trades made so far.
+    num_trades: u64,
+    fill_ids: &'a mut FillIdGenerator,
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Fill of the broker order made during the current session that may be busted.
+struct SessionFill<BrokerID: Id> {
+    datetime: DateTime,
+    broker_id: BrokerID,
+    /// Submitted ID of the order.
+    order_id: OrderID,
+    exec_id: ExecID,
+    trade_id: TradeID,
+    direction: Direction,
+    price: Tick,
+    size: Lots,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+/// Generator of the IDs of the fills and of the trades.
+/// IDs are not reset at the close, so they are unique within the run.
+struct FillIdGenerator {
+    next_exec_id: ExecID,
+    /// ID of the trade being matched. Fills of the resting orders at the price level
+    /// precede the fill of the aggressing order, which completes the trade.
+    next_trade_id: TradeID,
+}
+
+impl FillIdGenerator {
+    fn next_exec_id(&mut self) -> ExecID {
+        let exec_id = self.next_exec_id;
+        self.next_exec_id.0 += 1;
+        exec_id
+    }
+
+    fn get_trade_id(&self) -> TradeID {
+        self.next_trade_id
+    }
+
+    fn complete_trade(&mut self) -> TradeID {
+        let trade_id = self.next_trade_id;
+        self.next_trade_id.0 += 1;
+        trade_id
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+/// Policy of the [`BasicExchange`] regarding limit orders
+/// which prices are not multiples of the traded pair price increment.
+pub enum OffTickPricePolicy {
+    /// Discard the order with [`PlacementDiscardingReason::OffTickPrice`].
+    #[default]
+    Reject,
+    /// Round the price down to the nearest admissible one.
+    RoundDown,
+    /// Round the price to the nearest admissible one. Ties are rounded up.
+    RoundNearest,
+}
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+/// Policy of the [`BasicExchange`] regarding the order IDs reused by the same submitter
+/// for the same traded pair. Orders with inadmissible IDs are discarded
+/// with [`PlacementDiscardingReason::OrderWithSuchIDAlreadySubmitted`].
+pub enum DuplicateOrderIdPolicy {
+    /// IDs should be unique within the session and may be reused in the next ones.
+    #[default]
+    PerSession,
+    /// IDs should be unique across all the sessions.
+    RejectForever,
+    /// ID may be reused once the previous order with it is no longer resting,
+    /// i.e. it has been executed or cancelled.
+    ReuseAfterClosed,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+/// Order size restrictions of the traded pair.
+/// Are not applied to the orders submitted by the replay.
+pub struct OrderSizeLimits {
+    /// Minimum order size.
+    pub min_size: Option<Lots>,
+    /// Maximum order size.
+    pub max_size: Option<Lots>,
+    /// Order size should be a multiple of this value.
+    pub size_increment: Option<Lots>,
+    /// Minimum limit order notional, i.e. price multiplied by size in lots.
+    /// Is converted to ticks with the current price step of the traded pair,
+    /// so that it does not depend on the price step the trades are started with.
+    /// Is not checked for market orders since their execution price is not known in advance.
+    pub min_notional: Option<f64>,
+}
+
 /// [`Exchange`] that supports basic operations.
 pub struct BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
     where ExchangeID: Id,
@@ -81,20 +288,85 @@ pub struct BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
     name: ExchangeID,
 
     /// [Broker -> [Submitted Order ID -> Internal Order ID]]
-    broker_to_order_id: HashMap<
-        BrokerID,
-        HashMap<(TradedPair<Symbol, Settlement>, OrderID), OrderID>
-    >,
+    broker_to_order_id: HashMap<BrokerID, SubmittedToInternal<Symbol, Settlement>>,
     /// [Submitted Order ID -> Internal Order ID]
-    replay_order_ids: HashMap<(TradedPair<Symbol, Settlement>, OrderID), OrderID>,
+    replay_order_ids: SubmittedToInternal<Symbol, Settlement>,
 
     /// [Internal Order ID ->
     /// (Submitted Order ID, Whether it came from broker ( Broker ID ) or replay (None) )]
     internal_to_submitted: HashMap<OrderID, (OrderID, Option<BrokerID>)>,
+    /// Expiration datetimes of the good-till-date orders, by internal ID.
+    order_expirations: HashMap<OrderID, DateTime>,
+    fill_ids: FillIdGenerator,
 
     next_order_id: OrderID,
-    order_books: HashMap<TradedPair<Symbol, Settlement>, (OrderBook<false>, TickSize)>,
+    order_books: HashMap<TradedPair<Symbol, Settlement>, (OrderBook, TickSize)>,
     is_open: bool,
+    /// Set while the exchange is down.
+    /// Tells whether to cancel the resting broker orders when the downtime ends.
+    downtime: Option<bool>,
+
+    /// Minimum price increments (in ticks) for traded pairs. If absent, any price is admissible.
+    price_increments: HashMap<TradedPair<Symbol, Settlement>, Tick>,
+    off_tick_price_policy: OffTickPricePolicy,
+    /// Treatment of the resting limit orders that become off-grid when the increment changes.
+    off_grid_order_policy: OffGridOrderPolicy,
+    /// Price bands for traded pairs. If absent, any price is admissible.
+    price_bands: HashMap<TradedPair<Symbol, Settlement>, PriceBand>,
+    /// Fee schedules published for traded pairs.
+    fee_schedules: HashMap<TradedPair<Symbol, Settlement>, FeeSchedule>,
+    /// Fees charged for the fills of the broker orders. If absent, no fees are charged.
+    fee_model: Option<Box<dyn FeeModel<BrokerID, Symbol, Settlement>>>,
+    /// Order size restrictions for traded pairs.
+    size_limits: HashMap<TradedPair<Symbol, Settlement>, OrderSizeLimits>,
+    /// Whether to use submitted IDs of the replay limit orders as their internal IDs.
+    historical_order_ids: bool,
+    duplicate_order_id_policy: DuplicateOrderIdPolicy,
+    /// IDs submitted during the previous sessions along with their submitters
+    /// and traded pairs. Kept only under the [`DuplicateOrderIdPolicy::RejectForever`].
+    retired_order_ids: HashSet<(Option<BrokerID>, TradedPair<Symbol, Settlement>, OrderID)>,
+    /// Validators of the reconstructed order books.
+    book_validators: HashMap<TradedPair<Symbol, Settlement>, BookValidator>,
+    /// Clearing house novating the trades.
+    clearing_house: Option<SharedClearingHouse<BrokerID, ExchangeID, Symbol, Settlement>>,
+    /// Multi-leg instruments listed at the exchange.
+    spreads: HashMap<TradedPair<Symbol, Settlement>, SpreadDefinition<Symbol, Settlement>>,
+    /// Internal IDs of the resting shadow limit orders.
+    shadow_orders: HashSet<OrderID>,
+    closing_price_method: ClosingPriceMethod,
+    /// Number of the recent trades sent along with the recovery snapshots.
+    num_recent_trades: usize,
+    /// Trades of the current session affecting the closing prices or recent enough
+    /// to be sent along with the recovery snapshots.
+    trade_tapes: HashMap<TradedPair<Symbol, Settlement>, TradeTape>,
+    /// Fills of the broker orders made during the current session.
+    session_fills: HashMap<TradedPair<Symbol, Settlement>, Vec<SessionFill<BrokerID>>>,
+    /// Events of the auction being run along with the IDs of the crossed bids.
+    /// Is empty between the auctions and keeps its buffer for the next ones.
+    auction_events: Vec<(OrderID, OrderBookEvent)>,
+    /// Closing prices of the last sessions the traded pairs were traded in.
+    closing_prices: HashMap<TradedPair<Symbol, Settlement>, Tick>,
+    /// User-defined hooks, in the order they are called.
+    middleware: Vec<Box<dyn ExchangeMiddleware<ExchangeID, BrokerID, Symbol, Settlement>>>,
+    /// Order event counts of the current statistics interval.
+    throughput: Option<ThroughputMonitor<Symbol, Settlement>>,
+    /// Consolidated best prices to publish to and to protect.
+    trade_through_protection: Option<(
+        SharedConsolidatedQuotes<ExchangeID, Symbol, Settlement>,
+        TradeThroughPolicy
+    )>,
+    /// Whether to publish the [`ObDiff`](ExchangeEventNotification::ObDiff)s.
+    ob_diffs: bool,
+    /// Whether the dummy orders match the resting dummy orders.
+    dummy_matching: bool,
+    /// Realized volatility of the traded pairs, estimated from the trades.
+    volatility: Option<VolatilityMonitor<Symbol, Settlement>>,
+    /// Stop and stop-limit broker orders that have not been triggered yet.
+    stop_orders: StopBook<BrokerID, Symbol, Settlement>,
+    /// Current phase of the trading session.
+    trading_phase: TradingPhase,
+    /// Scheduled transitions between the trading phases.
+    session_calendar: Option<SessionCalendar>,
 }
 
 impl<ExchangeID, BrokerID, Symbol, Settlement>
@@ -133,7 +405,7 @@ Agent for BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
     type Action = ExchangeAction<
         BasicExchangeToReplay<Symbol, Settlement>,
         BasicExchangeToBroker<BrokerID, Symbol, Settlement>,
-        Nothing
+        BasicExchangeToItself<Symbol, Settlement>
     >;
 }
 
@@ -152,94 +424,280 @@ for BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
     type B2E = BasicBrokerToExchange<ExchangeID, Symbol, Settlement>;
     type E2R = BasicExchangeToReplay<Symbol, Settlement>;
     type E2B = BasicExchangeToBroker<BrokerID, Symbol, Settlement>;
-    type E2E = Nothing;
+    type E2E = BasicExchangeToItself<Symbol, Settlement>;
 
     fn wakeup<KerMsg: Ord, RNG: Rng>(
         &mut self,
-        _: MessageReceiver<KerMsg>,
-        _: impl FnMut(Self::Action, &mut RNG) -> KerMsg,
-        _: Self::E2E,
-        _: &mut RNG,
+        mut message_receiver: MessageReceiver<KerMsg>,
+        mut process_action: impl FnMut(Self::Action, &mut RNG) -> KerMsg,
+        scheduled_action: Self::E2E,
+        rng: &mut RNG,
     ) {
-        unreachable!("{} :: Exchange wakeups are not planned", self.current_dt)
+        let OrderExpiration { traded_pair, order_id, expiration } = match scheduled_action {
+            BasicExchangeToItself::OrderExpiration(expiration) => expiration,
+            BasicExchangeToItself::PhaseTransition(_) => {
+                let process_action = |action| process_action(action, rng);
+                return self.advance_session_schedule(&mut message_receiver, process_action);
+            }
+        };
+        // The order may have been cancelled at the close with its ID reused since
+        if self.order_expirations.get(&order_id) != Some(&expiration) {
+            return;
+        }
+        self.order_expirations.remove(&order_id);
+        let (submitted_order_id, broker_id) = self.internal_to_submitted[&order_id];
+        let process_action = |action| process_action(action, rng);
+        if let Some(broker_id) = broker_id {
+            self.cancel_resting_order::<_, _, _, false>(
+                message_receiver,
+                process_action,
+                traded_pair,
+                (order_id, submitted_order_id),
+                || broker_id,
+                CancellationReason::Expired,
+            )
+        } else {
+            self.cancel_resting_order::<_, _, _, true>(
+                message_receiver,
+                process_action,
+                traded_pair,
+                (order_id, submitted_order_id),
+                || unreachable!("Replay does not have BrokerID"),
+                CancellationReason::Expired,
+            )
+        }
     }
 
     fn process_broker_request<KerMsg: Ord, RNG: Rng>(
         &mut self,
-        message_receiver: MessageReceiver<KerMsg>,
+        mut message_receiver: MessageReceiver<KerMsg>,
         mut process_action: impl FnMut(Self::Action, &mut RNG) -> KerMsg,
-        request: Self::B2E,
+        mut request: Self::B2E,
         broker_id: BrokerID,
         rng: &mut RNG,
     ) {
+        self.validate_books::<false>();
         let get_broker_id = || broker_id;
-        let process_action = |action| process_action(action, rng);
+        let mut process_action = |action| process_action(action, rng);
+        self.publish_throughput_statistics(&mut message_receiver, &mut process_action);
+        self.publish_volatility_estimates(&mut message_receiver, &mut process_action);
+        self.advance_session_schedule(&mut message_receiver, &mut process_action);
+        match self.apply_broker_request_middleware(broker_id, &mut request) {
+            Verdict::Pass => {}
+            Verdict::Suppress => return,
+            Verdict::Reject(reply) => {
+                let reply = Self::create_broker_reply(self.current_dt, broker_id, reply);
+                message_receiver.push(process_action(reply));
+                return;
+            }
+        }
+        if self.downtime.is_some() {
+            if let Some(reply) = self.reject_during_downtime(broker_id, request.content) {
+                message_receiver.push(process_action(reply))
+            }
+            return;
+        }
         match request.content
         {
             BasicBrokerRequest::CancelLimitOrder(request) => {
-                self.try_cancel_limit_order::<_, _, _, false>(
-                    message_receiver, process_action, request, get_broker_id,
-                )
+                if let Some(reply) = self.try_cancel_stop_order(
+                    broker_id, request.traded_pair, request.order_id, None,
+                ) {
+                    message_receiver.push(process_action(reply))
+                } else {
+                    self.try_cancel_limit_order::<_, _, _, false>(
+                        message_receiver.reborrow(), &mut process_action, request, get_broker_id,
+                    )
+                }
             }
             BasicBrokerRequest::PlaceLimitOrder(order) => {
                 self.try_place_limit_order::<_, _, _, false>(
-                    message_receiver, process_action, order, get_broker_id,
+                    message_receiver.reborrow(), &mut process_action, order, None, get_broker_id,
+                )
+            }
+            BasicBrokerRequest::PlaceIcebergOrder(order) => {
+                self.try_place_limit_order::<_, _, _, false>(
+                    message_receiver.reborrow(),
+                    &mut process_action,
+                    order.into(),
+                    Some(order.display_size),
+                    get_broker_id,
                 )
             }
             BasicBrokerRequest::PlaceMarketOrder(order) => {
                 self.try_place_market_order::<_, _, _, false>(
-                    message_receiver, process_action, order, get_broker_id,
+                    message_receiver.reborrow(), &mut process_action, order, get_broker_id,
+                )
+            }
+            BasicBrokerRequest::PlaceStopOrder(order) => {
+                self.try_place_stop_order(
+                    &mut message_receiver,
+                    &mut process_action,
+                    PendingStop { broker_id, order, limit_price: None },
+                )
+            }
+            BasicBrokerRequest::PlaceStopLimitOrder(order) => {
+                self.try_place_stop_order(
+                    &mut message_receiver,
+                    &mut process_action,
+                    PendingStop::new_stop_limit(broker_id, order),
                 )
             }
+            BasicBrokerRequest::ReduceOrderSize(request) => {
+                if let Some(reply) = self.try_cancel_stop_order(
+                    broker_id, request.traded_pair, request.order_id, Some(request.size),
+                ) {
+                    message_receiver.push(process_action(reply))
+                } else {
+                    self.try_reduce_order_size::<_, _, _, false>(
+                        message_receiver.reborrow(), &mut process_action, request, get_broker_id,
+                    )
+                }
+            }
+            BasicBrokerRequest::QueryOrderStatus(request) => {
+                let order_status = OrderStatus {
+                    traded_pair: request.traded_pair,
+                    order_id: request.order_id,
+                    state: self.get_order_state(
+                        Some(broker_id),
+                        request.traded_pair,
+                        request.order_id,
+                    ),
+                };
+                let reply = Self::create_broker_reply(
+                    self.current_dt,
+                    broker_id,
+                    BasicExchangeToBrokerReply::OrderStatus(order_status),
+                );
+                message_receiver.push(process_action(reply))
+            }
+            BasicBrokerRequest::RequestRecoverySnapshot(traded_pair) => {
+                let reply = Self::create_broker_reply(
+                    self.current_dt,
+                    broker_id,
+                    BasicExchangeToBrokerReply::RecoverySnapshot(
+                        self.get_recovery_snapshot(traded_pair)
+                    ),
+                );
+                message_receiver.push(process_action(reply))
+            }
+            BasicBrokerRequest::Heartbeat(heartbeat) => {
+                let reply = Self::create_broker_reply(
+                    self.current_dt,
+                    broker_id,
+                    BasicExchangeToBrokerReply::Heartbeat(heartbeat),
+                );
+                message_receiver.push(process_action(reply))
+            }
+            BasicBrokerRequest::ReportOffBookTrade(trade) => {
+                // Reports in the pairs not traded at the exchange are ignored
+                if self.order_books.contains_key(&trade.traded_pair) {
+                    self.notify_everyone(
+                        &mut message_receiver,
+                        &mut process_action,
+                        ExchangeEventNotification::OffBookTradeReported(trade),
+                    )
+                }
+            }
         }
+        self.trigger_stop_orders(&mut message_receiver, &mut process_action);
+        self.publish_ob_diffs(&mut message_receiver, process_action);
+        self.publish_best_prices()
     }
 
     fn process_replay_request<KerMsg: Ord, RNG: Rng>(
         &mut self,
-        message_receiver: MessageReceiver<KerMsg>,
+        mut message_receiver: MessageReceiver<KerMsg>,
         mut process_action: impl FnMut(Self::Action, &mut RNG) -> KerMsg,
-        request: Self::R2E,
+        mut request: Self::R2E,
         rng: &mut RNG,
     ) {
+        self.validate_books::<false>();
+        self.publish_throughput_statistics(
+            &mut message_receiver,
+            |action| process_action(action, rng),
+        );
+        self.publish_volatility_estimates(
+            &mut message_receiver,
+            |action| process_action(action, rng),
+        );
+        self.advance_session_schedule(
+            &mut message_receiver,
+            |action| process_action(action, rng),
+        );
+        let current_dt = self.current_dt;
+        let passed = self.middleware.iter_mut().all(
+            |middleware| middleware.on_replay_request(current_dt, &mut request)
+        );
+        if !passed {
+            return;
+        }
         let get_broker_id_plug = || unreachable!("Replay does not have BrokerID");
-        let process_action = |action| process_action(action, rng);
+        let mut process_action = |action| process_action(action, rng);
         match request.content
         {
             BasicReplayRequest::ExchangeOpen => {
-                self.try_open(message_receiver, process_action)
+                self.try_open(message_receiver.reborrow(), &mut process_action)
             }
             BasicReplayRequest::StartTrades { traded_pair, price_step } => {
                 self.try_start_trades(
-                    message_receiver, process_action, traded_pair, price_step,
+                    message_receiver.reborrow(), &mut process_action, traded_pair, price_step,
                 )
             }
             BasicReplayRequest::PlaceMarketOrder(order) => {
                 self.try_place_market_order::<_, _, _, true>(
-                    message_receiver, process_action, order, get_broker_id_plug,
+                    message_receiver.reborrow(), &mut process_action, order, get_broker_id_plug,
                 )
             }
             BasicReplayRequest::PlaceLimitOrder(order) => {
                 self.try_place_limit_order::<_, _, _, true>(
-                    message_receiver, process_action, order, get_broker_id_plug,
+                    message_receiver.reborrow(),
+                    &mut process_action,
+                    order,
+                    None,
+                    get_broker_id_plug,
                 )
             }
+            BasicReplayRequest::SeedLimitOrder(order) => {
+                self.try_seed_limit_order(message_receiver.reborrow(), &mut process_action, order)
+            }
             BasicReplayRequest::CancelLimitOrder(request) => {
                 self.try_cancel_limit_order::<_, _, _, true>(
-                    message_receiver, process_action, request, get_broker_id_plug,
+                    message_receiver.reborrow(), &mut process_action, request, get_broker_id_plug,
+                )
+            }
+            BasicReplayRequest::ReduceOrderSize(request) => {
+                self.try_reduce_order_size::<_, _, _, true>(
+                    message_receiver.reborrow(), &mut process_action, request, get_broker_id_plug,
                 )
             }
             BasicReplayRequest::StopTrades(traded_pair) => {
-                self.try_stop_trades(message_receiver, process_action, traded_pair)
+                self.try_stop_trades(message_receiver.reborrow(), &mut process_action, traded_pair)
+            }
+            BasicReplayRequest::AdminAction(action) => {
+                self.try_apply_admin_action(message_receiver.reborrow(), &mut process_action, action)
+            }
+            BasicReplayRequest::StartDowntime { cancel_resting_orders } => {
+                self.start_downtime(message_receiver.reborrow(), &mut process_action, cancel_resting_orders)
+            }
+            BasicReplayRequest::EndDowntime => {
+                self.end_downtime(message_receiver.reborrow(), &mut process_action)
+            }
+            BasicReplayRequest::SetTradingPhase(phase) => {
+                self.set_trading_phase(&mut message_receiver, &mut process_action, phase)
             }
             BasicReplayRequest::ExchangeClosed => {
-                self.try_close(message_receiver, process_action)
+                self.try_close(message_receiver.reborrow(), &mut process_action)
             }
             BasicReplayRequest::BroadcastObStateToBrokers { traded_pair, max_levels } => {
                 self.try_broadcast_ob_state(
-                    message_receiver, process_action, traded_pair, max_levels,
+                    message_receiver.reborrow(), &mut process_action, traded_pair, max_levels,
                 )
             }
         }
+        self.trigger_stop_orders(&mut message_receiver, &mut process_action);
+        self.publish_ob_diffs(&mut message_receiver, process_action);
+        self.publish_best_prices()
     }
 
     fn connect_broker(&mut self, broker_id: BrokerID) {
@@ -267,299 +725,2092 @@ BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
             broker_to_order_id: Default::default(),
             replay_order_ids: Default::default(),
             internal_to_submitted: Default::default(),
+            order_expirations: Default::default(),
+            fill_ids: Default::default(),
             next_order_id: OrderID(0),
             order_books: Default::default(),
             is_open: false,
+            downtime: None,
+            price_increments: Default::default(),
+            off_tick_price_policy: Default::default(),
+            off_grid_order_policy: Default::default(),
+            price_bands: Default::default(),
+            fee_schedules: Default::default(),
+            fee_model: None,
+            size_limits: Default::default(),
+            historical_order_ids: false,
+            duplicate_order_id_policy: Default::default(),
+            retired_order_ids: Default::default(),
+            book_validators: Default::default(),
+            clearing_house: None,
+            spreads: Default::default(),
+            shadow_orders: Default::default(),
+            closing_price_method: Default::default(),
+            num_recent_trades: 0,
+            trade_tapes: Default::default(),
+            session_fills: Default::default(),
+            auction_events: Vec::new(),
+            closing_prices: Default::default(),
+            middleware: vec![],
+            throughput: None,
+            trade_through_protection: None,
+            ob_diffs: false,
+            dummy_matching: false,
+            volatility: None,
+            stop_orders: Default::default(),
+            trading_phase: Default::default(),
+            session_calendar: None,
         }
     }
 
-    fn try_broadcast_ob_state<KerMsg: Ord>(
-        &self,
-        mut message_receiver: MessageReceiver<KerMsg>,
-        mut process_action: impl FnMut(<Self as Agent>::Action) -> KerMsg,
+    /// Sets the policy regarding limit orders
+    /// which prices are not multiples of the traded pair price increment.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` — Policy to use.
+    pub fn with_off_tick_price_policy(mut self, policy: OffTickPricePolicy) -> Self {
+        self.off_tick_price_policy = policy;
+        self
+    }
+
+    /// Sets the treatment of the resting limit orders which prices become off-grid
+    /// when the minimum price increment is changed by the [`AdminAction::SetPriceIncrement`].
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` — Policy to use.
+    pub fn with_off_grid_order_policy(mut self, policy: OffGridOrderPolicy) -> Self {
+        self.off_grid_order_policy = policy;
+        self
+    }
+
+    /// Sets the minimum price increment for the traded pair.
+    ///
+    /// # Arguments
+    ///
+    /// * `traded_pair` — Traded pair.
+    /// * `increment` — Minimum price increment in ticks. Should be positive.
+    pub fn with_price_increment(
+        mut self,
         traded_pair: TradedPair<Symbol, Settlement>,
-        max_levels: usize,
-    ) {
-        if !self.is_open {
-            let reply = Self::create_replay_reply(
-                BasicExchangeToReplayReply::CannotBroadcastObState(
-                    CannotBroadcastObState {
-                        reason: InabilityToBroadcastObState::ExchangeClosed
-                    }
-                )
-            );
-            message_receiver.push(process_action(reply))
-        } else if let Some((order_book, _price_step)) = self.order_books.get(&traded_pair) {
-            let ob_snapshot = Rc::new(
-                ObSnapshot { traded_pair, state: order_book.get_ob_state(max_levels) }
-            );
-            let action_iterator = once_with(
-                || Self::create_replay_reply(
-                    BasicExchangeToReplayReply::ExchangeEventNotification(
-                        ExchangeEventNotification::ObSnapshot(Rc::clone(&ob_snapshot))
-                    )
-                )
-            ).chain(
-                self.broker_to_order_id.keys().map(
-                    |broker_id| Self::create_broker_reply(
-                        self.current_dt,
-                        *broker_id,
-                        BasicExchangeToBrokerReply::ExchangeEventNotification(
-                            ExchangeEventNotification::ObSnapshot(Rc::clone(&ob_snapshot))
-                        ),
-                    )
-                )
-            );
-            message_receiver.extend(action_iterator.map(process_action))
-        } else {
-            let reply = Self::create_replay_reply(
-                BasicExchangeToReplayReply::CannotBroadcastObState(
-                    CannotBroadcastObState {
-                        reason: InabilityToBroadcastObState::NoSuchTradedPair
-                    }
-                )
-            );
-            message_receiver.push(process_action(reply))
+        increment: Tick) -> Self
+    {
+        if increment <= Tick(0) {
+            panic!("Price increment should be positive. Got: {increment}")
         }
+        self.price_increments.insert(traded_pair, increment);
+        self
     }
 
-    fn try_cancel_limit_order<
-        KerMsg: Ord,
-        ProcessAction: FnMut(<Self as Agent>::Action) -> KerMsg,
-        GetBrokerID: Fn() -> BrokerID,
-        const REPLAY: bool
-    >(
-        &mut self,
-        mut message_receiver: MessageReceiver<KerMsg>,
-        mut process_action: ProcessAction,
-        request: LimitOrderCancelRequest<Symbol, Settlement>,
-        get_broker_id: GetBrokerID,
-    ) {
-        if !self.is_open {
-            let cannot_cancel_order = CannotCancelOrder {
-                traded_pair: request.traded_pair,
-                order_id: request.order_id,
-                reason: InabilityToCancelReason::ExchangeClosed,
-            };
-            let reply = if REPLAY {
-                Self::create_replay_reply(
-                    BasicExchangeToReplayReply::CannotCancelOrder(cannot_cancel_order)
-                )
-            } else {
-                Self::create_broker_reply(
-                    self.current_dt,
-                    get_broker_id(),
-                    BasicExchangeToBrokerReply::CannotCancelOrder(cannot_cancel_order),
-                )
-            };
-            message_receiver.push(process_action(reply));
-            return;
-        };
-        let order_id_map = if REPLAY {
-            &self.replay_order_ids
-        } else if let Some(order_id_map) = self.broker_to_order_id.get(&get_broker_id()) {
-            order_id_map
-        } else {
-            let cannot_cancel_order = CannotCancelOrder {
-                traded_pair: request.traded_pair,
-                order_id: request.order_id,
-                reason: InabilityToCancelReason::BrokerNotConnectedToExchange,
-            };
-            let reply = Self::create_broker_reply(
-                self.current_dt,
-                get_broker_id(),
-                BasicExchangeToBrokerReply::CannotCancelOrder(cannot_cancel_order),
-            );
-            message_receiver.push(process_action(reply));
-            return;
-        };
-        let cannot_cancel_order = if let Some(internal_order_id) = order_id_map.get(
-            &(request.traded_pair, request.order_id)
-        ) {
-            if let Some((order_book, _price_step)) = self.order_books.get_mut(&request.traded_pair)
-            {
-                if let Ok((limit_order, direction, price)) = order_book.cancel_limit_order(
-                    *internal_order_id
-                ) {
-                    let order_cancelled = OrderCancelled {
-                        traded_pair: request.traded_pair,
-                        order_id: request.order_id,
-                        reason: CancellationReason::BrokerRequested,
-                    };
-                    let broker_notification_iterator = self.broker_to_order_id.keys().map(
-                        |broker_id| Self::create_broker_reply(
-                            self.current_dt,
-                            *broker_id,
-                            BasicExchangeToBrokerReply::ExchangeEventNotification(
-                                ExchangeEventNotification::OrderCancelled(LimitOrderEventInfo {
-                                    traded_pair: request.traded_pair,
-                                    order_id: limit_order.id,
-                                    direction,
-                                    price,
-                                    size: limit_order.size,
-                                })
-                            ),
-                        )
-                    );
-                    if REPLAY {
-                        let replay_reply = || Self::create_replay_reply(
-                            BasicExchangeToReplayReply::OrderCancelled(order_cancelled)
-                        );
-                        let action_iterator = once_with(replay_reply)
-                            .chain(broker_notification_iterator);
-                        message_receiver.extend(action_iterator.map(process_action))
-                    } else {
-                        let replay_notification = || Self::create_replay_reply(
-                            BasicExchangeToReplayReply::ExchangeEventNotification(
-                                ExchangeEventNotification::OrderCancelled(
-                                    LimitOrderEventInfo {
-                                        traded_pair: request.traded_pair,
-                                        order_id: limit_order.id,
-                                        direction,
-                                        price,
-                                        size: limit_order.size,
-                                    }
-                                )
-                            )
-                        );
-                        let broker_reply = || Self::create_broker_reply(
-                            self.current_dt,
-                            get_broker_id(),
-                            BasicExchangeToBrokerReply::OrderCancelled(order_cancelled),
-                        );
-                        let action_iterator = once_with(replay_notification)
-                            .chain(once_with(broker_reply))
-                            .chain(broker_notification_iterator);
-                        message_receiver.extend(action_iterator.map(process_action))
-                    };
-                    return;
-                } else {
-                    InabilityToCancelReason::OrderAlreadyExecuted
-                }
-            } else {
-                InabilityToCancelReason::BrokerNotConnectedToExchange
-            }
+    /// Sets the range of admissible limit order prices for the traded pair.
+    /// Can be changed during the simulation by the [`AdminAction::SetPriceBand`].
+    ///
+    /// # Arguments
+    ///
+    /// * `traded_pair` — Traded pair.
+    /// * `band` — Price band.
+    pub fn with_price_band(
+        mut self,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        band: PriceBand) -> Self
+    {
+        if matches!(band, PriceBand { lower: Some(lower), upper: Some(upper) } if lower > upper) {
+            panic!("Price band lower bound should not exceed the upper one. Got: {band:?}")
+        }
+        self.price_bands.insert(traded_pair, band);
+        self
+    }
+
+    /// Publishes the fee schedule of the traded pair.
+    /// Can be changed during the simulation by the [`AdminAction::SetFeeSchedule`].
+    ///
+    /// # Arguments
+    ///
+    /// * `traded_pair` — Traded pair.
+    /// * `fees` — Fee schedule.
+    pub fn with_fee_schedule(
+        mut self,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        fees: FeeSchedule) -> Self
+    {
+        self.fee_schedules.insert(traded_pair, fees);
+        self
+    }
+
+    /// Returns the current fee schedule of the traded pair.
+    ///
+    /// # Arguments
+    ///
+    /// * `traded_pair` — Traded pair.
+    pub fn get_fee_schedule(&self, traded_pair: TradedPair<Symbol, Settlement>) -> FeeSchedule {
+        self.fee_schedules.get(&traded_pair).copied().unwrap_or_default()
+    }
+
+    /// Makes the `BasicExchange` charge the fees for the fills of the broker orders
+    /// and report them in the `OrderExecuted` and `OrderPartiallyExecuted` replies.
+    /// Fills of the replay and the shadow orders are not charged.
+    ///
+    /// # Arguments
+    ///
+    /// * `fee_model` — Fee model, e.g. the [`BpsFees`](fees::BpsFees).
+    pub fn with_fee_model(
+        mut self,
+        fee_model: impl FeeModel<BrokerID, Symbol, Settlement> + 'static) -> Self
+    {
+        self.fee_model = Some(Box::new(fee_model));
+        self
+    }
+
+    /// Sets the order size restrictions for the traded pair.
+    ///
+    /// # Arguments
+    ///
+    /// * `traded_pair` — Traded pair.
+    /// * `limits` — Order size restrictions.
+    pub fn with_size_limits(
+        mut self,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        limits: OrderSizeLimits) -> Self
+    {
+        if let Some(size_increment) = limits.size_increment {
+            if size_increment <= Lots(0) {
+                panic!("Size increment should be positive. Got: {size_increment}")
+            }
+        }
+        self.size_limits.insert(traded_pair, limits);
+        self
+    }
+
+    /// Sets whether to keep the IDs of the limit orders submitted by the replay
+    /// as their IDs in the order book, so that they are visible in the
+    /// [`OrderPlaced`](ExchangeEventNotification::OrderPlaced) and
+    /// [`OrderCancelled`](ExchangeEventNotification::OrderCancelled) notifications.
+    /// In this case the IDs of other orders are generated
+    /// starting from the [`OrderID::FIRST_SYNTHETIC`].
+    ///
+    /// # Arguments
+    ///
+    /// * `historical_order_ids` — Whether to keep the replay limit order IDs.
+    pub fn with_historical_order_ids(mut self, historical_order_ids: bool) -> Self {
+        self.historical_order_ids = historical_order_ids;
+        self.next_order_id = self.first_generated_order_id();
+        self
+    }
+
+    /// Sets the policy regarding the reused order IDs.
+    /// Defaults to the [`DuplicateOrderIdPolicy::PerSession`].
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` — Policy to use.
+    pub fn with_duplicate_order_id_policy(mut self, policy: DuplicateOrderIdPolicy) -> Self {
+        self.duplicate_order_id_policy = policy;
+        self
+    }
+
+    /// Compares the order book of the traded pair with the reference snapshots
+    /// as the simulation proceeds.
+    ///
+    /// # Arguments
+    ///
+    /// * `traded_pair` — Traded pair.
+    /// * `validator` — Validator holding the reference snapshots.
+    pub fn with_book_validator(
+        mut self,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        validator: BookValidator) -> Self
+    {
+        self.book_validators.insert(traded_pair, validator);
+        self
+    }
+
+    /// Returns the validator of the traded pair order book.
+    ///
+    /// # Arguments
+    ///
+    /// * `traded_pair` — Traded pair.
+    pub fn get_book_validator(
+        &self,
+        traded_pair: TradedPair<Symbol, Settlement>) -> Option<&BookValidator>
+    {
+        self.book_validators.get(&traded_pair)
+    }
+
+    /// Reports all trades to the clearing house
+    /// and makes it settle the positions whenever the exchange closes.
+    ///
+    /// # Arguments
+    ///
+    /// * `clearing_house` — Clearing house. Can be shared with other exchanges.
+    pub fn with_clearing_house(
+        mut self,
+        clearing_house: SharedClearingHouse<BrokerID, ExchangeID, Symbol, Settlement>) -> Self
+    {
+        self.clearing_house = Some(clearing_house);
+        self
+    }
+
+    /// Makes the exchange publish its best prices to the consolidated quotes
+    /// and protect them from being traded through by the broker orders according to the policy.
+    ///
+    /// # Arguments
+    ///
+    /// * `quotes` — Consolidated quotes. Should be shared with the other exchanges.
+    /// * `policy` — Treatment of the orders that would trade through the other exchanges.
+    pub fn with_trade_through_protection(
+        mut self,
+        quotes: SharedConsolidatedQuotes<ExchangeID, Symbol, Settlement>,
+        policy: TradeThroughPolicy) -> Self
+    {
+        self.trade_through_protection = Some((quotes, policy));
+        self
+    }
+
+    /// Sets the method of determining the closing prices published whenever the exchange closes.
+    /// The closing prices are also used by the clearing house as the settlement prices.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` — Method to use.
+    pub fn with_closing_price_method(mut self, method: ClosingPriceMethod) -> Self {
+        self.closing_price_method = method;
+        self
+    }
+
+    /// Makes the exchange publish the
+    /// [`ThroughputStatistics`](ExchangeEventNotification::ThroughputStatistics)
+    /// of the traded pairs with events over the consecutive intervals.
+    /// The interval is published at the first message the exchange receives after it ends.
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` — Length of the intervals.
+    pub fn with_throughput_statistics(mut self, interval: Duration) -> Self {
+        self.throughput = Some(ThroughputMonitor::new(interval));
+        self
+    }
+
+    /// Makes the exchange publish the changes of the resting orders
+    /// as the [`ObDiff`](ExchangeEventNotification::ObDiff)s to the brokers,
+    /// once per every incoming message that has changed any order book.
+    /// Changes pending when the trades are stopped are not published.
+    pub fn with_ob_diffs(mut self) -> Self {
+        self.ob_diffs = true;
+        self
+    }
+
+    /// Makes the dummy orders of the brokers match the resting dummy orders
+    /// as if the other dummy liquidity were real, e.g. for the counterfactual studies
+    /// of the multiple agents. Fills of the dummy orders by each other, both resting and incoming,
+    /// are reported as the [`GhostOrderExecuted`](BasicExchangeToBrokerReply::GhostOrderExecuted)
+    /// and [`GhostOrderPartiallyExecuted`](
+    /// BasicExchangeToBrokerReply::GhostOrderPartiallyExecuted) ones,
+    /// never mixed with the shadow fills of the incoming dummy orders by the real liquidity.
+    /// By default, dummy orders do not interact with each other.
+    pub fn with_dummy_matching(mut self) -> Self {
+        self.dummy_matching = true;
+        self
+    }
+
+    /// Sets the number of the recent trades of the current session sent along with the
+    /// [`RecoverySnapshot`]s, which the brokers request to bring their late subscribers
+    /// up to date. By default, no trades are sent.
+    ///
+    /// # Arguments
+    ///
+    /// * `num_trades` — Number of the trades per traded pair.
+    pub fn with_recovery_trades(mut self, num_trades: usize) -> Self {
+        self.num_recent_trades = num_trades;
+        self
+    }
+
+    /// Makes the exchange estimate the realized volatility of the traded pairs
+    /// from their trades over the rolling window.
+    /// Estimates are kept across the sessions.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` — Length of the estimation window.
+    pub fn with_volatility_estimation(mut self, window: Duration) -> Self {
+        self.volatility = Some(VolatilityMonitor::new(window));
+        self
+    }
+
+    /// Discards the broker limit orders with the prices outside of the band
+    /// around the last trade price, which width is proportional to the realized volatility.
+    /// Replay orders are never checked, since they reconstruct the historical order books.
+    /// Requires the [`with_volatility_estimation`](Self::with_volatility_estimation).
+    ///
+    /// # Arguments
+    ///
+    /// * `band` — Volatility band.
+    pub fn with_volatility_band(mut self, band: VolatilityBand) -> Self {
+        self.volatility.as_mut()
+            .unwrap_or_else(|| panic!("Volatility band requires the volatility estimation"))
+            .set_band(band);
+        self
+    }
+
+    /// Makes the exchange publish the
+    /// [`VolatilityEstimate`](ExchangeEventNotification::VolatilityEstimate)s
+    /// of the traded pairs over the consecutive intervals.
+    /// Estimates are published at the first message the exchange receives after the interval ends.
+    /// Requires the [`with_volatility_estimation`](Self::with_volatility_estimation).
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` — Length of the intervals.
+    pub fn with_volatility_estimates(mut self, interval: Duration) -> Self {
+        self.volatility.as_mut()
+            .unwrap_or_else(|| panic!("Volatility estimates require the volatility estimation"))
+            .set_publication_interval(interval);
+        self
+    }
+
+    /// Returns the volatility estimator of the traded pair
+    /// or `None` if the volatility is not estimated or there have been no trades.
+    ///
+    /// # Arguments
+    ///
+    /// * `traded_pair` — Traded pair.
+    pub fn get_volatility_estimator(
+        &self,
+        traded_pair: TradedPair<Symbol, Settlement>) -> Option<&VolatilityEstimator>
+    {
+        self.volatility.as_ref()?.get_estimator(traded_pair)
+    }
+
+    /// Makes the exchange go through the trading phases of the schedule every session.
+    /// Each transition is scheduled as the [`PhaseTransition`] wakeup at the opening,
+    /// so that the auction call ends with the crossing at its scheduled datetime.
+    /// Phases can also be switched by the replay
+    /// with the [`SetTradingPhase`](BasicReplayRequest::SetTradingPhase) requests.
+    ///
+    /// # Arguments
+    ///
+    /// * `schedule` — Trading phases of the session.
+    pub fn with_session_schedule(mut self, schedule: SessionSchedule) -> Self {
+        self.session_calendar = Some(SessionCalendar::new(schedule));
+        self
+    }
+
+    /// Returns the current phase of the trading session.
+    pub fn get_trading_phase(&self) -> TradingPhase {
+        self.trading_phase
+    }
+
+    /// Adds the user-defined hook observing the incoming messages before they are processed.
+    /// Hooks are called in the order they are added.
+    ///
+    /// # Arguments
+    ///
+    /// * `middleware` — Hook to add.
+    pub fn with_middleware(
+        mut self,
+        middleware: impl ExchangeMiddleware<ExchangeID, BrokerID, Symbol, Settlement> + 'static,
+    ) -> Self
+    {
+        self.middleware.push(Box::new(middleware));
+        self
+    }
+
+    fn apply_broker_request_middleware(
+        &mut self,
+        broker_id: BrokerID,
+        request: &mut BasicBrokerToExchange<ExchangeID, Symbol, Settlement>,
+    ) -> Verdict<BasicExchangeToBrokerReply<Symbol, Settlement>>
+    {
+        for middleware in &mut self.middleware {
+            match middleware.on_broker_request(self.current_dt, broker_id, request) {
+                Verdict::Pass => {}
+                verdict => return verdict
+            }
+        }
+        Verdict::Pass
+    }
+
+    /// Returns the closing price of the last session the traded pair was traded in.
+    ///
+    /// # Arguments
+    ///
+    /// * `traded_pair` — Traded pair.
+    pub fn get_closing_price(&self, traded_pair: TradedPair<Symbol, Settlement>) -> Option<Tick> {
+        self.closing_prices.get(&traded_pair).copied()
+    }
+
+    /// Lists the multi-leg instrument. Its fills are reported to the clearing house
+    /// and to the brokers as the fills of the legs.
+    /// The spread should be started as any other traded pair.
+    ///
+    /// # Arguments
+    ///
+    /// * `spread` — Traded pair of the spread.
+    /// * `definition` — Legs of the spread.
+    pub fn with_spread(
+        mut self,
+        spread: TradedPair<Symbol, Settlement>,
+        definition: SpreadDefinition<Symbol, Settlement>) -> Self
+    {
+        if let Some(leg) = definition.get_legs().iter().find(|leg| leg.traded_pair == spread) {
+            panic!("Spread cannot be its own leg: {:?}", leg.traded_pair)
+        }
+        self.spreads.insert(spread, definition);
+        self
+    }
+
+    /// Returns the definition of the spread.
+    ///
+    /// # Arguments
+    ///
+    /// * `spread` — Traded pair of the spread.
+    pub fn get_spread(
+        &self,
+        spread: TradedPair<Symbol, Settlement>) -> Option<&SpreadDefinition<Symbol, Settlement>>
+    {
+        self.spreads.get(&spread)
+    }
+
+    /// Returns the best bid and ask of the traded pair.
+    /// If the traded pair is a spread with the implied pricing enabled,
+    /// the quotes of its order book are improved by the ones implied by the leg books.
+    ///
+    /// # Arguments
+    ///
+    /// * `traded_pair` — Traded pair.
+    pub fn get_best_quote(
+        &self,
+        traded_pair: TradedPair<Symbol, Settlement>) -> (Option<Tick>, Option<Tick>)
+    {
+        let (bid, ask) = self.get_book_quote(traded_pair);
+        match self.spreads.get(&traded_pair) {
+            Some(definition) if definition.has_implied_pricing() => {
+                let (implied_bid, implied_ask) = definition.implied_quote(
+                    |leg| self.get_book_quote(leg)
+                );
+                (bid.max(implied_bid), ask.into_iter().chain(implied_ask).min())
+            }
+            _ => (bid, ask)
+        }
+    }
+
+    /// Returns the internal ID of the order submitted during the current session.
+    /// Submitted IDs are scoped by the submitter and the traded pair,
+    /// so the same ID may refer to the different orders of the different traded pairs,
+    /// while the internal IDs are unique across the exchange.
+    ///
+    /// # Arguments
+    ///
+    /// * `submitter` — Broker that submitted the order or `None` for the replay.
+    /// * `traded_pair` — Traded pair.
+    /// * `order_id` — Submitted order ID.
+    pub fn get_internal_order_id(
+        &self,
+        submitter: Option<BrokerID>,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        order_id: OrderID) -> Option<OrderID>
+    {
+        let order_ids = match submitter {
+            Some(broker_id) => self.broker_to_order_id.get(&broker_id)?,
+            None => &self.replay_order_ids
+        };
+        order_ids.get(&(traded_pair, order_id)).copied()
+    }
+
+    /// Returns the current state of the order submitted during the current session.
+    ///
+    /// # Arguments
+    ///
+    /// * `submitter` — Broker that submitted the order or `None` for the replay.
+    /// * `traded_pair` — Traded pair.
+    /// * `order_id` — Submitted order ID.
+    pub fn get_order_state(
+        &self,
+        submitter: Option<BrokerID>,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        order_id: OrderID) -> OrderState
+    {
+        let stop = submitter.and_then(
+            |broker_id| self.stop_orders.get(broker_id, traded_pair, order_id)
+        );
+        if let Some(stop) = stop {
+            return OrderState::PendingStop {
+                direction: stop.order.direction,
+                stop_price: stop.order.stop_price,
+                remaining_size: stop.order.size,
+            };
+        }
+        let internal_order_id = match self.get_internal_order_id(
+            submitter,
+            traded_pair,
+            order_id,
+        ) {
+            Some(internal_order_id) => internal_order_id,
+            None => return OrderState::Unknown
+        };
+        self.order_books.get(&traded_pair)
+            .and_then(|(order_book, _)| order_book.get_limit_order(internal_order_id))
+            .map_or(
+                OrderState::Closed,
+                |(direction, price, remaining_size)| OrderState::Resting {
+                    direction,
+                    price,
+                    remaining_size,
+                },
+            )
+    }
+
+    fn get_book_quote(
+        &self,
+        traded_pair: TradedPair<Symbol, Settlement>) -> (Option<Tick>, Option<Tick>)
+    {
+        if let Some((order_book, _)) = self.order_books.get(&traded_pair) {
+            (
+                order_book.get_ob_side_iter::<false>().next().map(|(price, _)| price),
+                order_book.get_ob_side_iter::<true>().next().map(|(price, _)| price)
+            )
+        } else {
+            (None, None)
+        }
+    }
+
+    /// Splits the order book of the traded pair from the exchange state
+    /// its events are interpreted against.
+    ///
+    /// # Arguments
+    ///
+    /// * `traded_pair` — Traded pair which order book exists.
+    /// * `spread_legs` — Spread legs with their reference prices if the traded pair is a spread.
+    fn get_match_context<'a>(
+        &'a mut self,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        spread_legs: &'a [LegReference<Symbol, Settlement>],
+    ) -> (&'a mut OrderBook, MatchContext<'a, ExchangeID, BrokerID, Symbol, Settlement>)
+    {
+        let (order_book, price_step) = self.order_books.get_mut(&traded_pair)
+            .unwrap_or_else(|| unreachable!("Cannot find order book for the {traded_pair:?}"));
+        let price_step = *price_step;
+        let closing_price_method = self.closing_price_method;
+        let num_recent_trades = self.num_recent_trades;
+        let context = MatchContext {
+            current_dt: self.current_dt,
+            traded_pair,
+            price_step,
+            spread_legs,
+            internal_to_submitted: &self.internal_to_submitted,
+            shadow_orders: &mut self.shadow_orders,
+            order_expirations: &mut self.order_expirations,
+            broker_to_order_id: &self.broker_to_order_id,
+            clearing: self.clearing_house.as_deref().map(
+                |clearing_house| (clearing_house, self.name)
+            ),
+            fee_model: self.fee_model.as_deref_mut(),
+            trade_tape: self.trade_tapes.entry(traded_pair).or_insert_with(
+                || TradeTape::new(closing_price_method, price_step, num_recent_trades)
+            ),
+            volatility: self.volatility.as_mut().map(
+                |volatility| volatility.get_estimator_mut(traded_pair)
+            ),
+            session_fills: self.session_fills.entry(traded_pair).or_default(),
+            num_trades: 0,
+            fill_ids: &mut self.fill_ids,
+        };
+        (order_book, context)
+    }
+
+    /// Returns the legs of the spread along with their reference prices,
+    /// i.e. the mid-prices of the leg books or their best quotes if only one side is present.
+    /// Returns an empty vector if the traded pair is not a spread.
+    fn get_spread_leg_references(
+        &self,
+        traded_pair: TradedPair<Symbol, Settlement>,
+    ) -> SpreadLegReferences<Symbol, Settlement>
+    {
+        let definition = if let Some(definition) = self.spreads.get(&traded_pair) {
+            definition
+        } else {
+            return SmallVec::new();
+        };
+        let spread_price_step = self.order_books.get(&traded_pair)
+            .map_or(TickSize(1.0), |(_, price_step)| *price_step);
+        definition.get_legs()
+            .iter()
+            .map(
+                |leg| {
+                    let (price, price_step) = match self.order_books.get(&leg.traded_pair) {
+                        Some((_, price_step)) => {
+                            let to_f64 = |price: Tick| price.to_f64(*price_step);
+                            let price = match self.get_book_quote(leg.traded_pair) {
+                                (Some(bid), Some(ask)) => Some((to_f64(bid) + to_f64(ask)) / 2.0),
+                                (bid, ask) => bid.or(ask).map(to_f64)
+                            };
+                            (price, *price_step)
+                        }
+                        // Leg that is not traded is priced on the spread grid
+                        None => (None, spread_price_step)
+                    };
+                    LegReference { leg: *leg, price, price_step }
+                }
+            )
+            .collect()
+    }
+
+    fn validate_books<const INCLUSIVE: bool>(&mut self) {
+        for (traded_pair, validator) in &mut self.book_validators {
+            validator.validate::<INCLUSIVE>(
+                self.current_dt,
+                self.order_books.get(traded_pair).map(|(order_book, _price_step)| order_book),
+            );
+        }
+    }
+
+    fn first_generated_order_id(&self) -> OrderID {
+        if self.historical_order_ids {
+            OrderID::FIRST_SYNTHETIC
+        } else {
+            OrderID(0)
+        }
+    }
+
+    /// Checks whether the order satisfies the size restrictions of the traded pair.
+    ///
+    /// # Arguments
+    ///
+    /// * `traded_pair` — Traded pair.
+    /// * `size` — Order size.
+    /// * `price` — Order price. `None` for market orders.
+    fn check_order_size(
+        &self,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        size: Lots,
+        price: Option<Tick>) -> Result<(), PlacementDiscardingReason>
+    {
+        let limits = if let Some(limits) = self.size_limits.get(&traded_pair) {
+            limits
+        } else {
+            return Ok(());
+        };
+        if matches!(limits.min_size, Some(min_size) if size < min_size) {
+            return Err(PlacementDiscardingReason::SizeBelowMinimum);
+        }
+        if matches!(limits.max_size, Some(max_size) if size > max_size) {
+            return Err(PlacementDiscardingReason::SizeAboveMaximum);
+        }
+        if matches!(limits.size_increment, Some(increment) if size.0 % increment.0 != 0) {
+            return Err(PlacementDiscardingReason::SizeNotMultipleOfIncrement);
+        }
+        if let (Some(min_notional), Some(price), Some((_, price_step))) = (
+            limits.min_notional, price, self.order_books.get(&traded_pair)
+        ) {
+            // Notional is compared in ticks multiplied by lots, erring on the side of the order
+            let min_notional = (min_notional / price_step.0 - ACCEPTABLE_PRECISION_ERROR).ceil();
+            if ((price.0 as i128) * (size.0 as i128)) < min_notional as i128 {
+                return Err(PlacementDiscardingReason::NotionalBelowMinimum);
+            }
+        }
+        Ok(())
+    }
+
+    fn publish_best_prices(&self) {
+        let quotes = if let Some((quotes, _)) = &self.trade_through_protection {
+            quotes
+        } else {
+            return;
+        };
+        let mut quotes = quotes.borrow_mut();
+        if !self.is_open
+            || self.downtime.is_some()
+            || self.trading_phase != TradingPhase::Continuous
+        {
+            return quotes.withdraw(self.name);
+        }
+        quotes.publish(
+            self.name,
+            self.order_books.iter().map(
+                |(traded_pair, (order_book, price_step))| (
+                    *traded_pair,
+                    order_book.get_ob_side_iter::<false>()
+                        .next()
+                        .map(|(price, _)| price.to_f64(*price_step)),
+                    order_book.get_ob_side_iter::<true>()
+                        .next()
+                        .map(|(price, _)| price.to_f64(*price_step)),
+                )
+            ),
+        )
+    }
+
+    /// Returns the least aggressive of the order price and the best price of the other exchanges,
+    /// rounded to the price increment, if the broker order would trade through the latter.
+    /// Orders of the spreads with the implied pricing are also protected
+    /// by the quotes implied by the leg books. Market orders have no price.
+    fn get_trade_through_price(
+        &self,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        direction: Direction,
+        price: Option<Tick>,
+        size: Lots) -> Option<Tick>
+    {
+        let (order_book, price_step) = self.order_books.get(&traded_pair)?;
+        let increment = self.price_increments.get(&traded_pair).map_or(1, |increment| increment.0);
+        let buy = direction == Direction::Buy;
+        let away_price = self.trade_through_protection.as_ref()
+            .filter(|(_, policy)| *policy != TradeThroughPolicy::Allow)
+            .and_then(
+                |(quotes, _)| {
+                    let quotes = quotes.borrow();
+                    // Prices are compared on the local grid, erring on the side of the order
+                    if buy {
+                        let (_, ask) = quotes.get_best_ask(traded_pair, Some(self.name))?;
+                        let price = (ask / price_step.0 + ACCEPTABLE_PRECISION_ERROR).floor() as i64;
+                        Some(Tick(price - price.rem_euclid(increment)))
+                    } else {
+                        let (_, bid) = quotes.get_best_bid(traded_pair, Some(self.name))?;
+                        let price = (bid / price_step.0 - ACCEPTABLE_PRECISION_ERROR).ceil() as i64;
+                        Some(Tick(price + (-price).rem_euclid(increment)))
+                    }
+                }
+            );
+        let implied_price = self.spreads.get(&traded_pair)
+            .filter(|definition| definition.has_implied_pricing())
+            .and_then(
+                |definition| {
+                    let (bid, ask) = definition.implied_quote(|leg| self.get_book_quote(leg));
+                    if buy {
+                        ask.map(|Tick(ask)| Tick(ask - ask.rem_euclid(increment)))
+                    } else {
+                        bid.map(|Tick(bid)| Tick(bid + (-bid).rem_euclid(increment)))
+                    }
+                }
+            );
+        let protected_price = if buy {
+            away_price.into_iter().chain(implied_price).min()?
+        } else {
+            away_price.into_iter().chain(implied_price).max()?
+        };
+        let levels: Box<dyn Iterator<Item=(Tick, Lots)>> = if buy {
+            Box::new(order_book.iter_level_sizes::<true>())
+        } else {
+            Box::new(order_book.iter_level_sizes::<false>())
+        };
+        let is_reachable = |level_price: Tick| price.is_none_or(
+            |price| if buy { level_price <= price } else { level_price >= price }
+        );
+        let mut remaining_size = size;
+        for (level_price, level_size) in levels {
+            if remaining_size <= Lots(0) || !is_reachable(level_price) {
+                break;
+            }
+            if if buy { level_price > protected_price } else { level_price < protected_price } {
+                return Some(protected_price);
+            }
+            remaining_size -= level_size
+        }
+        None
+    }
+
+    /// Returns the admissible price for the limit order according to the `OffTickPricePolicy`
+    /// or `None` if the order should be rejected.
+    fn admissible_price(&self, traded_pair: TradedPair<Symbol, Settlement>, price: Tick) -> Option<Tick>
+    {
+        let increment = if let Some(increment) = self.price_increments.get(&traded_pair) {
+            increment.0
+        } else {
+            return Some(price);
+        };
+        let remainder = price.0.rem_euclid(increment);
+        if remainder == 0 {
+            return Some(price);
+        }
+        match self.off_tick_price_policy {
+            OffTickPricePolicy::Reject => None,
+            OffTickPricePolicy::RoundDown => Some(Tick(price.0 - remainder)),
+            OffTickPricePolicy::RoundNearest => {
+                if 2 * remainder < increment {
+                    Some(Tick(price.0 - remainder))
+                } else {
+                    Some(Tick(price.0 - remainder + increment))
+                }
+            }
+        }
+    }
+
+    fn try_broadcast_ob_state<KerMsg: Ord>(
+        &self,
+        mut message_receiver: MessageReceiver<KerMsg>,
+        mut process_action: impl FnMut(<Self as Agent>::Action) -> KerMsg,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        max_levels: usize,
+    ) {
+        if !self.is_open {
+            let reply = Self::create_replay_reply(
+                BasicExchangeToReplayReply::CannotBroadcastObState(
+                    CannotBroadcastObState {
+                        reason: InabilityToBroadcastObState::ExchangeClosed
+                    }
+                )
+            );
+            message_receiver.push(process_action(reply))
+        } else if let Some((order_book, _price_step)) = self.order_books.get(&traded_pair) {
+            let ob_snapshot = Rc::new(
+                ObSnapshot { traded_pair, state: order_book.get_ob_state(max_levels) }
+            );
+            let action_iterator = once_with(
+                || Self::create_replay_reply(
+                    BasicExchangeToReplayReply::ExchangeEventNotification(
+                        ExchangeEventNotification::ObSnapshot(Rc::clone(&ob_snapshot))
+                    )
+                )
+            ).chain(
+                self.broker_to_order_id.keys().map(
+                    |broker_id| Self::create_broker_reply(
+                        self.current_dt,
+                        *broker_id,
+                        BasicExchangeToBrokerReply::ExchangeEventNotification(
+                            ExchangeEventNotification::ObSnapshot(Rc::clone(&ob_snapshot))
+                        ),
+                    )
+                )
+            );
+            message_receiver.extend(action_iterator.map(process_action))
+        } else {
+            let reply = Self::create_replay_reply(
+                BasicExchangeToReplayReply::CannotBroadcastObState(
+                    CannotBroadcastObState {
+                        reason: InabilityToBroadcastObState::NoSuchTradedPair
+                    }
+                )
+            );
+            message_receiver.push(process_action(reply))
+        }
+    }
+
+    fn try_cancel_limit_order<
+        KerMsg: Ord,
+        ProcessAction: FnMut(<Self as Agent>::Action) -> KerMsg,
+        GetBrokerID: Fn() -> BrokerID,
+        const REPLAY: bool
+    >(
+        &mut self,
+        mut message_receiver: MessageReceiver<KerMsg>,
+        mut process_action: ProcessAction,
+        request: LimitOrderCancelRequest<Symbol, Settlement>,
+        get_broker_id: GetBrokerID,
+    ) {
+        if !self.is_open {
+            let cannot_cancel_order = CannotCancelOrder {
+                traded_pair: request.traded_pair,
+                order_id: request.order_id,
+                reason: InabilityToCancelReason::ExchangeClosed,
+            };
+            let reply = if REPLAY {
+                Self::create_replay_reply(
+                    BasicExchangeToReplayReply::CannotCancelOrder(cannot_cancel_order)
+                )
+            } else {
+                Self::create_broker_reply(
+                    self.current_dt,
+                    get_broker_id(),
+                    BasicExchangeToBrokerReply::CannotCancelOrder(cannot_cancel_order),
+                )
+            };
+            message_receiver.push(process_action(reply));
+            return;
+        };
+        let order_id_map = if REPLAY {
+            &self.replay_order_ids
+        } else if let Some(order_id_map) = self.broker_to_order_id.get(&get_broker_id()) {
+            order_id_map
+        } else {
+            let cannot_cancel_order = CannotCancelOrder {
+                traded_pair: request.traded_pair,
+                order_id: request.order_id,
+                reason: InabilityToCancelReason::BrokerNotConnectedToExchange,
+            };
+            let reply = Self::create_broker_reply(
+                self.current_dt,
+                get_broker_id(),
+                BasicExchangeToBrokerReply::CannotCancelOrder(cannot_cancel_order),
+            );
+            message_receiver.push(process_action(reply));
+            return;
+        };
+        let cannot_cancel_order = if let Some(internal_order_id) = order_id_map.get(
+            &(request.traded_pair, request.order_id)
+        ).copied() {
+            if let Some((order_book, _price_step)) = self.order_books.get_mut(&request.traded_pair)
+            {
+                let displayed_size = order_book.get_displayed_size(internal_order_id);
+                if let Ok((limit_order, direction, price)) = order_book.cancel_limit_order(
+                    internal_order_id
+                ) {
+                    let order_cancelled = OrderCancelled {
+                        traded_pair: request.traded_pair,
+                        order_id: request.order_id,
+                        reason: CancellationReason::BrokerRequested,
+                    };
+                    self.order_expirations.remove(&internal_order_id);
+                    let shadow = self.shadow_orders.remove(&internal_order_id);
+                    if !shadow {
+                        self.count_throughput(request.traded_pair, |counts| counts.cancelled += 1)
+                    }
+                    self.notify_limit_order_update::<_, _, _, REPLAY>(
+                        message_receiver,
+                        process_action,
+                        get_broker_id,
+                        (
+                            BasicExchangeToBrokerReply::OrderCancelled(order_cancelled),
+                            BasicExchangeToReplayReply::OrderCancelled(order_cancelled),
+                        ),
+                        Self::create_public_notification(
+                            ExchangeEventNotification::OrderCancelled,
+                            LimitOrderEventInfo {
+                                traded_pair: request.traded_pair,
+                                order_id: limit_order.id,
+                                direction,
+                                price,
+                                size: limit_order.size,
+                            },
+                            displayed_size.filter(|_| !shadow),
+                        ),
+                    );
+                    return;
+                } else {
+                    InabilityToCancelReason::OrderAlreadyExecuted
+                }
+            } else {
+                InabilityToCancelReason::BrokerNotConnectedToExchange
+            }
+        } else {
+            InabilityToCancelReason::OrderHasNotBeenSubmitted
+        };
+        let cannot_cancel_order = CannotCancelOrder {
+            traded_pair: request.traded_pair,
+            order_id: request.order_id,
+            reason: cannot_cancel_order,
+        };
+        let reply = if REPLAY {
+            Self::create_replay_reply(
+                BasicExchangeToReplayReply::CannotCancelOrder(cannot_cancel_order)
+            )
         } else {
-            InabilityToCancelReason::OrderHasNotBeenSubmitted
+            Self::create_broker_reply(
+                self.current_dt,
+                get_broker_id(),
+                BasicExchangeToBrokerReply::CannotCancelOrder(cannot_cancel_order),
+            )
+        };
+        message_receiver.push(process_action(reply))
+    }
+
+    /// Cancels the resting limit order on behalf of the exchange itself
+    /// and notifies the agents. Does nothing if the order is not resting.
+    ///
+    /// # Arguments
+    ///
+    /// * `order_ids` — Internal and submitted IDs of the order.
+    /// * `reason` — Reason of the cancellation.
+    fn cancel_resting_order<
+        KerMsg: Ord,
+        ProcessAction: FnMut(<Self as Agent>::Action) -> KerMsg,
+        GetBrokerID: Fn() -> BrokerID,
+        const REPLAY: bool
+    >(
+        &mut self,
+        message_receiver: MessageReceiver<KerMsg>,
+        process_action: ProcessAction,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        order_ids: (OrderID, OrderID),
+        get_broker_id: GetBrokerID,
+        reason: CancellationReason,
+    ) {
+        let (internal_order_id, order_id) = order_ids;
+        let cancelled = self.order_books.get_mut(&traded_pair).and_then(
+            |(order_book, _price_step)| {
+                let displayed_size = order_book.get_displayed_size(internal_order_id);
+                let cancelled = order_book.cancel_limit_order(internal_order_id).ok()?;
+                Some((cancelled, displayed_size))
+            }
+        );
+        let ((limit_order, direction, price), displayed_size) = if let Some(cancelled) = cancelled {
+            cancelled
+        } else {
+            return;
+        };
+        let order_cancelled = OrderCancelled { traded_pair, order_id, reason };
+        self.order_expirations.remove(&internal_order_id);
+        let shadow = self.shadow_orders.remove(&internal_order_id);
+        if !shadow {
+            self.count_throughput(traded_pair, |counts| counts.cancelled += 1)
+        }
+        self.notify_limit_order_update::<_, _, _, REPLAY>(
+            message_receiver,
+            process_action,
+            get_broker_id,
+            (
+                BasicExchangeToBrokerReply::OrderCancelled(order_cancelled),
+                BasicExchangeToReplayReply::OrderCancelled(order_cancelled),
+            ),
+            Self::create_public_notification(
+                ExchangeEventNotification::OrderCancelled,
+                LimitOrderEventInfo {
+                    traded_pair,
+                    order_id: limit_order.id,
+                    direction,
+                    price,
+                    size: limit_order.size,
+                },
+                displayed_size.filter(|_| !shadow),
+            ),
+        )
+    }
+
+    fn try_reduce_order_size<
+        KerMsg: Ord,
+        ProcessAction: FnMut(<Self as Agent>::Action) -> KerMsg,
+        GetBrokerID: Fn() -> BrokerID,
+        const REPLAY: bool
+    >(
+        &mut self,
+        mut message_receiver: MessageReceiver<KerMsg>,
+        mut process_action: ProcessAction,
+        request: LimitOrderReductionRequest<Symbol, Settlement>,
+        get_broker_id: GetBrokerID,
+    ) {
+        let internal_order_id = if !self.is_open {
+            Err(InabilityToCancelReason::ExchangeClosed)
+        } else {
+            let order_id_map = if REPLAY {
+                Some(&self.replay_order_ids)
+            } else {
+                self.broker_to_order_id.get(&get_broker_id())
+            };
+            if let Some(order_id_map) = order_id_map {
+                order_id_map.get(&(request.traded_pair, request.order_id))
+                    .copied()
+                    .ok_or(InabilityToCancelReason::OrderHasNotBeenSubmitted)
+            } else {
+                Err(InabilityToCancelReason::BrokerNotConnectedToExchange)
+            }
+        };
+        let reduced = internal_order_id.and_then(
+            |internal_order_id| {
+                let (order_book, _price_step) = self.order_books
+                    .get_mut(&request.traded_pair)
+                    .ok_or(InabilityToCancelReason::NoSuchTradedPair)?;
+                let displayed_size = order_book.get_displayed_size(internal_order_id);
+                let reduced = order_book.reduce_limit_order(internal_order_id, request.size)
+                    .map_err(|_| InabilityToCancelReason::OrderAlreadyExecuted)?;
+                // Reserve of the iceberg order is reduced first, unseen by the other agents
+                let displayed_reduction = displayed_size.map(
+                    |displayed_size| displayed_size - order_book
+                        .get_displayed_size(internal_order_id)
+                        .unwrap_or(Lots(0))
+                );
+                Ok((reduced, displayed_reduction))
+            }
+        );
+        let ((limit_order, direction, price), displayed_reduction) = match reduced {
+            Ok(reduced) => reduced,
+            Err(reason) => {
+                let cannot_reduce = CannotCancelOrder {
+                    traded_pair: request.traded_pair,
+                    order_id: request.order_id,
+                    reason,
+                };
+                let reply = if REPLAY {
+                    Self::create_replay_reply(
+                        BasicExchangeToReplayReply::CannotReduceOrderSize(cannot_reduce)
+                    )
+                } else {
+                    Self::create_broker_reply(
+                        self.current_dt,
+                        get_broker_id(),
+                        BasicExchangeToBrokerReply::CannotReduceOrderSize(cannot_reduce),
+                    )
+                };
+                message_receiver.push(process_action(reply));
+                return;
+            }
+        };
+        let info = LimitOrderEventInfo {
+            traded_pair: request.traded_pair,
+            order_id: limit_order.id,
+            direction,
+            price,
+            size: limit_order.size,
+        };
+        if request.size >= limit_order.size {
+            let order_cancelled = OrderCancelled {
+                traded_pair: request.traded_pair,
+                order_id: request.order_id,
+                reason: CancellationReason::BrokerRequested,
+            };
+            self.order_expirations.remove(&limit_order.id);
+            let shadow = self.shadow_orders.remove(&limit_order.id);
+            if !shadow {
+                self.count_throughput(request.traded_pair, |counts| counts.cancelled += 1)
+            }
+            self.notify_limit_order_update::<_, _, _, REPLAY>(
+                message_receiver,
+                process_action,
+                get_broker_id,
+                (
+                    BasicExchangeToBrokerReply::OrderCancelled(order_cancelled),
+                    BasicExchangeToReplayReply::OrderCancelled(order_cancelled),
+                ),
+                Self::create_public_notification(
+                    ExchangeEventNotification::OrderCancelled,
+                    info,
+                    displayed_reduction.filter(|_| !shadow),
+                ),
+            )
+        } else {
+            let order_size_reduced = OrderSizeReduced {
+                traded_pair: request.traded_pair,
+                order_id: request.order_id,
+                size: request.size,
+                remaining_size: limit_order.size - request.size,
+            };
+            let shadow = self.shadow_orders.contains(&limit_order.id);
+            if !shadow {
+                self.count_throughput(request.traded_pair, |counts| counts.reduced += 1)
+            }
+            self.notify_limit_order_update::<_, _, _, REPLAY>(
+                message_receiver,
+                process_action,
+                get_broker_id,
+                (
+                    BasicExchangeToBrokerReply::OrderSizeReduced(order_size_reduced),
+                    BasicExchangeToReplayReply::OrderSizeReduced(order_size_reduced),
+                ),
+                Self::create_public_notification(
+                    ExchangeEventNotification::OrderSizeReduced,
+                    info,
+                    displayed_reduction.filter(|_| !shadow),
+                ),
+            )
+        }
+    }
+
+    /// Sends the reply to the agent that has requested the update of the limit order
+    /// and notifies the other agents of the update, if it is public.
+    fn notify_limit_order_update<
+        KerMsg: Ord,
+        ProcessAction: FnMut(<Self as Agent>::Action) -> KerMsg,
+        GetBrokerID: Fn() -> BrokerID,
+        const REPLAY: bool
+    >(
+        &self,
+        mut message_receiver: MessageReceiver<KerMsg>,
+        mut process_action: ProcessAction,
+        get_broker_id: GetBrokerID,
+        replies: (
+            BasicExchangeToBrokerReply<Symbol, Settlement>,
+            BasicExchangeToReplayReply<Symbol, Settlement>,
+        ),
+        notification: Option<ExchangeEventNotification<Symbol, Settlement>>,
+    ) {
+        let (broker_reply, replay_reply) = replies;
+        let reply = if REPLAY {
+            Self::create_replay_reply(replay_reply)
+        } else {
+            Self::create_broker_reply(self.current_dt, get_broker_id(), broker_reply)
+        };
+        let notification = if let Some(notification) = notification {
+            notification
+        } else {
+            message_receiver.push(process_action(reply));
+            return;
+        };
+        let broker_notification_iterator = self.broker_to_order_id.keys().map(
+            |broker_id| Self::create_broker_reply(
+                self.current_dt,
+                *broker_id,
+                BasicExchangeToBrokerReply::ExchangeEventNotification(notification.clone()),
+            )
+        );
+        if REPLAY {
+            let action_iterator = once(reply).chain(broker_notification_iterator);
+            message_receiver.extend(action_iterator.map(process_action))
+        } else {
+            let replay_notification = Self::create_replay_reply(
+                BasicExchangeToReplayReply::ExchangeEventNotification(notification.clone())
+            );
+            let action_iterator = [replay_notification, reply].into_iter()
+                .chain(broker_notification_iterator);
+            message_receiver.extend(action_iterator.map(process_action))
+        }
+    }
+
+    /// Creates the notification of the other agents about the update of the limit order,
+    /// exposing only its displayed size.
+    /// Returns `None` if the update is not seen by the other agents.
+    ///
+    /// # Arguments
+    ///
+    /// * `create_notification` — Notification kind.
+    /// * `info` — Update as seen by the owner of the order.
+    /// * `displayed_size` — Displayed size of the update. `None` for the shadow orders.
+    ///   Zero for the hidden orders and the reductions of the iceberg order reserves.
+    fn create_public_notification(
+        create_notification: impl FnOnce(
+            LimitOrderEventInfo<Symbol, Settlement>
+        ) -> ExchangeEventNotification<Symbol, Settlement>,
+        info: LimitOrderEventInfo<Symbol, Settlement>,
+        displayed_size: Option<Lots>) -> Option<ExchangeEventNotification<Symbol, Settlement>>
+    {
+        displayed_size
+            .filter(|displayed_size| *displayed_size != Lots(0))
+            .map(|size| create_notification(LimitOrderEventInfo { size, ..info }))
+    }
+
+    fn try_stop_trades<KerMsg: Ord>(
+        &mut self,
+        mut message_receiver: MessageReceiver<KerMsg>,
+        mut process_action: impl FnMut(<Self as Agent>::Action) -> KerMsg,
+        traded_pair: TradedPair<Symbol, Settlement>,
+    ) {
+        if !self.is_open {
+            let reply = Self::create_replay_reply(
+                BasicExchangeToReplayReply::CannotStopTrades(
+                    CannotStopTrades {
+                        reason: InabilityToStopTrades::ExchangeClosed
+                    }
+                )
+            );
+            message_receiver.push(process_action(reply))
+        } else if self.order_books.contains_key(&traded_pair) {
+            self.validate_books::<true>();
+            let mut stops_cancelled = Vec::new();
+            self.cancel_stop_orders(
+                &mut stops_cancelled,
+                |stop| stop.order.traded_pair == traded_pair,
+                CancellationReason::TradesStopped,
+            );
+            let (ob, _price_step) = self.order_books.remove(&traded_pair).unwrap_or_else(
+                || unreachable!("Cannot find order book for the {traded_pair:?}")
+            );
+            let order_cancel_iterator = ob.get_all_ids().map(
+                |internal_order_id| {
+                    let (order_id, from) = self.internal_to_submitted
+                        .get(&internal_order_id)
+                        .unwrap_or_else(
+                            || unreachable!(
+                                "Cannot find limit order with internal ID {} of the {:?}",
+                                internal_order_id, traded_pair
+                            )
+                        );
+                    let order_cancelled = OrderCancelled {
+                        traded_pair,
+                        order_id: *order_id,
+                        reason: CancellationReason::TradesStopped,
+                    };
+                    if let Some(broker_id) = from {
+                        Self::create_broker_reply(
+                            self.current_dt,
+                            *broker_id,
+                            BasicExchangeToBrokerReply::OrderCancelled(order_cancelled),
+                        )
+                    } else {
+                        Self::create_replay_reply(
+                            BasicExchangeToReplayReply::OrderCancelled(order_cancelled)
+                        )
+                    }
+                }
+            );
+            let trades_stopped_iterator = self.broker_to_order_id.keys().map(
+                |broker_id| Self::create_broker_reply(
+                    self.current_dt,
+                    *broker_id,
+                    BasicExchangeToBrokerReply::ExchangeEventNotification(
+                        ExchangeEventNotification::TradesStopped(traded_pair)
+                    ),
+                )
+            ).chain(
+                once_with(
+                    || Self::create_replay_reply(
+                        BasicExchangeToReplayReply::ExchangeEventNotification(
+                            ExchangeEventNotification::TradesStopped(traded_pair)
+                        )
+                    )
+                )
+            );
+            let action_iterator = order_cancel_iterator
+                .chain(stops_cancelled)
+                .chain(trades_stopped_iterator);
+            message_receiver.extend(action_iterator.map(process_action))
+        } else {
+            let reply = Self::create_replay_reply(
+                BasicExchangeToReplayReply::CannotStopTrades(
+                    CannotStopTrades {
+                        reason: InabilityToStopTrades::NoSuchTradedPair
+                    }
+                )
+            );
+            message_receiver.push(process_action(reply))
+        }
+    }
+
+    /// Returns the reason to cancel the resting limit order at the price
+    /// according to the current trading rules of the traded pair, if any.
+    fn check_resting_price(
+        &self,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        price: Tick) -> Option<CancellationReason>
+    {
+        if let Some(increment) = self.price_increments.get(&traded_pair) {
+            if price.0.rem_euclid(increment.0) != 0 {
+                return Some(CancellationReason::OffTickPrice);
+            }
+        }
+        match self.price_bands.get(&traded_pair) {
+            Some(band) if !band.contains(price) => Some(CancellationReason::OutsidePriceBand),
+            _ => None
+        }
+    }
+
+    fn try_apply_admin_action<KerMsg: Ord>(
+        &mut self,
+        mut message_receiver: MessageReceiver<KerMsg>,
+        mut process_action: impl FnMut(<Self as Agent>::Action) -> KerMsg,
+        action: AdminAction<Symbol, Settlement>,
+    ) {
+        let traded_pair = action.get_traded_pair();
+        let rejection_reason = if !self.order_books.contains_key(&traded_pair) {
+            Some(InabilityToApplyAdminAction::NoSuchTradedPair)
+        } else {
+            match action {
+                AdminAction::SetPriceIncrement { increment, .. } if increment <= Tick(0) => {
+                    Some(InabilityToApplyAdminAction::NonPositivePriceIncrement)
+                }
+                AdminAction::SetPriceBand {
+                    band: PriceBand { lower: Some(lower), upper: Some(upper) }, ..
+                } if lower > upper => {
+                    Some(InabilityToApplyAdminAction::InvertedPriceBand)
+                }
+                AdminAction::SetFeeSchedule { fees, .. }
+                if fees.maker_fee_bps + fees.taker_fee_bps < 0 => {
+                    Some(InabilityToApplyAdminAction::NegativeNetFee)
+                }
+                AdminAction::BustTrades {
+                    valid_prices: Some(PriceBand { lower: Some(lower), upper: Some(upper) }), ..
+                } if lower > upper => {
+                    Some(InabilityToApplyAdminAction::InvertedPriceBand)
+                }
+                _ => None
+            }
+        };
+        if let Some(reason) = rejection_reason {
+            let reply = Self::create_replay_reply(
+                BasicExchangeToReplayReply::CannotApplyAdminAction(
+                    CannotApplyAdminAction { action, reason }
+                )
+            );
+            message_receiver.push(process_action(reply));
+            return;
+        }
+        let mut actions = Vec::new();
+        let mut events: Vec<_> = match action {
+            AdminAction::SetPriceIncrement { increment, .. } => {
+                self.price_increments.insert(traded_pair, increment);
+                let (order_book, _price_step) = self.order_books.get_mut(&traded_pair)
+                    .unwrap_or_else(
+                        || unreachable!("Cannot find order book for the {traded_pair:?}")
+                    );
+                order_book.change_tick_size(increment, self.off_grid_order_policy)
+                    .into_iter()
+                    .map(|event| (event, CancellationReason::OffTickPrice))
+                    .collect()
+            }
+            AdminAction::SetPriceBand { band, .. } => {
+                self.price_bands.insert(traded_pair, band);
+                Vec::new()
+            }
+            AdminAction::SetFeeSchedule { fees, .. } => {
+                self.fee_schedules.insert(traded_pair, fees);
+                Vec::new()
+            }
+            AdminAction::BustTrades { since, valid_prices, .. } => {
+                self.bust_trades(&mut actions, traded_pair, since, valid_prices);
+                Vec::new()
+            }
+        };
+        let (order_book, _price_step) = self.order_books.get(&traded_pair).unwrap_or_else(
+            || unreachable!("Cannot find order book for the {traded_pair:?}")
+        );
+        let mut affected_orders: Vec<_> = order_book.get_all_ids_and_prices()
+            .filter_map(
+                |(internal_order_id, price)| self.check_resting_price(traded_pair, price)
+                    .map(|reason| (internal_order_id, reason))
+            )
+            .collect();
+        affected_orders.sort_unstable();
+        let (order_book, _price_step) = self.order_books.get_mut(&traded_pair).unwrap_or_else(
+            || unreachable!("Cannot find order book for the {traded_pair:?}")
+        );
+        for (id, reason) in affected_orders {
+            let displayed_size = order_book.get_displayed_size(id).unwrap_or(Lots(0));
+            let (limit_order, direction, price) = order_book.cancel_limit_order(id)
+                .unwrap_or_else(|err| unreachable!("Cannot cancel order {id}. Error: {err}"));
+            let size = limit_order.size;
+            events.push(
+                (
+                    TickSizeChangeEvent::Cancelled { id, direction, price, size, displayed_size },
+                    reason,
+                )
+            )
+        }
+        for (event, cancellation_reason) in events {
+            self.push_resting_order_replies(&mut actions, traded_pair, event, cancellation_reason);
+            if let TickSizeChangeEvent::Cancelled { id, .. } = event {
+                self.order_expirations.remove(&id);
+                self.shadow_orders.remove(&id);
+            }
+        }
+        let action_applied_iterator = self.broker_to_order_id.keys().map(
+            |broker_id| Self::create_broker_reply(
+                self.current_dt,
+                *broker_id,
+                BasicExchangeToBrokerReply::ExchangeEventNotification(
+                    ExchangeEventNotification::AdminActionApplied(action)
+                ),
+            )
+        ).chain(
+            once_with(
+                || Self::create_replay_reply(
+                    BasicExchangeToReplayReply::ExchangeEventNotification(
+                        ExchangeEventNotification::AdminActionApplied(action)
+                    )
+                )
+            )
+        );
+        actions.extend(action_applied_iterator);
+        message_receiver.extend(actions.into_iter().map(process_action))
+    }
+
+    /// Busts the fills of the broker orders made during the current session
+    /// and creates the replies to their owners.
+    ///
+    /// # Arguments
+    ///
+    /// * `actions` — Actions to push the replies to.
+    /// * `traded_pair` — Traded pair.
+    /// * `since` — Only the trades made since this datetime are busted.
+    /// * `valid_prices` — If set, only the trades at the prices outside of it are busted.
+    fn bust_trades(
+        &mut self,
+        actions: &mut Vec<<Self as Agent>::Action>,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        since: DateTime,
+        valid_prices: Option<PriceBand>,
+    ) {
+        let is_busted = |datetime: DateTime, price: Tick| {
+            datetime >= since && !valid_prices.is_some_and(|band| band.contains(price))
+        };
+        if let Some(tape) = self.trade_tapes.get_mut(&traded_pair) {
+            tape.retain(|datetime, price| !is_busted(datetime, price))
+        }
+        let Some(fills) = self.session_fills.get_mut(&traded_pair) else {
+            return;
+        };
+        let (busted, kept): (Vec<_>, _) = fills.drain(..).partition(
+            |fill| is_busted(fill.datetime, fill.price)
+        );
+        *fills = kept;
+        actions.extend(
+            busted.into_iter().map(
+                |fill| Self::create_broker_reply(
+                    self.current_dt,
+                    fill.broker_id,
+                    BasicExchangeToBrokerReply::TradeBusted(
+                        TradeBusted {
+                            traded_pair,
+                            order_id: fill.order_id,
+                            exec_id: fill.exec_id,
+                            trade_id: fill.trade_id,
+                            direction: fill.direction,
+                            price: fill.price,
+                            size: fill.size,
+                        }
+                    ),
+                )
+            )
+        )
+    }
+
+    /// Creates the replies to the owner of the resting limit order affected by the change
+    /// of the trading rules, as well as the notifications of the other agents.
+    fn push_resting_order_replies(
+        &self,
+        actions: &mut Vec<<Self as Agent>::Action>,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        event: TickSizeChangeEvent,
+        cancellation_reason: CancellationReason,
+    ) {
+        let (internal_order_id, owner_reply, notifications) = match event {
+            TickSizeChangeEvent::Cancelled { id, direction, price, size, displayed_size } => {
+                let (order_id, _) = self.get_submitted_order_id(traded_pair, id);
+                let order_cancelled = OrderCancelled {
+                    traded_pair,
+                    order_id,
+                    reason: cancellation_reason,
+                };
+                let cancelled = LimitOrderEventInfo {
+                    traded_pair,
+                    order_id: id,
+                    direction,
+                    price,
+                    size,
+                };
+                (
+                    id,
+                    (
+                        BasicExchangeToBrokerReply::OrderCancelled(order_cancelled),
+                        BasicExchangeToReplayReply::OrderCancelled(order_cancelled)
+                    ),
+                    Self::create_public_notification(
+                        ExchangeEventNotification::OrderCancelled,
+                        cancelled,
+                        Some(displayed_size),
+                    ).into_iter().collect()
+                )
+            }
+            TickSizeChangeEvent::Repriced {
+                id,
+                direction,
+                old_price,
+                new_price,
+                size,
+                old_displayed_size,
+                new_displayed_size,
+            } => {
+                let (order_id, _) = self.get_submitted_order_id(traded_pair, id);
+                let price_adjusted = OrderPriceAdjusted {
+                    traded_pair,
+                    order_id,
+                    original_price: old_price,
+                    price: new_price,
+                };
+                let event_info = |price| LimitOrderEventInfo {
+                    traded_pair,
+                    order_id: id,
+                    direction,
+                    price,
+                    size,
+                };
+                (
+                    id,
+                    (
+                        BasicExchangeToBrokerReply::OrderPriceAdjusted(price_adjusted),
+                        BasicExchangeToReplayReply::OrderPriceAdjusted(price_adjusted)
+                    ),
+                    [
+                        Self::create_public_notification(
+                            ExchangeEventNotification::OrderCancelled,
+                            event_info(old_price),
+                            Some(old_displayed_size),
+                        ),
+                        Self::create_public_notification(
+                            ExchangeEventNotification::OrderPlaced,
+                            event_info(new_price),
+                            Some(new_displayed_size),
+                        ),
+                    ].into_iter().flatten().collect()
+                )
+            }
         };
-        let cannot_cancel_order = CannotCancelOrder {
-            traded_pair: request.traded_pair,
-            order_id: request.order_id,
-            reason: cannot_cancel_order,
+        let (broker_reply, replay_reply) = owner_reply;
+        let notifications = if self.shadow_orders.contains(&internal_order_id) {
+            vec![]
+        } else {
+            notifications
         };
-        let reply = if REPLAY {
-            Self::create_replay_reply(
-                BasicExchangeToReplayReply::CannotCancelOrder(cannot_cancel_order)
+        if let (_, Some(broker_id)) = self.get_submitted_order_id(traded_pair, internal_order_id) {
+            actions.push(Self::create_broker_reply(self.current_dt, broker_id, broker_reply));
+            actions.extend(
+                notifications.iter().cloned().map(
+                    |notification| Self::create_replay_reply(
+                        BasicExchangeToReplayReply::ExchangeEventNotification(notification)
+                    )
+                )
             )
         } else {
-            Self::create_broker_reply(
-                self.current_dt,
-                get_broker_id(),
-                BasicExchangeToBrokerReply::CannotCancelOrder(cannot_cancel_order),
+            actions.push(Self::create_replay_reply(replay_reply))
+        }
+        for notification in notifications {
+            actions.extend(
+                self.broker_to_order_id.keys().map(
+                    |broker_id| Self::create_broker_reply(
+                        self.current_dt,
+                        *broker_id,
+                        BasicExchangeToBrokerReply::ExchangeEventNotification(
+                            notification.clone()
+                        ),
+                    )
+                )
             )
-        };
-        message_receiver.push(process_action(reply))
+        }
     }
 
-    fn try_stop_trades<KerMsg: Ord>(
-        &mut self,
-        mut message_receiver: MessageReceiver<KerMsg>,
-        mut process_action: impl FnMut(<Self as Agent>::Action) -> KerMsg,
+    fn get_submitted_order_id(
+        &self,
         traded_pair: TradedPair<Symbol, Settlement>,
-    ) {
-        if !self.is_open {
-            let reply = Self::create_replay_reply(
-                BasicExchangeToReplayReply::CannotStopTrades(
-                    CannotStopTrades {
-                        reason: InabilityToStopTrades::ExchangeClosed
+        internal_order_id: OrderID) -> (OrderID, Option<BrokerID>)
+    {
+        *self.internal_to_submitted.get(&internal_order_id).unwrap_or_else(
+            || unreachable!(
+                "Cannot find limit order with internal ID {internal_order_id} \
+                of the {traded_pair:?}"
+            )
+        )
+    }
+
+    fn create_replay_reply(
+        content: BasicExchangeToReplayReply<Symbol, Settlement>) -> <Self as Agent>::Action
+    {
+        ExchangeAction {
+            delay: NanoDuration::ZERO,
+            content: ExchangeActionKind::ExchangeToReplay(BasicExchangeToReplay { content }),
+        }
+    }
+
+    fn create_broker_reply(
+        current_dt: DateTime,
+        broker_id: BrokerID,
+        content: BasicExchangeToBrokerReply<Symbol, Settlement>) -> <Self as Agent>::Action
+    {
+        ExchangeAction {
+            delay: NanoDuration::ZERO,
+            content: ExchangeActionKind::ExchangeToBroker(
+                BasicExchangeToBroker {
+                    broker_id,
+                    exchange_dt: current_dt,
+                    content,
+                }.into()
+            ),
+        }
+    }
+
+    /// Creates the reply to the broker request received while the exchange is down.
+    /// Heartbeats are left unanswered.
+    fn reject_during_downtime(
+        &self,
+        broker_id: BrokerID,
+        request: BasicBrokerRequest<Symbol, Settlement>,
+    ) -> Option<<Self as Agent>::Action>
+    {
+        let discarded = |traded_pair, order_id| BasicExchangeToBrokerReply::OrderPlacementDiscarded(
+            OrderPlacementDiscarded {
+                traded_pair,
+                order_id,
+                reason: PlacementDiscardingReason::ExchangeDown,
+            }
+        );
+        let cannot_cancel = |traded_pair, order_id| CannotCancelOrder {
+            traded_pair,
+            order_id,
+            reason: InabilityToCancelReason::ExchangeDown,
+        };
+        let reply = match request {
+            BasicBrokerRequest::PlaceLimitOrder(order) => {
+                discarded(order.traded_pair, order.order_id)
+            }
+            BasicBrokerRequest::PlaceIcebergOrder(order) => {
+                discarded(order.traded_pair, order.order_id)
+            }
+            BasicBrokerRequest::PlaceMarketOrder(order) => {
+                discarded(order.traded_pair, order.order_id)
+            }
+            BasicBrokerRequest::PlaceStopOrder(order) => {
+                discarded(order.traded_pair, order.order_id)
+            }
+            BasicBrokerRequest::PlaceStopLimitOrder(order) => {
+                discarded(order.traded_pair, order.order_id)
+            }
+            BasicBrokerRequest::CancelLimitOrder(request) => {
+                BasicExchangeToBrokerReply::CannotCancelOrder(
+                    cannot_cancel(request.traded_pair, request.order_id)
+                )
+            }
+            BasicBrokerRequest::ReduceOrderSize(request) => {
+                BasicExchangeToBrokerReply::CannotReduceOrderSize(
+                    cannot_cancel(request.traded_pair, request.order_id)
+                )
+            }
+            BasicBrokerRequest::QueryOrderStatus(request) => {
+                BasicExchangeToBrokerReply::OrderStatus(
+                    OrderStatus {
+                        traded_pair: request.traded_pair,
+                        order_id: request.order_id,
+                        state: OrderState::Unknown,
                     }
                 )
-            );
-            message_receiver.push(process_action(reply))
-        } else if let Occupied(entry) = self.order_books.entry(traded_pair) {
-            let (ob, _price_step) = entry.remove();
-            let order_cancel_iterator = ob.get_all_ids().map(
-                |internal_order_id| {
-                    let (order_id, from) = self.internal_to_submitted
-                        .get(&internal_order_id)
-                        .unwrap_or_else(
-                            || unreachable!(
-                                "Cannot find limit order with internal ID: {}",
-                                internal_order_id
-                            )
-                        );
-                    let order_cancelled = OrderCancelled {
+            }
+            // Order books are frozen during the downtime, so the snapshot stays valid
+            BasicBrokerRequest::RequestRecoverySnapshot(traded_pair) => {
+                BasicExchangeToBrokerReply::RecoverySnapshot(
+                    self.get_recovery_snapshot(traded_pair)
+                )
+            }
+            // Prints missed during the downtime are not published later
+            BasicBrokerRequest::Heartbeat(_) | BasicBrokerRequest::ReportOffBookTrade(_) => {
+                return None;
+            }
+        };
+        Some(Self::create_broker_reply(self.current_dt, broker_id, reply))
+    }
+
+    fn get_recovery_snapshot(
+        &self,
+        traded_pair: TradedPair<Symbol, Settlement>) -> Rc<RecoverySnapshot<Symbol, Settlement>>
+    {
+        let state = self.order_books.get(&traded_pair).map_or(
+            ObState { bids: vec![], asks: vec![] },
+            |(order_book, _price_step)| order_book.get_ob_state(0),
+        );
+        let recent_trades = self.trade_tapes.get(&traded_pair)
+            .into_iter()
+            .flat_map(TradeTape::iter_recent_trades)
+            .map(
+                |(datetime, trade_id, direction, price, size)| (
+                    *datetime,
+                    MarketOrderEventInfo {
                         traded_pair,
-                        order_id: *order_id,
-                        reason: CancellationReason::TradesStopped,
-                    };
-                    if let Some(broker_id) = from {
-                        Self::create_broker_reply(
-                            self.current_dt,
-                            *broker_id,
-                            BasicExchangeToBrokerReply::OrderCancelled(order_cancelled),
-                        )
-                    } else {
-                        Self::create_replay_reply(
-                            BasicExchangeToReplayReply::OrderCancelled(order_cancelled)
-                        )
+                        trade_id: *trade_id,
+                        direction: *direction,
+                        price: *price,
+                        size: *size,
                     }
+                )
+            )
+            .collect();
+        Rc::new(RecoverySnapshot { traded_pair, state, recent_trades })
+    }
+
+    fn notify_everyone<KerMsg: Ord>(
+        &self,
+        message_receiver: &mut MessageReceiver<KerMsg>,
+        mut process_action: impl FnMut(<Self as Agent>::Action) -> KerMsg,
+        notification: ExchangeEventNotification<Symbol, Settlement>,
+    ) {
+        let action_iterator = self.broker_to_order_id.keys().map(
+            |broker_id| Self::create_broker_reply(
+                self.current_dt,
+                *broker_id,
+                BasicExchangeToBrokerReply::ExchangeEventNotification(notification.clone()),
+            )
+        ).chain(
+            once_with(
+                || Self::create_replay_reply(
+                    BasicExchangeToReplayReply::ExchangeEventNotification(notification.clone())
+                )
+            )
+        );
+        message_receiver.extend(action_iterator.map(&mut process_action))
+    }
+
+    fn publish_ob_diffs<KerMsg: Ord>(
+        &mut self,
+        message_receiver: &mut MessageReceiver<KerMsg>,
+        process_action: impl FnMut(<Self as Agent>::Action) -> KerMsg,
+    ) {
+        if !self.ob_diffs {
+            return;
+        }
+        let mut diffs: Vec<_> = self.order_books.iter_mut()
+            .filter_map(
+                |(traded_pair, (order_book, _price_step))| {
+                    let deltas = order_book.drain_journal();
+                    (!deltas.is_empty()).then(
+                        || Rc::new(ObDiff { traded_pair: *traded_pair, deltas })
+                    )
                 }
-            );
-            let trades_stopped_iterator = self.broker_to_order_id.keys().map(
+            )
+            .collect();
+        diffs.sort_unstable_by_key(|diff| diff.traded_pair);
+        let action_iterator = diffs.iter().flat_map(
+            |diff| self.broker_to_order_id.keys().map(
                 |broker_id| Self::create_broker_reply(
                     self.current_dt,
                     *broker_id,
                     BasicExchangeToBrokerReply::ExchangeEventNotification(
-                        ExchangeEventNotification::TradesStopped(traded_pair)
+                        ExchangeEventNotification::ObDiff(Rc::clone(diff))
                     ),
                 )
-            ).chain(
-                once_with(
-                    || Self::create_replay_reply(
-                        BasicExchangeToReplayReply::ExchangeEventNotification(
-                            ExchangeEventNotification::TradesStopped(traded_pair)
+            )
+        );
+        message_receiver.extend(action_iterator.map(process_action))
+    }
+
+    fn publish_throughput_statistics<KerMsg: Ord>(
+        &mut self,
+        message_receiver: &mut MessageReceiver<KerMsg>,
+        mut process_action: impl FnMut(<Self as Agent>::Action) -> KerMsg,
+    ) {
+        let interval = if let Some(throughput) = &mut self.throughput {
+            throughput.poll(self.current_dt)
+        } else {
+            return;
+        };
+        let interval = if let Some(interval) = interval {
+            interval
+        } else {
+            return;
+        };
+        for (traded_pair, counts) in interval.counts {
+            self.notify_everyone(
+                message_receiver,
+                &mut process_action,
+                ExchangeEventNotification::ThroughputStatistics(
+                    ThroughputStatistics {
+                        traded_pair,
+                        interval_start: interval.start,
+                        interval_end: interval.end,
+                        counts,
+                    }
+                ),
+            )
+        }
+    }
+
+    fn publish_volatility_estimates<KerMsg: Ord>(
+        &mut self,
+        message_receiver: &mut MessageReceiver<KerMsg>,
+        mut process_action: impl FnMut(<Self as Agent>::Action) -> KerMsg,
+    ) {
+        let estimates = if let Some(volatility) = &mut self.volatility {
+            volatility.poll(self.current_dt)
+        } else {
+            return;
+        };
+        for estimate in estimates {
+            self.notify_everyone(
+                message_receiver,
+                &mut process_action,
+                ExchangeEventNotification::VolatilityEstimate(estimate),
+            )
+        }
+    }
+
+    fn count_throughput(
+        &mut self,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        count: impl FnOnce(&mut ThroughputCounts))
+    {
+        if let Some(throughput) = &mut self.throughput {
+            count(throughput.get_counts_mut(traded_pair))
+        }
+    }
+
+    /// Puts the exchange down. Until the downtime ends, all broker requests are rejected,
+    /// whereas the replay requests are processed as usual,
+    /// since the market keeps going without the simulated participants.
+    fn start_downtime<KerMsg: Ord>(
+        &mut self,
+        mut message_receiver: MessageReceiver<KerMsg>,
+        process_action: impl FnMut(<Self as Agent>::Action) -> KerMsg,
+        cancel_resting_orders: bool,
+    ) {
+        if let Some(cancel) = &mut self.downtime {
+            *cancel |= cancel_resting_orders;
+            return;
+        }
+        self.downtime = Some(cancel_resting_orders);
+        self.notify_everyone(
+            &mut message_receiver,
+            process_action,
+            ExchangeEventNotification::DowntimeStarted,
+        )
+    }
+
+    /// Brings the exchange back up and, if requested,
+    /// cancels the broker limit orders that have been resting since before the downtime.
+    fn end_downtime<KerMsg: Ord>(
+        &mut self,
+        mut message_receiver: MessageReceiver<KerMsg>,
+        mut process_action: impl FnMut(<Self as Agent>::Action) -> KerMsg,
+    ) {
+        let cancel_resting_orders = if let Some(cancel) = self.downtime.take() {
+            cancel
+        } else {
+            return;
+        };
+        if cancel_resting_orders {
+            let mut traded_pairs: Vec<_> = self.order_books.keys().copied().collect();
+            traded_pairs.sort_unstable();
+            let mut actions = Vec::new();
+            for traded_pair in traded_pairs {
+                let (order_book, _price_step) = self.order_books.get_mut(&traded_pair)
+                    .unwrap_or_else(
+                        || unreachable!("Cannot find order book for the {traded_pair:?}")
+                    );
+                let mut broker_orders: Vec<_> = order_book.get_all_ids_and_prices()
+                    .map(|(internal_order_id, _price)| internal_order_id)
+                    .filter(
+                        |internal_order_id| matches!(
+                            self.internal_to_submitted.get(internal_order_id),
+                            Some((_, Some(_)))
                         )
                     )
-                )
-            );
-            let action_iterator = order_cancel_iterator.chain(trades_stopped_iterator);
-            message_receiver.extend(action_iterator.map(process_action))
-        } else {
-            let reply = Self::create_replay_reply(
-                BasicExchangeToReplayReply::CannotStopTrades(
-                    CannotStopTrades {
-                        reason: InabilityToStopTrades::NoSuchTradedPair
+                    .collect();
+                broker_orders.sort_unstable();
+                let events: Vec<_> = broker_orders.into_iter().map(
+                    |id| {
+                        let displayed_size = order_book.get_displayed_size(id)
+                            .unwrap_or(Lots(0));
+                        let (limit_order, direction, price) = order_book.cancel_limit_order(id)
+                            .unwrap_or_else(
+                                |err| unreachable!("Cannot cancel order {id}. Error: {err}")
+                            );
+                        let size = limit_order.size;
+                        TickSizeChangeEvent::Cancelled {
+                            id,
+                            direction,
+                            price,
+                            size,
+                            displayed_size,
+                        }
                     }
-                )
-            );
-            message_receiver.push(process_action(reply))
+                ).collect();
+                for event in events {
+                    self.push_resting_order_replies(
+                        &mut actions,
+                        traded_pair,
+                        event,
+                        CancellationReason::ExchangeDown,
+                    );
+                    if let TickSizeChangeEvent::Cancelled { id, .. } = event {
+                        self.order_expirations.remove(&id);
+                        self.shadow_orders.remove(&id);
+                    }
+                }
+            }
+            self.cancel_stop_orders(&mut actions, |_| true, CancellationReason::ExchangeDown);
+            message_receiver.extend(actions.into_iter().map(&mut process_action))
+        }
+        self.notify_everyone(
+            &mut message_receiver,
+            process_action,
+            ExchangeEventNotification::DowntimeEnded,
+        )
+    }
+
+    /// Switches to the trading phases of the session schedule that are due by now.
+    fn advance_session_schedule<KerMsg: Ord>(
+        &mut self,
+        message_receiver: &mut MessageReceiver<KerMsg>,
+        mut process_action: impl FnMut(<Self as Agent>::Action) -> KerMsg,
+    ) {
+        let phases = if let Some(calendar) = &mut self.session_calendar {
+            calendar.poll(self.current_dt)
+        } else {
+            return;
+        };
+        for phase in phases {
+            self.set_trading_phase(message_receiver, &mut process_action, phase)
+        }
+    }
+
+    /// Switches to the trading phase, running the auctions first if the auction call ends.
+    /// Ignored while the exchange is closed.
+    fn set_trading_phase<KerMsg: Ord>(
+        &mut self,
+        message_receiver: &mut MessageReceiver<KerMsg>,
+        mut process_action: impl FnMut(<Self as Agent>::Action) -> KerMsg,
+        phase: TradingPhase,
+    ) {
+        if !self.is_open || phase == self.trading_phase {
+            return;
         }
+        if self.trading_phase.is_auction_call() {
+            self.run_auctions(message_receiver, &mut process_action)
+        }
+        self.trading_phase = phase;
+        self.notify_everyone(
+            message_receiver,
+            process_action,
+            ExchangeEventNotification::TradingPhaseChanged(phase),
+        )
     }
 
-    fn create_replay_reply(
-        content: BasicExchangeToReplayReply<Symbol, Settlement>) -> <Self as Agent>::Action
-    {
-        ExchangeAction {
-            delay: 0,
-            content: ExchangeActionKind::ExchangeToReplay(BasicExchangeToReplay { content }),
+    /// Crosses the orders accumulated during the auction call at the equilibrium prices
+    /// of the traded pairs, preferring the ones closest to the last trade prices.
+    /// Crossed bids are treated as the aggressors, the fees included.
+    fn run_auctions<KerMsg: Ord>(
+        &mut self,
+        message_receiver: &mut MessageReceiver<KerMsg>,
+        mut process_action: impl FnMut(<Self as Agent>::Action) -> KerMsg,
+    ) {
+        let mut traded_pairs: Vec<_> = self.order_books.keys().copied().collect();
+        traded_pairs.sort_unstable();
+        for traded_pair in traded_pairs {
+            let (order_book, _price_step) = self.order_books.get_mut(&traded_pair)
+                .unwrap_or_else(|| unreachable!("Cannot find order book for the {traded_pair:?}"));
+            let reference_price = self.trade_tapes.get(&traded_pair)
+                .and_then(TradeTape::get_last_price)
+                .or_else(|| self.closing_prices.get(&traded_pair).copied());
+            let (price, size) = if let Some(auction) = order_book.get_auction_price(reference_price)
+            {
+                auction
+            } else {
+                continue;
+            };
+            let mut events = std::mem::take(&mut self.auction_events);
+            order_book.uncross(price, |bid_id, event| events.push((bid_id, event)));
+            self.notify_everyone(
+                message_receiver,
+                &mut process_action,
+                ExchangeEventNotification::AuctionPriceDetermined(
+                    AuctionInfo { traded_pair, price, size }
+                ),
+            );
+            let spread_legs = self.get_spread_leg_references(traded_pair);
+            let (_, mut context) = self.get_match_context(traded_pair, &spread_legs);
+            for (bid_id, event) in events.drain(..) {
+                let (order_id, broker_id) = context.internal_to_submitted[&bid_id];
+                let mut remaining_size = event.size;
+                if let Some(broker_id) = broker_id {
+                    Self::interpret_ob_event::<_, _, _, false, true, false>(
+                        &mut context,
+                        message_receiver,
+                        &mut process_action,
+                        &mut remaining_size,
+                        event,
+                        (order_id, bid_id),
+                        &|| broker_id,
+                    )
+                } else {
+                    Self::interpret_ob_event::<_, _, _, false, true, true>(
+                        &mut context,
+                        message_receiver,
+                        &mut process_action,
+                        &mut remaining_size,
+                        event,
+                        (order_id, bid_id),
+                        &|| unreachable!("Replay does not have BrokerID"),
+                    )
+                }
+            }
+            let num_trades = context.num_trades;
+            self.auction_events = events;
+            self.count_throughput(traded_pair, |counts| counts.executed += num_trades)
         }
     }
 
-    fn create_broker_reply(
-        current_dt: DateTime,
-        broker_id: BrokerID,
-        content: BasicExchangeToBrokerReply<Symbol, Settlement>) -> <Self as Agent>::Action
-    {
-        ExchangeAction {
-            delay: 0,
-            content: ExchangeActionKind::ExchangeToBroker(
-                BasicExchangeToBroker {
-                    broker_id,
-                    exchange_dt: current_dt,
-                    content,
-                }.into()
-            ),
+    /// Checks whether the order can be placed during the current trading phase.
+    ///
+    /// # Arguments
+    ///
+    /// * `immediate` — Whether the order should be executed or cancelled at once,
+    ///   e.g. the market one.
+    fn check_trading_phase(&self, immediate: bool) -> Result<(), PlacementDiscardingReason> {
+        if self.trading_phase == TradingPhase::PreOpen
+            || immediate && self.trading_phase.is_auction_call()
+        {
+            Err(PlacementDiscardingReason::NotAllowedInTradingPhase)
+        } else {
+            Ok(())
         }
     }
 
@@ -579,6 +2830,9 @@ BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
             message_receiver.push(process_action(reply))
         } else {
             self.is_open = true;
+            if let Some(calendar) = &mut self.session_calendar {
+                calendar.start_session(self.current_dt)
+            }
             let action_iterator = once_with(
                 || Self::create_replay_reply(
                     BasicExchangeToReplayReply::ExchangeEventNotification(
@@ -596,7 +2850,21 @@ BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
                     )
                 )
             );
-            message_receiver.extend(action_iterator.map(process_action))
+            message_receiver.extend(action_iterator.map(&mut process_action));
+            self.advance_session_schedule(&mut message_receiver, &mut process_action);
+            if let Some(calendar) = &self.session_calendar {
+                let transitions = calendar.get_upcoming_phases(self.current_dt).map(
+                    |(delay, phase)| ExchangeAction {
+                        delay: NanoDuration::from_duration(delay).unwrap_or_else(
+                            || panic!("Cannot express the delay until {phase:?} in nanoseconds")
+                        ),
+                        content: ExchangeActionKind::ExchangeToItself(
+                            PhaseTransition { phase }.into()
+                        ),
+                    }
+                );
+                message_receiver.extend(transitions.map(process_action))
+            }
         }
     }
 
@@ -607,16 +2875,66 @@ BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
     ) {
         if self.is_open
         {
+            if self.trading_phase.is_auction_call() {
+                self.run_auctions(&mut message_receiver, &mut process_action)
+            }
+            self.validate_books::<true>();
+            if self.duplicate_order_id_policy == DuplicateOrderIdPolicy::RejectForever {
+                self.retired_order_ids.extend(
+                    self.stop_orders.iter().map(
+                        |stop| (Some(stop.broker_id), stop.order.traded_pair, stop.order.order_id)
+                    )
+                )
+            }
+            let mut stops_cancelled = Vec::new();
+            self.cancel_stop_orders(
+                &mut stops_cancelled,
+                |_| true,
+                CancellationReason::ExchangeClosed,
+            );
+            let mut closing_prices: Vec<_> = self.trade_tapes.drain()
+                .filter_map(
+                    |(traded_pair, tape)| {
+                        let price = tape.get_closing_price(
+                            self.current_dt,
+                            self.closing_price_method,
+                        )?;
+                        Some((traded_pair, price, tape.get_price_step()))
+                    }
+                )
+                .collect();
+            closing_prices.sort_unstable_by_key(|(traded_pair, _, _)| *traded_pair);
+            self.session_fills.clear();
+            self.closing_prices.extend(
+                closing_prices.iter().map(|(traded_pair, price, _)| (*traded_pair, *price))
+            );
+            if let Some(clearing_house) = &self.clearing_house {
+                // Spread positions are novated as the positions of their legs
+                let settlement_prices = closing_prices.iter()
+                    .filter(|(traded_pair, _, _)| !self.spreads.contains_key(traded_pair))
+                    .map(|(traded_pair, price, _)| (*traded_pair, *price));
+                clearing_house.borrow_mut().settle_exchange(
+                    self.current_dt,
+                    self.name,
+                    settlement_prices,
+                )
+            }
             self.is_open = false;
+            let closing_price_notifications = || closing_prices.iter().map(
+                |(traded_pair, price, _)| ExchangeEventNotification::ClosingPrice {
+                    traded_pair: *traded_pair,
+                    price: *price,
+                }
+            );
             let broker_notification_iterator = self.broker_to_order_id.iter().map(
                 |(broker_id, submitted_to_internal)|
-                    once_with(
-                        || Self::create_broker_reply(
+                    closing_price_notifications().chain(
+                        once(ExchangeEventNotification::ExchangeClosed)
+                    ).map(
+                        |notification| Self::create_broker_reply(
                             self.current_dt,
                             *broker_id,
-                            BasicExchangeToBrokerReply::ExchangeEventNotification(
-                                ExchangeEventNotification::ExchangeClosed
-                            ),
+                            BasicExchangeToBrokerReply::ExchangeEventNotification(notification),
                         )
                     ).chain(
                         submitted_to_internal.keys().map(
@@ -635,11 +2953,11 @@ BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
                     )
             );
             let broker_notification_iterator = broker_notification_iterator.flatten();
-            let replay_notification_iterator = once(
-                Self::create_replay_reply(
-                    BasicExchangeToReplayReply::ExchangeEventNotification(
-                        ExchangeEventNotification::ExchangeClosed
-                    )
+            let replay_notification_iterator = closing_price_notifications().chain(
+                once(ExchangeEventNotification::ExchangeClosed)
+            ).map(
+                |notification| Self::create_replay_reply(
+                    BasicExchangeToReplayReply::ExchangeEventNotification(notification)
                 )
             ).chain(
                 self.replay_order_ids.keys().map(
@@ -654,13 +2972,32 @@ BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
                     )
                 )
             );
-            let action_iterator = broker_notification_iterator.chain(replay_notification_iterator);
+            let action_iterator = broker_notification_iterator
+                .chain(stops_cancelled)
+                .chain(replay_notification_iterator);
             message_receiver.extend(action_iterator.map(process_action));
+            if self.duplicate_order_id_policy == DuplicateOrderIdPolicy::RejectForever {
+                let broker_order_ids = self.broker_to_order_id.iter().flat_map(
+                    |(broker_id, order_ids)| order_ids.keys().map(
+                        |(traded_pair, order_id)| (Some(*broker_id), *traded_pair, *order_id)
+                    )
+                );
+                let replay_order_ids = self.replay_order_ids.keys().map(
+                    |(traded_pair, order_id)| (None, *traded_pair, *order_id)
+                );
+                self.retired_order_ids.extend(broker_order_ids.chain(replay_order_ids))
+            }
             self.broker_to_order_id.values_mut().for_each(HashMap::clear);
             self.replay_order_ids.clear();
             self.internal_to_submitted.clear();
+            self.order_expirations.clear();
+            self.shadow_orders.clear();
             self.order_books.values_mut().for_each(|(ob, _price_step)| ob.clear());
-            self.next_order_id = OrderID(0);
+            self.next_order_id = self.first_generated_order_id();
+            self.trading_phase = TradingPhase::Continuous;
+            if let Some(calendar) = &mut self.session_calendar {
+                calendar.end_session()
+            }
         } else {
             let reply = Self::create_replay_reply(
                 BasicExchangeToReplayReply::CannotCloseExchange(
@@ -691,7 +3028,14 @@ BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
             );
             message_receiver.push(process_action(reply))
         } else if let Vacant(entry) = self.order_books.entry(traded_pair) {
-            entry.insert((OrderBook::new(), price_step));
+            let mut order_book = OrderBook::new();
+            if self.ob_diffs {
+                order_book = order_book.with_journal()
+            }
+            if self.dummy_matching {
+                order_book = order_book.with_dummy_matching()
+            }
+            entry.insert((order_book, price_step));
             let broker_notification_iterator = self.broker_to_order_id.keys().map(
                 |broker_id| Self::create_broker_reply(
                     self.current_dt,
@@ -775,6 +3119,45 @@ BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
             message_receiver.push(process_action(reply));
             return;
         }
+        let trades_through = !REPLAY && self.get_trade_through_price(
+            order.traded_pair, order.direction, None, order.size,
+        ).is_some();
+        let checked = self.check_trading_phase(true)
+            .and(
+                if REPLAY {
+                    Ok(())
+                } else {
+                    self.check_order_size(order.traded_pair, order.size, None)
+                }
+            )
+            .and(
+                if trades_through {
+                    Err(PlacementDiscardingReason::TradeThrough)
+                } else {
+                    Ok(())
+                }
+            );
+        if let Err(reason) = checked {
+            let order_discarded = OrderPlacementDiscarded {
+                traded_pair: order.traded_pair,
+                order_id: order.order_id,
+                reason,
+            };
+            let reply = if REPLAY {
+                Self::create_replay_reply(
+                    BasicExchangeToReplayReply::OrderPlacementDiscarded(order_discarded)
+                )
+            } else {
+                Self::create_broker_reply(
+                    self.current_dt,
+                    get_broker_id(),
+                    BasicExchangeToBrokerReply::OrderPlacementDiscarded(order_discarded),
+                )
+            };
+            message_receiver.push(process_action(reply));
+            return;
+        }
+        let spread_legs = self.get_spread_leg_references(order.traded_pair);
         let order_id_map = if REPLAY {
             &mut self.replay_order_ids
         } else if let Some(order_id_map) = self.broker_to_order_id.get_mut(&get_broker_id()) {
@@ -793,11 +3176,24 @@ BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
             message_receiver.push(process_action(reply));
             return;
         };
-        let order_id_map = if let Vacant(entry) = order_id_map.entry(
-            (order.traded_pair, order.order_id)
-        ) {
-            entry
-        } else {
+        let is_duplicate = match order_id_map.get(&(order.traded_pair, order.order_id)) {
+            Some(internal_order_id) => {
+                self.duplicate_order_id_policy != DuplicateOrderIdPolicy::ReuseAfterClosed
+                    || self.order_books.get(&order.traded_pair).is_some_and(
+                        |(order_book, _)| order_book.contains_limit_order(*internal_order_id)
+                    )
+            }
+            None => self.retired_order_ids.contains(
+                &(
+                    if REPLAY { None } else { Some(get_broker_id()) },
+                    order.traded_pair,
+                    order.order_id,
+                )
+            )
+        } || !REPLAY && self.stop_orders.get(
+            get_broker_id(), order.traded_pair, order.order_id,
+        ).is_some();
+        if is_duplicate {
             let order_discarded = OrderPlacementDiscarded {
                 traded_pair: order.traded_pair,
                 order_id: order.order_id,
@@ -817,30 +3213,29 @@ BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
             message_receiver.push(process_action(reply));
             return;
         };
-        if let Some((order_book, _price_step)) = self.order_books.get_mut(&order.traded_pair)
-        {
+        if self.order_books.contains_key(&order.traded_pair) {
             let internal_order_id = self.next_order_id;
             self.next_order_id += OrderID(1);
             self.internal_to_submitted.insert(
                 internal_order_id,
                 (order.order_id, if REPLAY { None } else { Some(get_broker_id()) }),
             );
-            order_id_map.insert(internal_order_id);
+            order_id_map.insert((order.traded_pair, order.order_id), internal_order_id);
 
+            let (order_book, mut context) = self.get_match_context(
+                order.traded_pair, &spread_legs,
+            );
             let mut remaining_size = order.size;
             match (order.dummy, order.direction) {
                 (false, Direction::Buy) => {
                     let callback = |event|
                         Self::interpret_ob_event::<_, _, _, false, true, REPLAY>(
-                            self.current_dt,
-                            &self.internal_to_submitted,
-                            &self.broker_to_order_id,
+                            &mut context,
                             &mut message_receiver,
                             &mut process_action,
                             &mut remaining_size,
                             event,
-                            order.traded_pair,
-                            order.order_id,
+                            (order.order_id, internal_order_id),
                             &get_broker_id,
                         );
                     order_book.insert_market_order::<_, false, true>(
@@ -851,15 +3246,12 @@ BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
                 (false, Direction::Sell) => {
                     let callback = |event|
                         Self::interpret_ob_event::<_, _, _, false, false, REPLAY>(
-                            self.current_dt,
-                            &self.internal_to_submitted,
-                            &self.broker_to_order_id,
+                            &mut context,
                             &mut message_receiver,
                             &mut process_action,
                             &mut remaining_size,
                             event,
-                            order.traded_pair,
-                            order.order_id,
+                            (order.order_id, internal_order_id),
                             &get_broker_id,
                         );
                     order_book.insert_market_order::<_, false, false>(
@@ -870,15 +3262,12 @@ BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
                 (true, Direction::Buy) => {
                     let callback = |event|
                         Self::interpret_ob_event::<_, _, _, true, true, REPLAY>(
-                            self.current_dt,
-                            &self.internal_to_submitted,
-                            &self.broker_to_order_id,
+                            &mut context,
                             &mut message_receiver,
                             &mut process_action,
                             &mut remaining_size,
                             event,
-                            order.traded_pair,
-                            order.order_id,
+                            (order.order_id, internal_order_id),
                             &get_broker_id,
                         );
                     order_book.insert_market_order::<_, true, true>(
@@ -889,15 +3278,12 @@ BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
                 (true, Direction::Sell) => {
                     let callback = |event|
                         Self::interpret_ob_event::<_, _, _, true, false, REPLAY>(
-                            self.current_dt,
-                            &self.internal_to_submitted,
-                            &self.broker_to_order_id,
+                            &mut context,
                             &mut message_receiver,
                             &mut process_action,
                             &mut remaining_size,
                             event,
-                            order.traded_pair,
-                            order.order_id,
+                            (order.order_id, internal_order_id),
                             &get_broker_id,
                         );
                     order_book.insert_market_order::<_, true, false>(
@@ -906,6 +3292,8 @@ BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
                     )
                 }
             }
+            let num_trades = context.num_trades;
+            self.count_throughput(order.traded_pair, |counts| counts.executed += num_trades);
             if remaining_size != Lots(0) {
                 let not_fully_executed = MarketOrderNotFullyExecuted {
                     traded_pair: order.traded_pair,
@@ -950,6 +3338,112 @@ BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
         }
     }
 
+    /// Inserts the replay limit order into the order book without matching it and without
+    /// notifying the brokers, so that the order book can be seeded with the historical state.
+    /// Orders that would cross the opposite side of the order book are discarded.
+    fn try_seed_limit_order<KerMsg: Ord>(
+        &mut self,
+        mut message_receiver: MessageReceiver<KerMsg>,
+        mut process_action: impl FnMut(<Self as Agent>::Action) -> KerMsg,
+        order: LimitOrderPlacingRequest<Symbol, Settlement>,
+    ) {
+        let discard = |reason| Self::create_replay_reply(
+            BasicExchangeToReplayReply::OrderPlacementDiscarded(
+                OrderPlacementDiscarded {
+                    traded_pair: order.traded_pair,
+                    order_id: order.order_id,
+                    reason,
+                }
+            )
+        );
+        let reason = if !self.is_open {
+            Some(PlacementDiscardingReason::ExchangeClosed)
+        } else if order.size == Lots(0) {
+            Some(PlacementDiscardingReason::ZeroSize)
+        } else if self.replay_order_ids.contains_key(&(order.traded_pair, order.order_id))
+            || self.historical_order_ids && (
+            order.order_id >= OrderID::FIRST_SYNTHETIC
+                || self.internal_to_submitted.contains_key(&order.order_id)
+        ) {
+            Some(PlacementDiscardingReason::OrderWithSuchIDAlreadySubmitted)
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            message_receiver.push(process_action(discard(reason)));
+            return;
+        }
+        let price = self.admissible_price(order.traded_pair, order.price);
+        let (order_book, _price_step) = if let Some(order_book) = self.order_books.get_mut(
+            &order.traded_pair
+        ) {
+            order_book
+        } else {
+            let reason = PlacementDiscardingReason::NoSuchTradedPair;
+            message_receiver.push(process_action(discard(reason)));
+            return;
+        };
+        let price = if let Some(price) = price {
+            price
+        } else {
+            let reason = PlacementDiscardingReason::OffTickPrice;
+            message_receiver.push(process_action(discard(reason)));
+            return;
+        };
+        let crosses = match order.direction {
+            Direction::Buy => order_book.get_ob_side_iter::<true>().next().is_some_and(
+                |(best_ask, _)| price >= best_ask
+            ),
+            Direction::Sell => order_book.get_ob_side_iter::<false>().next().is_some_and(
+                |(best_bid, _)| price <= best_bid
+            ),
+        };
+        if crosses {
+            let reason = PlacementDiscardingReason::TradeThrough;
+            message_receiver.push(process_action(discard(reason)));
+            return;
+        }
+        let internal_order_id = if self.historical_order_ids {
+            order.order_id
+        } else {
+            let internal_order_id = self.next_order_id;
+            self.next_order_id += OrderID(1);
+            internal_order_id
+        };
+        let dt = self.current_dt;
+        match (order.dummy, order.direction) {
+            (false, Direction::Buy) => order_book.insert_limit_order_without_matching::<false, true>(
+                dt, internal_order_id, price, order.size,
+            ),
+            (false, Direction::Sell) => order_book.insert_limit_order_without_matching::<false, false>(
+                dt, internal_order_id, price, order.size,
+            ),
+            (true, Direction::Buy) => order_book.insert_limit_order_without_matching::<true, true>(
+                dt, internal_order_id, price, order.size,
+            ),
+            (true, Direction::Sell) => order_book.insert_limit_order_without_matching::<true, false>(
+                dt, internal_order_id, price, order.size,
+            ),
+        }
+        // Seeded orders are the state the brokers start from rather than the order book changes
+        order_book.drain_journal();
+        if order.dummy {
+            self.shadow_orders.insert(internal_order_id);
+        }
+        self.internal_to_submitted.insert(internal_order_id, (order.order_id, None));
+        self.replay_order_ids.insert((order.traded_pair, order.order_id), internal_order_id);
+        let order_accepted = OrderAccepted {
+            traded_pair: order.traded_pair,
+            order_id: order.order_id,
+            broker_order_id: order.order_id,
+            exchange_order_id: Some(internal_order_id),
+        };
+        let reply = Self::create_replay_reply(
+            BasicExchangeToReplayReply::OrderAccepted(order_accepted)
+        );
+        message_receiver.push(process_action(reply))
+    }
+
     fn try_place_limit_order<
         KerMsg: Ord,
         ProcessAction: FnMut(<Self as Agent>::Action) -> KerMsg,
@@ -959,179 +3453,361 @@ BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
         &mut self,
         mut message_receiver: MessageReceiver<KerMsg>,
         mut process_action: ProcessAction,
-        order: LimitOrderPlacingRequest<Symbol, Settlement>,
+        mut order: LimitOrderPlacingRequest<Symbol, Settlement>,
+        display_size: Option<Lots>,
         get_broker_id: GetBrokerID,
     ) {
+        let discard = {
+            let (traded_pair, order_id) = (order.traded_pair, order.order_id);
+            let (current_dt, get_broker_id) = (self.current_dt, &get_broker_id);
+            move |reason| {
+                let order_discarded = OrderPlacementDiscarded { traded_pair, order_id, reason };
+                if REPLAY {
+                    Self::create_replay_reply(
+                        BasicExchangeToReplayReply::OrderPlacementDiscarded(order_discarded)
+                    )
+                } else {
+                    Self::create_broker_reply(
+                        current_dt,
+                        get_broker_id(),
+                        BasicExchangeToBrokerReply::OrderPlacementDiscarded(order_discarded),
+                    )
+                }
+            }
+        };
         if !self.is_open {
-            let order_discarded = OrderPlacementDiscarded {
-                traded_pair: order.traded_pair,
-                order_id: order.order_id,
-                reason: PlacementDiscardingReason::ExchangeClosed,
-            };
-            let reply = if REPLAY {
-                Self::create_replay_reply(
-                    BasicExchangeToReplayReply::OrderPlacementDiscarded(order_discarded)
-                )
-            } else {
-                Self::create_broker_reply(
-                    self.current_dt,
-                    get_broker_id(),
-                    BasicExchangeToBrokerReply::OrderPlacementDiscarded(order_discarded),
-                )
-            };
+            let reply = discard(PlacementDiscardingReason::ExchangeClosed);
             message_receiver.push(process_action(reply));
             return;
         }
         if order.size == Lots(0) {
-            let order_discarded = OrderPlacementDiscarded {
-                traded_pair: order.traded_pair,
-                order_id: order.order_id,
-                reason: PlacementDiscardingReason::ZeroSize,
-            };
-            let reply = if REPLAY {
-                Self::create_replay_reply(
-                    BasicExchangeToReplayReply::OrderPlacementDiscarded(order_discarded)
-                )
-            } else {
-                Self::create_broker_reply(
-                    self.current_dt,
-                    get_broker_id(),
-                    BasicExchangeToBrokerReply::OrderPlacementDiscarded(order_discarded),
+            message_receiver.push(process_action(discard(PlacementDiscardingReason::ZeroSize)));
+            return;
+        }
+        if order.time_in_force.get_expiration().is_some_and(
+            |expiration| expiration <= self.current_dt
+        ) {
+            let reply = discard(PlacementDiscardingReason::AlreadyExpired);
+            message_receiver.push(process_action(reply));
+            return;
+        }
+        let admissible_price = self.admissible_price(order.traded_pair, order.price);
+        let checked_price = admissible_price.unwrap_or(order.price);
+        let is_auction_call = self.trading_phase.is_auction_call();
+        // Orders accumulated for the auction cannot trade through before it is run
+        let protected_price = if REPLAY || is_auction_call {
+            None
+        } else {
+            self.get_trade_through_price(
+                order.traded_pair, order.direction, Some(checked_price), order.size,
+            )
+        };
+        let reject_trade_through = protected_price.is_some() && matches!(
+            self.trade_through_protection,
+            Some((_, TradeThroughPolicy::Reject))
+        );
+        let immediate = matches!(
+            order.time_in_force,
+            TimeInForce::ImmediateOrCancel | TimeInForce::FillOrKill
+        );
+        let checked = self.check_trading_phase(immediate)
+            .and(
+                if REPLAY {
+                    Ok(())
+                } else {
+                    self.check_order_size(order.traded_pair, order.size, Some(checked_price))
+                }
+            )
+            .and(
+                if reject_trade_through {
+                    Err(PlacementDiscardingReason::TradeThrough)
+                } else {
+                    Ok(())
+                }
+            );
+        if let Err(reason) = checked {
+            message_receiver.push(process_action(discard(reason)));
+            return;
+        }
+        let keep_order_id = REPLAY && self.historical_order_ids;
+        let is_reusable = |order_id| {
+            self.duplicate_order_id_policy == DuplicateOrderIdPolicy::ReuseAfterClosed
+                && !self.order_books.values().any(
+                    |(order_book, _)| order_book.contains_limit_order(order_id)
                 )
-            };
+        };
+        if keep_order_id && (
+            order.order_id >= OrderID::FIRST_SYNTHETIC
+                || self.internal_to_submitted.contains_key(&order.order_id)
+                && !is_reusable(order.order_id)
+        ) {
+            let reply = discard(PlacementDiscardingReason::OrderWithSuchIDAlreadySubmitted);
             message_receiver.push(process_action(reply));
             return;
         }
+        let spread_legs = self.get_spread_leg_references(order.traded_pair);
         let order_id_map = if REPLAY {
             &mut self.replay_order_ids
         } else if let Some(order_id_map) = self.broker_to_order_id.get_mut(&get_broker_id()) {
             order_id_map
         } else {
-            let order_discarded = OrderPlacementDiscarded {
-                traded_pair: order.traded_pair,
-                order_id: order.order_id,
-                reason: PlacementDiscardingReason::BrokerNotConnectedToExchange,
-            };
-            let reply = Self::create_broker_reply(
-                self.current_dt,
-                get_broker_id(),
-                BasicExchangeToBrokerReply::OrderPlacementDiscarded(order_discarded),
-            );
+            let reply = discard(PlacementDiscardingReason::BrokerNotConnectedToExchange);
             message_receiver.push(process_action(reply));
             return;
         };
-        let order_id_map = if let Vacant(entry) = order_id_map.entry(
-            (order.traded_pair, order.order_id)
-        ) {
-            entry
-        } else {
-            let order_discarded = OrderPlacementDiscarded {
-                traded_pair: order.traded_pair,
-                order_id: order.order_id,
-                reason: PlacementDiscardingReason::OrderWithSuchIDAlreadySubmitted,
-            };
-            let reply = if REPLAY {
-                Self::create_replay_reply(
-                    BasicExchangeToReplayReply::OrderPlacementDiscarded(order_discarded)
+        let is_duplicate = match order_id_map.get(&(order.traded_pair, order.order_id)) {
+            Some(internal_order_id) => {
+                self.duplicate_order_id_policy != DuplicateOrderIdPolicy::ReuseAfterClosed
+                    || self.order_books.get(&order.traded_pair).is_some_and(
+                        |(order_book, _)| order_book.contains_limit_order(*internal_order_id)
+                    )
+            }
+            None => self.retired_order_ids.contains(
+                &(
+                    if REPLAY { None } else { Some(get_broker_id()) },
+                    order.traded_pair,
+                    order.order_id,
                 )
+            )
+        } || !REPLAY && self.stop_orders.get(
+            get_broker_id(), order.traded_pair, order.order_id,
+        ).is_some();
+        if is_duplicate {
+            let reply = discard(PlacementDiscardingReason::OrderWithSuchIDAlreadySubmitted);
+            message_receiver.push(process_action(reply));
+            return;
+        };
+        if let Some((order_book, _price_step)) = self.order_books.get(&order.traded_pair) {
+            let price = if let Some(price) = admissible_price {
+                price
             } else {
-                Self::create_broker_reply(
-                    self.current_dt,
-                    get_broker_id(),
-                    BasicExchangeToBrokerReply::OrderPlacementDiscarded(order_discarded),
-                )
+                let reply = discard(PlacementDiscardingReason::OffTickPrice);
+                message_receiver.push(process_action(reply));
+                return;
+            };
+            let price = match (protected_price, order.direction) {
+                (Some(protected_price), Direction::Buy) => price.min(protected_price),
+                (Some(protected_price), Direction::Sell) => price.max(protected_price),
+                (None, _) => price
+            };
+            let band = self.price_bands.get(&order.traded_pair);
+            let volatility_band = self.volatility.as_ref()
+                .filter(|_| !REPLAY)
+                .and_then(
+                    |volatility| volatility.get_price_band(order.traded_pair, self.current_dt)
+                );
+            if band.is_some_and(|band| !band.contains(price))
+                || volatility_band.is_some_and(|band| !band.contains(price))
+            {
+                let reply = discard(PlacementDiscardingReason::OutsidePriceBand);
+                message_receiver.push(process_action(reply));
+                return;
+            }
+            let is_fully_executable = || order.size == match order.direction {
+                Direction::Buy => order_book.get_executable_size::<true>(price, order.size),
+                Direction::Sell => order_book.get_executable_size::<false>(price, order.size),
+            };
+            if order.time_in_force == TimeInForce::FillOrKill && !is_fully_executable() {
+                let reply = discard(PlacementDiscardingReason::NotFullyFillable);
+                message_receiver.push(process_action(reply));
+                return;
+            }
+            if price != order.price {
+                let price_adjusted = OrderPriceAdjusted {
+                    traded_pair: order.traded_pair,
+                    order_id: order.order_id,
+                    original_price: order.price,
+                    price,
+                };
+                let reply = if REPLAY {
+                    Self::create_replay_reply(
+                        BasicExchangeToReplayReply::OrderPriceAdjusted(price_adjusted)
+                    )
+                } else {
+                    Self::create_broker_reply(
+                        self.current_dt,
+                        get_broker_id(),
+                        BasicExchangeToBrokerReply::OrderPriceAdjusted(price_adjusted),
+                    )
+                };
+                message_receiver.push(process_action(reply));
+                order.price = price
+            }
+            let internal_order_id = if keep_order_id {
+                order.order_id
+            } else {
+                let internal_order_id = self.next_order_id;
+                self.next_order_id += OrderID(1);
+                internal_order_id
             };
-            message_receiver.push(process_action(reply));
-            return;
-        };
-        if let Some((order_book, _price_step)) = self.order_books.get_mut(&order.traded_pair)
-        {
-            let internal_order_id = self.next_order_id;
-            self.next_order_id += OrderID(1);
             self.internal_to_submitted.insert(
                 internal_order_id,
                 (order.order_id, if REPLAY { None } else { Some(get_broker_id()) }),
             );
-            order_id_map.insert(internal_order_id);
+            order_id_map.insert((order.traded_pair, order.order_id), internal_order_id);
 
+            let dt = self.current_dt;
+            let (order_book, mut context) = self.get_match_context(
+                order.traded_pair, &spread_legs,
+            );
             let mut remaining_size = order.size;
             match (order.dummy, order.direction) {
+                // Orders accumulate without matching until the auction
+                (dummy, direction) if is_auction_call => {
+                    match (dummy, direction, display_size) {
+                        (false, Direction::Buy, Some(display_size)) => {
+                            order_book.insert_iceberg_order_without_matching::<true>(
+                                dt, internal_order_id, order.price, order.size, display_size,
+                            )
+                        }
+                        (false, Direction::Sell, Some(display_size)) => {
+                            order_book.insert_iceberg_order_without_matching::<false>(
+                                dt, internal_order_id, order.price, order.size, display_size,
+                            )
+                        }
+                        (false, Direction::Buy, None) => {
+                            order_book.insert_limit_order_without_matching::<false, true>(
+                                dt, internal_order_id, order.price, order.size,
+                            )
+                        }
+                        (false, Direction::Sell, None) => {
+                            order_book.insert_limit_order_without_matching::<false, false>(
+                                dt, internal_order_id, order.price, order.size,
+                            )
+                        }
+                        (true, Direction::Buy, _) => {
+                            order_book.insert_limit_order_without_matching::<true, true>(
+                                dt, internal_order_id, order.price, order.size,
+                            )
+                        }
+                        (true, Direction::Sell, _) => {
+                            order_book.insert_limit_order_without_matching::<true, false>(
+                                dt, internal_order_id, order.price, order.size,
+                            )
+                        }
+                    }
+                }
                 (false, Direction::Buy) => {
                     let callback = |event|
                         Self::interpret_ob_event::<_, _, _, false, true, REPLAY>(
-                            self.current_dt,
-                            &self.internal_to_submitted,
-                            &self.broker_to_order_id,
+                            &mut context,
                             &mut message_receiver,
                             &mut process_action,
                             &mut remaining_size,
                             event,
-                            order.traded_pair,
-                            order.order_id,
+                            (order.order_id, internal_order_id),
                             &get_broker_id,
                         );
-                    order_book.insert_limit_order::<_, false, true>(
-                        self.current_dt, internal_order_id, order.price, order.size, callback,
-                    )
+                    if immediate {
+                        order_book.insert_instant_limit_order::<_, false, true>(
+                            order.price, order.size, callback,
+                        )
+                    } else if let Some(display_size) = display_size {
+                        order_book.insert_iceberg_order::<_, true>(
+                            dt,
+                            internal_order_id,
+                            order.price,
+                            order.size,
+                            display_size,
+                            callback,
+                        )
+                    } else {
+                        order_book.insert_limit_order::<_, false, true>(
+                            dt, internal_order_id, order.price, order.size, callback,
+                        )
+                    }
                 }
                 (false, Direction::Sell) => {
                     let callback = |event|
                         Self::interpret_ob_event::<_, _, _, false, false, REPLAY>(
-                            self.current_dt,
-                            &self.internal_to_submitted,
-                            &self.broker_to_order_id,
+                            &mut context,
                             &mut message_receiver,
                             &mut process_action,
                             &mut remaining_size,
                             event,
-                            order.traded_pair,
-                            order.order_id,
+                            (order.order_id, internal_order_id),
                             &get_broker_id,
                         );
-                    order_book.insert_limit_order::<_, false, false>(
-                        self.current_dt, internal_order_id, order.price, order.size, callback,
-                    )
+                    if immediate {
+                        order_book.insert_instant_limit_order::<_, false, false>(
+                            order.price, order.size, callback,
+                        )
+                    } else if let Some(display_size) = display_size {
+                        order_book.insert_iceberg_order::<_, false>(
+                            dt,
+                            internal_order_id,
+                            order.price,
+                            order.size,
+                            display_size,
+                            callback,
+                        )
+                    } else {
+                        order_book.insert_limit_order::<_, false, false>(
+                            dt, internal_order_id, order.price, order.size, callback,
+                        )
+                    }
                 }
                 (true, Direction::Buy) => {
                     let callback = |event|
                         Self::interpret_ob_event::<_, _, _, true, true, REPLAY>(
-                            self.current_dt,
-                            &self.internal_to_submitted,
-                            &self.broker_to_order_id,
+                            &mut context,
                             &mut message_receiver,
                             &mut process_action,
                             &mut remaining_size,
                             event,
-                            order.traded_pair,
-                            order.order_id,
+                            (order.order_id, internal_order_id),
                             &get_broker_id,
                         );
-                    order_book.insert_limit_order::<_, true, true>(
-                        self.current_dt, internal_order_id, order.price, order.size, callback,
-                    )
+                    if immediate {
+                        order_book.insert_instant_limit_order::<_, true, true>(
+                            order.price, order.size, callback,
+                        )
+                    } else {
+                        order_book.insert_limit_order::<_, true, true>(
+                            dt, internal_order_id, order.price, order.size, callback,
+                        )
+                    }
                 }
                 (true, Direction::Sell) => {
                     let callback = |event|
                         Self::interpret_ob_event::<_, _, _, true, false, REPLAY>(
-                            self.current_dt,
-                            &self.internal_to_submitted,
-                            &self.broker_to_order_id,
+                            &mut context,
                             &mut message_receiver,
                             &mut process_action,
                             &mut remaining_size,
                             event,
-                            order.traded_pair,
-                            order.order_id,
+                            (order.order_id, internal_order_id),
                             &get_broker_id,
                         );
-                    order_book.insert_limit_order::<_, true, false>(
-                        self.current_dt, internal_order_id, order.price, order.size, callback,
-                    )
+                    if immediate {
+                        order_book.insert_instant_limit_order::<_, true, false>(
+                            order.price, order.size, callback,
+                        )
+                    } else {
+                        order_book.insert_limit_order::<_, true, false>(
+                            dt, internal_order_id, order.price, order.size, callback,
+                        )
+                    }
                 }
             }
+            let num_trades = context.num_trades;
+            self.count_throughput(
+                order.traded_pair,
+                |counts| {
+                    counts.executed += num_trades;
+                    if !order.dummy {
+                        counts.placed += 1
+                    }
+                },
+            );
+            if order.dummy && remaining_size != Lots(0) && !immediate {
+                self.shadow_orders.insert(internal_order_id);
+            }
             let order_accepted = OrderAccepted {
                 traded_pair: order.traded_pair,
                 order_id: order.order_id,
+                broker_order_id: order.order_id,
+                exchange_order_id: Some(internal_order_id),
             };
             let reply = if REPLAY {
                 Self::create_replay_reply(
@@ -1144,26 +3820,282 @@ BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
                     BasicExchangeToBrokerReply::OrderAccepted(order_accepted),
                 )
             };
+            message_receiver.push(process_action(reply));
+            if remaining_size == Lots(0) {
+                return;
+            }
+            match order.time_in_force {
+                // Remainder has not been inserted, so its cancellation is not published
+                TimeInForce::ImmediateOrCancel | TimeInForce::FillOrKill => {
+                    if !order.dummy {
+                        self.count_throughput(order.traded_pair, |counts| counts.cancelled += 1)
+                    }
+                    let order_cancelled = OrderCancelled {
+                        traded_pair: order.traded_pair,
+                        order_id: order.order_id,
+                        reason: CancellationReason::NotImmediatelyExecuted,
+                    };
+                    let reply = if REPLAY {
+                        Self::create_replay_reply(
+                            BasicExchangeToReplayReply::OrderCancelled(order_cancelled)
+                        )
+                    } else {
+                        Self::create_broker_reply(
+                            self.current_dt,
+                            get_broker_id(),
+                            BasicExchangeToBrokerReply::OrderCancelled(order_cancelled),
+                        )
+                    };
+                    message_receiver.push(process_action(reply))
+                }
+                TimeInForce::GoodTillDate(expiration) => {
+                    self.order_expirations.insert(internal_order_id, expiration);
+                    let delay = NanoDuration::from_duration(
+                        (expiration - self.current_dt).max(Duration::zero())
+                    ).unwrap_or_else(
+                        || panic!("Cannot express the delay until {expiration} in nanoseconds")
+                    );
+                    let wakeup = OrderExpiration {
+                        traded_pair: order.traded_pair,
+                        order_id: internal_order_id,
+                        expiration,
+                    };
+                    message_receiver.push(
+                        process_action(
+                            ExchangeAction {
+                                delay,
+                                content: ExchangeActionKind::ExchangeToItself(wakeup.into()),
+                            }
+                        )
+                    )
+                }
+                TimeInForce::Day | TimeInForce::GoodTillCancelled => {}
+            }
+        } else {
+            let reply = discard(PlacementDiscardingReason::NoSuchTradedPair);
             message_receiver.push(process_action(reply))
+        }
+    }
+
+    /// Accepts the stop or stop-limit broker order and triggers it at once
+    /// if the last trade of the session has already reached its stop price.
+    /// Limit price of the stop-limit order is checked only when the order is triggered.
+    fn try_place_stop_order<KerMsg: Ord>(
+        &mut self,
+        message_receiver: &mut MessageReceiver<KerMsg>,
+        mut process_action: impl FnMut(<Self as Agent>::Action) -> KerMsg,
+        stop: PendingStop<BrokerID, Symbol, Settlement>,
+    ) {
+        let PendingStop { broker_id, order, .. } = stop;
+        let is_duplicate = |order_id_map: &HashMap<_, _>| {
+            let is_duplicate = match order_id_map.get(&(order.traded_pair, order.order_id)) {
+                Some(internal_order_id) => {
+                    self.duplicate_order_id_policy != DuplicateOrderIdPolicy::ReuseAfterClosed
+                        || self.order_books.get(&order.traded_pair).is_some_and(
+                            |(order_book, _)| order_book.contains_limit_order(*internal_order_id)
+                        )
+                }
+                None => self.retired_order_ids.contains(
+                    &(Some(broker_id), order.traded_pair, order.order_id)
+                )
+            };
+            is_duplicate
+                || self.stop_orders.get(broker_id, order.traded_pair, order.order_id).is_some()
+        };
+        let discarding_reason = if !self.is_open {
+            Some(PlacementDiscardingReason::ExchangeClosed)
+        } else if order.size == Lots(0) {
+            Some(PlacementDiscardingReason::ZeroSize)
+        } else if let Err(reason) = self.check_trading_phase(false) {
+            Some(reason)
+        } else if let Some(order_id_map) = self.broker_to_order_id.get(&broker_id) {
+            if !self.order_books.contains_key(&order.traded_pair) {
+                Some(PlacementDiscardingReason::NoSuchTradedPair)
+            } else if is_duplicate(order_id_map) {
+                Some(PlacementDiscardingReason::OrderWithSuchIDAlreadySubmitted)
+            } else {
+                None
+            }
         } else {
-            let order_discarded = OrderPlacementDiscarded {
+            Some(PlacementDiscardingReason::BrokerNotConnectedToExchange)
+        };
+        if let Some(reason) = discarding_reason {
+            let reply = Self::create_broker_reply(
+                self.current_dt,
+                broker_id,
+                BasicExchangeToBrokerReply::OrderPlacementDiscarded(
+                    OrderPlacementDiscarded {
+                        traded_pair: order.traded_pair,
+                        order_id: order.order_id,
+                        reason,
+                    }
+                ),
+            );
+            message_receiver.push(process_action(reply));
+            return;
+        }
+        let reply = Self::create_broker_reply(
+            self.current_dt,
+            broker_id,
+            BasicExchangeToBrokerReply::OrderAccepted(
+                OrderAccepted {
+                    traded_pair: order.traded_pair,
+                    order_id: order.order_id,
+                    broker_order_id: order.order_id,
+                    exchange_order_id: None,
+                }
+            ),
+        );
+        message_receiver.push(process_action(reply));
+        let trigger_price = self.trade_tapes.get(&order.traded_pair)
+            .and_then(TradeTape::get_last_price)
+            .and_then(|last_price| stop.get_trigger_price(last_price, last_price));
+        if let Some(trigger_price) = trigger_price {
+            self.activate_stop_order(message_receiver, process_action, stop, trigger_price)
+        } else {
+            self.stop_orders.insert(stop)
+        }
+    }
+
+    /// Notifies the owner of the triggered stop or stop-limit order
+    /// and places it as the market or the limit order, respectively.
+    fn activate_stop_order<KerMsg: Ord>(
+        &mut self,
+        message_receiver: &mut MessageReceiver<KerMsg>,
+        mut process_action: impl FnMut(<Self as Agent>::Action) -> KerMsg,
+        stop: PendingStop<BrokerID, Symbol, Settlement>,
+        trigger_price: Tick,
+    ) {
+        let PendingStop { broker_id, order, limit_price } = stop;
+        let reply = Self::create_broker_reply(
+            self.current_dt,
+            broker_id,
+            BasicExchangeToBrokerReply::StopOrderTriggered(
+                StopOrderTriggered {
+                    traded_pair: order.traded_pair,
+                    order_id: order.order_id,
+                    trigger_price,
+                    limit_price,
+                }
+            ),
+        );
+        message_receiver.push(process_action(reply));
+        if let Some(price) = limit_price {
+            let order = LimitOrderPlacingRequest {
                 traded_pair: order.traded_pair,
                 order_id: order.order_id,
-                reason: PlacementDiscardingReason::NoSuchTradedPair,
+                direction: order.direction,
+                price,
+                size: order.size,
+                dummy: false,
+                time_in_force: TimeInForce::Day,
             };
-            let reply = if REPLAY {
-                Self::create_replay_reply(
-                    BasicExchangeToReplayReply::OrderPlacementDiscarded(order_discarded)
+            self.try_place_limit_order::<_, _, _, false>(
+                message_receiver.reborrow(), &mut process_action, order, None, || broker_id,
+            )
+        } else {
+            self.try_place_market_order::<_, _, _, false>(
+                message_receiver.reborrow(), &mut process_action, order.into(), || broker_id,
+            )
+        }
+    }
+
+    /// Triggers the pending stop orders reached by the trades made since the previous call,
+    /// including the trades of the orders triggered by the call itself.
+    /// Trades made while the exchange is down trigger nothing.
+    fn trigger_stop_orders<KerMsg: Ord>(
+        &mut self,
+        message_receiver: &mut MessageReceiver<KerMsg>,
+        mut process_action: impl FnMut(<Self as Agent>::Action) -> KerMsg,
+    ) {
+        loop {
+            // Few traded pairs trade per call, so they are looked up linearly
+            let traded_ranges: SmallVec<[_; 4]> = self.trade_tapes.iter_mut()
+                .filter_map(
+                    |(traded_pair, tape)| Some((*traded_pair, tape.take_traded_range()?))
                 )
-            } else {
-                Self::create_broker_reply(
+                .collect();
+            if traded_ranges.is_empty() || self.stop_orders.is_empty() || self.downtime.is_some()
+            {
+                return;
+            }
+            let triggered = self.stop_orders.take_triggered(
+                |traded_pair| traded_ranges.iter()
+                    .find(|(traded, _)| *traded == traded_pair)
+                    .map(|(_, range)| *range)
+            );
+            if triggered.is_empty() {
+                return;
+            }
+            for (stop, trigger_price) in triggered {
+                self.activate_stop_order(message_receiver, &mut process_action, stop, trigger_price)
+            }
+        }
+    }
+
+    /// Cancels or reduces the pending stop order of the broker
+    /// and returns the reply to it or `None` if there is no such order.
+    ///
+    /// # Arguments
+    ///
+    /// * `broker_id` — Owner of the order.
+    /// * `traded_pair` — Traded pair.
+    /// * `order_id` — Submitted order ID.
+    /// * `size` — Size to cancel or `None` to cancel the order entirely.
+    fn try_cancel_stop_order(
+        &mut self,
+        broker_id: BrokerID,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        order_id: OrderID,
+        size: Option<Lots>) -> Option<<Self as Agent>::Action>
+    {
+        let remaining_size = if let Some(size) = size {
+            self.stop_orders.reduce(broker_id, traded_pair, order_id, size)?
+        } else {
+            self.stop_orders.remove(broker_id, traded_pair, order_id)?;
+            Lots(0)
+        };
+        let reply = match size {
+            Some(size) if remaining_size != Lots(0) => {
+                BasicExchangeToBrokerReply::OrderSizeReduced(
+                    OrderSizeReduced { traded_pair, order_id, size, remaining_size }
+                )
+            }
+            _ => BasicExchangeToBrokerReply::OrderCancelled(
+                OrderCancelled {
+                    traded_pair,
+                    order_id,
+                    reason: CancellationReason::BrokerRequested,
+                }
+            )
+        };
+        Some(Self::create_broker_reply(self.current_dt, broker_id, reply))
+    }
+
+    /// Cancels the pending stop orders satisfying the predicate
+    /// and creates the replies to their owners.
+    fn cancel_stop_orders(
+        &mut self,
+        actions: &mut Vec<<Self as Agent>::Action>,
+        predicate: impl FnMut(&PendingStop<BrokerID, Symbol, Settlement>) -> bool,
+        reason: CancellationReason,
+    ) {
+        let cancelled = self.stop_orders.drain_filter(predicate);
+        actions.extend(
+            cancelled.into_iter().map(
+                |stop| Self::create_broker_reply(
                     self.current_dt,
-                    get_broker_id(),
-                    BasicExchangeToBrokerReply::OrderPlacementDiscarded(order_discarded),
+                    stop.broker_id,
+                    BasicExchangeToBrokerReply::OrderCancelled(
+                        OrderCancelled {
+                            traded_pair: stop.order.traded_pair,
+                            order_id: stop.order.order_id,
+                            reason,
+                        }
+                    ),
                 )
-            };
-            message_receiver.push(process_action(reply))
-        }
+            )
+        )
     }
 
     fn interpret_ob_event<
@@ -1174,56 +4106,178 @@ BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
         const BUY: bool,
         const REPLAY: bool
     >(
-        current_dt: DateTime,
-        internal_to_submitted: &HashMap<OrderID, (OrderID, Option<BrokerID>)>,
-        broker_to_order_id: &HashMap<
-            BrokerID,
-            HashMap<(TradedPair<Symbol, Settlement>, OrderID), OrderID>
-        >,
+        context: &mut MatchContext<ExchangeID, BrokerID, Symbol, Settlement>,
         message_receiver: &mut MessageReceiver<KerMsg>,
         mut process_action: ProcessAction,
         remaining_size: &mut Lots,
         event: OrderBookEvent,
-        traded_pair: TradedPair<Symbol, Settlement>,
-        new_order_id: OrderID,
+        new_order_ids: (OrderID, OrderID),
         get_broker_id: &GetBrokerID,
     ) {
-        let create_broker_notification = || BasicExchangeToBrokerReply::ExchangeEventNotification(
-            ExchangeEventNotification::TradeExecuted(
-                MarketOrderEventInfo {
+        let MatchContext {
+            current_dt, traded_pair, price_step, spread_legs, clearing, ..
+        } = *context;
+        let (new_order_id, new_exchange_order_id) = new_order_ids;
+        let create_trade_info = |trade_id| ExchangeEventNotification::TradeExecuted(
+            MarketOrderEventInfo {
+                traded_pair,
+                trade_id,
+                direction: if BUY { Direction::Buy } else { Direction::Sell },
+                price: event.price,
+                size: event.size,
+            }
+        );
+        let create_broker_notification = |trade_id| {
+            BasicExchangeToBrokerReply::ExchangeEventNotification(create_trade_info(trade_id))
+        };
+        let create_replay_notification = |trade_id| {
+            BasicExchangeToReplayReply::ExchangeEventNotification(create_trade_info(trade_id))
+        };
+
+        let novate = |resting: Option<BrokerID>| {
+            if let Some((clearing_house, exchange_id)) = clearing {
+                let aggressor = if REPLAY { None } else { Some(get_broker_id()) };
+                let (buyer, seller) = if BUY { (aggressor, resting) } else { (resting, aggressor) };
+                let mut clearing_house = clearing_house.borrow_mut();
+                if spread_legs.is_empty() {
+                    clearing_house.novate(
+                        NovatedTrade {
+                            datetime: current_dt,
+                            exchange_id,
+                            traded_pair,
+                            buyer,
+                            seller,
+                            price: event.price,
+                            size: event.size,
+                        }
+                    );
+                    return;
+                }
+                let price = event.price.to_f64(price_step);
+                // Leg fills are seen from the side of the buyer of the spread
+                for leg in split_spread_fill(Direction::Buy, price, event.size, spread_legs) {
+                    let (buyer, seller) = match leg.direction {
+                        Direction::Buy => (buyer, seller),
+                        Direction::Sell => (seller, buyer)
+                    };
+                    clearing_house.novate(
+                        NovatedTrade {
+                            datetime: current_dt,
+                            exchange_id,
+                            traded_pair: leg.traded_pair,
+                            buyer,
+                            seller,
+                            price: leg.price,
+                            size: leg.size,
+                        }
+                    )
+                }
+            }
+        };
+
+        let (aggressor_direction, resting_direction) = if BUY {
+            (Direction::Buy, Direction::Sell)
+        } else {
+            (Direction::Sell, Direction::Buy)
+        };
+        // Brokers book the spread fills as the fills of the legs
+        let with_legs = |execution, direction| if spread_legs.is_empty() {
+            execution
+        } else {
+            BasicExchangeToBrokerReply::SpreadOrderExecuted(
+                Rc::new(
+                    SpreadOrderExecuted {
+                        execution,
+                        legs: split_spread_fill(
+                            direction, event.price.to_f64(price_step), event.size, spread_legs,
+                        ),
+                    }
+                )
+            )
+        };
+        let mut charge = |broker_id, order_id, liquidity| context.fee_model.as_mut().map_or(
+            Fee(0),
+            |fee_model| fee_model.charge(
+                &ChargeableFill {
+                    datetime: current_dt,
+                    broker_id,
                     traded_pair,
-                    direction: if BUY { Direction::Buy } else { Direction::Sell },
+                    order_id,
+                    liquidity,
                     price: event.price,
                     size: event.size,
                 }
-            )
+            ),
         );
-        let create_replay_notification = || BasicExchangeToReplayReply::ExchangeEventNotification(
-            ExchangeEventNotification::TradeExecuted(
-                MarketOrderEventInfo {
-                    traded_pair,
-                    direction: if BUY { Direction::Buy } else { Direction::Sell },
+        let mut record_fill = |broker_id, order_id, exec_id, trade_id, direction| {
+            context.session_fills.push(
+                SessionFill {
+                    datetime: current_dt,
+                    broker_id,
+                    order_id,
+                    exec_id,
+                    trade_id,
+                    direction,
                     price: event.price,
                     size: event.size,
                 }
             )
-        );
+        };
 
+        // Fills of the dummy orders by the other dummy ones
+        let is_ghost = matches!(
+            event.kind,
+            OrderBookEventKind::OldGhostOrderExecuted(_)
+            | OrderBookEventKind::OldGhostOrderPartiallyExecuted(_)
+            | OrderBookEventKind::NewGhostOrderExecuted
+            | OrderBookEventKind::NewGhostOrderPartiallyExecuted
+        );
         match event.kind
         {
-            OrderBookEventKind::OldOrderExecuted(order_id) => {
-                if let Some((order_id, from)) = internal_to_submitted.get(&order_id) {
+            OrderBookEventKind::OldOrderExecuted(internal_order_id)
+            | OrderBookEventKind::OldHiddenOrderExecuted(internal_order_id)
+            | OrderBookEventKind::OldGhostOrderExecuted(internal_order_id) => {
+                let submitted = context.internal_to_submitted.get(&internal_order_id);
+                if let Some((order_id, from)) = submitted {
+                    context.order_expirations.remove(&internal_order_id);
+                    let is_shadow = context.shadow_orders.remove(&internal_order_id);
+                    let exec_id = context.fill_ids.next_exec_id();
+                    let trade_id = context.fill_ids.get_trade_id();
+                    let mut fee = Fee(0);
+                    if !is_shadow {
+                        novate(*from);
+                        if let Some(broker_id) = from {
+                            record_fill(
+                                *broker_id, *order_id, exec_id, trade_id, resting_direction,
+                            );
+                            fee = charge(*broker_id, *order_id, Liquidity::Maker)
+                        }
+                    }
                     let order_executed = OrderExecuted {
                         traded_pair,
                         order_id: *order_id,
+                        broker_order_id: *order_id,
+                        exchange_order_id: internal_order_id,
+                        exec_id,
+                        trade_id,
                         price: event.price,
                         size: event.size,
+                        fee,
                     };
                     let notification = if let Some(broker_id) = from {
                         Self::create_broker_reply(
                             current_dt,
                             *broker_id,
-                            BasicExchangeToBrokerReply::OrderExecuted(order_executed),
+                            if is_ghost {
+                                BasicExchangeToBrokerReply::GhostOrderExecuted(order_executed)
+                            } else if is_shadow {
+                                BasicExchangeToBrokerReply::ShadowOrderExecuted(order_executed)
+                            } else {
+                                with_legs(
+                                    BasicExchangeToBrokerReply::OrderExecuted(order_executed),
+                                    resting_direction,
+                                )
+                            },
                         )
                     } else {
                         Self::create_replay_reply(
@@ -1232,24 +4286,61 @@ BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
                     };
                     message_receiver.push(process_action(notification))
                 } else {
-                    panic!("Cannot find limit order with internal ID {order_id}")
+                    panic!(
+                        "Cannot find limit order with internal ID {internal_order_id} \
+                        of the {traded_pair:?}"
+                    )
                 }
             }
-            OrderBookEventKind::OldOrderPartiallyExecuted(order_id) => {
-                if let Some((order_id, from)) = internal_to_submitted.get(&order_id) {
+            OrderBookEventKind::OldOrderPartiallyExecuted(internal_order_id)
+            | OrderBookEventKind::OldHiddenOrderPartiallyExecuted(internal_order_id)
+            | OrderBookEventKind::OldGhostOrderPartiallyExecuted(internal_order_id) => {
+                let submitted = context.internal_to_submitted.get(&internal_order_id);
+                if let Some((order_id, from)) = submitted {
+                    let is_shadow = context.shadow_orders.contains(&internal_order_id);
+                    let exec_id = context.fill_ids.next_exec_id();
+                    let trade_id = context.fill_ids.get_trade_id();
+                    let mut fee = Fee(0);
+                    if !is_shadow {
+                        novate(*from);
+                        if let Some(broker_id) = from {
+                            record_fill(
+                                *broker_id, *order_id, exec_id, trade_id, resting_direction,
+                            );
+                            fee = charge(*broker_id, *order_id, Liquidity::Maker)
+                        }
+                    }
                     let order_partially_executed = OrderPartiallyExecuted {
                         traded_pair,
                         order_id: *order_id,
+                        broker_order_id: *order_id,
+                        exchange_order_id: internal_order_id,
+                        exec_id,
+                        trade_id,
                         price: event.price,
                         size: event.size,
+                        fee,
                     };
                     let notification = if let Some(broker_id) = from {
                         Self::create_broker_reply(
                             current_dt,
                             *broker_id,
-                            BasicExchangeToBrokerReply::OrderPartiallyExecuted(
-                                order_partially_executed
-                            ),
+                            if is_ghost {
+                                BasicExchangeToBrokerReply::GhostOrderPartiallyExecuted(
+                                    order_partially_executed
+                                )
+                            } else if is_shadow {
+                                BasicExchangeToBrokerReply::ShadowOrderPartiallyExecuted(
+                                    order_partially_executed
+                                )
+                            } else {
+                                with_legs(
+                                    BasicExchangeToBrokerReply::OrderPartiallyExecuted(
+                                        order_partially_executed
+                                    ),
+                                    resting_direction,
+                                )
+                            },
                         )
                     } else {
                         Self::create_replay_reply(
@@ -1260,16 +4351,89 @@ BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
                     };
                     message_receiver.push(process_action(notification))
                 } else {
-                    panic!("Cannot find limit order with internal ID {order_id}")
+                    panic!(
+                        "Cannot find limit order with internal ID {internal_order_id} \
+                        of the {traded_pair:?}"
+                    )
                 }
             }
-            OrderBookEventKind::NewOrderPartiallyExecuted => {
+            OrderBookEventKind::OldIcebergOrderRefilled(internal_order_id) => {
+                // Other agents see the next peak as the new order at the end of the price queue
+                let notification = ExchangeEventNotification::OrderPlaced(
+                    LimitOrderEventInfo {
+                        traded_pair,
+                        order_id: internal_order_id,
+                        direction: resting_direction,
+                        price: event.price,
+                        size: event.size,
+                    }
+                );
+                let (_, from) = context.internal_to_submitted.get(&internal_order_id)
+                    .unwrap_or_else(
+                        || panic!(
+                            "Cannot find limit order with internal ID {internal_order_id} \
+                            of the {traded_pair:?}"
+                        )
+                    );
+                // Replay is not notified of the refills of its own orders
+                let replay_notification = from.map(
+                    |_| Self::create_replay_reply(
+                        BasicExchangeToReplayReply::ExchangeEventNotification(
+                            notification.clone()
+                        )
+                    )
+                );
+                let broker_notification_iterator = context.broker_to_order_id.keys().map(
+                    |broker_id| Self::create_broker_reply(
+                        current_dt,
+                        *broker_id,
+                        BasicExchangeToBrokerReply::ExchangeEventNotification(
+                            notification.clone()
+                        ),
+                    )
+                );
+                message_receiver.extend(
+                    replay_notification.into_iter()
+                        .chain(broker_notification_iterator)
+                        .map(process_action)
+                )
+            }
+            OrderBookEventKind::NewOrderPartiallyExecuted
+            | OrderBookEventKind::NewGhostOrderPartiallyExecuted => {
                 *remaining_size -= event.size;
+                let exec_id = context.fill_ids.next_exec_id();
+                // Shadow fills do not make the trade
+                let trade_id = if DUMMY {
+                    context.fill_ids.get_trade_id()
+                } else {
+                    context.fill_ids.complete_trade()
+                };
+                let mut fee = Fee(0);
+                if !DUMMY {
+                    context.trade_tape.record(
+                        current_dt, trade_id, aggressor_direction, event.price, event.size,
+                    );
+                    if let Some(volatility) = &mut context.volatility {
+                        volatility.record(current_dt, event.price)
+                    }
+                    context.num_trades += 1;
+                    if !REPLAY {
+                        record_fill(
+                            get_broker_id(), new_order_id, exec_id, trade_id, aggressor_direction,
+                        );
+                        fee = charge(get_broker_id(), new_order_id, Liquidity::Taker)
+                    }
+                }
                 let order_partially_executed = OrderPartiallyExecuted {
                     traded_pair,
                     order_id: new_order_id,
+                    broker_order_id: new_order_id,
+                    exchange_order_id: new_exchange_order_id,
+                    exec_id,
+                    trade_id,
                     price: event.price,
                     size: event.size,
+                    fee,
                 };
                 let reply = if REPLAY {
                     Self::create_replay_reply(
@@ -1277,23 +4441,40 @@ BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
                             order_partially_executed
                         )
                     )
+                } else if DUMMY {
+                    Self::create_broker_reply(
+                        current_dt,
+                        get_broker_id(),
+                        if is_ghost {
+                            BasicExchangeToBrokerReply::GhostOrderPartiallyExecuted(
+                                order_partially_executed
+                            )
+                        } else {
+                            BasicExchangeToBrokerReply::ShadowOrderPartiallyExecuted(
+                                order_partially_executed
+                            )
+                        },
+                    )
                 } else {
                     Self::create_broker_reply(
                         current_dt,
                         get_broker_id(),
-                        BasicExchangeToBrokerReply::OrderPartiallyExecuted(
-                            order_partially_executed
+                        with_legs(
+                            BasicExchangeToBrokerReply::OrderPartiallyExecuted(
+                                order_partially_executed
+                            ),
+                            aggressor_direction,
                         ),
                     )
                 };
                 if DUMMY {
                     message_receiver.push(process_action(reply))
                 } else if REPLAY {
-                    let broker_notification_iterator = broker_to_order_id.keys().map(
+                    let broker_notification_iterator = context.broker_to_order_id.keys().map(
                         |broker_id| Self::create_broker_reply(
                             current_dt,
                             *broker_id,
-                            create_broker_notification(),
+                            create_broker_notification(trade_id),
                         )
                     );
                     message_receiver.extend(
@@ -1303,14 +4484,14 @@ BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
                     )
                 } else {
                     let replay_notification = Self::create_replay_reply(
-                        create_replay_notification()
+                        create_replay_notification(trade_id)
                     );
-                    let broker_notification_iterator = broker_to_order_id.keys()
+                    let broker_notification_iterator = context.broker_to_order_id.keys()
                         .map(
                             |broker_id| Self::create_broker_reply(
                                 current_dt,
                                 *broker_id,
-                                create_broker_notification(),
+                                create_broker_notification(trade_id),
                             )
                         );
                     message_receiver.extend(
@@ -1321,33 +4502,77 @@ BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
                     )
                 }
             }
-            OrderBookEventKind::NewOrderExecuted => {
+            OrderBookEventKind::NewOrderExecuted
+            | OrderBookEventKind::NewGhostOrderExecuted => {
                 *remaining_size -= event.size;
+                // Bid crossed by the auction may have been resting till date
+                context.order_expirations.remove(&new_exchange_order_id);
+                let exec_id = context.fill_ids.next_exec_id();
+                // Shadow fills do not make the trade
+                let trade_id = if DUMMY {
+                    context.fill_ids.get_trade_id()
+                } else {
+                    context.fill_ids.complete_trade()
+                };
+                let mut fee = Fee(0);
+                if !DUMMY {
+                    context.trade_tape.record(
+                        current_dt, trade_id, aggressor_direction, event.price, event.size,
+                    );
+                    if let Some(volatility) = &mut context.volatility {
+                        volatility.record(current_dt, event.price)
+                    }
+                    context.num_trades += 1;
+                    if !REPLAY {
+                        record_fill(
+                            get_broker_id(), new_order_id, exec_id, trade_id, aggressor_direction,
+                        );
+                        fee = charge(get_broker_id(), new_order_id, Liquidity::Taker)
+                    }
+                }
                 let order_executed = OrderExecuted {
                     traded_pair,
                     order_id: new_order_id,
+                    broker_order_id: new_order_id,
+                    exchange_order_id: new_exchange_order_id,
+                    exec_id,
+                    trade_id,
                     price: event.price,
                     size: event.size,
+                    fee,
                 };
                 let reply = if REPLAY {
                     Self::create_replay_reply(
                         BasicExchangeToReplayReply::OrderExecuted(order_executed)
                     )
+                } else if DUMMY {
+                    Self::create_broker_reply(
+                        current_dt,
+                        get_broker_id(),
+                        if is_ghost {
+                            BasicExchangeToBrokerReply::GhostOrderExecuted(order_executed)
+                        } else {
+                            BasicExchangeToBrokerReply::ShadowOrderExecuted(order_executed)
+                        },
+                    )
                 } else {
                     Self::create_broker_reply(
                         current_dt,
                         get_broker_id(),
-                        BasicExchangeToBrokerReply::OrderExecuted(order_executed),
+                        with_legs(
+                            BasicExchangeToBrokerReply::OrderExecuted(order_executed),
+                            aggressor_direction,
+                        ),
                     )
                 };
                 if DUMMY {
                     message_receiver.push(process_action(reply))
                 } else if REPLAY {
-                    let broker_notification_iterator = broker_to_order_id.keys().map(
+                    let broker_notification_iterator = context.broker_to_order_id.keys().map(
                         |broker_id| Self::create_broker_reply(
                             current_dt,
                             *broker_id,
-                            create_broker_notification(),
+                            create_broker_notification(trade_id),
                         )
                     );
                     message_receiver.extend(
@@ -1357,14 +4582,14 @@ BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
                     )
                 } else {
                     let replay_notification = Self::create_replay_reply(
-                        create_replay_notification()
+                        create_replay_notification(trade_id)
                     );
-                    let broker_notification_iterator = broker_to_order_id.keys()
+                    let broker_notification_iterator = context.broker_to_order_id.keys()
                         .map(
                             |broker_id| Self::create_broker_reply(
                                 current_dt,
                                 *broker_id,
-                                create_broker_notification(),
+                                create_broker_notification(trade_id),
                             )
                         );
                     message_receiver.extend(
@@ -1520,5 +4745,5 @@ pub type BasicVoidExchange<ExchangeID, BrokerID, Symbol, Settlement> = VoidExcha
     BasicBrokerToExchange<ExchangeID, Symbol, Settlement>,
     BasicExchangeToReplay<Symbol, Settlement>,
     BasicExchangeToBroker<BrokerID, Symbol, Settlement>,
-    Nothing
+    BasicExchangeToItself<Symbol, Settlement>
 >;
\ No newline at end of file
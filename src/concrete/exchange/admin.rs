@@ -0,0 +1,101 @@
+use crate::{
+    concrete::{
+        traded_pair::{settlement::GetSettlementLag, TradedPair},
+        types::Tick,
+    },
+    types::{DateTime, Id},
+};
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Range of admissible limit order prices of the traded pair.
+pub struct PriceBand {
+    /// Minimum admissible price.
+    pub lower: Option<Tick>,
+    /// Maximum admissible price.
+    pub upper: Option<Tick>,
+}
+
+impl PriceBand {
+    /// Checks whether the price lies within the `PriceBand`.
+    ///
+    /// # Arguments
+    ///
+    /// * `price` — Price to check.
+    pub fn contains(&self, price: Tick) -> bool {
+        self.lower.is_none_or(|lower| price >= lower)
+            && self.upper.is_none_or(|upper| price <= upper)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Trading fees of the traded pair, in basis points of the trade notional.
+/// Negative fees are rebates.
+///
+/// The exchange only publishes the schedule. Fees are charged by the brokers
+/// or by the [`FeeModel`](crate::concrete::exchange::fees::FeeModel) of the exchange.
+pub struct FeeSchedule {
+    /// Fee for the liquidity provision.
+    pub maker_fee_bps: i64,
+    /// Fee for the liquidity removal.
+    pub taker_fee_bps: i64,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Administrative action changing the trading rules of the traded pair.
+///
+/// Is submitted by the replay at the datetime it should take effect
+/// and is validated by the exchange before being applied.
+/// Once applied, it is announced to the connected brokers.
+pub enum AdminAction<Symbol: Id, Settlement: GetSettlementLag> {
+    /// Changes the minimum price increment. Resting limit orders whose prices become off-grid
+    /// are handled according to the off-grid order policy of the exchange.
+    SetPriceIncrement {
+        /// Traded pair.
+        traded_pair: TradedPair<Symbol, Settlement>,
+        /// New minimum price increment in ticks.
+        increment: Tick,
+    },
+    /// Changes the price band.
+    /// Resting limit orders whose prices fall outside of it are cancelled.
+    SetPriceBand {
+        /// Traded pair.
+        traded_pair: TradedPair<Symbol, Settlement>,
+        /// New price band.
+        band: PriceBand,
+    },
+    /// Changes the fee schedule.
+    SetFeeSchedule {
+        /// Traded pair.
+        traded_pair: TradedPair<Symbol, Settlement>,
+        /// New fee schedule.
+        fees: FeeSchedule,
+    },
+    /// Busts the erroneous trades of the current session, i.e. cancels the reported fills
+    /// of the broker orders. Owners of the busted fills are notified by the `TradeBusted`
+    /// replies and are expected to unwind them. Busted trades are removed from the trade tape.
+    ///
+    /// Positions of the clearing house, if any, are not unwound.
+    BustTrades {
+        /// Traded pair.
+        traded_pair: TradedPair<Symbol, Settlement>,
+        /// Only the trades made since this datetime are busted.
+        since: DateTime,
+        /// If set, only the trades at the prices outside of this band are busted.
+        valid_prices: Option<PriceBand>,
+    },
+}
+
+impl<Symbol: Id, Settlement: GetSettlementLag> AdminAction<Symbol, Settlement> {
+    /// Returns the traded pair the `AdminAction` concerns.
+    pub fn get_traded_pair(&self) -> TradedPair<Symbol, Settlement> {
+        match self {
+            Self::SetPriceIncrement { traded_pair, .. } => *traded_pair,
+            Self::SetPriceBand { traded_pair, .. } => *traded_pair,
+            Self::SetFeeSchedule { traded_pair, .. } => *traded_pair,
+            Self::BustTrades { traded_pair, .. } => *traded_pair
+        }
+    }
+}
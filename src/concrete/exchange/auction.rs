@@ -0,0 +1,115 @@
+use crate::types::{DateTime, Duration};
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Phase of the trading session of the [`BasicExchange`](crate::concrete::exchange::BasicExchange).
+pub enum TradingPhase {
+    /// Orders can only be cancelled or reduced in size.
+    PreOpen,
+    /// Limit orders accumulate without matching
+    /// and are crossed at the equilibrium price once the phase ends.
+    OpeningAuction,
+    /// Orders are matched as they arrive.
+    #[default]
+    Continuous,
+    /// Same as the [`OpeningAuction`](Self::OpeningAuction), yet at the end of the session.
+    ClosingAuction,
+}
+
+impl TradingPhase {
+    /// Whether the limit orders accumulate for the auction rather than being matched.
+    /// Market, immediate-or-cancel and fill-or-kill orders are not accepted then.
+    pub fn is_auction_call(self) -> bool {
+        matches!(self, Self::OpeningAuction | Self::ClosingAuction)
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+/// Trading phases of the session along with the offsets from the opening of the exchange
+/// they begin at. Session begins in the continuous phase unless the first offset is zero.
+pub struct SessionSchedule {
+    phases: Vec<(Duration, TradingPhase)>,
+}
+
+impl SessionSchedule {
+    /// Creates a new instance of the `SessionSchedule`.
+    ///
+    /// # Arguments
+    ///
+    /// * `phases` — Phases along with their offsets.
+    ///   Offsets should be non-negative and strictly increasing.
+    pub fn new(phases: impl IntoIterator<Item=(Duration, TradingPhase)>) -> Self {
+        let phases: Vec<_> = phases.into_iter().collect();
+        if let Some((offset, _)) = phases.first() {
+            if *offset < Duration::zero() {
+                panic!("Trading phase offsets should be non-negative. Got {offset}")
+            }
+        }
+        if let Some(pair) = phases.windows(2).find(|pair| pair[0].0 >= pair[1].0) {
+            panic!(
+                "Trading phase offsets should be strictly increasing. Got {} followed by {}",
+                pair[0].0,
+                pair[1].0
+            )
+        }
+        Self { phases }
+    }
+}
+
+/// Position of the exchange within its [`SessionSchedule`].
+///
+/// Transitions are polled whenever the exchange processes a message,
+/// including the wakeups it schedules for them at the opening,
+/// so each of them takes effect at its scheduled datetime.
+pub(crate) struct SessionCalendar {
+    schedule: SessionSchedule,
+    /// Opening datetime of the current session and the index of the next phase to begin.
+    /// `None` while the exchange is closed.
+    session: Option<(DateTime, usize)>,
+}
+
+impl SessionCalendar {
+    pub fn new(schedule: SessionSchedule) -> Self {
+        Self { schedule, session: None }
+    }
+
+    pub fn start_session(&mut self, open_dt: DateTime) {
+        self.session = Some((open_dt, 0))
+    }
+
+    pub fn end_session(&mut self) {
+        self.session = None
+    }
+
+    /// Returns the phases that have begun by the `current_dt` since the last poll,
+    /// in the order of the schedule.
+    pub fn poll(&mut self, current_dt: DateTime) -> Vec<TradingPhase> {
+        let (open_dt, next_phase) = if let Some(session) = &mut self.session {
+            session
+        } else {
+            return Vec::new();
+        };
+        let elapsed = current_dt - *open_dt;
+        let begun: Vec<_> = self.schedule.phases[*next_phase..].iter()
+            .take_while(|(offset, _)| *offset <= elapsed)
+            .map(|(_, phase)| *phase)
+            .collect();
+        *next_phase += begun.len();
+        begun
+    }
+
+    /// Returns the phases yet to begin along with the delays till their beginning.
+    pub fn get_upcoming_phases(
+        &self,
+        current_dt: DateTime) -> impl Iterator<Item=(Duration, TradingPhase)> + '_
+    {
+        let (open_dt, next_phase) = if let Some(session) = self.session {
+            session
+        } else {
+            (current_dt, self.schedule.phases.len())
+        };
+        let elapsed = current_dt - open_dt;
+        self.schedule.phases[next_phase..].iter()
+            .map(move |(offset, phase)| (*offset - elapsed, *phase))
+    }
+}
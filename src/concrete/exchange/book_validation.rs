@@ -0,0 +1,327 @@
+use {
+    crate::{
+        concrete::{
+            order_book::OrderBook,
+            types::{Direction, Lots, Tick, TickSize},
+        },
+        types::DateTime,
+    },
+    std::{collections::VecDeque, fs::File, io::Write, path::Path},
+};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+/// Reference order book snapshot supplied by the user, e.g. taken from the data vendor.
+pub struct ReferenceSnapshot {
+    /// Datetime of the snapshot. The snapshot is considered
+    /// to include all the events that happened at this datetime.
+    pub datetime: DateTime,
+    /// Aggregated bid levels, the best one first.
+    pub bids: Vec<(Tick, Lots)>,
+    /// Aggregated ask levels, the best one first.
+    pub asks: Vec<(Tick, Lots)>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+/// Mismatch between the reconstructed order book level and the reference one.
+pub struct BookDivergence {
+    /// Datetime of the reference snapshot.
+    pub datetime: DateTime,
+    /// Order book side.
+    pub direction: Direction,
+    /// Zero-based index of the level counting from the best price.
+    pub level: usize,
+    /// Reference level. `None` if the reference side is empty
+    /// while the reconstructed one is not.
+    pub expected: Option<(Tick, Lots)>,
+    /// Reconstructed level.
+    pub actual: Option<(Tick, Lots)>,
+}
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+/// Summary of the order book reconstruction quality.
+pub struct BookValidationReport {
+    /// Number of reference snapshots compared with the reconstructed order book.
+    pub num_checked: usize,
+    /// Number of reference snapshots that fully matched the reconstructed order book.
+    pub num_matched: usize,
+    /// Total number of diverged levels.
+    pub num_divergent_levels: usize,
+}
+
+/// Compares the reconstructed [`OrderBook`] of the traded pair
+/// with the reference snapshots as the simulation proceeds.
+pub struct BookValidator {
+    references: VecDeque<ReferenceSnapshot>,
+    price_step: TickSize,
+    divergence_log: Option<File>,
+    report: BookValidationReport,
+}
+
+impl BookValidator {
+    /// Creates a new instance of the `BookValidator`.
+    ///
+    /// # Arguments
+    ///
+    /// * `references` — Reference snapshots. Need not be sorted.
+    /// * `price_step` — Price quotation step. Used for logging.
+    pub fn new(
+        references: impl IntoIterator<Item=ReferenceSnapshot>,
+        price_step: impl Into<TickSize>) -> Self
+    {
+        let mut references: Vec<_> = references.into_iter().collect();
+        references.sort_by_key(|snapshot| snapshot.datetime);
+        Self {
+            references: references.into(),
+            price_step: price_step.into(),
+            divergence_log: None,
+            report: Default::default(),
+        }
+    }
+
+    /// Writes every diverged level to the csv-file.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` — Path to the csv-file to create.
+    pub fn with_divergence_log(mut self, file: impl AsRef<Path>) -> Self {
+        let file = file.as_ref();
+        let log = File::create(file).unwrap_or_else(
+            |err| panic!("Cannot create file {file:?}. Error: {err}")
+        );
+        writeln!(
+            &log,
+            "Timestamp,SIDE,LEVEL,EXPECTED_PRICE,EXPECTED_SIZE,ACTUAL_PRICE,ACTUAL_SIZE"
+        ).unwrap_or_else(|err| panic!("Cannot write to file {file:?}. Error: {err}"));
+        self.divergence_log = Some(log);
+        self
+    }
+
+    /// Returns the summary of the comparisons made so far.
+    pub fn get_report(&self) -> BookValidationReport {
+        self.report
+    }
+
+    /// Returns the number of reference snapshots that have not been compared yet.
+    pub fn num_remaining(&self) -> usize {
+        self.references.len()
+    }
+
+    /// Compares the order book with all the due reference snapshots.
+    /// Returns all found divergences.
+    ///
+    /// # Parameters
+    /// * `INCLUSIVE` — Whether no more events can happen at the `current_dt`.
+    ///
+    /// # Arguments
+    ///
+    /// * `current_dt` — Current datetime.
+    /// * `order_book` — Reconstructed order book. `None` is considered to be an empty one.
+    pub fn validate<const INCLUSIVE: bool>(
+        &mut self,
+        current_dt: DateTime,
+        order_book: Option<&OrderBook>) -> Vec<BookDivergence>
+    {
+        let mut divergences = Vec::new();
+        while let Some(snapshot) = self.references.front() {
+            if snapshot.datetime > current_dt || !INCLUSIVE && snapshot.datetime == current_dt {
+                break;
+            }
+            let num_divergences_before = divergences.len();
+            let (bids, asks) = if let Some(order_book) = order_book {
+                (
+                    Self::aggregate_side::<false>(order_book, snapshot.bids.len().max(1)),
+                    Self::aggregate_side::<true>(order_book, snapshot.asks.len().max(1))
+                )
+            } else {
+                Default::default()
+            };
+            for (direction, expected, actual) in [
+                (Direction::Buy, &snapshot.bids, bids),
+                (Direction::Sell, &snapshot.asks, asks)
+            ] {
+                if expected.is_empty() {
+                    if let Some(actual) = actual.first() {
+                        divergences.push(
+                            BookDivergence {
+                                datetime: snapshot.datetime,
+                                direction,
+                                level: 0,
+                                expected: None,
+                                actual: Some(*actual),
+                            }
+                        )
+                    }
+                }
+                for (level, expected) in expected.iter().enumerate() {
+                    let actual = actual.get(level).copied();
+                    if actual != Some(*expected) {
+                        divergences.push(
+                            BookDivergence {
+                                datetime: snapshot.datetime,
+                                direction,
+                                level,
+                                expected: Some(*expected),
+                                actual,
+                            }
+                        )
+                    }
+                }
+            }
+            let num_divergent_levels = divergences.len() - num_divergences_before;
+            self.report.num_checked += 1;
+            self.report.num_divergent_levels += num_divergent_levels;
+            if num_divergent_levels == 0 {
+                self.report.num_matched += 1
+            }
+            self.references.pop_front();
+        }
+        if let Some(log) = &mut self.divergence_log {
+            let price_step = self.price_step;
+            let format_level = |level: Option<(Tick, Lots)>| match level {
+                Some((price, size)) => format!("{:.4},{size}", price.to_f64(price_step)),
+                None => ",".into()
+            };
+            for divergence in &divergences {
+                writeln!(
+                    log,
+                    "{},{},{},{},{}",
+                    divergence.datetime,
+                    divergence.direction,
+                    divergence.level,
+                    format_level(divergence.expected),
+                    format_level(divergence.actual),
+                ).unwrap_or_else(|err| panic!("Cannot write to file {log:?}. Error: {err}"))
+            }
+        }
+        divergences
+    }
+
+    fn aggregate_side<const UPPER: bool>(
+        order_book: &OrderBook,
+        max_levels: usize) -> Vec<(Tick, Lots)>
+    {
+        order_book.get_ob_side_iter::<UPPER>()
+            .take(max_levels)
+            .map(|(price, level)| (price, level.map(|(_, size, _)| size).sum()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{concrete::types::OrderID, test_utils::dt},
+    };
+
+    fn order_book(bids: &[(i64, i64)], asks: &[(i64, i64)]) -> OrderBook {
+        let mut order_book = OrderBook::new();
+        let mut id = 0;
+        for &(price, size) in bids {
+            id += 1;
+            order_book.insert_limit_order_without_matching::<false, true>(
+                dt(0), OrderID(id), Tick(price), Lots(size),
+            )
+        }
+        for &(price, size) in asks {
+            id += 1;
+            order_book.insert_limit_order_without_matching::<false, false>(
+                dt(0), OrderID(id), Tick(price), Lots(size),
+            )
+        }
+        order_book
+    }
+
+    fn reference(second: u32, bids: &[(i64, i64)], asks: &[(i64, i64)]) -> ReferenceSnapshot {
+        let levels = |side: &[(i64, i64)]| side.iter()
+            .map(|&(price, size)| (Tick(price), Lots(size)))
+            .collect();
+        ReferenceSnapshot { datetime: dt(second), bids: levels(bids), asks: levels(asks) }
+    }
+
+    #[test]
+    fn test_matched() {
+        let mut validator = BookValidator::new(
+            [reference(1, &[(99, 5)], &[(101, 3), (102, 1)])],
+            TickSize(0.01),
+        );
+        let order_book = order_book(&[(99, 2), (99, 3), (98, 1)], &[(101, 3), (102, 1)]);
+
+        // Snapshot is not due yet
+        assert_eq!(validator.validate::<false>(dt(1), Some(&order_book)), []);
+        assert_eq!(validator.num_remaining(), 1);
+
+        // Levels beyond the reference depth are not compared
+        assert_eq!(validator.validate::<true>(dt(1), Some(&order_book)), []);
+        assert_eq!(
+            validator.get_report(),
+            BookValidationReport { num_checked: 1, num_matched: 1, num_divergent_levels: 0 }
+        )
+    }
+
+    #[test]
+    fn test_missing_level() {
+        let mut validator = BookValidator::new(
+            [reference(1, &[(99, 5), (98, 1)], &[(101, 3)])],
+            TickSize(0.01),
+        );
+        let order_book = order_book(&[(99, 5)], &[]);
+        assert_eq!(
+            validator.validate::<true>(dt(2), Some(&order_book)),
+            [
+                BookDivergence {
+                    datetime: dt(1),
+                    direction: Direction::Buy,
+                    level: 1,
+                    expected: Some((Tick(98), Lots(1))),
+                    actual: None,
+                },
+                BookDivergence {
+                    datetime: dt(1),
+                    direction: Direction::Sell,
+                    level: 0,
+                    expected: Some((Tick(101), Lots(3))),
+                    actual: None,
+                },
+            ]
+        );
+        assert_eq!(
+            validator.get_report(),
+            BookValidationReport { num_checked: 1, num_matched: 0, num_divergent_levels: 2 }
+        )
+    }
+
+    #[test]
+    fn test_extra_level() {
+        let mut validator = BookValidator::new(
+            [reference(2, &[(99, 5)], &[]), reference(1, &[(99, 5), (97, 1)], &[(101, 3)])],
+            TickSize(0.01),
+        );
+        let order_book = order_book(&[(99, 5), (98, 2), (97, 1)], &[(101, 3)]);
+        assert_eq!(
+            validator.validate::<true>(dt(2), Some(&order_book)),
+            [
+                BookDivergence {
+                    datetime: dt(1),
+                    direction: Direction::Buy,
+                    level: 1,
+                    expected: Some((Tick(97), Lots(1))),
+                    actual: Some((Tick(98), Lots(2))),
+                },
+                // Reconstructed side is not empty while the reference one is
+                BookDivergence {
+                    datetime: dt(2),
+                    direction: Direction::Sell,
+                    level: 0,
+                    expected: None,
+                    actual: Some((Tick(101), Lots(3))),
+                },
+            ]
+        );
+        assert_eq!(
+            validator.get_report(),
+            BookValidationReport { num_checked: 2, num_matched: 0, num_divergent_levels: 2 }
+        );
+        assert_eq!(validator.validate::<true>(dt(3), None), [])
+    }
+}
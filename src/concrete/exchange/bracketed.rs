@@ -0,0 +1,452 @@
+use {
+    crate::{
+        interface::{
+            exchange::{Exchange, ExchangeAction, ExchangeActionKind},
+            message::ExchangeToItself,
+        },
+        types::{Agent, DateTime, Named, TimeSync},
+        utils::{
+            collections::HashMap,
+            queue::{LessElementBinaryHeap, MessageReceiver, ScheduleHandle},
+        },
+    },
+    rand::{rngs::StdRng, Rng, SeedableRng},
+};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+/// Wakeup of the single replica of the [`BracketedExchange`].
+pub struct VariantWakeUp<E2E: ExchangeToItself> {
+    /// Index of the variant replica. `None` stands for the reference replica.
+    pub variant: Option<usize>,
+    /// Wakeup of the replica.
+    pub wakeup: E2E,
+}
+
+impl<E2E: ExchangeToItself> ExchangeToItself for VariantWakeUp<E2E> {
+    fn get_schedule_handle(&self) -> Option<ScheduleHandle> {
+        self.wakeup.get_schedule_handle()
+    }
+}
+
+/// [`Exchange`] that runs several variants of the same strategy against one market replay
+/// without letting them impact each other.
+///
+/// Every variant trades at its own replica of the exchange, and every replica
+/// receives all the replay requests, so each variant sees the same fills as if it were alone.
+/// Thus, the parameters of the strategy can be compared under identical market conditions
+/// without running a separate replay for each of them.
+///
+/// The replay is answered only by the reference replica, to which no broker is connected:
+/// the replies of the variant replicas to the replay are dropped,
+/// so the replay is driven by the market unaffected by any variant.
+///
+/// Each replica draws from its own random number generator seeded with the same seed
+/// instead of the [`Kernel`](crate::kernel::Kernel) one, so the randomness consumed
+/// by one variant does not change what the others see.
+/// Replicas should not share any other state either, e.g. a clearing house.
+pub struct BracketedExchange<E: Exchange> {
+    current_dt: DateTime,
+    reference: (E, StdRng),
+    variants: Vec<(E, StdRng)>,
+    broker_variants: HashMap<E::BrokerID, usize>,
+}
+
+impl<E: Exchange> BracketedExchange<E>
+    where E::R2E: Clone
+{
+    /// Creates a new instance of the `BracketedExchange`.
+    ///
+    /// # Arguments
+    ///
+    /// * `reference` — Replica answering the replay.
+    /// * `variants` — Replicas the variants trade at. Should be named as the `reference`.
+    /// * `seed` — Seed of the random number generators of the replicas.
+    pub fn new(mut reference: E, variants: impl IntoIterator<Item=E>, seed: u64) -> Self {
+        let name = reference.get_name();
+        let variants: Vec<_> = variants.into_iter()
+            .map(|variant| (variant, StdRng::seed_from_u64(seed)))
+            .collect();
+        if let Some(variant) = variants.iter().position(|(variant, _)| variant.get_name() != name) {
+            panic!(
+                "Variant {variant} replica is named {} while the reference one is named {name}",
+                variants[variant].0.get_name()
+            )
+        }
+        Self {
+            current_dt: *reference.current_datetime_mut(),
+            reference: (reference, StdRng::seed_from_u64(seed)),
+            variants,
+            broker_variants: Default::default(),
+        }
+    }
+
+    /// Assigns the broker to the replica of the variant.
+    /// Should be called before the broker is connected to the `BracketedExchange`.
+    ///
+    /// # Arguments
+    ///
+    /// * `broker_id` — Broker of the variant trader.
+    /// * `variant` — Index of the variant replica.
+    pub fn with_broker_variant(mut self, broker_id: E::BrokerID, variant: usize) -> Self {
+        if variant >= self.variants.len() {
+            panic!("There are only {} variant replicas. Got: {variant}", self.variants.len())
+        }
+        self.broker_variants.insert(broker_id, variant);
+        self
+    }
+
+    /// Returns the reference replica.
+    pub fn get_reference(&self) -> &E {
+        &self.reference.0
+    }
+
+    /// Returns the replica of the variant.
+    ///
+    /// # Arguments
+    ///
+    /// * `variant` — Index of the variant replica.
+    pub fn get_variant(&self, variant: usize) -> &E {
+        &self.variants[variant].0
+    }
+
+    /// Returns the number of the variant replicas.
+    pub fn num_variants(&self) -> usize {
+        self.variants.len()
+    }
+
+    /// Returns the replica along with its random number generator.
+    fn get_replica(&mut self, variant: Option<usize>) -> (&mut E, &mut StdRng) {
+        let (replica, rng) = match variant {
+            Some(variant) => self.variants.get_mut(variant).unwrap_or_else(
+                || panic!("Unknown variant replica: {variant}")
+            ),
+            None => &mut self.reference
+        };
+        *replica.current_datetime_mut() = self.current_dt;
+        (replica, rng)
+    }
+
+    fn dispatch<KerMsg: Ord, RNG: Rng>(
+        &mut self,
+        variant: Option<usize>,
+        message_receiver: &mut MessageReceiver<KerMsg>,
+        process_action: &mut impl FnMut(<Self as Agent>::Action, &mut RNG) -> KerMsg,
+        rng: &mut RNG,
+        call: impl FnOnce(&mut E, MessageReceiver<E::Action>, &mut StdRng))
+    {
+        let mut replica_actions = LessElementBinaryHeap(Default::default());
+        let (replica, replica_rng) = self.get_replica(variant);
+        call(replica, MessageReceiver::new(&mut replica_actions), replica_rng);
+        while let Some(action) = replica_actions.pop() {
+            let content = match action.content {
+                ExchangeActionKind::ExchangeToItself(wakeup) => {
+                    ExchangeActionKind::ExchangeToItself(VariantWakeUp { variant, wakeup })
+                }
+                ExchangeActionKind::ExchangeToReplay(reply) => {
+                    // Replay is answered only by the reference replica
+                    if variant.is_some() {
+                        continue;
+                    }
+                    ExchangeActionKind::ExchangeToReplay(reply)
+                }
+                ExchangeActionKind::ExchangeToBroker(reply) => {
+                    ExchangeActionKind::ExchangeToBroker(reply)
+                }
+            };
+            let action = ExchangeAction { delay: action.delay, content };
+            message_receiver.push(process_action(action, rng))
+        }
+    }
+}
+
+impl<E: Exchange> TimeSync for BracketedExchange<E> {
+    fn current_datetime_mut(&mut self) -> &mut DateTime {
+        &mut self.current_dt
+    }
+}
+
+impl<E: Exchange> Named<E::ExchangeID> for BracketedExchange<E> {
+    fn get_name(&self) -> E::ExchangeID {
+        self.reference.0.get_name()
+    }
+}
+
+impl<E: Exchange> Agent for BracketedExchange<E> {
+    type Action = ExchangeAction<E::E2R, E::E2B, VariantWakeUp<E::E2E>>;
+}
+
+impl<E: Exchange> Exchange for BracketedExchange<E>
+    where E::R2E: Clone
+{
+    type ExchangeID = E::ExchangeID;
+    type BrokerID = E::BrokerID;
+
+    type R2E = E::R2E;
+    type B2E = E::B2E;
+    type E2R = E::E2R;
+    type E2B = E::E2B;
+    type E2E = VariantWakeUp<E::E2E>;
+
+    fn wakeup<KerMsg: Ord, RNG: Rng>(
+        &mut self,
+        mut message_receiver: MessageReceiver<KerMsg>,
+        mut process_action: impl FnMut(Self::Action, &mut RNG) -> KerMsg,
+        scheduled_action: Self::E2E,
+        rng: &mut RNG,
+    ) {
+        let VariantWakeUp { variant, wakeup } = scheduled_action;
+        self.dispatch(
+            variant,
+            &mut message_receiver,
+            &mut process_action,
+            rng,
+            |replica, receiver, rng| replica.wakeup(receiver, |action, _| action, wakeup, rng),
+        )
+    }
+
+    fn process_broker_request<KerMsg: Ord, RNG: Rng>(
+        &mut self,
+        mut message_receiver: MessageReceiver<KerMsg>,
+        mut process_action: impl FnMut(Self::Action, &mut RNG) -> KerMsg,
+        request: Self::B2E,
+        broker_id: Self::BrokerID,
+        rng: &mut RNG,
+    ) {
+        let variant = *self.broker_variants.get(&broker_id).unwrap_or_else(
+            || panic!("Broker {broker_id} is not assigned to any variant replica")
+        );
+        self.dispatch(
+            Some(variant),
+            &mut message_receiver,
+            &mut process_action,
+            rng,
+            |replica, receiver, rng| replica.process_broker_request(
+                receiver, |action, _| action, request, broker_id, rng,
+            ),
+        )
+    }
+
+    fn process_replay_request<KerMsg: Ord, RNG: Rng>(
+        &mut self,
+        mut message_receiver: MessageReceiver<KerMsg>,
+        mut process_action: impl FnMut(Self::Action, &mut RNG) -> KerMsg,
+        request: Self::R2E,
+        rng: &mut RNG,
+    ) {
+        for variant in (0..self.variants.len()).map(Some).chain([None]) {
+            let request = request.clone();
+            self.dispatch(
+                variant,
+                &mut message_receiver,
+                &mut process_action,
+                rng,
+                |replica, receiver, rng| replica.process_replay_request(
+                    receiver, |action, _| action, request, rng,
+                ),
+            )
+        }
+    }
+
+    fn connect_broker(&mut self, broker_id: Self::BrokerID) {
+        let variant = *self.broker_variants.get(&broker_id).unwrap_or_else(
+            || panic!("Broker {broker_id} is not assigned to any variant replica")
+        );
+        self.variants[variant].0.connect_broker(broker_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            interface::message::{
+                BrokerToExchange,
+                ExchangeToBroker,
+                ExchangeToReplay,
+                ReplayToExchange,
+            },
+            test_utils::dt,
+            types::Nothing,
+        },
+        super::*,
+    };
+
+    #[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+    struct Roll;
+
+    impl ReplayToExchange for Roll {
+        type ExchangeID = &'static str;
+
+        fn get_exchange_id(&self) -> &'static str {
+            "MOEX"
+        }
+    }
+
+    impl BrokerToExchange for Roll {
+        type ExchangeID = &'static str;
+
+        fn get_exchange_id(&self) -> &'static str {
+            "MOEX"
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+    struct Rolled {
+        broker_id: Option<u8>,
+        value: u64,
+    }
+
+    impl ExchangeToReplay for Rolled {}
+
+    impl ExchangeToBroker for Rolled {
+        type BrokerID = u8;
+
+        fn get_broker_id(&self) -> u8 {
+            self.broker_id.unwrap()
+        }
+    }
+
+    /// Exchange that rolls a die for the replay and its brokers upon each request.
+    struct DiceExchange {
+        current_dt: DateTime,
+        brokers: Vec<u8>,
+    }
+
+    impl TimeSync for DiceExchange {
+        fn current_datetime_mut(&mut self) -> &mut DateTime {
+            &mut self.current_dt
+        }
+    }
+
+    impl Named<&'static str> for DiceExchange {
+        fn get_name(&self) -> &'static str {
+            "MOEX"
+        }
+    }
+
+    impl Agent for DiceExchange {
+        type Action = ExchangeAction<Rolled, Rolled, Nothing>;
+    }
+
+    impl Exchange for DiceExchange {
+        type ExchangeID = &'static str;
+        type BrokerID = u8;
+
+        type R2E = Roll;
+        type B2E = Roll;
+        type E2R = Rolled;
+        type E2B = Rolled;
+        type E2E = Nothing;
+
+        fn wakeup<KerMsg: Ord, RNG: Rng>(
+            &mut self,
+            _: MessageReceiver<KerMsg>,
+            _: impl FnMut(Self::Action, &mut RNG) -> KerMsg,
+            _: Self::E2E,
+            _: &mut RNG,
+        ) {}
+
+        fn process_broker_request<KerMsg: Ord, RNG: Rng>(
+            &mut self,
+            mut message_receiver: MessageReceiver<KerMsg>,
+            mut process_action: impl FnMut(Self::Action, &mut RNG) -> KerMsg,
+            _: Self::B2E,
+            broker_id: Self::BrokerID,
+            rng: &mut RNG,
+        ) {
+            let reply = Rolled { broker_id: Some(broker_id), value: rng.gen() };
+            let action = ExchangeAction {
+                delay: Default::default(),
+                content: ExchangeActionKind::ExchangeToBroker(reply),
+            };
+            message_receiver.push(process_action(action, rng))
+        }
+
+        fn process_replay_request<KerMsg: Ord, RNG: Rng>(
+            &mut self,
+            mut message_receiver: MessageReceiver<KerMsg>,
+            mut process_action: impl FnMut(Self::Action, &mut RNG) -> KerMsg,
+            _: Self::R2E,
+            rng: &mut RNG,
+        ) {
+            let value = rng.gen();
+            let replies = self.brokers.iter()
+                .map(|broker_id| ExchangeActionKind::ExchangeToBroker(
+                    Rolled { broker_id: Some(*broker_id), value }
+                ))
+                .chain([ExchangeActionKind::ExchangeToReplay(Rolled { broker_id: None, value })]);
+            for content in replies {
+                let action = ExchangeAction { delay: Default::default(), content };
+                message_receiver.push(process_action(action, rng))
+            }
+        }
+
+        fn connect_broker(&mut self, broker_id: Self::BrokerID) {
+            self.brokers.push(broker_id)
+        }
+    }
+
+    type Action = <BracketedExchange<DiceExchange> as Agent>::Action;
+
+    fn bracketed(seed: u64) -> BracketedExchange<DiceExchange> {
+        let current_dt = dt(0);
+        let dice = || DiceExchange { current_dt, brokers: vec![] };
+        let mut exchange = BracketedExchange::new(dice(), [dice(), dice()], seed)
+            .with_broker_variant(1, 0)
+            .with_broker_variant(2, 1);
+        exchange.connect_broker(1);
+        exchange.connect_broker(2);
+        exchange
+    }
+
+    /// Returns the rolls of the replay and of the brokers 1 and 2.
+    fn roll(exchange: &mut BracketedExchange<DiceExchange>) -> (Vec<u64>, Vec<(u8, u64)>) {
+        let mut queue = LessElementBinaryHeap(Default::default());
+        exchange.process_replay_request(
+            MessageReceiver::new(&mut queue),
+            |action, _| action,
+            Roll,
+            &mut StdRng::seed_from_u64(42),
+        );
+        let (mut replay, mut brokers) = (vec![], vec![]);
+        while let Some(Action { content, .. }) = queue.pop() {
+            match content {
+                ExchangeActionKind::ExchangeToReplay(rolled) => replay.push(rolled.value),
+                ExchangeActionKind::ExchangeToBroker(rolled) => {
+                    brokers.push((rolled.get_broker_id(), rolled.value))
+                }
+                ExchangeActionKind::ExchangeToItself(_) => unreachable!()
+            }
+        }
+        brokers.sort_unstable();
+        (replay, brokers)
+    }
+
+    #[test]
+    fn test_replay_is_answered_by_reference() {
+        let mut exchange = bracketed(0);
+        let (replay, brokers) = roll(&mut exchange);
+        let [value] = replay[..] else { panic!("Unexpected replay replies: {replay:?}") };
+        // Replicas draw the same numbers regardless of the kernel random number generator
+        assert_eq!(brokers, [(1, value), (2, value)]);
+        assert_eq!(roll(&mut bracketed(0)).0, [value]);
+        assert_ne!(roll(&mut bracketed(1)).0, [value]);
+    }
+
+    #[test]
+    fn test_replicas_do_not_share_rng() {
+        let mut exchange = bracketed(0);
+        let mut queue = LessElementBinaryHeap(Default::default());
+        exchange.process_broker_request(
+            MessageReceiver::new(&mut queue),
+            |action, _| action,
+            Roll,
+            1,
+            &mut StdRng::seed_from_u64(42),
+        );
+        let (replay, brokers) = roll(&mut exchange);
+        let [value] = replay[..] else { panic!("Unexpected replay replies: {replay:?}") };
+        // Draw of the first variant does not affect the second one and the reference
+        assert!(matches!(brokers[..], [(1, first), (2, second)] if first != value && second == value))
+    }
+}
@@ -0,0 +1,145 @@
+use {
+    crate::{
+        concrete::types::{Direction, Lots, Tick, TickSize, TradeID},
+        types::{DateTime, Duration},
+    },
+    std::collections::VecDeque,
+};
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Hash)]
+/// Method of determining the official closing price of the traded pair.
+/// The closing price is also used as the settlement price.
+pub enum ClosingPriceMethod {
+    /// Price of the last trade of the session.
+    #[default]
+    LastTrade,
+    /// Volume-weighted average price of the trades made within the given window
+    /// before the close, rounded to the nearest tick.
+    /// Falls back to the last trade price if there were no trades within the window.
+    Vwap(Duration),
+}
+
+/// Trades of the single traded pair made during the session
+/// that may still affect its closing price or are recent enough to be replayed
+/// to the late subscribers.
+pub(crate) struct TradeTape {
+    price_step: TickSize,
+    window: Duration,
+    /// Trades within the window before the last one, including the last trade itself.
+    trades: VecDeque<(DateTime, Tick, Lots)>,
+    num_recent_trades: usize,
+    /// Last `num_recent_trades` trades, oldest first.
+    recent_trades: VecDeque<(DateTime, TradeID, Direction, Tick, Lots)>,
+    /// Lowest and highest trade prices since the last
+    /// [`take_traded_range`](Self::take_traded_range).
+    traded_range: Option<(Tick, Tick)>,
+}
+
+impl TradeTape {
+    pub fn new(method: ClosingPriceMethod, price_step: TickSize, num_recent_trades: usize) -> Self {
+        let window = match method {
+            ClosingPriceMethod::LastTrade => Duration::zero(),
+            ClosingPriceMethod::Vwap(window) => window
+        };
+        Self {
+            price_step,
+            window,
+            trades: Default::default(),
+            num_recent_trades,
+            recent_trades: Default::default(),
+            traded_range: None,
+        }
+    }
+
+    pub fn get_price_step(&self) -> TickSize {
+        self.price_step
+    }
+
+    pub fn record(
+        &mut self,
+        datetime: DateTime,
+        trade_id: TradeID,
+        direction: Direction,
+        price: Tick,
+        size: Lots)
+    {
+        self.trades.push_back((datetime, price, size));
+        if self.num_recent_trades != 0 {
+            if self.recent_trades.len() == self.num_recent_trades {
+                self.recent_trades.pop_front();
+            }
+            self.recent_trades.push_back((datetime, trade_id, direction, price, size))
+        }
+        self.traded_range = Some(
+            self.traded_range.map_or(
+                (price, price),
+                |(low, high)| (low.min(price), high.max(price)),
+            )
+        );
+        let horizon = datetime - self.window;
+        while self.trades.front().is_some_and(|(trade_dt, _, _)| *trade_dt < horizon) {
+            self.trades.pop_front();
+        }
+    }
+
+    /// Returns the price of the last trade or `None` if there has been none.
+    pub fn get_last_price(&self) -> Option<Tick> {
+        self.trades.back().map(|(_, price, _)| *price)
+    }
+
+    /// Returns the most recent trades, oldest first.
+    /// Each trade is described by its datetime, ID, aggressor direction, price and size.
+    pub fn iter_recent_trades(
+        &self) -> impl Iterator<Item=&(DateTime, TradeID, Direction, Tick, Lots)> + '_
+    {
+        self.recent_trades.iter()
+    }
+
+    /// Returns the lowest and the highest trade prices since the previous call
+    /// or `None` if there have been no trades since then.
+    pub fn take_traded_range(&mut self) -> Option<(Tick, Tick)> {
+        self.traded_range.take()
+    }
+
+    /// Removes the trades not satisfying the predicate, e.g. the busted ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `predicate` — Predicate taking the datetime and the price of the trade.
+    pub fn retain(&mut self, mut predicate: impl FnMut(DateTime, Tick) -> bool) {
+        self.trades.retain(|(trade_dt, price, _)| predicate(*trade_dt, *price));
+        self.recent_trades.retain(|(trade_dt, _, _, price, _)| predicate(*trade_dt, *price))
+    }
+
+    /// Returns the closing price or `None` if there were no trades during the session.
+    ///
+    /// # Arguments
+    ///
+    /// * `close_dt` — Datetime of the close.
+    /// * `method` — Method of determining the closing price.
+    pub fn get_closing_price(
+        &self,
+        close_dt: DateTime,
+        method: ClosingPriceMethod) -> Option<Tick>
+    {
+        let (_, last_price, _) = self.trades.back()?;
+        let window = match method {
+            ClosingPriceMethod::LastTrade => return Some(*last_price),
+            ClosingPriceMethod::Vwap(window) => window
+        };
+        let horizon = close_dt - window;
+        let (notional, volume) = self.trades.iter()
+            .filter(|(trade_dt, _, _)| *trade_dt >= horizon)
+            .fold(
+                (0, 0),
+                |(notional, volume), (_, price, size)| {
+                    (notional + price.0 as i128 * size.0 as i128, volume + size.0 as i128)
+                },
+            );
+        if volume == 0 {
+            Some(*last_price)
+        } else {
+            Some(Tick((notional as f64 / volume as f64).round() as i64))
+        }
+    }
+}
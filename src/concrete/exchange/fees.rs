@@ -0,0 +1,287 @@
+use crate::{
+    concrete::{
+        traded_pair::{settlement::GetSettlementLag, TradedPair},
+        types::{Fee, Lots, OrderID, Tick},
+    },
+    types::{DateTime, Id},
+    utils::collections::{HashMap, HashSet},
+};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+/// Whether the filled order has provided or removed the liquidity.
+pub enum Liquidity {
+    /// Resting order.
+    Maker,
+    /// Aggressor order.
+    Taker,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+/// Fill of the broker order the fee is charged for.
+pub struct ChargeableFill<BrokerID: Id, Symbol: Id, Settlement: GetSettlementLag> {
+    /// Datetime of the fill.
+    pub datetime: DateTime,
+    /// Owner of the order.
+    pub broker_id: BrokerID,
+    /// Traded pair.
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    /// Submitted ID of the order.
+    pub order_id: OrderID,
+    /// Whether the order has provided or removed the liquidity.
+    pub liquidity: Liquidity,
+    /// Fill price.
+    pub price: Tick,
+    /// Fill size.
+    pub size: Lots,
+}
+
+impl<BrokerID: Id, Symbol: Id, Settlement: GetSettlementLag>
+ChargeableFill<BrokerID, Symbol, Settlement>
+{
+    /// Returns the notional of the fill, in ticks times lots.
+    pub fn get_notional(&self) -> i64 {
+        self.price.0 * self.size.0
+    }
+}
+
+/// Trading fees the [`BasicExchange`](crate::concrete::exchange::BasicExchange) charges
+/// for the fills of the broker orders. Charged fees are reported in the `OrderExecuted`
+/// and `OrderPartiallyExecuted` replies.
+///
+/// Is implemented for the closures and for the pairs of the models, which fees are summed,
+/// e.g. to charge the per-order fee on top of the basis point one.
+pub trait FeeModel<BrokerID: Id, Symbol: Id, Settlement: GetSettlementLag> {
+    /// Returns the fee for the fill. Is called for every fill in the order they happen.
+    ///
+    /// # Arguments
+    ///
+    /// * `fill` — Fill of the broker order.
+    fn charge(&mut self, fill: &ChargeableFill<BrokerID, Symbol, Settlement>) -> Fee;
+}
+
+impl<BrokerID, Symbol, Settlement, F> FeeModel<BrokerID, Symbol, Settlement> for F
+    where BrokerID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag,
+          F: FnMut(&ChargeableFill<BrokerID, Symbol, Settlement>) -> Fee
+{
+    fn charge(&mut self, fill: &ChargeableFill<BrokerID, Symbol, Settlement>) -> Fee {
+        self(fill)
+    }
+}
+
+impl<BrokerID, Symbol, Settlement, A, B> FeeModel<BrokerID, Symbol, Settlement> for (A, B)
+    where BrokerID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag,
+          A: FeeModel<BrokerID, Symbol, Settlement>,
+          B: FeeModel<BrokerID, Symbol, Settlement>
+{
+    fn charge(&mut self, fill: &ChargeableFill<BrokerID, Symbol, Settlement>) -> Fee {
+        self.0.charge(fill) + self.1.charge(fill)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Maker and taker fees in basis points of the fill notional. Negative fees are rebates.
+pub struct BpsFees {
+    maker_fee_bps: f64,
+    taker_fee_bps: f64,
+}
+
+impl BpsFees {
+    /// Creates a new instance of the `BpsFees`.
+    ///
+    /// # Arguments
+    ///
+    /// * `maker_fee_bps` — Fee for the liquidity provision.
+    /// * `taker_fee_bps` — Fee for the liquidity removal.
+    pub fn new(maker_fee_bps: f64, taker_fee_bps: f64) -> Self {
+        check_bps(maker_fee_bps);
+        check_bps(taker_fee_bps);
+        Self { maker_fee_bps, taker_fee_bps }
+    }
+}
+
+impl<BrokerID, Symbol, Settlement> FeeModel<BrokerID, Symbol, Settlement> for BpsFees
+    where BrokerID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    fn charge(&mut self, fill: &ChargeableFill<BrokerID, Symbol, Settlement>) -> Fee {
+        let bps = match fill.liquidity {
+            Liquidity::Maker => self.maker_fee_bps,
+            Liquidity::Taker => self.taker_fee_bps
+        };
+        get_bps_fee(fill.get_notional(), bps)
+    }
+}
+
+/// Flat fee charged once per order, with its first fill.
+pub struct PerOrderFee<BrokerID: Id> {
+    fee: Fee,
+    charged_orders: HashSet<(BrokerID, OrderID)>,
+}
+
+impl<BrokerID: Id> PerOrderFee<BrokerID> {
+    /// Creates a new instance of the `PerOrderFee`.
+    ///
+    /// # Arguments
+    ///
+    /// * `fee` — Fee per order.
+    pub fn new(fee: Fee) -> Self {
+        Self { fee, charged_orders: Default::default() }
+    }
+}
+
+impl<BrokerID, Symbol, Settlement> FeeModel<BrokerID, Symbol, Settlement>
+for PerOrderFee<BrokerID>
+    where BrokerID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    fn charge(&mut self, fill: &ChargeableFill<BrokerID, Symbol, Settlement>) -> Fee {
+        if self.charged_orders.insert((fill.broker_id, fill.order_id)) {
+            self.fee
+        } else {
+            Fee(0)
+        }
+    }
+}
+
+/// Maker and taker fees in basis points of the fill notional, tiered by the volume
+/// the broker has traded at the exchange since the start of the simulation.
+/// Fill is charged at the tier reached before it.
+pub struct TieredFees<BrokerID: Id> {
+    /// Volume thresholds along with the maker and taker fees, sorted by the thresholds.
+    tiers: Vec<(Lots, f64, f64)>,
+    volumes: HashMap<BrokerID, Lots>,
+}
+
+impl<BrokerID: Id> TieredFees<BrokerID> {
+    /// Creates a new instance of the `TieredFees`.
+    ///
+    /// # Arguments
+    ///
+    /// * `tiers` — Volume thresholds along with the maker and taker fees.
+    ///   The lowest threshold should be zero.
+    pub fn new(tiers: impl IntoIterator<Item=(Lots, f64, f64)>) -> Self {
+        let mut tiers: Vec<_> = tiers.into_iter().collect();
+        tiers.sort_by_key(|(threshold, _, _)| *threshold);
+        match tiers.first() {
+            Some((Lots(0), _, _)) => {}
+            _ => panic!("Lowest volume threshold of the fee tiers should be zero")
+        }
+        for (_, maker_fee_bps, taker_fee_bps) in &tiers {
+            check_bps(*maker_fee_bps);
+            check_bps(*taker_fee_bps)
+        }
+        Self { tiers, volumes: Default::default() }
+    }
+
+    /// Returns the volume the broker has traded.
+    ///
+    /// # Arguments
+    ///
+    /// * `broker_id` — Broker.
+    pub fn get_volume(&self, broker_id: BrokerID) -> Lots {
+        self.volumes.get(&broker_id).copied().unwrap_or(Lots(0))
+    }
+}
+
+impl<BrokerID, Symbol, Settlement> FeeModel<BrokerID, Symbol, Settlement>
+for TieredFees<BrokerID>
+    where BrokerID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    fn charge(&mut self, fill: &ChargeableFill<BrokerID, Symbol, Settlement>) -> Fee {
+        let volume = self.volumes.entry(fill.broker_id).or_insert(Lots(0));
+        let (_, maker_fee_bps, taker_fee_bps) = self.tiers.iter()
+            .rev()
+            .find(|(threshold, _, _)| *threshold <= *volume)
+            .unwrap_or_else(|| unreachable!("Lowest volume threshold should be zero"));
+        *volume += fill.size;
+        let bps = match fill.liquidity {
+            Liquidity::Maker => *maker_fee_bps,
+            Liquidity::Taker => *taker_fee_bps
+        };
+        get_bps_fee(fill.get_notional(), bps)
+    }
+}
+
+fn check_bps(bps: f64) {
+    if !bps.is_finite() {
+        panic!("Fee should be finite. Got {bps} bps")
+    }
+}
+
+fn get_bps_fee(notional: i64, bps: f64) -> Fee {
+    Fee::from_ticks(notional as f64 * bps / 10_000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            concrete::traded_pair::settlement::concrete::SpotSettlement,
+            test_utils::{dt, pair},
+        },
+        super::*,
+    };
+
+    fn fill(
+        broker_id: u8,
+        order_id: u64,
+        liquidity: Liquidity,
+        size: i64) -> ChargeableFill<u8, &'static str, SpotSettlement>
+    {
+        ChargeableFill {
+            datetime: dt(0),
+            broker_id,
+            traded_pair: pair(),
+            order_id: OrderID(order_id),
+            liquidity,
+            price: Tick(100),
+            size: Lots(size),
+        }
+    }
+
+    #[test]
+    fn test_bps_fees()
+    {
+        let mut fees = BpsFees::new(-1.0, 5.0);
+        assert_eq!(fees.charge(&fill(0, 0, Liquidity::Taker, 1)), Fee::from_ticks(0.05));
+        assert_eq!(fees.charge(&fill(0, 0, Liquidity::Maker, 2)), Fee::from_ticks(-0.02))
+    }
+
+    #[test]
+    fn test_per_order_fee_is_charged_once()
+    {
+        let mut fees = (PerOrderFee::new(Fee(7)), BpsFees::new(0.0, 5.0));
+        assert_eq!(fees.charge(&fill(0, 0, Liquidity::Taker, 1)), Fee(50_007));
+        assert_eq!(fees.charge(&fill(0, 0, Liquidity::Taker, 1)), Fee(50_000));
+        // Orders of different brokers may share the ID
+        assert_eq!(fees.charge(&fill(1, 0, Liquidity::Taker, 1)), Fee(50_007))
+    }
+
+    #[test]
+    fn test_tiered_fees()
+    {
+        let mut fees = TieredFees::new([(Lots(10), 1.0, 2.0), (Lots(0), 2.0, 4.0)]);
+        // Fill crossing the threshold is charged at the tier reached before it
+        assert_eq!(fees.charge(&fill(0, 0, Liquidity::Taker, 10)), Fee::from_ticks(0.4));
+        assert_eq!(fees.charge(&fill(0, 1, Liquidity::Taker, 1)), Fee::from_ticks(0.02));
+        assert_eq!(fees.charge(&fill(0, 1, Liquidity::Maker, 1)), Fee::from_ticks(0.01));
+        assert_eq!(fees.charge(&fill(1, 0, Liquidity::Maker, 1)), Fee::from_ticks(0.02));
+        assert_eq!(fees.get_volume(0), Lots(12));
+        assert_eq!(fees.get_volume(2), Lots(0))
+    }
+
+    #[test]
+    #[should_panic(expected = "Lowest volume threshold of the fee tiers should be zero")]
+    fn test_tiered_fees_require_zero_threshold()
+    {
+        TieredFees::<u8>::new([(Lots(1), 1.0, 2.0)]);
+    }
+}
@@ -0,0 +1,141 @@
+use {
+    crate::{
+        concrete::traded_pair::{settlement::GetSettlementLag, TradedPair},
+        types::Id,
+        utils::collections::HashMap,
+    },
+    std::{cell::RefCell, collections::BTreeMap, rc::Rc},
+};
+
+/// Best bid and ask prices of the single exchange.
+type BestBidOffer = (Option<f64>, Option<f64>);
+
+/// [`ConsolidatedQuotes`] shared between the exchanges of the single simulation thread.
+pub type SharedConsolidatedQuotes<ExchangeID, Symbol, Settlement> = Rc<
+    RefCell<ConsolidatedQuotes<ExchangeID, Symbol, Settlement>>
+>;
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+/// Policy of the [`BasicExchange`](crate::concrete::exchange::BasicExchange) regarding
+/// the broker orders that would trade through the best prices of the other exchanges.
+///
+/// Order trades through if it would execute at the price worse than the best price
+/// of the opposite side published by another exchange.
+/// Market orders cannot be re-priced, so they are discarded under any enforcing policy.
+/// Replay orders are never checked, since they reconstruct the historical order books.
+pub enum TradeThroughPolicy {
+    /// Publish the best prices without enforcing the protection.
+    #[default]
+    Allow,
+    /// Discard the order with the `TradeThrough` reason.
+    Reject,
+    /// Re-price the limit order to the least aggressive of its price
+    /// and the best price of the other exchanges, rounded to the price increment.
+    Reprice,
+}
+
+/// Best bid and ask prices of the traded pairs published by the exchanges,
+/// i.e. the consolidated best bid-offer.
+///
+/// Prices are kept as [`f64`], since the price steps may differ between the exchanges.
+/// Exchanges publish their prices after processing every request,
+/// so the other exchanges observe them without any latency.
+pub struct ConsolidatedQuotes<ExchangeID: Id, Symbol: Id, Settlement: GetSettlementLag> {
+    quotes: HashMap<TradedPair<Symbol, Settlement>, BTreeMap<ExchangeID, BestBidOffer>>,
+}
+
+impl<ExchangeID: Id, Symbol: Id, Settlement: GetSettlementLag> Default
+for ConsolidatedQuotes<ExchangeID, Symbol, Settlement>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<ExchangeID: Id, Symbol: Id, Settlement: GetSettlementLag>
+ConsolidatedQuotes<ExchangeID, Symbol, Settlement>
+{
+    /// Creates a new instance of the `ConsolidatedQuotes`.
+    pub fn new() -> Self {
+        Self { quotes: Default::default() }
+    }
+
+    /// Replaces all the prices published by the exchange.
+    ///
+    /// # Arguments
+    ///
+    /// * `exchange_id` — Publishing exchange.
+    /// * `quotes` — Traded pairs along with their best bid and ask prices.
+    pub fn publish(
+        &mut self,
+        exchange_id: ExchangeID,
+        quotes: impl IntoIterator<Item=(TradedPair<Symbol, Settlement>, Option<f64>, Option<f64>)>)
+    {
+        self.withdraw(exchange_id);
+        for (traded_pair, bid, ask) in quotes {
+            if bid.is_some() || ask.is_some() {
+                self.quotes.entry(traded_pair).or_default().insert(exchange_id, (bid, ask));
+            }
+        }
+    }
+
+    /// Removes all the prices published by the exchange, e.g. when it closes.
+    ///
+    /// # Arguments
+    ///
+    /// * `exchange_id` — Exchange.
+    pub fn withdraw(&mut self, exchange_id: ExchangeID) {
+        self.quotes.retain(
+            |_, quotes| {
+                quotes.remove(&exchange_id);
+                !quotes.is_empty()
+            }
+        )
+    }
+
+    /// Returns the highest bid price of the traded pair along with its exchange.
+    /// Ties are broken in favour of the lowest exchange ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `traded_pair` — Traded pair.
+    /// * `excluded` — Exchange which prices are not taken into account, e.g. the requesting one.
+    pub fn get_best_bid(
+        &self,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        excluded: Option<ExchangeID>) -> Option<(ExchangeID, f64)>
+    {
+        self.iter_side(traded_pair, excluded, |(bid, _)| bid)
+            .reduce(|best, other| if other.1 > best.1 { other } else { best })
+    }
+
+    /// Returns the lowest ask price of the traded pair along with its exchange.
+    /// Ties are broken in favour of the lowest exchange ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `traded_pair` — Traded pair.
+    /// * `excluded` — Exchange which prices are not taken into account, e.g. the requesting one.
+    pub fn get_best_ask(
+        &self,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        excluded: Option<ExchangeID>) -> Option<(ExchangeID, f64)>
+    {
+        self.iter_side(traded_pair, excluded, |(_, ask)| ask)
+            .reduce(|best, other| if other.1 < best.1 { other } else { best })
+    }
+
+    fn iter_side(
+        &self,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        excluded: Option<ExchangeID>,
+        get_price: fn(BestBidOffer) -> Option<f64>,
+    ) -> impl Iterator<Item=(ExchangeID, f64)> + '_
+    {
+        self.quotes.get(&traded_pair)
+            .into_iter()
+            .flatten()
+            .filter(move |(exchange_id, _)| Some(**exchange_id) != excluded)
+            .filter_map(move |(exchange_id, quote)| Some((*exchange_id, get_price(*quote)?)))
+    }
+}
@@ -0,0 +1,306 @@
+use crate::{
+    concrete::{
+        message_protocol::exchange::reply::LegExecuted,
+        traded_pair::{settlement::GetSettlementLag, TradedPair},
+        types::{Direction, Lots, Tick, TickSize},
+    },
+    types::Id,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Leg of the [`SpreadDefinition`].
+pub struct SpreadLeg<Symbol: Id, Settlement: GetSettlementLag> {
+    /// Traded pair of the leg.
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    /// Number of the leg lots per one lot of the spread.
+    /// Positive if the leg is bought when the spread is bought, negative otherwise.
+    pub ratio: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Exchange-listed multi-leg instrument, e.g. calendar spread or butterfly.
+///
+/// The spread is traded in its own order book. Its price is the ratio-weighted sum
+/// of the leg prices, and its fills are novated and booked as the fills of the legs.
+pub struct SpreadDefinition<Symbol: Id, Settlement: GetSettlementLag> {
+    legs: Vec<SpreadLeg<Symbol, Settlement>>,
+    implied_pricing: bool,
+}
+
+impl<Symbol: Id, Settlement: GetSettlementLag> SpreadDefinition<Symbol, Settlement>
+{
+    /// Creates a new instance of the `SpreadDefinition`.
+    ///
+    /// # Arguments
+    ///
+    /// * `legs` — Legs of the spread. Ratios should be non-zero.
+    pub fn new(legs: impl IntoIterator<Item=SpreadLeg<Symbol, Settlement>>) -> Self {
+        let legs: Vec<_> = legs.into_iter().collect();
+        if legs.is_empty() {
+            panic!("Spread should have at least one leg")
+        }
+        if let Some(leg) = legs.iter().find(|leg| leg.ratio == 0) {
+            panic!("Spread leg ratio should be non-zero. Got zero for: {:?}", leg.traded_pair)
+        }
+        Self { legs, implied_pricing: false }
+    }
+
+    /// Creates the calendar spread. Buying the spread means
+    /// buying the `near` contract and selling the `far` one.
+    ///
+    /// # Arguments
+    ///
+    /// * `near` — Contract with the nearer maturity.
+    /// * `far` — Contract with the farther maturity.
+    pub fn calendar(
+        near: TradedPair<Symbol, Settlement>,
+        far: TradedPair<Symbol, Settlement>) -> Self
+    {
+        Self::new(
+            [
+                SpreadLeg { traded_pair: near, ratio: 1 },
+                SpreadLeg { traded_pair: far, ratio: -1 },
+            ]
+        )
+    }
+
+    /// Creates the butterfly. Buying the spread means buying the `lower` and the `upper` wings
+    /// and selling twice as many `middle` contracts.
+    ///
+    /// # Arguments
+    ///
+    /// * `lower` — Lower wing.
+    /// * `middle` — Body.
+    /// * `upper` — Upper wing.
+    pub fn butterfly(
+        lower: TradedPair<Symbol, Settlement>,
+        middle: TradedPair<Symbol, Settlement>,
+        upper: TradedPair<Symbol, Settlement>) -> Self
+    {
+        Self::new(
+            [
+                SpreadLeg { traded_pair: lower, ratio: 1 },
+                SpreadLeg { traded_pair: middle, ratio: -2 },
+                SpreadLeg { traded_pair: upper, ratio: 1 },
+            ]
+        )
+    }
+
+    /// Sets whether the spread quotes should be improved by the ones implied by the leg books.
+    /// Orders of such a spread do not trade through the implied quotes in the spread order book
+    /// and are repriced to them instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `implied_pricing` — Whether to derive implied quotes from the leg books.
+    pub fn with_implied_pricing(mut self, implied_pricing: bool) -> Self {
+        self.implied_pricing = implied_pricing;
+        self
+    }
+
+    /// Returns the legs of the spread.
+    pub fn get_legs(&self) -> &[SpreadLeg<Symbol, Settlement>] {
+        &self.legs
+    }
+
+    /// Checks whether the implied pricing is enabled.
+    pub fn has_implied_pricing(&self) -> bool {
+        self.implied_pricing
+    }
+
+    /// Returns the best bid and ask of the spread implied by the leg quotes.
+    /// The bid is `None` if it cannot be implied because some of the leg quotes are absent.
+    /// The same holds for the ask.
+    ///
+    /// # Arguments
+    ///
+    /// * `get_leg_quote` — Returns the best bid and ask of the leg.
+    pub fn implied_quote(
+        &self,
+        mut get_leg_quote: impl FnMut(
+            TradedPair<Symbol, Settlement>
+        ) -> (Option<Tick>, Option<Tick>)) -> (Option<Tick>, Option<Tick>)
+    {
+        let mut implied_bid = Some(Tick(0));
+        let mut implied_ask = Some(Tick(0));
+        for leg in &self.legs {
+            let (bid, ask) = get_leg_quote(leg.traded_pair);
+            // Selling the spread sells the positive legs at their bids
+            // and buys the negative ones at their asks. Buying it does the opposite.
+            let (sell_price, buy_price) = if leg.ratio > 0 { (bid, ask) } else { (ask, bid) };
+            let weighted = |price: Tick| Tick(price.0 * leg.ratio);
+            implied_bid = implied_bid.zip(sell_price.map(weighted)).map(|(sum, add)| sum + add);
+            implied_ask = implied_ask.zip(buy_price.map(weighted)).map(|(sum, add)| sum + add);
+        }
+        (implied_bid, implied_ask)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Leg of the spread along with the data its share of the spread trade is priced by.
+pub(crate) struct LegReference<Symbol: Id, Settlement: GetSettlementLag> {
+    pub leg: SpreadLeg<Symbol, Settlement>,
+    /// Reference price of the leg, e.g. the mid-price of its order book.
+    pub price: Option<f64>,
+    /// Price step of the leg.
+    pub price_step: TickSize,
+}
+
+/// Splits the spread trade price between the legs.
+///
+/// All legs but one are priced at their reference prices.
+/// The remaining one, i.e. the first leg without the reference price or the first leg
+/// if all of them have one, absorbs the difference so that the ratio-weighted sum
+/// of the leg prices equals the spread price. Legs without the reference price
+/// other than the absorbing one are priced at zero.
+///
+/// # Arguments
+///
+/// * `spread_price` — Spread trade price.
+/// * `legs` — Spread legs along with their reference prices.
+pub(crate) fn allocate_leg_prices<Symbol: Id, Settlement: GetSettlementLag>(
+    spread_price: f64,
+    legs: &[LegReference<Symbol, Settlement>]) -> Vec<f64>
+{
+    let anchor = legs.iter().position(|leg| leg.price.is_none()).unwrap_or(0);
+    let mut prices: Vec<_> = legs.iter()
+        .map(|leg| leg.price.unwrap_or_default())
+        .collect();
+    let others: f64 = legs.iter()
+        .zip(&prices)
+        .enumerate()
+        .filter(|(i, _)| *i != anchor)
+        .map(|(_, (leg, price))| leg.leg.ratio as f64 * price)
+        .sum();
+    prices[anchor] = (spread_price - others) / legs[anchor].leg.ratio as f64;
+    prices
+}
+
+/// Splits the fill of the spread order into the fills of its legs,
+/// priced by the [`allocate_leg_prices`] and rounded to the leg price steps.
+///
+/// # Arguments
+///
+/// * `direction` — Direction of the spread order.
+/// * `spread_price` — Spread trade price.
+/// * `size` — Spread trade size.
+/// * `legs` — Spread legs along with their reference prices.
+pub(crate) fn split_spread_fill<Symbol: Id, Settlement: GetSettlementLag>(
+    direction: Direction,
+    spread_price: f64,
+    size: Lots,
+    legs: &[LegReference<Symbol, Settlement>]) -> Vec<LegExecuted<Symbol, Settlement>>
+{
+    legs.iter()
+        .zip(allocate_leg_prices(spread_price, legs))
+        .map(
+            |(leg, price)| LegExecuted {
+                traded_pair: leg.leg.traded_pair,
+                direction: match (direction, leg.leg.ratio > 0) {
+                    (direction, true) => direction,
+                    (Direction::Buy, false) => Direction::Sell,
+                    (Direction::Sell, false) => Direction::Buy,
+                },
+                price: Tick((price / leg.price_step.0).round() as i64),
+                size: Lots(size.0 * leg.leg.ratio.abs()),
+            }
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            concrete::traded_pair::settlement::concrete::SpotSettlement,
+            test_utils::symbol_pair,
+        },
+        super::*,
+    };
+
+    fn reference(
+        symbol: &'static str,
+        ratio: i64,
+        price: Option<f64>) -> LegReference<&'static str, SpotSettlement>
+    {
+        LegReference {
+            leg: SpreadLeg { traded_pair: symbol_pair(symbol), ratio },
+            price,
+            price_step: TickSize(0.5),
+        }
+    }
+
+    #[test]
+    fn test_allocate_leg_prices()
+    {
+        // Anchor is the first leg if all of them have the reference price
+        let legs = [reference("ESZ", 1, Some(100.0)), reference("ESH", -1, Some(98.0))];
+        assert_eq!(allocate_leg_prices(3.0, &legs), [101.0, 98.0]);
+
+        // Otherwise it is the first leg without one, the others without one are priced at zero
+        let legs = [
+            reference("ESZ", 1, Some(100.0)),
+            reference("ESH", -2, None),
+            reference("ESM", 1, None),
+        ];
+        assert_eq!(allocate_leg_prices(2.0, &legs), [100.0, 49.0, 0.0]);
+        let legs = [
+            reference("ESZ", 1, Some(100.0)),
+            reference("ESH", -2, None),
+            reference("ESM", 1, Some(104.0)),
+        ];
+        assert_eq!(allocate_leg_prices(4.0, &legs), [100.0, 100.0, 104.0])
+    }
+
+    #[test]
+    fn test_split_spread_fill()
+    {
+        let legs = [
+            reference("ESZ", 1, Some(100.0)),
+            reference("ESH", -2, Some(101.0)),
+            reference("ESM", 1, None),
+        ];
+        let fills = split_spread_fill(Direction::Sell, 1.0, Lots(3), &legs);
+        let expected = [
+            (symbol_pair("ESZ"), Direction::Sell, Tick(200), Lots(3)),
+            (symbol_pair("ESH"), Direction::Buy, Tick(202), Lots(6)),
+            (symbol_pair("ESM"), Direction::Sell, Tick(206), Lots(3)),
+        ];
+        assert_eq!(
+            fills.iter()
+                .map(|fill| (fill.traded_pair, fill.direction, fill.price, fill.size))
+                .collect::<Vec<_>>(),
+            expected
+        )
+    }
+
+    #[test]
+    fn test_implied_quote()
+    {
+        let (near, far) = (symbol_pair("ESZ"), symbol_pair("ESH"));
+        let calendar = SpreadDefinition::calendar(near, far).with_implied_pricing(true);
+        assert!(calendar.has_implied_pricing());
+
+        // Spread is sold by selling the near leg at its bid and buying the far one at its ask
+        let quotes = |leg| if leg == near {
+            (Some(Tick(100)), Some(Tick(102)))
+        } else {
+            (Some(Tick(97)), Some(Tick(98)))
+        };
+        assert_eq!(calendar.implied_quote(quotes), (Some(Tick(2)), Some(Tick(5))));
+
+        // Side cannot be implied if any leg quote it needs is absent
+        let quotes = |leg| if leg == near {
+            (Some(Tick(100)), None)
+        } else {
+            (Some(Tick(97)), Some(Tick(98)))
+        };
+        assert_eq!(calendar.implied_quote(quotes), (Some(Tick(2)), None));
+
+        let butterfly = SpreadDefinition::butterfly(
+            symbol_pair("ESZ"), symbol_pair("ESH"), symbol_pair("ESM"),
+        );
+        let quotes = |_| (Some(Tick(100)), Some(Tick(101)));
+        assert_eq!(butterfly.implied_quote(quotes), (Some(Tick(-2)), Some(Tick(2))))
+    }
+}
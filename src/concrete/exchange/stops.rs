@@ -0,0 +1,274 @@
+use {
+    crate::{
+        concrete::{
+            order::{StopLimitOrderPlacingRequest, StopOrderPlacingRequest},
+            traded_pair::{settlement::GetSettlementLag, TradedPair},
+            types::{Direction, Lots, OrderID, Tick},
+        },
+        types::Id,
+        utils::collections::HashMap,
+    },
+    std::collections::BTreeMap,
+};
+
+/// Owner, traded pair and submitted ID of the pending stop order.
+type StopKey<BrokerID, Symbol, Settlement> = (BrokerID, TradedPair<Symbol, Settlement>, OrderID);
+
+#[derive(Debug, Clone, Copy)]
+/// Stop or stop-limit broker order waiting for its trigger.
+pub(crate) struct PendingStop<BrokerID: Id, Symbol: Id, Settlement: GetSettlementLag> {
+    pub broker_id: BrokerID,
+    pub order: StopOrderPlacingRequest<Symbol, Settlement>,
+    /// Price of the limit order to place once triggered. `None` stands for the stop order.
+    pub limit_price: Option<Tick>,
+}
+
+impl<BrokerID: Id, Symbol: Id, Settlement: GetSettlementLag>
+PendingStop<BrokerID, Symbol, Settlement>
+{
+    pub fn new_stop_limit(
+        broker_id: BrokerID,
+        order: StopLimitOrderPlacingRequest<Symbol, Settlement>) -> Self
+    {
+        let StopLimitOrderPlacingRequest {
+            traded_pair, order_id, direction, stop_price, price, size
+        } = order;
+        Self {
+            broker_id,
+            order: StopOrderPlacingRequest { traded_pair, order_id, direction, stop_price, size },
+            limit_price: Some(price),
+        }
+    }
+
+    /// Returns the trade price triggering the order if there has been one
+    /// between the `low` and the `high` trade prices.
+    pub fn get_trigger_price(&self, low: Tick, high: Tick) -> Option<Tick> {
+        match self.order.direction {
+            Direction::Buy => (high >= self.order.stop_price).then_some(high),
+            Direction::Sell => (low <= self.order.stop_price).then_some(low)
+        }
+    }
+
+    fn get_key(&self) -> StopKey<BrokerID, Symbol, Settlement> {
+        (self.broker_id, self.order.traded_pair, self.order.order_id)
+    }
+}
+
+/// Pending stop and stop-limit orders of the brokers, in the order of their placement,
+/// which is also the order they are triggered in.
+pub(crate) struct StopBook<BrokerID: Id, Symbol: Id, Settlement: GetSettlementLag> {
+    /// Pending orders by their placement numbers.
+    orders: BTreeMap<u64, PendingStop<BrokerID, Symbol, Settlement>>,
+    placement_nos: HashMap<StopKey<BrokerID, Symbol, Settlement>, u64>,
+    next_placement_no: u64,
+}
+
+impl<BrokerID: Id, Symbol: Id, Settlement: GetSettlementLag> Default
+for StopBook<BrokerID, Symbol, Settlement>
+{
+    fn default() -> Self {
+        Self {
+            orders: Default::default(),
+            placement_nos: Default::default(),
+            next_placement_no: 0,
+        }
+    }
+}
+
+impl<BrokerID: Id, Symbol: Id, Settlement: GetSettlementLag> StopBook<BrokerID, Symbol, Settlement>
+{
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&PendingStop<BrokerID, Symbol, Settlement>> {
+        self.orders.values()
+    }
+
+    pub fn get(
+        &self,
+        broker_id: BrokerID,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        order_id: OrderID) -> Option<&PendingStop<BrokerID, Symbol, Settlement>>
+    {
+        let placement_no = self.placement_nos.get(&(broker_id, traded_pair, order_id))?;
+        self.orders.get(placement_no)
+    }
+
+    pub fn insert(&mut self, stop: PendingStop<BrokerID, Symbol, Settlement>) {
+        let placement_no = self.next_placement_no;
+        if self.placement_nos.insert(stop.get_key(), placement_no).is_some() {
+            panic!("Stop order {:?} is already pending", stop.get_key())
+        }
+        self.next_placement_no += 1;
+        self.orders.insert(placement_no, stop);
+    }
+
+    pub fn remove(
+        &mut self,
+        broker_id: BrokerID,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        order_id: OrderID) -> Option<PendingStop<BrokerID, Symbol, Settlement>>
+    {
+        let placement_no = self.placement_nos.remove(&(broker_id, traded_pair, order_id))?;
+        self.orders.remove(&placement_no)
+    }
+
+    /// Reduces the size of the pending order and returns its remaining size.
+    /// If the `size` is not less than the size of the order, the order is removed.
+    pub fn reduce(
+        &mut self,
+        broker_id: BrokerID,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        order_id: OrderID,
+        size: Lots) -> Option<Lots>
+    {
+        let placement_no = *self.placement_nos.get(&(broker_id, traded_pair, order_id))?;
+        let order = &mut self.orders.get_mut(&placement_no)?.order;
+        if size >= order.size {
+            self.remove(broker_id, traded_pair, order_id);
+            Some(Lots(0))
+        } else {
+            order.size -= size;
+            Some(order.size)
+        }
+    }
+
+    /// Removes the orders triggered by the trades and returns them
+    /// along with their trigger prices.
+    ///
+    /// # Arguments
+    ///
+    /// * `get_traded_range` — Returns the lowest and the highest trade prices
+    ///   of the traded pair since the last check or `None` if there have been no trades.
+    pub fn take_triggered(
+        &mut self,
+        mut get_traded_range: impl FnMut(TradedPair<Symbol, Settlement>) -> Option<(Tick, Tick)>)
+        -> Vec<(PendingStop<BrokerID, Symbol, Settlement>, Tick)>
+    {
+        let triggered: Vec<_> = self.orders.iter()
+            .filter_map(
+                |(placement_no, stop)| {
+                    let (low, high) = get_traded_range(stop.order.traded_pair)?;
+                    Some((*placement_no, stop.get_trigger_price(low, high)?))
+                }
+            )
+            .collect();
+        triggered.into_iter()
+            .map(|(placement_no, trigger_price)| (self.take(placement_no), trigger_price))
+            .collect()
+    }
+
+    /// Removes the orders satisfying the predicate and returns them.
+    pub fn drain_filter(
+        &mut self,
+        mut predicate: impl FnMut(&PendingStop<BrokerID, Symbol, Settlement>) -> bool)
+        -> Vec<PendingStop<BrokerID, Symbol, Settlement>>
+    {
+        let removed: Vec<_> = self.orders.iter()
+            .filter(|(_, stop)| predicate(stop))
+            .map(|(placement_no, _)| *placement_no)
+            .collect();
+        removed.into_iter().map(|placement_no| self.take(placement_no)).collect()
+    }
+
+    fn take(&mut self, placement_no: u64) -> PendingStop<BrokerID, Symbol, Settlement> {
+        let stop = self.orders.remove(&placement_no).unwrap_or_else(
+            || unreachable!("Stop order with placement number {placement_no} is not pending")
+        );
+        self.placement_nos.remove(&stop.get_key());
+        stop
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            concrete::traded_pair::settlement::concrete::SpotSettlement,
+            test_utils::{pair, symbol_pair},
+        },
+        super::*,
+    };
+
+    fn stop(
+        broker_id: u8,
+        order_id: u64,
+        direction: Direction,
+        stop_price: i64) -> PendingStop<u8, &'static str, SpotSettlement>
+    {
+        PendingStop {
+            broker_id,
+            order: StopOrderPlacingRequest {
+                traded_pair: pair(),
+                order_id: OrderID(order_id),
+                direction,
+                stop_price: Tick(stop_price),
+                size: Lots(2),
+            },
+            limit_price: None,
+        }
+    }
+
+    #[test]
+    fn test_trigger_price() {
+        let buy = stop(0, 0, Direction::Buy, 100);
+        assert_eq!(buy.get_trigger_price(Tick(90), Tick(99)), None);
+        assert_eq!(buy.get_trigger_price(Tick(90), Tick(100)), Some(Tick(100)));
+        // Trades have run through the stop price
+        assert_eq!(buy.get_trigger_price(Tick(95), Tick(105)), Some(Tick(105)));
+
+        let sell = stop(0, 0, Direction::Sell, 100);
+        assert_eq!(sell.get_trigger_price(Tick(101), Tick(110)), None);
+        assert_eq!(sell.get_trigger_price(Tick(100), Tick(110)), Some(Tick(100)));
+        assert_eq!(sell.get_trigger_price(Tick(95), Tick(105)), Some(Tick(95)))
+    }
+
+    #[test]
+    fn test_take_triggered() {
+        let mut book = StopBook::default();
+        book.insert(stop(0, 0, Direction::Buy, 103));
+        book.insert(stop(0, 1, Direction::Sell, 97));
+        book.insert(stop(1, 0, Direction::Buy, 101));
+        book.insert(stop(0, 2, Direction::Buy, 110));
+
+        // Triggered orders are taken in the order of their placement
+        let triggered = book.take_triggered(|_| Some((Tick(96), Tick(104))));
+        let triggered: Vec<_> = triggered.iter()
+            .map(|(stop, price)| (stop.broker_id, stop.order.order_id, *price))
+            .collect();
+        assert_eq!(
+            triggered,
+            [(0, OrderID(0), Tick(104)), (0, OrderID(1), Tick(96)), (1, OrderID(0), Tick(104))]
+        );
+        assert!(book.get(0, pair(), OrderID(0)).is_none());
+        assert!(book.get(0, pair(), OrderID(2)).is_some());
+
+        // No trades in the traded pair
+        assert!(book.take_triggered(|_| None).is_empty());
+        assert_eq!(book.iter().count(), 1)
+    }
+
+    #[test]
+    fn test_orders_are_keyed_by_broker_pair_and_id() {
+        let mut book = StopBook::default();
+        book.insert(stop(0, 0, Direction::Buy, 103));
+        book.insert(stop(1, 0, Direction::Buy, 104));
+        assert!(book.get(0, symbol_pair("EUR"), OrderID(0)).is_none());
+
+        assert_eq!(book.reduce(1, pair(), OrderID(0), Lots(1)), Some(Lots(1)));
+        assert_eq!(book.get(1, pair(), OrderID(0)).unwrap().order.size, Lots(1));
+        assert_eq!(book.get(0, pair(), OrderID(0)).unwrap().order.size, Lots(2));
+        assert_eq!(book.reduce(1, pair(), OrderID(0), Lots(1)), Some(Lots(0)));
+        assert!(book.get(1, pair(), OrderID(0)).is_none());
+
+        let removed = book.drain_filter(|stop| stop.order.stop_price == Tick(103));
+        assert_eq!(removed.len(), 1);
+        assert!(book.is_empty());
+        assert!(book.remove(0, pair(), OrderID(0)).is_none());
+
+        // Removed order IDs may be reused
+        book.insert(stop(0, 0, Direction::Sell, 90));
+        assert_eq!(book.get(0, pair(), OrderID(0)).unwrap().order.direction, Direction::Sell)
+    }
+}
@@ -0,0 +1,1102 @@
+use {
+    crate::{
+        concrete::{
+            exchange::{
+                admin::{AdminAction, PriceBand},
+                auction::{SessionSchedule, TradingPhase},
+                BasicExchange,
+                fees::BpsFees,
+                OffTickPricePolicy,
+                OrderSizeLimits,
+                spread::SpreadDefinition,
+            },
+            message_protocol::{
+                broker::request::{BasicBrokerRequest, BasicBrokerToExchange},
+                exchange::reply::{
+                    BasicExchangeToBrokerReply,
+                    BasicExchangeToReplayReply,
+                    CancellationReason,
+                    CannotApplyAdminAction,
+                    ExchangeEventNotification,
+                    InabilityToApplyAdminAction,
+                    OrderCancelled,
+                    OrderExecuted,
+                    OrderPlacementDiscarded,
+                    OrderPriceAdjusted,
+                    PlacementDiscardingReason,
+                    StopOrderTriggered,
+                },
+                replay::request::{BasicReplayRequest, BasicReplayToExchange},
+            },
+            middleware::{ExchangeMiddleware, Verdict},
+            order::{
+                IcebergOrderPlacingRequest,
+                LimitOrderCancelRequest,
+                LimitOrderPlacingRequest,
+                MarketOrderPlacingRequest,
+                StopOrderPlacingRequest,
+                TimeInForce,
+            },
+            traded_pair::settlement::concrete::SpotSettlement,
+            types::{Direction, Fee, Lots, OrderID, Tick, TickSize},
+        },
+        interface::exchange::{Exchange, ExchangeActionKind},
+        test_utils::{dt, pair, symbol_pair},
+        types::{Agent, DateTime, Duration, NanoDuration, TimeSync},
+        utils::queue::{LessElementBinaryHeap, MessageReceiver},
+    },
+    rand::{rngs::StdRng, SeedableRng},
+    std::rc::Rc,
+};
+
+type TestExchange = BasicExchange<&'static str, u8, &'static str, SpotSettlement>;
+type Action = <TestExchange as Agent>::Action;
+type Reply = BasicExchangeToBrokerReply<&'static str, SpotSettlement>;
+type Notification = ExchangeEventNotification<&'static str, SpotSettlement>;
+type Wakeup = <TestExchange as Exchange>::E2E;
+
+const EXCHANGE: &str = "MOEX";
+const BROKER: u8 = 1;
+
+fn replay(
+    exchange: &mut TestExchange,
+    content: BasicReplayRequest<&'static str, SpotSettlement>) -> Vec<Action>
+{
+    let mut queue = LessElementBinaryHeap::default();
+    let mut actions = Vec::new();
+    exchange.process_replay_request(
+        MessageReceiver::new(&mut queue),
+        |action, _| {
+            actions.push(action);
+            actions.len()
+        },
+        BasicReplayToExchange { exchange_id: EXCHANGE, content },
+        &mut StdRng::seed_from_u64(0),
+    );
+    actions
+}
+
+fn broker(
+    exchange: &mut TestExchange,
+    content: BasicBrokerRequest<&'static str, SpotSettlement>) -> Vec<Action>
+{
+    let mut queue = LessElementBinaryHeap::default();
+    let mut actions = Vec::new();
+    exchange.process_broker_request(
+        MessageReceiver::new(&mut queue),
+        |action, _| {
+            actions.push(action);
+            actions.len()
+        },
+        BasicBrokerToExchange { exchange_id: EXCHANGE, content },
+        BROKER,
+        &mut StdRng::seed_from_u64(0),
+    );
+    actions
+}
+
+fn wakeup(exchange: &mut TestExchange, scheduled_action: Wakeup) -> Vec<Action> {
+    let mut queue = LessElementBinaryHeap::default();
+    let mut actions = Vec::new();
+    exchange.wakeup(
+        MessageReceiver::new(&mut queue),
+        |action, _| {
+            actions.push(action);
+            actions.len()
+        },
+        scheduled_action,
+        &mut StdRng::seed_from_u64(0),
+    );
+    actions
+}
+
+/// Returns the wakeups the exchange has scheduled along with their delays.
+fn get_wakeups(actions: &[Action]) -> Vec<(NanoDuration, Wakeup)> {
+    actions.iter()
+        .filter_map(
+            |action| match action.content {
+                ExchangeActionKind::ExchangeToItself(wakeup) => Some((action.delay, wakeup)),
+                _ => None
+            }
+        )
+        .collect()
+}
+
+/// Returns the public notifications received by the [`BROKER`].
+fn get_notifications(actions: &[Action]) -> Vec<Notification> {
+    actions.iter()
+        .filter_map(
+            |action| match &action.content {
+                ExchangeActionKind::ExchangeToBroker(reply) => match &reply.content {
+                    Reply::ExchangeEventNotification(notification) => Some(notification.clone()),
+                    _ => None
+                }
+                _ => None
+            }
+        )
+        .collect()
+}
+
+/// Returns the replies of the exchange to the [`BROKER`] except for the public notifications.
+fn get_replies(actions: &[Action]) -> Vec<Reply> {
+    actions.iter()
+        .filter_map(
+            |action| match &action.content {
+                ExchangeActionKind::ExchangeToBroker(reply) if reply.broker_id == BROKER => {
+                    Some(reply.content.clone())
+                }
+                _ => None
+            }
+        )
+        .filter(|reply| !matches!(reply, Reply::ExchangeEventNotification(_)))
+        .collect()
+}
+
+/// Returns the submitted IDs, the prices and the sizes of the fills of the [`BROKER`] orders.
+fn get_fills(replies: &[Reply]) -> Vec<(OrderID, Tick, Lots)> {
+    replies.iter()
+        .filter_map(
+            |reply| match reply {
+                Reply::OrderExecuted(executed) => {
+                    Some((executed.order_id, executed.price, executed.size))
+                }
+                Reply::OrderPartiallyExecuted(executed) => {
+                    Some((executed.order_id, executed.price, executed.size))
+                }
+                _ => None
+            }
+        )
+        .collect()
+}
+
+/// Creates the open exchange trading the [`pair`] with the [`BROKER`] connected.
+fn open_exchange(exchange: TestExchange) -> TestExchange {
+    let mut exchange = exchange;
+    *exchange.current_datetime_mut() = dt(0);
+    exchange.connect_broker(BROKER);
+    replay(&mut exchange, BasicReplayRequest::ExchangeOpen);
+    replay(
+        &mut exchange,
+        BasicReplayRequest::StartTrades { traded_pair: pair(), price_step: TickSize(0.01) },
+    );
+    exchange
+}
+
+fn limit_order(
+    order_id: u64,
+    direction: Direction,
+    price: i64,
+    size: i64) -> LimitOrderPlacingRequest<&'static str, SpotSettlement>
+{
+    LimitOrderPlacingRequest {
+        traded_pair: pair(),
+        order_id: OrderID(order_id),
+        direction,
+        price: Tick(price),
+        size: Lots(size),
+        dummy: false,
+        time_in_force: Default::default(),
+    }
+}
+
+fn market_order(
+    order_id: u64,
+    direction: Direction,
+    size: i64) -> MarketOrderPlacingRequest<&'static str, SpotSettlement>
+{
+    MarketOrderPlacingRequest {
+        traded_pair: pair(),
+        order_id: OrderID(order_id),
+        direction,
+        size: Lots(size),
+        dummy: false,
+    }
+}
+
+#[test]
+fn test_stop_order_cascade()
+{
+    let mut exchange = open_exchange(TestExchange::new(EXCHANGE));
+    for (order_id, price, size) in [(0, 101, 1), (1, 102, 1), (2, 103, 5)] {
+        let order = limit_order(order_id, Direction::Sell, price, size);
+        replay(&mut exchange, BasicReplayRequest::PlaceLimitOrder(order));
+    }
+    for (order_id, stop_price) in [(10, 101), (11, 102), (12, 104)] {
+        let replies = get_replies(
+            &broker(
+                &mut exchange,
+                BasicBrokerRequest::PlaceStopOrder(
+                    StopOrderPlacingRequest {
+                        traded_pair: pair(),
+                        order_id: OrderID(order_id),
+                        direction: Direction::Buy,
+                        stop_price: Tick(stop_price),
+                        size: Lots(1),
+                    }
+                ),
+            )
+        );
+        assert!(matches!(replies.as_slice(), [Reply::OrderAccepted(_)]))
+    }
+
+    // Trade at 101 triggers the first stop order, its fill at 102 triggers the second one
+    let replies = get_replies(
+        &replay(
+            &mut exchange,
+            BasicReplayRequest::PlaceMarketOrder(market_order(3, Direction::Buy, 1)),
+        )
+    );
+    let triggered: Vec<_> = replies.iter()
+        .filter_map(
+            |reply| if let Reply::StopOrderTriggered(
+                StopOrderTriggered { order_id, trigger_price, .. }
+            ) = reply {
+                Some((*order_id, *trigger_price))
+            } else {
+                None
+            }
+        )
+        .collect();
+    assert_eq!(triggered, [(OrderID(10), Tick(101)), (OrderID(11), Tick(102))]);
+    assert_eq!(
+        get_fills(&replies),
+        [(OrderID(10), Tick(102), Lots(1)), (OrderID(11), Tick(103), Lots(1))]
+    );
+
+    // Stop price of the last order has not been reached
+    let replies = get_replies(
+        &broker(
+            &mut exchange,
+            BasicBrokerRequest::CancelLimitOrder(
+                LimitOrderCancelRequest {
+                    traded_pair: pair(),
+                    order_id: OrderID(12),
+                }
+            ),
+        )
+    );
+    assert!(matches!(replies.as_slice(), [Reply::OrderCancelled(_)]))
+}
+
+/// Returns the replies of the exchange to the replay.
+fn get_replay_replies(
+    actions: &[Action]) -> Vec<BasicExchangeToReplayReply<&'static str, SpotSettlement>>
+{
+    actions.iter()
+        .filter_map(
+            |action| match &action.content {
+                ExchangeActionKind::ExchangeToReplay(reply) => Some(reply.content.clone()),
+                _ => None
+            }
+        )
+        .collect()
+}
+
+#[test]
+fn test_seed_limit_order()
+{
+    let mut exchange = open_exchange(TestExchange::new(EXCHANGE).with_ob_diffs());
+    let order = limit_order(0, Direction::Sell, 101, 2);
+    let actions = replay(&mut exchange, BasicReplayRequest::SeedLimitOrder(order));
+    assert!(get_notifications(&actions).is_empty());
+    assert!(
+        matches!(
+            get_replay_replies(&actions).as_slice(),
+            [BasicExchangeToReplayReply::OrderAccepted(_)]
+        )
+    );
+
+    // Seeded orders are not matched against the order book
+    let order = limit_order(1, Direction::Buy, 101, 1);
+    let actions = replay(&mut exchange, BasicReplayRequest::SeedLimitOrder(order));
+    assert!(get_notifications(&actions).is_empty());
+    assert!(
+        matches!(
+            get_replay_replies(&actions).as_slice(),
+            [BasicExchangeToReplayReply::OrderPlacementDiscarded(
+                OrderPlacementDiscarded { reason: PlacementDiscardingReason::TradeThrough, .. }
+            )]
+        )
+    );
+
+    // Ordinary orders trade against the seeded ones and are broadcast
+    let actions = place_broker_order(&mut exchange, limit_order(2, Direction::Buy, 101, 1));
+    assert_eq!(get_fills(&get_replies(&actions)), [(OrderID(2), Tick(101), Lots(1))]);
+    assert!(!get_notifications(&actions).is_empty())
+}
+
+/// Places the sell limit orders of the replay at the given prices and sizes.
+fn place_asks(exchange: &mut TestExchange, asks: &[(u64, i64, i64)]) {
+    for &(order_id, price, size) in asks {
+        let order = limit_order(order_id, Direction::Sell, price, size);
+        replay(exchange, BasicReplayRequest::PlaceLimitOrder(order));
+    }
+}
+
+fn place_broker_order(
+    exchange: &mut TestExchange,
+    order: LimitOrderPlacingRequest<&'static str, SpotSettlement>) -> Vec<Action>
+{
+    broker(exchange, BasicBrokerRequest::PlaceLimitOrder(order))
+}
+
+#[test]
+fn test_immediate_or_cancel()
+{
+    let mut exchange = open_exchange(TestExchange::new(EXCHANGE));
+    place_asks(&mut exchange, &[(0, 101, 1), (1, 102, 2)]);
+
+    let actions = place_broker_order(
+        &mut exchange,
+        LimitOrderPlacingRequest {
+            time_in_force: TimeInForce::ImmediateOrCancel,
+            ..limit_order(10, Direction::Buy, 101, 3)
+        },
+    );
+    let replies = get_replies(&actions);
+    assert_eq!(get_fills(&replies), [(OrderID(10), Tick(101), Lots(1))]);
+    assert!(
+        matches!(
+            replies.last(),
+            Some(
+                Reply::OrderCancelled(
+                    OrderCancelled {
+                        order_id: OrderID(10),
+                        reason: CancellationReason::NotImmediatelyExecuted,
+                        ..
+                    }
+                )
+            )
+        )
+    );
+    // Remainder has not rested in the order book
+    assert!(
+        !get_notifications(&actions).iter().any(
+            |notification| matches!(notification, Notification::OrderCancelled(_))
+        )
+    );
+    let (order_book, _) = &exchange.order_books[&pair()];
+    assert_eq!(order_book.get_executable_size::<false>(Tick(0), Lots(10)), Lots(0));
+    assert_eq!(order_book.get_executable_size::<true>(Tick(102), Lots(10)), Lots(2))
+}
+
+#[test]
+fn test_fill_or_kill()
+{
+    let mut exchange = open_exchange(TestExchange::new(EXCHANGE));
+    place_asks(&mut exchange, &[(0, 101, 1), (1, 102, 2)]);
+    let fill_or_kill = |order_id, price, size| LimitOrderPlacingRequest {
+        time_in_force: TimeInForce::FillOrKill,
+        ..limit_order(order_id, Direction::Buy, price, size)
+    };
+
+    let replies = get_replies(&place_broker_order(&mut exchange, fill_or_kill(10, 101, 2)));
+    assert!(
+        matches!(
+            replies.as_slice(),
+            [
+                Reply::OrderPlacementDiscarded(
+                    OrderPlacementDiscarded {
+                        reason: PlacementDiscardingReason::NotFullyFillable,
+                        ..
+                    }
+                )
+            ]
+        )
+    );
+
+    let replies = get_replies(&place_broker_order(&mut exchange, fill_or_kill(11, 102, 3)));
+    assert_eq!(
+        get_fills(&replies),
+        [(OrderID(11), Tick(101), Lots(1)), (OrderID(11), Tick(102), Lots(2))]
+    );
+    assert!(!replies.iter().any(|reply| matches!(reply, Reply::OrderCancelled(_))))
+}
+
+#[test]
+fn test_good_till_date()
+{
+    let mut exchange = open_exchange(TestExchange::new(EXCHANGE));
+    let good_till = |order_id, price, expiration| LimitOrderPlacingRequest {
+        time_in_force: TimeInForce::GoodTillDate(expiration),
+        ..limit_order(order_id, Direction::Buy, price, 1)
+    };
+
+    let replies = get_replies(&place_broker_order(&mut exchange, good_till(10, 100, dt(0))));
+    assert!(
+        matches!(
+            replies.as_slice(),
+            [
+                Reply::OrderPlacementDiscarded(
+                    OrderPlacementDiscarded {
+                        reason: PlacementDiscardingReason::AlreadyExpired,
+                        ..
+                    }
+                )
+            ]
+        )
+    );
+
+    let actions = place_broker_order(&mut exchange, good_till(11, 100, dt(10)));
+    let [(delay, expiration)] = get_wakeups(&actions)[..] else {
+        panic!("Expiration of the order should be scheduled")
+    };
+    assert_eq!(delay, NanoDuration::from_duration(Duration::seconds(10)).unwrap());
+    let actions = place_broker_order(&mut exchange, good_till(12, 99, dt(20)));
+    let [(_, filled_expiration)] = get_wakeups(&actions)[..] else {
+        panic!("Expiration of the order should be scheduled")
+    };
+
+    *exchange.current_datetime_mut() = dt(10);
+    let replies = get_replies(&wakeup(&mut exchange, expiration));
+    assert!(
+        matches!(
+            replies.as_slice(),
+            [
+                Reply::OrderCancelled(
+                    OrderCancelled {
+                        order_id: OrderID(11),
+                        reason: CancellationReason::Expired,
+                        ..
+                    }
+                )
+            ]
+        )
+    );
+
+    replay(
+        &mut exchange,
+        BasicReplayRequest::PlaceMarketOrder(market_order(0, Direction::Sell, 1)),
+    );
+    assert!(exchange.order_expirations.is_empty());
+    *exchange.current_datetime_mut() = dt(20);
+    assert!(get_replies(&wakeup(&mut exchange, filled_expiration)).is_empty())
+}
+
+#[test]
+fn test_iceberg_and_hidden_orders()
+{
+    let mut exchange = open_exchange(TestExchange::new(EXCHANGE));
+    for (order_id, size, display_size) in [(10, 5, 2), (11, 2, 0)] {
+        let replies = get_replies(
+            &broker(
+                &mut exchange,
+                BasicBrokerRequest::PlaceIcebergOrder(
+                    IcebergOrderPlacingRequest {
+                        traded_pair: pair(),
+                        order_id: OrderID(order_id),
+                        direction: Direction::Sell,
+                        price: Tick(100),
+                        size: Lots(size),
+                        display_size: Lots(display_size),
+                    }
+                ),
+            )
+        );
+        assert!(matches!(replies.as_slice(), [Reply::OrderAccepted(_)]))
+    }
+    place_asks(&mut exchange, &[(0, 100, 1)]);
+
+    // Hidden order keeps its time priority over the displayed order submitted after it,
+    // while the next peak of the iceberg is queued behind both of them
+    let actions = replay(
+        &mut exchange,
+        BasicReplayRequest::PlaceMarketOrder(market_order(1, Direction::Buy, 4)),
+    );
+    let replies = get_replies(&actions);
+    assert_eq!(
+        get_fills(&replies),
+        [(OrderID(10), Tick(100), Lots(2)), (OrderID(11), Tick(100), Lots(2))]
+    );
+    assert!(matches!(replies.last(), Some(Reply::OrderExecuted(_))));
+
+    let actions = replay(
+        &mut exchange,
+        BasicReplayRequest::PlaceMarketOrder(market_order(2, Direction::Buy, 10)),
+    );
+    let replies = get_replies(&actions);
+    assert_eq!(
+        get_fills(&replies),
+        [(OrderID(10), Tick(100), Lots(2)), (OrderID(10), Tick(100), Lots(1))]
+    );
+    assert!(matches!(replies.last(), Some(Reply::OrderExecuted(_))));
+    let (order_book, _) = &exchange.order_books[&pair()];
+    assert_eq!(order_book.get_executable_size::<true>(Tick(100), Lots(10)), Lots(0))
+}
+
+#[test]
+fn test_iceberg_never_shows_reserve()
+{
+    let mut exchange = open_exchange(TestExchange::new(EXCHANGE));
+    let replies = get_replies(
+        &broker(
+            &mut exchange,
+            BasicBrokerRequest::PlaceIcebergOrder(
+                IcebergOrderPlacingRequest {
+                    traded_pair: pair(),
+                    order_id: OrderID(10),
+                    direction: Direction::Sell,
+                    price: Tick(100),
+                    size: Lots(10),
+                    display_size: Lots(3),
+                }
+            ),
+        )
+    );
+    assert!(matches!(replies.as_slice(), [Reply::OrderAccepted(_)]));
+    let get_book_changes = |actions: &[Action]| -> Vec<_> {
+        get_notifications(actions).into_iter()
+            .filter_map(
+                |notification| match notification {
+                    Notification::OrderPlaced(info) => Some(("placed", info.price, info.size)),
+                    Notification::OrderCancelled(info) => {
+                        Some(("cancelled", info.price, info.size))
+                    }
+                    Notification::TradeExecuted(info) => Some(("traded", info.price, info.size)),
+                    _ => None
+                }
+            )
+            .collect()
+    };
+
+    // Executed peak is replaced by the next one, which is executed in its turn
+    let actions = replay(
+        &mut exchange,
+        BasicReplayRequest::PlaceMarketOrder(market_order(1, Direction::Buy, 4)),
+    );
+    assert_eq!(
+        get_fills(&get_replies(&actions)),
+        [(OrderID(10), Tick(100), Lots(3)), (OrderID(10), Tick(100), Lots(1))]
+    );
+    assert_eq!(
+        get_book_changes(&actions),
+        [("placed", Tick(100), Lots(3)), ("traded", Tick(100), Lots(4))]
+    );
+    let (order_book, _) = &exchange.order_books[&pair()];
+    assert_eq!(order_book.get_ob_state(0).asks[0].1[0].0, Lots(2));
+
+    // Reserve is not cancelled publicly
+    let actions = broker(
+        &mut exchange,
+        BasicBrokerRequest::CancelLimitOrder(
+            LimitOrderCancelRequest { traded_pair: pair(), order_id: OrderID(10) }
+        ),
+    );
+    assert!(matches!(get_replies(&actions).as_slice(), [Reply::OrderCancelled(_)]));
+    assert_eq!(get_book_changes(&actions), [("cancelled", Tick(100), Lots(2))])
+}
+
+#[test]
+fn test_dummy_matching()
+{
+    let mut exchange = open_exchange(TestExchange::new(EXCHANGE).with_dummy_matching());
+    place_asks(&mut exchange, &[(0, 100, 3)]);
+    let dummy_order = |order_id, direction| LimitOrderPlacingRequest {
+        dummy: true,
+        ..limit_order(order_id, direction, 100, 4)
+    };
+    place_broker_order(&mut exchange, dummy_order(10, Direction::Sell));
+
+    // Fills of the incoming dummy order by the resting dummy one are not mixed
+    // with its shadow fills by the real liquidity
+    let replies = get_replies(&place_broker_order(&mut exchange, dummy_order(11, Direction::Buy)));
+    let fills: Vec<_> = replies.iter()
+        .filter_map(
+            |reply| match reply {
+                Reply::ShadowOrderPartiallyExecuted(executed) => {
+                    Some(("Shadow", executed.order_id, executed.size))
+                }
+                Reply::GhostOrderPartiallyExecuted(executed) => {
+                    Some(("Ghost", executed.order_id, executed.size))
+                }
+                Reply::GhostOrderExecuted(executed) => {
+                    Some(("GhostExecuted", executed.order_id, executed.size))
+                }
+                _ => None
+            }
+        )
+        .collect();
+    assert_eq!(
+        fills,
+        [
+            ("Ghost", OrderID(10), Lots(1)),
+            ("Shadow", OrderID(11), Lots(3)),
+            ("GhostExecuted", OrderID(11), Lots(1)),
+        ]
+    );
+    assert!(get_fills(&replies).is_empty());
+    // Real liquidity is not affected by the dummy orders
+    let (order_book, _) = &exchange.order_books[&pair()];
+    assert_eq!(order_book.get_executable_size::<true>(Tick(100), Lots(10)), Lots(3))
+}
+
+#[test]
+fn test_opening_auction_uncross()
+{
+    let mut exchange = TestExchange::new(EXCHANGE).with_session_schedule(
+        SessionSchedule::new(
+            [
+                (Duration::zero(), TradingPhase::OpeningAuction),
+                (Duration::seconds(10), TradingPhase::Continuous),
+            ]
+        )
+    );
+    *exchange.current_datetime_mut() = dt(0);
+    exchange.connect_broker(BROKER);
+    let actions = replay(&mut exchange, BasicReplayRequest::ExchangeOpen);
+    let [(delay, transition)] = get_wakeups(&actions)[..] else {
+        panic!("Transition to the continuous phase should be scheduled")
+    };
+    assert_eq!(delay, NanoDuration::from_duration(Duration::seconds(10)).unwrap());
+    assert_eq!(exchange.get_trading_phase(), TradingPhase::OpeningAuction);
+    replay(
+        &mut exchange,
+        BasicReplayRequest::StartTrades { traded_pair: pair(), price_step: TickSize(0.01) },
+    );
+
+    // Crossing orders accumulate during the call
+    let actions = place_broker_order(&mut exchange, limit_order(10, Direction::Buy, 101, 2));
+    let replies = get_replies(&actions);
+    assert!(matches!(replies.as_slice(), [Reply::OrderAccepted(_)]));
+    place_asks(&mut exchange, &[(0, 100, 1)]);
+    let replies = get_replies(
+        &broker(
+            &mut exchange,
+            BasicBrokerRequest::PlaceMarketOrder(market_order(11, Direction::Buy, 1)),
+        )
+    );
+    assert!(
+        matches!(
+            replies.as_slice(),
+            [
+                Reply::OrderPlacementDiscarded(
+                    OrderPlacementDiscarded {
+                        reason: PlacementDiscardingReason::NotAllowedInTradingPhase,
+                        ..
+                    }
+                )
+            ]
+        )
+    );
+
+    // Call ends with the uncross at its scheduled datetime without any other message
+    *exchange.current_datetime_mut() = dt(10);
+    let actions = wakeup(&mut exchange, transition);
+    assert_eq!(exchange.get_trading_phase(), TradingPhase::Continuous);
+    let replies = get_replies(&actions);
+    assert_eq!(get_fills(&replies), [(OrderID(10), Tick(100), Lots(1))]);
+    assert!(matches!(replies.as_slice(), [Reply::OrderPartiallyExecuted(_)]));
+    assert!(
+        get_notifications(&actions).contains(
+            &Notification::TradingPhaseChanged(TradingPhase::Continuous)
+        )
+    )
+}
+
+#[test]
+fn test_order_size_limits()
+{
+    let limits = OrderSizeLimits {
+        min_size: Some(Lots(2)),
+        max_size: Some(Lots(100)),
+        size_increment: Some(Lots(2)),
+        min_notional: Some(10.0),
+    };
+    let mut exchange = open_exchange(TestExchange::new(EXCHANGE).with_size_limits(pair(), limits));
+    let get_discarding_reason = |actions: &[Action]| match get_replies(actions).as_slice() {
+        [Reply::OrderPlacementDiscarded(OrderPlacementDiscarded { reason, .. })] => Some(*reason),
+        _ => None
+    };
+    for (order_id, price, size, reason) in [
+        (10, 100, 1, PlacementDiscardingReason::SizeBelowMinimum),
+        (11, 100, 102, PlacementDiscardingReason::SizeAboveMaximum),
+        (12, 100, 3, PlacementDiscardingReason::SizeNotMultipleOfIncrement),
+        (13, 100, 8, PlacementDiscardingReason::NotionalBelowMinimum),
+    ] {
+        let actions = place_broker_order(
+            &mut exchange,
+            limit_order(order_id, Direction::Buy, price, size),
+        );
+        assert_eq!(get_discarding_reason(&actions), Some(reason))
+    }
+    // Notional is checked in price units: 1.00 * 10 lots
+    let actions = place_broker_order(&mut exchange, limit_order(14, Direction::Buy, 100, 10));
+    assert!(matches!(get_replies(&actions).as_slice(), [Reply::OrderAccepted(_)]));
+    // Market orders are not checked for the notional
+    let actions = broker(
+        &mut exchange,
+        BasicBrokerRequest::PlaceMarketOrder(market_order(15, Direction::Buy, 1)),
+    );
+    assert_eq!(
+        get_discarding_reason(&actions),
+        Some(PlacementDiscardingReason::SizeBelowMinimum)
+    );
+
+    // Replay orders are not restricted
+    place_asks(&mut exchange, &[(0, 101, 1), (1, 102, 3)]);
+    let (order_book, _) = &exchange.order_books[&pair()];
+    assert_eq!(order_book.get_executable_size::<true>(Tick(102), Lots(10)), Lots(4))
+}
+
+#[test]
+fn test_off_tick_prices()
+{
+    let place = |policy, order_id, price| {
+        let exchange = TestExchange::new(EXCHANGE)
+            .with_price_increment(pair(), Tick(4))
+            .with_off_tick_price_policy(policy);
+        let mut exchange = open_exchange(exchange);
+        let replies = get_replies(
+            &place_broker_order(&mut exchange, limit_order(order_id, Direction::Buy, price, 1))
+        );
+        let (order_book, _) = &exchange.order_books[&pair()];
+        let resting_price = order_book.get_all_ids_and_prices().next().map(|(_, price)| price);
+        (replies, resting_price)
+    };
+
+    let (replies, resting_price) = place(OffTickPricePolicy::Reject, 10, 103);
+    assert!(
+        matches!(
+            replies.as_slice(),
+            [
+                Reply::OrderPlacementDiscarded(
+                    OrderPlacementDiscarded {
+                        order_id: OrderID(10),
+                        reason: PlacementDiscardingReason::OffTickPrice,
+                        ..
+                    }
+                )
+            ]
+        )
+    );
+    assert_eq!(resting_price, None);
+
+    for (policy, price, adjusted_price) in [
+        (OffTickPricePolicy::RoundDown, 103, 100),
+        (OffTickPricePolicy::RoundNearest, 101, 100),
+        (OffTickPricePolicy::RoundNearest, 103, 104),
+        // Ties are rounded up
+        (OffTickPricePolicy::RoundNearest, 102, 104),
+    ] {
+        let (replies, resting_price) = place(policy, 10, price);
+        match replies.as_slice() {
+            [Reply::OrderPriceAdjusted(adjusted), Reply::OrderAccepted(_)] => assert_eq!(
+                *adjusted,
+                OrderPriceAdjusted {
+                    traded_pair: pair(),
+                    order_id: OrderID(10),
+                    original_price: Tick(price),
+                    price: Tick(adjusted_price),
+                }
+            ),
+            replies => panic!("Order price should be adjusted, got: {replies:?}")
+        }
+        assert_eq!(resting_price, Some(Tick(adjusted_price)))
+    }
+
+    // On-grid prices are not adjusted by any policy
+    for policy in [
+        OffTickPricePolicy::Reject,
+        OffTickPricePolicy::RoundDown,
+        OffTickPricePolicy::RoundNearest,
+    ] {
+        let (replies, resting_price) = place(policy, 10, 104);
+        assert!(matches!(replies.as_slice(), [Reply::OrderAccepted(_)]));
+        assert_eq!(resting_price, Some(Tick(104)))
+    }
+}
+
+#[test]
+fn test_min_notional_after_price_step_change()
+{
+    let limits = OrderSizeLimits { min_notional: Some(0.3), ..Default::default() };
+    let mut exchange = open_exchange(TestExchange::new(EXCHANGE).with_size_limits(pair(), limits));
+    let is_accepted = |exchange: &mut TestExchange, order_id, price, size| matches!(
+        get_replies(
+            &place_broker_order(exchange, limit_order(order_id, Direction::Buy, price, size))
+        ).as_slice(),
+        [Reply::OrderAccepted(_)]
+    );
+    // 0.1 * 3 lots is not below 0.3 despite the floating-point error of the price step
+    assert!(!is_accepted(&mut exchange, 10, 10, 2));
+    assert!(is_accepted(&mut exchange, 11, 10, 3));
+
+    // Price increment does not change the meaning of the ticks
+    replay(
+        &mut exchange,
+        BasicReplayRequest::AdminAction(
+            AdminAction::SetPriceIncrement { traded_pair: pair(), increment: Tick(5) }
+        ),
+    );
+    assert!(!is_accepted(&mut exchange, 12, 5, 1));
+    assert!(is_accepted(&mut exchange, 13, 10, 4));
+
+    // Whereas the price step does
+    replay(&mut exchange, BasicReplayRequest::StopTrades(pair()));
+    replay(
+        &mut exchange,
+        BasicReplayRequest::StartTrades { traded_pair: pair(), price_step: TickSize(0.1) },
+    );
+    assert!(is_accepted(&mut exchange, 14, 5, 1))
+}
+
+#[test]
+fn test_spread_fills_and_implied_pricing()
+{
+    let (near, far, spread) = (symbol_pair("SiZ"), symbol_pair("SiH"), symbol_pair("SiZH"));
+    let definition = SpreadDefinition::calendar(near, far).with_implied_pricing(true);
+    let mut exchange = open_exchange(TestExchange::new(EXCHANGE).with_spread(spread, definition));
+    for traded_pair in [near, far, spread] {
+        replay(
+            &mut exchange,
+            BasicReplayRequest::StartTrades { traded_pair, price_step: TickSize(0.01) },
+        );
+    }
+    let order = |traded_pair, order_id, direction, price| LimitOrderPlacingRequest {
+        traded_pair,
+        ..limit_order(order_id, direction, price, 1)
+    };
+    // Implied spread quote is 2-6
+    for (order_id, traded_pair, direction, price) in [
+        (0, near, Direction::Buy, 100),
+        (1, near, Direction::Sell, 102),
+        (2, far, Direction::Buy, 96),
+        (3, far, Direction::Sell, 98),
+    ] {
+        let order = order(traded_pair, order_id, direction, price);
+        replay(&mut exchange, BasicReplayRequest::PlaceLimitOrder(order));
+    }
+    assert_eq!(exchange.get_best_quote(spread), (Some(Tick(2)), Some(Tick(6))));
+
+    // Spread orders do not trade through the implied quote
+    let order_4 = order(spread, 4, Direction::Sell, 7);
+    replay(&mut exchange, BasicReplayRequest::PlaceLimitOrder(order_4));
+    let replies = get_replies(
+        &place_broker_order(&mut exchange, order(spread, 10, Direction::Buy, 8))
+    );
+    assert!(
+        matches!(
+            replies.as_slice(),
+            [
+                Reply::OrderPriceAdjusted(OrderPriceAdjusted { price: Tick(6), .. }),
+                Reply::OrderAccepted(_)
+            ]
+        )
+    );
+
+    // Spread fills are reported along with the fills of the legs priced around their mid-prices
+    let order = order(spread, 5, Direction::Sell, 6);
+    let replies = get_replies(&replay(&mut exchange, BasicReplayRequest::PlaceLimitOrder(order)));
+    match replies.as_slice() {
+        [Reply::SpreadOrderExecuted(executed)] => {
+            assert!(
+                matches!(
+                    executed.execution,
+                    Reply::OrderExecuted(
+                        OrderExecuted { order_id: OrderID(10), price: Tick(6), size: Lots(1), .. }
+                    )
+                )
+            );
+            let legs: Vec<_> = executed.legs.iter()
+                .map(|leg| (leg.traded_pair, leg.direction, leg.price, leg.size))
+                .collect();
+            assert_eq!(
+                legs,
+                [
+                    (near, Direction::Buy, Tick(103), Lots(1)),
+                    (far, Direction::Sell, Tick(97), Lots(1))
+                ]
+            )
+        }
+        replies => panic!("Spread order should be executed, got: {replies:?}")
+    }
+}
+
+/// Rejects the broker orders larger than 5 lots, suppresses the broker order 11
+/// and the replay orders priced above 200.
+struct OrderGate;
+
+impl ExchangeMiddleware<&'static str, u8, &'static str, SpotSettlement> for OrderGate {
+    fn on_broker_request(
+        &mut self,
+        _: DateTime,
+        _: u8,
+        request: &mut BasicBrokerToExchange<&'static str, &'static str, SpotSettlement>,
+    ) -> Verdict<Reply>
+    {
+        match &request.content {
+            BasicBrokerRequest::PlaceLimitOrder(order) if order.size > Lots(5) => {
+                Verdict::Reject(
+                    Reply::OrderPlacementDiscarded(
+                        OrderPlacementDiscarded {
+                            traded_pair: order.traded_pair,
+                            order_id: order.order_id,
+                            reason: PlacementDiscardingReason::SizeAboveMaximum,
+                        }
+                    )
+                )
+            }
+            BasicBrokerRequest::PlaceLimitOrder(order) if order.order_id == OrderID(11) => {
+                Verdict::Suppress
+            }
+            _ => Verdict::Pass
+        }
+    }
+
+    fn on_replay_request(
+        &mut self,
+        _: DateTime,
+        request: &mut BasicReplayToExchange<&'static str, &'static str, SpotSettlement>) -> bool
+    {
+        !matches!(
+            &request.content,
+            BasicReplayRequest::PlaceLimitOrder(order) if order.price > Tick(200)
+        )
+    }
+}
+
+#[test]
+fn test_middleware()
+{
+    let mut exchange = open_exchange(TestExchange::new(EXCHANGE).with_middleware(OrderGate));
+    place_asks(&mut exchange, &[(0, 101, 1), (1, 300, 1)]);
+
+    let actions = place_broker_order(&mut exchange, limit_order(10, Direction::Buy, 101, 6));
+    assert_eq!(
+        get_replies(&actions),
+        [
+            Reply::OrderPlacementDiscarded(
+                OrderPlacementDiscarded {
+                    traded_pair: pair(),
+                    order_id: OrderID(10),
+                    reason: PlacementDiscardingReason::SizeAboveMaximum,
+                }
+            )
+        ]
+    );
+    assert!(place_broker_order(&mut exchange, limit_order(11, Direction::Buy, 101, 1)).is_empty());
+
+    // Suppressed replay order has not rested in the order book
+    let actions = place_broker_order(&mut exchange, limit_order(12, Direction::Buy, 300, 2));
+    assert_eq!(get_fills(&get_replies(&actions)), [(OrderID(12), Tick(101), Lots(1))])
+}
+
+#[test]
+fn test_bust_trades()
+{
+    let mut exchange = open_exchange(TestExchange::new(EXCHANGE));
+    place_asks(&mut exchange, &[(0, 100, 1), (1, 150, 1)]);
+    for (second, order_id, price) in [(1, 10, 100), (2, 11, 150)] {
+        *exchange.current_datetime_mut() = dt(second);
+        place_broker_order(&mut exchange, limit_order(order_id, Direction::Buy, price, 1));
+    }
+    let bust = |exchange: &mut TestExchange, since, valid_prices| {
+        let action = AdminAction::BustTrades { traded_pair: pair(), since, valid_prices };
+        let actions = replay(exchange, BasicReplayRequest::AdminAction(action));
+        get_replies(&actions).into_iter()
+            .map(
+                |reply| match reply {
+                    Reply::TradeBusted(busted) => (busted.order_id, busted.price, busted.size),
+                    reply => panic!("Unexpected reply: {reply:?}")
+                }
+            )
+            .collect::<Vec<_>>()
+    };
+
+    // Trades within the valid prices are kept
+    let valid_prices = PriceBand { lower: Some(Tick(90)), upper: Some(Tick(110)) };
+    assert_eq!(
+        bust(&mut exchange, dt(1), Some(valid_prices)),
+        [(OrderID(11), Tick(150), Lots(1))]
+    );
+    // Busted trades are not busted again
+    assert!(bust(&mut exchange, dt(2), None).is_empty());
+    assert_eq!(bust(&mut exchange, dt(0), None), [(OrderID(10), Tick(100), Lots(1))]);
+
+    let inverted = PriceBand { lower: Some(Tick(110)), upper: Some(Tick(90)) };
+    let action = AdminAction::BustTrades {
+        traded_pair: pair(),
+        since: dt(0),
+        valid_prices: Some(inverted),
+    };
+    assert!(
+        matches!(
+            get_replay_replies(&replay(&mut exchange, BasicReplayRequest::AdminAction(action)))
+                .as_slice(),
+            [
+                BasicExchangeToReplayReply::CannotApplyAdminAction(
+                    CannotApplyAdminAction {
+                        reason: InabilityToApplyAdminAction::InvertedPriceBand, ..
+                    }
+                )
+            ]
+        )
+    )
+}
+
+#[test]
+fn test_fees()
+{
+    let mut exchange = open_exchange(
+        TestExchange::new(EXCHANGE).with_fee_model(BpsFees::new(-1.0, 5.0))
+    );
+    let get_fees = |replies: &[Reply]| replies.iter()
+        .filter_map(
+            |reply| match reply {
+                Reply::OrderExecuted(executed) => Some((executed.order_id, executed.fee)),
+                Reply::OrderPartiallyExecuted(executed) => Some((executed.order_id, executed.fee)),
+                _ => None
+            }
+        )
+        .collect::<Vec<_>>();
+
+    // Aggressor broker order is charged the taker fee
+    place_asks(&mut exchange, &[(0, 100, 1)]);
+    let actions = place_broker_order(&mut exchange, limit_order(10, Direction::Buy, 100, 1));
+    assert_eq!(get_fees(&get_replies(&actions)), [(OrderID(10), Fee::from_ticks(0.05))]);
+
+    // Resting broker order is paid the maker rebate, whereas the replay order is not charged
+    place_broker_order(&mut exchange, limit_order(11, Direction::Sell, 105, 2));
+    let actions = replay(
+        &mut exchange,
+        BasicReplayRequest::PlaceLimitOrder(limit_order(1, Direction::Buy, 105, 2)),
+    );
+    assert_eq!(get_fees(&get_replies(&actions)), [(OrderID(11), Fee::from_ticks(-0.021))])
+}
+
+#[test]
+fn test_recovery_snapshot()
+{
+    let mut exchange = open_exchange(TestExchange::new(EXCHANGE).with_recovery_trades(2));
+    place_asks(&mut exchange, &[(0, 100, 1), (1, 101, 1), (2, 102, 5)]);
+    for (order_id, price) in [(10, 100), (11, 101), (12, 102)] {
+        place_broker_order(&mut exchange, limit_order(order_id, Direction::Buy, price, 1));
+    }
+    let recover = |exchange: &mut TestExchange, traded_pair| match get_replies(
+        &broker(exchange, BasicBrokerRequest::RequestRecoverySnapshot(traded_pair))
+    ).as_slice() {
+        [Reply::RecoverySnapshot(snapshot)] => Rc::clone(snapshot),
+        replies => panic!("Unexpected replies: {replies:?}")
+    };
+
+    // Only the last trades are sent along with the order book
+    let snapshot = recover(&mut exchange, pair());
+    assert!(snapshot.state.bids.is_empty());
+    assert_eq!(
+        snapshot.state.asks.iter()
+            .map(|(price, orders)| (*price, orders.iter().map(|(size, _)| *size).collect()))
+            .collect::<Vec<(Tick, Vec<Lots>)>>(),
+        [(Tick(102), vec![Lots(4)])]
+    );
+    assert_eq!(
+        snapshot.recent_trades.iter()
+            .map(|(_, trade)| (trade.direction, trade.price, trade.size))
+            .collect::<Vec<_>>(),
+        [(Direction::Buy, Tick(101), Lots(1)), (Direction::Buy, Tick(102), Lots(1))]
+    );
+
+    // Snapshot of the traded pair not traded yet is empty
+    let snapshot = recover(&mut exchange, symbol_pair("EUR"));
+    assert!(snapshot.state.bids.is_empty() && snapshot.state.asks.is_empty());
+    assert!(snapshot.recent_trades.is_empty())
+}
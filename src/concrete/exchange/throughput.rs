@@ -0,0 +1,64 @@
+use {
+    crate::{
+        concrete::{
+            message_protocol::exchange::reply::ThroughputCounts,
+            traded_pair::{settlement::GetSettlementLag, TradedPair},
+        },
+        types::{DateTime, Duration, Id},
+    },
+    std::collections::BTreeMap,
+};
+
+/// Counts of the order events in the traded pairs during the completed interval.
+pub(crate) struct CompletedInterval<Symbol: Id, Settlement: GetSettlementLag> {
+    pub start: DateTime,
+    pub end: DateTime,
+    pub counts: BTreeMap<TradedPair<Symbol, Settlement>, ThroughputCounts>,
+}
+
+/// Accumulates the order events of the traded pairs over the consecutive intervals.
+///
+/// Monitor is polled lazily, i.e. whenever the exchange processes a message,
+/// so the interval is published at the first message after it ends.
+/// Intervals are counted from the first poll. Intervals without events are skipped.
+pub(crate) struct ThroughputMonitor<Symbol: Id, Settlement: GetSettlementLag> {
+    interval: Duration,
+    interval_start: Option<DateTime>,
+    counts: BTreeMap<TradedPair<Symbol, Settlement>, ThroughputCounts>,
+}
+
+impl<Symbol: Id, Settlement: GetSettlementLag> ThroughputMonitor<Symbol, Settlement> {
+    pub fn new(interval: Duration) -> Self {
+        if interval <= Duration::zero() {
+            panic!("Throughput statistics interval should be positive. Got {interval}")
+        }
+        Self { interval, interval_start: None, counts: Default::default() }
+    }
+
+    pub fn get_counts_mut(
+        &mut self,
+        traded_pair: TradedPair<Symbol, Settlement>) -> &mut ThroughputCounts
+    {
+        self.counts.entry(traded_pair).or_default()
+    }
+
+    /// Returns the counts of the last interval if it has ended by the `current_dt`.
+    /// Should be called before the events occurring at the `current_dt` are counted.
+    pub fn poll(&mut self, current_dt: DateTime) -> Option<CompletedInterval<Symbol, Settlement>> {
+        let start = *self.interval_start.get_or_insert(current_dt);
+        let end = start + self.interval;
+        if current_dt < end {
+            return None;
+        }
+        let interval_nanos = self.interval.num_nanoseconds().unwrap_or(i64::MAX);
+        let elapsed = (current_dt - end).num_nanoseconds().unwrap_or(i64::MAX);
+        let skipped = elapsed / interval_nanos;
+        self.interval_start = Some(end + Duration::nanoseconds(skipped * interval_nanos));
+        let counts = std::mem::take(&mut self.counts);
+        if counts.is_empty() {
+            None
+        } else {
+            Some(CompletedInterval { start, end, counts })
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use {
+    crate::{
+        concrete::{
+            exchange::admin::PriceBand,
+            message_protocol::exchange::reply::VolatilityEstimate,
+            traded_pair::{settlement::GetSettlementLag, TradedPair},
+            types::Tick,
+        },
+        types::{DateTime, Duration, Id},
+    },
+    std::collections::{BTreeMap, VecDeque},
+};
+
+#[derive(Debug, Clone)]
+/// Rolling realized volatility of the single traded pair,
+/// i.e. the square root of the sum of the squared log returns between the consecutive trades
+/// made within the window.
+///
+/// Busted trades are not removed from the estimate.
+pub struct VolatilityEstimator {
+    window: Duration,
+    last_trade: Option<(DateTime, Tick)>,
+    /// Log returns within the window along with the datetimes of the trades ending them.
+    returns: VecDeque<(DateTime, f64)>,
+}
+
+impl VolatilityEstimator {
+    /// Creates a new instance of the `VolatilityEstimator`.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` — Length of the estimation window. Should be positive.
+    pub fn new(window: Duration) -> Self {
+        check_window(window);
+        Self { window, last_trade: None, returns: Default::default() }
+    }
+
+    /// Updates the estimate with the trade.
+    /// Trades at the non-positive prices only reset the last trade price.
+    ///
+    /// # Arguments
+    ///
+    /// * `datetime` — Datetime of the trade.
+    /// * `price` — Trade price.
+    pub fn record(&mut self, datetime: DateTime, price: Tick) {
+        if let Some((_, last_price)) = self.last_trade {
+            if last_price > Tick(0) && price > Tick(0) {
+                self.returns.push_back((datetime, (price.0 as f64 / last_price.0 as f64).ln()))
+            }
+        }
+        self.last_trade = Some((datetime, price));
+        let horizon = datetime - self.window;
+        while self.returns.front().is_some_and(|(return_dt, _)| *return_dt < horizon) {
+            self.returns.pop_front();
+        }
+    }
+
+    /// Returns the price of the last trade or `None` if there has been none.
+    pub fn get_last_price(&self) -> Option<Tick> {
+        self.last_trade.map(|(_, price)| price)
+    }
+
+    /// Returns the number of the returns within the window ending at the datetime.
+    ///
+    /// # Arguments
+    ///
+    /// * `current_dt` — End of the window.
+    pub fn get_num_returns(&self, current_dt: DateTime) -> usize {
+        self.iter_returns(current_dt).count()
+    }
+
+    /// Returns the realized volatility over the window ending at the datetime
+    /// or `None` if there are no returns within it.
+    ///
+    /// # Arguments
+    ///
+    /// * `current_dt` — End of the window.
+    pub fn get_volatility(&self, current_dt: DateTime) -> Option<f64> {
+        let mut returns = self.iter_returns(current_dt).peekable();
+        returns.peek()?;
+        Some(returns.map(|log_return| log_return * log_return).sum::<f64>().sqrt())
+    }
+
+    fn iter_returns(&self, current_dt: DateTime) -> impl Iterator<Item=f64> + '_ {
+        let horizon = current_dt - self.window;
+        self.returns.iter()
+            .filter(move |(return_dt, _)| *return_dt >= horizon)
+            .map(|(_, log_return)| *log_return)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Price band centered at the last trade price, which half-width is proportional
+/// to the realized volatility.
+/// Is applied on top of the static [`PriceBand`] of the traded pair.
+pub struct VolatilityBand {
+    /// Half-width of the band, in the realized volatilities.
+    pub multiplier: f64,
+    /// Minimum half-width of the band, e.g. to keep the market open while it is quiet.
+    pub min_half_width: Tick,
+    /// Minimum number of the returns within the window for the band to be applied.
+    pub min_num_returns: usize,
+}
+
+impl VolatilityBand {
+    /// Returns the band around the last trade price.
+    ///
+    /// # Arguments
+    ///
+    /// * `last_price` — Last trade price.
+    /// * `volatility` — Realized volatility.
+    pub fn get_price_band(&self, last_price: Tick, volatility: f64) -> PriceBand {
+        let half_width = ((last_price.0 as f64 * self.multiplier * volatility).ceil() as i64)
+            .max(self.min_half_width.0);
+        PriceBand {
+            lower: Some(last_price - Tick(half_width)),
+            upper: Some(last_price + Tick(half_width)),
+        }
+    }
+}
+
+/// Volatility estimators of the traded pairs shared by the trading rules of the exchange
+/// and published to the brokers.
+///
+/// Publication is polled lazily, i.e. whenever the exchange processes a message,
+/// so the estimates are published at the first message after the interval ends.
+pub(crate) struct VolatilityMonitor<Symbol: Id, Settlement: GetSettlementLag> {
+    window: Duration,
+    estimators: BTreeMap<TradedPair<Symbol, Settlement>, VolatilityEstimator>,
+    band: Option<VolatilityBand>,
+    publication_interval: Option<Duration>,
+    next_publication: Option<DateTime>,
+}
+
+impl<Symbol: Id, Settlement: GetSettlementLag> VolatilityMonitor<Symbol, Settlement> {
+    pub fn new(window: Duration) -> Self {
+        check_window(window);
+        Self {
+            window,
+            estimators: Default::default(),
+            band: None,
+            publication_interval: None,
+            next_publication: None,
+        }
+    }
+
+    pub fn set_band(&mut self, band: VolatilityBand) {
+        if !band.multiplier.is_finite() || band.multiplier < 0.0 {
+            panic!("Volatility band multiplier should be non-negative. Got {}", band.multiplier)
+        }
+        if band.min_half_width < Tick(0) {
+            panic!(
+                "Volatility band minimum half-width should be non-negative. Got {}",
+                band.min_half_width
+            )
+        }
+        self.band = Some(band)
+    }
+
+    pub fn set_publication_interval(&mut self, interval: Duration) {
+        if interval <= Duration::zero() {
+            panic!("Volatility publication interval should be positive. Got {interval}")
+        }
+        self.publication_interval = Some(interval)
+    }
+
+    pub fn get_estimator(
+        &self,
+        traded_pair: TradedPair<Symbol, Settlement>) -> Option<&VolatilityEstimator>
+    {
+        self.estimators.get(&traded_pair)
+    }
+
+    pub fn get_estimator_mut(
+        &mut self,
+        traded_pair: TradedPair<Symbol, Settlement>) -> &mut VolatilityEstimator
+    {
+        let window = self.window;
+        self.estimators.entry(traded_pair).or_insert_with(|| VolatilityEstimator::new(window))
+    }
+
+    /// Returns the volatility band of the traded pair at the `current_dt`, if any.
+    pub fn get_price_band(
+        &self,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        current_dt: DateTime) -> Option<PriceBand>
+    {
+        let band = self.band?;
+        let estimator = self.estimators.get(&traded_pair)?;
+        if estimator.get_num_returns(current_dt) < band.min_num_returns {
+            return None;
+        }
+        let volatility = estimator.get_volatility(current_dt)?;
+        Some(band.get_price_band(estimator.get_last_price()?, volatility))
+    }
+
+    /// Returns the estimates of the traded pairs with the returns within the window
+    /// if the publication interval has ended by the `current_dt`.
+    /// Intervals are counted from the first poll.
+    pub fn poll(&mut self, current_dt: DateTime) -> Vec<VolatilityEstimate<Symbol, Settlement>> {
+        let interval = if let Some(interval) = self.publication_interval {
+            interval
+        } else {
+            return Vec::new();
+        };
+        let next_publication = *self.next_publication.get_or_insert(current_dt + interval);
+        if current_dt < next_publication {
+            return Vec::new();
+        }
+        let interval_nanos = interval.num_nanoseconds().unwrap_or(i64::MAX);
+        let elapsed = (current_dt - next_publication).num_nanoseconds().unwrap_or(i64::MAX);
+        let skipped = elapsed / interval_nanos;
+        self.next_publication = Some(
+            next_publication + Duration::nanoseconds((skipped + 1) * interval_nanos)
+        );
+        self.estimators.iter()
+            .filter_map(
+                |(traded_pair, estimator)| Some(
+                    VolatilityEstimate {
+                        traded_pair: *traded_pair,
+                        datetime: current_dt,
+                        volatility_ppm: (estimator.get_volatility(current_dt)? * 1e6).round()
+                            as u64,
+                        num_returns: estimator.get_num_returns(current_dt) as u64,
+                        last_price: estimator.get_last_price()?,
+                    }
+                )
+            )
+            .collect()
+    }
+}
+
+fn check_window(window: Duration) {
+    if window <= Duration::zero() {
+        panic!("Volatility estimation window should be positive. Got {window}")
+    }
+}
@@ -0,0 +1,175 @@
+use {
+    crate::types::{DateTime, Duration, Id, NanoDuration},
+    std::collections::BTreeMap,
+};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Keep-alive message. The recipient echoes it back unchanged,
+/// so that the sender can measure the round-trip time
+/// and compare its clock with the datetime of the reply.
+pub struct Heartbeat {
+    /// Datetime the heartbeat is sent at, according to the clock of the sender.
+    pub sent_dt: DateTime,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+/// Parameters of the [`HeartbeatMonitor`].
+pub struct HeartbeatConfig {
+    /// Time between the consecutive heartbeats sent to the single peer.
+    pub interval: NanoDuration,
+    /// Time without the echoed heartbeats after which the peer is considered missing.
+    /// Should exceed the `interval` plus the round-trip time,
+    /// otherwise the peer is reported missing between the heartbeats.
+    pub timeout: NanoDuration,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+/// Result of the [`HeartbeatMonitor::poll`].
+pub struct HeartbeatPoll<PeerID: Id> {
+    /// Heartbeats to send, along with the delays to send them with.
+    pub due: Vec<(PeerID, Heartbeat, NanoDuration)>,
+    /// Peers that have just been found missing.
+    pub missed: Vec<PeerID>,
+}
+
+struct PeerState {
+    /// Datetime of the next heartbeat that has not been scheduled yet.
+    next_dt: DateTime,
+    /// Datetime the last echoed heartbeat is received at.
+    last_received_dt: DateTime,
+    /// Round-trip time of the last echoed heartbeat.
+    round_trip: Option<Duration>,
+    missed: bool,
+}
+
+/// Schedules the heartbeats sent to the peers and detects the missing ones.
+///
+/// Monitor is polled lazily, i.e. whenever its owner processes an event.
+/// Heartbeats are scheduled at most one interval ahead with the corresponding delays,
+/// so the echoed heartbeats keep the schedule going even if no other events occur.
+/// The peer is found missing at the first poll after the timeout elapses,
+/// so the owner should poll at the [`HeartbeatMonitor::get_next_deadline`] as well.
+pub struct HeartbeatMonitor<PeerID: Id> {
+    config: HeartbeatConfig,
+    peers: BTreeMap<PeerID, PeerState>,
+}
+
+impl<PeerID: Id> HeartbeatMonitor<PeerID> {
+    /// Creates a new instance of the `HeartbeatMonitor`.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` — Heartbeat interval and timeout.
+    pub fn new(config: HeartbeatConfig) -> Self {
+        if config.interval.is_zero() {
+            panic!("Heartbeat interval should be positive")
+        }
+        if config.timeout.is_zero() {
+            panic!("Heartbeat timeout should be positive")
+        }
+        Self { config, peers: Default::default() }
+    }
+
+    /// Returns the heartbeat interval and timeout.
+    pub fn get_config(&self) -> HeartbeatConfig {
+        self.config
+    }
+
+    /// Starts monitoring the peer. The first heartbeat is due immediately.
+    /// Does nothing if the peer is already monitored.
+    ///
+    /// # Arguments
+    ///
+    /// * `peer_id` — ID of the peer.
+    /// * `current_dt` — Current datetime.
+    pub fn add_peer(&mut self, peer_id: PeerID, current_dt: DateTime) {
+        self.peers.entry(peer_id).or_insert(
+            PeerState {
+                next_dt: current_dt,
+                last_received_dt: current_dt,
+                round_trip: None,
+                missed: false,
+            }
+        );
+    }
+
+    /// Schedules the heartbeats due within the next interval
+    /// and detects the peers that have gone missing. Peers are ordered by their IDs.
+    ///
+    /// # Arguments
+    ///
+    /// * `current_dt` — Current datetime.
+    pub fn poll(&mut self, current_dt: DateTime) -> HeartbeatPoll<PeerID> {
+        let HeartbeatConfig { interval, timeout } = self.config;
+        let mut poll = HeartbeatPoll { due: Vec::new(), missed: Vec::new() };
+        for (peer_id, peer) in &mut self.peers {
+            if !peer.missed && peer.last_received_dt + timeout <= current_dt {
+                peer.missed = true;
+                poll.missed.push(*peer_id)
+            }
+            if peer.next_dt < current_dt {
+                // Nothing has been scheduled for a while, so the skipped heartbeats are not sent
+                peer.next_dt = current_dt
+            }
+            if peer.next_dt <= current_dt + interval {
+                let delay = NanoDuration::from_duration(peer.next_dt - current_dt)
+                    .unwrap_or_else(
+                        || panic!("Cannot express the delay until {} in nanoseconds", peer.next_dt)
+                    );
+                poll.due.push((*peer_id, Heartbeat { sent_dt: peer.next_dt }, delay));
+                peer.next_dt = peer.next_dt + interval
+            }
+        }
+        poll
+    }
+
+    /// Registers the heartbeat echoed by the peer.
+    /// Returns `true` if the peer was missing and is now restored.
+    ///
+    /// # Arguments
+    ///
+    /// * `peer_id` — ID of the peer.
+    /// * `heartbeat` — Echoed heartbeat.
+    /// * `current_dt` — Current datetime.
+    pub fn receive(&mut self, peer_id: PeerID, heartbeat: Heartbeat, current_dt: DateTime) -> bool {
+        let peer = if let Some(peer) = self.peers.get_mut(&peer_id) {
+            peer
+        } else {
+            return false;
+        };
+        peer.last_received_dt = peer.last_received_dt.max(current_dt);
+        peer.round_trip = Some(current_dt - heartbeat.sent_dt);
+        let restored = peer.missed;
+        peer.missed = false;
+        restored
+    }
+
+    /// Returns the earliest datetime a peer that is not missing yet
+    /// is found missing at unless it echoes a heartbeat meanwhile.
+    /// `None` if all peers are missing.
+    pub fn get_next_deadline(&self) -> Option<DateTime> {
+        self.peers.values()
+            .filter(|peer| !peer.missed)
+            .map(|peer| peer.last_received_dt + self.config.timeout)
+            .min()
+    }
+
+    /// Returns whether the peer is considered missing.
+    ///
+    /// # Arguments
+    ///
+    /// * `peer_id` — ID of the peer.
+    pub fn is_missing(&self, peer_id: PeerID) -> bool {
+        self.peers.get(&peer_id).is_some_and(|peer| peer.missed)
+    }
+
+    /// Returns the round-trip time of the last heartbeat echoed by the peer.
+    ///
+    /// # Arguments
+    ///
+    /// * `peer_id` — ID of the peer.
+    pub fn get_round_trip(&self, peer_id: PeerID) -> Option<Duration> {
+        self.peers.get(&peer_id).and_then(|peer| peer.round_trip)
+    }
+}
@@ -1,4 +1,6 @@
 /// Utilities for creating entities from config structs and config files.
 pub mod config;
 /// Utilities for reading historical data from `OneTick`.
-pub mod one_tick;
\ No newline at end of file
+pub mod one_tick;
+/// Stochastic resting depth for replaying trades without the history of the limit orders.
+pub mod synthetic_book;
\ No newline at end of file
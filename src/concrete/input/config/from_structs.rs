@@ -2,9 +2,18 @@ use {
     crate::{
         concrete::{
             broker::BasicBroker,
-            exchange::BasicExchange,
-            input::one_tick::{OneTickTradedPairReader, OneTickTrdPrlConfig},
+            exchange::{
+                BasicExchange,
+                DuplicateOrderIdPolicy,
+                OffTickPricePolicy,
+                OrderSizeLimits,
+            },
+            input::{
+                one_tick::{OneTickEventTable, OneTickTradedPairReader, OneTickTrdPrlConfig},
+                synthetic_book::SyntheticBookConfig,
+            },
             replay::{
+                ExchangeDowntime,
                 ExchangeSession,
                 GetNextObSnapshotDelay,
                 OneTickReplay,
@@ -12,7 +21,7 @@ use {
             },
             traded_pair::{settlement::GetSettlementLag, TradedPair},
             trader::SpreadWriter,
-            types::TickSize,
+            types::{Tick, TickSize},
         },
         types::{DateTime, Id},
     },
@@ -31,6 +40,7 @@ pub struct OneTickTradedPairReaderConfig<ExchangeID, Symbol, Settlement>
     /// Traded pair.
     pub traded_pair: TradedPair<Symbol, Settlement>,
     /// Path to file containing paths to files with PRL-ticks.
+    /// Not read if the `synthetic_book` is set.
     pub prl_files: PathBuf,
     /// PRL-reader configuration.
     pub prl_args: OneTickTrdPrlConfig,
@@ -40,6 +50,71 @@ pub struct OneTickTradedPairReaderConfig<ExchangeID, Symbol, Settlement>
     pub trd_args: OneTickTrdPrlConfig,
     /// File for logging errors.
     pub err_log_file: Option<PathBuf>,
+    /// Whether to submit limit orders with their historical IDs.
+    pub preserve_order_ids: bool,
+    /// Already parsed TRD- and PRL-ticks. If set, the files are not read again.
+    pub event_table: Option<OneTickEventTable>,
+    /// Maximum number of the history files parsed in the background ahead of the replay.
+    /// If zero, the files are parsed in the replay thread.
+    pub prefetch_files: usize,
+    /// If set, only the trades are replayed against the synthetic resting depth
+    /// instead of the PRL-ticks.
+    pub synthetic_book: Option<SyntheticBookConfig>,
+}
+
+impl<ExchangeID, Symbol, Settlement>
+OneTickTradedPairReaderConfig<ExchangeID, Symbol, Settlement>
+    where ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    /// Parses the TRD- and PRL-ticks once so that all the readers
+    /// created from this config share them instead of reading the files.
+    pub fn with_shared_event_table(mut self) -> Self {
+        if self.event_table.is_none() {
+            self.event_table = Some(
+                if self.synthetic_book.is_some() {
+                    OneTickEventTable::trades_only(
+                        &self.trd_files,
+                        self.trd_args.clone().with_default_symbol(self.traded_pair),
+                    )
+                } else {
+                    OneTickEventTable::new(
+                        &self.prl_files,
+                        self.prl_args.clone().with_default_symbol(self.traded_pair),
+                        &self.trd_files,
+                        self.trd_args.clone().with_default_symbol(self.traded_pair),
+                    )
+                }
+            )
+        }
+        self
+    }
+
+    #[cfg(feature = "mmap")]
+    /// Same as the [`with_shared_event_table`](Self::with_shared_event_table)
+    /// but loads the TRD- and PRL-ticks from the binary cache file if it is up to date.
+    /// See [`OneTickEventTable::cached`].
+    ///
+    /// # Arguments
+    ///
+    /// * `cache_file` — Path to the binary cache file.
+    ///   Not used if the `synthetic_book` is set, since then only the TRD-ticks are parsed.
+    pub fn with_cached_event_table(mut self, cache_file: impl AsRef<Path>) -> Self {
+        if self.synthetic_book.is_some() {
+            return self.with_shared_event_table();
+        }
+        self.event_table = Some(
+            OneTickEventTable::cached(
+                cache_file,
+                &self.prl_files,
+                self.prl_args.clone().with_default_symbol(self.traded_pair),
+                &self.trd_files,
+                self.trd_args.clone().with_default_symbol(self.traded_pair),
+            )
+        );
+        self
+    }
 }
 
 impl<ExchangeID, Symbol, Settlement>
@@ -50,15 +125,40 @@ for OneTickTradedPairReader<ExchangeID, Symbol, Settlement>
           Settlement: GetSettlementLag
 {
     fn from(config: &OneTickTradedPairReaderConfig<ExchangeID, Symbol, Settlement>) -> Self {
-        OneTickTradedPairReader::new(
-            config.exchange_id,
-            config.traded_pair,
-            config.prl_files.clone(),
-            config.prl_args.clone(),
-            config.trd_files.clone(),
-            config.trd_args.clone(),
-            config.err_log_file.clone(),
-        )
+        if let Some(event_table) = &config.event_table {
+            let reader = OneTickTradedPairReader::from_event_table(
+                config.exchange_id,
+                config.traded_pair,
+                event_table,
+                config.err_log_file.clone(),
+            );
+            if let Some(synthetic_book) = config.synthetic_book {
+                reader.with_synthetic_depth(synthetic_book)
+            } else {
+                reader
+            }
+        } else if let Some(synthetic_book) = config.synthetic_book {
+            OneTickTradedPairReader::with_synthetic_book(
+                config.exchange_id,
+                config.traded_pair,
+                config.trd_files.clone(),
+                config.trd_args.clone(),
+                synthetic_book,
+                config.err_log_file.clone(),
+            )
+        } else {
+            OneTickTradedPairReader::new(
+                config.exchange_id,
+                config.traded_pair,
+                config.prl_files.clone(),
+                config.prl_args.clone(),
+                config.trd_files.clone(),
+                config.trd_args.clone(),
+                config.err_log_file.clone(),
+            )
+        }
+            .with_preserved_order_ids(config.preserve_order_ids)
+            .with_prefetch(config.prefetch_files)
     }
 }
 
@@ -72,16 +172,73 @@ pub struct OneTickReplayConfig<ExchangeID, Symbol, ObSnapshotDelay, Settlement>
 {
     /// Start datetime.
     pub start_dt: DateTime,
+    /// Datetime to start pre-loading the order books from.
+    /// If set, the order books are seeded with the history events
+    /// between this datetime and the `start_dt`.
+    pub book_seed_start: Option<DateTime>,
     /// Traded pair configs.
     pub traded_pair_configs: Vec<OneTickTradedPairReaderConfig<ExchangeID, Symbol, Settlement>>,
     /// Exchange sessions.
     pub exchange_open_close_events: Vec<ExchangeSession<ExchangeID>>,
     /// Traded pair lifetimes.
     pub traded_pair_lifetimes: Vec<TradedPairLifetime<ExchangeID, Symbol, Settlement>>,
+    /// Unplanned exchange downtime windows.
+    pub exchange_downtimes: Vec<ExchangeDowntime<ExchangeID>>,
     /// OB-snapshot delay scheduler.
     pub ob_snapshot_delay_scheduler: ObSnapshotDelay,
 }
 
+impl<ExchangeID, Symbol, ObSnapshotDelay, Settlement>
+OneTickReplayConfig<ExchangeID, Symbol, ObSnapshotDelay, Settlement>
+    where ExchangeID: Id,
+          Symbol: Id,
+          ObSnapshotDelay: GetNextObSnapshotDelay<ExchangeID, Symbol, Settlement>,
+          Settlement: GetSettlementLag
+{
+    /// Parses the history of every traded pair once so that all the replays
+    /// created from this config, e.g. in different threads, share it.
+    pub fn with_shared_event_tables(mut self) -> Self {
+        self.traded_pair_configs = self.traded_pair_configs.into_iter()
+            .map(OneTickTradedPairReaderConfig::with_shared_event_table)
+            .collect();
+        self
+    }
+
+    /// Makes the readers of every traded pair parse the next history files
+    /// in the background while the current ones are being replayed.
+    ///
+    /// # Arguments
+    ///
+    /// * `num_files` — Maximum number of the parsed files waiting to be replayed per reader.
+    pub fn with_prefetch(mut self, num_files: usize) -> Self {
+        for config in &mut self.traded_pair_configs {
+            config.prefetch_files = num_files
+        }
+        self
+    }
+
+    /// Whether the readers of the traded pairs of the exchange preserve the historical order IDs,
+    /// i.e. whether the exchange should be built
+    /// [`with_historical_order_ids`](BasicExchange::with_historical_order_ids).
+    /// Panics if the traded pairs of the exchange disagree on it.
+    ///
+    /// # Arguments
+    ///
+    /// * `exchange_id` — ID of the exchange.
+    pub fn preserves_order_ids(&self, exchange_id: ExchangeID) -> bool {
+        let mut configs = self.traded_pair_configs.iter()
+            .filter(|config| config.exchange_id == exchange_id);
+        let preserve_order_ids = configs.next().is_some_and(|config| config.preserve_order_ids);
+        if configs.any(|config| config.preserve_order_ids != preserve_order_ids) {
+            panic!(
+                "Traded pairs of the exchange {exchange_id} should either all preserve \
+                historical order IDs or none of them"
+            )
+        }
+        preserve_order_ids
+    }
+}
+
 impl<BrokerID, ExchangeID, Symbol, ObSnapshotDelay, Settlement>
 From<&OneTickReplayConfig<ExchangeID, Symbol, ObSnapshotDelay, Settlement>>
 for OneTickReplay<BrokerID, ExchangeID, Symbol, ObSnapshotDelay, Settlement>
@@ -94,11 +251,20 @@ for OneTickReplay<BrokerID, ExchangeID, Symbol, ObSnapshotDelay, Settlement>
     fn from(cfg: &OneTickReplayConfig<ExchangeID, Symbol, ObSnapshotDelay, Settlement>) -> Self {
         Self::new(
             cfg.start_dt,
-            cfg.traded_pair_configs.iter().map(From::from),
+            cfg.traded_pair_configs.iter().map(
+                |config| {
+                    let reader = OneTickTradedPairReader::from(config);
+                    if let Some(book_seed_start) = cfg.book_seed_start {
+                        reader.with_book_seeding(book_seed_start, cfg.start_dt)
+                    } else {
+                        reader
+                    }
+                }
+            ),
             cfg.exchange_open_close_events.iter().cloned(),
             cfg.traded_pair_lifetimes.iter().cloned(),
             cfg.ob_snapshot_delay_scheduler.clone(),
-        )
+        ).with_downtimes(cfg.exchange_downtimes.iter().cloned())
     }
 }
 
@@ -115,6 +281,51 @@ for BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
     }
 }
 
+#[derive(Clone)]
+/// Initializer-config for [`BasicExchange`].
+pub struct BasicExchangeConfig<ExchangeID, Symbol, Settlement>
+    where ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    /// ID of the `BasicExchange`.
+    pub name: ExchangeID,
+    /// Policy regarding limit orders with prices off the price increment grid.
+    pub off_tick_price_policy: OffTickPricePolicy,
+    /// Minimum price increments (in ticks) for traded pairs.
+    pub price_increments: Vec<(TradedPair<Symbol, Settlement>, Tick)>,
+    /// Order size restrictions for traded pairs.
+    pub size_limits: Vec<(TradedPair<Symbol, Settlement>, OrderSizeLimits)>,
+    /// Whether to keep the IDs of the limit orders submitted by the replay.
+    pub historical_order_ids: bool,
+    /// Policy regarding the reused order IDs.
+    pub duplicate_order_id_policy: DuplicateOrderIdPolicy,
+}
+
+impl<ExchangeID, BrokerID, Symbol, Settlement>
+From<&BasicExchangeConfig<ExchangeID, Symbol, Settlement>>
+for BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>
+    where ExchangeID: Id,
+          BrokerID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    fn from(cfg: &BasicExchangeConfig<ExchangeID, Symbol, Settlement>) -> Self {
+        let exchange = cfg.price_increments.iter().fold(
+            Self::new(cfg.name)
+                .with_off_tick_price_policy(cfg.off_tick_price_policy)
+                .with_historical_order_ids(cfg.historical_order_ids)
+                .with_duplicate_order_id_policy(cfg.duplicate_order_id_policy),
+            |exchange, (traded_pair, increment)|
+                exchange.with_price_increment(*traded_pair, *increment),
+        );
+        cfg.size_limits.iter().fold(
+            exchange,
+            |exchange, (traded_pair, limits)| exchange.with_size_limits(*traded_pair, *limits),
+        )
+    }
+}
+
 impl<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
 From<&BrokerID>
 for BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
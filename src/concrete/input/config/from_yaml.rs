@@ -7,8 +7,10 @@ use {
                     from_yaml::{config_fields::*, yaml_utils::*},
                 },
                 one_tick::OneTickTrdPrlConfig,
+                synthetic_book::SyntheticBookConfig,
             },
             replay::{
+                ExchangeDowntime,
                 ExchangeSession,
                 GetNextObSnapshotDelay,
                 TradedPairLifetime,
@@ -19,17 +21,18 @@ use {
         types::{
             DateTime,
             Id,
+            NanoDuration,
         },
+        utils::collections::HashMap,
     },
     csv::{ReaderBuilder, StringRecord},
     std::{
-        collections::HashMap,
         fs::read_to_string,
         iter::once,
         path::{Path, PathBuf},
         str::FromStr,
     },
-    yaml_rust::{Yaml, yaml::Hash, YamlLoader},
+    yaml_rust::{Yaml, yaml::{Array, Hash}, YamlLoader},
 };
 
 mod yaml_utils
@@ -125,6 +128,46 @@ mod yaml_utils
         }
     }
 
+    pub fn expect_yaml_bool(
+        yml: &Yaml,
+        path: &Path,
+        get_current_section: impl FnOnce() -> String) -> bool
+    {
+        match yml {
+            Yaml::Boolean(boolean) => *boolean,
+            Yaml::BadValue => panic!(
+                "{path:?} does not have \"{}\" section", get_current_section()
+            ),
+            _ => panic!(
+                "\"{}\" section of the {path:?} YAML file should be Boolean. Got {yml:?}",
+                get_current_section(),
+            )
+        }
+    }
+
+    pub fn expect_yaml_usize(
+        yml: &Yaml,
+        path: &Path,
+        get_current_section: impl FnOnce() -> String) -> usize
+    {
+        match yml {
+            Yaml::Integer(integer) => usize::try_from(*integer).unwrap_or_else(
+                |err| panic!(
+                    "\"{}\" section of the {path:?} YAML file should be non-negative. \
+                    Got {integer}. Error: {err}",
+                    get_current_section(),
+                )
+            ),
+            Yaml::BadValue => panic!(
+                "{path:?} does not have \"{}\" section", get_current_section()
+            ),
+            _ => panic!(
+                "\"{}\" section of the {path:?} YAML file should be Integer. Got {yml:?}",
+                get_current_section(),
+            )
+        }
+    }
+
     pub fn read_yaml_hashmap_field<'a>(
         map: &'a Hash,
         field: &str,
@@ -143,6 +186,8 @@ mod yaml_utils
     }
 
     #[derive(Debug, Clone, derive_more::From)]
+    // Numeric values are only printed in the error messages
+    #[allow(dead_code)]
     pub enum YamlValue {
         Real(f64),
         Integer(i64),
@@ -185,6 +230,8 @@ mod config_fields {
     pub const SIMULATION_TIME: &str = "Simulation Time";
     pub const EXCHANGES: &str = "Exchanges";
     pub const TRADED_PAIRS: &str = "Traded Pairs";
+    pub const TRADERS: &str = "Traders";
+    pub const BROKER: &str = "Broker";
 
     /// Can be set as defaults
     pub const DATETIME_FORMAT: &str = "datetime_format";
@@ -197,20 +244,26 @@ mod config_fields {
     pub const SIZE_COLNAME: &str = "size_colname";
     pub const PRICE_COLNAME: &str = "price_colname";
     pub const BUY_SELL_FLAG_COLNAME: &str = "buy_sell_flag_colname";
+    pub const SYMBOL_COLNAME: &str = "symbol_colname";
     pub const START_COLNAME: &str = "start_colname";
     pub const STOP_COLNAME: &str = "stop_colname";
 
     /// Simulation time specific fields
     pub const START: &str = "start";
     pub const END: &str = "end";
+    pub const BOOK_SEED_START: &str = "book_seed_start";
 
     /// Exchanges specific fields
     pub const NAME: &str = "name";
     pub const SESSIONS: &str = "sessions";
+    pub const DOWNTIMES: &str = "downtimes";
 
     /// Exchange session specific fields
     pub const PATH: &str = "path";
 
+    /// Exchange downtime specific fields
+    pub const CANCEL_RESTING_ORDERS: &str = "cancel_resting_orders";
+
     /// Traded Pairs specific fields
     pub const EXCHANGE: &str = "exchange";
     pub const KIND: &str = "kind";
@@ -218,12 +271,27 @@ mod config_fields {
     pub const BASE: &str = "base";
     pub const PRICE_STEP: &str = "price_step";
     pub const ERR_LOG_FILE: &str = "err_log_file";
+    pub const PRESERVE_ORDER_IDS: &str = "preserve_order_ids";
+    pub const PREFETCH_FILES: &str = "prefetch_files";
     pub const START_STOP_DATETIMES: &str = "start_stop_datetimes";
     pub const TRD: &str = "trd";
     pub const PRL: &str = "prl";
+    pub const SYNTHETIC_BOOK: &str = "synthetic_book";
+
+    /// Synthetic book specific fields
+    pub const NUM_LEVELS: &str = "num_levels";
+    pub const MEAN_ORDER_SIZE: &str = "mean_order_size";
+    pub const MEAN_LIFETIME: &str = "mean_lifetime";
+    pub const SEED: &str = "seed";
 
     /// TRD-PRL specific fields
     pub const PATH_LIST: &str = "path_list";
+    pub const MERGE_FILES: &str = "merge_files";
+
+    /// Traders specific fields
+    pub const TYPE: &str = "type";
+    pub const PARAMS: &str = "params";
+    pub const TAGS: &str = "tags";
 }
 
 mod defaults {
@@ -239,9 +307,39 @@ mod defaults {
 /// * `path` — Path to YAML-config.
 /// * `_traded_pair_parser` — Traded pair parser.
 /// * `ob_snapshot_delay_scheduler` — OB-snapshot delay scheduler to use by the
-///                                   [`OneTickReplay`](crate::concrete::replay).
+///   [`OneTickReplay`](crate::concrete::replay).
 pub fn parse_yaml<ExchangeID, Symbol, TPP, ObSnapshotDelay, Settlement>(
     path: impl AsRef<Path>,
+    traded_pair_parser: TPP,
+    ob_snapshot_delay_scheduler: ObSnapshotDelay,
+) -> (
+    Vec<ExchangeID>,
+    OneTickReplayConfig<ExchangeID, Symbol, ObSnapshotDelay, Settlement>,
+    DateTime,
+    DateTime
+)
+    where ExchangeID: Id + FromStr,
+          Symbol: Id + FromStr,
+          TPP: TradedPairParser<Symbol, Settlement>,
+          ObSnapshotDelay: GetNextObSnapshotDelay<ExchangeID, Symbol, Settlement>,
+          Settlement: GetSettlementLag
+{
+    parse_yaml_with_overrides(path, &[], traded_pair_parser, ob_snapshot_delay_scheduler)
+}
+
+/// Same as the [`parse_yaml`], but applies the [`ConfigOverrides`](ConfigOverride)
+/// to the YAML-config before parsing it.
+///
+/// # Arguments
+///
+/// * `path` — Path to YAML-config.
+/// * `overrides` — Overrides to apply in the given order.
+/// * `_traded_pair_parser` — Traded pair parser.
+/// * `ob_snapshot_delay_scheduler` — OB-snapshot delay scheduler to use by the
+///   [`OneTickReplay`](crate::concrete::replay).
+pub fn parse_yaml_with_overrides<ExchangeID, Symbol, TPP, ObSnapshotDelay, Settlement>(
+    path: impl AsRef<Path>,
+    overrides: &[ConfigOverride],
     _traded_pair_parser: TPP,
     ob_snapshot_delay_scheduler: ObSnapshotDelay,
 ) -> (
@@ -256,19 +354,17 @@ pub fn parse_yaml<ExchangeID, Symbol, TPP, ObSnapshotDelay, Settlement>(
           ObSnapshotDelay: GetNextObSnapshotDelay<ExchangeID, Symbol, Settlement>,
           Settlement: GetSettlementLag
 {
-    const POSSIBLE_SECTIONS: [&str; 4] = [
+    const POSSIBLE_SECTIONS: [&str; 6] = [
         DEFAULTS,
         SIMULATION_TIME,
         EXCHANGES,
-        TRADED_PAIRS
+        TRADED_PAIRS,
+        TRADERS,
+        BROKER
     ];
 
     let path = path.as_ref();
-    let yml = read_to_string(path)
-        .unwrap_or_else(|err| panic!("Cannot read the following file: {path:?}. Error: {err}"));
-    let yml = YamlLoader::load_from_str(&yml)
-        .unwrap_or_else(|err| panic!("Bad YAML file: {path:?}. Error: {err}"));
-    let yml = &yml[0];
+    let yml = &load_yaml(path, overrides);
 
     let cwd = std::env::current_dir().expect("Cannot get current working directory");
     let parent_dir = path.parent().unwrap_or_else(
@@ -297,11 +393,20 @@ pub fn parse_yaml<ExchangeID, Symbol, TPP, ObSnapshotDelay, Settlement>(
     let mut defaults = init_defaults();
 
     parse_defaults_section(yml, path, &mut defaults);
-    let (start, end) = parse_simulation_time_section(yml, path, defaults.clone());
+    let (start, end, book_seed_start) = parse_simulation_time_section(
+        yml, path, defaults.clone(),
+    );
 
-    let (exchanges, sessions): (_, Vec<_>) = parse_exchanges_section(yml, path, &defaults)
-        .into_iter()
-        .unzip();
+    let mut exchanges = Vec::new();
+    let mut sessions = Vec::new();
+    let mut downtimes = Vec::new();
+    for (exchange, exchange_sessions, exchange_downtimes) in
+    parse_exchanges_section(yml, path, &defaults)
+    {
+        exchanges.push(exchange);
+        sessions.extend(exchange_sessions);
+        downtimes.extend(exchange_downtimes)
+    }
 
     let (traded_pair_readers, start_stop_events): (Vec<_>, Vec<_>) =
         parse_traded_pairs_section::<ExchangeID, Symbol, Settlement, TPP>(yml, path, defaults)
@@ -311,18 +416,384 @@ pub fn parse_yaml<ExchangeID, Symbol, TPP, ObSnapshotDelay, Settlement>(
         |err| panic!("Cannot set current working directory to {cwd:?}. Error: {err}")
     );
 
-    (
-        exchanges,
-        OneTickReplayConfig {
-            start_dt: start,
-            traded_pair_configs: traded_pair_readers,
-            exchange_open_close_events: sessions.into_iter().flatten().collect(),
-            traded_pair_lifetimes: start_stop_events.into_iter().flatten().collect(),
-            ob_snapshot_delay_scheduler,
-        },
-        start,
-        end
-    )
+    let replay_config = OneTickReplayConfig {
+        start_dt: start,
+        book_seed_start,
+        traded_pair_configs: traded_pair_readers,
+        exchange_open_close_events: sessions,
+        traded_pair_lifetimes: start_stop_events.into_iter().flatten().collect(),
+        exchange_downtimes: downtimes,
+        ob_snapshot_delay_scheduler,
+    };
+    // Exchange keeps either all the historical order IDs or none of them
+    for exchange in &exchanges {
+        replay_config.preserves_order_ids(*exchange);
+    }
+
+    (exchanges, replay_config, start, end)
+}
+
+/// Reads the YAML-config and applies the overrides to it.
+fn load_yaml(path: &Path, overrides: &[ConfigOverride]) -> Yaml {
+    let yml = read_to_string(path)
+        .unwrap_or_else(|err| panic!("Cannot read the following file: {path:?}. Error: {err}"));
+    let mut yml = YamlLoader::load_from_str(&yml)
+        .unwrap_or_else(|err| panic!("Bad YAML file: {path:?}. Error: {err}"))
+        .swap_remove(0);
+    for config_override in overrides {
+        config_override.apply(&mut yml, path)
+    }
+    yml
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Override of the single parameter of the YAML-config,
+/// so that the jobs could vary the parameters without generating the configs.
+///
+/// Parsed from the `<PATH>=<VALUE>` string, e.g. `Traders.0.params.price_step=0.001`.
+/// Path consists of the dot-separated section names and the zero-based array indices.
+/// Value is parsed as the YAML scalar, e.g. `0.001` is Real and `"0.001"` is String.
+///
+/// # Examples
+///
+/// ```
+/// use {trading_backtester::concrete::input::config::from_yaml::ConfigOverride, yaml_rust::Yaml};
+///
+/// let config_override: ConfigOverride = "Traders.0.params.price_step=0.001".parse().unwrap();
+/// assert_eq!(config_override.path, ["Traders", "0", "params", "price_step"]);
+/// assert_eq!(config_override.value, Yaml::Real("0.001".into()));
+/// ```
+pub struct ConfigOverride {
+    /// Path to the parameter.
+    pub path: Vec<String>,
+    /// New value of the parameter.
+    pub value: Yaml,
+}
+
+impl FromStr for ConfigOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (path, value) = s.split_once('=').ok_or_else(
+            || format!("Config override should be of the <PATH>=<VALUE> form. Got: {s}")
+        )?;
+        let path: Vec<String> = path.split('.').map(String::from).collect();
+        if path.iter().any(String::is_empty) {
+            return Err(format!("Config override path contains empty sections. Got: {s}"));
+        }
+        let value = YamlLoader::load_from_str(value)
+            .map_err(|err| format!("Bad value of the config override {s}. Error: {err}"))?
+            .into_iter()
+            .next()
+            .unwrap_or(Yaml::Null);
+        Ok(Self { path, value })
+    }
+}
+
+impl ConfigOverride {
+    /// Sets the parameter of the YAML-config to the override value.
+    /// Missing named entries are created, whereas the array indices should exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `yml` — Parsed YAML-config.
+    /// * `path` — Path to YAML-config.
+    pub fn apply(&self, yml: &mut Yaml, path: &Path) {
+        let mut target = yml;
+        for (depth, section) in self.path.iter().enumerate() {
+            let get_current_section = || self.path[..=depth].join(" :: ");
+            target = match target {
+                Yaml::Hash(map) => map.entry(Yaml::String(section.clone())).or_insert(Yaml::Null),
+                Yaml::Array(array) => {
+                    let len = array.len();
+                    section.parse::<usize>().ok()
+                        .and_then(|index| array.get_mut(index))
+                        .unwrap_or_else(
+                            || panic!(
+                                "Cannot override \"{}\" section of the {path:?} YAML file. \
+                                Expected an index of the array of {len} entries",
+                                get_current_section()
+                            )
+                        )
+                }
+                Yaml::Null => {
+                    *target = Yaml::Hash(Hash::new());
+                    let Yaml::Hash(map) = target else { unreachable!() };
+                    map.entry(Yaml::String(section.clone())).or_insert(Yaml::Null)
+                }
+                _ => panic!(
+                    "Cannot override \"{}\" section of the {path:?} YAML file, \
+                    since its parent is neither named entries nor an array. Got {target:?}",
+                    get_current_section()
+                )
+            }
+        }
+        *target = self.value.clone()
+    }
+}
+
+/// Constructs a trader from the `params` block of the trader entry of the YAML-config.
+pub trait TraderFactory<TraderID: Id, T> {
+    /// Creates a new trader.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` — ID of the trader.
+    /// * `params` — `params` block of the trader entry or [`Yaml::BadValue`] if it is absent.
+    fn create_trader(&self, name: TraderID, params: &Yaml) -> T;
+}
+
+impl<TraderID, T, F> TraderFactory<TraderID, T> for F
+    where TraderID: Id,
+          F: Fn(TraderID, &Yaml) -> T
+{
+    fn create_trader(&self, name: TraderID, params: &Yaml) -> T {
+        self(name, params)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Trader entry of the YAML-config.
+pub struct YamlTraderConfig<TraderID: Id> {
+    /// ID of the trader.
+    pub name: TraderID,
+    /// Trader type. Key of the [`TraderFactory`] in the [`TraderRegistry`].
+    pub kind: String,
+    /// `params` block of the trader entry. [`Yaml::BadValue`] if the block is absent.
+    pub params: Yaml,
+    /// Arbitrary tags of the trader, e.g. `cohort=A`, to group the results by.
+    /// Empty if the `tags` list is absent.
+    pub tags: Vec<String>,
+}
+
+/// Registry of [`TraderFactories`](TraderFactory) keyed by the trader `type` string.
+pub struct TraderRegistry<TraderID: Id, T> {
+    factories: HashMap<String, Box<dyn TraderFactory<TraderID, T> + Send + Sync>>,
+}
+
+impl<TraderID: Id, T> Default for TraderRegistry<TraderID, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<TraderID: Id, T> TraderRegistry<TraderID, T>
+{
+    /// Creates a new empty instance of the `TraderRegistry`.
+    pub fn new() -> Self {
+        Self { factories: Default::default() }
+    }
+
+    /// Registers the [`TraderFactory`] for the trader type.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` — Trader type.
+    /// * `factory` — Factory to construct traders of the given type.
+    pub fn with_factory(
+        mut self,
+        kind: impl Into<String>,
+        factory: impl TraderFactory<TraderID, T> + Send + Sync + 'static) -> Self
+    {
+        let kind = kind.into();
+        if self.factories.contains_key(&kind) {
+            panic!("TraderFactory for the trader type \"{kind}\" is already registered")
+        }
+        self.factories.insert(kind, Box::new(factory));
+        self
+    }
+
+    /// Whether the [`TraderFactory`] for the trader type is registered.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` — Trader type.
+    pub fn is_registered(&self, kind: &str) -> bool {
+        self.factories.contains_key(kind)
+    }
+
+    /// Creates a new trader from its YAML-config entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` — Trader entry of the YAML-config.
+    pub fn create_trader(&self, config: &YamlTraderConfig<TraderID>) -> T {
+        let factory = self.factories.get(&config.kind).unwrap_or_else(
+            || panic!(
+                "Cannot find TraderFactory for the trader type \"{}\". Registered types: {:?}",
+                config.kind,
+                self.factories.keys().collect::<Vec<_>>()
+            )
+        );
+        factory.create_trader(config.name, &config.params)
+    }
+}
+
+/// Parses "Traders" section of the YAML-config.
+/// Returns an empty vector if there is no such section.
+///
+/// # Arguments
+///
+/// * `path` — Path to YAML-config.
+pub fn parse_traders_yaml<TraderID: Id + FromStr>(
+    path: impl AsRef<Path>) -> Vec<YamlTraderConfig<TraderID>>
+{
+    parse_traders_yaml_with_overrides(path, &[])
+}
+
+/// Same as the [`parse_traders_yaml`], but applies the [`ConfigOverrides`](ConfigOverride)
+/// to the YAML-config before parsing it.
+///
+/// # Arguments
+///
+/// * `path` — Path to YAML-config.
+/// * `overrides` — Overrides to apply in the given order.
+pub fn parse_traders_yaml_with_overrides<TraderID: Id + FromStr>(
+    path: impl AsRef<Path>,
+    overrides: &[ConfigOverride]) -> Vec<YamlTraderConfig<TraderID>>
+{
+    const POSSIBLE_KEYS: [&str; 4] = [
+        NAME,
+        TYPE,
+        PARAMS,
+        TAGS
+    ];
+    const SECTION: &str = TRADERS;
+    const FULL_SECTION_PATH: fn() -> String = || SECTION.into();
+
+    let path = path.as_ref();
+    let yml = &load_yaml(path, overrides);
+
+    let traders = match &yml[SECTION] {
+        Yaml::BadValue => return Vec::new(),
+        traders => expect_yaml_array(traders, path, FULL_SECTION_PATH)
+    };
+    traders.iter().zip(1..).map(
+        |(trader, i)| {
+            let get_current_section = || format!("{SECTION} :: {i}");
+            let trader = expect_yaml_hashmap(trader, path, get_current_section);
+
+            for key in trader.keys() {
+                let get_current_section = || format!("{SECTION} :: {i} :: {key:?}");
+                let key = expect_yaml_string(key, path, get_current_section);
+                if !POSSIBLE_KEYS.contains(&key.as_str()) {
+                    panic!(
+                        "\"{key}\" cannot be present in the \"{}\" section. \
+                        Possible keys: {POSSIBLE_KEYS:?}",
+                        get_current_section()
+                    )
+                }
+            }
+
+            let field = NAME;
+            let full_section_path = || format!("{SECTION} :: {i} :: {field}");
+            let name = match read_yaml_hashmap_field(trader, field, path, full_section_path) {
+                Yaml::Integer(name) => name.to_string(),
+                name => expect_yaml_string(name, path, full_section_path).clone()
+            };
+            let name = FromStr::from_str(&name).unwrap_or_else(
+                |_| panic!(
+                    "Section \"{}\". Cannot parse \"{name}\" to TraderID",
+                    full_section_path()
+                )
+            );
+
+            let field = TYPE;
+            let full_section_path = || format!("{SECTION} :: {i} :: {field}");
+            let kind = read_yaml_hashmap_field(trader, field, path, full_section_path);
+            let kind = expect_yaml_string(kind, path, full_section_path).clone();
+
+            let params = try_read_yaml_hashmap_field(trader, PARAMS)
+                .cloned()
+                .unwrap_or(Yaml::BadValue);
+
+            let field = TAGS;
+            let full_section_path = || format!("{SECTION} :: {i} :: {field}");
+            let tags = try_read_yaml_hashmap_field(trader, field).map_or_else(
+                Vec::new,
+                |tags| expect_yaml_array(tags, path, full_section_path).iter()
+                    .map(|tag| expect_yaml_string(tag, path, full_section_path).clone())
+                    .collect(),
+            );
+
+            YamlTraderConfig { name, kind, params, tags }
+        }
+    ).collect()
+}
+
+#[derive(Debug, Clone)]
+/// Broker entry of the YAML-config.
+pub struct YamlBrokerConfig<BrokerID: Id> {
+    /// ID of the broker.
+    pub name: BrokerID,
+    /// `params` block of the broker entry. [`Yaml::BadValue`] if the block is absent.
+    pub params: Yaml,
+}
+
+/// Parses "Broker" section of the YAML-config.
+/// Returns `None` if there is no such section.
+///
+/// # Arguments
+///
+/// * `path` — Path to YAML-config.
+pub fn parse_broker_yaml<BrokerID: Id + FromStr>(
+    path: impl AsRef<Path>) -> Option<YamlBrokerConfig<BrokerID>>
+{
+    parse_broker_yaml_with_overrides(path, &[])
+}
+
+/// Same as the [`parse_broker_yaml`], but applies the [`ConfigOverrides`](ConfigOverride)
+/// to the YAML-config before parsing it.
+///
+/// # Arguments
+///
+/// * `path` — Path to YAML-config.
+/// * `overrides` — Overrides to apply in the given order.
+pub fn parse_broker_yaml_with_overrides<BrokerID: Id + FromStr>(
+    path: impl AsRef<Path>,
+    overrides: &[ConfigOverride]) -> Option<YamlBrokerConfig<BrokerID>>
+{
+    const POSSIBLE_KEYS: [&str; 2] = [
+        NAME,
+        PARAMS
+    ];
+    const SECTION: &str = BROKER;
+    const FULL_SECTION_PATH: fn() -> String = || SECTION.into();
+
+    let path = path.as_ref();
+    let yml = &load_yaml(path, overrides);
+
+    let broker = try_expect_yaml_hashmap(&yml[SECTION], path, FULL_SECTION_PATH)?;
+    for key in broker.keys() {
+        let get_current_section = || format!("{SECTION} :: {key:?}");
+        let key = expect_yaml_string(key, path, get_current_section);
+        if !POSSIBLE_KEYS.contains(&key.as_str()) {
+            panic!(
+                "\"{key}\" cannot be present in the \"{}\" section. \
+                Possible keys: {POSSIBLE_KEYS:?}",
+                get_current_section()
+            )
+        }
+    }
+
+    let field = NAME;
+    let full_section_path = || format!("{SECTION} :: {field}");
+    let name = match read_yaml_hashmap_field(broker, field, path, full_section_path) {
+        Yaml::Integer(name) => name.to_string(),
+        name => expect_yaml_string(name, path, full_section_path).clone()
+    };
+    let name = FromStr::from_str(&name).unwrap_or_else(
+        |_| panic!(
+            "Section \"{}\". Cannot parse \"{name}\" to BrokerID",
+            full_section_path()
+        )
+    );
+
+    let params = try_read_yaml_hashmap_field(broker, PARAMS)
+        .cloned()
+        .unwrap_or(Yaml::BadValue);
+
+    Some(YamlBrokerConfig { name, params })
 }
 
 type Env = HashMap<String, YamlValue>;
@@ -364,7 +835,7 @@ fn update_env<const KEYS_NUM: usize>(
 
 fn parse_defaults_section(yaml: &Yaml, path: &Path, defaults: &mut Env)
 {
-    const POSSIBLE_KEYS: [&str; 12] = [
+    const POSSIBLE_KEYS: [&str; 13] = [
         DATETIME_FORMAT,
         CSV_SEP,
         OPEN_COLNAME,
@@ -375,6 +846,7 @@ fn parse_defaults_section(yaml: &Yaml, path: &Path, defaults: &mut Env)
         PRICE_COLNAME,
         SIZE_COLNAME,
         BUY_SELL_FLAG_COLNAME,
+        SYMBOL_COLNAME,
         START_COLNAME,
         STOP_COLNAME
     ];
@@ -391,12 +863,13 @@ fn parse_defaults_section(yaml: &Yaml, path: &Path, defaults: &mut Env)
 fn parse_simulation_time_section(
     yaml: &Yaml,
     path: &Path,
-    mut env: Env) -> (DateTime, DateTime)
+    mut env: Env) -> (DateTime, DateTime, Option<DateTime>)
 {
-    const POSSIBLE_KEYS: [&str; 3] = [
+    const POSSIBLE_KEYS: [&str; 4] = [
         DATETIME_FORMAT,
         START,
         END,
+        BOOK_SEED_START,
     ];
     const SECTION: &str = SIMULATION_TIME;
     const FULL_SECTION_PATH: fn() -> String = || SECTION.into();
@@ -458,17 +931,47 @@ fn parse_simulation_time_section(
         )
     );
 
-    (start, end)
+    let field = BOOK_SEED_START;
+    let get_current_section = || format!("{SECTION} :: {field}");
+    let book_seed_start = env.get(field).map(
+        |book_seed_start| {
+            let book_seed_start = if let YamlValue::String(book_seed_start) = book_seed_start {
+                book_seed_start.as_str()
+            } else {
+                panic!("\"{}\" should be String. Got: {book_seed_start:?}", get_current_section())
+            };
+            let book_seed_start = DateTime::parse_from_str(book_seed_start, datetime_format)
+                .unwrap_or_else(
+                    |err| panic!(
+                        "Section \"{}\". Cannot parse to DateTime: \"{book_seed_start}\". \
+                        Datetime format used: \"{datetime_format}\". Error: {err}",
+                        get_current_section()
+                    )
+                );
+            if book_seed_start > start {
+                panic!(
+                    "Section \"{}\". {book_seed_start} should not be greater than {start}",
+                    get_current_section()
+                )
+            }
+            book_seed_start
+        }
+    );
+
+    (start, end, book_seed_start)
 }
 
 fn parse_exchanges_section<'a, ExchangeID: Id + FromStr>(
     yaml: &'a Yaml,
     path: &'a Path,
-    env: &'a Env) -> impl 'a + IntoIterator<Item=(ExchangeID, Vec<ExchangeSession<ExchangeID>>)>
+    env: &'a Env) -> impl 'a + IntoIterator<
+    Item=(ExchangeID, Vec<ExchangeSession<ExchangeID>>, Vec<ExchangeDowntime<ExchangeID>>)
+>
 {
-    const POSSIBLE_KEYS: [&str; 2] = [
+    const POSSIBLE_KEYS: [&str; 3] = [
         NAME,
-        SESSIONS
+        SESSIONS,
+        DOWNTIMES
     ];
     const SECTION: &str = EXCHANGES;
     const FULL_SECTION_PATH: fn() -> String = || SECTION.into();
@@ -508,11 +1011,95 @@ fn parse_exchanges_section<'a, ExchangeID: Id + FromStr>(
             let sessions = parse_exchange_sessions(
                 sessions, name, path, env.clone(), &full_section_path,
             );
-            (name, sessions)
+
+            let field = DOWNTIMES;
+            let full_section_path = || format!("{SECTION} :: {i} :: {field}");
+            let downtimes = try_read_yaml_hashmap_field(exchange, field)
+                .map(
+                    |downtimes| {
+                        let downtimes = expect_yaml_array(downtimes, path, full_section_path);
+                        parse_exchange_downtimes(downtimes, name, path, env, full_section_path)
+                    }
+                )
+                .unwrap_or_default();
+            (name, sessions, downtimes)
         }
     )
 }
 
+fn parse_exchange_downtimes<ExchangeID: Id>(
+    yaml: &Array,
+    name: ExchangeID,
+    path: &Path,
+    env: &Env,
+    full_section_path: impl Copy + Fn() -> String) -> Vec<ExchangeDowntime<ExchangeID>>
+{
+    const POSSIBLE_KEYS: [&str; 4] = [
+        DATETIME_FORMAT,
+        START,
+        END,
+        CANCEL_RESTING_ORDERS
+    ];
+
+    yaml.iter().zip(1..).map(
+        |(downtime, i)| {
+            let section = || format!("{} :: {i}", full_section_path());
+            let mut env = env.clone();
+            update_env(
+                expect_yaml_hashmap(downtime, path, section),
+                &mut env, path, section, POSSIBLE_KEYS,
+            );
+
+            let field = DATETIME_FORMAT;
+            let datetime_format = env
+                .get(field)
+                .unwrap_or_else(
+                    || unreachable!("Section \"{}\" should contain \"{field}\" value", section())
+                );
+
+            let get_current_section = || format!("{} :: {field}", section());
+            let datetime_format = if let YamlValue::String(v) = datetime_format {
+                v.as_str()
+            } else {
+                panic!("\"{}\" should be String. Got: {datetime_format:?}", get_current_section())
+            };
+
+            let parse_datetime = |field| {
+                let get_current_section = || format!("{} :: {field}", section());
+                let datetime = env.get(field).unwrap_or_else(
+                    || panic!("Section \"{}\" should contain \"{field}\" value", section())
+                );
+                let datetime = if let YamlValue::String(datetime) = datetime {
+                    datetime.as_str()
+                } else {
+                    panic!("\"{}\" should be String. Got: {datetime:?}", get_current_section())
+                };
+                DateTime::parse_from_str(datetime, datetime_format).unwrap_or_else(
+                    |err| panic!(
+                        "Section \"{}\". Cannot parse to DateTime: \"{datetime}\". \
+                        Datetime format used: \"{datetime_format}\". Error: {err}",
+                        get_current_section()
+                    )
+                )
+            };
+            let start_dt = parse_datetime(START);
+            let end_dt = parse_datetime(END);
+
+            let field = CANCEL_RESTING_ORDERS;
+            let get_current_section = || format!("{} :: {field}", section());
+            let cancel_resting_orders = match env.get(field) {
+                None => false,
+                Some(YamlValue::Boolean(cancel)) => *cancel,
+                Some(cancel) => panic!(
+                    "\"{}\" should be Boolean. Got: {cancel:?}", get_current_section()
+                )
+            };
+
+            ExchangeDowntime { exchange_id: name, start_dt, end_dt, cancel_resting_orders }
+        }
+    ).collect()
+}
+
 fn parse_exchange_sessions<ExchangeID: Id>(
     yaml: &Hash,
     name: ExchangeID,
@@ -730,7 +1317,7 @@ fn parse_traded_pairs_section<
         Vec<TradedPairLifetime<ExchangeID, Symbol, Settlement>>
     )
 > {
-    const POSSIBLE_KEYS: [&str; 9] = [
+    const POSSIBLE_KEYS: [&str; 12] = [
         EXCHANGE,
         KIND,
         QUOTED,
@@ -738,8 +1325,11 @@ fn parse_traded_pairs_section<
         PRICE_STEP,
         START_STOP_DATETIMES,
         ERR_LOG_FILE,
+        PRESERVE_ORDER_IDS,
+        PREFETCH_FILES,
         TRD,
         PRL,
+        SYNTHETIC_BOOK,
     ];
     const SECTION: &str = "Traded Pairs";
     const FULL_SECTION_PATH: fn() -> String = || SECTION.into();
@@ -813,6 +1403,18 @@ fn parse_traded_pairs_section<
                 None
             };
 
+            let field = PRESERVE_ORDER_IDS;
+            let full_section_path = || format!("{SECTION} :: {i} :: {field}");
+            let preserve_order_ids = try_read_yaml_hashmap_field(map, field)
+                .map(|preserve| expect_yaml_bool(preserve, path, full_section_path))
+                .unwrap_or(false);
+
+            let field = PREFETCH_FILES;
+            let full_section_path = || format!("{SECTION} :: {i} :: {field}");
+            let prefetch_files = try_read_yaml_hashmap_field(map, field)
+                .map(|num_files| expect_yaml_usize(num_files, path, full_section_path))
+                .unwrap_or(0);
+
             let traded_pair = TPParser::parse(exchange, kind, quoted, base);
 
             let field = START_STOP_DATETIMES;
@@ -824,10 +1426,14 @@ fn parse_traded_pairs_section<
                 env.clone(), path, full_section_path,
             );
 
-            let traded_pair_reader = gen_traded_pair_reader(
-                map, traded_pair, price_step, exchange,
-                env.clone(), path, get_current_section, err_log_file,
-            );
+            let traded_pair_reader = OneTickTradedPairReaderConfig {
+                err_log_file,
+                preserve_order_ids,
+                prefetch_files,
+                ..gen_traded_pair_reader(
+                    map, traded_pair, price_step, exchange, env.clone(), path, get_current_section,
+                )
+            };
 
             (traded_pair_reader, trade_start_stops)
         }
@@ -1076,7 +1682,7 @@ fn gen_traded_pair_reader<
     env: HashMap<String, YamlValue>,
     path: &Path,
     get_current_section: impl Fn() -> String,
-    err_log_file: Option<PathBuf>) -> OneTickTradedPairReaderConfig<ExchangeID, Symbol, Settlement>
+) -> OneTickTradedPairReaderConfig<ExchangeID, Symbol, Settlement>
 {
     let field = TRD;
     let full_section_path = || format!("{} :: {field}", get_current_section());
@@ -1087,15 +1693,33 @@ fn gen_traded_pair_reader<
         trd, env.clone(), price_step, path, full_section_path,
     );
 
-    let field = PRL;
+    let field = SYNTHETIC_BOOK;
     let full_section_path = || format!("{} :: {field}", get_current_section());
-    let prl = read_yaml_hashmap_field(map, field, path, full_section_path);
-    let prl = expect_yaml_hashmap(prl, path, full_section_path);
-
-    let (prl_files, prl_parsing_info) = gen_trd_prl_config::<_, false>(
-        prl, env, price_step, path, full_section_path,
+    let synthetic_book = try_read_yaml_hashmap_field(map, field).map(
+        |synthetic_book| {
+            let synthetic_book = expect_yaml_hashmap(synthetic_book, path, full_section_path);
+            parse_synthetic_book(synthetic_book, path, full_section_path)
+        }
     );
 
+    let field = PRL;
+    let full_section_path = || format!("{} :: {field}", get_current_section());
+    let (prl_files, prl_parsing_info) = if synthetic_book.is_some() {
+        if try_read_yaml_hashmap_field(map, field).is_some() {
+            panic!(
+                "\"{field}\" cannot be present in the \"{}\" section \
+                along with the \"{SYNTHETIC_BOOK}\"",
+                get_current_section()
+            )
+        }
+        // PRL-ticks are not read in the synthetic mode
+        (PathBuf::new(), trd_parsing_info.clone())
+    } else {
+        let prl = read_yaml_hashmap_field(map, field, path, full_section_path);
+        let prl = expect_yaml_hashmap(prl, path, full_section_path);
+        gen_trd_prl_config::<_, false>(prl, env, price_step, path, full_section_path)
+    };
+
     OneTickTradedPairReaderConfig {
         exchange_id,
         traded_pair,
@@ -1103,10 +1727,59 @@ fn gen_traded_pair_reader<
         prl_args: prl_parsing_info,
         trd_files,
         trd_args: trd_parsing_info,
-        err_log_file,
+        err_log_file: None,
+        preserve_order_ids: false,
+        event_table: None,
+        prefetch_files: 0,
+        synthetic_book,
     }
 }
 
+fn parse_synthetic_book(
+    map: &Hash,
+    path: &Path,
+    get_current_section: impl Fn() -> String) -> SyntheticBookConfig
+{
+    const POSSIBLE_KEYS: [&str; 4] = [NUM_LEVELS, MEAN_ORDER_SIZE, MEAN_LIFETIME, SEED];
+    for key in map.keys() {
+        let key = expect_yaml_string(key, path, || format!("{} :: {key:?}", get_current_section()));
+        if !POSSIBLE_KEYS.contains(&key.as_str()) {
+            panic!(
+                "\"{key}\" cannot be present in the \"{}\" section. \
+                Possible keys: {POSSIBLE_KEYS:?}",
+                get_current_section()
+            )
+        }
+    }
+
+    let field = NUM_LEVELS;
+    let full_section_path = || format!("{} :: {field}", get_current_section());
+    let num_levels = read_yaml_hashmap_field(map, field, path, full_section_path);
+    let num_levels = expect_yaml_usize(num_levels, path, full_section_path);
+
+    let field = MEAN_ORDER_SIZE;
+    let full_section_path = || format!("{} :: {field}", get_current_section());
+    let mean_order_size = read_yaml_hashmap_field(map, field, path, full_section_path);
+    let mean_order_size = expect_yaml_real(mean_order_size, path, full_section_path);
+    let mean_order_size = f64::from_str(mean_order_size).unwrap_or_else(
+        |err| panic!("Section \"{}\". Cannot parse to f64: {mean_order_size}. Error: {err}",
+                     full_section_path())
+    );
+
+    let field = MEAN_LIFETIME;
+    let full_section_path = || format!("{} :: {field}", get_current_section());
+    let mean_lifetime = read_yaml_hashmap_field(map, field, path, full_section_path);
+    let mean_lifetime = expect_yaml_usize(mean_lifetime, path, full_section_path) as u64;
+    let mean_lifetime = NanoDuration(mean_lifetime);
+
+    let field = SEED;
+    let full_section_path = || format!("{} :: {field}", get_current_section());
+    let seed = read_yaml_hashmap_field(map, field, path, full_section_path);
+    let seed = expect_yaml_usize(seed, path, full_section_path) as u64;
+
+    SyntheticBookConfig { num_levels, mean_order_size, mean_lifetime, seed }
+}
+
 const fn get_order_id_colname<const IS_TRD: bool>() -> &'static str {
     if IS_TRD {
         REFERENCE_ORDER_ID_COLNAME
@@ -1125,6 +1798,7 @@ fn gen_trd_prl_config<F: Fn() -> String, const IS_TRD: bool>(
     let order_id_colname = get_order_id_colname::<IS_TRD>();
     let possible_keys = [
         PATH_LIST,
+        MERGE_FILES,
         DATETIME_FORMAT,
         CSV_SEP,
         OPEN_COLNAME,
@@ -1133,7 +1807,8 @@ fn gen_trd_prl_config<F: Fn() -> String, const IS_TRD: bool>(
         order_id_colname,
         PRICE_COLNAME,
         SIZE_COLNAME,
-        BUY_SELL_FLAG_COLNAME
+        BUY_SELL_FLAG_COLNAME,
+        SYMBOL_COLNAME
     ];
 
     update_env(map, &mut env, path, &full_section_path, possible_keys);
@@ -1249,6 +1924,28 @@ fn gen_trd_prl_config<F: Fn() -> String, const IS_TRD: bool>(
     };
 
 
+    let field = SYMBOL_COLNAME;
+    let get_current_section = || format!("{} :: {field}", full_section_path());
+    let symbol_colname = env.get(field).map(
+        |symbol_colname| if let YamlValue::String(v) = symbol_colname {
+            v.to_string()
+        } else {
+            panic!("\"{}\" should be String. Got: {symbol_colname:?}", get_current_section())
+        }
+    );
+
+
+    let field = MERGE_FILES;
+    let get_current_section = || format!("{} :: {field}", full_section_path());
+    let merge_files = env.get(field).is_some_and(
+        |merge_files| if let YamlValue::Boolean(v) = merge_files {
+            *v
+        } else {
+            panic!("\"{}\" should be Boolean. Got: {merge_files:?}", get_current_section())
+        }
+    );
+
+
     let field = PATH_LIST;
     let path_list = env
         .get(field)
@@ -1282,6 +1979,9 @@ fn gen_trd_prl_config<F: Fn() -> String, const IS_TRD: bool>(
         datetime_format,
         csv_sep,
         price_step: price_step.into(),
+        symbol_colname,
+        symbol: None,
+        merge_files,
     };
 
     (path_list, info)
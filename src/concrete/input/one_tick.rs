@@ -1,25 +1,51 @@
 use {
     crate::{
         concrete::{
-            message_protocol::replay::request::{BasicReplayRequest, BasicReplayToExchange},
-            order::{LimitOrderCancelRequest, LimitOrderPlacingRequest, MarketOrderPlacingRequest},
+            input::synthetic_book::{SyntheticBook, SyntheticBookConfig},
+            message_protocol::replay::request::{
+                BasicReplayRequest,
+                BasicReplayToBroker,
+                BasicReplayToExchange,
+            },
+            order::{
+                LimitOrderCancelRequest,
+                LimitOrderPlacingRequest,
+                MarketOrderPlacingRequest,
+                TimeInForce,
+            },
             traded_pair::{settlement::GetSettlementLag, TradedPair},
-            types::{Direction, Lots, OrderID, Tick, TickSize},
+            types::{DecimalTickSize, Direction, Lots, OrderID, Tick, TickSize},
         },
         interface::replay::{ReplayAction, ReplayActionKind},
-        types::{DateTime, Id, NeverType, Nothing},
+        types::{DateTime, Id, IdAllocator, Named, Nothing},
+        utils::collections::HashMap,
     },
     csv::{Reader, ReaderBuilder, StringRecord},
     std::{
-        cmp::Ordering,
-        collections::{hash_map::Entry::{Occupied, Vacant}, HashMap, VecDeque},
+        cmp::{Ordering, Reverse},
+        collections::{hash_map::Entry::{Occupied, Vacant}, BinaryHeap, VecDeque},
         fs::File,
         io::{BufRead, BufReader, Write},
+        iter::once,
+        panic::resume_unwind,
         path::{Path, PathBuf},
         str::FromStr,
+        sync::{mpsc::{sync_channel, Receiver}, Arc},
+        thread::{spawn, JoinHandle},
     },
 };
 
+#[cfg(feature = "mmap")]
+/// Memory-mapped binary cache of the parsed history.
+pub mod cache;
+
+/// Action of the replay reading the OneTick files.
+type OneTickReplayAction<BrokerID, ExchangeID, Symbol, Settlement> = ReplayAction<
+    Nothing,
+    BasicReplayToExchange<ExchangeID, Symbol, Settlement>,
+    BasicReplayToBroker<BrokerID, ExchangeID, Symbol, Settlement>
+>;
+
 /// OneTick traded pair reader.
 pub struct OneTickTradedPairReader<ExchangeID, Symbol, Settlement>
     where ExchangeID: Id,
@@ -31,8 +57,8 @@ pub struct OneTickTradedPairReader<ExchangeID, Symbol, Settlement>
     /// Traded pair.
     pub traded_pair: TradedPair<Symbol, Settlement>,
 
-    trd_reader: OneTickHistoryReader,
-    prl_reader: OneTickHistoryReader,
+    trd_reader: HistoryCursor,
+    prl_reader: Option<HistoryCursor>,
 
     next_trd: Option<HistoryEntry>,
     next_prl: Option<HistoryEntry>,
@@ -43,6 +69,31 @@ pub struct OneTickTradedPairReader<ExchangeID, Symbol, Settlement>
 
     /// File for logging errors.
     pub err_log_file: Option<File>,
+
+    preserve_order_ids: bool,
+
+    synthetic_book: Option<SyntheticBook>,
+    pending_requests: VecDeque<(DateTime, BasicReplayRequest<Symbol, Settlement>)>,
+
+    seed_entries: VecDeque<HistoryEntry>,
+}
+
+#[derive(Clone)]
+/// Parsed TRD- and PRL-ticks of the single traded pair.
+///
+/// Parsing is done once, whereas every [`OneTickTradedPairReader`]
+/// created with the [`from_event_table`](OneTickTradedPairReader::from_event_table)
+/// only holds its own cursor over the shared immutable entries.
+/// Cloning is cheap and the table can be shared between threads.
+pub struct OneTickEventTable {
+    prl_entries: Arc<[HistoryEntry]>,
+    trd_entries: Arc<[HistoryEntry]>,
+}
+
+/// Iterator over the history entries either read from the files or shared in memory.
+enum HistoryCursor {
+    Files(Box<OneTickHistoryReader>),
+    Shared { entries: Arc<[HistoryEntry]>, position: usize },
 }
 
 pub(crate) struct OneTickHistoryReader
@@ -50,14 +101,29 @@ pub(crate) struct OneTickHistoryReader
     files_to_parse: VecDeque<PathBuf>,
     buffered_entries: VecDeque<HistoryEntry>,
     args: OneTickTrdPrlConfig,
+    /// Datetime of the last entry of the last read file along with the file itself.
+    last_entry: Option<(DateTime, PathBuf)>,
+    prefetcher: Option<Prefetcher>,
 }
 
-#[derive(Copy, Clone)]
-pub(crate) struct HistoryEntry {
+/// Background thread parsing the next files while the current one is being replayed.
+struct Prefetcher {
+    receiver: Receiver<(PathBuf, Vec<HistoryEntry>)>,
+    handle: Option<JoinHandle<()>>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+/// Parsed TRD- or PRL-tick.
+pub struct HistoryEntry {
+    /// Datetime of the tick.
     pub datetime: DateTime,
+    /// Size of the order or trade.
     pub size: Lots,
+    /// Side of the order or aggressor side of the trade.
     pub direction: Direction,
+    /// Price of the order or trade.
     pub price: Tick,
+    /// Historical order ID.
     pub order_id: OrderID,
 }
 
@@ -80,6 +146,15 @@ pub struct OneTickTrdPrlConfig {
     pub csv_sep: char,
     /// Price step to use.
     pub price_step: f64,
+    /// Name of the symbol column. If set, the files are expected to contain
+    /// the ticks of several symbols and only the rows of the `symbol` are read.
+    pub symbol_colname: Option<String>,
+    /// Symbol which rows to read if the `symbol_colname` is set.
+    /// If absent, the readers use the symbol of the traded pair quoted asset.
+    pub symbol: Option<String>,
+    /// Whether to k-way merge the listed files by time instead of reading them one by one.
+    /// Without the merging, the files should be listed in the order of time and not overlap.
+    pub merge_files: bool,
 }
 
 pub(crate) struct OneTickHistoryEntryColumnIndexer {
@@ -88,6 +163,7 @@ pub(crate) struct OneTickHistoryEntryColumnIndexer {
     pub datetime_idx: usize,
     pub buy_sell_flag_idx: usize,
     pub order_id_idx: usize,
+    pub symbol_idx: Option<usize>,
 }
 
 impl<ExchangeID, Symbol, Settlement>
@@ -116,14 +192,92 @@ OneTickTradedPairReader<ExchangeID, Symbol, Settlement>
         trd_args: OneTickTrdPrlConfig,
         err_log_file: Option<PathBuf>) -> Self
     {
-        let mut prl_reader = OneTickHistoryReader::new(prl_files, prl_args);
-        let mut trd_reader = OneTickHistoryReader::new(trd_files, trd_args);
+        let prl_args = prl_args.with_default_symbol(traded_pair);
+        let trd_args = trd_args.with_default_symbol(traded_pair);
+        let prl_reader = HistoryCursor::from_files(prl_files, prl_args);
+        let trd_reader = HistoryCursor::from_files(trd_files, trd_args);
+        Self::new_with_prl_reader(exchange_id, traded_pair, prl_reader, trd_reader, err_log_file)
+    }
+
+    /// Creates a new instance of the `OneTickTradedPairReader`
+    /// iterating over the already parsed [`OneTickEventTable`].
+    ///
+    /// # Arguments
+    ///
+    /// * `exchange_id` — Exchange ID.
+    /// * `traded_pair` — Traded pair.
+    /// * `event_table` — Parsed TRD- and PRL-ticks.
+    /// * `err_log_file` — File for logging errors.
+    pub fn from_event_table(
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        event_table: &OneTickEventTable,
+        err_log_file: Option<PathBuf>) -> Self
+    {
+        let prl_reader = HistoryCursor::Shared {
+            entries: event_table.prl_entries.clone(),
+            position: 0,
+        };
+        let trd_reader = HistoryCursor::Shared {
+            entries: event_table.trd_entries.clone(),
+            position: 0,
+        };
+        Self::new_with_prl_reader(exchange_id, traded_pair, prl_reader, trd_reader, err_log_file)
+    }
+
+    fn new_with_prl_reader(
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        mut prl_reader: HistoryCursor,
+        trd_reader: HistoryCursor,
+        err_log_file: Option<PathBuf>) -> Self
+    {
+        let mut reader = Self::new_with_trd_reader(
+            exchange_id, traded_pair, trd_reader, err_log_file,
+        );
+        reader.next_prl = prl_reader.next();
+        reader.prl_reader = Some(prl_reader);
+        reader
+    }
+
+    /// Creates a new instance of the `OneTickTradedPairReader`
+    /// that replays only the trades against the synthetic resting depth.
+    /// Useful when the history of the limit orders (PRL-ticks) is unavailable.
+    ///
+    /// # Arguments
+    ///
+    /// * `exchange_id` — Exchange ID.
+    /// * `traded_pair` — Traded pair.
+    /// * `trd_files` — Path to file containing paths to files with TRD-ticks.
+    /// * `trd_args` — TRD-reader configuration.
+    /// * `synthetic_book` — Parameters of the synthetic resting depth.
+    /// * `err_log_file` — File for logging errors.
+    pub fn with_synthetic_book(
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        trd_files: PathBuf,
+        trd_args: OneTickTrdPrlConfig,
+        synthetic_book: SyntheticBookConfig,
+        err_log_file: Option<PathBuf>) -> Self
+    {
+        let trd_args = trd_args.with_default_symbol(traded_pair);
+        let trd_reader = HistoryCursor::from_files(trd_files, trd_args);
+        Self::new_with_trd_reader(exchange_id, traded_pair, trd_reader, err_log_file)
+            .with_synthetic_depth(synthetic_book)
+    }
+
+    fn new_with_trd_reader(
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        mut trd_reader: HistoryCursor,
+        err_log_file: Option<PathBuf>) -> Self
+    {
         Self {
             exchange_id,
-            next_prl: prl_reader.next(),
+            next_prl: None,
             next_trd: trd_reader.next(),
             trd_reader,
-            prl_reader,
+            prl_reader: None,
             active_limit_orders: Default::default(),
             traded_pair,
             err_log_file: if let Some(err_log_file) = err_log_file {
@@ -135,27 +289,193 @@ OneTickTradedPairReader<ExchangeID, Symbol, Settlement>
                 None
             },
             limit_submitted_to_internal: Default::default(),
+            preserve_order_ids: false,
+            synthetic_book: None,
+            pending_requests: Default::default(),
+            seed_entries: Default::default(),
+        }
+    }
+
+    /// Replaces the PRL-ticks with the synthetic resting depth,
+    /// so that only the trades are replayed.
+    ///
+    /// # Arguments
+    ///
+    /// * `synthetic_book` — Parameters of the synthetic resting depth.
+    pub fn with_synthetic_depth(mut self, synthetic_book: SyntheticBookConfig) -> Self {
+        self.prl_reader = None;
+        self.next_prl = None;
+        self.synthetic_book = Some(SyntheticBook::new(synthetic_book));
+        self
+    }
+
+    /// Sets whether to submit limit orders with their historical IDs instead of generated ones.
+    /// In this case the IDs of the market orders are generated
+    /// starting from the [`OrderID::FIRST_SYNTHETIC`].
+    ///
+    /// # Arguments
+    ///
+    /// * `preserve_order_ids` — Whether to preserve historical order IDs.
+    pub fn with_preserved_order_ids(mut self, preserve_order_ids: bool) -> Self {
+        self.preserve_order_ids = preserve_order_ids;
+        self
+    }
+
+    /// Parses the next history files in the background threads, one per TRD and PRL files,
+    /// while the current ones are being replayed. Has no effect on the readers
+    /// iterating over the [`OneTickEventTable`] and on the ones merging the files.
+    ///
+    /// # Arguments
+    ///
+    /// * `num_files` — Maximum number of the parsed files waiting to be replayed. Zero disables.
+    pub fn with_prefetch(mut self, num_files: usize) -> Self {
+        if num_files != 0 {
+            for reader in self.prl_reader.iter_mut().chain(once(&mut self.trd_reader)) {
+                if let HistoryCursor::Files(reader) = reader {
+                    reader.start_prefetch(num_files)
+                }
+            }
+        }
+        self
+    }
+
+    /// Pre-loads the order book with the limit orders that are still active at the `seed_end`,
+    /// netting all the history events between the `seed_start` and the `seed_end`.
+    /// The netted limit orders are inserted at the `seed_end` in their historical time priority
+    /// as [`SeedLimitOrder`](BasicReplayRequest::SeedLimitOrder) requests,
+    /// so that they are neither matched nor broadcast to the brokers as the new orders.
+    /// Events preceding the `seed_start` are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `seed_start` — Datetime to start netting the history events from.
+    /// * `seed_end` — Datetime to submit the netted limit orders at, e.g. the simulation start.
+    pub fn with_book_seeding(mut self, seed_start: DateTime, seed_end: DateTime) -> Self {
+        if seed_end < seed_start {
+            panic!("Book seeding end {seed_end} is less than book seeding start {seed_start}")
+        }
+        let mut resting_orders: HashMap<OrderID, (u64, HistoryEntry)> = Default::default();
+        let mut seq_no = 0;
+        loop {
+            let (entry, is_prl) = match (&self.next_prl, &self.next_trd) {
+                (Some(prl), Some(trd)) => {
+                    let cmp = prl.datetime.cmp(&trd.datetime);
+                    if cmp == Ordering::Less
+                        || cmp == Ordering::Equal && prl.order_id < trd.order_id
+                    {
+                        (*prl, true)
+                    } else {
+                        (*trd, false)
+                    }
+                }
+                (Some(prl), _) => (*prl, true),
+                (_, Some(trd)) => (*trd, false),
+                _ => break
+            };
+            if entry.datetime >= seed_end {
+                break;
+            }
+            if is_prl {
+                self.next_prl = self.next_prl_entry()
+            } else {
+                self.next_trd = self.trd_reader.next()
+            }
+            if entry.datetime < seed_start {
+                continue;
+            }
+            if !is_prl {
+                if let Occupied(mut resting) = resting_orders.entry(entry.order_id) {
+                    let (_, order) = resting.get_mut();
+                    if order.size > entry.size {
+                        order.size -= entry.size
+                    } else {
+                        resting.remove();
+                    }
+                }
+            } else if entry.size == Lots(0) {
+                resting_orders.remove(&entry.order_id);
+            } else if let Vacant(resting) = resting_orders.entry(entry.order_id) {
+                resting.insert((seq_no, entry));
+                seq_no += 1
+            }
+        }
+        let mut resting_orders: Vec<_> = resting_orders.into_values().collect();
+        resting_orders.sort_unstable_by_key(|(seq_no, _)| *seq_no);
+        self.seed_entries = resting_orders.into_iter()
+            .map(|(_, entry)| HistoryEntry { datetime: seed_end, ..entry })
+            .collect();
+        self
+    }
+
+    fn generate_order_id(preserve_order_ids: bool, id_allocator: &IdAllocator) -> OrderID {
+        let order_id = OrderID(id_allocator.next_id());
+        if preserve_order_ids {
+            OrderID(OrderID::FIRST_SYNTHETIC.0 | order_id.0)
+        } else {
+            order_id
         }
     }
 
     /// Forgets information about recently submitted limit orders.
     pub fn clear(&mut self) {
         self.active_limit_orders.clear();
-        self.limit_submitted_to_internal.clear()
+        self.limit_submitted_to_internal.clear();
+        if let Some(synthetic_book) = &mut self.synthetic_book {
+            synthetic_book.clear()
+        }
+    }
+
+    /// Reconciles the synthetic resting depth with the partial execution
+    /// of the replay limit order. Has no effect if the reader replays the PRL-ticks.
+    ///
+    /// # Arguments
+    ///
+    /// * `order_id` — ID of the limit order.
+    /// * `size` — Executed size.
+    pub fn on_order_partially_executed(&mut self, order_id: OrderID, size: Lots) {
+        if let Some(synthetic_book) = &mut self.synthetic_book {
+            synthetic_book.on_order_partially_executed(order_id, size)
+        }
+    }
+
+    /// Reconciles the synthetic resting depth with the replay limit order
+    /// that has left the order book, i.e. has been executed or cancelled.
+    /// Has no effect if the reader replays the PRL-ticks.
+    ///
+    /// # Arguments
+    ///
+    /// * `order_id` — ID of the limit order.
+    pub fn on_order_removed(&mut self, order_id: OrderID) {
+        if let Some(synthetic_book) = &mut self.synthetic_book {
+            synthetic_book.on_order_removed(order_id)
+        }
     }
 
     /// Produces next [`RelayAction`](crate::interface::replay) based on the history information.
     ///
     /// # Arguments
     ///
-    /// * `next_order_id` — Next ID of the new order.
-    pub fn next<BrokerID: Id>(&mut self, next_order_id: &mut OrderID) -> Option<
-        ReplayAction<
-            Nothing,
-            BasicReplayToExchange<ExchangeID, Symbol, Settlement>,
-            NeverType<BrokerID>
-        >
+    /// * `id_allocator` — Allocator of the IDs of the new orders.
+    pub fn next<BrokerID: Id>(&mut self, id_allocator: &IdAllocator) -> Option<
+        OneTickReplayAction<BrokerID, ExchangeID, Symbol, Settlement>
     > {
+        while let Some(entry) = self.seed_entries.pop_front() {
+            let mut res = self.process_prl(entry, id_allocator);
+            if let Some(
+                ReplayAction { content: ReplayActionKind::ReplayToExchange(action), .. }
+            ) = &mut res {
+                // Seeded orders are the initial state rather than the new orders to be broadcast
+                if let BasicReplayRequest::PlaceLimitOrder(order) = action.content {
+                    action.content = BasicReplayRequest::SeedLimitOrder(order)
+                }
+            }
+            if res.is_some() {
+                return res;
+            }
+        }
+        if self.synthetic_book.is_some() {
+            return self.next_synthetic(id_allocator);
+        }
         loop {
             let res;
             match (&self.next_prl, &self.next_trd)
@@ -166,22 +486,22 @@ OneTickTradedPairReader<ExchangeID, Symbol, Settlement>
                         || cmp == Ordering::Equal && prl.order_id < trd.order_id
                     {
                         let prl = *prl;
-                        res = self.process_prl(prl, next_order_id);
-                        self.next_prl = self.prl_reader.next()
+                        res = self.process_prl(prl, id_allocator);
+                        self.next_prl = self.next_prl_entry()
                     } else {
                         let trd = *trd;
-                        res = self.process_trd(trd, next_order_id);
+                        res = self.process_trd(trd, id_allocator);
                         self.next_trd = self.trd_reader.next()
                     }
                 }
                 (Some(prl), _) => {
                     let prl = *prl;
-                    res = self.process_prl(prl, next_order_id);
-                    self.next_prl = self.prl_reader.next()
+                    res = self.process_prl(prl, id_allocator);
+                    self.next_prl = self.next_prl_entry()
                 }
                 (_, Some(trd)) => {
                     let trd = *trd;
-                    res = self.process_trd(trd, next_order_id);
+                    res = self.process_trd(trd, id_allocator);
                     self.next_trd = self.trd_reader.next()
                 }
                 _ => { return None; }
@@ -192,15 +512,51 @@ OneTickTradedPairReader<ExchangeID, Symbol, Settlement>
         }
     }
 
+    fn next_prl_entry(&mut self) -> Option<HistoryEntry> {
+        self.prl_reader.as_mut().and_then(HistoryCursor::next)
+    }
+
+    fn next_synthetic<BrokerID: Id>(&mut self, id_allocator: &IdAllocator) -> Option<
+        OneTickReplayAction<BrokerID, ExchangeID, Symbol, Settlement>
+    > {
+        loop {
+            if let Some((datetime, request)) = self.pending_requests.pop_front() {
+                return Some(self.create_replay_to_exchange(datetime, request));
+            }
+            let trd = self.next_trd?;
+            let synthetic_book = self.synthetic_book.as_mut().unwrap_or_else(
+                || unreachable!("Synthetic book is not set")
+            );
+            if let Some((datetime, order_id)) = synthetic_book.pop_expired(trd.datetime) {
+                let request = BasicReplayRequest::CancelLimitOrder(
+                    LimitOrderCancelRequest { traded_pair: self.traded_pair, order_id }
+                );
+                return Some(self.create_replay_to_exchange(datetime, request));
+            }
+            self.next_trd = self.trd_reader.next();
+            if trd.size == Lots(0) {
+                continue;
+            }
+            let preserve_order_ids = self.preserve_order_ids;
+            let requests = synthetic_book.process_trade(
+                trd.datetime,
+                self.traded_pair,
+                trd.direction,
+                trd.price,
+                trd.size,
+                || Self::generate_order_id(preserve_order_ids, id_allocator),
+            );
+            self.pending_requests.extend(
+                requests.into_iter().map(|request| (trd.datetime, request))
+            )
+        }
+    }
+
     fn create_replay_to_exchange<BrokerID: Id>(
         &self,
         datetime: DateTime,
-        content: BasicReplayRequest<Symbol, Settlement>) -> ReplayAction<
-        Nothing,
-        BasicReplayToExchange<
-            ExchangeID, Symbol, Settlement
-        >,
-        NeverType<BrokerID>
+        content: BasicReplayRequest<Symbol, Settlement>) -> OneTickReplayAction<
+        BrokerID, ExchangeID, Symbol, Settlement
     > {
         ReplayAction {
             datetime,
@@ -216,18 +572,24 @@ OneTickTradedPairReader<ExchangeID, Symbol, Settlement>
     fn process_prl<BrokerID: Id>(
         &mut self,
         prl: HistoryEntry,
-        next_order_id: &mut OrderID) -> Option<
-        ReplayAction<
-            Nothing,
-            BasicReplayToExchange<ExchangeID, Symbol, Settlement>,
-            NeverType<BrokerID>
-        >
+        id_allocator: &IdAllocator) -> Option<
+        OneTickReplayAction<BrokerID, ExchangeID, Symbol, Settlement>
     > {
         let entry = self.active_limit_orders.entry(prl.order_id);
         if prl.size != Lots(0) {
             if let Vacant(entry) = entry {
-                let order_id = *next_order_id;
-                *next_order_id += OrderID(1);
+                let order_id = if self.preserve_order_ids {
+                    if prl.order_id >= OrderID::FIRST_SYNTHETIC {
+                        panic!(
+                            "Historical order ID {} should be less than {}",
+                            prl.order_id,
+                            OrderID::FIRST_SYNTHETIC
+                        )
+                    }
+                    prl.order_id
+                } else {
+                    OrderID(id_allocator.next_id())
+                };
                 entry.insert((order_id, prl.size));
                 self.limit_submitted_to_internal.insert(order_id, prl.order_id);
                 let replay_action = self.create_replay_to_exchange(
@@ -240,6 +602,7 @@ OneTickTradedPairReader<ExchangeID, Symbol, Settlement>
                             price: prl.price,
                             size: prl.size,
                             dummy: false,
+                            time_in_force: TimeInForce::Day,
                         }
                     ),
                 );
@@ -274,12 +637,8 @@ OneTickTradedPairReader<ExchangeID, Symbol, Settlement>
     fn process_trd<BrokerID: Id>(
         &mut self,
         mut trd: HistoryEntry,
-        next_order_id: &mut OrderID) -> Option<
-        ReplayAction<
-            Nothing,
-            BasicReplayToExchange<ExchangeID, Symbol, Settlement>,
-            NeverType<BrokerID>
-        >
+        id_allocator: &IdAllocator) -> Option<
+        OneTickReplayAction<BrokerID, ExchangeID, Symbol, Settlement>
     > {
         if let Some((_, size)) = self.active_limit_orders.get_mut(&trd.order_id) {
             if *size >= trd.size {
@@ -301,8 +660,7 @@ OneTickTradedPairReader<ExchangeID, Symbol, Settlement>
                 *size = Lots(0)
             }
             let result = if trd.size != Lots(0) {
-                let order_id = *next_order_id;
-                *next_order_id += OrderID(1);
+                let order_id = Self::generate_order_id(self.preserve_order_ids, id_allocator);
                 let replay_action = self.create_replay_to_exchange(
                     trd.datetime,
                     BasicReplayRequest::PlaceMarketOrder(
@@ -335,15 +693,117 @@ OneTickTradedPairReader<ExchangeID, Symbol, Settlement>
     }
 }
 
+impl OneTickTrdPrlConfig {
+    /// Sets the `symbol` to the one of the traded pair quoted asset
+    /// if the `symbol_colname` is set and the `symbol` is not.
+    ///
+    /// # Arguments
+    ///
+    /// * `traded_pair` — Traded pair which rows to read.
+    pub fn with_default_symbol<Symbol: Id, Settlement: GetSettlementLag>(
+        mut self,
+        traded_pair: TradedPair<Symbol, Settlement>) -> Self
+    {
+        if self.symbol_colname.is_some() && self.symbol.is_none() {
+            self.symbol = Some(traded_pair.quoted_asset.get_name().to_string())
+        }
+        self
+    }
+}
+
+impl OneTickEventTable {
+    /// Parses all the TRD- and PRL-ticks of the traded pair.
+    ///
+    /// # Arguments
+    ///
+    /// * `prl_files` — Path to file containing paths to files with PRL-ticks.
+    /// * `prl_args` — PRL-reader configuration.
+    /// * `trd_files` — Path to file containing paths to files with TRD-ticks.
+    /// * `trd_args` — TRD-reader configuration.
+    pub fn new(
+        prl_files: impl AsRef<Path>,
+        prl_args: OneTickTrdPrlConfig,
+        trd_files: impl AsRef<Path>,
+        trd_args: OneTickTrdPrlConfig) -> Self
+    {
+        Self {
+            prl_entries: OneTickHistoryReader::new(prl_files, prl_args).collect(),
+            trd_entries: OneTickHistoryReader::new(trd_files, trd_args).collect(),
+        }
+    }
+
+    /// Parses all the TRD-ticks of the traded pair replayed against the synthetic resting depth.
+    /// The table has no PRL-ticks.
+    ///
+    /// # Arguments
+    ///
+    /// * `trd_files` — Path to file containing paths to files with TRD-ticks.
+    /// * `trd_args` — TRD-reader configuration.
+    pub fn trades_only(trd_files: impl AsRef<Path>, trd_args: OneTickTrdPrlConfig) -> Self {
+        Self {
+            prl_entries: Arc::new([]),
+            trd_entries: OneTickHistoryReader::new(trd_files, trd_args).collect(),
+        }
+    }
+
+    /// Returns the number of parsed PRL-ticks.
+    pub fn num_prl_entries(&self) -> usize {
+        self.prl_entries.len()
+    }
+
+    /// Returns the number of parsed TRD-ticks.
+    pub fn num_trd_entries(&self) -> usize {
+        self.trd_entries.len()
+    }
+
+    /// Returns an iterator over the PRL-ticks strictly before the datetime,
+    /// e.g. to warm up the indicators without looking ahead of the simulation start.
+    ///
+    /// # Arguments
+    ///
+    /// * `dt` — Exclusive upper bound of the tick datetimes.
+    pub fn iter_prl_before(&self, dt: DateTime) -> impl Iterator<Item=&HistoryEntry> {
+        self.prl_entries.iter().take_while(move |entry| entry.datetime < dt)
+    }
+
+    /// Returns an iterator over the TRD-ticks strictly before the datetime,
+    /// e.g. to warm up the indicators without looking ahead of the simulation start.
+    ///
+    /// # Arguments
+    ///
+    /// * `dt` — Exclusive upper bound of the tick datetimes.
+    pub fn iter_trd_before(&self, dt: DateTime) -> impl Iterator<Item=&HistoryEntry> {
+        self.trd_entries.iter().take_while(move |entry| entry.datetime < dt)
+    }
+}
+
+impl HistoryCursor {
+    fn from_files(files_to_parse: impl AsRef<Path>, args: OneTickTrdPrlConfig) -> Self {
+        HistoryCursor::Files(Box::new(OneTickHistoryReader::new(files_to_parse, args)))
+    }
+}
+
+impl Iterator for HistoryCursor {
+    type Item = HistoryEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            HistoryCursor::Files(reader) => reader.next(),
+            HistoryCursor::Shared { entries, position } => {
+                let entry = entries.get(*position).copied();
+                *position += 1;
+                entry
+            }
+        }
+    }
+}
+
 impl Iterator for OneTickHistoryReader {
     type Item = HistoryEntry;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let next_entry = self.buffered_entries.pop_front();
-        if next_entry.is_some() {
-            return next_entry;
-        };
-        self.buffer_next_file();
+        // Files may have no entries, e.g. no rows of the symbol
+        while self.buffered_entries.is_empty() && self.buffer_next_file() {}
         self.buffered_entries.pop_front()
     }
 }
@@ -353,30 +813,13 @@ impl OneTickHistoryReader
     fn new(files_to_parse: impl AsRef<Path>, args: OneTickTrdPrlConfig) -> Self
     {
         let files_to_parse = files_to_parse.as_ref();
-        let files = {
-            let files_to_parse = Path::new(files_to_parse);
-            let file = File::open(files_to_parse).unwrap_or_else(
-                |err| panic!("Cannot read the following file: {files_to_parse:?}. Error: {err}")
-            );
-            let files_to_parse_dir = files_to_parse.parent().unwrap_or_else(
-                || panic!("Cannot get parent directory of the {files_to_parse:?}")
-            );
-            BufReader::new(&file)
-                .lines()
-                .filter_map(
-                    |path| {
-                        let path = path.ok()?;
-                        let path = Path::new(&path);
-                        let result = if path.is_relative() {
-                            files_to_parse_dir.join(path)
-                        } else {
-                            PathBuf::from(path)
-                        };
-                        Some(result)
-                    }
-                )
-                .collect()
-        };
+        if let (Some(symbol_colname), None) = (&args.symbol_colname, &args.symbol) {
+            panic!(
+                "Symbol to read should be specified along with the {symbol_colname} column \
+                for the files listed in {files_to_parse:?}"
+            )
+        }
+        let files = Self::read_file_list(files_to_parse);
         let mut res = Self::new_for_vecdeque(files, args);
         if !res.buffer_next_file() {
             panic!("No history files provided in {files_to_parse:?}")
@@ -384,35 +827,141 @@ impl OneTickHistoryReader
         res
     }
 
+    fn read_file_list(files_to_parse: &Path) -> VecDeque<PathBuf> {
+        let file = File::open(files_to_parse).unwrap_or_else(
+            |err| panic!("Cannot read the following file: {files_to_parse:?}. Error: {err}")
+        );
+        let files_to_parse_dir = files_to_parse.parent().unwrap_or_else(
+            || panic!("Cannot get parent directory of the {files_to_parse:?}")
+        );
+        BufReader::new(&file)
+            .lines()
+            .filter_map(
+                |path| {
+                    let path = path.ok()?;
+                    let path = Path::new(&path);
+                    let result = if path.is_relative() {
+                        files_to_parse_dir.join(path)
+                    } else {
+                        PathBuf::from(path)
+                    };
+                    Some(result)
+                }
+            )
+            .collect()
+    }
+
     fn new_for_vecdeque(files_to_parse: VecDeque<PathBuf>, args: OneTickTrdPrlConfig) -> Self {
         Self {
             files_to_parse,
             buffered_entries: Default::default(),
             args,
+            last_entry: None,
+            prefetcher: None,
         }
     }
 
     fn buffer_next_file(&mut self) -> bool
     {
-        let file_to_read = if let Some(file_to_read) = self.files_to_parse.pop_front() {
-            file_to_read
+        if self.args.merge_files {
+            return self.buffer_merged_files();
+        }
+        let (file_to_read, entries) = if let Some(prefetcher) = &mut self.prefetcher {
+            if let Some(parsed) = prefetcher.recv() {
+                parsed
+            } else {
+                return false;
+            }
+        } else if let Some(file_to_read) = self.files_to_parse.pop_front() {
+            let entries = self.read_file(&file_to_read);
+            (file_to_read, entries)
         } else {
             return false;
         };
+        if let (Some((last_dt, last_file)), Some(first_entry)) = (
+            &self.last_entry, entries.first()
+        ) {
+            if first_entry.datetime < *last_dt {
+                panic!(
+                    "File {file_to_read:?} starts at {} that precedes the end {last_dt} \
+                    of the previous file {last_file:?}. Files should be listed in the order \
+                    of time and not overlap unless they are merged",
+                    first_entry.datetime
+                )
+            }
+        }
+        if let Some(last_entry) = entries.last() {
+            self.last_entry = Some((last_entry.datetime, file_to_read))
+        }
+        self.buffered_entries.extend(entries);
+        true
+    }
+
+    /// Moves the parsing of the remaining files to the background thread.
+    fn start_prefetch(&mut self, num_files: usize)
+    {
+        if self.args.merge_files || self.prefetcher.is_some() {
+            return;
+        }
+        let files_to_parse = std::mem::take(&mut self.files_to_parse);
+        let parser = Self::new_for_vecdeque(Default::default(), self.args.clone());
+        let (sender, receiver) = sync_channel(num_files);
+        let handle = spawn(
+            move || for file_to_read in files_to_parse {
+                let entries = parser.read_file(&file_to_read);
+                // The reader has been dropped
+                if sender.send((file_to_read, entries)).is_err() {
+                    break;
+                }
+            }
+        );
+        self.prefetcher = Some(Prefetcher { receiver, handle: Some(handle) })
+    }
+
+    /// Reads all the remaining files at once and k-way merges their entries by time.
+    /// Entries with equal datetimes keep the order of the files and of the rows within them.
+    fn buffer_merged_files(&mut self) -> bool
+    {
+        if self.files_to_parse.is_empty() {
+            return false;
+        }
+        let files: Vec<_> = self.files_to_parse.drain(..).collect();
+        let tables: Vec<_> = files.iter().map(|file| self.read_file(file)).collect();
+        let mut heads: BinaryHeap<_> = tables.iter()
+            .enumerate()
+            .filter_map(|(i, entries)| Some(Reverse((entries.first()?.datetime, i, 0))))
+            .collect();
+        while let Some(Reverse((_, i, position))) = heads.pop() {
+            let entries = &tables[i];
+            self.buffered_entries.push_back(entries[position]);
+            if let Some(next_entry) = entries.get(position + 1) {
+                heads.push(Reverse((next_entry.datetime, i, position + 1)))
+            }
+        }
+        true
+    }
+
+    /// Parses the file checking that its entries are sorted by time.
+    fn read_file(&self, file_to_read: &Path) -> Vec<HistoryEntry>
+    {
         let mut cur_file_reader = ReaderBuilder::new()
             .delimiter(self.args.csv_sep as u8)
-            .from_path(&file_to_read)
+            .from_path(file_to_read)
             .unwrap_or_else(
                 |err| panic!("Cannot read the following file: {file_to_read:?}. Error: {err}")
             );
         let col_idx_info = OneTickHistoryEntryColumnIndexer::new(
             &mut cur_file_reader,
-            &file_to_read,
+            file_to_read,
             &self.args,
         );
 
         let price_step = TickSize(self.args.price_step);
+        let decimal_price_step = DecimalTickSize::try_from(price_step).unwrap_or_else(
+            |err| panic!("Cannot represent price step {price_step} as a decimal. Error: {err}")
+        );
         let datetime_format = &self.args.datetime_format;
+        let symbol = self.args.symbol.as_deref();
 
         let process_next_entry = |(record, row_n): (Result<StringRecord, csv::Error>, _)| {
             let record = record.unwrap_or_else(
@@ -421,13 +970,18 @@ impl OneTickHistoryReader
                     Error: {err}"
                 )
             );
+            if let Some(symbol_idx) = col_idx_info.symbol_idx {
+                if symbol != Some(&record[symbol_idx]) {
+                    return None;
+                }
+            }
             let datetime = &record[col_idx_info.datetime_idx];
             let order_id = &record[col_idx_info.order_id_idx];
             let price = &record[col_idx_info.price_idx];
             let size = &record[col_idx_info.size_idx];
             let bs_flag = &record[col_idx_info.buy_sell_flag_idx];
 
-            HistoryEntry {
+            let entry = HistoryEntry {
                 datetime: DateTime::parse_from_str(datetime, datetime_format).unwrap_or_else(
                     |err| panic!(
                         "Cannot parse to NaiveDateTime: {datetime}. \
@@ -442,16 +996,58 @@ impl OneTickHistoryReader
                     "1" | "S" | "s" | "True" | "true" => Direction::Sell,
                     _ => panic!("Cannot parse buy-sell flag: {bs_flag}")
                 },
-                price: Tick::from_decimal_str(price, price_step),
+                price: Tick::try_from_decimal_str(price, decimal_price_step).unwrap_or_else(
+                    |err| panic!(
+                        "Cannot convert {row_n}-th price {price} to Tick \
+                        with the following price step: {price_step}. \
+                        File: {file_to_read:?}. Error: {err}"
+                    )
+                ),
                 order_id: OrderID::from_str(order_id).unwrap_or_else(
                     |err| panic!("Cannot parse to OrderID (u64): {order_id}. Error: {err}")
                 ),
-            }
+            };
+            Some((row_n, entry))
         };
-        self.buffered_entries.extend(
-            cur_file_reader.records().zip(2..).map(process_next_entry)
-        );
-        true
+        let mut last_row: Option<(usize, DateTime)> = None;
+        cur_file_reader.records()
+            .zip(2..)
+            .filter_map(process_next_entry)
+            .map(
+                |(row_n, entry)| {
+                    if let Some((last_row_n, last_dt)) = last_row {
+                        if entry.datetime < last_dt {
+                            panic!(
+                                "{row_n}-th CSV-record of the file {file_to_read:?} \
+                                has datetime {} that precedes the datetime {last_dt} \
+                                of the {last_row_n}-th one. \
+                                Entries should be sorted in ascending order by time",
+                                entry.datetime
+                            )
+                        }
+                    }
+                    last_row = Some((row_n, entry.datetime));
+                    entry
+                }
+            )
+            .collect()
+    }
+}
+
+impl Prefetcher
+{
+    /// Waits for the next parsed file. Returns `None` if all files have been parsed.
+    /// Resumes the panic of the background thread, e.g. caused by the malformed file.
+    fn recv(&mut self) -> Option<(PathBuf, Vec<HistoryEntry>)> {
+        if let Ok(parsed) = self.receiver.recv() {
+            return Some(parsed);
+        }
+        if let Some(handle) = self.handle.take() {
+            if let Err(panic) = handle.join() {
+                resume_unwind(panic)
+            }
+        }
+        None
     }
 }
 
@@ -468,12 +1064,14 @@ impl OneTickHistoryEntryColumnIndexer
         let mut size_idx = None;
         let mut price_idx = None;
         let mut buy_sell_flag_idx = None;
+        let mut symbol_idx = None;
 
         let order_id_colname = &args.order_id_colname;
         let datetime_colname = &args.datetime_colname;
         let size_colname = &args.size_colname;
         let price_colname = &args.price_colname;
         let bs_flag_colname = &args.buy_sell_flag_colname;
+        let symbol_colname = args.symbol_colname.as_deref();
 
         for (i, header) in csv_reader
             .headers()
@@ -515,6 +1113,12 @@ impl OneTickHistoryEntryColumnIndexer
                 } else {
                     panic!("Duplicate column {bs_flag_colname} in the file: {path_for_debug:?}")
                 }
+            } else if Some(header) == symbol_colname {
+                if symbol_idx.is_none() {
+                    symbol_idx = Some(i)
+                } else {
+                    panic!("Duplicate column {header} in the file: {path_for_debug:?}")
+                }
             }
         };
         let price_idx = price_idx.unwrap_or_else(
@@ -532,12 +1136,171 @@ impl OneTickHistoryEntryColumnIndexer
         let order_id_idx = order_id_idx.unwrap_or_else(
             || panic!("Cannot find {order_id_colname} column in the CSV-file: {path_for_debug:?}")
         );
+        if let (Some(symbol_colname), None) = (symbol_colname, symbol_idx) {
+            panic!("Cannot find {symbol_colname} column in the CSV-file: {path_for_debug:?}")
+        }
         Self {
             price_idx,
             size_idx,
             datetime_idx,
             buy_sell_flag_idx,
             order_id_idx,
+            symbol_idx,
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            test_utils::pair,
+            types::NanoDuration,
+        },
+        super::*,
+        std::{env::temp_dir, fs::{create_dir_all, write}},
+    };
+
+    fn args() -> OneTickTrdPrlConfig {
+        OneTickTrdPrlConfig {
+            datetime_colname: "Timestamp".into(),
+            order_id_colname: "ORDER_ID".into(),
+            price_colname: "PRICE".into(),
+            size_colname: "SIZE".into(),
+            buy_sell_flag_colname: "BUY_SELL_FLAG".into(),
+            datetime_format: "%Y-%m-%d %H:%M:%S%.f".into(),
+            csv_sep: ',',
+            price_step: 0.01,
+            symbol_colname: None,
+            symbol: None,
+            merge_files: false,
+        }
+    }
+
+    /// Writes the PRL-files with the given rows to the directory unique for the test.
+    fn write_files(test_name: &str, files: &[&[&str]]) -> VecDeque<PathBuf> {
+        let dir = temp_dir().join(format!("trading_backtester_{test_name}_{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        files.iter()
+            .enumerate()
+            .map(
+                |(i, rows)| {
+                    let file = dir.join(format!("{i}.csv"));
+                    let mut content = String::from("Timestamp,ORDER_ID,PRICE,SIZE,BUY_SELL_FLAG\n");
+                    rows.iter().for_each(|row| content.extend([row, "\n"]));
+                    write(&file, content).unwrap();
+                    file
+                }
+            )
+            .collect()
+    }
+
+    #[test]
+    fn test_prefetch() {
+        let files = write_files(
+            "prefetch",
+            &[
+                &["2022-01-03 10:00:00.0,1,100.01,5,B", "2022-01-03 10:00:01.0,2,100.02,3,S"],
+                &[],
+                &["2022-01-03 10:00:02.0,3,100.03,1,S"],
+                &["2022-01-03 10:00:02.0,4,100.00,2,B"],
+            ],
+        );
+        let expected: Vec<_> = OneTickHistoryReader::new_for_vecdeque(files.clone(), args())
+            .collect();
+        assert_eq!(
+            expected.iter().map(|entry| (entry.order_id, entry.price)).collect::<Vec<_>>(),
+            [
+                (OrderID(1), Tick(10001)),
+                (OrderID(2), Tick(10002)),
+                (OrderID(3), Tick(10003)),
+                (OrderID(4), Tick(10000)),
+            ]
+        );
+        for num_files in 1..=3 {
+            let mut reader = OneTickHistoryReader::new_for_vecdeque(files.clone(), args());
+            reader.start_prefetch(num_files);
+            assert!(reader.files_to_parse.is_empty());
+            assert_eq!(reader.collect::<Vec<_>>(), expected)
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    #[should_panic(expected = "Cannot parse buy-sell flag: X")]
+    fn test_prefetch_resumes_parsing_panic() {
+        let files = write_files(
+            "prefetch_panic",
+            &[
+                &["2022-01-03 10:00:00.0,1,100.01,5,B"],
+                &["2022-01-03 10:00:01.0,2,100.02,3,X"],
+            ],
+        );
+        let mut reader = OneTickHistoryReader::new_for_vecdeque(files, args());
+        reader.start_prefetch(1);
+        assert_eq!(reader.next().map(|entry| entry.order_id), Some(OrderID(1)));
+        reader.next();
+    }
+
+    #[test]
+    fn test_synthetic_book_is_deterministic() {
+        let files = write_files(
+            "synthetic_book",
+            &[
+                &[
+                    "2022-01-03 10:00:00.0,1,100.01,5,B",
+                    "2022-01-03 10:00:01.0,2,100.00,3,S",
+                    "2022-01-03 10:00:02.0,3,100.03,7,B",
+                    "2022-01-03 10:00:05.0,4,99.98,2,S",
+                ],
+            ],
+        );
+        let trd_files = files[0].with_file_name("trd_list.txt");
+        write(&trd_files, "0.csv").unwrap();
+        let traded_pair = pair();
+        let replay = |seed| {
+            let mut reader = OneTickTradedPairReader::with_synthetic_book(
+                "MOEX",
+                traded_pair,
+                trd_files.clone(),
+                args(),
+                SyntheticBookConfig {
+                    num_levels: 3,
+                    mean_order_size: 4.0,
+                    mean_lifetime: NanoDuration(2_000_000_000),
+                    seed,
+                },
+                None,
+            );
+            let id_allocator = IdAllocator::new(0);
+            let mut actions = Vec::new();
+            while let Some(action) = reader.next::<u8>(&id_allocator) {
+                actions.push(action)
+            }
+            actions
+        };
+        let actions = replay(42);
+        let market_orders: Vec<_> = actions.iter()
+            .filter_map(
+                |action| match action.content {
+                    ReplayActionKind::ReplayToExchange(
+                        BasicReplayToExchange {
+                            content: BasicReplayRequest::PlaceMarketOrder(order), ..
+                        }
+                    ) => Some((action.datetime, order.direction, order.size)),
+                    _ => None
+                }
+            )
+            .collect();
+        assert_eq!(
+            market_orders.iter().map(|(_, direction, size)| (*direction, *size)).collect::<Vec<_>>(),
+            [
+                (Direction::Buy, Lots(5)),
+                (Direction::Sell, Lots(3)),
+                (Direction::Buy, Lots(7)),
+                (Direction::Sell, Lots(2)),
+            ]
+        );
+        assert!(actions.windows(2).all(|pair| pair[0].datetime <= pair[1].datetime));
+        assert_eq!(actions, replay(42));
+        assert_ne!(actions, replay(43))
+    }
+}
@@ -0,0 +1,207 @@
+use {
+    crate::{
+        concrete::{
+            input::one_tick::{
+                HistoryEntry,
+                OneTickEventTable,
+                OneTickHistoryReader,
+                OneTickTrdPrlConfig,
+            },
+            types::{Direction, Lots, OrderID, Tick},
+        },
+        types::{Date, DateTime, Duration},
+        utils::metadata::stable_hash,
+    },
+    memmap2::Mmap,
+    std::{
+        fs::{rename, File},
+        io::{BufWriter, Write},
+        path::{Path, PathBuf},
+        sync::atomic::{AtomicU64, Ordering},
+        time::UNIX_EPOCH,
+    },
+};
+
+const MAGIC: &[u8; 8] = b"OTKTBL01";
+const HEADER_SIZE: usize = MAGIC.len() + 3 * 8;
+const RECORD_SIZE: usize = 8 + 4 + 8 + 1 + 8 + 8;
+
+static NEXT_TMP_FILE_NO: AtomicU64 = AtomicU64::new(0);
+
+impl OneTickEventTable {
+    /// Loads the parsed TRD- and PRL-ticks from the memory-mapped binary cache file.
+    /// If the cache file is absent or has been built from other sources,
+    /// parses the csv-files and writes the cache file.
+    ///
+    /// The cache file is keyed by the hash of the listed csv-file paths,
+    /// their sizes and modification times, as well as of the reader configurations.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache_file` — Path to the binary cache file.
+    /// * `prl_files` — Path to file containing paths to files with PRL-ticks.
+    /// * `prl_args` — PRL-reader configuration.
+    /// * `trd_files` — Path to file containing paths to files with TRD-ticks.
+    /// * `trd_args` — TRD-reader configuration.
+    pub fn cached(
+        cache_file: impl AsRef<Path>,
+        prl_files: impl AsRef<Path>,
+        prl_args: OneTickTrdPrlConfig,
+        trd_files: impl AsRef<Path>,
+        trd_args: OneTickTrdPrlConfig) -> Self
+    {
+        let cache_file = cache_file.as_ref();
+        let (prl_files, trd_files) = (prl_files.as_ref(), trd_files.as_ref());
+        let source_hash = get_source_hash([(prl_files, &prl_args), (trd_files, &trd_args)]);
+        if let Some(event_table) = load(cache_file, source_hash) {
+            return event_table;
+        }
+        let event_table = Self::new(prl_files, prl_args, trd_files, trd_args);
+        store(&event_table, cache_file, source_hash);
+        event_table
+    }
+}
+
+fn get_source_hash<'a>(
+    sources: impl IntoIterator<Item=(&'a Path, &'a OneTickTrdPrlConfig)>) -> u64
+{
+    let mut key = Vec::new();
+    for (files_to_parse, args) in sources {
+        for file in OneTickHistoryReader::read_file_list(files_to_parse) {
+            let metadata = file.metadata().unwrap_or_else(
+                |err| panic!("Cannot get metadata of the file {file:?}. Error: {err}")
+            );
+            let modified = metadata.modified().ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |modified| modified.as_nanos());
+            key.extend(file.to_string_lossy().as_bytes());
+            key.push(0);
+            key.extend(metadata.len().to_le_bytes());
+            key.extend(modified.to_le_bytes());
+        }
+        for field in [
+            &args.datetime_colname,
+            &args.order_id_colname,
+            &args.price_colname,
+            &args.size_colname,
+            &args.buy_sell_flag_colname,
+            &args.datetime_format
+        ] {
+            key.extend(field.as_bytes());
+            key.push(0);
+        }
+        key.extend((args.csv_sep as u32).to_le_bytes());
+        key.extend(args.price_step.to_bits().to_le_bytes());
+        key.push(args.merge_files as u8);
+        for field in [&args.symbol_colname, &args.symbol] {
+            if let Some(field) = field {
+                key.push(1);
+                key.extend(field.as_bytes())
+            }
+            key.push(0);
+        }
+    }
+    stable_hash(&key)
+}
+
+fn load(cache_file: &Path, source_hash: u64) -> Option<OneTickEventTable> {
+    let file = File::open(cache_file).ok()?;
+    // SAFETY: The cache file is not supposed to be modified while it is mapped.
+    // Truncated or foreign files are rejected by the header checks below.
+    let mmap = unsafe { Mmap::map(&file) }.ok()?;
+    if mmap.len() < HEADER_SIZE || &mmap[..MAGIC.len()] != MAGIC {
+        return None;
+    }
+    let read_u64 = |offset: usize| u64::from_le_bytes(
+        mmap[offset..offset + 8].try_into().unwrap_or_else(
+            |err| unreachable!("Slice should be 8 bytes long. Error: {err}")
+        )
+    );
+    let num_prl_entries = read_u64(MAGIC.len() + 8) as usize;
+    let num_trd_entries = read_u64(MAGIC.len() + 16) as usize;
+    if read_u64(MAGIC.len()) != source_hash
+        || mmap.len() != HEADER_SIZE + (num_prl_entries + num_trd_entries) * RECORD_SIZE
+    {
+        return None;
+    }
+    let mut entries = mmap[HEADER_SIZE..].chunks_exact(RECORD_SIZE).map(decode);
+    let prl_entries = entries.by_ref().take(num_prl_entries).collect::<Option<_>>()?;
+    let trd_entries = entries.collect::<Option<_>>()?;
+    Some(OneTickEventTable { prl_entries, trd_entries })
+}
+
+fn store(event_table: &OneTickEventTable, cache_file: &Path, source_hash: u64) {
+    let tmp_file = {
+        let mut tmp_file = cache_file.as_os_str().to_owned();
+        tmp_file.push(
+            format!(
+                ".{}.{}.tmp",
+                std::process::id(),
+                NEXT_TMP_FILE_NO.fetch_add(1, Ordering::Relaxed)
+            )
+        );
+        PathBuf::from(tmp_file)
+    };
+    let file = File::create(&tmp_file).unwrap_or_else(
+        |err| panic!("Cannot create file {tmp_file:?}. Error: {err}")
+    );
+    let mut writer = BufWriter::new(file);
+    let mut write = |bytes: &[u8]| writer.write_all(bytes).unwrap_or_else(
+        |err| panic!("Cannot write to file {tmp_file:?}. Error: {err}")
+    );
+    write(MAGIC);
+    write(&source_hash.to_le_bytes());
+    write(&(event_table.prl_entries.len() as u64).to_le_bytes());
+    write(&(event_table.trd_entries.len() as u64).to_le_bytes());
+    for entry in event_table.prl_entries.iter().chain(event_table.trd_entries.iter()) {
+        write(&encode(entry))
+    }
+    writer.flush().unwrap_or_else(|err| panic!("Cannot write to file {tmp_file:?}. Error: {err}"));
+    rename(&tmp_file, cache_file).unwrap_or_else(
+        |err| panic!("Cannot rename file {tmp_file:?} to {cache_file:?}. Error: {err}")
+    )
+}
+
+fn epoch() -> DateTime {
+    Date::from_ymd_opt(1970, 1, 1)
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .unwrap_or_else(|| unreachable!("Unix epoch should be a valid datetime"))
+}
+
+fn encode(entry: &HistoryEntry) -> [u8; RECORD_SIZE] {
+    let since_epoch = entry.datetime - epoch();
+    let mut record = [0; RECORD_SIZE];
+    record[0..8].copy_from_slice(&since_epoch.num_seconds().to_le_bytes());
+    record[8..12].copy_from_slice(&since_epoch.subsec_nanos().to_le_bytes());
+    record[12..20].copy_from_slice(&entry.size.0.to_le_bytes());
+    record[20] = match entry.direction {
+        Direction::Buy => 0,
+        Direction::Sell => 1
+    };
+    record[21..29].copy_from_slice(&entry.price.0.to_le_bytes());
+    record[29..37].copy_from_slice(&entry.order_id.0.to_le_bytes());
+    record
+}
+
+fn decode(record: &[u8]) -> Option<HistoryEntry> {
+    let read_i64 = |offset: usize| Some(
+        i64::from_le_bytes(record[offset..offset + 8].try_into().ok()?)
+    );
+    let seconds = read_i64(0)?;
+    let nanoseconds = i32::from_le_bytes(record[8..12].try_into().ok()?);
+    Some(
+        HistoryEntry {
+            datetime: epoch()
+                + Duration::seconds(seconds)
+                + Duration::nanoseconds(nanoseconds as i64),
+            size: Lots(read_i64(12)?),
+            direction: match record[20] {
+                0 => Direction::Buy,
+                1 => Direction::Sell,
+                _ => return None
+            },
+            price: Tick(read_i64(21)?),
+            order_id: OrderID(read_i64(29)? as u64),
+        }
+    )
+}
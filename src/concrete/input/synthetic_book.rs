@@ -0,0 +1,434 @@
+use {
+    crate::{
+        concrete::{
+            message_protocol::replay::request::BasicReplayRequest,
+            order::{
+                LimitOrderCancelRequest,
+                LimitOrderPlacingRequest,
+                MarketOrderPlacingRequest,
+                TimeInForce,
+            },
+            traded_pair::{settlement::GetSettlementLag, TradedPair},
+            types::{Direction, Lots, OrderID, Tick},
+        },
+        types::{DateTime, Id, NanoDuration},
+        utils::collections::HashMap,
+    },
+    rand::{rngs::StdRng, Rng, SeedableRng},
+    std::{
+        cmp::Reverse,
+        collections::{BinaryHeap, BTreeMap, VecDeque},
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Parameters of the stochastically generated resting depth
+/// used to replay trades when the history of the limit orders is unavailable.
+pub struct SyntheticBookConfig {
+    /// Number of price levels to maintain on each side of the last trade price.
+    pub num_levels: usize,
+    /// Mean size of the synthetic limit order.
+    /// Sizes are drawn from the exponential distribution and rounded up.
+    pub mean_order_size: f64,
+    /// Mean lifetime of the synthetic limit order.
+    /// Lifetimes are drawn from the exponential distribution.
+    /// The limit order is cancelled once its lifetime expires.
+    pub mean_lifetime: NanoDuration,
+    /// Seed of the random number generator.
+    pub seed: u64,
+}
+
+/// Resting depth generated around the historical trades.
+///
+/// Before every trade the synthetic orders that contradict the trade price are cancelled
+/// and the passive side is filled up with enough depth to execute the trade.
+/// After the trade the empty levels around the trade price are replenished.
+///
+/// The fills of the replayed trades are predicted at once, while the depth taken by the traders
+/// is reconciled from the exchange replies.
+pub(crate) struct SyntheticBook {
+    config: SyntheticBookConfig,
+    rng: StdRng,
+    orders: HashMap<OrderID, (Direction, Tick, Lots)>,
+    /// Predicted fills of the synthetic orders not yet reported by the exchange.
+    unconfirmed_fills: HashMap<OrderID, Lots>,
+    bids: BTreeMap<Tick, VecDeque<OrderID>>,
+    asks: BTreeMap<Tick, VecDeque<OrderID>>,
+    expirations: BinaryHeap<Reverse<(DateTime, OrderID)>>,
+}
+
+impl SyntheticBook {
+    pub fn new(config: SyntheticBookConfig) -> Self {
+        if config.mean_order_size <= 0.0 {
+            panic!("Mean synthetic order size should be positive. Got: {}", config.mean_order_size)
+        }
+        if config.mean_lifetime.is_zero() {
+            panic!("Mean synthetic order lifetime should be positive")
+        }
+        Self {
+            rng: StdRng::seed_from_u64(config.seed),
+            config,
+            orders: Default::default(),
+            unconfirmed_fills: Default::default(),
+            bids: Default::default(),
+            asks: Default::default(),
+            expirations: Default::default(),
+        }
+    }
+
+    /// Forgets all the synthetic orders.
+    pub fn clear(&mut self) {
+        self.orders.clear();
+        self.unconfirmed_fills.clear();
+        self.bids.clear();
+        self.asks.clear();
+        self.expirations.clear()
+    }
+
+    /// Pops the earliest expired synthetic order if it expires no later than the `until`.
+    pub fn pop_expired(&mut self, until: DateTime) -> Option<(DateTime, OrderID)> {
+        while let Some(Reverse((datetime, order_id))) = self.expirations.peek().copied() {
+            if !self.orders.contains_key(&order_id) {
+                self.expirations.pop();
+                continue;
+            }
+            if datetime > until {
+                return None;
+            }
+            self.expirations.pop();
+            self.remove_order(order_id);
+            return Some((datetime, order_id));
+        }
+        None
+    }
+
+    /// Reconciles the partial execution of the synthetic order reported by the exchange.
+    /// The part of the fill not predicted from the replayed trades is the depth
+    /// taken by the traders, so it is removed from the synthetic order.
+    ///
+    /// # Arguments
+    ///
+    /// * `order_id` — ID of the synthetic order.
+    /// * `size` — Executed size.
+    pub fn on_order_partially_executed(&mut self, order_id: OrderID, size: Lots) {
+        let predicted = if let Some(unconfirmed) = self.unconfirmed_fills.get_mut(&order_id) {
+            let predicted = size.min(*unconfirmed);
+            *unconfirmed -= predicted;
+            if *unconfirmed == Lots(0) {
+                self.unconfirmed_fills.remove(&order_id);
+            }
+            predicted
+        } else {
+            Lots(0)
+        };
+        let taken = size - predicted;
+        if taken == Lots(0) {
+            return;
+        }
+        if let Some((_, _, order_size)) = self.orders.get_mut(&order_id) {
+            if *order_size > taken {
+                *order_size -= taken
+            } else {
+                self.remove_order(order_id)
+            }
+        }
+    }
+
+    /// Forgets the synthetic order that the exchange reported as executed or cancelled.
+    ///
+    /// # Arguments
+    ///
+    /// * `order_id` — ID of the synthetic order.
+    pub fn on_order_removed(&mut self, order_id: OrderID) {
+        self.unconfirmed_fills.remove(&order_id);
+        self.remove_order(order_id)
+    }
+
+    /// Returns requests that reproduce the historical trade.
+    ///
+    /// # Arguments
+    ///
+    /// * `datetime` — Datetime of the trade.
+    /// * `traded_pair` — Traded pair.
+    /// * `direction` — Direction of the aggressive order.
+    /// * `price` — Trade price.
+    /// * `size` — Trade size.
+    /// * `generate_order_id` — Generator of the new order IDs.
+    pub fn process_trade<Symbol, Settlement>(
+        &mut self,
+        datetime: DateTime,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        direction: Direction,
+        price: Tick,
+        size: Lots,
+        mut generate_order_id: impl FnMut() -> OrderID,
+    ) -> Vec<BasicReplayRequest<Symbol, Settlement>>
+        where Symbol: Id,
+              Settlement: GetSettlementLag
+    {
+        let mut requests = Vec::new();
+        let (passive, passive_step, contradicting_orders) = match direction {
+            Direction::Buy => (
+                Direction::Sell,
+                1,
+                self.asks.range(..price)
+                    .chain(self.bids.range(price..))
+                    .flat_map(|(_, level)| level.iter().copied())
+                    .collect::<Vec<_>>()
+            ),
+            Direction::Sell => (
+                Direction::Buy,
+                -1,
+                self.bids.range(price + Tick(1)..)
+                    .chain(self.asks.range(..=price))
+                    .flat_map(|(_, level)| level.iter().copied())
+                    .collect::<Vec<_>>()
+            )
+        };
+        for order_id in contradicting_orders {
+            self.remove_order(order_id);
+            requests.push(
+                BasicReplayRequest::CancelLimitOrder(
+                    LimitOrderCancelRequest { traded_pair, order_id }
+                )
+            )
+        }
+
+        let depth = self.level_depth(passive, price);
+        if depth < size {
+            let order_size = size - depth + self.sample_size();
+            requests.push(
+                self.place(datetime, traded_pair, passive, price, order_size, &mut generate_order_id)
+            )
+        }
+        requests.push(
+            BasicReplayRequest::PlaceMarketOrder(
+                MarketOrderPlacingRequest {
+                    traded_pair,
+                    order_id: generate_order_id(),
+                    direction,
+                    size,
+                    dummy: false,
+                }
+            )
+        );
+        self.consume(passive, price, size);
+
+        for level in 0..self.config.num_levels as i64 {
+            let passive_price = price + Tick(passive_step * level);
+            let aggressive_price = price - Tick(passive_step * (level + 1));
+            for (side, level_price) in [(passive, passive_price), (direction, aggressive_price)] {
+                if self.level_depth(side, level_price) == Lots(0) {
+                    let order_size = self.sample_size();
+                    requests.push(
+                        self.place(
+                            datetime, traded_pair, side, level_price, order_size,
+                            &mut generate_order_id,
+                        )
+                    )
+                }
+            }
+        }
+        requests
+    }
+
+    fn place<Symbol, Settlement>(
+        &mut self,
+        datetime: DateTime,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        direction: Direction,
+        price: Tick,
+        size: Lots,
+        generate_order_id: &mut impl FnMut() -> OrderID,
+    ) -> BasicReplayRequest<Symbol, Settlement>
+        where Symbol: Id,
+              Settlement: GetSettlementLag
+    {
+        let order_id = generate_order_id();
+        let lifetime = self.sample_exponential(self.config.mean_lifetime.as_nanos() as f64);
+        self.expirations.push(
+            Reverse((datetime + NanoDuration(lifetime.ceil() as u64), order_id))
+        );
+        self.orders.insert(order_id, (direction, price, size));
+        self.side_mut(direction).entry(price).or_default().push_back(order_id);
+        BasicReplayRequest::PlaceLimitOrder(
+            LimitOrderPlacingRequest {
+                traded_pair,
+                order_id,
+                direction,
+                price,
+                size,
+                dummy: false,
+                time_in_force: TimeInForce::Day,
+            }
+        )
+    }
+
+    fn consume(&mut self, direction: Direction, price: Tick, mut size: Lots) {
+        let level = match direction {
+            Direction::Buy => self.bids.get(&price),
+            Direction::Sell => self.asks.get(&price)
+        };
+        let level = if let Some(level) = level { level } else { return; };
+        let mut executed = Vec::new();
+        for order_id in level.iter() {
+            if size == Lots(0) {
+                break;
+            }
+            let (_, _, order_size) = self.orders.get_mut(order_id).unwrap_or_else(
+                || unreachable!("Cannot find synthetic order with ID {order_id}")
+            );
+            let filled = size.min(*order_size);
+            *order_size -= filled;
+            size -= filled;
+            *self.unconfirmed_fills.entry(*order_id).or_insert(Lots(0)) += filled;
+            if *order_size == Lots(0) {
+                executed.push(*order_id)
+            }
+        }
+        executed.into_iter().for_each(|order_id| self.remove_order(order_id))
+    }
+
+    fn remove_order(&mut self, order_id: OrderID) {
+        if let Some((direction, price, _)) = self.orders.remove(&order_id) {
+            let side = self.side_mut(direction);
+            if let Some(level) = side.get_mut(&price) {
+                level.retain(|id| *id != order_id);
+                if level.is_empty() {
+                    side.remove(&price);
+                }
+            }
+        }
+    }
+
+    fn level_depth(&self, direction: Direction, price: Tick) -> Lots {
+        let side = match direction {
+            Direction::Buy => &self.bids,
+            Direction::Sell => &self.asks
+        };
+        side.get(&price)
+            .map(|level| level.iter().map(|order_id| self.orders[order_id].2).sum())
+            .unwrap_or(Lots(0))
+    }
+
+    fn side_mut(&mut self, direction: Direction) -> &mut BTreeMap<Tick, VecDeque<OrderID>> {
+        match direction {
+            Direction::Buy => &mut self.bids,
+            Direction::Sell => &mut self.asks
+        }
+    }
+
+    fn sample_size(&mut self) -> Lots {
+        Lots(self.sample_exponential(self.config.mean_order_size).ceil().max(1.0) as i64)
+    }
+
+    fn sample_exponential(&mut self, mean: f64) -> f64 {
+        -(1.0 - self.rng.gen::<f64>()).ln() * mean
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            concrete::traded_pair::settlement::concrete::SpotSettlement,
+            test_utils::{dt, pair},
+        },
+        super::*,
+    };
+
+    fn config(seed: u64) -> SyntheticBookConfig {
+        SyntheticBookConfig { num_levels: 2, mean_order_size: 3.0, mean_lifetime: NanoDuration(1_000), seed }
+    }
+
+    fn trade(
+        book: &mut SyntheticBook,
+        next_id: &mut u64,
+        direction: Direction,
+        price: i64,
+        size: i64) -> Vec<BasicReplayRequest<&'static str, SpotSettlement>>
+    {
+        book.process_trade(
+            dt(0), pair(), direction, Tick(price), Lots(size),
+            || {
+                *next_id += 1;
+                OrderID(*next_id)
+            },
+        )
+    }
+
+    #[test]
+    fn test_process_trade_is_deterministic() {
+        let replay = |seed| {
+            let mut book = SyntheticBook::new(config(seed));
+            let mut next_id = 0;
+            [(Direction::Buy, 100, 5), (Direction::Sell, 99, 4), (Direction::Buy, 101, 6)]
+                .into_iter()
+                .flat_map(
+                    |(direction, price, size)| trade(&mut book, &mut next_id, direction, price, size)
+                )
+                .collect::<Vec<_>>()
+        };
+        let requests = replay(7);
+        assert!(
+            matches!(
+                requests[..2],
+                [
+                    BasicReplayRequest::PlaceLimitOrder(
+                        LimitOrderPlacingRequest {
+                            direction: Direction::Sell, price: Tick(100), size, ..
+                        }
+                    ),
+                    BasicReplayRequest::PlaceMarketOrder(
+                        MarketOrderPlacingRequest {
+                            direction: Direction::Buy, size: Lots(5), ..
+                        }
+                    ),
+                ] if size > Lots(5)
+            )
+        );
+        assert_eq!(requests, replay(7));
+        assert_ne!(requests, replay(8))
+    }
+
+    #[test]
+    fn test_reconcile_depth_taken_by_traders() {
+        let mut book = SyntheticBook::new(config(7));
+        let mut next_id = 0;
+        let requests = trade(&mut book, &mut next_id, Direction::Buy, 100, 5);
+        let (passive_id, passive_size) = match requests[0] {
+            BasicReplayRequest::PlaceLimitOrder(order) => (order.order_id, order.size),
+            _ => unreachable!("Synthetic book should first place the passive order")
+        };
+        let remaining_size = passive_size - Lots(5);
+        assert_eq!(book.level_depth(Direction::Sell, Tick(100)), remaining_size);
+
+        // Fill of the replayed trade is already accounted for
+        book.on_order_partially_executed(passive_id, Lots(5));
+        assert_eq!(book.level_depth(Direction::Sell, Tick(100)), remaining_size);
+        assert!(book.unconfirmed_fills.is_empty());
+
+        // Trader takes the rest of the passive order
+        book.on_order_removed(passive_id);
+        assert_eq!(book.level_depth(Direction::Sell, Tick(100)), Lots(0));
+
+        // Trader takes a single lot from the next level
+        let depth = book.level_depth(Direction::Sell, Tick(101));
+        let order_id = book.asks[&Tick(101)][0];
+        book.on_order_partially_executed(order_id, Lots(1));
+        assert_eq!(book.level_depth(Direction::Sell, Tick(101)), depth - Lots(1));
+
+        // Order that is already gone is not cancelled once contradicting the trade price
+        let requests = trade(&mut book, &mut next_id, Direction::Sell, 100, 1);
+        assert!(
+            !requests.iter().any(
+                |request| matches!(
+                    request,
+                    BasicReplayRequest::CancelLimitOrder(
+                        LimitOrderCancelRequest { order_id, .. }
+                    ) if *order_id == passive_id
+                )
+            )
+        )
+    }
+}
@@ -1,9 +1,12 @@
 use {
-    crate::{interface::latency::LatencyGenerator, types::{DateTime, Id}},
+    crate::{interface::latency::LatencyGenerator, types::{DateTime, Id, NanoDuration}},
     rand::Rng,
-    std::marker::PhantomData,
+    std::{marker::PhantomData, num::NonZeroU64},
 };
 
+/// End-to-end latencies of the trader → broker → exchange paths.
+pub mod path;
+
 /// Constant [`LatencyGenerator`].
 #[derive(Copy, Clone, Default)]
 pub struct ConstantLatency<OuterID: Id, const OUTGOING: u64, const INCOMING: u64>
@@ -24,10 +27,131 @@ for ConstantLatency<OuterID, OUTGOING, INCOMING>
 {
     type OuterID = OuterID;
 
-    fn outgoing_latency(&mut self, _: Self::OuterID, _: DateTime, _: &mut impl Rng) -> u64 {
-        OUTGOING
+    fn outgoing_latency(
+        &mut self,
+        _: Self::OuterID,
+        _: DateTime,
+        _: &mut impl Rng) -> NanoDuration
+    {
+        NanoDuration(OUTGOING)
+    }
+    fn incoming_latency(
+        &mut self,
+        _: Self::OuterID,
+        _: DateTime,
+        _: &mut impl Rng) -> NanoDuration
+    {
+        NanoDuration(INCOMING)
+    }
+}
+
+/// [`LatencyGenerator`] limiting the throughput of the links of the wrapped one,
+/// e.g. to model retail or WAN connectivity.
+/// Bursts of messages, such as full OB-snapshots, are queued before the latent delay is sampled.
+#[derive(Copy, Clone)]
+pub struct BandwidthLatency<L: LatencyGenerator> {
+    inner: L,
+    outgoing_bandwidth: Option<NonZeroU64>,
+    incoming_bandwidth: Option<NonZeroU64>,
+}
+
+impl<L: LatencyGenerator> BandwidthLatency<L>
+{
+    /// Creates a new instance of the `BandwidthLatency`.
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` — Generator of the latent delays.
+    /// * `outgoing_bandwidth` — Throughput of the outgoing links, in bytes per second.
+    /// * `incoming_bandwidth` — Throughput of the incoming links, in bytes per second.
+    pub fn new(
+        inner: L,
+        outgoing_bandwidth: Option<NonZeroU64>,
+        incoming_bandwidth: Option<NonZeroU64>) -> Self
+    {
+        Self { inner, outgoing_bandwidth, incoming_bandwidth }
+    }
+}
+
+impl<L: LatencyGenerator> LatencyGenerator for BandwidthLatency<L>
+{
+    type OuterID = L::OuterID;
+
+    fn outgoing_latency(
+        &mut self,
+        outer_id: Self::OuterID,
+        event_dt: DateTime,
+        rng: &mut impl Rng) -> NanoDuration
+    {
+        self.inner.outgoing_latency(outer_id, event_dt, rng)
+    }
+    fn incoming_latency(
+        &mut self,
+        outer_id: Self::OuterID,
+        event_dt: DateTime,
+        rng: &mut impl Rng) -> NanoDuration
+    {
+        self.inner.incoming_latency(outer_id, event_dt, rng)
+    }
+    fn outgoing_bandwidth(&self, _: Self::OuterID) -> Option<NonZeroU64> {
+        self.outgoing_bandwidth
+    }
+    fn incoming_bandwidth(&self, _: Self::OuterID) -> Option<NonZeroU64> {
+        self.incoming_bandwidth
+    }
+    fn save_state(&self) -> Option<Vec<u8>> {
+        self.inner.save_state()
+    }
+    fn restore_state(&mut self, state: &[u8]) {
+        self.inner.restore_state(state)
+    }
+}
+
+/// Model of the time the [`Trader`](crate::interface::trader::Trader) spends
+/// computing its decisions. Meant to be sampled by the
+/// [`Trader::sample_decision_latency`](crate::interface::trader::Trader::sample_decision_latency).
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DecisionLatency {
+    /// Every decision takes the same time.
+    Fixed(NanoDuration),
+    /// Decision time is sampled uniformly from the inclusive range.
+    Uniform {
+        min: NanoDuration,
+        max: NanoDuration,
+    },
+}
+
+impl Default for DecisionLatency {
+    fn default() -> Self {
+        Self::Fixed(NanoDuration::ZERO)
     }
-    fn incoming_latency(&mut self, _: Self::OuterID, _: DateTime, _: &mut impl Rng) -> u64 {
-        INCOMING
+}
+
+impl DecisionLatency {
+    /// Creates a new instance of the `DecisionLatency::Uniform`.
+    ///
+    /// # Arguments
+    ///
+    /// * `min` — Minimum decision time.
+    /// * `max` — Maximum decision time.
+    pub fn uniform(min: NanoDuration, max: NanoDuration) -> Self {
+        if min > max {
+            panic!("Minimum decision latency {min} is greater than the maximum one {max}")
+        }
+        Self::Uniform { min, max }
     }
-}
\ No newline at end of file
+
+    /// Samples the decision time. Fixed decision time does not consume the `rng`.
+    ///
+    /// # Arguments
+    ///
+    /// * `rng` — Random number generator.
+    pub fn sample(&self, rng: &mut impl Rng) -> NanoDuration {
+        match *self {
+            Self::Fixed(latency) => latency,
+            Self::Uniform { min, max } => {
+                NanoDuration::from_nanos(rng.gen_range(min.as_nanos()..=max.as_nanos()))
+            }
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use {
+    crate::{interface::latency::LatencyGenerator, types::{DateTime, NanoDuration}},
+    rand::Rng,
+    std::fmt::{Display, Formatter},
+};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+/// Summary of the sampled distribution of the latent delays.
+pub struct LatencyStats {
+    /// Minimum delay.
+    pub min: NanoDuration,
+    /// Mean delay, rounded down.
+    pub mean: NanoDuration,
+    /// Median delay.
+    pub median: NanoDuration,
+    /// 99th percentile of the delays.
+    pub p99: NanoDuration,
+    /// Maximum delay.
+    pub max: NanoDuration,
+}
+
+impl LatencyStats {
+    /// Summarizes the sampled delays. Returns `None` if there are no samples.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` — Sampled delays in arbitrary order.
+    pub fn from_samples(mut samples: Vec<NanoDuration>) -> Option<Self> {
+        samples.sort_unstable();
+        let (min, max) = (*samples.first()?, *samples.last()?);
+        // Nearest-rank percentile
+        let percentile = |p: usize| samples[(samples.len() * p).div_ceil(100).max(1) - 1];
+        let sum: u128 = samples.iter().map(|delay| delay.0 as u128).sum();
+        Some(
+            Self {
+                min,
+                mean: NanoDuration((sum / samples.len() as u128) as u64),
+                median: percentile(50),
+                p99: percentile(99),
+                max,
+            }
+        )
+    }
+}
+
+impl Display for LatencyStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let Self { min, mean, median, p99, max } = self;
+        write!(f, "min {min}, mean {mean}, median {median}, p99 {p99}, max {max}")
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+/// End-to-end latency distributions of the [`LatencyPath`].
+pub struct PathLatencyReport {
+    /// Number of the sampled round trips.
+    pub num_samples: usize,
+    /// One-way delays from the trader to the exchange.
+    pub request: LatencyStats,
+    /// One-way delays from the exchange to the trader.
+    pub reply: LatencyStats,
+    /// Round-trip delays, i.e. the sums of the request and the reply delays
+    /// of the same round trips.
+    pub round_trip: LatencyStats,
+}
+
+impl Display for PathLatencyReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Latency path, {} samples:", self.num_samples)?;
+        writeln!(f, "  trader -> exchange: {}", self.request)?;
+        writeln!(f, "  exchange -> trader: {}", self.reply)?;
+        write!(f, "  round trip: {}", self.round_trip)
+    }
+}
+
+/// Composes the latency models of the trader and the broker
+/// along the trader → broker → exchange path and back.
+///
+/// Hops are sampled in the order the [`Kernel`](crate::kernel::Kernel) samples them,
+/// each at the datetime the message is sent over the hop:
+///
+/// 1. Trader → broker: outgoing latency of the trader generator.
+/// 2. Broker → exchange: outgoing latency of the broker generator.
+/// 3. Exchange → broker: incoming latency of the broker generator.
+/// 4. Broker → trader: incoming latency of the trader generator.
+///
+/// Processing is assumed to be instantaneous. Action delays of the agents,
+/// e.g. the decision latencies, and the link queueing are not included.
+///
+/// Generators are [`Copy`], so the `LatencyPath` should be given copies of the ones
+/// used in the simulation, so as not to affect their states.
+///
+/// # Examples
+///
+/// ```
+/// use {
+///     rand::{rngs::StdRng, SeedableRng},
+///     trading_backtester::{
+///         concrete::latency::{path::LatencyPath, ConstantLatency},
+///         types::{DateTime, NanoDuration},
+///     },
+/// };
+///
+/// let mut path = LatencyPath::new(
+///     ConstantLatency::<u8, 100, 150>::new(), 0,
+///     ConstantLatency::<u8, 20, 30>::new(), 0,
+/// );
+/// let mut rng = StdRng::seed_from_u64(0);
+/// let report = path.sample(DateTime::default(), 1000, &mut rng);
+/// assert_eq!(report.request.max, NanoDuration(120));
+/// assert_eq!(report.round_trip.median, NanoDuration(300));
+/// ```
+pub struct LatencyPath<TraderLatency, BrokerLatency>
+    where TraderLatency: LatencyGenerator,
+          BrokerLatency: LatencyGenerator
+{
+    trader_latency: TraderLatency,
+    broker_id: TraderLatency::OuterID,
+    broker_latency: BrokerLatency,
+    exchange_id: BrokerLatency::OuterID,
+}
+
+impl<TraderLatency, BrokerLatency> LatencyPath<TraderLatency, BrokerLatency>
+    where TraderLatency: LatencyGenerator,
+          BrokerLatency: LatencyGenerator
+{
+    /// Creates a new instance of the `LatencyPath`.
+    ///
+    /// # Arguments
+    ///
+    /// * `trader_latency` — Latency generator of the trader.
+    /// * `broker_id` — ID of the broker the trader is connected to.
+    /// * `broker_latency` — Latency generator of the broker.
+    /// * `exchange_id` — ID of the exchange the broker is connected to.
+    pub fn new(
+        trader_latency: TraderLatency,
+        broker_id: TraderLatency::OuterID,
+        broker_latency: BrokerLatency,
+        exchange_id: BrokerLatency::OuterID) -> Self
+    {
+        Self { trader_latency, broker_id, broker_latency, exchange_id }
+    }
+
+    /// Samples the delay of the message sent by the trader to the exchange.
+    ///
+    /// # Arguments
+    ///
+    /// * `sent_dt` — Datetime at which the trader sends the message.
+    /// * `rng` — Random number generator.
+    pub fn sample_request(&mut self, sent_dt: DateTime, rng: &mut impl Rng) -> NanoDuration {
+        let to_broker = self.trader_latency.outgoing_latency(self.broker_id, sent_dt, rng);
+        let to_exchange = self.broker_latency.outgoing_latency(
+            self.exchange_id,
+            sent_dt + to_broker,
+            rng,
+        );
+        to_broker + to_exchange
+    }
+
+    /// Samples the delay of the message sent by the exchange to the trader.
+    ///
+    /// # Arguments
+    ///
+    /// * `sent_dt` — Datetime at which the exchange sends the message.
+    /// * `rng` — Random number generator.
+    pub fn sample_reply(&mut self, sent_dt: DateTime, rng: &mut impl Rng) -> NanoDuration {
+        let to_broker = self.broker_latency.incoming_latency(self.exchange_id, sent_dt, rng);
+        let to_trader = self.trader_latency.incoming_latency(
+            self.broker_id,
+            sent_dt + to_broker,
+            rng,
+        );
+        to_broker + to_trader
+    }
+
+    /// Samples the round trips starting at the given datetime
+    /// and summarizes the one-way and the round-trip delays.
+    ///
+    /// # Arguments
+    ///
+    /// * `sent_dt` — Datetime at which the trader sends the requests.
+    /// * `num_samples` — Number of the round trips to sample. Should be positive.
+    /// * `rng` — Random number generator.
+    pub fn sample(
+        &mut self,
+        sent_dt: DateTime,
+        num_samples: usize,
+        rng: &mut impl Rng) -> PathLatencyReport
+    {
+        if num_samples == 0 {
+            panic!("Number of samples should be positive")
+        }
+        let mut requests = Vec::with_capacity(num_samples);
+        let mut replies = Vec::with_capacity(num_samples);
+        let mut round_trips = Vec::with_capacity(num_samples);
+        for _ in 0..num_samples {
+            let request = self.sample_request(sent_dt, rng);
+            let reply = self.sample_reply(sent_dt + request, rng);
+            requests.push(request);
+            replies.push(reply);
+            round_trips.push(request + reply)
+        }
+        let summarize = |samples| LatencyStats::from_samples(samples).unwrap_or_else(
+            || unreachable!("Samples should not be empty")
+        );
+        PathLatencyReport {
+            num_samples,
+            request: summarize(requests),
+            reply: summarize(replies),
+            round_trip: summarize(round_trips),
+        }
+    }
+}
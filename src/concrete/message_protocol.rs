@@ -5,4 +5,8 @@ pub mod exchange;
 /// [`Replay`](crate::interface::replay::Replay)-outgoing messages.
 pub mod replay;
 /// [`Trader`](crate::interface::trader::Trader)-outgoing messages.
-pub mod trader;
\ No newline at end of file
+pub mod trader;
+
+/// Size, in bytes, of the fixed part of every basic message
+/// transmitted over the bandwidth-constrained link.
+pub const MESSAGE_HEADER_SIZE: u64 = 64;
\ No newline at end of file
@@ -3,4 +3,7 @@
 pub mod reply;
 /// Basic implementation of the [`BrokerToExchange`](crate::interface::message::BrokerToExchange)
 /// message.
-pub mod request;
\ No newline at end of file
+pub mod request;
+/// Basic implementation of the [`BrokerToItself`](crate::interface::message::BrokerToItself)
+/// messages.
+pub mod wakeup;
\ No newline at end of file
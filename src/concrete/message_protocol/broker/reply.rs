@@ -1,20 +1,35 @@
-use crate::{
-    concrete::{
-        message_protocol::exchange::reply::{
-            ExchangeEventNotification,
-            MarketOrderNotFullyExecuted,
-            OrderAccepted,
-            OrderExecuted,
-            OrderPartiallyExecuted,
+use {
+    crate::{
+        concrete::{
+            broker::portfolio::PortfolioSnapshot,
+            heartbeat::Heartbeat,
+            message_protocol::{
+                exchange::reply::{
+                    ExchangeEventNotification,
+                    MarketOrderNotFullyExecuted,
+                    OrderAccepted,
+                    OrderExecuted,
+                    OrderPartiallyExecuted,
+                    OrderPriceAdjusted,
+                    OrderSizeReduced,
+                    OrderStatus,
+                    RecoverySnapshot,
+                    StopOrderTriggered,
+                    TradeBusted,
+                },
+                MESSAGE_HEADER_SIZE,
+            },
+            traded_pair::{settlement::GetSettlementLag, TradedPair},
+            types::{AccountID, Direction, Lots, OrderID, Tick},
         },
-        traded_pair::{settlement::GetSettlementLag, TradedPair},
-        types::OrderID,
+        interface::message::BrokerToTrader,
+        types::{DateTime, Id},
     },
-    interface::message::BrokerToTrader,
-    types::{DateTime, Id},
+    std::rc::Rc,
 };
 
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BasicBrokerToTrader<
     TraderID: Id,
     ExchangeID: Id,
@@ -35,29 +50,104 @@ for BasicBrokerToTrader<TraderID, ExchangeID, Symbol, Settlement>
     fn get_trader_id(&self) -> Self::TraderID {
         self.trader_id
     }
+
+    fn get_size_in_bytes(&self) -> u64 {
+        match &self.content {
+            BasicBrokerReply::ExchangeEventNotification(
+                ExchangeEventNotification::ObSnapshot(snapshot)
+            ) => MESSAGE_HEADER_SIZE + snapshot.state.get_size_in_bytes(),
+            BasicBrokerReply::ExchangeEventNotification(
+                ExchangeEventNotification::ObDiff(diff)
+            ) => MESSAGE_HEADER_SIZE + diff.get_size_in_bytes(),
+            BasicBrokerReply::RecoverySnapshot(snapshot) => {
+                MESSAGE_HEADER_SIZE + snapshot.get_size_in_bytes()
+            }
+            _ => MESSAGE_HEADER_SIZE
+        }
+    }
+
+    fn get_event_dt(&self) -> Option<DateTime> {
+        Some(self.event_dt)
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BasicBrokerReply<Symbol: Id, Settlement: GetSettlementLag>
 {
     OrderAccepted(OrderAccepted<Symbol, Settlement>),
 
     OrderPlacementDiscarded(OrderPlacementDiscarded<Symbol, Settlement>),
 
+    OrderPriceAdjusted(OrderPriceAdjusted<Symbol, Settlement>),
+
     OrderPartiallyExecuted(OrderPartiallyExecuted<Symbol, Settlement>),
 
     OrderExecuted(OrderExecuted<Symbol, Settlement>),
 
+    ShadowOrderPartiallyExecuted(OrderPartiallyExecuted<Symbol, Settlement>),
+
+    ShadowOrderExecuted(OrderExecuted<Symbol, Settlement>),
+
+    GhostOrderPartiallyExecuted(OrderPartiallyExecuted<Symbol, Settlement>),
+
+    GhostOrderExecuted(OrderExecuted<Symbol, Settlement>),
+
     MarketOrderNotFullyExecuted(MarketOrderNotFullyExecuted<Symbol, Settlement>),
 
+    StopOrderTriggered(StopOrderTriggered<Symbol, Settlement>),
+
     OrderCancelled(OrderCancelled<Symbol, Settlement>),
 
+    OrderCarriedOver(OrderCarriedOver<Symbol, Settlement>),
+
     CannotCancelOrder(CannotCancelOrder<Symbol, Settlement>),
 
+    OrderSizeReduced(OrderSizeReduced<Symbol, Settlement>),
+
+    CannotReduceOrderSize(CannotCancelOrder<Symbol, Settlement>),
+
     ExchangeEventNotification(ExchangeEventNotification<Symbol, Settlement>),
+
+    FirmQuote(FirmQuote<Symbol, Settlement>),
+
+    QuoteTraded(QuoteTraded<Symbol, Settlement>),
+
+    QuoteRejected(QuoteRejected<Symbol, Settlement>),
+
+    RecoverySnapshot(Rc<RecoverySnapshot<Symbol, Settlement>>),
+
+    SubscriptionRejected(SubscriptionRejected<Symbol, Settlement>),
+
+    SessionDown,
+
+    SessionUp,
+
+    BorrowRecalled(BorrowRecalled<Symbol, Settlement>),
+
+    BuyInPlaced(BuyIn<Symbol, Settlement>),
+
+    BuyInExecuted(OrderPartiallyExecuted<Symbol, Settlement>),
+
+    BuyInNotFullyExecuted(MarketOrderNotFullyExecuted<Symbol, Settlement>),
+
+    TradeBusted(TradeBusted<Symbol, Settlement>),
+
+    OrderStatus(OrderStatus<Symbol, Settlement>),
+
+    Heartbeat(Heartbeat),
+
+    HeartbeatMissed,
+
+    HeartbeatRestored,
+
+    PortfolioSnapshot(Rc<PortfolioSnapshot<Symbol, Settlement>>),
+
+    PositionClosedOut(PositionClosedOut<Symbol, Settlement>),
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OrderPlacementDiscarded<Symbol: Id, Settlement: GetSettlementLag> {
     pub traded_pair: TradedPair<Symbol, Settlement>,
     pub order_id: OrderID,
@@ -65,6 +155,7 @@ pub struct OrderPlacementDiscarded<Symbol: Id, Settlement: GetSettlementLag> {
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PlacementDiscardingReason
 {
     OrderWithSuchIDAlreadySubmitted,
@@ -78,6 +169,40 @@ pub enum PlacementDiscardingReason
     BrokerNotConnectedToExchange,
 
     TraderNotRegistered,
+
+    OffTickPrice,
+
+    SizeBelowMinimum,
+
+    SizeAboveMaximum,
+
+    SizeNotMultipleOfIncrement,
+
+    NotionalBelowMinimum,
+
+    OutsidePriceBand,
+
+    NoDealerQuote,
+
+    DealerInventoryLimitExceeded,
+
+    TraderNotEntitled,
+
+    BrokerSessionDown,
+
+    KillSwitchTripped,
+
+    ExchangeDown,
+
+    TradeThrough,
+
+    OrderTypeNotSupported,
+
+    NotFullyFillable,
+
+    NotAllowedInTradingPhase,
+
+    AlreadyExpired,
 }
 
 type ExchangePlacementDiscardingReason = crate::concrete::message_protocol::exchange::reply::PlacementDiscardingReason;
@@ -100,11 +225,45 @@ impl From<ExchangePlacementDiscardingReason> for PlacementDiscardingReason {
             ExchangePlacementDiscardingReason::NoSuchTradedPair => {
                 Self::NoSuchTradedPair
             }
+            ExchangePlacementDiscardingReason::OffTickPrice => {
+                Self::OffTickPrice
+            }
+            ExchangePlacementDiscardingReason::SizeBelowMinimum => {
+                Self::SizeBelowMinimum
+            }
+            ExchangePlacementDiscardingReason::SizeAboveMaximum => {
+                Self::SizeAboveMaximum
+            }
+            ExchangePlacementDiscardingReason::SizeNotMultipleOfIncrement => {
+                Self::SizeNotMultipleOfIncrement
+            }
+            ExchangePlacementDiscardingReason::NotionalBelowMinimum => {
+                Self::NotionalBelowMinimum
+            }
+            ExchangePlacementDiscardingReason::OutsidePriceBand => {
+                Self::OutsidePriceBand
+            }
+            ExchangePlacementDiscardingReason::ExchangeDown => {
+                Self::ExchangeDown
+            }
+            ExchangePlacementDiscardingReason::TradeThrough => {
+                Self::TradeThrough
+            }
+            ExchangePlacementDiscardingReason::NotFullyFillable => {
+                Self::NotFullyFillable
+            }
+            ExchangePlacementDiscardingReason::NotAllowedInTradingPhase => {
+                Self::NotAllowedInTradingPhase
+            }
+            ExchangePlacementDiscardingReason::AlreadyExpired => {
+                Self::AlreadyExpired
+            }
         }
     }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OrderCancelled<Symbol: Id, Settlement: GetSettlementLag> {
     pub traded_pair: TradedPair<Symbol, Settlement>,
     pub order_id: OrderID,
@@ -112,14 +271,31 @@ pub struct OrderCancelled<Symbol: Id, Settlement: GetSettlementLag> {
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CancellationReason {
     TraderRequested,
     BrokerRequested,
     TradesStopped,
     ExchangeClosed,
+    OffTickPrice,
+    OutsidePriceBand,
+    SessionDown,
+    KillSwitchTripped,
+    Expired,
+    ExchangeDown,
+    NotImmediatelyExecuted,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OrderCarriedOver<Symbol: Id, Settlement: GetSettlementLag> {
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    pub order_id: OrderID,
+    pub expiration: Option<DateTime>,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CannotCancelOrder<Symbol: Id, Settlement: GetSettlementLag> {
     pub traded_pair: TradedPair<Symbol, Settlement>,
     pub order_id: OrderID,
@@ -127,6 +303,7 @@ pub struct CannotCancelOrder<Symbol: Id, Settlement: GetSettlementLag> {
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum InabilityToCancelReason
 {
     OrderHasNotBeenSubmitted,
@@ -140,6 +317,10 @@ pub enum InabilityToCancelReason
     BrokerNotConnectedToExchange,
 
     TraderNotRegistered,
+
+    BrokerSessionDown,
+
+    ExchangeDown,
 }
 
 type ExchangeInabilityToCancelReason = crate::concrete::message_protocol::exchange::reply::InabilityToCancelReason;
@@ -162,6 +343,111 @@ impl From<ExchangeInabilityToCancelReason> for InabilityToCancelReason {
             ExchangeInabilityToCancelReason::NoSuchTradedPair => {
                 Self::NoSuchTradedPair
             }
+            ExchangeInabilityToCancelReason::ExchangeDown => {
+                Self::ExchangeDown
+            }
         }
     }
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PositionClosedOut<Symbol: Id, Settlement: GetSettlementLag> {
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    pub account: AccountID,
+    /// Size closed out of each leg. Zero for the netting positions.
+    pub size: Lots,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FirmQuote<Symbol: Id, Settlement: GetSettlementLag> {
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    pub request_id: OrderID,
+    pub direction: Direction,
+    pub price: Tick,
+    pub size: Lots,
+    pub valid_until: DateTime,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QuoteTraded<Symbol: Id, Settlement: GetSettlementLag> {
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    pub request_id: OrderID,
+    pub direction: Direction,
+    pub price: Tick,
+    pub size: Lots,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QuoteRejected<Symbol: Id, Settlement: GetSettlementLag> {
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    pub request_id: OrderID,
+    pub reason: QuoteRejectionReason,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum QuoteRejectionReason
+{
+    QuotingDisabled,
+
+    NoReferencePrice,
+
+    ZeroSize,
+
+    RequestWithSuchIDAlreadySubmitted,
+
+    NoSuchQuote,
+
+    QuoteExpired,
+
+    BrokerNotConnectedToExchange,
+
+    TraderNotEntitled,
+
+    KillSwitchTripped,
+
+    BrokerSessionDown,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SubscriptionRejected<Symbol: Id, Settlement: GetSettlementLag> {
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    pub reason: SubscriptionRejectionReason,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SubscriptionRejectionReason
+{
+    TraderNotRegistered,
+
+    BrokerNotConnectedToExchange,
+
+    TraderNotEntitled,
+
+    BrokerSessionDown,
+
+    NotSupported,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BorrowRecalled<Symbol: Id, Settlement: GetSettlementLag> {
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    pub size: Lots,
+    pub deadline: DateTime,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BuyIn<Symbol: Id, Settlement: GetSettlementLag> {
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    pub order_id: OrderID,
+    pub account: AccountID,
+    pub size: Lots,
+}
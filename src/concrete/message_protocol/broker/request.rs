@@ -1,13 +1,25 @@
 use crate::{
     concrete::{
-        order::{LimitOrderCancelRequest, LimitOrderPlacingRequest, MarketOrderPlacingRequest},
-        traded_pair::settlement::GetSettlementLag,
+        heartbeat::Heartbeat,
+        message_protocol::{exchange::reply::OffBookTradeInfo, MESSAGE_HEADER_SIZE},
+        order::{
+            IcebergOrderPlacingRequest,
+            LimitOrderCancelRequest,
+            LimitOrderPlacingRequest,
+            LimitOrderReductionRequest,
+            MarketOrderPlacingRequest,
+            OrderStatusRequest,
+            StopLimitOrderPlacingRequest,
+            StopOrderPlacingRequest,
+        },
+        traded_pair::{settlement::GetSettlementLag, TradedPair},
     },
     interface::message::BrokerToExchange,
     types::Id,
 };
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BasicBrokerToExchange<
     ExchangeID: Id,
     Symbol: Id,
@@ -25,9 +37,14 @@ for BasicBrokerToExchange<ExchangeID, Symbol, Settlement>
     fn get_exchange_id(&self) -> Self::ExchangeID {
         self.exchange_id
     }
+
+    fn get_size_in_bytes(&self) -> u64 {
+        MESSAGE_HEADER_SIZE
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BasicBrokerRequest<Symbol: Id, Settlement: GetSettlementLag>
 {
     CancelLimitOrder(LimitOrderCancelRequest<Symbol, Settlement>),
@@ -35,4 +52,20 @@ pub enum BasicBrokerRequest<Symbol: Id, Settlement: GetSettlementLag>
     PlaceLimitOrder(LimitOrderPlacingRequest<Symbol, Settlement>),
 
     PlaceMarketOrder(MarketOrderPlacingRequest<Symbol, Settlement>),
+
+    PlaceIcebergOrder(IcebergOrderPlacingRequest<Symbol, Settlement>),
+
+    PlaceStopOrder(StopOrderPlacingRequest<Symbol, Settlement>),
+
+    PlaceStopLimitOrder(StopLimitOrderPlacingRequest<Symbol, Settlement>),
+
+    ReduceOrderSize(LimitOrderReductionRequest<Symbol, Settlement>),
+
+    QueryOrderStatus(OrderStatusRequest<Symbol, Settlement>),
+
+    RequestRecoverySnapshot(TradedPair<Symbol, Settlement>),
+
+    Heartbeat(Heartbeat),
+
+    ReportOffBookTrade(OffBookTradeInfo<Symbol, Settlement>),
 }
\ No newline at end of file
@@ -0,0 +1,78 @@
+use crate::{
+    concrete::{
+        traded_pair::{settlement::GetSettlementLag, TradedPair},
+        types::{AccountID, Direction, Lots, Tick},
+    },
+    interface::message::BrokerToItself,
+    types::{DateTime, Id},
+};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Message the [`BasicBroker`](crate::concrete::broker::BasicBroker)
+/// schedules for itself or sends to the other brokers.
+pub enum BasicBrokerToItself<
+    BrokerID: Id,
+    TraderID: Id,
+    ExchangeID: Id,
+    Symbol: Id,
+    Settlement: GetSettlementLag
+> {
+    /// Wakeup scheduled at the start or the end of the session outage.
+    SessionCheck,
+    /// Wakeup scheduled at the datetime the exchange is considered missing
+    /// unless it echoes a heartbeat meanwhile.
+    HeartbeatCheck,
+    /// Wakeup scheduled at the datetime the next portfolio snapshots are due.
+    PortfolioSnapshot,
+    /// Wakeup scheduled at the datetime the trader holding back
+    /// the conflated order book snapshot is not busy anymore.
+    SnapshotFlush,
+    /// Fill given up to the clearing broker.
+    GiveUp(GiveUp<BrokerID, TraderID, ExchangeID, Symbol, Settlement>),
+}
+
+impl<BrokerID: Id, TraderID: Id, ExchangeID: Id, Symbol: Id, Settlement: GetSettlementLag>
+BrokerToItself
+for BasicBrokerToItself<BrokerID, TraderID, ExchangeID, Symbol, Settlement> {}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Fill the executing broker transfers to the clearing broker,
+/// so that the position is held at the latter.
+pub struct GiveUp<
+    BrokerID: Id,
+    TraderID: Id,
+    ExchangeID: Id,
+    Symbol: Id,
+    Settlement: GetSettlementLag
+> {
+    /// Clearing broker the fill is given up to.
+    pub clearing_broker_id: BrokerID,
+    /// Trader at the clearing broker the fill is booked to.
+    pub trader_id: TraderID,
+    /// Account of the trader at the clearing broker.
+    /// If absent, the allocation rule of the clearing broker is applied.
+    pub account: Option<AccountID>,
+    /// Exchange the fill happened at.
+    pub exchange_id: ExchangeID,
+    /// Traded pair.
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    /// Direction of the order. Busted fills are given up in the reversed direction.
+    pub direction: Direction,
+    /// Fill price.
+    pub price: Tick,
+    /// Fill size.
+    pub size: Lots,
+    /// Exchange datetime of the fill.
+    pub executed_dt: DateTime,
+}
+
+impl<BrokerID: Id, TraderID: Id, ExchangeID: Id, Symbol: Id, Settlement: GetSettlementLag>
+From<GiveUp<BrokerID, TraderID, ExchangeID, Symbol, Settlement>>
+for BasicBrokerToItself<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
+{
+    fn from(give_up: GiveUp<BrokerID, TraderID, ExchangeID, Symbol, Settlement>) -> Self {
+        Self::GiveUp(give_up)
+    }
+}
@@ -1,4 +1,7 @@
 /// Basic implementation of the [`ExchangeToBroker`](crate::interface::message::ExchangeToBroker)
 /// and the [`ExchangeToReplay`](crate::interface::message::ExchangeToReplay)
 /// messages.
-pub mod reply;
\ No newline at end of file
+pub mod reply;
+/// Basic implementation of the [`ExchangeToItself`](crate::interface::message::ExchangeToItself)
+/// messages.
+pub mod wakeup;
\ No newline at end of file
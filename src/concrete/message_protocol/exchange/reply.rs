@@ -1,8 +1,12 @@
 use {
     crate::{
         concrete::{
+            exchange::{admin::AdminAction, auction::TradingPhase},
+            heartbeat::Heartbeat,
+            message_protocol::MESSAGE_HEADER_SIZE,
+            order_book::ObDelta,
             traded_pair::{settlement::GetSettlementLag, TradedPair},
-            types::{Direction, Lots, ObState, OrderID, Tick, TickSize},
+            types::{Direction, ExecID, Fee, Lots, ObState, OrderID, Tick, TickSize, TradeID},
         },
         interface::message::{ExchangeToBroker, ExchangeToReplay},
         types::{
@@ -14,6 +18,7 @@ use {
 };
 
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BasicExchangeToBroker<
     BrokerID: Id,
     Symbol: Id,
@@ -37,9 +42,25 @@ for BasicExchangeToBroker<BrokerID, Symbol, Settlement>
     fn get_broker_id(&self) -> Self::BrokerID {
         self.broker_id
     }
+
+    fn get_size_in_bytes(&self) -> u64 {
+        match &self.content {
+            BasicExchangeToBrokerReply::ExchangeEventNotification(
+                ExchangeEventNotification::ObSnapshot(snapshot)
+            ) => MESSAGE_HEADER_SIZE + snapshot.state.get_size_in_bytes(),
+            BasicExchangeToBrokerReply::ExchangeEventNotification(
+                ExchangeEventNotification::ObDiff(diff)
+            ) => MESSAGE_HEADER_SIZE + diff.get_size_in_bytes(),
+            BasicExchangeToBrokerReply::RecoverySnapshot(snapshot) => {
+                MESSAGE_HEADER_SIZE + snapshot.get_size_in_bytes()
+            }
+            _ => MESSAGE_HEADER_SIZE
+        }
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BasicExchangeToReplay<Symbol: Id, Settlement: GetSettlementLag> {
     pub content: BasicExchangeToReplayReply<Symbol, Settlement>,
 }
@@ -48,26 +69,54 @@ impl<Symbol: Id, Settlement: GetSettlementLag> ExchangeToReplay
 for BasicExchangeToReplay<Symbol, Settlement> {}
 
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BasicExchangeToBrokerReply<Symbol: Id, Settlement: GetSettlementLag>
 {
     OrderAccepted(OrderAccepted<Symbol, Settlement>),
 
     OrderPlacementDiscarded(OrderPlacementDiscarded<Symbol, Settlement>),
 
+    OrderPriceAdjusted(OrderPriceAdjusted<Symbol, Settlement>),
+
     OrderPartiallyExecuted(OrderPartiallyExecuted<Symbol, Settlement>),
 
     OrderExecuted(OrderExecuted<Symbol, Settlement>),
 
+    SpreadOrderExecuted(Rc<SpreadOrderExecuted<Symbol, Settlement>>),
+
+    ShadowOrderPartiallyExecuted(OrderPartiallyExecuted<Symbol, Settlement>),
+
+    ShadowOrderExecuted(OrderExecuted<Symbol, Settlement>),
+
+    GhostOrderPartiallyExecuted(OrderPartiallyExecuted<Symbol, Settlement>),
+
+    GhostOrderExecuted(OrderExecuted<Symbol, Settlement>),
+
     MarketOrderNotFullyExecuted(MarketOrderNotFullyExecuted<Symbol, Settlement>),
 
+    StopOrderTriggered(StopOrderTriggered<Symbol, Settlement>),
+
     OrderCancelled(OrderCancelled<Symbol, Settlement>),
 
     CannotCancelOrder(CannotCancelOrder<Symbol, Settlement>),
 
+    OrderSizeReduced(OrderSizeReduced<Symbol, Settlement>),
+
+    CannotReduceOrderSize(CannotCancelOrder<Symbol, Settlement>),
+
     ExchangeEventNotification(ExchangeEventNotification<Symbol, Settlement>),
+
+    TradeBusted(TradeBusted<Symbol, Settlement>),
+
+    OrderStatus(OrderStatus<Symbol, Settlement>),
+
+    RecoverySnapshot(Rc<RecoverySnapshot<Symbol, Settlement>>),
+
+    Heartbeat(Heartbeat),
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BasicExchangeToReplayReply<Symbol: Id, Settlement: GetSettlementLag>
 {
     CannotOpenExchange(CannotOpenExchange),
@@ -78,6 +127,8 @@ pub enum BasicExchangeToReplayReply<Symbol: Id, Settlement: GetSettlementLag>
 
     OrderPlacementDiscarded(OrderPlacementDiscarded<Symbol, Settlement>),
 
+    OrderPriceAdjusted(OrderPriceAdjusted<Symbol, Settlement>),
+
     OrderPartiallyExecuted(OrderPartiallyExecuted<Symbol, Settlement>),
 
     OrderExecuted(OrderExecuted<Symbol, Settlement>),
@@ -88,6 +139,10 @@ pub enum BasicExchangeToReplayReply<Symbol: Id, Settlement: GetSettlementLag>
 
     CannotCancelOrder(CannotCancelOrder<Symbol, Settlement>),
 
+    OrderSizeReduced(OrderSizeReduced<Symbol, Settlement>),
+
+    CannotReduceOrderSize(CannotCancelOrder<Symbol, Settlement>),
+
     ExchangeEventNotification(ExchangeEventNotification<Symbol, Settlement>),
 
     CannotCloseExchange(CannotCloseExchange),
@@ -95,26 +150,37 @@ pub enum BasicExchangeToReplayReply<Symbol: Id, Settlement: GetSettlementLag>
     CannotBroadcastObState(CannotBroadcastObState),
 
     CannotStopTrades(CannotStopTrades),
+
+    CannotApplyAdminAction(CannotApplyAdminAction<Symbol, Settlement>),
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CannotOpenExchange {
     pub reason: InabilityToOpenExchangeReason,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CannotStartTrades<Symbol: Id, Settlement: GetSettlementLag> {
     pub traded_pair: TradedPair<Symbol, Settlement>,
     pub reason: InabilityToStartTrades,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OrderAccepted<Symbol: Id, Settlement: GetSettlementLag> {
     pub traded_pair: TradedPair<Symbol, Settlement>,
     pub order_id: OrderID,
+    /// ID of the order assigned by the broker, i.e. the one the exchange has received.
+    pub broker_order_id: OrderID,
+    /// ID of the order assigned by the exchange.
+    /// `None` for the stop orders, which enter the order book only when triggered.
+    pub exchange_order_id: Option<OrderID>,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OrderPlacementDiscarded<Symbol: Id, Settlement: GetSettlementLag> {
     pub traded_pair: TradedPair<Symbol, Settlement>,
     pub order_id: OrderID,
@@ -122,22 +188,113 @@ pub struct OrderPlacementDiscarded<Symbol: Id, Settlement: GetSettlementLag> {
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OrderPriceAdjusted<Symbol: Id, Settlement: GetSettlementLag> {
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    pub order_id: OrderID,
+    pub original_price: Tick,
+    pub price: Tick,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OrderPartiallyExecuted<Symbol: Id, Settlement: GetSettlementLag> {
     pub traded_pair: TradedPair<Symbol, Settlement>,
     pub order_id: OrderID,
+    /// ID of the order assigned by the broker, i.e. the one the exchange has received.
+    pub broker_order_id: OrderID,
+    /// ID of the order assigned by the exchange.
+    pub exchange_order_id: OrderID,
+    pub exec_id: ExecID,
+    pub trade_id: TradeID,
     pub price: Tick,
     pub size: Lots,
+    pub fee: Fee,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OrderStatus<Symbol: Id, Settlement: GetSettlementLag> {
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    pub order_id: OrderID,
+    pub state: OrderState,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OrderState
+{
+    /// Remaining size includes the reserve of the iceberg order.
+    Resting {
+        direction: Direction,
+        price: Tick,
+        remaining_size: Lots,
+    },
+
+    /// Stop or stop-limit order that has not been triggered yet.
+    PendingStop {
+        direction: Direction,
+        stop_price: Tick,
+        remaining_size: Lots,
+    },
+
+    Closed,
+
+    Unknown,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TradeBusted<Symbol: Id, Settlement: GetSettlementLag> {
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    pub order_id: OrderID,
+    /// ID of the busted fill.
+    pub exec_id: ExecID,
+    pub trade_id: TradeID,
+    pub direction: Direction,
+    pub price: Tick,
+    pub size: Lots,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OrderExecuted<Symbol: Id, Settlement: GetSettlementLag> {
     pub traded_pair: TradedPair<Symbol, Settlement>,
     pub order_id: OrderID,
+    /// ID of the order assigned by the broker, i.e. the one the exchange has received.
+    pub broker_order_id: OrderID,
+    /// ID of the order assigned by the exchange.
+    pub exchange_order_id: OrderID,
+    pub exec_id: ExecID,
+    pub trade_id: TradeID,
+    pub price: Tick,
+    pub size: Lots,
+    pub fee: Fee,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Execution of the spread order along with the fills of its legs,
+/// which are booked as the positions instead of the spread itself.
+pub struct SpreadOrderExecuted<Symbol: Id, Settlement: GetSettlementLag> {
+    /// Either the [`OrderExecuted`] or the [`OrderPartiallyExecuted`] of the spread order.
+    pub execution: BasicExchangeToBrokerReply<Symbol, Settlement>,
+    pub legs: Vec<LegExecuted<Symbol, Settlement>>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Fill of the spread leg the spread order execution is novated as.
+pub struct LegExecuted<Symbol: Id, Settlement: GetSettlementLag> {
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    /// Direction of the leg fill for the owner of the spread order.
+    pub direction: Direction,
     pub price: Tick,
     pub size: Lots,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MarketOrderNotFullyExecuted<Symbol: Id, Settlement: GetSettlementLag> {
     pub traded_pair: TradedPair<Symbol, Settlement>,
     pub order_id: OrderID,
@@ -145,6 +302,20 @@ pub struct MarketOrderNotFullyExecuted<Symbol: Id, Settlement: GetSettlementLag>
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Stop or stop-limit order has been triggered by the trade
+/// and is about to be placed as the market or the limit order, respectively.
+pub struct StopOrderTriggered<Symbol: Id, Settlement: GetSettlementLag> {
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    pub order_id: OrderID,
+    /// Trade price the order has been triggered at.
+    pub trigger_price: Tick,
+    /// Price of the limit order to place or `None` for the stop order.
+    pub limit_price: Option<Tick>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OrderCancelled<Symbol: Id, Settlement: GetSettlementLag> {
     pub traded_pair: TradedPair<Symbol, Settlement>,
     pub order_id: OrderID,
@@ -152,13 +323,24 @@ pub struct OrderCancelled<Symbol: Id, Settlement: GetSettlementLag> {
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CannotCancelOrder<Symbol: Id, Settlement: GetSettlementLag> {
     pub traded_pair: TradedPair<Symbol, Settlement>,
     pub order_id: OrderID,
     pub reason: InabilityToCancelReason,
 }
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OrderSizeReduced<Symbol: Id, Settlement: GetSettlementLag> {
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    pub order_id: OrderID,
+    pub size: Lots,
+    pub remaining_size: Lots,
+}
+
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ExchangeEventNotification<Symbol: Id, Settlement: GetSettlementLag>
 {
     ExchangeOpen,
@@ -167,38 +349,72 @@ pub enum ExchangeEventNotification<Symbol: Id, Settlement: GetSettlementLag>
 
     OrderCancelled(LimitOrderEventInfo<Symbol, Settlement>),
 
+    OrderSizeReduced(LimitOrderEventInfo<Symbol, Settlement>),
+
     OrderPlaced(LimitOrderEventInfo<Symbol, Settlement>),
 
     TradeExecuted(MarketOrderEventInfo<Symbol, Settlement>),
 
+    OffBookTradeReported(OffBookTradeInfo<Symbol, Settlement>),
+
     ObSnapshot(Rc<ObSnapshot<Symbol, Settlement>>),
 
+    ObDiff(Rc<ObDiff<Symbol, Settlement>>),
+
     TradesStopped(TradedPair<Symbol, Settlement>),
 
+    AdminActionApplied(AdminAction<Symbol, Settlement>),
+
+    ClosingPrice { traded_pair: TradedPair<Symbol, Settlement>, price: Tick },
+
+    ThroughputStatistics(ThroughputStatistics<Symbol, Settlement>),
+
+    VolatilityEstimate(VolatilityEstimate<Symbol, Settlement>),
+
+    DowntimeStarted,
+
+    DowntimeEnded,
+
+    TradingPhaseChanged(TradingPhase),
+
+    AuctionPriceDetermined(AuctionInfo<Symbol, Settlement>),
+
     ExchangeClosed,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CannotCloseExchange {
     pub reason: InabilityToCloseExchangeReason,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CannotBroadcastObState {
     pub reason: InabilityToBroadcastObState,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CannotStopTrades {
     pub reason: InabilityToStopTrades,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CannotApplyAdminAction<Symbol: Id, Settlement: GetSettlementLag> {
+    pub action: AdminAction<Symbol, Settlement>,
+    pub reason: InabilityToApplyAdminAction,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum InabilityToOpenExchangeReason {
     AlreadyOpen
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum InabilityToStartTrades {
     AlreadyStarted,
     ExchangeClosed,
@@ -206,6 +422,7 @@ pub enum InabilityToStartTrades {
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PlacementDiscardingReason
 {
     OrderWithSuchIDAlreadySubmitted,
@@ -217,16 +434,45 @@ pub enum PlacementDiscardingReason
     BrokerNotConnectedToExchange,
 
     NoSuchTradedPair,
+
+    OffTickPrice,
+
+    SizeBelowMinimum,
+
+    SizeAboveMaximum,
+
+    SizeNotMultipleOfIncrement,
+
+    NotionalBelowMinimum,
+
+    OutsidePriceBand,
+
+    ExchangeDown,
+
+    TradeThrough,
+
+    NotFullyFillable,
+
+    NotAllowedInTradingPhase,
+
+    AlreadyExpired,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CancellationReason {
     BrokerRequested,
     TradesStopped,
     ExchangeClosed,
+    OffTickPrice,
+    OutsidePriceBand,
+    ExchangeDown,
+    Expired,
+    NotImmediatelyExecuted,
 }
 
 #[derive(derive_more::Display, Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum InabilityToCancelReason
 {
     OrderHasNotBeenSubmitted,
@@ -238,44 +484,167 @@ pub enum InabilityToCancelReason
     BrokerNotConnectedToExchange,
 
     NoSuchTradedPair,
+
+    ExchangeDown,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum InabilityToCloseExchangeReason {
     AlreadyClosed
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum InabilityToBroadcastObState {
     ExchangeClosed,
     NoSuchTradedPair,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum InabilityToStopTrades {
     ExchangeClosed,
     NoSuchTradedPair,
 }
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum InabilityToApplyAdminAction {
+    NoSuchTradedPair,
+    NonPositivePriceIncrement,
+    InvertedPriceBand,
+    NegativeNetFee,
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LimitOrderEventInfo<Symbol: Id, Settlement: GetSettlementLag> {
     pub traded_pair: TradedPair<Symbol, Settlement>,
     pub order_id: OrderID,
     pub direction: Direction,
     pub price: Tick,
+    /// Displayed size of the update. Reserves of the iceberg orders are not exposed,
+    /// and the updates of the hidden orders are not published at all.
     pub size: Lots,
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MarketOrderEventInfo<Symbol: Id, Settlement: GetSettlementLag> {
     pub traded_pair: TradedPair<Symbol, Settlement>,
+    pub trade_id: TradeID,
     pub direction: Direction,
     pub price: Tick,
     pub size: Lots,
 }
 
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Outcome of the auction in the traded pair, published before its trades.
+pub struct AuctionInfo<Symbol: Id, Settlement: GetSettlementLag> {
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    /// Equilibrium price all the orders are crossed at.
+    pub price: Tick,
+    /// Total size crossed.
+    pub size: Lots,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Trade executed away from the order book, e.g. internalized by the dealer,
+/// and printed to the tape of the exchange later.
+pub struct OffBookTradeInfo<Symbol: Id, Settlement: GetSettlementLag> {
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    /// ID of the trade assigned by the reporting party.
+    pub trade_id: TradeID,
+    /// Direction of the aggressing side.
+    pub direction: Direction,
+    pub price: Tick,
+    pub size: Lots,
+    /// Datetime the trade has been executed at.
+    pub executed_dt: DateTime,
+}
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Numbers of the order events in the single traded pair. Shadow orders are not counted.
+pub struct ThroughputCounts {
+    /// Limit orders accepted by the exchange.
+    pub placed: u64,
+    /// Limit orders cancelled on request.
+    pub cancelled: u64,
+    /// Limit orders reduced in size on request.
+    pub reduced: u64,
+    /// Trades.
+    pub executed: u64,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Order event counts of the traded pair over the interval.
+pub struct ThroughputStatistics<Symbol: Id, Settlement: GetSettlementLag> {
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    pub interval_start: DateTime,
+    pub interval_end: DateTime,
+    pub counts: ThroughputCounts,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Realized volatility of the traded pair over the estimation window ending at the `datetime`.
+pub struct VolatilityEstimate<Symbol: Id, Settlement: GetSettlementLag> {
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    pub datetime: DateTime,
+    /// Square root of the sum of the squared log returns between the trades,
+    /// in millionths.
+    pub volatility_ppm: u64,
+    /// Number of the returns the estimate is based on.
+    pub num_returns: u64,
+    /// Last trade price.
+    pub last_price: Tick,
+}
+
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ObSnapshot<Symbol: Id, Settlement: GetSettlementLag> {
     pub traded_pair: TradedPair<Symbol, Settlement>,
     pub state: ObState,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Changes of the resting orders of the traded pair since the previous `ObDiff`,
+/// in the order they happened.
+pub struct ObDiff<Symbol: Id, Settlement: GetSettlementLag> {
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    pub deltas: Vec<ObDelta>,
+}
+
+impl<Symbol: Id, Settlement: GetSettlementLag> ObDiff<Symbol, Settlement> {
+    /// Returns the size of the deltas in bytes, as they would be sent over the network.
+    pub fn get_size_in_bytes(&self) -> u64 {
+        (self.deltas.len() * core::mem::size_of::<ObDelta>()) as u64
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Full-depth order book of the traded pair along with its recent trades,
+/// sent on request to bring the late subscriber up to date
+/// before it starts receiving the increments.
+pub struct RecoverySnapshot<Symbol: Id, Settlement: GetSettlementLag> {
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    /// State of the order book. Empty if the trades in the traded pair have not started.
+    pub state: ObState,
+    /// Recent trades of the current session, oldest first, along with their datetimes.
+    pub recent_trades: Vec<(DateTime, MarketOrderEventInfo<Symbol, Settlement>)>,
+}
+
+impl<Symbol: Id, Settlement: GetSettlementLag> RecoverySnapshot<Symbol, Settlement> {
+    /// Returns the size of the snapshot in bytes, as it would be sent over the network,
+    /// with the trades transmitted as 40-byte records.
+    pub fn get_size_in_bytes(&self) -> u64 {
+        self.state.get_size_in_bytes() + 40 * self.recent_trades.len() as u64
+    }
+}
@@ -0,0 +1,60 @@
+use crate::{
+    concrete::{
+        exchange::auction::TradingPhase,
+        traded_pair::{settlement::GetSettlementLag, TradedPair},
+        types::OrderID,
+    },
+    interface::message::ExchangeToItself,
+    types::{DateTime, Id},
+};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Wakeup scheduled by the [`BasicExchange`](crate::concrete::exchange::BasicExchange).
+pub enum BasicExchangeToItself<Symbol: Id, Settlement: GetSettlementLag> {
+    OrderExpiration(OrderExpiration<Symbol, Settlement>),
+
+    PhaseTransition(PhaseTransition),
+}
+
+impl<Symbol: Id, Settlement: GetSettlementLag> ExchangeToItself
+for BasicExchangeToItself<Symbol, Settlement> {}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Wakeup scheduled at the expiration datetime of the good-till-date limit order.
+pub struct OrderExpiration<Symbol: Id, Settlement: GetSettlementLag> {
+    /// Traded pair of the order.
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    /// Internal ID the order has been assigned by the exchange.
+    pub order_id: OrderID,
+    /// Expiration datetime of the order.
+    /// Tells the order from the one assigned the same internal ID in the later session.
+    pub expiration: DateTime,
+}
+
+impl<Symbol: Id, Settlement: GetSettlementLag> From<OrderExpiration<Symbol, Settlement>>
+for BasicExchangeToItself<Symbol, Settlement>
+{
+    fn from(expiration: OrderExpiration<Symbol, Settlement>) -> Self {
+        Self::OrderExpiration(expiration)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Wakeup scheduled at the beginning of the trading phase of the
+/// [`SessionSchedule`](crate::concrete::exchange::auction::SessionSchedule).
+/// Is ignored if the session has been closed since.
+pub struct PhaseTransition {
+    /// Phase to begin.
+    pub phase: TradingPhase,
+}
+
+impl<Symbol: Id, Settlement: GetSettlementLag> From<PhaseTransition>
+for BasicExchangeToItself<Symbol, Settlement>
+{
+    fn from(transition: PhaseTransition) -> Self {
+        Self::PhaseTransition(transition)
+    }
+}
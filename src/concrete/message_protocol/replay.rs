@@ -1,3 +1,3 @@
 /// Basic implementation of the [`ReplayToExchange`](crate::interface::message::ReplayToExchange)
-/// messages.
-pub mod request;
\ No newline at end of file
+/// and the [`ReplayToBroker`](crate::interface::message::ReplayToBroker) messages.
+pub mod request;
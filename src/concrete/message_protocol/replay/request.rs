@@ -1,14 +1,21 @@
 use crate::{
     concrete::{
-        order::{LimitOrderCancelRequest, LimitOrderPlacingRequest, MarketOrderPlacingRequest},
+        exchange::{admin::AdminAction, auction::TradingPhase},
+        order::{
+            LimitOrderCancelRequest,
+            LimitOrderPlacingRequest,
+            LimitOrderReductionRequest,
+            MarketOrderPlacingRequest,
+        },
         traded_pair::{settlement::GetSettlementLag, TradedPair},
         types::TickSize,
     },
-    interface::message::ReplayToExchange,
-    types::Id,
+    interface::message::{ReplayToBroker, ReplayToExchange},
+    types::{DateTime, Id},
 };
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BasicReplayToExchange<
     ExchangeID: Id,
     Symbol: Id,
@@ -33,6 +40,7 @@ for BasicReplayToExchange<ExchangeID, Symbol, Settlement>
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BasicReplayRequest<Symbol: Id, Settlement: GetSettlementLag>
 {
     ExchangeOpen,
@@ -41,13 +49,63 @@ pub enum BasicReplayRequest<Symbol: Id, Settlement: GetSettlementLag>
 
     CancelLimitOrder(LimitOrderCancelRequest<Symbol, Settlement>),
 
+    ReduceOrderSize(LimitOrderReductionRequest<Symbol, Settlement>),
+
     PlaceMarketOrder(MarketOrderPlacingRequest<Symbol, Settlement>),
 
     PlaceLimitOrder(LimitOrderPlacingRequest<Symbol, Settlement>),
 
+    /// Limit order to be inserted into the order book without matching and notifying brokers.
+    SeedLimitOrder(LimitOrderPlacingRequest<Symbol, Settlement>),
+
     BroadcastObStateToBrokers { traded_pair: TradedPair<Symbol, Settlement>, max_levels: usize },
 
     StopTrades(TradedPair<Symbol, Settlement>),
 
+    AdminAction(AdminAction<Symbol, Settlement>),
+
+    StartDowntime { cancel_resting_orders: bool },
+
+    EndDowntime,
+
+    SetTradingPhase(TradingPhase),
+
     ExchangeClosed,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BasicReplayToBroker<
+    BrokerID: Id,
+    ExchangeID: Id,
+    Symbol: Id,
+    Settlement: GetSettlementLag
+> {
+    pub broker_id: BrokerID,
+    pub exchange_id: ExchangeID,
+    pub content: BasicReplayToBrokerRequest<Symbol, Settlement>,
+}
+
+impl<
+    BrokerID: Id,
+    ExchangeID: Id,
+    Symbol: Id,
+    Settlement: GetSettlementLag
+>
+ReplayToBroker
+for BasicReplayToBroker<BrokerID, ExchangeID, Symbol, Settlement>
+{
+    type BrokerID = BrokerID;
+    fn get_broker_id(&self) -> Self::BrokerID {
+        self.broker_id
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BasicReplayToBrokerRequest<Symbol: Id, Settlement: GetSettlementLag>
+{
+    RecallBorrow { traded_pair: TradedPair<Symbol, Settlement>, deadline: DateTime },
+
+    BuyIn(TradedPair<Symbol, Settlement>),
+}
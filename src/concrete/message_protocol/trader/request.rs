@@ -1,13 +1,29 @@
 use crate::{
     concrete::{
-        order::{LimitOrderCancelRequest, LimitOrderPlacingRequest, MarketOrderPlacingRequest},
-        traded_pair::settlement::GetSettlementLag,
+        heartbeat::Heartbeat,
+        order::{
+            IcebergOrderPlacingRequest,
+            LimitOrderCancelRequest,
+            LimitOrderPlacingRequest,
+            LimitOrderReductionRequest,
+            MarketOrderPlacingRequest,
+            OrderStatusRequest,
+            QuoteAcceptance,
+            QuoteRequest,
+            StopLimitOrderPlacingRequest,
+            StopOrderPlacingRequest,
+        },
+        message_protocol::MESSAGE_HEADER_SIZE,
+        traded_pair::{settlement::GetSettlementLag, TradedPair},
+        trader::subscriptions::SubscriptionRequest,
+        types::AccountID,
     },
     interface::message::TraderToBroker,
     types::Id,
 };
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BasicTraderToBroker<
     BrokerID: Id,
     ExchangeID: Id,
@@ -15,6 +31,7 @@ pub struct BasicTraderToBroker<
     Settlement: GetSettlementLag
 > {
     pub broker_id: BrokerID,
+    pub account: Option<AccountID>,
     pub content: BasicTraderRequest<ExchangeID, Symbol, Settlement>,
 }
 
@@ -31,9 +48,14 @@ for BasicTraderToBroker<BrokerID, ExchangeID, Symbol, Settlement>
     fn get_broker_id(&self) -> Self::BrokerID {
         self.broker_id
     }
+
+    fn get_size_in_bytes(&self) -> u64 {
+        MESSAGE_HEADER_SIZE
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BasicTraderRequest<
     ExchangeID: Id,
     Symbol: Id,
@@ -43,5 +65,52 @@ pub enum BasicTraderRequest<
 
     PlaceLimitOrder(LimitOrderPlacingRequest<Symbol, Settlement>, ExchangeID),
 
+    PlaceIcebergOrder(IcebergOrderPlacingRequest<Symbol, Settlement>, ExchangeID),
+
     PlaceMarketOrder(MarketOrderPlacingRequest<Symbol, Settlement>, ExchangeID),
-}
\ No newline at end of file
+
+    PlaceStopOrder(StopOrderPlacingRequest<Symbol, Settlement>, ExchangeID),
+
+    PlaceStopLimitOrder(StopLimitOrderPlacingRequest<Symbol, Settlement>, ExchangeID),
+
+    RequestQuote(QuoteRequest<Symbol, Settlement>, ExchangeID),
+
+    AcceptQuote(QuoteAcceptance<Symbol, Settlement>, ExchangeID),
+
+    ReduceOrderSize(LimitOrderReductionRequest<Symbol, Settlement>, ExchangeID),
+
+    QueryOrderStatus(OrderStatusRequest<Symbol, Settlement>, ExchangeID),
+
+    Subscribe(SubscriptionRequest<Symbol, Settlement>, ExchangeID),
+
+    Heartbeat(Heartbeat, ExchangeID),
+
+    /// Closes out the long and short legs of the hedging position of the account
+    /// against each other. Account is the one of the request or the default one.
+    CloseOutPosition(TradedPair<Symbol, Settlement>, ExchangeID),
+}
+
+impl<
+    ExchangeID: Id,
+    Symbol: Id,
+    Settlement: GetSettlementLag
+> BasicTraderRequest<ExchangeID, Symbol, Settlement>
+{
+    pub fn get_exchange_id(&self) -> ExchangeID {
+        match self {
+            Self::CancelLimitOrder(_, exchange_id)
+            | Self::PlaceLimitOrder(_, exchange_id)
+            | Self::PlaceIcebergOrder(_, exchange_id)
+            | Self::PlaceMarketOrder(_, exchange_id)
+            | Self::PlaceStopOrder(_, exchange_id)
+            | Self::PlaceStopLimitOrder(_, exchange_id)
+            | Self::RequestQuote(_, exchange_id)
+            | Self::AcceptQuote(_, exchange_id)
+            | Self::ReduceOrderSize(_, exchange_id)
+            | Self::QueryOrderStatus(_, exchange_id)
+            | Self::Subscribe(_, exchange_id)
+            | Self::Heartbeat(_, exchange_id)
+            | Self::CloseOutPosition(_, exchange_id) => *exchange_id
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use crate::{
+    concrete::{
+        message_protocol::{
+            broker::{reply::BasicBrokerReply, request::BasicBrokerToExchange},
+            exchange::reply::{BasicExchangeToBroker, BasicExchangeToBrokerReply},
+            replay::request::BasicReplayToExchange,
+            trader::request::BasicTraderToBroker,
+        },
+        traded_pair::settlement::GetSettlementLag,
+    },
+    types::{DateTime, Id},
+};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+/// Decision of the middleware about the incoming message.
+pub enum Verdict<Reply> {
+    /// Process the message, possibly modified by the middleware.
+    Pass,
+    /// Drop the message silently.
+    Suppress,
+    /// Drop the message and answer its sender with the given reply instead.
+    Reject(Reply),
+}
+
+/// User-defined hook of the [`BasicBroker`](crate::concrete::broker::BasicBroker)
+/// that can observe, modify or suppress the incoming messages
+/// before the broker processes them.
+///
+/// Hooks are called in the order they are added to the broker.
+/// Once some hook suppresses or rejects the message, the next ones are not called.
+pub trait BrokerMiddleware<BrokerID, TraderID, ExchangeID, Symbol, Settlement>
+    where BrokerID: Id,
+          TraderID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    /// Decides whether the broker should process the trader request.
+    /// The rejection reply is sent to the trader concerning the exchange of the request.
+    /// Passes everything by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `datetime` — Current datetime.
+    /// * `trader_id` — Trader the request is received from.
+    /// * `request` — Request to decide on. Can be modified in place.
+    fn on_trader_request(
+        &mut self,
+        datetime: DateTime,
+        trader_id: TraderID,
+        request: &mut BasicTraderToBroker<BrokerID, ExchangeID, Symbol, Settlement>,
+    ) -> Verdict<BasicBrokerReply<Symbol, Settlement>>
+    {
+        let _ = (datetime, trader_id, request);
+        Verdict::Pass
+    }
+
+    /// Decides whether the broker should process the exchange reply.
+    /// Returns `false` to suppress the reply. Passes everything by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `datetime` — Current datetime.
+    /// * `exchange_id` — Exchange the reply is received from.
+    /// * `reply` — Reply to decide on. Can be modified in place.
+    fn on_exchange_reply(
+        &mut self,
+        datetime: DateTime,
+        exchange_id: ExchangeID,
+        reply: &mut BasicExchangeToBroker<BrokerID, Symbol, Settlement>) -> bool
+    {
+        let _ = (datetime, exchange_id, reply);
+        true
+    }
+}
+
+/// User-defined hook of the [`BasicExchange`](crate::concrete::exchange::BasicExchange)
+/// that can observe, modify or suppress the incoming messages
+/// before the exchange processes them.
+///
+/// Hooks are called in the order they are added to the exchange.
+/// Once some hook suppresses or rejects the message, the next ones are not called.
+pub trait ExchangeMiddleware<ExchangeID, BrokerID, Symbol, Settlement>
+    where ExchangeID: Id,
+          BrokerID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    /// Decides whether the exchange should process the broker request.
+    /// Passes everything by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `datetime` — Current datetime.
+    /// * `broker_id` — Broker the request is received from.
+    /// * `request` — Request to decide on. Can be modified in place.
+    fn on_broker_request(
+        &mut self,
+        datetime: DateTime,
+        broker_id: BrokerID,
+        request: &mut BasicBrokerToExchange<ExchangeID, Symbol, Settlement>,
+    ) -> Verdict<BasicExchangeToBrokerReply<Symbol, Settlement>>
+    {
+        let _ = (datetime, broker_id, request);
+        Verdict::Pass
+    }
+
+    /// Decides whether the exchange should process the replay request.
+    /// Returns `false` to suppress the request. Passes everything by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `datetime` — Current datetime.
+    /// * `request` — Request to decide on. Can be modified in place.
+    fn on_replay_request(
+        &mut self,
+        datetime: DateTime,
+        request: &mut BasicReplayToExchange<ExchangeID, Symbol, Settlement>) -> bool
+    {
+        let _ = (datetime, request);
+        true
+    }
+}
@@ -3,10 +3,11 @@ use crate::{
         traded_pair::{settlement::GetSettlementLag, TradedPair},
         types::{Direction, Lots, OrderID, Tick},
     },
-    types::Id,
+    types::{DateTime, Id},
 };
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Limit order cancel request.
 pub struct LimitOrderCancelRequest<Symbol: Id, Settlement: GetSettlementLag> {
     /// Traded pair.
@@ -16,6 +17,30 @@ pub struct LimitOrderCancelRequest<Symbol: Id, Settlement: GetSettlementLag> {
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Request for the current state of the order.
+/// Order IDs are scoped by the submitter and the traded pair.
+pub struct OrderStatusRequest<Symbol: Id, Settlement: GetSettlementLag> {
+    /// Traded pair.
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    /// ID of the order to query.
+    pub order_id: OrderID,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Request to cancel the part of the remaining size of the limit order.
+pub struct LimitOrderReductionRequest<Symbol: Id, Settlement: GetSettlementLag> {
+    /// Traded pair.
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    /// ID of the order to reduce.
+    pub order_id: OrderID,
+    /// Size to cancel. If it is not less than the remaining size, the order is cancelled.
+    pub size: Lots,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Limit order placing request.
 pub struct LimitOrderPlacingRequest<Symbol: Id, Settlement: GetSettlementLag> {
     /// Traded pair.
@@ -28,11 +53,93 @@ pub struct LimitOrderPlacingRequest<Symbol: Id, Settlement: GetSettlementLag> {
     pub price: Tick,
     /// Size of the order to place.
     pub size: Lots,
-    /// Whether the order is dummy.
+    /// Whether the order is a shadow one, i.e. it is matched against the order book
+    /// without affecting it and is invisible to the other agents.
+    /// Fills of the shadow orders are hypothetical and are reported to the brokers
+    /// as `ShadowOrderExecuted` and `ShadowOrderPartiallyExecuted`.
+    /// If the exchange matches shadow orders with each other, their fills by each other,
+    /// of both the resting and the incoming ones,
+    /// are reported as `GhostOrderExecuted` and `GhostOrderPartiallyExecuted`.
     pub dummy: bool,
+    /// How long the order may rest in the order book.
+    pub time_in_force: TimeInForce,
+}
+
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Time-in-force of the limit order.
+/// Exchanges cancel all orders at the close, so the good-till-cancelled
+/// and the good-till-date orders are carried over the sessions by the brokers.
+pub enum TimeInForce {
+    /// Order rests until the close of the session.
+    #[default]
+    Day,
+    /// Order rests until it is executed or cancelled.
+    GoodTillCancelled,
+    /// Order rests until the datetime, at which the exchange cancels it.
+    GoodTillDate(DateTime),
+    /// Part of the order not executed at once is cancelled.
+    ImmediateOrCancel,
+    /// Order is discarded unless it can be executed at once in full.
+    FillOrKill,
+}
+
+impl TimeInForce {
+    /// Returns the expiration datetime of the good-till-date order or `None` otherwise.
+    pub fn get_expiration(self) -> Option<DateTime> {
+        if let Self::GoodTillDate(expiration) = self {
+            Some(expiration)
+        } else {
+            None
+        }
+    }
+
+    /// Checks whether the order outlives the session.
+    pub fn is_carried_over(self) -> bool {
+        matches!(self, Self::GoodTillCancelled | Self::GoodTillDate(_))
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Iceberg or hidden limit order placing request.
+/// Iceberg order displays its size by the peaks, losing the queue priority
+/// whenever the next peak is displayed. Hidden order is not displayed at all,
+/// yet keeps its time priority over the displayed orders of the same price submitted after it.
+pub struct IcebergOrderPlacingRequest<Symbol: Id, Settlement: GetSettlementLag> {
+    /// Traded pair.
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    /// ID of the order to place.
+    pub order_id: OrderID,
+    /// Direction of the order to place.
+    pub direction: Direction,
+    /// Price of the order to place.
+    pub price: Tick,
+    /// Size of the order to place.
+    pub size: Lots,
+    /// Size of the displayed peaks. If zero, the order is hidden.
+    pub display_size: Lots,
+}
+
+impl<Symbol: Id, Settlement: GetSettlementLag> From<IcebergOrderPlacingRequest<Symbol, Settlement>>
+for LimitOrderPlacingRequest<Symbol, Settlement>
+{
+    fn from(order: IcebergOrderPlacingRequest<Symbol, Settlement>) -> Self {
+        let IcebergOrderPlacingRequest { traded_pair, order_id, direction, price, size, .. } = order;
+        Self {
+            traded_pair,
+            order_id,
+            direction,
+            price,
+            size,
+            dummy: false,
+            time_in_force: TimeInForce::Day,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Limit order placing request.
 pub struct MarketOrderPlacingRequest<Symbol: Id, Settlement: GetSettlementLag> {
     /// Traded pair.
@@ -43,6 +150,100 @@ pub struct MarketOrderPlacingRequest<Symbol: Id, Settlement: GetSettlementLag> {
     pub direction: Direction,
     /// Size of the order to place.
     pub size: Lots,
-    /// Whether the order is dummy.
+    /// Whether the order is a shadow one, i.e. it is matched against the order book
+    /// without affecting it and is invisible to the other agents.
+    /// Fills of the shadow orders are hypothetical and are reported to the brokers
+    /// as `ShadowOrderExecuted` and `ShadowOrderPartiallyExecuted`.
     pub dummy: bool,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Stop order placing request.
+/// Stop order rests at the exchange unseen by the other agents until the trade
+/// at the stop price or through it, i.e. at or above it for the buy order
+/// and at or below it for the sell one. Then it is placed as the market order.
+pub struct StopOrderPlacingRequest<Symbol: Id, Settlement: GetSettlementLag> {
+    /// Traded pair.
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    /// ID of the order to place.
+    pub order_id: OrderID,
+    /// Direction of the order to place.
+    pub direction: Direction,
+    /// Trade price triggering the order.
+    pub stop_price: Tick,
+    /// Size of the order to place.
+    pub size: Lots,
+}
+
+impl<Symbol: Id, Settlement: GetSettlementLag> From<StopOrderPlacingRequest<Symbol, Settlement>>
+for MarketOrderPlacingRequest<Symbol, Settlement>
+{
+    fn from(order: StopOrderPlacingRequest<Symbol, Settlement>) -> Self {
+        let StopOrderPlacingRequest { traded_pair, order_id, direction, size, .. } = order;
+        Self { traded_pair, order_id, direction, size, dummy: false }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Stop-limit order placing request.
+/// Is triggered the same way as the [`StopOrderPlacingRequest`],
+/// but is placed as the day limit order.
+pub struct StopLimitOrderPlacingRequest<Symbol: Id, Settlement: GetSettlementLag> {
+    /// Traded pair.
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    /// ID of the order to place.
+    pub order_id: OrderID,
+    /// Direction of the order to place.
+    pub direction: Direction,
+    /// Trade price triggering the order.
+    pub stop_price: Tick,
+    /// Price of the limit order placed once the order is triggered.
+    pub price: Tick,
+    /// Size of the order to place.
+    pub size: Lots,
+}
+
+impl<Symbol: Id, Settlement: GetSettlementLag>
+From<StopLimitOrderPlacingRequest<Symbol, Settlement>>
+for LimitOrderPlacingRequest<Symbol, Settlement>
+{
+    fn from(order: StopLimitOrderPlacingRequest<Symbol, Settlement>) -> Self {
+        let StopLimitOrderPlacingRequest {
+            traded_pair, order_id, direction, price, size, ..
+        } = order;
+        Self {
+            traded_pair,
+            order_id,
+            direction,
+            price,
+            size,
+            dummy: false,
+            time_in_force: TimeInForce::Day,
+        }
+    }
+}
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Request for a firm quote.
+pub struct QuoteRequest<Symbol: Id, Settlement: GetSettlementLag> {
+    /// Traded pair.
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    /// ID of the request. Should be unique among the requests of the trader.
+    pub request_id: OrderID,
+    /// Direction the requester is willing to trade in.
+    pub direction: Direction,
+    /// Size to quote.
+    pub size: Lots,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Request to trade at the previously received firm quote.
+pub struct QuoteAcceptance<Symbol: Id, Settlement: GetSettlementLag> {
+    /// Traded pair.
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    /// ID of the quote request.
+    pub request_id: OrderID,
+}
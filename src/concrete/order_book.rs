@@ -1,8 +1,20 @@
 use {
-    crate::{concrete::types::{Direction, Lots, ObState, OrderID, Tick}, types::DateTime},
+    crate::{
+        concrete::types::{
+            get_depth_imbalance,
+            get_microprice,
+            Direction,
+            Lots,
+            ObState,
+            OrderID,
+            Tick,
+        },
+        types::DateTime,
+        utils::collections::HashMap,
+    },
     std::{
-        cmp::Ordering,
-        collections::{hash_map::Entry::Occupied, HashMap, VecDeque},
+        cmp::{Ordering, Reverse},
+        collections::{hash_map::Entry::Occupied, VecDeque},
         fmt::{Display, Formatter},
         iter::{once, repeat_with},
     },
@@ -11,7 +23,11 @@ use {
 #[cfg(test)]
 mod tests;
 
+/// Maximum number of emptied price levels kept by the [`OrderBook`] for reuse.
+const MAX_SPARE_LEVELS: usize = 64;
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// [`OrderBook`] internal limit order representation.
 pub struct LimitOrder {
     /// Order unique identifier.
@@ -27,12 +43,7 @@ pub struct LimitOrder {
 }
 
 /// Order book that only supports simple limit and market orders.
-///
-/// # Parameters
-///
-/// * `MATCH_DUMMY_WITH_DUMMY` — whether to match incoming dummy orders
-/// with already submitted dummy orders.
-pub struct OrderBook<const MATCH_DUMMY_WITH_DUMMY: bool> {
+pub struct OrderBook {
     /// Bid levels.
     bids: VecDeque<VecDeque<LimitOrder>>,
     /// Ask levels.
@@ -43,12 +54,56 @@ pub struct OrderBook<const MATCH_DUMMY_WITH_DUMMY: bool> {
     best_ask: Tick,
     /// Map [OrderId -> (Price, Whether it is bid)]
     id_to_price_and_side: HashMap<OrderID, (Tick, bool)>,
+    /// Emptied price levels whose buffers are reused for the new ones.
+    spare_levels: Vec<VecDeque<LimitOrder>>,
+    /// Changes of the resting orders since the last drain. `None` if they are not recorded.
+    journal: Option<Vec<ObDelta>>,
+    /// Non-displayed parts of the resting iceberg and hidden orders.
+    reserves: HashMap<OrderID, Reserve>,
+    /// Whether to match incoming dummy orders with already submitted dummy orders.
+    match_dummy_with_dummy: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Non-displayed part of the resting iceberg or hidden order.
+struct Reserve {
+    /// Size of the displayed peaks. Zero if the order is fully hidden.
+    display_size: Lots,
+    /// Size not yet displayed.
+    size: Lots,
+}
+
+impl Reserve {
+    #[inline]
+    fn is_hidden(&self) -> bool {
+        self.display_size == Lots(0)
+    }
+
+    #[inline]
+    /// Splits the total remaining size into the resting and the reserve ones.
+    /// Returns the resting size.
+    fn split(&mut self, total_size: Lots) -> Lots {
+        let resting_size = if self.is_hidden() {
+            total_size
+        } else {
+            total_size.min(self.display_size)
+        };
+        self.size = total_size - resting_size;
+        resting_size
+    }
+}
+
+#[inline]
+/// Whether the order is the hidden one and thus should not be shown.
+fn is_hidden(reserves: &HashMap<OrderID, Reserve>, id: OrderID) -> bool {
+    !reserves.is_empty() && reserves.get(&id).is_some_and(Reserve::is_hidden)
 }
 
 /// Borrows [`OrderBook`] side and performs cleanup on drop.
 struct SideWrapper<'a, const UPPER: bool, const FROM_BOTH_ENDS: bool> {
     side: &'a mut VecDeque<VecDeque<LimitOrder>>,
     best_price: &'a mut Tick,
+    spare_levels: &'a mut Vec<VecDeque<LimitOrder>>,
 }
 
 impl<const UPPER: bool, const SHRINK_BOTH_ENDS: bool>
@@ -66,7 +121,9 @@ SideWrapper<'_, UPPER, SHRINK_BOTH_ENDS>
             if !level.is_empty() {
                 break;
             }
-            self.side.pop_front();
+            if let Some(level) = self.side.pop_front() {
+                recycle_level(self.spare_levels, level)
+            }
             if UPPER {
                 *self.best_price += Tick(1)
             } else {
@@ -78,7 +135,9 @@ SideWrapper<'_, UPPER, SHRINK_BOTH_ENDS>
                 if !level.is_empty() {
                     break;
                 }
-                self.side.pop_back();
+                if let Some(level) = self.side.pop_back() {
+                    recycle_level(self.spare_levels, level)
+                }
             }
         }
     }
@@ -93,6 +152,32 @@ Drop for SideWrapper<'_, UPPER, SHRINK_BOTH_ENDS>
     }
 }
 
+#[inline]
+/// Keeps the buffer of the emptied level for reuse if it has allocated memory.
+fn recycle_level(spare_levels: &mut Vec<VecDeque<LimitOrder>>, mut level: VecDeque<LimitOrder>) {
+    if level.capacity() != 0 && spare_levels.len() < MAX_SPARE_LEVELS {
+        level.clear();
+        spare_levels.push(level)
+    }
+}
+
+#[inline]
+/// Creates a new price level containing a single order,
+/// reusing the buffer of an emptied level if there is one.
+fn new_level(spare_levels: &mut Vec<VecDeque<LimitOrder>>, order: LimitOrder) -> VecDeque<LimitOrder> {
+    let mut level = spare_levels.pop().unwrap_or_default();
+    level.push_back(order);
+    level
+}
+
+#[inline]
+/// Appends the delta to the journal if it is enabled.
+fn record(journal: &mut Option<Vec<ObDelta>>, delta: ObDelta) {
+    if let Some(journal) = journal {
+        journal.push(delta)
+    }
+}
+
 /// Borrows [`OrderBook`] side level and performs cleanup on drop.
 struct LevelWrapper<'a, const SHRINK_BOTH_ENDS: bool> (&'a mut VecDeque<LimitOrder>);
 
@@ -133,6 +218,7 @@ Drop for LevelWrapper<'_, SHRINK_BOTH_ENDS>
 }
 
 #[derive(Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Order book execution event.
 pub struct OrderBookEvent {
     /// Size of the diff.
@@ -144,6 +230,7 @@ pub struct OrderBookEvent {
 }
 
 #[derive(Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Order book event.
 pub enum OrderBookEventKind {
     /// New order executed.
@@ -154,6 +241,109 @@ pub enum OrderBookEventKind {
     OldOrderExecuted(OrderID),
     /// Old limit order partially executed.
     OldOrderPartiallyExecuted(OrderID),
+    /// Old hidden limit order fully executed, consuming the non-displayed liquidity.
+    OldHiddenOrderExecuted(OrderID),
+    /// Old hidden limit order partially executed, consuming the non-displayed liquidity.
+    OldHiddenOrderPartiallyExecuted(OrderID),
+    /// Old dummy limit order fully executed by the new dummy order.
+    /// Emitted only if the [`OrderBook`] matches dummy orders with each other.
+    OldGhostOrderExecuted(OrderID),
+    /// Old dummy limit order partially executed by the new dummy order.
+    /// Emitted only if the [`OrderBook`] matches dummy orders with each other.
+    OldGhostOrderPartiallyExecuted(OrderID),
+    /// New dummy order fully executed, the given size of it by the old dummy orders.
+    /// Emitted only if the [`OrderBook`] matches dummy orders with each other.
+    NewGhostOrderExecuted,
+    /// New dummy order partially executed by the old dummy orders for the given price.
+    /// Emitted only if the [`OrderBook`] matches dummy orders with each other.
+    NewGhostOrderPartiallyExecuted,
+    /// Next peak of the old iceberg order displayed at the end of its price queue
+    /// after the previous peak has been executed. Size is the one of the new peak.
+    OldIcebergOrderRefilled(OrderID),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Change of the single resting limit order recorded by the [`OrderBook`] journal.
+/// Dummy and hidden orders are never recorded, iceberg orders are recorded by their peaks.
+pub enum ObDelta {
+    /// Order was added to the end of the price queue.
+    Add {
+        /// Order ID.
+        order_id: OrderID,
+        /// Order direction.
+        direction: Direction,
+        /// Order price.
+        price: Tick,
+        /// Order size.
+        size: Lots,
+    },
+    /// Remaining size of the order was changed, e.g. by the partial execution,
+    /// preserving its queue priority.
+    Modify {
+        /// Order ID.
+        order_id: OrderID,
+        /// New remaining size.
+        size: Lots,
+    },
+    /// Order was removed, e.g. cancelled or fully executed.
+    Delete {
+        /// Order ID.
+        order_id: OrderID,
+    },
+    /// All the orders were removed.
+    Clear,
+}
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+/// Treatment of the resting limit orders which prices become off-grid
+/// when the tick size of the [`OrderBook`] changes.
+pub enum OffGridOrderPolicy {
+    /// Cancel the off-grid orders.
+    #[default]
+    Cancel,
+    /// Move the off-grid orders to the nearest on-grid price levels
+    /// farther from the opposite side of the book, i.e. round bids down and asks up,
+    /// so that the book never becomes crossed.
+    /// Moved orders are queued behind the orders already resting at the new levels
+    /// in the order of their submission.
+    Rebucket,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+/// What happened to the resting limit order when the tick size of the [`OrderBook`] changed.
+pub enum TickSizeChangeEvent {
+    /// Order was moved to the new price level.
+    Repriced {
+        /// Order ID.
+        id: OrderID,
+        /// Order direction.
+        direction: Direction,
+        /// Price before the change.
+        old_price: Tick,
+        /// Price after the change.
+        new_price: Tick,
+        /// Remaining size.
+        size: Lots,
+        /// Displayed size before the change. Zero for the hidden orders.
+        old_displayed_size: Lots,
+        /// Displayed size after the change, i.e. the new peak of the iceberg order.
+        /// Zero for the hidden orders.
+        new_displayed_size: Lots,
+    },
+    /// Order was cancelled.
+    Cancelled {
+        /// Order ID.
+        id: OrderID,
+        /// Order direction.
+        direction: Direction,
+        /// Order price.
+        price: Tick,
+        /// Cancelled size.
+        size: Lots,
+        /// Cancelled displayed size. Zero for the hidden orders.
+        displayed_size: Lots,
+    },
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
@@ -167,19 +357,21 @@ impl Display for NoSuchID {
     }
 }
 
+/// Result of the matching of the new order with the price level.
+/// Carries the size matched with the old dummy orders, if those are matched.
 enum MatchingStatus {
-    FullyExecuted,
-    PartiallyExecuted(Lots),
+    FullyExecuted { ghost_size: Lots },
+    PartiallyExecuted { exec_size: Lots, ghost_size: Lots },
 }
 
-impl<const MATCH_DUMMY_WITH_DUMMY: bool> Default for OrderBook<MATCH_DUMMY_WITH_DUMMY> {
+impl Default for OrderBook {
     #[inline]
     fn default() -> Self {
         Self::new()
     }
 }
 
-impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
+impl OrderBook
 {
     #[inline]
     /// Creates a new instance of the `OrderBook`.
@@ -190,17 +382,63 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
             best_bid: Tick(0),
             best_ask: Tick(0),
             id_to_price_and_side: Default::default(),
+            spare_levels: Default::default(),
+            journal: None,
+            reserves: Default::default(),
+            match_dummy_with_dummy: false,
         }
     }
 
+    #[inline]
+    /// Makes the `OrderBook` record the changes of the resting non-dummy orders
+    /// into the journal, e.g. to publish the order book deltas.
+    pub fn with_journal(mut self) -> Self {
+        self.journal = Some(vec![]);
+        self
+    }
+
+    #[inline]
+    /// Makes the incoming dummy orders match the resting dummy orders,
+    /// as if the other dummy liquidity were real.
+    /// Such fills of the resting dummy orders are reported as the
+    /// [`OldGhostOrderExecuted`](OrderBookEventKind::OldGhostOrderExecuted)
+    /// and [`OldGhostOrderPartiallyExecuted`](OrderBookEventKind::OldGhostOrderPartiallyExecuted),
+    /// while the ones of the incoming dummy orders are reported as the
+    /// [`NewGhostOrderExecuted`](OrderBookEventKind::NewGhostOrderExecuted)
+    /// and [`NewGhostOrderPartiallyExecuted`](OrderBookEventKind::NewGhostOrderPartiallyExecuted)
+    /// separately from their fills by the non-dummy orders.
+    /// By default, dummy orders do not interact with each other.
+    pub fn with_dummy_matching(mut self) -> Self {
+        self.match_dummy_with_dummy = true;
+        self
+    }
+
+    #[inline]
+    /// Whether the incoming dummy orders match the resting dummy orders.
+    pub fn is_matching_dummy_with_dummy(&self) -> bool {
+        self.match_dummy_with_dummy
+    }
+
+    #[inline]
+    /// Takes the changes recorded since the last call, in the order they happened.
+    /// Empty if the journal is not enabled.
+    pub fn drain_journal(&mut self) -> Vec<ObDelta> {
+        self.journal.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
     #[inline]
     /// Clears the `OrderBook`.
     pub fn clear(&mut self) {
+        if !self.id_to_price_and_side.is_empty() {
+            record(&mut self.journal, ObDelta::Clear)
+        }
         self.best_bid = Tick(0);
         self.best_ask = Tick(0);
-        self.bids.clear();
-        self.asks.clear();
+        for level in self.bids.drain(..).chain(self.asks.drain(..)) {
+            recycle_level(&mut self.spare_levels, level)
+        }
         self.id_to_price_and_side.clear();
+        self.reserves.clear();
     }
 
     #[inline]
@@ -235,8 +473,82 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
             )
     }
 
+    #[inline]
+    /// Yields all IDs and prices of the active limit orders, in no particular order.
+    pub fn get_all_ids_and_prices(&self) -> impl Iterator<Item=(OrderID, Tick)> + '_ {
+        self.id_to_price_and_side.iter().map(|(id, (price, _))| (*id, *price))
+    }
+
+    #[inline]
+    /// Checks whether the limit order is resting in the `OrderBook`.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` — Order ID to check.
+    pub fn contains_limit_order(&self, id: OrderID) -> bool {
+        self.id_to_price_and_side.contains_key(&id)
+    }
+
+    #[inline]
+    /// Returns the direction, the price and the remaining size of the resting limit order
+    /// or `None` if there is no such order.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` — Order ID to look up.
+    pub fn get_limit_order(&self, id: OrderID) -> Option<(Direction, Tick, Lots)> {
+        let (direction, price, order) = self.find_limit_order(id)?;
+        Some((direction, price, order.size + self.get_reserve_size(id)))
+    }
+
+    #[inline]
+    /// Returns the displayed remaining size of the resting limit order,
+    /// i.e. the current peak of the iceberg order and zero for the hidden one,
+    /// or `None` if there is no such order.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` — Order ID to look up.
+    pub fn get_displayed_size(&self, id: OrderID) -> Option<Lots> {
+        let (_, _, order) = self.find_limit_order(id)?;
+        Some(if self.is_hidden(id) { Lots(0) } else { order.size })
+    }
+
+    fn find_limit_order(&self, id: OrderID) -> Option<(Direction, Tick, &LimitOrder)> {
+        let (price, buy) = *self.id_to_price_and_side.get(&id)?;
+        let (direction, level) = if buy {
+            (Direction::Buy, self.bids.get((self.best_bid - price).0 as usize)?)
+        } else {
+            (Direction::Sell, self.asks.get((price - self.best_ask).0 as usize)?)
+        };
+        let order = level.iter().find(|order| order.id == id && order.size != Lots(0))?;
+        Some((direction, price, order))
+    }
+
+    #[inline]
+    /// Checks whether the resting limit order is the hidden one.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` — Order ID to check.
+    pub fn is_hidden(&self, id: OrderID) -> bool {
+        is_hidden(&self.reserves, id)
+    }
+
+    #[inline]
+    /// Returns the size of the resting iceberg order not yet displayed.
+    /// Zero for the other orders.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` — Order ID to look up.
+    pub fn get_reserve_size(&self, id: OrderID) -> Lots {
+        self.reserves.get(&id).map_or(Lots(0), |reserve| reserve.size)
+    }
+
     #[inline]
     /// Cancels limit order, returning the cancelled limit order meta-information if successful.
+    /// Size of the cancelled iceberg order includes its reserve.
     ///
     /// # Arguments
     ///
@@ -265,9 +577,17 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
         price: Tick) -> (LimitOrder, Direction, Tick)
     {
         let mut opposite_side = if UPPER {
-            SideWrapper::<UPPER, true> { side: &mut self.asks, best_price: &mut self.best_ask }
+            SideWrapper::<UPPER, true> {
+                side: &mut self.asks,
+                best_price: &mut self.best_ask,
+                spare_levels: &mut self.spare_levels,
+            }
         } else {
-            SideWrapper::<UPPER, true> { side: &mut self.bids, best_price: &mut self.best_bid }
+            SideWrapper::<UPPER, true> {
+                side: &mut self.bids,
+                best_price: &mut self.best_bid,
+                spare_levels: &mut self.spare_levels,
+            }
         };
         let (side, best_price) = opposite_side.get_side_and_price();
         let offset = if UPPER {
@@ -281,8 +601,15 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
                     .filter(|order| order.id == id && order.size != Lots(0))
                     .next()
                 {
-                    let cancelled_order = *order;
+                    let mut cancelled_order = *order;
                     order.size = Lots(0);
+                    let reserve = self.reserves.remove(&id);
+                    if let Some(reserve) = reserve {
+                        cancelled_order.size += reserve.size
+                    }
+                    if !cancelled_order.is_dummy && !reserve.is_some_and(|r| r.is_hidden()) {
+                        record(&mut self.journal, ObDelta::Delete { order_id: id })
+                    }
                     let direction = if UPPER {
                         Direction::Sell
                     } else {
@@ -307,6 +634,7 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
 
     #[inline]
     /// Updates size of the limit order and places it to the end of the current price queue.
+    /// New size of the iceberg order includes its reserve.
     ///
     /// # Arguments
     ///
@@ -340,7 +668,18 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
                     order.size = Lots(0);
                     let LimitOrder { is_dummy, dt, .. } = *order;
                     LevelWrapper::<true>(level);
-                    level.push_back(LimitOrder { id, size: new_size, is_dummy, dt })
+                    let reserve = self.reserves.get_mut(&id);
+                    let is_hidden = reserve.as_ref().is_some_and(|reserve| reserve.is_hidden());
+                    let new_size = reserve.map_or(new_size, |reserve| reserve.split(new_size));
+                    level.push_back(LimitOrder { id, size: new_size, is_dummy, dt });
+                    if !is_dummy && !is_hidden {
+                        let direction = if buy { Direction::Buy } else { Direction::Sell };
+                        record(&mut self.journal, ObDelta::Delete { order_id: id });
+                        record(
+                            &mut self.journal,
+                            ObDelta::Add { order_id: id, direction, price, size: new_size },
+                        )
+                    }
                 } else {
                     unreachable!("No active order with such ID {} was found at the level", id)
                 }
@@ -359,6 +698,7 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
 
     #[inline]
     /// Updates size of the limit order.
+    /// New size of the iceberg order includes its reserve.
     ///
     /// # Arguments
     ///
@@ -386,7 +726,12 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
                     .filter(|order| order.id == id && order.size != Lots(0))
                     .next()
                 {
-                    order.size = new_size
+                    let reserve = self.reserves.get_mut(&id);
+                    let is_hidden = reserve.as_ref().is_some_and(|reserve| reserve.is_hidden());
+                    order.size = reserve.map_or(new_size, |reserve| reserve.split(new_size));
+                    if !order.is_dummy && !is_hidden {
+                        record(&mut self.journal, ObDelta::Modify { order_id: id, size: order.size })
+                    }
                 } else {
                     unreachable!("No active order with such ID {} was found at the level", id)
                 }
@@ -403,7 +748,173 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
         Ok(())
     }
 
-    /// Inserts limit order that is cancelled immediately after insertion.
+    #[inline]
+    /// Reduces the remaining size of the limit order, preserving its queue priority.
+    /// If the reduction is not less than the remaining size, the order is cancelled.
+    /// Reserve of the iceberg order is reduced first.
+    /// Returns the limit order meta-information before the reduction if successful.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` — Order ID to reduce.
+    /// * `size` — Size to cancel.
+    pub fn reduce_limit_order(
+        &mut self,
+        id: OrderID,
+        size: Lots) -> Result<(LimitOrder, Direction, Tick), NoSuchID>
+    {
+        let (price, buy) = if let Some(v) = self.id_to_price_and_side.get(&id) {
+            *v
+        } else {
+            return Err(NoSuchID);
+        };
+        let (side, offset, direction) = if buy {
+            (&mut self.bids, isize::from(self.best_bid - price), Direction::Buy)
+        } else {
+            (&mut self.asks, isize::from(price - self.best_ask), Direction::Sell)
+        };
+        if offset >= 0 {
+            if let Some(level) = side.get_mut(offset as usize) {
+                if let Some(order) = level.iter_mut()
+                    .find(|order| order.id == id && order.size != Lots(0))
+                {
+                    let reserve = self.reserves.get_mut(&id);
+                    let reserve_size = reserve.as_ref().map_or(Lots(0), |reserve| reserve.size);
+                    if size < order.size + reserve_size {
+                        let mut original_order = *order;
+                        original_order.size += reserve_size;
+                        if let Some(reserve) = reserve {
+                            let is_hidden = reserve.is_hidden();
+                            let from_reserve = size.min(reserve.size);
+                            reserve.size -= from_reserve;
+                            order.size -= size - from_reserve;
+                            if is_hidden || from_reserve == size {
+                                return Ok((original_order, direction, price));
+                            }
+                        } else {
+                            order.size -= size
+                        }
+                        if !order.is_dummy {
+                            record(
+                                &mut self.journal,
+                                ObDelta::Modify { order_id: id, size: order.size },
+                            )
+                        }
+                        return Ok((original_order, direction, price));
+                    }
+                } else {
+                    unreachable!("No active order with such ID {} was found at the level", id)
+                }
+            } else {
+                unreachable!(
+                    "No non-empty level was found for the given offset {} \
+                    from the best price",
+                    offset
+                )
+            }
+        } else {
+            unreachable!("Offset from the best price appeared to be negative: {}", offset)
+        }
+        self.cancel_limit_order(id)
+    }
+
+    /// Converts the resting limit orders to the new tick size.
+    /// Returns the events describing what happened to each order
+    /// which price is not a multiple of the `increment`, in the order of their IDs.
+    ///
+    /// # Arguments
+    ///
+    /// * `increment` — New minimum price increment. Should be positive.
+    /// * `policy` — Treatment of the off-grid orders.
+    pub fn change_tick_size(
+        &mut self,
+        increment: Tick,
+        policy: OffGridOrderPolicy) -> Vec<TickSizeChangeEvent>
+    {
+        if increment <= Tick(0) {
+            panic!("Price increment should be positive. Got: {increment}")
+        }
+        let mut off_grid: Vec<_> = self.get_all_ids_and_prices()
+            .filter(|(_, price)| price.0.rem_euclid(increment.0) != 0)
+            .map(|(id, _)| id)
+            .collect();
+        off_grid.sort_unstable();
+        let mut events = Vec::with_capacity(off_grid.len());
+        let mut moved = Vec::new();
+        for id in off_grid {
+            let display_size = self.reserves.get(&id).map(|reserve| reserve.display_size);
+            let displayed_size = self.get_displayed_size(id).unwrap_or(Lots(0));
+            let (order, direction, price) = self.cancel_limit_order(id).unwrap_or_else(
+                |err| unreachable!("Cannot cancel order {id}. Error: {err}")
+            );
+            match policy {
+                OffGridOrderPolicy::Cancel => events.push(
+                    TickSizeChangeEvent::Cancelled {
+                        id,
+                        direction,
+                        price,
+                        size: order.size,
+                        displayed_size,
+                    }
+                ),
+                OffGridOrderPolicy::Rebucket => {
+                    let remainder = price.0.rem_euclid(increment.0);
+                    let new_price = match direction {
+                        Direction::Buy => Tick(price.0 - remainder),
+                        Direction::Sell => Tick(price.0 - remainder + increment.0)
+                    };
+                    events.push(
+                        TickSizeChangeEvent::Repriced {
+                            id,
+                            direction,
+                            old_price: price,
+                            new_price,
+                            size: order.size,
+                            old_displayed_size: displayed_size,
+                            new_displayed_size: display_size.map_or(
+                                order.size,
+                                |display_size| order.size.min(display_size),
+                            ),
+                        }
+                    );
+                    moved.push((order, direction, new_price, display_size))
+                }
+            }
+        }
+        moved.sort_unstable_by_key(|(order, _, _, _)| (order.dt, order.id));
+        for (order, direction, price, display_size) in moved {
+            let LimitOrder { id, size, is_dummy, dt } = order;
+            if let Some(display_size) = display_size {
+                match direction {
+                    Direction::Buy => self.insert_iceberg_order_without_matching::<true>(
+                        dt, id, price, size, display_size,
+                    ),
+                    Direction::Sell => self.insert_iceberg_order_without_matching::<false>(
+                        dt, id, price, size, display_size,
+                    )
+                }
+                continue;
+            }
+            match (is_dummy, direction) {
+                (false, Direction::Buy) => {
+                    self.insert_limit_order_without_matching::<false, true>(dt, id, price, size)
+                }
+                (false, Direction::Sell) => {
+                    self.insert_limit_order_without_matching::<false, false>(dt, id, price, size)
+                }
+                (true, Direction::Buy) => {
+                    self.insert_limit_order_without_matching::<true, true>(dt, id, price, size)
+                }
+                (true, Direction::Sell) => {
+                    self.insert_limit_order_without_matching::<true, false>(dt, id, price, size)
+                }
+            }
+        }
+        events
+    }
+
+    /// Inserts limit order that is cancelled immediately after insertion,
+    /// i.e. matches it without inserting its remainder.
     ///
     /// # Parameters
     ///
@@ -412,8 +923,6 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
     ///
     /// # Arguments
     ///
-    /// * `dt` — Submission datetime.
-    /// * `id` — ID of the order to insert.
     /// * `price` — Order price.
     /// * `size` — Order size.
     /// * `callback` — Callback.
@@ -423,66 +932,11 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
         const BUY: bool
     >(
         &mut self,
-        mut price: Tick,
-        mut size: Lots,
+        price: Tick,
+        size: Lots,
         mut callback: CallBack,
     ) {
-        let mut opposite_side = if BUY {
-            SideWrapper::<BUY, false> { side: &mut self.asks, best_price: &mut self.best_ask }
-        } else {
-            SideWrapper::<BUY, false> { side: &mut self.bids, best_price: &mut self.best_bid }
-        };
-        let (opposite_side, best_price) = opposite_side.get_side_and_price();
-        // Match the new limit order
-        // with already submitted limit orders from the opposite side of the order book
-        if !opposite_side.is_empty() {
-            let intersection_depth = if BUY {
-                isize::from(price - best_price)
-            } else {
-                isize::from(best_price - price)
-            };
-            // Nearly the same logic as in the insert_market_order method
-            if intersection_depth >= 0 {
-                price = best_price;
-                for mut level in opposite_side.iter_mut()
-                    .take((1 + intersection_depth) as usize)
-                    .map(LevelWrapper::<false>)
-                {
-                    let level = level.get_level();
-                    match Self::match_with_level::<_, DUMMY>(
-                        level, price, size, &mut callback, &mut self.id_to_price_and_side,
-                    ) {
-                        MatchingStatus::FullyExecuted => {
-                            callback(
-                                OrderBookEvent {
-                                    size,
-                                    price,
-                                    kind: OrderBookEventKind::NewOrderExecuted,
-                                }
-                            );
-                            return;
-                        }
-                        MatchingStatus::PartiallyExecuted(exec_size) => {
-                            if exec_size != Lots(0) {
-                                size -= exec_size;
-                                callback(
-                                    OrderBookEvent {
-                                        size: exec_size,
-                                        price,
-                                        kind: OrderBookEventKind::NewOrderPartiallyExecuted,
-                                    }
-                                )
-                            }
-                        }
-                    }
-                    if BUY {
-                        price += Tick(1)
-                    } else {
-                        price -= Tick(1)
-                    }
-                }
-            }
-        }
+        self.match_limit_order::<_, DUMMY, BUY>(price, size, &mut callback);
     }
 
     /// Inserts limit order.
@@ -504,69 +958,101 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
         dt: DateTime,
         id: OrderID,
         price: Tick,
-        mut size: Lots,
+        size: Lots,
         mut callback: CallBack,
     ) {
-        {
-            let mut opposite_side = if BUY {
-                SideWrapper::<BUY, false> { side: &mut self.asks, best_price: &mut self.best_ask }
+        if let Some(size) = self.match_limit_order::<_, DUMMY, BUY>(price, size, &mut callback) {
+            self.insert_limit_order_without_matching::<DUMMY, BUY>(dt, id, price, size)
+        }
+    }
+
+    /// Inserts iceberg or hidden limit order.
+    /// The iceberg order displays its size by the peaks of the `display_size`.
+    /// When the peak is executed, the next one is placed to the end of the price queue.
+    /// The hidden order is not displayed at all, yet keeps its time priority,
+    /// i.e. it is executed before the displayed orders of the same price submitted after it.
+    /// Executions of the hidden orders are reported as the hidden ones.
+    ///
+    /// # Parameters
+    ///
+    /// * `BUY` — Whether the order is bid.
+    ///
+    /// # Arguments
+    ///
+    /// * `dt` — Submission datetime.
+    /// * `id` — ID of the order to insert.
+    /// * `price` — Order price.
+    /// * `size` — Order size.
+    /// * `display_size` — Size of the peaks. If zero, the order is hidden.
+    /// * `callback` — Callback.
+    pub fn insert_iceberg_order<CallBack: FnMut(OrderBookEvent), const BUY: bool>(
+        &mut self,
+        dt: DateTime,
+        id: OrderID,
+        price: Tick,
+        size: Lots,
+        display_size: Lots,
+        mut callback: CallBack,
+    ) {
+        if let Some(size) = self.match_limit_order::<_, false, BUY>(price, size, &mut callback) {
+            self.insert_iceberg_order_without_matching::<BUY>(dt, id, price, size, display_size)
+        }
+    }
+
+    /// Matches the new limit order with the opposite side of the order book.
+    /// Returns the remaining size or `None` if the order is fully executed.
+    fn match_limit_order<CallBack: FnMut(OrderBookEvent), const DUMMY: bool, const BUY: bool>(
+        &mut self,
+        price: Tick,
+        mut size: Lots,
+        callback: &mut CallBack) -> Option<Lots>
+    {
+        let mut opposite_side = if BUY {
+            SideWrapper::<BUY, false> {
+                side: &mut self.asks,
+                best_price: &mut self.best_ask,
+                spare_levels: &mut self.spare_levels,
+            }
+        } else {
+            SideWrapper::<BUY, false> {
+                side: &mut self.bids,
+                best_price: &mut self.best_bid,
+                spare_levels: &mut self.spare_levels,
+            }
+        };
+        // Match the new limit order
+        // with already submitted limit orders from the opposite side of the order book
+        let (opposite_side, best_price) = opposite_side.get_side_and_price();
+        if !opposite_side.is_empty() {
+            let intersection_depth = if BUY {
+                isize::from(price - best_price)
             } else {
-                SideWrapper::<BUY, false> { side: &mut self.bids, best_price: &mut self.best_bid }
+                isize::from(best_price - price)
             };
-            // Match the new limit order
-            // with already submitted limit orders from the opposite side of the order book
-            let (opposite_side, best_price) = opposite_side.get_side_and_price();
-            if !opposite_side.is_empty() {
-                let intersection_depth = if BUY {
-                    isize::from(price - best_price)
-                } else {
-                    isize::from(best_price - price)
-                };
-                let mut price = best_price;
-                // Nearly the same logic as in the insert_market_order method
-                if intersection_depth >= 0 {
-                    for mut level in opposite_side.iter_mut()
-                        .take((1 + intersection_depth) as usize)
-                        .map(LevelWrapper::<false>)
-                    {
-                        let level = level.get_level();
-                        match Self::match_with_level::<_, DUMMY>(
-                            level, price, size, &mut callback, &mut self.id_to_price_and_side,
-                        ) {
-                            MatchingStatus::FullyExecuted => {
-                                callback(
-                                    OrderBookEvent {
-                                        size,
-                                        price,
-                                        kind: OrderBookEventKind::NewOrderExecuted,
-                                    }
-                                );
-                                return;
-                            }
-                            MatchingStatus::PartiallyExecuted(exec_size) => {
-                                if exec_size != Lots(0) {
-                                    size -= exec_size;
-                                    callback(
-                                        OrderBookEvent {
-                                            size: exec_size,
-                                            price,
-                                            kind: OrderBookEventKind::NewOrderPartiallyExecuted,
-                                        }
-                                    )
-                                }
-                            }
-                        }
-                        if BUY {
-                            price += Tick(1)
-                        } else {
-                            price -= Tick(1)
-                        }
+            let mut price = best_price;
+            // Nearly the same logic as in the insert_market_order method
+            if intersection_depth >= 0 {
+                for mut level in opposite_side.iter_mut()
+                    .take((1 + intersection_depth) as usize)
+                    .map(LevelWrapper::<false>)
+                {
+                    let level = level.get_level();
+                    let status = Self::match_with_level::<_, DUMMY>(
+                        level, price, size, callback, &mut self.id_to_price_and_side,
+                        &mut self.journal, &mut self.reserves, self.match_dummy_with_dummy,
+                    );
+                    if Self::report_new_order_matching(status, price, &mut size, callback) {
+                        return None;
+                    }
+                    if BUY {
+                        price += Tick(1)
+                    } else {
+                        price -= Tick(1)
                     }
                 }
             }
         }
-
-        self.insert_limit_order_without_matching::<DUMMY, BUY>(dt, id, price, size)
+        Some(size)
     }
 
     #[inline]
@@ -574,7 +1060,8 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
     ///
     /// # Safety invariants
     /// * Should not be inserted into the side of the order book
-    /// if the current best price of the opposite side is better than the `price`.
+    ///   if the current best price of the opposite side is better than the `price`,
+    ///   unless the orders are accumulated for the auction. See [`uncross`](Self::uncross).
     ///
     /// # Parameters
     ///
@@ -597,14 +1084,20 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
     ) {
         // Insert the remaining size of the new limit order into the order book
         self.id_to_price_and_side.insert(id, (price, BUY));
+        if !DUMMY && !is_hidden(&self.reserves, id) {
+            let direction = if BUY { Direction::Buy } else { Direction::Sell };
+            record(&mut self.journal, ObDelta::Add { order_id: id, direction, price, size })
+        }
         let side = if BUY {
             &mut self.bids
         } else {
             &mut self.asks
         };
+        let spare_levels = &mut self.spare_levels;
+        let order = LimitOrder { dt, id, size, is_dummy: DUMMY };
         if side.is_empty() {
             // Case if the corresponding side of the order book does not have any orders
-            side.push_back([LimitOrder { dt, id, size, is_dummy: DUMMY }].into());
+            side.push_back(new_level(spare_levels, order));
             if BUY {
                 self.best_bid = price
             } else {
@@ -622,7 +1115,7 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
                 for _ in 1..-offset {
                     side.push_front(Default::default())
                 }
-                side.push_front([LimitOrder { dt, id, size, is_dummy: DUMMY }].into());
+                side.push_front(new_level(spare_levels, order));
                 if BUY {
                     self.best_bid = price
                 } else {
@@ -632,18 +1125,56 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
                 // If not, place order in the depth of the corresponding side
                 let offset = offset as usize;
                 if let Some(level) = side.get_mut(offset) {
-                    level.push_back(LimitOrder { dt, id, size, is_dummy: DUMMY })
+                    if level.capacity() == 0 {
+                        *level = new_level(spare_levels, order)
+                    } else {
+                        level.push_back(order)
+                    }
                 } else {
                     side.extend(
                         repeat_with(Default::default)
                             .take(offset - side.len())
-                            .chain(once([LimitOrder { dt, id, size, is_dummy: DUMMY }].into()))
+                            .chain(once(new_level(spare_levels, order)))
                     )
                 }
             }
         }
     }
 
+    #[inline]
+    /// Inserts iceberg or hidden limit order without matching.
+    /// See [`insert_iceberg_order`](Self::insert_iceberg_order).
+    ///
+    /// # Safety invariants
+    /// * Should not be inserted into the side of the order book
+    ///   if the current best price of the opposite side is better than the `price`,
+    ///   unless the orders are accumulated for the auction. See [`uncross`](Self::uncross).
+    ///
+    /// # Parameters
+    ///
+    /// * `BUY` — Whether the order is bid.
+    ///
+    /// # Arguments
+    ///
+    /// * `dt` — Submission datetime.
+    /// * `id` — ID of the order to insert.
+    /// * `price` — Order price.
+    /// * `size` — Order size.
+    /// * `display_size` — Size of the peaks. If zero, the order is hidden.
+    pub fn insert_iceberg_order_without_matching<const BUY: bool>(
+        &mut self,
+        dt: DateTime,
+        id: OrderID,
+        price: Tick,
+        size: Lots,
+        display_size: Lots,
+    ) {
+        let mut reserve = Reserve { display_size, size: Lots(0) };
+        let size = reserve.split(size);
+        self.reserves.insert(id, reserve);
+        self.insert_limit_order_without_matching::<false, BUY>(dt, id, price, size)
+    }
+
     /// Inserts market order.
     ///
     /// # Parameters
@@ -660,39 +1191,28 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
         mut callback: CallBack,
     ) {
         let mut opposite_side = if BUY {
-            SideWrapper::<BUY, false> { side: &mut self.asks, best_price: &mut self.best_ask }
+            SideWrapper::<BUY, false> {
+                side: &mut self.asks,
+                best_price: &mut self.best_ask,
+                spare_levels: &mut self.spare_levels,
+            }
         } else {
-            SideWrapper::<BUY, false> { side: &mut self.bids, best_price: &mut self.best_bid }
+            SideWrapper::<BUY, false> {
+                side: &mut self.bids,
+                best_price: &mut self.best_bid,
+                spare_levels: &mut self.spare_levels,
+            }
         };
         let (side, mut price) = opposite_side.get_side_and_price();
         for mut level in side.iter_mut().map(LevelWrapper::<false>)
         {
             let level = level.get_level();
-            match Self::match_with_level::<_, DUMMY>(
+            let status = Self::match_with_level::<_, DUMMY>(
                 level, price, size, &mut callback, &mut self.id_to_price_and_side,
-            ) {
-                MatchingStatus::FullyExecuted => {
-                    callback(
-                        OrderBookEvent {
-                            size,
-                            price,
-                            kind: OrderBookEventKind::NewOrderExecuted,
-                        }
-                    );
-                    return;
-                }
-                MatchingStatus::PartiallyExecuted(exec_size) => {
-                    if exec_size != Lots(0) {
-                        size -= exec_size;
-                        callback(
-                            OrderBookEvent {
-                                size: exec_size,
-                                price,
-                                kind: OrderBookEventKind::NewOrderPartiallyExecuted,
-                            }
-                        )
-                    }
-                }
+                &mut self.journal, &mut self.reserves, self.match_dummy_with_dummy,
+            );
+            if Self::report_new_order_matching(status, price, &mut size, &mut callback) {
+                return;
             }
             if BUY {
                 price += Tick(1)
@@ -703,7 +1223,8 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
     }
 
     #[inline]
-    /// Returns an iterator over the order book side.
+    /// Returns an iterator over the displayed part of the order book side,
+    /// i.e. without the dummy and the hidden orders and without the reserves of the icebergs.
     ///
     /// # Parameters
     /// * `UPPER` — Whether the side is asks.
@@ -722,7 +1243,10 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
                 |level| level
                     .iter()
                     .filter_map(
-                        |order| if order.size != Lots(0) && !order.is_dummy {
+                        |order| if order.size != Lots(0)
+                            && !order.is_dummy
+                            && !is_hidden(&self.reserves, order.id)
+                        {
                             Some((order.id, order.size, order.dt))
                         } else {
                             None
@@ -752,6 +1276,87 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
             )
     }
 
+    #[inline]
+    /// Returns an iterator over the non-empty bid levels, the best one first.
+    /// Each level is yielded along with its price as an iterator over the references
+    /// to the active limit orders in the order of their queue priority, dummy ones included.
+    pub fn iter_bids(
+        &self
+    ) -> impl Iterator<Item=(Tick, impl Iterator<Item=&LimitOrder> + '_)> + '_
+    {
+        self.iter_side::<false>()
+    }
+
+    #[inline]
+    /// Returns an iterator over the non-empty ask levels, the best one first.
+    /// Each level is yielded along with its price as an iterator over the references
+    /// to the active limit orders in the order of their queue priority, dummy ones included.
+    pub fn iter_asks(
+        &self
+    ) -> impl Iterator<Item=(Tick, impl Iterator<Item=&LimitOrder> + '_)> + '_
+    {
+        self.iter_side::<true>()
+    }
+
+    #[inline]
+    /// Returns the side of the price level and an iterator over the references
+    /// to its active limit orders in the order of their queue priority, dummy ones included.
+    /// Returns `None` if there are no active limit orders at the price.
+    ///
+    /// # Arguments
+    ///
+    /// * `price` — Price of the level.
+    pub fn level_at(
+        &self,
+        price: Tick) -> Option<(Direction, impl Iterator<Item=&LimitOrder> + '_)>
+    {
+        let is_active = |order: &&LimitOrder| order.size != Lots(0);
+        let ask_level = if price >= self.best_ask {
+            self.asks.get((price - self.best_ask).0 as usize)
+        } else {
+            None
+        };
+        let bid_level = if price <= self.best_bid {
+            self.bids.get((self.best_bid - price).0 as usize)
+        } else {
+            None
+        };
+        [(Direction::Sell, ask_level), (Direction::Buy, bid_level)]
+            .into_iter()
+            .find_map(
+                |(direction, level)| {
+                    let level = level?;
+                    if level.iter().any(|order| order.size != Lots(0)) {
+                        Some((direction, level.iter().filter(is_active)))
+                    } else {
+                        None
+                    }
+                }
+            )
+    }
+
+    #[inline]
+    fn iter_side<const UPPER: bool>(
+        &self
+    ) -> impl Iterator<Item=(Tick, impl Iterator<Item=&LimitOrder> + '_)> + '_
+    {
+        let (side, best_price) = if UPPER {
+            (&self.asks, self.best_ask)
+        } else {
+            (&self.bids, self.best_bid)
+        };
+        let is_active = |order: &&LimitOrder| order.size != Lots(0);
+        side.iter()
+            .zip(0..)
+            .filter(|(level, _)| level.iter().any(|order| order.size != Lots(0)))
+            .map(
+                move |(level, i)| {
+                    let price = if UPPER { best_price + Tick(i) } else { best_price - Tick(i) };
+                    (price, level.iter().filter(is_active))
+                }
+            )
+    }
+
     #[inline]
     /// Returns an iterator over the order book volume-weighted pending times.
     ///
@@ -810,7 +1415,7 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
     /// # Arguments
     ///
     /// * `max_levels` — Maximum number of non-empty price levels to get.
-    ///                  If zero, the number of levels is considered unlimited.
+    ///   If zero, the number of levels is considered unlimited.
     pub fn get_ob_side<const UPPER: bool>(
         &self,
         max_levels: usize) -> Vec<(Tick, Vec<(OrderID, Lots, DateTime)>)>
@@ -833,7 +1438,7 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
     /// # Arguments
     ///
     /// * `max_levels` — Maximum number of non-empty price levels to get.
-    ///                  If zero, the number of levels is considered unlimited.
+    ///   If zero, the number of levels is considered unlimited.
     pub fn get_ob_side_with_no_id<const UPPER: bool>(
         &self,
         max_levels: usize) -> Vec<(Tick, Vec<(Lots, DateTime)>)>
@@ -853,7 +1458,7 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
     /// # Arguments
     ///
     /// * `max_levels` — Maximum number of non-empty price levels per side to get.
-    ///                  If zero, full order book state is returned.
+    ///   If zero, full order book state is returned.
     pub fn get_ob_state(&self, max_levels: usize) -> ObState {
         ObState {
             bids: self.get_ob_side_with_no_id::<false>(max_levels),
@@ -861,17 +1466,255 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
         }
     }
 
+    /// Returns the microprice, in ticks, ignoring the dummy orders.
+    /// See [`ObState::get_microprice`].
+    pub fn get_microprice(&self) -> Option<f64> {
+        get_microprice(self.iter_level_sizes::<false>(), self.iter_level_sizes::<true>())
+    }
+
+    /// Returns the imbalance of the best levels, ignoring the dummy orders.
+    /// See [`ObState::get_top_imbalance`].
+    pub fn get_top_imbalance(&self) -> Option<f64> {
+        self.get_depth_imbalance(0.0, 1)
+    }
+
+    /// Returns the weighted depth imbalance, ignoring the dummy orders.
+    /// See [`ObState::get_depth_imbalance`].
+    ///
+    /// # Arguments
+    ///
+    /// * `decay` — Weight decay per level. Should be within `[0, 1]`.
+    /// * `max_levels` — Maximum number of non-empty price levels per side to take into account.
+    ///   If zero, the number of levels is considered unlimited.
+    pub fn get_depth_imbalance(&self, decay: f64, max_levels: usize) -> Option<f64> {
+        get_depth_imbalance(
+            self.iter_level_sizes::<false>(),
+            self.iter_level_sizes::<true>(),
+            decay,
+            max_levels,
+        )
+    }
+
+    /// Returns an iterator over the non-empty levels of the side along with their sizes,
+    /// the best one first, ignoring the dummy orders.
+    ///
+    /// # Parameters
+    /// * `UPPER` — Whether the side is asks.
+    ///
+    pub(crate) fn iter_level_sizes<const UPPER: bool>(
+        &self) -> impl Iterator<Item=(Tick, Lots)> + '_
+    {
+        self.get_ob_side_iter::<UPPER>()
+            .map(|(price, level)| (price, level.map(|(_, size, _)| size).sum()))
+    }
+
+    /// Returns the size the incoming limit order would be executed by at once,
+    /// capped by its size. Hidden orders and reserves of the icebergs are counted,
+    /// while the dummy orders are not.
+    ///
+    /// # Parameters
+    /// * `BUY` — Whether the incoming order is the buy one.
+    ///
+    /// # Arguments
+    ///
+    /// * `price` — Price of the incoming order.
+    /// * `size` — Size of the incoming order.
+    pub fn get_executable_size<const BUY: bool>(&self, price: Tick, size: Lots) -> Lots {
+        let mut executable_size = Lots(0);
+        for (level_price, level) in self.iter_side::<BUY>() {
+            if executable_size >= size
+                || if BUY { level_price > price } else { level_price < price }
+            {
+                break;
+            }
+            executable_size += level
+                .filter(|order| !order.is_dummy)
+                .map(|order| order.size + self.get_reserve_size(order.id))
+                .sum();
+        }
+        executable_size.min(size)
+    }
+
+    /// Returns the equilibrium price of the crossed order book along with the size
+    /// to be executed at it or `None` if the order book is not crossed.
+    /// Candidates are the prices of the crossing levels. The chosen one maximizes
+    /// the executed size, ties are broken by the least imbalance between the demand
+    /// and the supply, then by the closeness to the `reference_price`, then by the lower price.
+    /// Hidden orders and reserves of the icebergs are counted, while the dummy orders are not.
+    ///
+    /// # Arguments
+    ///
+    /// * `reference_price` — Price to prefer among the equivalent ones, e.g. the last trade one.
+    pub fn get_auction_price(&self, reference_price: Option<Tick>) -> Option<(Tick, Lots)> {
+        let bids = self.get_non_dummy_level_sizes(self.iter_bids());
+        let asks = self.get_non_dummy_level_sizes(self.iter_asks());
+        let (best_bid, _) = *bids.first()?;
+        let (best_ask, _) = *asks.first()?;
+        let crossing_bids = bids.iter().take_while(|(price, _)| *price >= best_ask);
+        let crossing_asks = asks.iter().take_while(|(price, _)| *price <= best_bid);
+        crossing_bids.chain(crossing_asks)
+            .map(
+                |(price, _)| {
+                    let demand: Lots = bids.iter()
+                        .take_while(|(bid_price, _)| bid_price >= price)
+                        .map(|(_, size)| *size)
+                        .sum();
+                    let supply: Lots = asks.iter()
+                        .take_while(|(ask_price, _)| ask_price <= price)
+                        .map(|(_, size)| *size)
+                        .sum();
+                    (*price, demand, supply)
+                }
+            )
+            .max_by_key(
+                |(price, demand, supply)| (
+                    (*demand).min(*supply),
+                    Reverse((demand.0 - supply.0).abs()),
+                    Reverse(reference_price.map_or(0, |reference| (price.0 - reference.0).abs())),
+                    Reverse(*price),
+                )
+            )
+            .map(|(price, demand, supply)| (price, demand.min(supply)))
+    }
+
+    /// Returns the total sizes of the non-dummy orders of the levels, reserves included,
+    /// skipping the levels without them.
+    fn get_non_dummy_level_sizes<'a, Level: Iterator<Item=&'a LimitOrder>>(
+        &self,
+        levels: impl Iterator<Item=(Tick, Level)>) -> Vec<(Tick, Lots)>
+    {
+        levels
+            .map(
+                |(price, level)| {
+                    let size = level
+                        .filter(|order| !order.is_dummy)
+                        .map(|order| order.size + self.get_reserve_size(order.id))
+                        .sum();
+                    (price, size)
+                }
+            )
+            .filter(|(_, size)| *size != Lots(0))
+            .collect()
+    }
+
+    /// Crosses the resting orders at the auction price, e.g. the one returned by the
+    /// [`get_auction_price`](Self::get_auction_price).
+    /// Bids crossing the `price` are matched with the asks in the order of their priority,
+    /// each as if it were the incoming one, yet keeping its queue position if not fully executed.
+    /// Reserve of the iceberg bid is executed first. Dummy bids are left as is.
+    /// All the executions are reported at the auction price,
+    /// while the refills of the iceberg asks keep their prices.
+    ///
+    /// # Arguments
+    ///
+    /// * `price` — Auction price.
+    /// * `callback` — Callback receiving the ID of the bid matched along with the event.
+    pub fn uncross(&mut self, price: Tick, mut callback: impl FnMut(OrderID, OrderBookEvent)) {
+        loop {
+            let bid = self.iter_bids()
+                .take_while(|(bid_price, _)| *bid_price >= price)
+                .find_map(
+                    |(_, mut level)| level.find(|order| !order.is_dummy).map(|order| order.id)
+                );
+            let id = if let Some(id) = bid {
+                id
+            } else {
+                break;
+            };
+            let size = self.get_limit_order(id)
+                .map(|(_, _, size)| size)
+                .unwrap_or_else(|| unreachable!("Cannot find bid {id}"));
+            let executable_size = self.get_executable_size::<true>(price, size);
+            if executable_size == Lots(0) {
+                break;
+            }
+            self.match_limit_order::<_, false, true>(
+                price,
+                executable_size,
+                &mut |mut event: OrderBookEvent| {
+                    if !matches!(event.kind, OrderBookEventKind::OldIcebergOrderRefilled(_)) {
+                        event.price = price
+                    }
+                    // Bid is not fully executed if it has been matched with all the asks
+                    if executable_size < size && event.kind == OrderBookEventKind::NewOrderExecuted
+                    {
+                        event.kind = OrderBookEventKind::NewOrderPartiallyExecuted
+                    }
+                    callback(id, event)
+                },
+            );
+            self.reduce_limit_order(id, executable_size)
+                .unwrap_or_else(|err| unreachable!("Cannot reduce bid {id}. Error: {err}"));
+        }
+    }
+
+    /// Reports the execution of the new order at the price level,
+    /// reporting its fills by the old dummy orders separately from the other ones.
+    /// Reduces the `size` by the executed one.
+    /// Returns whether the new order is fully executed.
+    fn report_new_order_matching(
+        status: MatchingStatus,
+        price: Tick,
+        size: &mut Lots,
+        callback: &mut impl FnMut(OrderBookEvent)) -> bool
+    {
+        let (exec_size, ghost_size, is_executed) = match status {
+            MatchingStatus::FullyExecuted { ghost_size } => (*size, ghost_size, true),
+            MatchingStatus::PartiallyExecuted { exec_size, ghost_size } => {
+                (exec_size, ghost_size, false)
+            }
+        };
+        let real_size = exec_size - ghost_size;
+        if real_size != Lots(0) {
+            callback(
+                OrderBookEvent {
+                    size: real_size,
+                    price,
+                    kind: if is_executed && ghost_size == Lots(0) {
+                        OrderBookEventKind::NewOrderExecuted
+                    } else {
+                        OrderBookEventKind::NewOrderPartiallyExecuted
+                    },
+                }
+            )
+        }
+        if ghost_size != Lots(0) {
+            callback(
+                OrderBookEvent {
+                    size: ghost_size,
+                    price,
+                    kind: if is_executed {
+                        OrderBookEventKind::NewGhostOrderExecuted
+                    } else {
+                        OrderBookEventKind::NewGhostOrderPartiallyExecuted
+                    },
+                }
+            )
+        }
+        *size -= exec_size;
+        is_executed
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn match_with_level<Callback: FnMut(OrderBookEvent), const DUMMY: bool>(
         level: &mut VecDeque<LimitOrder>,
         price: Tick,
         size: Lots,
         callback: &mut Callback,
-        id_to_price_and_side: &mut HashMap<OrderID, (Tick, bool)>) -> MatchingStatus
+        id_to_price_and_side: &mut HashMap<OrderID, (Tick, bool)>,
+        journal: &mut Option<Vec<ObDelta>>,
+        reserves: &mut HashMap<OrderID, Reserve>,
+        match_dummy_with_dummy: bool) -> MatchingStatus
     {
+        // Dummy orders do not change the sizes of the non-dummy ones
         if DUMMY {
-            Self::match_dummy_with_level(level, price, size, callback, id_to_price_and_side)
+            Self::match_dummy_with_level(
+                level, price, size, callback, id_to_price_and_side, match_dummy_with_dummy,
+            )
         } else {
-            Self::match_real_with_level(level, price, size, callback, id_to_price_and_side)
+            Self::match_real_with_level(
+                level, price, size, callback, id_to_price_and_side, journal, reserves,
+            )
         }
     }
 
@@ -880,12 +1723,14 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
         price: Tick,
         mut size: Lots,
         callback: &mut impl FnMut(OrderBookEvent),
-        id_to_price_and_side: &mut HashMap<OrderID, (Tick, bool)>) -> MatchingStatus
+        id_to_price_and_side: &mut HashMap<OrderID, (Tick, bool)>,
+        match_dummy_with_dummy: bool) -> MatchingStatus
     {
         let size_before_matching = size;
+        let mut ghost_size = Lots(0);
         for order in level.iter_mut().filter(|order| order.size != Lots(0)) {
             if order.is_dummy {
-                if MATCH_DUMMY_WITH_DUMMY {
+                if match_dummy_with_dummy {
                     match size.cmp(&order.size) {
                         Ordering::Less => {
                             // (OrderExecuted, OrderPartiallyExecuted)
@@ -893,11 +1738,14 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
                                 OrderBookEvent {
                                     size,
                                     price,
-                                    kind: OrderBookEventKind::OldOrderPartiallyExecuted(order.id),
+                                    kind: OrderBookEventKind::OldGhostOrderPartiallyExecuted(
+                                        order.id
+                                    ),
                                 }
                             );
                             order.size -= size;
-                            return MatchingStatus::FullyExecuted;
+                            ghost_size += size;
+                            return MatchingStatus::FullyExecuted { ghost_size };
                         }
                         Ordering::Equal => {
                             // (OrderExecuted, OrderExecuted)
@@ -911,11 +1759,12 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
                                 OrderBookEvent {
                                     size,
                                     price,
-                                    kind: OrderBookEventKind::OldOrderExecuted(order.id),
+                                    kind: OrderBookEventKind::OldGhostOrderExecuted(order.id),
                                 }
                             );
                             order.size = Lots(0);
-                            return MatchingStatus::FullyExecuted;
+                            ghost_size += size;
+                            return MatchingStatus::FullyExecuted { ghost_size };
                         }
                         Ordering::Greater => {
                             // (OrderPartiallyExecuted, OrderExecuted)
@@ -929,10 +1778,11 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
                                 OrderBookEvent {
                                     size: order.size,
                                     price,
-                                    kind: OrderBookEventKind::OldOrderExecuted(order.id),
+                                    kind: OrderBookEventKind::OldGhostOrderExecuted(order.id),
                                 }
                             );
                             size -= order.size;
+                            ghost_size += order.size;
                             order.size = Lots(0);
                         }
                     }
@@ -940,10 +1790,10 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
             } else if size > order.size {
                 size -= order.size;
             } else {
-                return MatchingStatus::FullyExecuted;
+                return MatchingStatus::FullyExecuted { ghost_size };
             }
         }
-        MatchingStatus::PartiallyExecuted(size_before_matching - size)
+        MatchingStatus::PartiallyExecuted { exec_size: size_before_matching - size, ghost_size }
     }
 
     fn match_real_with_level(
@@ -951,87 +1801,135 @@ impl<const MATCH_DUMMY_WITH_DUMMY: bool> OrderBook<MATCH_DUMMY_WITH_DUMMY>
         price: Tick,
         mut size: Lots,
         callback: &mut impl FnMut(OrderBookEvent),
-        id_to_price_and_side: &mut HashMap<OrderID, (Tick, bool)>) -> MatchingStatus
+        id_to_price_and_side: &mut HashMap<OrderID, (Tick, bool)>,
+        journal: &mut Option<Vec<ObDelta>>,
+        reserves: &mut HashMap<OrderID, Reserve>) -> MatchingStatus
     {
         let size_before_matching = size;
-        for order in level.iter_mut().filter(|order| order.size != Lots(0)) {
-            if !order.is_dummy {
-                match size.cmp(&order.size) {
-                    Ordering::Less => {
+        // Next peaks of the iceberg orders which previous ones are executed
+        let mut refilled = Vec::new();
+        loop {
+            for order in level.iter_mut().filter(|order| order.size != Lots(0)) {
+                if !order.is_dummy {
+                    let reserve = if reserves.is_empty() {
+                        None
+                    } else {
+                        reserves.get_mut(&order.id)
+                    };
+                    let has_reserve = reserve.is_some();
+                    let is_hidden = reserve.as_ref().is_some_and(|reserve| reserve.is_hidden());
+                    let (executed, partially_executed) = if is_hidden {
+                        (
+                            OrderBookEventKind::OldHiddenOrderExecuted(order.id),
+                            OrderBookEventKind::OldHiddenOrderPartiallyExecuted(order.id),
+                        )
+                    } else {
+                        (
+                            OrderBookEventKind::OldOrderExecuted(order.id),
+                            OrderBookEventKind::OldOrderPartiallyExecuted(order.id),
+                        )
+                    };
+                    if size < order.size {
                         // (OrderExecuted, OrderPartiallyExecuted)
-                        callback(
-                            OrderBookEvent {
-                                size,
-                                price,
-                                kind: OrderBookEventKind::OldOrderPartiallyExecuted(order.id),
-                            }
-                        );
+                        callback(OrderBookEvent { size, price, kind: partially_executed });
                         order.size -= size;
-                        return MatchingStatus::FullyExecuted;
+                        if !is_hidden {
+                            record(journal, ObDelta::Modify { order_id: order.id, size: order.size })
+                        }
+                        size = Lots(0);
+                        break;
                     }
-                    Ordering::Equal => {
-                        // (OrderExecuted, OrderExecuted)
+                    let exec_size = order.size;
+                    let refill = reserve.filter(|reserve| reserve.size != Lots(0)).map(
+                        |reserve| {
+                            let refill = reserve.size.min(reserve.display_size);
+                            reserve.size -= refill;
+                            refill
+                        }
+                    );
+                    if let Some(refill) = refill {
+                        // Peak is executed, yet the order is not
+                        callback(OrderBookEvent { size: exec_size, price, kind: partially_executed });
+                        refilled.push(LimitOrder { size: refill, ..*order })
+                    } else {
+                        // (OrderPartiallyExecuted or OrderExecuted, OrderExecuted)
                         id_to_price_and_side.remove(&order.id).unwrap_or_else(
                             || unreachable!(
                                 "id_to_price_and_side does not contain {}",
                                 order.id
                             )
                         );
-                        callback(
-                            OrderBookEvent {
-                                size,
-                                price,
-                                kind: OrderBookEventKind::OldOrderExecuted(order.id),
-                            }
-                        );
-                        order.size = Lots(0);
-                        return MatchingStatus::FullyExecuted;
+                        if has_reserve {
+                            reserves.remove(&order.id);
+                        }
+                        callback(OrderBookEvent { size: exec_size, price, kind: executed })
                     }
-                    Ordering::Greater => {
-                        // (OrderPartiallyExecuted, OrderExecuted)
-                        id_to_price_and_side.remove(&order.id).unwrap_or_else(
-                            || unreachable!(
-                                "id_to_price_and_side does not contain {}",
-                                order.id
-                            )
-                        );
-                        callback(
-                            OrderBookEvent {
-                                size: order.size,
-                                price,
-                                kind: OrderBookEventKind::OldOrderExecuted(order.id),
-                            }
-                        );
-                        size -= order.size;
-                        order.size = Lots(0);
+                    size -= exec_size;
+                    order.size = Lots(0);
+                    if !is_hidden {
+                        record(journal, ObDelta::Delete { order_id: order.id })
                     }
-                }
-            } else if order.size > size {
-                callback(
-                    OrderBookEvent {
-                        size,
-                        price,
-                        kind: OrderBookEventKind::OldOrderPartiallyExecuted(order.id),
+                    if size == Lots(0) {
+                        break;
                     }
-                );
-                order.size -= size;
-            } else {
-                id_to_price_and_side.remove(&order.id).unwrap_or_else(
-                    || unreachable!(
-                        "id_to_price_and_side does not contain {}",
-                        order.id
-                    )
-                );
+                } else if order.size > size {
+                    callback(
+                        OrderBookEvent {
+                            size,
+                            price,
+                            kind: OrderBookEventKind::OldOrderPartiallyExecuted(order.id),
+                        }
+                    );
+                    order.size -= size;
+                } else {
+                    id_to_price_and_side.remove(&order.id).unwrap_or_else(
+                        || unreachable!(
+                            "id_to_price_and_side does not contain {}",
+                            order.id
+                        )
+                    );
+                    callback(
+                        OrderBookEvent {
+                            size: order.size,
+                            price,
+                            kind: OrderBookEventKind::OldOrderExecuted(order.id),
+                        }
+                    );
+                    order.size = Lots(0);
+                }
+            }
+            if refilled.is_empty() {
+                break;
+            }
+            for order in refilled.drain(..) {
                 callback(
                     OrderBookEvent {
                         size: order.size,
                         price,
-                        kind: OrderBookEventKind::OldOrderExecuted(order.id),
+                        kind: OrderBookEventKind::OldIcebergOrderRefilled(order.id),
                     }
                 );
-                order.size = Lots(0);
+                if journal.is_some() {
+                    let (_, buy) = id_to_price_and_side[&order.id];
+                    let direction = if buy { Direction::Buy } else { Direction::Sell };
+                    record(
+                        journal,
+                        ObDelta::Add { order_id: order.id, direction, price, size: order.size },
+                    )
+                }
+                level.push_back(order)
+            }
+            if size == Lots(0) {
+                break;
+            }
+        }
+        if size == Lots(0) {
+            MatchingStatus::FullyExecuted { ghost_size: Lots(0) }
+        } else {
+            MatchingStatus::PartiallyExecuted {
+                exec_size: size_before_matching - size,
+                ghost_size: Lots(0),
             }
         }
-        MatchingStatus::PartiallyExecuted(size_before_matching - size)
     }
 }
\ No newline at end of file
@@ -1,13 +1,22 @@
 use crate::{
     concrete::{
-        order_book::{LimitOrder, NoSuchID, OrderBook, OrderBookEvent, OrderBookEventKind::*},
+        order_book::{
+            LimitOrder,
+            NoSuchID,
+            ObDelta,
+            OffGridOrderPolicy,
+            OrderBook,
+            OrderBookEvent,
+            OrderBookEventKind::*,
+            TickSizeChangeEvent,
+        },
         types::{Direction::*, Lots, ObState, OrderID, Tick},
     },
     types::{Date, DateTime},
 };
 
 fn insert_limit_order<const DUMMY: bool, const BID: bool>(
-    ob: &mut OrderBook<false>,
+    ob: &mut OrderBook,
     dt: DateTime,
     id: OrderID,
     price: Tick,
@@ -20,7 +29,7 @@ fn insert_limit_order<const DUMMY: bool, const BID: bool>(
 }
 
 fn insert_market_order<const DUMMY: bool, const BUY: bool>(
-    ob: &mut OrderBook<false>,
+    ob: &mut OrderBook,
     size: Lots) -> Vec<OrderBookEvent>
 {
     let mut ob_events = Vec::new();
@@ -29,7 +38,7 @@ fn insert_market_order<const DUMMY: bool, const BUY: bool>(
     ob_events
 }
 
-fn default_example<const TEST: bool>() -> OrderBook<false>
+fn default_example<const TEST: bool>() -> OrderBook
 {
     let mut order_book = OrderBook::new();
     for (dt, id, price, size, bid) in [
@@ -54,7 +63,7 @@ fn default_example<const TEST: bool>() -> OrderBook<false>
     order_book
 }
 
-fn default_example_bids(order_book: &mut OrderBook<false>)
+fn default_example_bids(order_book: &mut OrderBook)
 {
     for (dt, id, price, size, bid) in [
         (Date::from_ymd(2020, 02, 03).and_hms(12, 03, 04), OrderID(1), Tick(23), Lots(4), true),
@@ -69,7 +78,7 @@ fn default_example_bids(order_book: &mut OrderBook<false>)
     }
 }
 
-fn default_example_asks(order_book: &mut OrderBook<false>)
+fn default_example_asks(order_book: &mut OrderBook)
 {
     for (dt, id, price, size, bid) in [
         (Date::from_ymd(2020, 02, 03).and_hms(07, 00, 00), OrderID(0), Tick(27), Lots(3), false),
@@ -86,7 +95,7 @@ fn default_example_asks(order_book: &mut OrderBook<false>)
     }
 }
 
-fn default_example_dummies(order_book: &mut OrderBook<false>)
+fn default_example_dummies(order_book: &mut OrderBook)
 {
     for (dt, id, price, size, bid) in [
         (Date::from_ymd(2020, 02, 04).and_hms(07, 00, 00), OrderID(8), Tick(26), Lots(3), true),
@@ -244,6 +253,110 @@ fn test_clear()
     assert_eq!(order_book.get_ob_state(0), ObState { bids: vec![], asks: vec![] })
 }
 
+#[test]
+fn test_change_tick_size_cancelling()
+{
+    let mut order_book = default_example::<false>();
+    assert_eq!(
+        order_book.change_tick_size(Tick(2), OffGridOrderPolicy::Cancel),
+        [
+            (OrderID(0), Sell, Tick(27), Lots(3)),
+            (OrderID(1), Buy, Tick(23), Lots(4)),
+            (OrderID(3), Buy, Tick(23), Lots(44)),
+            (OrderID(4), Sell, Tick(29), Lots(126)),
+            (OrderID(6), Sell, Tick(29), Lots(8)),
+        ].map(
+            |(id, direction, price, size)| TickSizeChangeEvent::Cancelled {
+                id,
+                direction,
+                price,
+                size,
+                displayed_size: size,
+            }
+        )
+    );
+    assert_eq!(
+        order_book.get_ob_state(0),
+        ObState {
+            bids: vec![
+                (Tick(26), vec![(Lots(8), Date::from_ymd(2020, 02, 03).and_hms(12, 03, 05))]),
+            ],
+            asks: vec![
+                (
+                    Tick(28),
+                    vec![
+                        (Lots(6), Date::from_ymd(2020, 02, 03).and_hms(12, 08, 11)),
+                        (Lots(3), Date::from_ymd(2020, 02, 03).and_hms(12, 08, 14)),
+                    ]
+                ),
+            ],
+        }
+    );
+    assert_eq!(order_book.best_bid, Tick(26));
+    assert_eq!(order_book.best_ask, Tick(28));
+}
+
+#[test]
+fn test_change_tick_size_rebucketing()
+{
+    let mut order_book = default_example::<false>();
+    assert_eq!(
+        order_book.change_tick_size(Tick(2), OffGridOrderPolicy::Rebucket),
+        [
+            (OrderID(0), Sell, Tick(27), Tick(28), Lots(3)),
+            (OrderID(1), Buy, Tick(23), Tick(22), Lots(4)),
+            (OrderID(3), Buy, Tick(23), Tick(22), Lots(44)),
+            (OrderID(4), Sell, Tick(29), Tick(30), Lots(126)),
+            (OrderID(6), Sell, Tick(29), Tick(30), Lots(8)),
+        ].map(
+            |(id, direction, old_price, new_price, size)| TickSizeChangeEvent::Repriced {
+                id,
+                direction,
+                old_price,
+                new_price,
+                size,
+                old_displayed_size: size,
+                new_displayed_size: size,
+            }
+        )
+    );
+    assert_eq!(
+        order_book.get_ob_state(0),
+        ObState {
+            bids: vec![
+                (Tick(26), vec![(Lots(8), Date::from_ymd(2020, 02, 03).and_hms(12, 03, 05))]),
+                (
+                    Tick(22),
+                    vec![
+                        (Lots(4), Date::from_ymd(2020, 02, 03).and_hms(12, 03, 04)),
+                        (Lots(44), Date::from_ymd(2020, 02, 03).and_hms(12, 08, 04)),
+                    ]
+                ),
+            ],
+            asks: vec![
+                (
+                    Tick(28),
+                    vec![
+                        (Lots(6), Date::from_ymd(2020, 02, 03).and_hms(12, 08, 11)),
+                        (Lots(3), Date::from_ymd(2020, 02, 03).and_hms(12, 08, 14)),
+                        (Lots(3), Date::from_ymd(2020, 02, 03).and_hms(07, 00, 00)),
+                    ]
+                ),
+                (
+                    Tick(30),
+                    vec![
+                        (Lots(126), Date::from_ymd(2020, 02, 03).and_hms(12, 08, 09)),
+                        (Lots(8), Date::from_ymd(2020, 02, 03).and_hms(12, 08, 11)),
+                    ]
+                ),
+            ],
+        }
+    );
+    assert_eq!(order_book.best_bid, Tick(26));
+    assert_eq!(order_book.best_ask, Tick(28));
+    assert_eq!(order_book.change_tick_size(Tick(2), OffGridOrderPolicy::Rebucket), [])
+}
+
 #[test]
 fn test_insert_real_sell_market_order()
 {
@@ -1332,6 +1445,106 @@ fn test_insert_dummy_buy_limit_order_bid_overflow()
     assert_eq!(order_book.best_ask, Tick(27))
 }
 
+#[test]
+fn test_reduce_limit_order()
+{
+    let mut order_book = default_example::<false>();
+
+    assert_eq!(
+        order_book.reduce_limit_order(OrderID(5), Lots(4)),
+        Ok((
+            LimitOrder {
+                id: OrderID(5),
+                size: Lots(6),
+                is_dummy: false,
+                dt: Date::from_ymd(2020, 02, 03).and_hms(12, 08, 11),
+            },
+            Sell,
+            Tick(28)
+        ))
+    );
+    assert_eq!(
+        order_book.get_ob_state(1).asks,
+        vec![
+            (
+                Tick(27),
+                vec![(Lots(3), Date::from_ymd(2020, 02, 03).and_hms(07, 00, 00))]
+            ),
+        ]
+    );
+    assert_eq!(order_book.cancel_limit_order(OrderID(0)).map(|(_, _, price)| price), Ok(Tick(27)));
+    // Reduced order keeps its place in the queue
+    assert_eq!(
+        order_book.get_ob_state(1).asks,
+        vec![
+            (
+                Tick(28),
+                vec![
+                    (Lots(2), Date::from_ymd(2020, 02, 03).and_hms(12, 08, 11)),
+                    (Lots(3), Date::from_ymd(2020, 02, 03).and_hms(12, 08, 14)),
+                ]
+            ),
+        ]
+    );
+
+    assert_eq!(
+        order_book.reduce_limit_order(OrderID(2), Lots(8)),
+        Ok((
+            LimitOrder {
+                id: OrderID(2),
+                size: Lots(8),
+                is_dummy: false,
+                dt: Date::from_ymd(2020, 02, 03).and_hms(12, 03, 05),
+            },
+            Buy,
+            Tick(26)
+        ))
+    );
+    assert_eq!(order_book.best_bid, Tick(23));
+    assert_eq!(order_book.reduce_limit_order(OrderID(2), Lots(1)), Err(NoSuchID))
+}
+
+#[test]
+fn test_iter_levels()
+{
+    let mut order_book = default_example::<false>();
+    default_example_dummies(&mut order_book);
+    assert_eq!(
+        order_book.iter_bids()
+            .map(|(price, level)| (price, level.map(|order| order.id).collect()))
+            .collect::<Vec<(_, Vec<_>)>>(),
+        vec![
+            (Tick(26), vec![OrderID(2), OrderID(8)]),
+            (Tick(23), vec![OrderID(1), OrderID(3)]),
+        ]
+    );
+    assert_eq!(
+        order_book.iter_asks()
+            .map(|(price, level)| (price, level.map(|order| order.id).collect()))
+            .collect::<Vec<(_, Vec<_>)>>(),
+        vec![
+            (Tick(27), vec![OrderID(0), OrderID(9)]),
+            (Tick(28), vec![OrderID(5), OrderID(7)]),
+            (Tick(29), vec![OrderID(4), OrderID(6)]),
+        ]
+    );
+    let level_at = |order_book: &OrderBook, price| order_book.level_at(price).map(
+        |(direction, level)| (direction, level.map(|order| order.id).collect::<Vec<_>>())
+    );
+    assert_eq!(level_at(&order_book, Tick(28)), Some((Sell, vec![OrderID(5), OrderID(7)])));
+    assert_eq!(level_at(&order_book, Tick(23)), Some((Buy, vec![OrderID(1), OrderID(3)])));
+    assert_eq!(level_at(&order_book, Tick(24)), None);
+    assert_eq!(level_at(&order_book, Tick(30)), None);
+
+    order_book.cancel_limit_order(OrderID(5)).unwrap();
+    order_book.cancel_limit_order(OrderID(7)).unwrap();
+    assert_eq!(level_at(&order_book, Tick(28)), None);
+    assert_eq!(
+        order_book.iter_asks().map(|(price, _)| price).collect::<Vec<_>>(),
+        vec![Tick(27), Tick(29)]
+    )
+}
+
 #[test]
 fn test_cancel_limit_order()
 {
@@ -1505,4 +1718,240 @@ fn test_cancel_limit_order()
         order_book.cancel_limit_order(OrderID(52557)),
         Err(NoSuchID)
     );
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_microprice_and_imbalance()
+{
+    let dt = Date::from_ymd_opt(2024, 1, 2).unwrap().and_hms_opt(10, 0, 0).unwrap();
+    let state = ObState {
+        bids: vec![
+            (Tick(99), vec![(Lots(1), dt), (Lots(2), dt)]),
+            (Tick(97), vec![(Lots(4), dt)]),
+        ],
+        asks: vec![
+            (Tick(100), vec![(Lots(1), dt)]),
+            (Tick(102), vec![(Lots(6), dt)]),
+        ],
+    };
+    assert_eq!(state.get_microprice(), Some(99.75));
+    assert_eq!(state.get_top_imbalance(), Some(0.5));
+    assert_eq!(state.get_depth_imbalance(1.0, 0), Some(0.0));
+    assert_eq!(state.get_depth_imbalance(0.5, 0), Some(1.0 / 9.0));
+    assert_eq!(state.get_depth_imbalance(0.5, 1), state.get_top_imbalance());
+    assert_eq!(ObState { bids: vec![], asks: state.asks }.get_microprice(), None);
+
+    let mut order_book = default_example::<false>();
+    default_example_dummies(&mut order_book);
+    let state = order_book.get_ob_state(0);
+    assert_eq!(order_book.get_microprice(), state.get_microprice());
+    assert_eq!(order_book.get_top_imbalance(), state.get_top_imbalance());
+    assert_eq!(order_book.get_depth_imbalance(0.5, 2), state.get_depth_imbalance(0.5, 2))
+}
+
+#[test]
+fn test_journal()
+{
+    let dt = Date::from_ymd_opt(2024, 1, 2).unwrap().and_hms_opt(10, 0, 0).unwrap();
+    let mut order_book = OrderBook::new().with_journal();
+    insert_limit_order::<false, true>(&mut order_book, dt, OrderID(1), Tick(100), Lots(5));
+    insert_limit_order::<true, true>(&mut order_book, dt, OrderID(2), Tick(98), Lots(5));
+    insert_limit_order::<false, false>(&mut order_book, dt, OrderID(3), Tick(105), Lots(4));
+    insert_market_order::<false, false>(&mut order_book, Lots(2));
+    insert_market_order::<false, false>(&mut order_book, Lots(3));
+    order_book.cancel_limit_order(OrderID(3)).unwrap();
+    assert_eq!(
+        order_book.drain_journal(),
+        vec![
+            ObDelta::Add { order_id: OrderID(1), direction: Buy, price: Tick(100), size: Lots(5) },
+            ObDelta::Add { order_id: OrderID(3), direction: Sell, price: Tick(105), size: Lots(4) },
+            ObDelta::Modify { order_id: OrderID(1), size: Lots(3) },
+            ObDelta::Delete { order_id: OrderID(1) },
+            ObDelta::Delete { order_id: OrderID(3) },
+        ]
+    );
+    assert_eq!(order_book.drain_journal(), vec![]);
+
+    order_book.clear();
+    assert_eq!(order_book.drain_journal(), vec![ObDelta::Clear]);
+    assert_eq!(OrderBook::new().drain_journal(), vec![])
+}
+
+#[test]
+fn test_iceberg_and_hidden_orders()
+{
+    let dt = Date::from_ymd_opt(2024, 1, 2).unwrap().and_hms_opt(10, 0, 0).unwrap();
+    let event = |size, kind| OrderBookEvent { size: Lots(size), price: Tick(100), kind };
+    let mut order_book = OrderBook::new();
+    order_book.insert_iceberg_order::<_, false>(
+        dt, OrderID(1), Tick(100), Lots(10), Lots(3), |_| unreachable!(),
+    );
+    order_book.insert_iceberg_order::<_, false>(
+        dt, OrderID(2), Tick(100), Lots(5), Lots(0), |_| unreachable!(),
+    );
+    insert_limit_order::<false, false>(&mut order_book, dt, OrderID(3), Tick(100), Lots(2));
+    assert_eq!(
+        order_book.get_ob_state(0),
+        ObState { bids: vec![], asks: vec![(Tick(100), vec![(Lots(3), dt), (Lots(2), dt)])] }
+    );
+    assert_eq!(order_book.get_limit_order(OrderID(1)), Some((Sell, Tick(100), Lots(10))));
+    assert_eq!(order_book.get_displayed_size(OrderID(1)), Some(Lots(3)));
+    assert_eq!(order_book.get_displayed_size(OrderID(2)), Some(Lots(0)));
+    assert_eq!(order_book.get_displayed_size(OrderID(3)), Some(Lots(2)));
+    assert!(order_book.is_hidden(OrderID(2)));
+
+    // Hidden order is executed before the displayed one submitted after it
+    assert_eq!(
+        insert_market_order::<false, true>(&mut order_book, Lots(4)),
+        vec![
+            event(3, OldOrderPartiallyExecuted(OrderID(1))),
+            event(1, OldHiddenOrderPartiallyExecuted(OrderID(2))),
+            event(3, OldIcebergOrderRefilled(OrderID(1))),
+            event(4, NewOrderExecuted),
+        ]
+    );
+    // Next peak of the iceberg loses the queue priority
+    assert_eq!(
+        order_book.get_ob_state(0),
+        ObState { bids: vec![], asks: vec![(Tick(100), vec![(Lots(2), dt), (Lots(3), dt)])] }
+    );
+    assert_eq!(order_book.get_reserve_size(OrderID(1)), Lots(4));
+
+    assert_eq!(
+        insert_market_order::<false, true>(&mut order_book, Lots(100)),
+        vec![
+            event(4, OldHiddenOrderExecuted(OrderID(2))),
+            event(2, OldOrderExecuted(OrderID(3))),
+            event(3, OldOrderPartiallyExecuted(OrderID(1))),
+            event(3, OldIcebergOrderRefilled(OrderID(1))),
+            event(3, OldOrderPartiallyExecuted(OrderID(1))),
+            event(1, OldIcebergOrderRefilled(OrderID(1))),
+            event(1, OldOrderExecuted(OrderID(1))),
+            event(13, NewOrderPartiallyExecuted),
+        ]
+    );
+    assert_eq!(order_book.get_ob_state(0), ObState { bids: vec![], asks: vec![] });
+    assert!(!order_book.contains_limit_order(OrderID(1)));
+    assert_eq!(order_book.get_displayed_size(OrderID(1)), None)
+}
+
+#[test]
+fn test_executable_size()
+{
+    let dt = Date::from_ymd_opt(2024, 1, 2).unwrap().and_hms_opt(10, 0, 0).unwrap();
+    let mut order_book = OrderBook::new();
+    order_book.insert_iceberg_order::<_, false>(
+        dt, OrderID(1), Tick(100), Lots(10), Lots(3), |_| unreachable!(),
+    );
+    order_book.insert_iceberg_order::<_, false>(
+        dt, OrderID(2), Tick(101), Lots(5), Lots(0), |_| unreachable!(),
+    );
+    insert_limit_order::<true, false>(&mut order_book, dt, OrderID(3), Tick(101), Lots(7));
+    insert_limit_order::<false, true>(&mut order_book, dt, OrderID(4), Tick(98), Lots(2));
+
+    assert_eq!(order_book.get_executable_size::<true>(Tick(99), Lots(20)), Lots(0));
+    assert_eq!(order_book.get_executable_size::<true>(Tick(100), Lots(20)), Lots(10));
+    assert_eq!(order_book.get_executable_size::<true>(Tick(101), Lots(20)), Lots(15));
+    assert_eq!(order_book.get_executable_size::<true>(Tick(101), Lots(12)), Lots(12));
+    assert_eq!(order_book.get_executable_size::<false>(Tick(98), Lots(20)), Lots(2));
+    assert_eq!(order_book.get_executable_size::<false>(Tick(99), Lots(20)), Lots(0))
+}
+
+#[test]
+fn test_auction()
+{
+    let dt = Date::from_ymd_opt(2024, 1, 2).unwrap().and_hms_opt(10, 0, 0).unwrap();
+    let event = |size, kind| OrderBookEvent { size: Lots(size), price: Tick(101), kind };
+    let mut order_book = OrderBook::new();
+    let mut insert = |id, price, buy, size| if buy {
+        order_book.insert_limit_order_without_matching::<false, true>(dt, id, price, size)
+    } else {
+        order_book.insert_limit_order_without_matching::<false, false>(dt, id, price, size)
+    };
+    insert(OrderID(1), Tick(102), true, Lots(5));
+    insert(OrderID(2), Tick(101), true, Lots(10));
+    insert(OrderID(3), Tick(99), true, Lots(4));
+    insert(OrderID(4), Tick(100), false, Lots(8));
+    insert(OrderID(5), Tick(101), false, Lots(6));
+    assert_eq!(order_book.get_auction_price(None), Some((Tick(101), Lots(14))));
+
+    let mut events = Vec::new();
+    order_book.uncross(Tick(101), |id, event| events.push((id, event)));
+    assert_eq!(
+        events,
+        vec![
+            (OrderID(1), event(5, OldOrderPartiallyExecuted(OrderID(4)))),
+            (OrderID(1), event(5, NewOrderExecuted)),
+            (OrderID(2), event(3, OldOrderExecuted(OrderID(4)))),
+            (OrderID(2), event(3, NewOrderPartiallyExecuted)),
+            (OrderID(2), event(6, OldOrderExecuted(OrderID(5)))),
+            (OrderID(2), event(6, NewOrderPartiallyExecuted)),
+        ]
+    );
+    assert_eq!(
+        order_book.get_ob_state(0),
+        ObState {
+            bids: vec![(Tick(101), vec![(Lots(1), dt)]), (Tick(99), vec![(Lots(4), dt)])],
+            asks: vec![],
+        }
+    );
+    assert_eq!(order_book.get_auction_price(None), None)
+}
+
+#[test]
+fn test_dummy_matching()
+{
+    let dt = Date::from_ymd_opt(2024, 1, 2).unwrap().and_hms_opt(10, 0, 0).unwrap();
+    let event = |size, kind| OrderBookEvent { size: Lots(size), price: Tick(100), kind };
+    let insert_asks = |order_book: &mut OrderBook| {
+        insert_limit_order::<false, false>(order_book, dt, OrderID(1), Tick(100), Lots(3));
+        insert_limit_order::<true, false>(order_book, dt, OrderID(2), Tick(100), Lots(4));
+    };
+
+    let mut order_book = OrderBook::new();
+    assert!(!order_book.is_matching_dummy_with_dummy());
+    insert_asks(&mut order_book);
+    assert_eq!(
+        insert_market_order::<true, true>(&mut order_book, Lots(5)),
+        vec![event(3, NewOrderPartiallyExecuted)]
+    );
+    assert!(order_book.contains_limit_order(OrderID(2)));
+
+    let mut order_book = OrderBook::new().with_dummy_matching();
+    assert!(order_book.is_matching_dummy_with_dummy());
+    insert_asks(&mut order_book);
+    // Fills by the dummy orders are not mixed with the ones by the real orders
+    assert_eq!(
+        insert_market_order::<true, true>(&mut order_book, Lots(5)),
+        vec![
+            event(2, OldGhostOrderPartiallyExecuted(OrderID(2))),
+            event(3, NewOrderPartiallyExecuted),
+            event(2, NewGhostOrderExecuted),
+        ]
+    );
+    assert_eq!(
+        insert_market_order::<true, true>(&mut order_book, Lots(10)),
+        vec![
+            event(2, OldGhostOrderExecuted(OrderID(2))),
+            event(3, NewOrderPartiallyExecuted),
+            event(2, NewGhostOrderPartiallyExecuted),
+        ]
+    );
+    insert_limit_order::<true, false>(&mut order_book, dt, OrderID(3), Tick(100), Lots(4));
+    assert_eq!(
+        insert_limit_order::<true, true>(&mut order_book, dt, OrderID(4), Tick(100), Lots(4)),
+        vec![
+            event(1, OldGhostOrderPartiallyExecuted(OrderID(3))),
+            event(3, NewOrderPartiallyExecuted),
+            event(1, NewGhostOrderExecuted),
+        ]
+    );
+    assert!(!order_book.contains_limit_order(OrderID(4)));
+    assert!(!order_book.contains_limit_order(OrderID(2)));
+    assert!(order_book.contains_limit_order(OrderID(3)));
+    // Real orders are not affected by the dummy ones
+    assert_eq!(
+        order_book.get_ob_state(0),
+        ObState { bids: vec![], asks: vec![(Tick(100), vec![(Lots(3), dt)])] }
+    )
+}
@@ -1,17 +1,26 @@
 use {
     crate::{
         concrete::{
+            exchange::{admin::AdminAction, auction::TradingPhase},
             input::one_tick::OneTickTradedPairReader,
             message_protocol::{
                 exchange::reply::{
                     BasicExchangeToReplay,
                     BasicExchangeToReplayReply,
                     ExchangeEventNotification,
+                    OrderCancelled,
+                    OrderExecuted,
+                    OrderPartiallyExecuted,
+                },
+                replay::request::{
+                    BasicReplayRequest,
+                    BasicReplayToBroker,
+                    BasicReplayToBrokerRequest,
+                    BasicReplayToExchange,
                 },
-                replay::request::{BasicReplayRequest, BasicReplayToExchange},
             },
             traded_pair::{settlement::GetSettlementLag, TradedPair},
-            types::{OrderID, TickSize},
+            types::TickSize,
         },
         interface::{
             message::{
@@ -28,22 +37,24 @@ use {
             DateTime,
             Duration,
             Id,
-            NeverType,
+            IdAllocator,
             Nothing,
             TimeSync,
         },
-        utils::queue::LessElementBinaryHeap,
+        utils::{collections::{HashMap, HashSet}, queue::LessElementBinaryHeap},
     },
     rand::Rng,
     std::{
         cmp::Reverse,
-        collections::{HashMap, HashSet},
         io::Write,
         marker::PhantomData,
         num::NonZeroU64,
     },
 };
 
+/// Concrete implementors of the [`GetNextObSnapshotDelay`].
+pub mod snapshot_delay;
+
 /// Trait for OrderBook snapshot broadcasting schedulers.
 pub trait GetNextObSnapshotDelay<ExchangeID, Symbol, Settlement>
     where ExchangeID: Id,
@@ -68,6 +79,24 @@ pub trait GetNextObSnapshotDelay<ExchangeID, Symbol, Settlement>
         traded_pair: TradedPair<Symbol, Settlement>,
         rng: &mut impl Rng,
         current_dt: DateTime) -> Option<(NonZeroU64, usize)>;
+
+    /// Called whenever the [`OneTickReplay`] submits the order event of the traded pair,
+    /// e.g. to adapt the delays to the activity of the order book.
+    /// Does nothing by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `exchange_id` — Exchange the event is submitted to.
+    /// * `traded_pair` — Traded pair of the event.
+    /// * `datetime` — Datetime of the event.
+    fn on_ob_event(
+        &mut self,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        datetime: DateTime)
+    {
+        let _ = (exchange_id, traded_pair, datetime);
+    }
 }
 
 /// Reads and processes OneTick csv-files for multiple traded pairs.
@@ -85,7 +114,7 @@ pub struct OneTickReplay<BrokerID, ExchangeID, Symbol, ObSnapshotDelay, Settleme
             ReplayAction<
                 Nothing,
                 BasicReplayToExchange<ExchangeID, Symbol, Settlement>,
-                NeverType<BrokerID>
+                BasicReplayToBroker<BrokerID, ExchangeID, Symbol, Settlement>
             >,
             i64
         )
@@ -93,7 +122,8 @@ pub struct OneTickReplay<BrokerID, ExchangeID, Symbol, ObSnapshotDelay, Settleme
 
     active_traded_pairs: HashSet<(ExchangeID, TradedPair<Symbol, Settlement>)>,
 
-    next_order_id: OrderID,
+    /// Allocator of the IDs of the generated orders, shared with the traders by the kernel.
+    id_allocator: IdAllocator,
 
     ob_snapshot_delay_scheduler: ObSnapshotDelay,
 }
@@ -106,6 +136,16 @@ pub struct ExchangeSession<ExchangeID: Id> {
     pub close_dt: DateTime,
 }
 
+#[derive(Copy, Clone)]
+/// Unplanned exchange downtime window.
+pub struct ExchangeDowntime<ExchangeID: Id> {
+    pub exchange_id: ExchangeID,
+    pub start_dt: DateTime,
+    pub end_dt: DateTime,
+    /// Whether to cancel the resting broker limit orders when the exchange recovers.
+    pub cancel_resting_orders: bool,
+}
+
 #[derive(Copy, Clone)]
 /// Traded pair lifetime.
 pub struct TradedPairLifetime<ExchangeID, Symbol, Settlement>
@@ -226,12 +266,12 @@ OneTickReplay<BrokerID, ExchangeID, Symbol, ObSnapshotDelay, Settlement>
                     }
                 }
         );
-        let mut next_order_id = OrderID(0);
+        let id_allocator = IdAllocator::default();
         let (first_events, traded_pair_readers): (Vec<_>, _) = traded_pair_readers.into_iter()
             .enumerate()
             .map(
                 |(i, mut pair_reader)| {
-                    let first_event = pair_reader.next(&mut next_order_id).unwrap_or_else(
+                    let first_event = pair_reader.next(&id_allocator).unwrap_or_else(
                         || panic!("Traded pair reader {i} is empty")
                     );
                     (Reverse((first_event, i as i64)), pair_reader)
@@ -251,8 +291,172 @@ OneTickReplay<BrokerID, ExchangeID, Symbol, ObSnapshotDelay, Settlement>
             traded_pair_readers,
             ob_snapshot_delay_scheduler,
             active_traded_pairs: Default::default(),
-            next_order_id,
+            id_allocator,
+        }
+    }
+
+    /// Schedules the administrative action changing the trading rules of the exchange.
+    /// The exchange validates the action before applying it. Rejected actions cause a panic.
+    ///
+    /// # Arguments
+    ///
+    /// * `exchange_id` — Exchange to apply the action at.
+    /// * `datetime` — Datetime the action takes effect at.
+    /// * `action` — Administrative action.
+    pub fn with_admin_action(
+        mut self,
+        exchange_id: ExchangeID,
+        datetime: DateTime,
+        action: AdminAction<Symbol, Settlement>) -> Self
+    {
+        if datetime < self.current_dt {
+            panic!(
+                "Admin action datetime {datetime} is less than start_dt {}",
+                self.current_dt
+            )
+        }
+        let action = ReplayAction {
+            datetime,
+            content: ReplayActionKind::ReplayToExchange(
+                BasicReplayToExchange {
+                    exchange_id,
+                    content: BasicReplayRequest::AdminAction(action),
+                }
+            ),
+        };
+        self.action_queue.push((action, -1));
+        self
+    }
+
+    /// Schedules the switch of the exchange to the trading phase, e.g. to start or end
+    /// the auction call regardless of the session schedule of the exchange.
+    ///
+    /// # Arguments
+    ///
+    /// * `exchange_id` — Exchange to switch.
+    /// * `datetime` — Datetime the phase begins at.
+    /// * `phase` — Trading phase.
+    pub fn with_trading_phase(
+        mut self,
+        exchange_id: ExchangeID,
+        datetime: DateTime,
+        phase: TradingPhase) -> Self
+    {
+        if datetime < self.current_dt {
+            panic!(
+                "Trading phase datetime {datetime} is less than start_dt {}",
+                self.current_dt
+            )
+        }
+        let action = ReplayAction {
+            datetime,
+            content: ReplayActionKind::ReplayToExchange(
+                BasicReplayToExchange {
+                    exchange_id,
+                    content: BasicReplayRequest::SetTradingPhase(phase),
+                }
+            ),
+        };
+        self.action_queue.push((action, -1));
+        self
+    }
+
+    /// Schedules the downtime windows of the exchanges.
+    /// While down, the exchange rejects all the broker requests,
+    /// whereas the history keeps being replayed.
+    ///
+    /// # Arguments
+    ///
+    /// * `downtimes` — Downtime windows.
+    ///                 Windows of the same exchange should be in the ascending order
+    ///                 and should not overlap.
+    pub fn with_downtimes(
+        mut self,
+        downtimes: impl IntoIterator<Item=ExchangeDowntime<ExchangeID>>) -> Self
+    {
+        let mut prev_dt: HashMap<ExchangeID, DateTime> = Default::default();
+        for ExchangeDowntime { exchange_id, start_dt, end_dt, cancel_resting_orders } in downtimes {
+            let prev_dt = prev_dt.entry(exchange_id).or_insert(self.current_dt);
+            if start_dt < *prev_dt {
+                panic!(
+                    "Exchange {exchange_id} downtime start datetime {start_dt} is less than \
+                    start_dt or the end of the previous downtime {prev_dt}"
+                )
+            }
+            if end_dt <= start_dt {
+                panic!(
+                    "Exchange {exchange_id} downtime end datetime {end_dt} is not greater than \
+                    the corresponding start datetime {start_dt}"
+                )
+            }
+            *prev_dt = end_dt;
+            let action = |datetime, content| ReplayAction {
+                datetime,
+                content: ReplayActionKind::ReplayToExchange(
+                    BasicReplayToExchange { exchange_id, content }
+                ),
+            };
+            self.action_queue.push(
+                (action(start_dt, BasicReplayRequest::StartDowntime { cancel_resting_orders }), -1)
+            );
+            self.action_queue.push((action(end_dt, BasicReplayRequest::EndDowntime), -1))
+        }
+        self
+    }
+
+    /// Schedules the recall of the borrow of the traded pair by the broker.
+    /// Traders with the short positions are notified and have the `window` to cover them.
+    /// Then the broker buys in the remaining short positions with the forced market orders.
+    /// The broker should book the fills to the accounts of the traders.
+    ///
+    /// # Arguments
+    ///
+    /// * `broker_id` — Broker recalling the borrow.
+    /// * `exchange_id` — Exchange the traded pair is listed at.
+    /// * `traded_pair` — Traded pair.
+    /// * `datetime` — Datetime the recall is announced at.
+    /// * `window` — Time the traders have to cover their short positions.
+    pub fn with_borrow_recall(
+        mut self,
+        broker_id: BrokerID,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        datetime: DateTime,
+        window: Duration) -> Self
+    {
+        if datetime < self.current_dt {
+            panic!(
+                "Borrow recall datetime {datetime} is less than start_dt {}",
+                self.current_dt
+            )
+        }
+        if window < Duration::zero() {
+            panic!("Borrow recall window should not be negative. Got {window}")
         }
+        let deadline = datetime + window;
+        let recall = ReplayAction {
+            datetime,
+            content: ReplayActionKind::ReplayToBroker(
+                BasicReplayToBroker {
+                    broker_id,
+                    exchange_id,
+                    content: BasicReplayToBrokerRequest::RecallBorrow { traded_pair, deadline },
+                }
+            ),
+        };
+        let buy_in = ReplayAction {
+            datetime: deadline,
+            content: ReplayActionKind::ReplayToBroker(
+                BasicReplayToBroker {
+                    broker_id,
+                    exchange_id,
+                    content: BasicReplayToBrokerRequest::BuyIn(traded_pair),
+                }
+            ),
+        };
+        self.action_queue.push((recall, -1));
+        self.action_queue.push((buy_in, -1));
+        self
     }
 }
 
@@ -282,17 +486,20 @@ for OneTickReplay<BrokerID, ExchangeID, Symbol, ObSnapshotDelay, Settlement>
     type Item = ReplayAction<
         Nothing,
         BasicReplayToExchange<ExchangeID, Symbol, Settlement>,
-        NeverType<BrokerID>
+        BasicReplayToBroker<BrokerID, ExchangeID, Symbol, Settlement>
     >;
 
     fn next(&mut self) -> Option<Self::Item>
     {
         if let Some((action, reader_idx)) = self.action_queue.pop() {
             if reader_idx != -1 {
-                if let Some(next_action) = self.traded_pair_readers
+                let reader = self.traded_pair_readers
                     .get_mut(reader_idx as usize)
-                    .unwrap_or_else(|| unreachable!("Index {} is out of bounds", reader_idx))
-                    .next(&mut self.next_order_id)
+                    .unwrap_or_else(|| unreachable!("Index {} is out of bounds", reader_idx));
+                self.ob_snapshot_delay_scheduler.on_ob_event(
+                    reader.exchange_id, reader.traded_pair, action.datetime,
+                );
+                if let Some(next_action) = reader.next(&self.id_allocator)
                 {
                     self.action_queue.push((next_action, reader_idx))
                 }
@@ -320,7 +527,7 @@ for OneTickReplay<BrokerID, ExchangeID, Symbol, ObSnapshotDelay, Settlement>
     type B2R = Nothing;
     type R2R = Nothing;
     type R2E = BasicReplayToExchange<ExchangeID, Symbol, Settlement>;
-    type R2B = NeverType<BrokerID>;
+    type R2B = BasicReplayToBroker<BrokerID, ExchangeID, Symbol, Settlement>;
 
     fn wakeup(
         &mut self,
@@ -436,11 +643,29 @@ for OneTickReplay<BrokerID, ExchangeID, Symbol, ObSnapshotDelay, Settlement>
                     )
                 }
             }
+            BasicExchangeToReplayReply::OrderPartiallyExecuted(
+                OrderPartiallyExecuted { traded_pair, order_id, size, .. }
+            ) => {
+                self.traded_pair_readers.iter_mut()
+                    .filter(|reader| reader.exchange_id == exchange_id
+                        && reader.traded_pair == traded_pair)
+                    .for_each(|reader| reader.on_order_partially_executed(order_id, size))
+            }
+            BasicExchangeToReplayReply::OrderExecuted(OrderExecuted { traded_pair, order_id, .. })
+            | BasicExchangeToReplayReply::OrderCancelled(
+                OrderCancelled { traded_pair, order_id, .. }
+            ) => {
+                self.traded_pair_readers.iter_mut()
+                    .filter(|reader| reader.exchange_id == exchange_id
+                        && reader.traded_pair == traded_pair)
+                    .for_each(|reader| reader.on_order_removed(order_id))
+            }
             BasicExchangeToReplayReply::OrderPlacementDiscarded(_) |
             BasicExchangeToReplayReply::CannotOpenExchange(_) |
             BasicExchangeToReplayReply::CannotStartTrades(_) |
             BasicExchangeToReplayReply::CannotCloseExchange(_) |
-            BasicExchangeToReplayReply::CannotStopTrades(_) => {
+            BasicExchangeToReplayReply::CannotStopTrades(_) |
+            BasicExchangeToReplayReply::CannotApplyAdminAction(_) => {
                 panic!("{} :: {reply:?}. Exchange {exchange_id}", self.current_dt)
             }
             _ => {}
@@ -458,6 +683,10 @@ for OneTickReplay<BrokerID, ExchangeID, Symbol, ObSnapshotDelay, Settlement>
             self.current_dt
         )
     }
+
+    fn get_id_allocator(&self) -> Option<&IdAllocator> {
+        Some(&self.id_allocator)
+    }
 }
 
 /// [`Replay`] that is doing nothing.
@@ -576,5 +805,5 @@ pub type BasicVoidReplay<BrokerID, ExchangeID, Symbol, Settlement> = VoidReplay<
     Nothing,
     Nothing,
     BasicReplayToExchange<ExchangeID, Symbol, Settlement>,
-    NeverType<BrokerID>
+    BasicReplayToBroker<BrokerID, ExchangeID, Symbol, Settlement>
 >;
\ No newline at end of file
@@ -0,0 +1,165 @@
+use {
+    crate::{
+        concrete::{
+            replay::GetNextObSnapshotDelay,
+            traded_pair::{settlement::GetSettlementLag, TradedPair},
+        },
+        types::{DateTime, Id},
+        utils::collections::HashMap,
+    },
+    rand::Rng,
+    std::{collections::hash_map::Entry::{Occupied, Vacant}, num::NonZeroU64},
+};
+
+#[derive(Clone)]
+/// OB-snapshot delay scheduler that requests the snapshots more often
+/// during the bursts of the order book activity and backs off in the quiet periods,
+/// so that the number of the snapshots is reduced with little loss of information.
+///
+/// Activity is the number of the order events the [`OneTickReplay`](super::OneTickReplay)
+/// has submitted for the traded pair since the previous snapshot.
+/// Next delay is scaled so that the number of the events per snapshot approaches the target one
+/// provided the rate of the events persists. After the snapshot with no events
+/// the delay is doubled. Delays are kept within the bounds.
+///
+/// # Examples
+///
+/// ```
+/// use {
+///     rand::{rngs::StdRng, SeedableRng},
+///     std::num::NonZeroU64,
+///     trading_backtester::{
+///         concrete::{
+///             replay::{snapshot_delay::AdaptiveObSnapshotDelay, GetNextObSnapshotDelay},
+///             traded_pair::{settlement::concrete::SpotSettlement, Base, TradedPair},
+///         },
+///         types::Date,
+///     },
+/// };
+///
+/// let pair = TradedPair {
+///     quoted_asset: Base::new("USD").into(),
+///     settlement_asset: Base::new("RUB").into(),
+///     settlement_determinant: SpotSettlement,
+/// };
+/// let dt = Date::from_ymd_opt(2022, 1, 3).unwrap().and_hms_opt(10, 0, 0).unwrap();
+/// let mut rng = StdRng::seed_from_u64(0);
+/// let mut scheduler = AdaptiveObSnapshotDelay::new(
+///     NonZeroU64::new(100).unwrap(),
+///     NonZeroU64::new(1000).unwrap(),
+///     1,
+/// );
+/// let mut next_delay = |scheduler: &mut AdaptiveObSnapshotDelay<_, _, _>| {
+///     scheduler.get_ob_snapshot_delay("MOEX", pair, &mut rng, dt).unwrap().0.get()
+/// };
+///
+/// assert_eq!(next_delay(&mut scheduler), 100);
+/// // Quiet period
+/// assert_eq!(next_delay(&mut scheduler), 200);
+/// assert_eq!(next_delay(&mut scheduler), 400);
+/// // Four events within 400 nanoseconds
+/// (0..4).for_each(|_| scheduler.on_ob_event("MOEX", pair, dt));
+/// assert_eq!(next_delay(&mut scheduler), 100);
+/// ```
+pub struct AdaptiveObSnapshotDelay<ExchangeID, Symbol, Settlement>
+    where ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    min_delay: NonZeroU64,
+    max_delay: NonZeroU64,
+    max_levels: usize,
+    events_per_snapshot: NonZeroU64,
+    /// Current delay and the number of the events since the previous snapshot.
+    activity: HashMap<(ExchangeID, TradedPair<Symbol, Settlement>), (u64, u64)>,
+}
+
+impl<ExchangeID, Symbol, Settlement> AdaptiveObSnapshotDelay<ExchangeID, Symbol, Settlement>
+    where ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    /// Creates a new instance of the `AdaptiveObSnapshotDelay` targeting one event per snapshot.
+    /// First snapshot of the traded pair is requested after the minimum delay.
+    ///
+    /// # Arguments
+    ///
+    /// * `min_delay` — Minimum delay between the snapshots, in nanoseconds.
+    /// * `max_delay` — Maximum delay between the snapshots, in nanoseconds.
+    ///   Should not be less than the `min_delay`.
+    /// * `max_levels` — Maximum number of the order book levels to broadcast.
+    pub fn new(min_delay: NonZeroU64, max_delay: NonZeroU64, max_levels: usize) -> Self {
+        if min_delay > max_delay {
+            panic!(
+                "Minimum OB-snapshot delay should not exceed the maximum one. \
+                Got {min_delay} and {max_delay}"
+            )
+        }
+        Self {
+            min_delay,
+            max_delay,
+            max_levels,
+            events_per_snapshot: NonZeroU64::MIN,
+            activity: Default::default(),
+        }
+    }
+
+    /// Sets the target number of the order events per snapshot.
+    /// The greater it is, the fewer snapshots are requested.
+    ///
+    /// # Arguments
+    ///
+    /// * `events_per_snapshot` — Number of the events.
+    pub fn with_events_per_snapshot(mut self, events_per_snapshot: NonZeroU64) -> Self {
+        self.events_per_snapshot = events_per_snapshot;
+        self
+    }
+}
+
+impl<ExchangeID, Symbol, Settlement> GetNextObSnapshotDelay<ExchangeID, Symbol, Settlement>
+for AdaptiveObSnapshotDelay<ExchangeID, Symbol, Settlement>
+    where ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    fn get_ob_snapshot_delay(
+        &mut self,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        _: &mut impl Rng,
+        _: DateTime) -> Option<(NonZeroU64, usize)>
+    {
+        let (min_delay, max_delay) = (self.min_delay.get(), self.max_delay.get());
+        let delay = match self.activity.entry((exchange_id, traded_pair)) {
+            Occupied(mut entry) => {
+                let (delay, num_events) = entry.get_mut();
+                let next_delay = if *num_events == 0 {
+                    delay.saturating_mul(2)
+                } else {
+                    // Events have been counted over the previous delay
+                    let scaled = *delay as u128 * self.events_per_snapshot.get() as u128
+                        / *num_events as u128;
+                    scaled.min(u64::MAX as u128) as u64
+                };
+                *delay = next_delay.clamp(min_delay, max_delay);
+                *num_events = 0;
+                *delay
+            }
+            Vacant(entry) => entry.insert((min_delay, 0)).0
+        };
+        let delay = NonZeroU64::new(delay).unwrap_or_else(|| unreachable!());
+        Some((delay, self.max_levels))
+    }
+
+    fn on_ob_event(
+        &mut self,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        _: DateTime)
+    {
+        // Events preceding the first snapshot are not counted, since they have no interval
+        if let Some((_, num_events)) = self.activity.get_mut(&(exchange_id, traded_pair)) {
+            *num_events += 1
+        }
+    }
+}
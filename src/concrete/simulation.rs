@@ -0,0 +1,496 @@
+use {
+    crate::{
+        concrete::{
+            broker::BasicBroker,
+            exchange::BasicExchange,
+            input::config::{
+                from_structs::OneTickReplayConfig,
+                from_yaml::{
+                    parse_traders_yaml,
+                    parse_yaml,
+                    TraderFactory,
+                    TraderRegistry,
+                    YamlTraderConfig,
+                },
+            },
+            message_protocol::{
+                broker::reply::BasicBrokerToTrader,
+                trader::request::BasicTraderToBroker,
+            },
+            replay::{GetNextObSnapshotDelay, OneTickReplay},
+            traded_pair::{parser::TradedPairParser, settlement::GetSettlementLag},
+            trader::{
+                subscriptions::{SubscriptionConfig, SubscriptionList},
+                warm_start::{WarmStart, WarmStartable},
+            },
+        },
+        interface::trader::Trader,
+        kernel::{KernelAgents, KernelBuilder},
+        types::{DateTime, Id, Named},
+        utils::{collections::HashMap, metadata::SimulationMetadata},
+    },
+    rand::rngs::StdRng,
+    std::{
+        collections::BTreeMap,
+        path::{Path, PathBuf},
+        str::FromStr,
+    },
+};
+
+/// Results of the simulation run by the [`SimulationBuilder`].
+pub struct SimulationSummary<TraderID, BrokerID, ExchangeID, Symbol, Settlement, T>
+    where TraderID: Id,
+          BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    /// Metadata of the run.
+    pub metadata: SimulationMetadata,
+    /// Simulation start datetime.
+    pub start_dt: DateTime,
+    /// Simulation end datetime.
+    pub end_dt: DateTime,
+    /// Traders in their final state.
+    pub traders: HashMap<TraderID, T>,
+    /// Broker in its final state, holding the analytics it has recorded,
+    /// e.g. the account ledger, the statements and the kill switch events.
+    pub broker: BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>,
+    /// Exchanges in their final state.
+    pub exchanges: HashMap<ExchangeID, BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>>,
+    /// Tags of the traders listed in the YAML-config.
+    pub trader_tags: HashMap<TraderID, Vec<String>>,
+    /// Tags of the broker.
+    pub broker_tags: Vec<String>,
+}
+
+impl<TraderID, BrokerID, ExchangeID, Symbol, Settlement, T>
+SimulationSummary<TraderID, BrokerID, ExchangeID, Symbol, Settlement, T>
+    where TraderID: Id,
+          BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    /// Returns the metadata of the run with the tags of the trader and the broker,
+    /// e.g. for the header or the sidecar of the trader output file.
+    ///
+    /// # Arguments
+    ///
+    /// * `trader_id` — Trader.
+    pub fn get_trader_metadata(&self, trader_id: TraderID) -> SimulationMetadata {
+        let trader_tags = self.trader_tags.get(&trader_id).map(Vec::as_slice).unwrap_or_default();
+        self.metadata.clone()
+            .with_tags(trader_tags.iter().cloned())
+            .with_tags(self.broker_tags.iter().cloned())
+    }
+
+    /// Groups the traders by the values of their `<key>=<value>` tags,
+    /// e.g. by the `cohort`. Traders without such a tag are omitted.
+    /// Traders within the group are sorted.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` — Tag key.
+    pub fn group_traders(&self, key: &str) -> BTreeMap<String, Vec<TraderID>> {
+        let mut groups: BTreeMap<String, Vec<TraderID>> = BTreeMap::new();
+        for (trader_id, tags) in &self.trader_tags {
+            let values = tags.iter().filter_map(
+                |tag| tag.split_once('=')
+                    .filter(|(tag_key, _)| *tag_key == key)
+                    .map(|(_, value)| value)
+            );
+            for value in values {
+                groups.entry(value.to_string()).or_default().push(*trader_id)
+            }
+        }
+        for traders in groups.values_mut() {
+            traders.sort()
+        }
+        groups
+    }
+}
+
+type Sink<TraderID, BrokerID, ExchangeID, Symbol, Settlement, T> = Box<
+    dyn FnOnce(&SimulationSummary<TraderID, BrokerID, ExchangeID, Symbol, Settlement, T>)
+>;
+
+type WarmStartHook<T> = Box<dyn Fn(&mut T)>;
+
+/// Wires the YAML-config, the [`TraderRegistry`], the agents and the output sinks
+/// into the single simulation run.
+///
+/// Exchanges, traded pairs and traders are taken from the YAML-config.
+/// Every trader is connected to the single [`BasicBroker`]
+/// and subscribed to all the traded pairs listed in the config.
+/// The broker is connected to all the [`BasicExchanges`](BasicExchange)
+/// and the market data is replayed by the [`OneTickReplay`].
+pub struct SimulationBuilder<TraderID, BrokerID, ExchangeID, Symbol, ObSnapshotDelay, Settlement, T>
+    where TraderID: Id,
+          BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          ObSnapshotDelay: GetNextObSnapshotDelay<ExchangeID, Symbol, Settlement>,
+          Settlement: GetSettlementLag
+{
+    config_path: PathBuf,
+    replay_config: OneTickReplayConfig<ExchangeID, Symbol, ObSnapshotDelay, Settlement>,
+    date_range: (DateTime, DateTime),
+    trader_configs: Vec<YamlTraderConfig<TraderID>>,
+    registry: TraderRegistry<TraderID, T>,
+    broker: BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>,
+    exchanges: Vec<BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>>,
+    subscriptions: SubscriptionList,
+    broker_tags: Vec<String>,
+    /// Traders of the previous run to continue with instead of creating the new ones.
+    carried_traders: Option<HashMap<TraderID, T>>,
+    warm_start: Option<WarmStartHook<T>>,
+    seed: Option<u64>,
+    metadata: Option<SimulationMetadata>,
+    sinks: Vec<Sink<TraderID, BrokerID, ExchangeID, Symbol, Settlement, T>>,
+}
+
+impl<TraderID, BrokerID, ExchangeID, Symbol, ObSnapshotDelay, Settlement, T>
+SimulationBuilder<TraderID, BrokerID, ExchangeID, Symbol, ObSnapshotDelay, Settlement, T>
+    where TraderID: Id + FromStr,
+          BrokerID: Id,
+          ExchangeID: Id + FromStr,
+          Symbol: Id + FromStr,
+          ObSnapshotDelay: Clone + GetNextObSnapshotDelay<ExchangeID, Symbol, Settlement>,
+          Settlement: GetSettlementLag,
+          T: Trader<
+              TraderID=TraderID,
+              BrokerID=BrokerID,
+              T2B=BasicTraderToBroker<BrokerID, ExchangeID, Symbol, Settlement>,
+              B2T=BasicBrokerToTrader<TraderID, ExchangeID, Symbol, Settlement>
+          >
+{
+    /// Parses the YAML-config and creates a new instance of the `SimulationBuilder`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` — Path to YAML-config.
+    /// * `traded_pair_parser` — Traded pair parser.
+    /// * `ob_snapshot_delay_scheduler` — OB-snapshot delay scheduler of the [`OneTickReplay`].
+    /// * `broker_id` — ID of the [`BasicBroker`] the traders are connected to.
+    pub fn from_yaml(
+        path: impl AsRef<Path>,
+        traded_pair_parser: impl TradedPairParser<Symbol, Settlement>,
+        ob_snapshot_delay_scheduler: ObSnapshotDelay,
+        broker_id: BrokerID) -> Self
+    {
+        let path = path.as_ref();
+        let (exchange_ids, replay_config, start_dt, end_dt) = parse_yaml(
+            path,
+            traded_pair_parser,
+            ob_snapshot_delay_scheduler,
+        );
+        let exchanges = exchange_ids.iter()
+            .map(
+                |exchange_id| BasicExchange::from(exchange_id).with_historical_order_ids(
+                    replay_config.preserves_order_ids(*exchange_id)
+                )
+            )
+            .collect();
+        Self {
+            config_path: path.to_path_buf(),
+            replay_config,
+            date_range: (start_dt, end_dt),
+            trader_configs: parse_traders_yaml(path),
+            registry: TraderRegistry::new(),
+            broker: BasicBroker::new(broker_id),
+            exchanges,
+            subscriptions: SubscriptionList::subscribe().to_ob_snapshots(),
+            broker_tags: vec![],
+            carried_traders: None,
+            warm_start: None,
+            seed: None,
+            metadata: None,
+            sinks: vec![],
+        }
+    }
+
+    /// Registers the [`TraderFactory`] for the trader type used in the YAML-config.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` — Trader type.
+    /// * `factory` — Factory to construct traders of the given type.
+    pub fn with_factory(
+        mut self,
+        kind: impl Into<String>,
+        factory: impl TraderFactory<TraderID, T> + Send + Sync + 'static) -> Self
+    {
+        self.registry = self.registry.with_factory(kind, factory);
+        self
+    }
+
+    /// Replaces the default [`BasicBroker`], e.g. with the one having the account ledger,
+    /// the statements or the kill switch attached.
+    ///
+    /// # Arguments
+    ///
+    /// * `broker` — Broker the traders are connected to.
+    pub fn with_broker(
+        mut self,
+        broker: BasicBroker<BrokerID, TraderID, ExchangeID, Symbol, Settlement>) -> Self
+    {
+        self.broker = broker;
+        self
+    }
+
+    /// Replaces the default [`BasicExchange`] with the same ID,
+    /// e.g. with the one having the middleware attached.
+    /// Keeps the historical order IDs if the traded pairs of the exchange preserve them.
+    ///
+    /// # Arguments
+    ///
+    /// * `exchange` — Exchange listed in the YAML-config.
+    pub fn with_exchange(
+        mut self,
+        exchange: BasicExchange<ExchangeID, BrokerID, Symbol, Settlement>) -> Self
+    {
+        let exchange_id = exchange.get_name();
+        let slot = self.exchanges.iter_mut()
+            .find(|default| default.get_name() == exchange_id)
+            .unwrap_or_else(|| panic!("Exchange {exchange_id} is not listed in the config"));
+        *slot = exchange.with_historical_order_ids(
+            self.replay_config.preserves_order_ids(exchange_id)
+        );
+        self
+    }
+
+    /// Sets the subscriptions of every trader to every traded pair.
+    /// Defaults to the OB-snapshots only.
+    ///
+    /// # Arguments
+    ///
+    /// * `subscriptions` — Subscriptions to the order book events.
+    pub fn with_subscriptions(mut self, subscriptions: SubscriptionList) -> Self {
+        self.subscriptions = subscriptions;
+        self
+    }
+
+    /// Sets the tags of the broker, e.g. `venue=primary`, added to the [`SimulationSummary`].
+    /// Trader tags are listed in the YAML-config.
+    ///
+    /// # Arguments
+    ///
+    /// * `tags` — Tags of the broker.
+    pub fn with_broker_tags(mut self, tags: impl IntoIterator<Item=impl Into<String>>) -> Self {
+        self.broker_tags = tags.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Continues the previous run with its broker and traders in their final state,
+    /// e.g. to run the long-horizon simulation month by month with the YAML-config per month.
+    /// The broker keeps its ledger and tags and the traders keep their registrations
+    /// and subscriptions, whereas the exchanges are created anew from the YAML-config.
+    /// The YAML-config should list the same traders and start after the previous run ends,
+    /// at which the exchanges should be closed.
+    ///
+    /// # Arguments
+    ///
+    /// * `summary` — Summary of the previous run.
+    pub fn with_carried_state(
+        mut self,
+        summary: SimulationSummary<TraderID, BrokerID, ExchangeID, Symbol, Settlement, T>) -> Self
+    {
+        let (start_dt, _) = self.date_range;
+        if start_dt < summary.end_dt {
+            panic!(
+                "Run configured by {:?} starts at {start_dt} before the end \
+                of the previous run {}",
+                self.config_path,
+                summary.end_dt
+            )
+        }
+        self.broker = summary.broker;
+        self.broker_tags = summary.broker_tags;
+        self.carried_traders = Some(summary.traders);
+        self
+    }
+
+    /// Hands the indicator series precomputed from the history before the start of the run
+    /// to every trader created from the YAML-config before it is registered at the broker.
+    /// Carried traders are not warmed up again.
+    ///
+    /// # Arguments
+    ///
+    /// * `warm_start` — Indicator series computed for the start of the run.
+    pub fn with_warm_start(mut self, warm_start: WarmStart) -> Self
+        where T: WarmStartable
+    {
+        let (start_dt, _) = self.date_range;
+        if warm_start.get_start_dt() > start_dt {
+            panic!(
+                "Warm start computed for {} looks ahead of the run configured by {:?} \
+                starting at {start_dt}",
+                warm_start.get_start_dt(),
+                self.config_path
+            )
+        }
+        self.warm_start = Some(Box::new(move |trader: &mut T| trader.warm_start(&warm_start)));
+        self
+    }
+
+    /// Sets seed for the [`Kernel`](crate::kernel::Kernel) random number generator.
+    ///
+    /// # Arguments
+    ///
+    /// * `seed` — Seed.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Sets the metadata of the run, e.g. with the git commit of the user binary.
+    /// The seed is stamped by the `SimulationBuilder` itself.
+    /// Defaults to the metadata with the hash of the YAML-config.
+    ///
+    /// # Arguments
+    ///
+    /// * `metadata` — Metadata of the run.
+    pub fn with_metadata(mut self, metadata: SimulationMetadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Adds the output sink called with the [`SimulationSummary`] once the simulation is over.
+    /// Sinks are called in the order they are added.
+    ///
+    /// # Arguments
+    ///
+    /// * `sink` — Output sink, e.g. writing the metadata sidecars of the trader output files.
+    pub fn with_sink<F>(mut self, sink: F) -> Self
+        where F: FnOnce(&SimulationSummary<TraderID, BrokerID, ExchangeID, Symbol, Settlement, T>)
+                 + 'static
+    {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// Returns trader entries of the YAML-config.
+    pub fn get_trader_configs(&self) -> &[YamlTraderConfig<TraderID>] {
+        &self.trader_configs
+    }
+
+    /// Runs the simulation, passes its summary to the output sinks and returns it.
+    pub fn run(self) -> SimulationSummary<TraderID, BrokerID, ExchangeID, Symbol, Settlement, T>
+    {
+        let Self {
+            config_path,
+            replay_config,
+            date_range,
+            trader_configs,
+            registry,
+            broker,
+            exchanges,
+            subscriptions,
+            broker_tags,
+            carried_traders,
+            warm_start,
+            seed,
+            metadata,
+            sinks,
+        } = self;
+
+        if trader_configs.is_empty() {
+            panic!("There are no traders in the config {config_path:?}")
+        }
+        for config in trader_configs.iter().filter(|_| carried_traders.is_none()) {
+            if !registry.is_registered(&config.kind) {
+                panic!("Trader {} has unsupported type \"{}\"", config.name, config.kind)
+            }
+        }
+        let mut metadata = metadata.unwrap_or_else(
+            || SimulationMetadata::new().with_config_file(&config_path)
+        );
+        if let Some(seed) = seed {
+            metadata = metadata.with_seed(seed)
+        }
+
+        let trader_tags = trader_configs.iter()
+            .map(|config| (config.name, config.tags.clone()))
+            .collect();
+        let broker_id = broker.get_name();
+        let exchange_ids: Vec<_> = exchanges.iter().map(BasicExchange::get_name).collect();
+        let replay = OneTickReplay::from(&replay_config);
+
+        let kernel = if let Some(traders) = carried_traders {
+            let mismatch = traders.len() != trader_configs.len() || trader_configs.iter().any(
+                |config| !traders.contains_key(&config.name)
+            );
+            if mismatch {
+                panic!(
+                    "Traders in the config {config_path:?} differ from the ones of the previous run"
+                )
+            }
+            let agents = KernelAgents {
+                traders,
+                brokers: HashMap::from_iter([(broker_id, broker)]),
+                exchanges: Default::default(),
+                auxiliaries: Default::default(),
+            };
+            KernelBuilder::from_previous_run(
+                agents,
+                exchanges,
+                [(broker_id, exchange_ids)],
+                replay,
+                date_range,
+            )
+        } else {
+            let subscriptions: Vec<_> = replay_config.traded_pair_configs.iter()
+                .map(
+                    |config| SubscriptionConfig::new(
+                        config.exchange_id,
+                        config.traded_pair,
+                        subscriptions,
+                    )
+                )
+                .collect();
+            let traders = trader_configs.iter().map(
+                |config| {
+                    let mut trader = registry.create_trader(config);
+                    if let Some(warm_start) = &warm_start {
+                        warm_start(&mut trader)
+                    }
+                    (trader, [(broker_id, subscriptions.clone())])
+                }
+            );
+            KernelBuilder::new(
+                exchanges,
+                [(broker, exchange_ids)],
+                traders,
+                replay,
+                date_range,
+            )
+        };
+        let kernel = match seed {
+            Some(seed) => kernel.with_seed(seed),
+            None => kernel
+        };
+        let KernelAgents { traders, mut brokers, exchanges, .. } = kernel
+            .with_rng::<StdRng>()
+            .build()
+            .run_simulation_into_agents();
+
+        let (start_dt, end_dt) = date_range;
+        let summary = SimulationSummary {
+            metadata,
+            start_dt,
+            end_dt,
+            traders,
+            broker: brokers.remove(&broker_id).unwrap_or_else(
+                || unreachable!("Broker {broker_id} is not found among the agents")
+            ),
+            exchanges,
+            trader_tags,
+            broker_tags,
+        };
+        for sink in sinks {
+            sink(&summary)
+        }
+        summary
+    }
+}
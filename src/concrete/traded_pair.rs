@@ -13,6 +13,7 @@ pub mod parser;
 pub mod settlement;
 
 #[derive(Debug, Clone, Copy, PartialOrd, Ord, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Traded pair.
 pub struct TradedPair<Name: Id, Settlement: GetSettlementLag> {
     /// Quoted asset.
@@ -25,6 +26,7 @@ pub struct TradedPair<Name: Id, Settlement: GetSettlementLag> {
 
 enum_def! {
     #[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Ord, Eq, Hash)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     /// Asset.
     pub Asset<Name: Id> {
         /// Base asset.
@@ -37,6 +39,7 @@ enum_def! {
 }
 
 #[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Ord, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Base asset.
 pub struct Base<Name: Id> {
     /// Unique ID of the `Base`.
@@ -44,6 +47,7 @@ pub struct Base<Name: Id> {
 }
 
 #[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Ord, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Futures contract.
 pub struct Futures<Name: Id> {
     /// Unique ID of the `Futures`.
@@ -59,6 +63,7 @@ pub struct Futures<Name: Id> {
 }
 
 #[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Ord, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Option contract.
 pub struct OptionContract<Name: Id> {
     /// Unique ID of the `OptionContract`.
@@ -76,6 +81,7 @@ pub struct OptionContract<Name: Id> {
 }
 
 #[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Ord, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Option kind.
 pub enum OptionKind {
     /// European put option.
@@ -157,6 +163,16 @@ impl<Name: Id> Named<Name> for OptionContract<Name> {
     }
 }
 
+impl<Name: Id> Named<Name> for Asset<Name> {
+    fn get_name(&self) -> Name {
+        match self {
+            Asset::Base(asset) => asset.get_name(),
+            Asset::Futures(asset) => asset.get_name(),
+            Asset::OptionContract(asset) => asset.get_name()
+        }
+    }
+}
+
 impl<Name: Id> Into<Asset<Name>> for Base<Name> {
     fn into(self) -> Asset<Name> {
         Asset::Base(self)
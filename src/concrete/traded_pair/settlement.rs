@@ -1,5 +1,5 @@
 use {
-    crate::types::DateTime,
+    crate::types::{DateTime, NanoDuration},
     std::{fmt::Debug, hash::Hash},
 };
 
@@ -9,10 +9,10 @@ pub mod concrete;
 /// Gets settlement lag.
 pub trait GetSettlementLag: Debug + Copy + Ord + Hash
 {
-    /// Generates settlement lag in nanoseconds since `transaction_dt`.
+    /// Generates settlement lag since `transaction_dt`.
     ///
     /// # Arguments
     ///
     /// * `transaction_dt` — transaction datetime.
-    fn get_settlement_lag(&self, transaction_dt: DateTime) -> u64;
+    fn get_settlement_lag(&self, transaction_dt: DateTime) -> NanoDuration;
 }
\ No newline at end of file
@@ -1,49 +1,54 @@
 use {
     crate::{
-        types::DateTime,
+        types::{DateTime, NanoDuration},
         utils::constants::*,
     },
     super::GetSettlementLag,
 };
 
 #[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Ord, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Panics upon calling `get_settlement_lag`.
 pub struct VoidSettlement;
 
 impl GetSettlementLag for VoidSettlement {
-    fn get_settlement_lag(&self, _: DateTime) -> u64 {
+    fn get_settlement_lag(&self, _: DateTime) -> NanoDuration {
         unreachable!("VoidSettlement::get_settlement_lag method called")
     }
 }
 
 #[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Ord, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Immediate settlement.
 pub struct SpotSettlement;
 
 impl GetSettlementLag for SpotSettlement {
-    fn get_settlement_lag(&self, _: DateTime) -> u64 { NOW }
+    fn get_settlement_lag(&self, _: DateTime) -> NanoDuration { NOW }
 }
 
 #[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Ord, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// One minute settlement.
 pub struct PreciseOneMinuteSettlement;
 
 impl GetSettlementLag for PreciseOneMinuteSettlement {
-    fn get_settlement_lag(&self, _: DateTime) -> u64 { ONE_MINUTE }
+    fn get_settlement_lag(&self, _: DateTime) -> NanoDuration { ONE_MINUTE }
 }
 
 #[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Ord, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// One hour settlement.
 pub struct PreciseOneHourSettlement;
 
 impl GetSettlementLag for PreciseOneHourSettlement {
-    fn get_settlement_lag(&self, _: DateTime) -> u64 { ONE_HOUR }
+    fn get_settlement_lag(&self, _: DateTime) -> NanoDuration { ONE_HOUR }
 }
 
 #[derive(Debug, Clone, Copy, PartialOrd, PartialEq, Ord, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// 24-hour settlement.
 pub struct PreciseOneDaySettlement;
 
 impl GetSettlementLag for PreciseOneDaySettlement {
-    fn get_settlement_lag(&self, _: DateTime) -> u64 { ONE_DAY }
+    fn get_settlement_lag(&self, _: DateTime) -> NanoDuration { ONE_DAY }
 }
\ No newline at end of file
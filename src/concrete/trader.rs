@@ -17,15 +17,41 @@ use {
         },
         kernel::LatentActionProcessor,
         types::{Agent, Date, DateTime, Id, Named, Nothing, TimeSync},
-        utils::queue::MessageReceiver,
+        utils::{
+            metadata::{MetadataPlacement, SimulationMetadata},
+            queue::MessageReceiver,
+        },
     },
     rand::Rng,
-    std::{fs::File, io::Write, marker::PhantomData, path::Path},
+    std::{fs::File, io::Write, marker::PhantomData, path::{Path, PathBuf}},
 };
 
+/// Tracking of the market data event and receipt times for lookahead-free views.
+pub mod as_of;
+/// Consolidation of the OB-snapshots of the several exchanges into the single feed.
+pub mod consolidated_feed;
+/// Detection of the crossed consolidated best bid-offers across the exchanges.
+pub mod crossed_venues;
+/// Routing of the trader requests through several brokers with failover on session outages.
+pub mod failover;
+#[cfg(feature = "lookahead_guard")]
+/// Re-export of the [`kernel::lookahead`](crate::kernel::lookahead) wrappers
+/// detecting the use of the market data ahead of its delivery.
+pub mod lookahead_guard;
+#[cfg(feature = "optimizer")]
+/// Constrained portfolio optimization computing the rebalancing trades.
+pub mod optimizer;
+/// Recording of the configurable OB-snapshot fields into the per-pair files.
+pub mod quote_recorder;
+/// Correlates trader requests with broker replies and tracks acknowledgement timeouts.
+pub mod request_tracker;
 /// Defines trader subscription
 /// to pairs (`ExchangeID`, [`TradedPair`](crate::concrete::traded_pair::TradedPair)).
 pub mod subscriptions;
+/// Trader-side order entry rate limiting with coalescing of the queued requests.
+pub mod throttle;
+/// Precomputation of the indicator series from the history before the simulation start.
+pub mod warm_start;
 
 /// [`Trader`] that writes best bid-offer to a csv-file whenever it receives OB update.
 pub struct SpreadWriter<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
@@ -39,6 +65,7 @@ pub struct SpreadWriter<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
     current_dt: DateTime,
     price_step: TickSize,
     file: File,
+    path: PathBuf,
     phantom: PhantomData<(BrokerID, ExchangeID, Symbol, Settlement)>,
 }
 
@@ -58,20 +85,55 @@ SpreadWriter<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
     /// * `price_step` — Price quotation step.
     /// * `file` — Path to the csv-file to create.
     pub fn new(name: TraderID, price_step: impl Into<TickSize>, file: impl AsRef<Path>) -> Self {
-        let file = file.as_ref();
-        let file = File::create(file).unwrap_or_else(
-            |err| panic!("Cannot create file {file:?}. Error: {err}")
-        );
-        writeln!(&file, "Timestamp,BID_PRICE,BID_SIZE,ASK_PRICE,ASK_SIZE")
-            .unwrap_or_else(|err| panic!("Cannot write to file {file:?}. Error: {err}"));
+        let path = file.as_ref().to_path_buf();
         SpreadWriter {
             name,
             current_dt: Date::from_ymd(1970, 1, 1).and_hms(0, 0, 0),
             price_step: price_step.into(),
-            file,
+            file: Self::create_file(&path, None),
+            path,
             phantom: Default::default(),
         }
     }
+
+    /// Stamps the csv-file with the [`SimulationMetadata`].
+    /// Should be called before the simulation starts since it recreates the csv-file.
+    ///
+    /// # Arguments
+    ///
+    /// * `metadata` — Metadata to stamp the csv-file with.
+    /// * `placement` — Whether to write the metadata into the header or into the sidecar file.
+    pub fn with_metadata(
+        mut self,
+        metadata: &SimulationMetadata,
+        placement: MetadataPlacement,
+    ) -> Self {
+        match placement {
+            MetadataPlacement::Header => {
+                self.file = Self::create_file(&self.path, Some(metadata))
+            }
+            MetadataPlacement::Sidecar => metadata.write_sidecar(&self.path)
+        }
+        self
+    }
+
+    /// Returns the path to the csv-file.
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    fn create_file(path: &Path, metadata: Option<&SimulationMetadata>) -> File {
+        let file = File::create(path).unwrap_or_else(
+            |err| panic!("Cannot create file {path:?}. Error: {err}")
+        );
+        if let Some(metadata) = metadata {
+            metadata.write_header(&file)
+                .unwrap_or_else(|err| panic!("Cannot write to file {path:?}. Error: {err}"))
+        }
+        writeln!(&file, "Timestamp,BID_PRICE,BID_SIZE,ASK_PRICE,ASK_SIZE")
+            .unwrap_or_else(|err| panic!("Cannot write to file {path:?}. Error: {err}"));
+        file
+    }
 }
 
 impl<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
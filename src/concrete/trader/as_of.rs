@@ -0,0 +1,315 @@
+use {
+    crate::{
+        concrete::{
+            message_protocol::{
+                broker::reply::{BasicBrokerReply, BasicBrokerToTrader},
+                exchange::reply::ExchangeEventNotification,
+            },
+            traded_pair::{settlement::GetSettlementLag, TradedPair},
+        },
+        types::{DateTime, Duration, Id, NanoDuration},
+        utils::collections::HashMap,
+    },
+    std::collections::VecDeque,
+};
+
+/// Events of the single feed in the order of their exchange datetimes.
+type FeedHistory<Symbol, Settlement> = VecDeque<ReceivedEvent<Symbol, Settlement>>;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+/// Market data notification along with the datetimes it happened at and became known at.
+pub struct ReceivedEvent<Symbol: Id, Settlement: GetSettlementLag> {
+    /// Datetime of the event at the exchange.
+    pub event_dt: DateTime,
+    /// Datetime the trader received the notification at.
+    pub receipt_dt: DateTime,
+    /// Notification.
+    pub notification: ExchangeEventNotification<Symbol, Settlement>,
+}
+
+/// Tracks what the trader knew about each market data feed and when,
+/// so that the strategy can avoid the lookahead bias when mixing feeds with different delays.
+///
+/// The feed is identified by the exchange and the traded pair.
+/// Notifications not related to a single traded pair are not tracked.
+pub struct AsOfClock<ExchangeID, Symbol, Settlement>
+    where ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    feeds: HashMap<(ExchangeID, TradedPair<Symbol, Settlement>), FeedHistory<Symbol, Settlement>>,
+    retention: Option<NanoDuration>,
+}
+
+impl<ExchangeID, Symbol, Settlement> Default for AsOfClock<ExchangeID, Symbol, Settlement>
+    where ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    fn default() -> Self {
+        Self {
+            feeds: Default::default(),
+            retention: None,
+        }
+    }
+}
+
+impl<ExchangeID, Symbol, Settlement> AsOfClock<ExchangeID, Symbol, Settlement>
+    where ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    /// Creates a new instance of the `AsOfClock`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets how long to keep the events. By default, they are kept forever.
+    /// The latest event of every feed is kept regardless of its age.
+    ///
+    /// # Arguments
+    ///
+    /// * `retention` — Time since the receipt after which the events are dropped.
+    pub fn with_retention(mut self, retention: NanoDuration) -> Self {
+        self.retention = Some(retention);
+        self
+    }
+
+    /// Records the market data notification contained in the broker reply.
+    /// Returns `false` if the reply is not a notification related to a single traded pair.
+    ///
+    /// # Arguments
+    ///
+    /// * `reply` — Reply received by the trader.
+    /// * `receipt_dt` — Current datetime of the trader.
+    pub fn record<TraderID: Id>(
+        &mut self,
+        reply: &BasicBrokerToTrader<TraderID, ExchangeID, Symbol, Settlement>,
+        receipt_dt: DateTime) -> bool
+    {
+        let notification = if let BasicBrokerReply::ExchangeEventNotification(
+            notification
+        ) = &reply.content {
+            notification
+        } else {
+            return false;
+        };
+        let traded_pair = match notification {
+            ExchangeEventNotification::TradesStarted { traded_pair, .. } => *traded_pair,
+            ExchangeEventNotification::OrderCancelled(cancelled) => cancelled.traded_pair,
+            ExchangeEventNotification::OrderSizeReduced(reduced) => reduced.traded_pair,
+            ExchangeEventNotification::OrderPlaced(placed) => placed.traded_pair,
+            ExchangeEventNotification::TradeExecuted(trade) => trade.traded_pair,
+            ExchangeEventNotification::OffBookTradeReported(trade) => trade.traded_pair,
+            ExchangeEventNotification::ObSnapshot(snapshot) => snapshot.traded_pair,
+            ExchangeEventNotification::ObDiff(diff) => diff.traded_pair,
+            ExchangeEventNotification::TradesStopped(traded_pair) => *traded_pair,
+            ExchangeEventNotification::AdminActionApplied(action) => action.get_traded_pair(),
+            ExchangeEventNotification::ClosingPrice { traded_pair, .. } => *traded_pair,
+            ExchangeEventNotification::ThroughputStatistics(statistics) => statistics.traded_pair,
+            ExchangeEventNotification::VolatilityEstimate(estimate) => estimate.traded_pair,
+            ExchangeEventNotification::AuctionPriceDetermined(auction) => auction.traded_pair,
+            ExchangeEventNotification::ExchangeOpen |
+            ExchangeEventNotification::DowntimeStarted |
+            ExchangeEventNotification::DowntimeEnded |
+            ExchangeEventNotification::TradingPhaseChanged(_) |
+            ExchangeEventNotification::ExchangeClosed => return false
+        };
+        let events = self.feeds.entry((reply.exchange_id, traded_pair)).or_default();
+        // Feeds are usually delivered in the order of the events,
+        // so the insertion point is almost always at the back.
+        let i = events.len() - events.iter()
+            .rev()
+            .take_while(|event| event.event_dt > reply.event_dt)
+            .count();
+        events.insert(
+            i,
+            ReceivedEvent {
+                event_dt: reply.event_dt,
+                receipt_dt,
+                notification: notification.clone(),
+            },
+        );
+        let horizon = self.retention
+            .and_then(NanoDuration::to_duration)
+            .and_then(|retention| receipt_dt.checked_sub_signed(retention));
+        if let Some(horizon) = horizon {
+            let is_expired = |event: &ReceivedEvent<_, _>| event.receipt_dt < horizon;
+            while events.len() > 1 && events.front().is_some_and(is_expired) {
+                events.pop_front();
+            }
+        }
+        true
+    }
+
+    /// Returns the latest event of the feed that happened at or before the `as_of_dt`.
+    ///
+    /// # Arguments
+    ///
+    /// * `exchange_id` — Exchange of the feed.
+    /// * `traded_pair` — Traded pair of the feed.
+    /// * `as_of_dt` — Exchange datetime to look up the event as of.
+    pub fn latest_as_of(
+        &self,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        as_of_dt: DateTime) -> Option<&ReceivedEvent<Symbol, Settlement>>
+    {
+        self.iter_as_of(exchange_id, traded_pair, as_of_dt).last()
+    }
+
+    /// Yields the events of the feed that happened at or before the `as_of_dt`,
+    /// in the order of their exchange datetimes.
+    ///
+    /// # Arguments
+    ///
+    /// * `exchange_id` — Exchange of the feed.
+    /// * `traded_pair` — Traded pair of the feed.
+    /// * `as_of_dt` — Exchange datetime to look up the events as of.
+    pub fn iter_as_of(
+        &self,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        as_of_dt: DateTime) -> impl Iterator<Item=&ReceivedEvent<Symbol, Settlement>>
+    {
+        self.feeds.get(&(exchange_id, traded_pair))
+            .into_iter()
+            .flatten()
+            .take_while(move |event| event.event_dt <= as_of_dt)
+    }
+
+    /// Returns the latest exchange datetime up to which all the feeds are known,
+    /// i.e. the minimum over the feeds of the datetimes of their latest events.
+    /// Views as of this datetime are consistent across the feeds.
+    /// Returns `None` if some of the feeds have not delivered any event yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `feeds` — Exchanges and traded pairs of the feeds.
+    pub fn get_watermark(
+        &self,
+        feeds: impl IntoIterator<
+            Item=(ExchangeID, TradedPair<Symbol, Settlement>)
+        >) -> Option<DateTime>
+    {
+        let mut watermark: Option<DateTime> = None;
+        for feed in feeds {
+            let event_dt = self.feeds.get(&feed)?.back()?.event_dt;
+            watermark = Some(watermark.map_or(event_dt, |watermark| watermark.min(event_dt)))
+        }
+        watermark
+    }
+
+    /// Returns the delay between the latest event of the feed and its receipt.
+    ///
+    /// # Arguments
+    ///
+    /// * `exchange_id` — Exchange of the feed.
+    /// * `traded_pair` — Traded pair of the feed.
+    pub fn get_last_delay(
+        &self,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>) -> Option<Duration>
+    {
+        self.feeds.get(&(exchange_id, traded_pair))?
+            .back()
+            .map(|event| event.receipt_dt - event.event_dt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            concrete::{
+                message_protocol::exchange::reply::MarketOrderEventInfo,
+                traded_pair::settlement::concrete::SpotSettlement,
+                types::{Direction, Lots, Tick, TradeID},
+            },
+            test_utils::{dt, pair, symbol_pair},
+        },
+        super::*,
+    };
+
+    type Clock = AsOfClock<&'static str, &'static str, SpotSettlement>;
+    type Reply = BasicBrokerToTrader<u8, &'static str, &'static str, SpotSettlement>;
+
+    fn trade(symbol: &'static str, event_dt: DateTime, price: i64) -> Reply {
+        BasicBrokerToTrader {
+            trader_id: 0,
+            exchange_id: "MOEX",
+            event_dt,
+            content: BasicBrokerReply::ExchangeEventNotification(
+                ExchangeEventNotification::TradeExecuted(
+                    MarketOrderEventInfo {
+                        traded_pair: symbol_pair(symbol),
+                        trade_id: TradeID(price as u64),
+                        direction: Direction::Buy,
+                        price: Tick(price),
+                        size: Lots(1),
+                    }
+                )
+            ),
+        }
+    }
+
+    fn prices<'a>(
+        events: impl Iterator<Item=&'a ReceivedEvent<&'static str, SpotSettlement>>) -> Vec<i64>
+    {
+        events
+            .map(
+                |event| match &event.notification {
+                    ExchangeEventNotification::TradeExecuted(trade) => trade.price.0,
+                    notification => unreachable!("Unexpected notification: {notification:?}")
+                }
+            )
+            .collect()
+    }
+
+    #[test]
+    fn test_events_as_of() {
+        let mut clock = Clock::new();
+        assert!(clock.record(&trade("USD", dt(1), 100), dt(2)));
+        // Delayed event is ordered by its exchange datetime
+        assert!(clock.record(&trade("USD", dt(3), 102), dt(4)));
+        assert!(clock.record(&trade("USD", dt(2), 101), dt(5)));
+        let exchange_open = Reply {
+            content: BasicBrokerReply::ExchangeEventNotification(
+                ExchangeEventNotification::ExchangeOpen
+            ),
+            ..trade("USD", dt(5), 0)
+        };
+        assert!(!clock.record(&exchange_open, dt(5)));
+
+        assert_eq!(prices(clock.iter_as_of("MOEX", pair(), dt(2))), [100, 101]);
+        assert_eq!(prices(clock.latest_as_of("MOEX", pair(), dt(3)).into_iter()), [102]);
+        assert!(clock.latest_as_of("MOEX", pair(), dt(0)).is_none());
+        assert!(clock.latest_as_of("NYSE", pair(), dt(3)).is_none());
+        assert_eq!(clock.get_last_delay("MOEX", pair()), Some(Duration::seconds(1)));
+    }
+
+    #[test]
+    fn test_watermark() {
+        let mut clock = Clock::new();
+        let feeds = [("MOEX", pair()), ("MOEX", symbol_pair("EUR"))];
+        clock.record(&trade("USD", dt(3), 100), dt(3));
+        assert_eq!(clock.get_watermark(feeds), None);
+        clock.record(&trade("EUR", dt(1), 90), dt(4));
+        assert_eq!(clock.get_watermark(feeds), Some(dt(1)));
+        clock.record(&trade("EUR", dt(5), 91), dt(5));
+        assert_eq!(clock.get_watermark(feeds), Some(dt(3)))
+    }
+
+    #[test]
+    fn test_retention() {
+        let mut clock = Clock::new().with_retention(NanoDuration(2_000_000_000));
+        clock.record(&trade("USD", dt(1), 100), dt(1));
+        clock.record(&trade("USD", dt(2), 101), dt(2));
+        clock.record(&trade("USD", dt(4), 102), dt(4));
+        assert_eq!(prices(clock.iter_as_of("MOEX", pair(), dt(4))), [101, 102]);
+        // Latest event is kept regardless of its age
+        clock.record(&trade("USD", dt(5), 103), dt(10));
+        assert_eq!(prices(clock.iter_as_of("MOEX", pair(), dt(10))), [103])
+    }
+}
+
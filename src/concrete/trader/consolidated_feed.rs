@@ -0,0 +1,211 @@
+use {
+    crate::{
+        concrete::{
+            message_protocol::{
+                broker::reply::{BasicBrokerReply, BasicBrokerToTrader},
+                exchange::reply::{ExchangeEventNotification, ObSnapshot},
+            },
+            traded_pair::{settlement::GetSettlementLag, TradedPair},
+            types::{Lots, ObState, Tick},
+        },
+        types::{DateTime, Duration, Id},
+        utils::collections::HashMap,
+    },
+    std::{cmp::Reverse, collections::BTreeMap, rc::Rc},
+};
+
+/// Price levels of the order book side, the best one first.
+type ObSide = [(Tick, Vec<(Lots, DateTime)>)];
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+/// Latest OB-snapshot of the single exchange along with the datetimes
+/// it was taken at and became known at.
+pub struct VenueSnapshot<Symbol: Id, Settlement: GetSettlementLag> {
+    /// Datetime of the snapshot at the exchange.
+    pub event_dt: DateTime,
+    /// Datetime the trader received the snapshot at.
+    pub receipt_dt: DateTime,
+    /// Snapshot.
+    pub snapshot: Rc<ObSnapshot<Symbol, Settlement>>,
+}
+
+impl<Symbol: Id, Settlement: GetSettlementLag> VenueSnapshot<Symbol, Settlement> {
+    /// Returns the arrival latency of the snapshot.
+    pub fn get_delay(&self) -> Duration {
+        self.receipt_dt - self.event_dt
+    }
+}
+
+/// Consolidates the OB-snapshots of the traded pairs received from the several exchanges
+/// into the single synthetic book per traded pair, so that the multi-venue strategy
+/// can subscribe to the snapshots of every exchange and query one feed.
+///
+/// Every exchange contributes its latest snapshot as of the moment it was received,
+/// so the consolidated book reflects the arrival latencies of the exchanges.
+/// Snapshots arriving out of the order of their exchange datetimes are ignored.
+/// Snapshots of the exchange are dropped once it stops trades or closes.
+/// Price levels are merged by their ticks, so the price steps of the exchanges should be equal.
+pub struct ConsolidatedFeed<ExchangeID, Symbol, Settlement>
+    where ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    venues: HashMap<
+        TradedPair<Symbol, Settlement>,
+        BTreeMap<ExchangeID, VenueSnapshot<Symbol, Settlement>>
+    >,
+}
+
+impl<ExchangeID, Symbol, Settlement> Default for ConsolidatedFeed<ExchangeID, Symbol, Settlement>
+    where ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    fn default() -> Self {
+        Self { venues: Default::default() }
+    }
+}
+
+impl<ExchangeID, Symbol, Settlement> ConsolidatedFeed<ExchangeID, Symbol, Settlement>
+    where ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    /// Creates a new instance of the `ConsolidatedFeed`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Updates the feed with the notification contained in the broker reply.
+    /// Returns the traded pair which consolidated book has changed, if any.
+    /// Closing of the exchange may change several books, so `None` is returned then.
+    ///
+    /// # Arguments
+    ///
+    /// * `reply` — Reply received by the trader.
+    /// * `receipt_dt` — Current datetime of the trader.
+    pub fn process_reply<TraderID: Id>(
+        &mut self,
+        reply: &BasicBrokerToTrader<TraderID, ExchangeID, Symbol, Settlement>,
+        receipt_dt: DateTime) -> Option<TradedPair<Symbol, Settlement>>
+    {
+        let notification = if let BasicBrokerReply::ExchangeEventNotification(
+            notification
+        ) = &reply.content {
+            notification
+        } else {
+            return None;
+        };
+        match notification {
+            ExchangeEventNotification::ObSnapshot(snapshot) => {
+                let venue = VenueSnapshot {
+                    event_dt: reply.event_dt,
+                    receipt_dt,
+                    snapshot: snapshot.clone(),
+                };
+                let venues = self.venues.entry(snapshot.traded_pair).or_default();
+                match venues.get(&reply.exchange_id) {
+                    Some(latest) if latest.event_dt > reply.event_dt => None,
+                    _ => {
+                        venues.insert(reply.exchange_id, venue);
+                        Some(snapshot.traded_pair)
+                    }
+                }
+            }
+            ExchangeEventNotification::TradesStopped(traded_pair) => {
+                self.venues.get_mut(traded_pair)?.remove(&reply.exchange_id)?;
+                Some(*traded_pair)
+            }
+            ExchangeEventNotification::ExchangeClosed => {
+                for venues in self.venues.values_mut() {
+                    venues.remove(&reply.exchange_id);
+                }
+                None
+            }
+            _ => None
+        }
+    }
+
+    /// Returns an iterator over the latest snapshots of the traded pair per exchange,
+    /// ordered by the exchange IDs.
+    ///
+    /// # Arguments
+    ///
+    /// * `traded_pair` — Traded pair.
+    pub fn iter_venues(
+        &self,
+        traded_pair: TradedPair<Symbol, Settlement>,
+    ) -> impl Iterator<Item=(ExchangeID, &VenueSnapshot<Symbol, Settlement>)> + '_
+    {
+        self.venues.get(&traded_pair)
+            .into_iter()
+            .flatten()
+            .map(|(exchange_id, venue)| (*exchange_id, venue))
+    }
+
+    /// Returns the consolidated book of the traded pair, merging the price levels
+    /// of the exchanges. Orders of the merged level are ordered by the exchange IDs.
+    /// The book is crossed if the prices of the exchanges are.
+    ///
+    /// # Arguments
+    ///
+    /// * `traded_pair` — Traded pair.
+    pub fn get_book(&self, traded_pair: TradedPair<Symbol, Settlement>) -> ObState {
+        let mut bids = BTreeMap::<_, Vec<_>>::new();
+        let mut asks = BTreeMap::<_, Vec<_>>::new();
+        for (_, venue) in self.iter_venues(traded_pair) {
+            for (price, orders) in &venue.snapshot.state.bids {
+                bids.entry(Reverse(*price)).or_default().extend(orders)
+            }
+            for (price, orders) in &venue.snapshot.state.asks {
+                asks.entry(*price).or_default().extend(orders)
+            }
+        }
+        ObState {
+            bids: bids.into_iter().map(|(Reverse(price), orders)| (price, orders)).collect(),
+            asks: asks.into_iter().collect(),
+        }
+    }
+
+    /// Returns the highest bid of the traded pair along with its exchange and size.
+    /// Ties are broken in favour of the lowest exchange ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `traded_pair` — Traded pair.
+    pub fn get_best_bid(
+        &self,
+        traded_pair: TradedPair<Symbol, Settlement>) -> Option<(ExchangeID, Tick, Lots)>
+    {
+        self.iter_best(traded_pair, |state| &state.bids)
+            .reduce(|best, other| if other.1 > best.1 { other } else { best })
+    }
+
+    /// Returns the lowest ask of the traded pair along with its exchange and size.
+    /// Ties are broken in favour of the lowest exchange ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `traded_pair` — Traded pair.
+    pub fn get_best_ask(
+        &self,
+        traded_pair: TradedPair<Symbol, Settlement>) -> Option<(ExchangeID, Tick, Lots)>
+    {
+        self.iter_best(traded_pair, |state| &state.asks)
+            .reduce(|best, other| if other.1 < best.1 { other } else { best })
+    }
+
+    fn iter_best(
+        &self,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        get_side: fn(&ObState) -> &ObSide,
+    ) -> impl Iterator<Item=(ExchangeID, Tick, Lots)> + '_
+    {
+        self.iter_venues(traded_pair).filter_map(
+            move |(exchange_id, venue)| {
+                let (price, orders) = get_side(&venue.snapshot.state).first()?;
+                Some((exchange_id, *price, orders.iter().map(|(size, _)| *size).sum()))
+            }
+        )
+    }
+}
@@ -0,0 +1,328 @@
+use {
+    crate::{
+        concrete::{
+            latency::ConstantLatency,
+            message_protocol::{
+                broker::reply::{BasicBrokerReply, BasicBrokerToTrader},
+                exchange::reply::ExchangeEventNotification,
+                trader::request::BasicTraderToBroker,
+            },
+            traded_pair::{settlement::GetSettlementLag, TradedPair},
+            types::{Lots, ObState, Tick},
+        },
+        interface::{latency::Latent, trader::{Trader, TraderAction}},
+        kernel::LatentActionProcessor,
+        types::{Agent, Date, DateTime, Duration, Id, Named, Nothing, TimeSync},
+        utils::{collections::HashMap, queue::MessageReceiver},
+    },
+    rand::Rng,
+    std::{collections::BTreeMap, marker::PhantomData},
+};
+
+/// Best bid and ask of the exchange along with their sizes.
+type BestBidOffer = (Option<(Tick, Lots)>, Option<(Tick, Lots)>);
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+/// Period during which the consolidated best bid-offer of the traded pair is crossed,
+/// i.e. the best bid at one exchange exceeds the best ask at another.
+pub struct CrossedVenueEpisode<ExchangeID: Id, Symbol: Id, Settlement: GetSettlementLag> {
+    /// Traded pair.
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    /// Exchange with the best bid at the moment of the largest theoretical profit.
+    pub bid_exchange: ExchangeID,
+    /// Exchange with the best ask at the moment of the largest theoretical profit.
+    pub ask_exchange: ExchangeID,
+    /// Datetime the crossing is observed at.
+    pub start_dt: DateTime,
+    /// Datetime the crossing is resolved at. `None` if it lasts until the end of the simulation.
+    pub end_dt: Option<DateTime>,
+    /// Largest difference between the best bid and the best ask.
+    pub max_spread: Tick,
+    /// Largest theoretical profit, in ticks times lots, of buying at the best ask
+    /// and selling at the best bid the size available at both of them.
+    pub max_profit: i64,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+/// Summary of the [`CrossedVenueEpisode`]s of the single traded pair.
+pub struct CrossedVenueSummary {
+    /// Number of the episodes.
+    pub num_episodes: usize,
+    /// Total duration of the episodes.
+    /// Episodes lasting until the end of the simulation end at the last observed snapshot.
+    pub total_duration: Duration,
+    /// Largest difference between the best bid and the best ask over the episodes.
+    pub max_spread: Tick,
+    /// Sum of the largest theoretical profits of the episodes, in ticks times lots.
+    pub total_profit: i64,
+}
+
+/// [`Trader`] that flags the moments when the consolidated best bid-offer
+/// of the traded pair across the exchanges is crossed.
+///
+/// Best bid-offers are taken from the OB-snapshots as they are received,
+/// i.e. after the latencies, so the crossings reflect what the trader could actually see.
+/// Persistent crossings usually point at the unrealistic multi-venue configuration,
+/// e.g. at the misaligned datasets or at the price steps differing between the exchanges.
+/// Quotes of the exchange are dropped once it stops trades or closes.
+pub struct CrossedVenueMonitor<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
+    where TraderID: Id,
+          BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    name: TraderID,
+    current_dt: DateTime,
+    quotes: HashMap<TradedPair<Symbol, Settlement>, BTreeMap<ExchangeID, BestBidOffer>>,
+    episodes: Vec<CrossedVenueEpisode<ExchangeID, Symbol, Settlement>>,
+    /// Indices of the episodes that have not been resolved yet.
+    open_episodes: HashMap<TradedPair<Symbol, Settlement>, usize>,
+    phantom: PhantomData<BrokerID>,
+}
+
+impl<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
+CrossedVenueMonitor<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
+    where TraderID: Id,
+          BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    /// Creates a new instance of the `CrossedVenueMonitor`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` — ID of the `CrossedVenueMonitor`.
+    pub fn new(name: TraderID) -> Self {
+        CrossedVenueMonitor {
+            name,
+            current_dt: Date::from_ymd_opt(1970, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+            quotes: Default::default(),
+            episodes: vec![],
+            open_episodes: Default::default(),
+            phantom: Default::default(),
+        }
+    }
+
+    /// Returns the crossing episodes in the order they have started.
+    pub fn get_episodes(&self) -> &[CrossedVenueEpisode<ExchangeID, Symbol, Settlement>] {
+        &self.episodes
+    }
+
+    /// Returns the summaries of the crossing episodes per traded pair, ordered by the pairs.
+    pub fn get_summary(&self) -> Vec<(TradedPair<Symbol, Settlement>, CrossedVenueSummary)> {
+        let mut summary = BTreeMap::new();
+        for episode in &self.episodes {
+            let pair_summary = summary.entry(episode.traded_pair).or_insert(
+                CrossedVenueSummary {
+                    num_episodes: 0,
+                    total_duration: Duration::zero(),
+                    max_spread: episode.max_spread,
+                    total_profit: 0,
+                }
+            );
+            pair_summary.num_episodes += 1;
+            let end_dt = episode.end_dt.unwrap_or(self.current_dt);
+            pair_summary.total_duration += end_dt - episode.start_dt;
+            pair_summary.max_spread = pair_summary.max_spread.max(episode.max_spread);
+            pair_summary.total_profit += episode.max_profit
+        }
+        summary.into_iter().collect()
+    }
+
+    fn update_quote(
+        &mut self,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>,
+        state: &ObState)
+    {
+        let get_best = |side: &[(Tick, Vec<(Lots, DateTime)>)]| side.first().map(
+            |(price, orders)| (*price, orders.iter().map(|(size, _)| *size).sum())
+        );
+        self.quotes.entry(traded_pair).or_default().insert(
+            exchange_id,
+            (get_best(&state.bids), get_best(&state.asks)),
+        );
+        self.check_crossing(traded_pair)
+    }
+
+    fn drop_quotes(
+        &mut self,
+        exchange_id: ExchangeID,
+        traded_pair: Option<TradedPair<Symbol, Settlement>>)
+    {
+        let mut affected = Vec::new();
+        for (pair, quotes) in &mut self.quotes {
+            if traded_pair.is_none_or(|traded_pair| traded_pair == *pair)
+                && quotes.remove(&exchange_id).is_some()
+            {
+                affected.push(*pair)
+            }
+        }
+        affected.sort_unstable();
+        affected.into_iter().for_each(|traded_pair| self.check_crossing(traded_pair))
+    }
+
+    fn check_crossing(&mut self, traded_pair: TradedPair<Symbol, Settlement>) {
+        let quotes = if let Some(quotes) = self.quotes.get(&traded_pair) {
+            quotes
+        } else {
+            return;
+        };
+        // Ties are broken in favour of the lowest exchange ID
+        let best_bid = quotes.iter()
+            .filter_map(|(exchange_id, (bid, _))| bid.map(|bid| (*exchange_id, bid)))
+            .reduce(|best, other| if other.1.0 > best.1.0 { other } else { best });
+        let best_ask = quotes.iter()
+            .filter_map(|(exchange_id, (_, ask))| ask.map(|ask| (*exchange_id, ask)))
+            .reduce(|best, other| if other.1.0 < best.1.0 { other } else { best });
+        let crossing = match (best_bid, best_ask) {
+            (Some((bid_exchange, (bid, bid_size))), Some((ask_exchange, (ask, ask_size))))
+            if bid > ask => {
+                let spread = bid - ask;
+                Some((bid_exchange, ask_exchange, spread, spread.0 * bid_size.0.min(ask_size.0)))
+            }
+            _ => None
+        };
+        match (crossing, self.open_episodes.get(&traded_pair)) {
+            (Some((bid_exchange, ask_exchange, spread, profit)), Some(&i)) => {
+                let episode = &mut self.episodes[i];
+                episode.max_spread = episode.max_spread.max(spread);
+                if profit > episode.max_profit {
+                    episode.bid_exchange = bid_exchange;
+                    episode.ask_exchange = ask_exchange;
+                    episode.max_profit = profit
+                }
+            }
+            (Some((bid_exchange, ask_exchange, spread, profit)), None) => {
+                self.open_episodes.insert(traded_pair, self.episodes.len());
+                self.episodes.push(
+                    CrossedVenueEpisode {
+                        traded_pair,
+                        bid_exchange,
+                        ask_exchange,
+                        start_dt: self.current_dt,
+                        end_dt: None,
+                        max_spread: spread,
+                        max_profit: profit,
+                    }
+                )
+            }
+            (None, Some(_)) => {
+                let i = self.open_episodes.remove(&traded_pair).unwrap_or_else(|| unreachable!());
+                self.episodes[i].end_dt = Some(self.current_dt)
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+impl<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
+TimeSync for CrossedVenueMonitor<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
+    where TraderID: Id,
+          BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    fn current_datetime_mut(&mut self) -> &mut DateTime { &mut self.current_dt }
+}
+
+impl<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
+Named<TraderID> for CrossedVenueMonitor<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
+    where TraderID: Id,
+          BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    fn get_name(&self) -> TraderID { self.name }
+}
+
+impl<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
+Agent for CrossedVenueMonitor<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
+    where TraderID: Id,
+          BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    type Action = TraderAction<
+        BasicTraderToBroker<BrokerID, ExchangeID, Symbol, Settlement>,
+        Nothing
+    >;
+}
+
+impl<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
+Latent
+for CrossedVenueMonitor<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
+    where TraderID: Id,
+          BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    type OuterID = BrokerID;
+    type LatencyGenerator = ConstantLatency<BrokerID, 0, 0>;
+
+    fn get_latency_generator(&self) -> Self::LatencyGenerator {
+        ConstantLatency::<BrokerID, 0, 0>::new()
+    }
+}
+
+impl<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
+Trader
+for CrossedVenueMonitor<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
+    where TraderID: Id,
+          BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    type TraderID = TraderID;
+    type BrokerID = BrokerID;
+
+    type B2T = BasicBrokerToTrader<TraderID, ExchangeID, Symbol, Settlement>;
+    type T2T = Nothing;
+    type T2B = BasicTraderToBroker<BrokerID, ExchangeID, Symbol, Settlement>;
+
+    fn wakeup<KerMsg: Ord>(
+        &mut self,
+        _: MessageReceiver<KerMsg>,
+        _: impl LatentActionProcessor<Self::Action, Self::BrokerID, KerMsg=KerMsg>,
+        _: Self::T2T,
+        _: &mut impl Rng,
+    ) {
+        unreachable!("Trader {} did not schedule any wakeups", self.get_name())
+    }
+
+    fn process_broker_reply<KerMsg: Ord>(
+        &mut self,
+        _: MessageReceiver<KerMsg>,
+        _: impl LatentActionProcessor<Self::Action, Self::BrokerID, KerMsg=KerMsg>,
+        reply: Self::B2T,
+        _: BrokerID,
+        _: &mut impl Rng,
+    ) {
+        let notification = if let BasicBrokerReply::ExchangeEventNotification(notification) =
+            reply.content
+        {
+            notification
+        } else {
+            return;
+        };
+        match notification {
+            ExchangeEventNotification::ObSnapshot(snapshot) => {
+                self.update_quote(reply.exchange_id, snapshot.traded_pair, &snapshot.state)
+            }
+            ExchangeEventNotification::TradesStopped(traded_pair) => {
+                self.drop_quotes(reply.exchange_id, Some(traded_pair))
+            }
+            ExchangeEventNotification::ExchangeClosed => self.drop_quotes(reply.exchange_id, None),
+            _ => {}
+        }
+    }
+
+    fn upon_register_at_broker(&mut self, _: BrokerID) {}
+}
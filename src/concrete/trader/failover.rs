@@ -0,0 +1,327 @@
+use {
+    crate::{
+        concrete::{
+            message_protocol::{
+                broker::reply::{BasicBrokerReply, BasicBrokerToTrader, CancellationReason},
+                trader::request::{BasicTraderRequest, BasicTraderToBroker},
+            },
+            order::{IcebergOrderPlacingRequest, LimitOrderPlacingRequest},
+            traded_pair::settlement::GetSettlementLag,
+            types::{AccountID, Lots, OrderID},
+        },
+        types::Id,
+        utils::collections::{HashMap, HashSet},
+    },
+};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+/// Limit order routed through the broker.
+struct RoutedOrder<BrokerID, ExchangeID, Symbol, Settlement>
+    where BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    broker_id: BrokerID,
+    exchange_id: ExchangeID,
+    account: Option<AccountID>,
+    /// Placing request with the size reduced by the fills.
+    request: LimitOrderPlacingRequest<Symbol, Settlement>,
+    /// Size of the displayed peaks if the order is the iceberg or the hidden one.
+    display_size: Option<Lots>,
+}
+
+/// Routes the requests of the trader registered with several brokers for the same exchange
+/// and fails over to the backup broker when the session of the active one goes down.
+///
+/// Brokers are tried in the order of preference, skipping the ones that have reported
+/// the [`BasicBrokerReply::SessionDown`]. The limit orders cancelled due to the outage
+/// are resubmitted, with the same IDs and the remaining sizes, through the next broker up.
+/// If there is none, the orders are held until some broker of the route is restored.
+pub struct BrokerFailover<BrokerID, ExchangeID, Symbol, Settlement>
+    where BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    routes: HashMap<ExchangeID, Vec<BrokerID>>,
+    down: HashSet<BrokerID>,
+    open_orders: HashMap<OrderID, RoutedOrder<BrokerID, ExchangeID, Symbol, Settlement>>,
+    stranded_orders: Vec<RoutedOrder<BrokerID, ExchangeID, Symbol, Settlement>>,
+    num_failovers: usize,
+}
+
+impl<BrokerID, ExchangeID, Symbol, Settlement> Default
+for BrokerFailover<BrokerID, ExchangeID, Symbol, Settlement>
+    where BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    fn default() -> Self {
+        Self {
+            routes: Default::default(),
+            down: Default::default(),
+            open_orders: Default::default(),
+            stranded_orders: vec![],
+            num_failovers: 0,
+        }
+    }
+}
+
+impl<BrokerID, ExchangeID, Symbol, Settlement>
+BrokerFailover<BrokerID, ExchangeID, Symbol, Settlement>
+    where BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    /// Creates a new instance of the `BrokerFailover`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the brokers to route the requests concerning the exchange through.
+    /// Requests concerning the exchanges without the route are passed unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `exchange_id` — Exchange.
+    /// * `brokers` — Brokers the trader is registered with, in the order of preference.
+    pub fn with_route(
+        mut self,
+        exchange_id: ExchangeID,
+        brokers: impl IntoIterator<Item=BrokerID>) -> Self
+    {
+        let brokers: Vec<_> = brokers.into_iter().collect();
+        if brokers.is_empty() {
+            panic!("Route to Exchange {exchange_id} should list at least one broker")
+        }
+        self.routes.insert(exchange_id, brokers);
+        self
+    }
+
+    /// Returns the most preferred broker of the route which session is up.
+    ///
+    /// # Arguments
+    ///
+    /// * `exchange_id` — Exchange.
+    pub fn get_active_broker(&self, exchange_id: ExchangeID) -> Option<BrokerID> {
+        self.routes.get(&exchange_id)?
+            .iter()
+            .find(|broker_id| !self.down.contains(broker_id))
+            .copied()
+    }
+
+    /// Returns whether the broker has reported its session down.
+    ///
+    /// # Arguments
+    ///
+    /// * `broker_id` — Broker.
+    pub fn is_broker_down(&self, broker_id: BrokerID) -> bool {
+        self.down.contains(&broker_id)
+    }
+
+    /// Returns the number of the limit orders resubmitted through the backup brokers.
+    pub fn get_num_failovers(&self) -> usize {
+        self.num_failovers
+    }
+
+    /// Routes the request. Cancellations and size reductions are sent to the broker
+    /// holding the order, heartbeats and subscriptions are sent to the broker
+    /// they are addressed to, while other requests are sent to the active broker or,
+    /// if all brokers are down, to the most preferred one, which rejects them.
+    /// Returns `None` if the request is the cancellation or the size reduction
+    /// of the order held by the failover, which is then applied to the held order.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` — Request to route. Its `broker_id` is overwritten.
+    pub fn route(
+        &mut self,
+        mut request: BasicTraderToBroker<BrokerID, ExchangeID, Symbol, Settlement>,
+    ) -> Option<BasicTraderToBroker<BrokerID, ExchangeID, Symbol, Settlement>>
+    {
+        let exchange_id = match request.content {
+            BasicTraderRequest::CancelLimitOrder(cancel, exchange_id) => {
+                if let Some(order) = self.open_orders.get(&cancel.order_id) {
+                    request.broker_id = order.broker_id;
+                    return Some(request);
+                }
+                let stranded = self.stranded_orders.iter().position(
+                    |order| order.request.order_id == cancel.order_id
+                );
+                if let Some(i) = stranded {
+                    self.stranded_orders.remove(i);
+                    return None;
+                }
+                exchange_id
+            }
+            BasicTraderRequest::ReduceOrderSize(reduction, exchange_id) => {
+                if let Some(order) = self.open_orders.get(&reduction.order_id) {
+                    request.broker_id = order.broker_id;
+                    return Some(request);
+                }
+                let stranded = self.stranded_orders.iter().position(
+                    |order| order.request.order_id == reduction.order_id
+                );
+                if let Some(i) = stranded {
+                    let order = &mut self.stranded_orders[i].request;
+                    if reduction.size < order.size {
+                        order.size -= reduction.size
+                    } else {
+                        self.stranded_orders.remove(i);
+                    }
+                    return None;
+                }
+                exchange_id
+            }
+            BasicTraderRequest::QueryOrderStatus(query, exchange_id) => {
+                if let Some(order) = self.open_orders.get(&query.order_id) {
+                    request.broker_id = order.broker_id;
+                    return Some(request);
+                }
+                exchange_id
+            }
+            // Heartbeats, subscriptions and close-outs are addressed to the particular broker
+            BasicTraderRequest::Heartbeat(..)
+            | BasicTraderRequest::Subscribe(..)
+            | BasicTraderRequest::CloseOutPosition(..) => {
+                return Some(request);
+            }
+            BasicTraderRequest::PlaceLimitOrder(_, exchange_id) => exchange_id,
+            BasicTraderRequest::PlaceIcebergOrder(_, exchange_id) => exchange_id,
+            BasicTraderRequest::PlaceMarketOrder(_, exchange_id) => exchange_id,
+            BasicTraderRequest::PlaceStopOrder(_, exchange_id) => exchange_id,
+            BasicTraderRequest::PlaceStopLimitOrder(_, exchange_id) => exchange_id,
+            BasicTraderRequest::RequestQuote(_, exchange_id) => exchange_id,
+            BasicTraderRequest::AcceptQuote(_, exchange_id) => exchange_id,
+        };
+        let route = if let Some(route) = self.routes.get(&exchange_id) {
+            route
+        } else {
+            return Some(request);
+        };
+        request.broker_id = self.get_active_broker(exchange_id).unwrap_or(route[0]);
+        let routed_order = match request.content {
+            BasicTraderRequest::PlaceLimitOrder(placing, _) => Some((placing, None)),
+            BasicTraderRequest::PlaceIcebergOrder(placing, _) => {
+                Some((placing.into(), Some(placing.display_size)))
+            }
+            _ => None
+        };
+        if let Some((placing, display_size)) = routed_order {
+            self.open_orders.insert(
+                placing.order_id,
+                RoutedOrder {
+                    broker_id: request.broker_id,
+                    exchange_id,
+                    account: request.account,
+                    request: placing,
+                    display_size,
+                },
+            );
+        }
+        Some(request)
+    }
+
+    /// Updates the state of the brokers and the orders with the reply.
+    /// Returns the requests resubmitting the orders cancelled due to the session outage
+    /// or held since all brokers of their routes have been down.
+    ///
+    /// # Arguments
+    ///
+    /// * `reply` — Reply received from the broker.
+    /// * `broker_id` — Broker the reply is received from.
+    pub fn process_reply<TraderID: Id>(
+        &mut self,
+        reply: &BasicBrokerToTrader<TraderID, ExchangeID, Symbol, Settlement>,
+        broker_id: BrokerID,
+    ) -> Vec<BasicTraderToBroker<BrokerID, ExchangeID, Symbol, Settlement>>
+    {
+        let order_id = match &reply.content {
+            BasicBrokerReply::SessionDown => {
+                self.down.insert(broker_id);
+                return vec![];
+            }
+            BasicBrokerReply::SessionUp => {
+                self.down.remove(&broker_id);
+                let stranded = std::mem::take(&mut self.stranded_orders);
+                return stranded.into_iter()
+                    .filter_map(|order| self.resubmit(order))
+                    .collect();
+            }
+            BasicBrokerReply::OrderPartiallyExecuted(executed) => {
+                if let Some(order) = self.open_orders.get_mut(&executed.order_id) {
+                    if order.broker_id == broker_id {
+                        order.request.size -= executed.size
+                    }
+                }
+                return vec![];
+            }
+            BasicBrokerReply::OrderSizeReduced(reduced) => {
+                if let Some(order) = self.open_orders.get_mut(&reduced.order_id) {
+                    if order.broker_id == broker_id {
+                        order.request.size = reduced.remaining_size
+                    }
+                }
+                return vec![];
+            }
+            BasicBrokerReply::OrderPlacementDiscarded(discarded) => discarded.order_id,
+            BasicBrokerReply::OrderExecuted(executed) => executed.order_id,
+            BasicBrokerReply::ShadowOrderExecuted(executed) => executed.order_id,
+            BasicBrokerReply::GhostOrderExecuted(executed) => executed.order_id,
+            BasicBrokerReply::OrderCancelled(cancelled) => cancelled.order_id,
+            _ => return vec![]
+        };
+        let order = match self.open_orders.get(&order_id) {
+            Some(order) if order.broker_id == broker_id => *order,
+            _ => return vec![]
+        };
+        self.open_orders.remove(&order_id);
+        match &reply.content {
+            BasicBrokerReply::OrderCancelled(cancelled)
+            if cancelled.reason == CancellationReason::SessionDown => {
+                self.resubmit(order).into_iter().collect()
+            }
+            _ => vec![]
+        }
+    }
+
+    fn resubmit(
+        &mut self,
+        mut order: RoutedOrder<BrokerID, ExchangeID, Symbol, Settlement>,
+    ) -> Option<BasicTraderToBroker<BrokerID, ExchangeID, Symbol, Settlement>>
+    {
+        let broker_id = if let Some(broker_id) = self.get_active_broker(order.exchange_id) {
+            broker_id
+        } else {
+            self.stranded_orders.push(order);
+            return None;
+        };
+        if broker_id != order.broker_id {
+            self.num_failovers += 1
+        }
+        order.broker_id = broker_id;
+        self.open_orders.insert(order.request.order_id, order);
+        let RoutedOrder { exchange_id, request, display_size, .. } = order;
+        let content = if let Some(display_size) = display_size {
+            let LimitOrderPlacingRequest { traded_pair, order_id, direction, price, size, .. } =
+                request;
+            BasicTraderRequest::PlaceIcebergOrder(
+                IcebergOrderPlacingRequest {
+                    traded_pair,
+                    order_id,
+                    direction,
+                    price,
+                    size,
+                    display_size,
+                },
+                exchange_id,
+            )
+        } else {
+            BasicTraderRequest::PlaceLimitOrder(request, exchange_id)
+        };
+        Some(BasicTraderToBroker { broker_id, account: order.account, content })
+    }
+}
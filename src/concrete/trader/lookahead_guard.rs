@@ -0,0 +1 @@
+pub use crate::kernel::lookahead::{Delivered, LookaheadGuard, LookaheadPolicy};
@@ -0,0 +1,188 @@
+use {crate::concrete::types::Lots, std::{collections::BTreeMap, fmt::Debug}};
+
+#[derive(Debug, Clone, PartialEq)]
+/// Constraints of the portfolio weights. Absent constraints are not applied.
+/// Weights are signed shares of the capital, negative ones stand for the short positions.
+pub struct PortfolioConstraints<Sector: Ord> {
+    /// Maximum absolute weight of the single instrument.
+    pub max_weight: Option<f64>,
+    /// Maximum sum of the absolute weights of the instruments of the sector.
+    pub sector_caps: BTreeMap<Sector, f64>,
+    /// Maximum sum of the absolute weight changes of the single rebalancing.
+    pub max_turnover: Option<f64>,
+}
+
+impl<Sector: Ord> Default for PortfolioConstraints<Sector> {
+    fn default() -> Self {
+        Self { max_weight: None, sector_caps: Default::default(), max_turnover: None }
+    }
+}
+
+/// Computes the rebalancing trades bringing the portfolio as close to the desired weights
+/// as the [`PortfolioConstraints`] allow.
+///
+/// The desired weights are adjusted in the following order:
+///
+/// 1. Weights are clipped to the `max_weight`.
+/// 2. Weights of every sector exceeding its cap are scaled down proportionally.
+/// 3. If the turnover exceeds the `max_turnover`, the weight changes are scaled down
+///    proportionally, i.e. the portfolio moves only part of the way to the adjusted weights.
+///
+/// If the current weights satisfy the constraints, so do the resulting ones.
+///
+/// # Examples
+///
+/// ```
+/// use {
+///     std::collections::BTreeMap,
+///     trading_backtester::concrete::{
+///         trader::optimizer::{PortfolioConstraints, PortfolioOptimizer},
+///         types::Lots,
+///     },
+/// };
+///
+/// let constraints = PortfolioConstraints {
+///     max_weight: Some(0.5),
+///     sector_caps: BTreeMap::from([("Tech", 0.6)]),
+///     max_turnover: None,
+/// };
+/// let optimizer = PortfolioOptimizer::new(constraints)
+///     .with_sector("AAPL", "Tech")
+///     .with_sector("MSFT", "Tech");
+///
+/// let desired = BTreeMap::from([("AAPL", 0.6), ("MSFT", 0.6)]);
+/// let prices = BTreeMap::from([("AAPL", 100.0), ("MSFT", 200.0)]);
+/// let trades = optimizer.compute_trades(1000.0, &prices, &BTreeMap::new(), &desired);
+/// assert_eq!(trades, BTreeMap::from([("AAPL", Lots(3)), ("MSFT", Lots(2))]));
+/// ```
+pub struct PortfolioOptimizer<Instrument: Ord, Sector: Ord> {
+    constraints: PortfolioConstraints<Sector>,
+    sectors: BTreeMap<Instrument, Sector>,
+}
+
+impl<Instrument, Sector> PortfolioOptimizer<Instrument, Sector>
+    where Instrument: Ord + Copy + Debug,
+          Sector: Ord + Copy
+{
+    /// Creates a new instance of the `PortfolioOptimizer`.
+    ///
+    /// # Arguments
+    ///
+    /// * `constraints` — Constraints of the portfolio weights.
+    pub fn new(constraints: PortfolioConstraints<Sector>) -> Self {
+        let caps = constraints.max_weight.iter()
+            .chain(constraints.sector_caps.values())
+            .chain(constraints.max_turnover.iter());
+        for cap in caps {
+            if cap.is_nan() || *cap < 0.0 {
+                panic!("Portfolio constraints should be non-negative. Got: {cap}")
+            }
+        }
+        Self { constraints, sectors: Default::default() }
+    }
+
+    /// Assigns the instrument to the sector.
+    /// Instruments without the sector are not subject to the sector caps.
+    ///
+    /// # Arguments
+    ///
+    /// * `instrument` — Instrument.
+    /// * `sector` — Sector of the instrument.
+    pub fn with_sector(mut self, instrument: Instrument, sector: Sector) -> Self {
+        self.sectors.insert(instrument, sector);
+        self
+    }
+
+    /// Returns the weights the portfolio should be rebalanced to.
+    ///
+    /// # Arguments
+    ///
+    /// * `current` — Current weights. Absent instruments have zero weights.
+    /// * `desired` — Desired weights. Absent instruments have zero weights.
+    pub fn optimize_weights(
+        &self,
+        current: &BTreeMap<Instrument, f64>,
+        desired: &BTreeMap<Instrument, f64>) -> BTreeMap<Instrument, f64>
+    {
+        let PortfolioConstraints { max_weight, sector_caps, max_turnover } = &self.constraints;
+        let mut weights: BTreeMap<Instrument, f64> = current.keys()
+            .chain(desired.keys())
+            .map(|instrument| (*instrument, desired.get(instrument).copied().unwrap_or(0.0)))
+            .collect();
+        if let Some(max_weight) = max_weight {
+            for weight in weights.values_mut() {
+                *weight = weight.clamp(-max_weight, *max_weight)
+            }
+        }
+        for (sector, cap) in sector_caps {
+            let exposure: f64 = weights.iter()
+                .filter(|(instrument, _)| self.sectors.get(instrument) == Some(sector))
+                .map(|(_, weight)| weight.abs())
+                .sum();
+            if exposure <= *cap {
+                continue;
+            }
+            let scale = cap / exposure;
+            weights.iter_mut()
+                .filter(|(instrument, _)| self.sectors.get(instrument) == Some(sector))
+                .for_each(|(_, weight)| *weight *= scale)
+        }
+        if let Some(max_turnover) = max_turnover {
+            let get_current = |instrument: &Instrument| {
+                current.get(instrument).copied().unwrap_or(0.0)
+            };
+            let turnover: f64 = weights.iter()
+                .map(|(instrument, weight)| (weight - get_current(instrument)).abs())
+                .sum();
+            if turnover > *max_turnover {
+                let scale = max_turnover / turnover;
+                for (instrument, weight) in &mut weights {
+                    let current = get_current(instrument);
+                    *weight = current + (*weight - current) * scale
+                }
+            }
+        }
+        weights
+    }
+
+    /// Returns the trades, in lots, rebalancing the portfolio to the optimized weights.
+    /// Instruments that do not need trading are omitted.
+    ///
+    /// # Arguments
+    ///
+    /// * `capital` — Capital of the portfolio.
+    /// * `lot_prices` — Prices of one lot of every traded instrument, in units of the capital.
+    /// * `positions` — Current positions. Absent instruments have zero positions.
+    /// * `desired` — Desired weights. Absent instruments have zero weights.
+    pub fn compute_trades(
+        &self,
+        capital: f64,
+        lot_prices: &BTreeMap<Instrument, f64>,
+        positions: &BTreeMap<Instrument, Lots>,
+        desired: &BTreeMap<Instrument, f64>) -> BTreeMap<Instrument, Lots>
+    {
+        if capital.is_nan() || capital <= 0.0 {
+            panic!("Capital should be positive. Got: {capital}")
+        }
+        let get_lot_price = |instrument: &Instrument| *lot_prices.get(instrument).unwrap_or_else(
+            || panic!("Lot price of the {instrument:?} is not provided")
+        );
+        let current = positions.iter()
+            .map(
+                |(instrument, size)| {
+                    (*instrument, size.0 as f64 * get_lot_price(instrument) / capital)
+                }
+            )
+            .collect();
+        self.optimize_weights(&current, desired).into_iter()
+            .filter_map(
+                |(instrument, weight)| {
+                    let target = (weight * capital / get_lot_price(&instrument)).round() as i64;
+                    let position = positions.get(&instrument).map_or(0, |size| size.0);
+                    let trade = target - position;
+                    (trade != 0).then_some((instrument, Lots(trade)))
+                }
+            )
+            .collect()
+    }
+}
@@ -0,0 +1,439 @@
+use {
+    crate::{
+        concrete::{
+            latency::ConstantLatency,
+            message_protocol::{
+                broker::reply::{BasicBrokerReply, BasicBrokerToTrader},
+                exchange::reply::ExchangeEventNotification,
+                trader::request::BasicTraderToBroker,
+            },
+            traded_pair::{Asset, settlement::GetSettlementLag, TradedPair},
+            types::{Lots, ObState, Tick, TickSize},
+        },
+        interface::{latency::Latent, trader::{Trader, TraderAction}},
+        kernel::LatentActionProcessor,
+        types::{Agent, Date, DateTime, Id, Named, Nothing, TimeSync},
+        utils::{
+            collections::HashMap,
+            metadata::{MetadataPlacement, SimulationMetadata},
+            queue::MessageReceiver,
+        },
+    },
+    rand::Rng,
+    std::{fs::File, io::Write, marker::PhantomData, path::{Path, PathBuf}},
+};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+/// Field recorded by the [`QuoteRecorder`].
+/// Sizes are summed over the orders of the price levels within the depth of the recorder.
+pub enum QuoteField {
+    /// Best bid price.
+    BidPrice,
+    /// Size of the bids.
+    BidSize,
+    /// Best ask price.
+    AskPrice,
+    /// Size of the asks.
+    AskSize,
+    /// Mean of the best bid and ask prices.
+    MidPrice,
+    /// Difference between the best ask and bid prices.
+    Spread,
+    /// Mean of the best bid and ask prices weighted by the sizes of the opposite sides.
+    Microprice,
+    /// Difference between the sizes of the bids and the asks divided by their sum.
+    Imbalance,
+}
+
+impl QuoteField {
+    fn get_column_name(self) -> &'static str {
+        match self {
+            Self::BidPrice => "BID_PRICE",
+            Self::BidSize => "BID_SIZE",
+            Self::AskPrice => "ASK_PRICE",
+            Self::AskSize => "ASK_SIZE",
+            Self::MidPrice => "MID_PRICE",
+            Self::Spread => "SPREAD",
+            Self::Microprice => "MICROPRICE",
+            Self::Imbalance => "IMBALANCE"
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+/// Output format of the [`QuoteRecorder`].
+pub enum QuoteFormat {
+    /// Comma-separated values with the header.
+    Csv,
+    /// Tab-separated values with the header.
+    Tsv,
+    /// JSON object per line keyed by the lowercase column names.
+    JsonLines,
+}
+
+/// [`Trader`] that records the configurable fields of the OB-snapshots
+/// into the separate file per exchange and traded pair.
+///
+/// Generalizes the [`SpreadWriter`](crate::concrete::trader::SpreadWriter),
+/// whose output is reproduced by the default fields and format.
+/// Snapshots with either side empty are skipped.
+pub struct QuoteRecorder<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
+    where TraderID: Id,
+          BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    name: TraderID,
+    current_dt: DateTime,
+    price_step: TickSize,
+    path_template: String,
+    fields: Vec<QuoteField>,
+    depth: usize,
+    format: QuoteFormat,
+    precision: usize,
+    metadata: Option<(SimulationMetadata, MetadataPlacement)>,
+    files: HashMap<(ExchangeID, TradedPair<Symbol, Settlement>), (File, PathBuf)>,
+    phantom: PhantomData<BrokerID>,
+}
+
+impl<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
+QuoteRecorder<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
+    where TraderID: Id,
+          BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    /// Creates a new instance of the `QuoteRecorder` recording the best bid-offer
+    /// with the 4-digit precision into the csv-files.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` — ID of the `QuoteRecorder`.
+    /// * `price_step` — Price quotation step.
+    /// * `path_template` — Path to the files to create. `{exchange}` and `{pair}` placeholders
+    ///   are replaced with the exchange ID and the quoted and settlement symbols of the pair,
+    ///   e.g. `quotes_{exchange}_{pair}.csv`.
+    pub fn new(
+        name: TraderID,
+        price_step: impl Into<TickSize>,
+        path_template: impl Into<String>) -> Self
+    {
+        QuoteRecorder {
+            name,
+            current_dt: Date::from_ymd_opt(1970, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+            price_step: price_step.into(),
+            path_template: path_template.into(),
+            fields: vec![
+                QuoteField::BidPrice,
+                QuoteField::BidSize,
+                QuoteField::AskPrice,
+                QuoteField::AskSize,
+            ],
+            depth: 1,
+            format: QuoteFormat::Csv,
+            precision: 4,
+            metadata: None,
+            files: Default::default(),
+            phantom: Default::default(),
+        }
+    }
+
+    /// Sets the recorded fields in the order of the columns.
+    ///
+    /// # Arguments
+    ///
+    /// * `fields` — Fields to record.
+    pub fn with_fields(mut self, fields: impl IntoIterator<Item=QuoteField>) -> Self {
+        self.fields = fields.into_iter().collect();
+        if self.fields.is_empty() {
+            panic!("QuoteRecorder {} should record at least one field", self.name)
+        }
+        self
+    }
+
+    /// Sets the number of the price levels the sizes, the microprice and the imbalance
+    /// are computed over. By default, only the best levels are used.
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` — Number of the price levels.
+    pub fn with_depth(mut self, depth: usize) -> Self {
+        if depth == 0 {
+            panic!("QuoteRecorder {} depth should be positive", self.name)
+        }
+        self.depth = depth;
+        self
+    }
+
+    /// Sets the output format.
+    ///
+    /// # Arguments
+    ///
+    /// * `format` — Output format.
+    pub fn with_format(mut self, format: QuoteFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets the number of the digits after the decimal point of the non-size fields.
+    ///
+    /// # Arguments
+    ///
+    /// * `precision` — Number of the digits.
+    pub fn with_precision(mut self, precision: usize) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Stamps the files with the [`SimulationMetadata`] when they are created.
+    ///
+    /// # Arguments
+    ///
+    /// * `metadata` — Metadata to stamp the files with.
+    /// * `placement` — Whether to write the metadata into the header or into the sidecar file.
+    ///   Header is written as the comment lines, so it breaks the strict JSON Lines parsers.
+    pub fn with_metadata(
+        mut self,
+        metadata: &SimulationMetadata,
+        placement: MetadataPlacement) -> Self
+    {
+        self.metadata = Some((metadata.clone(), placement));
+        self
+    }
+
+    /// Returns the paths to the files created so far.
+    pub fn get_paths(&self) -> impl Iterator<Item=&Path> {
+        self.files.values().map(|(_, path)| path.as_path())
+    }
+
+    fn get_path(
+        &self,
+        exchange_id: ExchangeID,
+        traded_pair: TradedPair<Symbol, Settlement>) -> PathBuf
+    {
+        let pair = format!(
+            "{}_{}",
+            get_symbol(traded_pair.quoted_asset),
+            get_symbol(traded_pair.settlement_asset)
+        );
+        self.path_template
+            .replace("{exchange}", &exchange_id.to_string())
+            .replace("{pair}", &pair)
+            .into()
+    }
+
+    fn create_file(&self, path: &Path) -> File {
+        let file = File::create(path).unwrap_or_else(
+            |err| panic!("Cannot create file {path:?}. Error: {err}")
+        );
+        match &self.metadata {
+            Some((metadata, MetadataPlacement::Header)) => metadata.write_header(&file)
+                .unwrap_or_else(|err| panic!("Cannot write to file {path:?}. Error: {err}")),
+            Some((metadata, MetadataPlacement::Sidecar)) => metadata.write_sidecar(path),
+            None => {}
+        }
+        let separator = match self.format {
+            QuoteFormat::Csv => ",",
+            QuoteFormat::Tsv => "\t",
+            QuoteFormat::JsonLines => return file
+        };
+        let header = self.fields.iter()
+            .map(|field| field.get_column_name())
+            .fold(String::from("Timestamp"), |header, name| header + separator + name);
+        writeln!(&file, "{header}")
+            .unwrap_or_else(|err| panic!("Cannot write to file {path:?}. Error: {err}"));
+        file
+    }
+
+    fn format_row(&self, event_dt: DateTime, state: &ObState) -> Option<String> {
+        let (bid, bid_size) = self.get_side(&state.bids)?;
+        let (ask, ask_size) = self.get_side(&state.asks)?;
+        let bid_price = bid.to_f64(self.price_step);
+        let ask_price = ask.to_f64(self.price_step);
+        if bid_price >= ask_price {
+            panic!("Bid price should be lower than Ask price. Got: {bid_price:.4} {ask_price:.4}")
+        }
+        let total_size = (bid_size.0 + ask_size.0) as f64;
+        let precision = self.precision;
+        let values = self.fields.iter().map(
+            |field| match field {
+                QuoteField::BidPrice => format!("{bid_price:.precision$}"),
+                QuoteField::BidSize => bid_size.to_string(),
+                QuoteField::AskPrice => format!("{ask_price:.precision$}"),
+                QuoteField::AskSize => ask_size.to_string(),
+                QuoteField::MidPrice => format!("{:.precision$}", (bid_price + ask_price) / 2.0),
+                QuoteField::Spread => format!("{:.precision$}", ask_price - bid_price),
+                QuoteField::Microprice => format!(
+                    "{:.precision$}",
+                    (bid_price * ask_size.0 as f64 + ask_price * bid_size.0 as f64) / total_size
+                ),
+                QuoteField::Imbalance => format!(
+                    "{:.precision$}",
+                    (bid_size.0 - ask_size.0) as f64 / total_size
+                ),
+            }
+        );
+        let row = match self.format {
+            QuoteFormat::Csv | QuoteFormat::Tsv => {
+                let separator = if self.format == QuoteFormat::Csv { ',' } else { '\t' };
+                values.fold(
+                    event_dt.to_string(),
+                    |mut row, value| {
+                        row.push(separator);
+                        row + &value
+                    },
+                )
+            }
+            QuoteFormat::JsonLines => {
+                let values = self.fields.iter().zip(values).fold(
+                    format!("\"timestamp\":\"{event_dt}\""),
+                    |row, (field, value)| format!(
+                        "{row},\"{}\":{value}", field.get_column_name().to_lowercase()
+                    ),
+                );
+                format!("{{{values}}}")
+            }
+        };
+        Some(row)
+    }
+
+    /// Returns the best price and the size of the side within the depth.
+    fn get_side(&self, levels: &[(Tick, Vec<(Lots, DateTime)>)]) -> Option<(Tick, Lots)> {
+        let (best, _) = levels.first()?;
+        let size = levels.iter()
+            .take(self.depth)
+            .flat_map(|(_, orders)| orders)
+            .map(|(size, _dt)| *size)
+            .sum();
+        Some((*best, size))
+    }
+}
+
+fn get_symbol<Symbol: Id>(asset: Asset<Symbol>) -> Symbol {
+    match asset {
+        Asset::Base(asset) => asset.symbol,
+        Asset::Futures(asset) => asset.symbol,
+        Asset::OptionContract(asset) => asset.symbol
+    }
+}
+
+impl<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
+TimeSync for QuoteRecorder<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
+    where TraderID: Id,
+          BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    fn current_datetime_mut(&mut self) -> &mut DateTime { &mut self.current_dt }
+}
+
+impl<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
+Named<TraderID> for QuoteRecorder<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
+    where TraderID: Id,
+          BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    fn get_name(&self) -> TraderID { self.name }
+}
+
+impl<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
+Agent for QuoteRecorder<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
+    where TraderID: Id,
+          BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    type Action = TraderAction<
+        BasicTraderToBroker<BrokerID, ExchangeID, Symbol, Settlement>,
+        Nothing
+    >;
+}
+
+impl<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
+Latent
+for QuoteRecorder<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
+    where TraderID: Id,
+          BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    type OuterID = BrokerID;
+    type LatencyGenerator = ConstantLatency<BrokerID, 0, 0>;
+
+    fn get_latency_generator(&self) -> Self::LatencyGenerator {
+        ConstantLatency::<BrokerID, 0, 0>::new()
+    }
+}
+
+impl<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
+Trader
+for QuoteRecorder<TraderID, BrokerID, ExchangeID, Symbol, Settlement>
+    where TraderID: Id,
+          BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    type TraderID = TraderID;
+    type BrokerID = BrokerID;
+
+    type B2T = BasicBrokerToTrader<TraderID, ExchangeID, Symbol, Settlement>;
+    type T2T = Nothing;
+    type T2B = BasicTraderToBroker<BrokerID, ExchangeID, Symbol, Settlement>;
+
+    fn wakeup<KerMsg: Ord>(
+        &mut self,
+        _: MessageReceiver<KerMsg>,
+        _: impl LatentActionProcessor<Self::Action, Self::BrokerID, KerMsg=KerMsg>,
+        _: Self::T2T,
+        _: &mut impl Rng,
+    ) {
+        unreachable!("Trader {} did not schedule any wakeups", self.get_name())
+    }
+
+    fn process_broker_reply<KerMsg: Ord>(
+        &mut self,
+        _: MessageReceiver<KerMsg>,
+        _: impl LatentActionProcessor<Self::Action, Self::BrokerID, KerMsg=KerMsg>,
+        reply: Self::B2T,
+        _: BrokerID,
+        _: &mut impl Rng,
+    ) {
+        let snapshot = if let BasicBrokerReply::ExchangeEventNotification(
+            ExchangeEventNotification::ObSnapshot(snapshot)) = reply.content
+        {
+            snapshot
+        } else {
+            return;
+        };
+        let row = if let Some(row) = self.format_row(reply.event_dt, &snapshot.state) {
+            row
+        } else {
+            return;
+        };
+        let key = (reply.exchange_id, snapshot.traded_pair);
+        if !self.files.contains_key(&key) {
+            let path = self.get_path(reply.exchange_id, snapshot.traded_pair);
+            if self.get_paths().any(|other| other == path) {
+                panic!(
+                    "QuoteRecorder {} path template {:?} maps several traded pairs to {path:?}",
+                    self.name, self.path_template
+                )
+            }
+            let file = self.create_file(&path);
+            self.files.insert(key, (file, path));
+        }
+        let (file, path) = self.files.get_mut(&key).unwrap_or_else(|| unreachable!());
+        writeln!(file, "{row}")
+            .unwrap_or_else(|err| panic!("Cannot write to file {path:?}. Error: {err}"))
+    }
+
+    fn upon_register_at_broker(&mut self, _: BrokerID) {}
+}
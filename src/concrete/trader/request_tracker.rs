@@ -0,0 +1,553 @@
+use {
+    crate::{
+        concrete::{
+            message_protocol::{
+                broker::reply::{BasicBrokerReply, BasicBrokerToTrader},
+                trader::request::{BasicTraderRequest, BasicTraderToBroker},
+            },
+            traded_pair::{settlement::GetSettlementLag, TradedPair},
+            types::OrderID,
+        },
+        interface::{
+            message::TraderToItself,
+            trader::{TraderAction, TraderActionKind},
+        },
+        types::{DateTime, Id, NanoDuration},
+        utils::collections::HashMap,
+    },
+    std::collections::hash_map::Entry::Occupied,
+};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+/// Kind of the tracked request.
+pub enum RequestKind {
+    /// Limit order placing request.
+    PlaceLimitOrder,
+    /// Iceberg or hidden order placing request.
+    PlaceIcebergOrder,
+    /// Market order placing request.
+    PlaceMarketOrder,
+    /// Stop order placing request.
+    PlaceStopOrder,
+    /// Stop-limit order placing request.
+    PlaceStopLimitOrder,
+    /// Limit order cancel request.
+    CancelLimitOrder,
+    /// Limit order size reduction request.
+    ReduceOrderSize,
+    /// Request for quote.
+    RequestQuote,
+    /// Firm quote acceptance.
+    AcceptQuote,
+    /// Order status request.
+    QueryOrderStatus,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+/// [`Trader`](crate::interface::trader::Trader)-to-itself message
+/// scheduled by the [`RequestTracker`] to check whether the request has been acknowledged.
+pub struct RequestTimeout {
+    /// ID of the order the request refers to.
+    pub order_id: OrderID,
+    /// Kind of the request.
+    pub kind: RequestKind,
+    /// Sequence number of the request.
+    /// Distinguishes requests of the same kind referring to the same order.
+    pub request_no: u64,
+}
+
+impl TraderToItself for RequestTimeout {}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+/// Request that has not been acknowledged yet.
+pub struct PendingRequest<BrokerID, ExchangeID, Symbol, Settlement>
+    where BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    /// Broker the request was sent to.
+    pub broker_id: BrokerID,
+    /// Exchange the request refers to.
+    pub exchange_id: ExchangeID,
+    /// Traded pair the request refers to.
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    /// Kind of the request.
+    pub kind: RequestKind,
+    /// Datetime the request was sent at.
+    pub sent_dt: DateTime,
+    /// Sequence number of the request.
+    pub request_no: u64,
+}
+
+/// Correlates outgoing order requests with incoming broker replies by order ID
+/// and schedules timeout checks for the unacknowledged ones.
+///
+/// Any reply concerning the order acknowledges the pending placing request,
+/// whereas only `OrderCancelled` and `CannotCancelOrder` acknowledge the pending cancel request.
+/// `OrderStatus` acknowledges only the pending order status request.
+/// Quote requests and acceptances are correlated by the request ID.
+/// `FirmQuote` acknowledges the former, `QuoteTraded` acknowledges the latter,
+/// and `QuoteRejected` acknowledges either of them.
+pub struct RequestTracker<BrokerID, ExchangeID, Symbol, Settlement>
+    where BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    timeout: NanoDuration,
+    next_request_no: u64,
+    pending: HashMap<
+        (OrderID, RequestKind),
+        PendingRequest<BrokerID, ExchangeID, Symbol, Settlement>
+    >,
+}
+
+impl<BrokerID, ExchangeID, Symbol, Settlement>
+RequestTracker<BrokerID, ExchangeID, Symbol, Settlement>
+    where BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    /// Creates a new instance of the `RequestTracker`.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` — Time to wait for the acknowledgement of the request.
+    pub fn new(timeout: NanoDuration) -> Self {
+        Self {
+            timeout,
+            next_request_no: 0,
+            pending: Default::default(),
+        }
+    }
+
+    /// Starts tracking the request. Returns the action sending the request to the broker
+    /// and the action scheduling the corresponding [`RequestTimeout`] check.
+    /// Heartbeats, which are monitored by the
+    /// [`HeartbeatMonitor`](crate::concrete::heartbeat::HeartbeatMonitor),
+    /// as well as subscriptions and position close-outs, which have no IDs,
+    /// are sent without being tracked.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` — Request to send.
+    /// * `current_dt` — Current datetime of the trader.
+    pub fn track<T2T>(
+        &mut self,
+        request: BasicTraderToBroker<BrokerID, ExchangeID, Symbol, Settlement>,
+        current_dt: DateTime,
+    ) -> Vec<TraderAction<BasicTraderToBroker<BrokerID, ExchangeID, Symbol, Settlement>, T2T>>
+        where T2T: TraderToItself + From<RequestTimeout>
+    {
+        let send = TraderAction {
+            delay: NanoDuration::ZERO,
+            content: TraderActionKind::TraderToBroker(request),
+        };
+        let (order_id, traded_pair, kind, exchange_id) = match request.content {
+            BasicTraderRequest::CancelLimitOrder(request, exchange_id) => {
+                (request.order_id, request.traded_pair, RequestKind::CancelLimitOrder, exchange_id)
+            }
+            BasicTraderRequest::ReduceOrderSize(request, exchange_id) => {
+                (request.order_id, request.traded_pair, RequestKind::ReduceOrderSize, exchange_id)
+            }
+            BasicTraderRequest::PlaceLimitOrder(request, exchange_id) => {
+                (request.order_id, request.traded_pair, RequestKind::PlaceLimitOrder, exchange_id)
+            }
+            BasicTraderRequest::PlaceIcebergOrder(request, exchange_id) => {
+                (request.order_id, request.traded_pair, RequestKind::PlaceIcebergOrder, exchange_id)
+            }
+            BasicTraderRequest::PlaceMarketOrder(request, exchange_id) => {
+                (request.order_id, request.traded_pair, RequestKind::PlaceMarketOrder, exchange_id)
+            }
+            BasicTraderRequest::PlaceStopOrder(request, exchange_id) => {
+                (request.order_id, request.traded_pair, RequestKind::PlaceStopOrder, exchange_id)
+            }
+            BasicTraderRequest::PlaceStopLimitOrder(request, exchange_id) => (
+                request.order_id,
+                request.traded_pair,
+                RequestKind::PlaceStopLimitOrder,
+                exchange_id,
+            ),
+            BasicTraderRequest::RequestQuote(request, exchange_id) => {
+                (request.request_id, request.traded_pair, RequestKind::RequestQuote, exchange_id)
+            }
+            BasicTraderRequest::AcceptQuote(request, exchange_id) => {
+                (request.request_id, request.traded_pair, RequestKind::AcceptQuote, exchange_id)
+            }
+            BasicTraderRequest::QueryOrderStatus(request, exchange_id) => {
+                (request.order_id, request.traded_pair, RequestKind::QueryOrderStatus, exchange_id)
+            }
+            BasicTraderRequest::Heartbeat(..)
+            | BasicTraderRequest::Subscribe(..)
+            | BasicTraderRequest::CloseOutPosition(..) => return vec![send]
+        };
+        let request_no = self.next_request_no;
+        self.next_request_no += 1;
+        self.pending.insert(
+            (order_id, kind),
+            PendingRequest {
+                broker_id: request.broker_id,
+                exchange_id,
+                traded_pair,
+                kind,
+                sent_dt: current_dt,
+                request_no,
+            },
+        );
+        vec![
+            send,
+            TraderAction {
+                delay: self.timeout,
+                content: TraderActionKind::TraderToItself(
+                    RequestTimeout { order_id, kind, request_no }.into()
+                ),
+            },
+        ]
+    }
+
+    /// Acknowledges the pending request the reply refers to.
+    /// Returns the acknowledged request if there was one.
+    ///
+    /// # Arguments
+    ///
+    /// * `reply` — Reply received from the broker.
+    pub fn process_reply<TraderID: Id>(
+        &mut self,
+        reply: &BasicBrokerToTrader<TraderID, ExchangeID, Symbol, Settlement>,
+    ) -> Option<PendingRequest<BrokerID, ExchangeID, Symbol, Settlement>>
+    {
+        let order_id = match &reply.content {
+            BasicBrokerReply::OrderCancelled(cancelled) => {
+                return self.pending.remove(&(cancelled.order_id, RequestKind::CancelLimitOrder))
+                    .or_else(
+                        || self.pending.remove(&(cancelled.order_id, RequestKind::ReduceOrderSize))
+                    );
+            }
+            BasicBrokerReply::CannotCancelOrder(cannot_cancel) => {
+                return self.pending.remove(
+                    &(cannot_cancel.order_id, RequestKind::CancelLimitOrder)
+                );
+            }
+            BasicBrokerReply::OrderSizeReduced(reduced) => {
+                return self.pending.remove(&(reduced.order_id, RequestKind::ReduceOrderSize));
+            }
+            BasicBrokerReply::CannotReduceOrderSize(cannot_reduce) => {
+                return self.pending.remove(
+                    &(cannot_reduce.order_id, RequestKind::ReduceOrderSize)
+                );
+            }
+            BasicBrokerReply::OrderAccepted(accepted) => accepted.order_id,
+            BasicBrokerReply::OrderPlacementDiscarded(discarded) => discarded.order_id,
+            BasicBrokerReply::OrderPriceAdjusted(adjusted) => adjusted.order_id,
+            BasicBrokerReply::OrderPartiallyExecuted(executed) => executed.order_id,
+            BasicBrokerReply::OrderExecuted(executed) => executed.order_id,
+            BasicBrokerReply::ShadowOrderPartiallyExecuted(executed) => executed.order_id,
+            BasicBrokerReply::ShadowOrderExecuted(executed) => executed.order_id,
+            BasicBrokerReply::GhostOrderPartiallyExecuted(executed) => executed.order_id,
+            BasicBrokerReply::GhostOrderExecuted(executed) => executed.order_id,
+            BasicBrokerReply::MarketOrderNotFullyExecuted(not_fully_executed) => {
+                not_fully_executed.order_id
+            }
+            BasicBrokerReply::StopOrderTriggered(_) => return None,
+            BasicBrokerReply::ExchangeEventNotification(_) => return None,
+            BasicBrokerReply::SessionDown => return None,
+            BasicBrokerReply::SessionUp => return None,
+            BasicBrokerReply::OrderCarriedOver(_) => return None,
+            BasicBrokerReply::BorrowRecalled(_) => return None,
+            BasicBrokerReply::BuyInPlaced(_) => return None,
+            BasicBrokerReply::BuyInExecuted(_) => return None,
+            BasicBrokerReply::BuyInNotFullyExecuted(_) => return None,
+            BasicBrokerReply::TradeBusted(_) => return None,
+            BasicBrokerReply::Heartbeat(_) => return None,
+            BasicBrokerReply::HeartbeatMissed => return None,
+            BasicBrokerReply::HeartbeatRestored => return None,
+            BasicBrokerReply::PortfolioSnapshot(_) => return None,
+            BasicBrokerReply::PositionClosedOut(_) => return None,
+            BasicBrokerReply::RecoverySnapshot(_) => return None,
+            BasicBrokerReply::SubscriptionRejected(_) => return None,
+            BasicBrokerReply::OrderStatus(status) => {
+                return self.pending.remove(&(status.order_id, RequestKind::QueryOrderStatus));
+            }
+            BasicBrokerReply::FirmQuote(quote) => {
+                return self.pending.remove(&(quote.request_id, RequestKind::RequestQuote));
+            }
+            BasicBrokerReply::QuoteTraded(traded) => {
+                return self.pending.remove(&(traded.request_id, RequestKind::AcceptQuote));
+            }
+            BasicBrokerReply::QuoteRejected(rejected) => {
+                return self.pending.remove(&(rejected.request_id, RequestKind::AcceptQuote))
+                    .or_else(
+                        || self.pending.remove(&(rejected.request_id, RequestKind::RequestQuote))
+                    );
+            }
+        };
+        self.pending.remove(&(order_id, RequestKind::PlaceLimitOrder))
+            .or_else(|| self.pending.remove(&(order_id, RequestKind::PlaceIcebergOrder)))
+            .or_else(|| self.pending.remove(&(order_id, RequestKind::PlaceMarketOrder)))
+            .or_else(|| self.pending.remove(&(order_id, RequestKind::PlaceStopOrder)))
+            .or_else(|| self.pending.remove(&(order_id, RequestKind::PlaceStopLimitOrder)))
+    }
+
+    /// Checks whether the request the `timeout` refers to is still pending.
+    /// If so, stops tracking it and calls `on_timeout`.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` — Scheduled timeout check.
+    /// * `on_timeout` — Callback to call with the timed out request.
+    pub fn handle_timeout(
+        &mut self,
+        timeout: RequestTimeout,
+        on_timeout: impl FnOnce(OrderID, PendingRequest<BrokerID, ExchangeID, Symbol, Settlement>),
+    ) {
+        if let Occupied(entry) = self.pending.entry((timeout.order_id, timeout.kind)) {
+            if entry.get().request_no == timeout.request_no {
+                on_timeout(timeout.order_id, entry.remove())
+            }
+        }
+    }
+
+    /// Returns the pending request of the given kind referring to the order.
+    ///
+    /// # Arguments
+    ///
+    /// * `order_id` — ID of the order.
+    /// * `kind` — Kind of the request.
+    pub fn get_pending(
+        &self,
+        order_id: OrderID,
+        kind: RequestKind,
+    ) -> Option<&PendingRequest<BrokerID, ExchangeID, Symbol, Settlement>> {
+        self.pending.get(&(order_id, kind))
+    }
+
+    /// Checks whether there is any pending request referring to the order.
+    ///
+    /// # Arguments
+    ///
+    /// * `order_id` — ID of the order.
+    pub fn is_pending(&self, order_id: OrderID) -> bool {
+        [
+            RequestKind::PlaceLimitOrder,
+            RequestKind::PlaceIcebergOrder,
+            RequestKind::PlaceMarketOrder,
+            RequestKind::PlaceStopOrder,
+            RequestKind::PlaceStopLimitOrder,
+            RequestKind::CancelLimitOrder,
+        ]
+            .into_iter()
+            .any(|kind| self.pending.contains_key(&(order_id, kind)))
+    }
+
+    /// Yields all pending requests.
+    pub fn iter_pending(
+        &self
+    ) -> impl Iterator<Item=(OrderID, &PendingRequest<BrokerID, ExchangeID, Symbol, Settlement>)> {
+        self.pending.iter().map(|((order_id, _), request)| (*order_id, request))
+    }
+
+    /// Returns the number of pending requests.
+    pub fn num_pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Stops tracking all pending requests.
+    pub fn clear(&mut self) {
+        self.pending.clear()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            concrete::{
+                message_protocol::{
+                    broker::reply::{CancellationReason, OrderCancelled},
+                    exchange::reply::OrderAccepted,
+                },
+                heartbeat::Heartbeat,
+                order::{LimitOrderCancelRequest, LimitOrderPlacingRequest},
+                traded_pair::settlement::concrete::SpotSettlement,
+                types::{Direction, Lots, Tick},
+            },
+            test_utils::{dt, pair},
+        },
+        super::*,
+    };
+
+    type Tracker = RequestTracker<u8, u8, &'static str, SpotSettlement>;
+
+    fn place(order_id: u64) -> BasicTraderToBroker<u8, u8, &'static str, SpotSettlement> {
+        BasicTraderToBroker {
+            broker_id: 1,
+            account: None,
+            content: BasicTraderRequest::PlaceLimitOrder(
+                LimitOrderPlacingRequest {
+                    traded_pair: pair(),
+                    order_id: OrderID(order_id),
+                    direction: Direction::Buy,
+                    price: Tick(100),
+                    size: Lots(1),
+                    dummy: false,
+                    time_in_force: Default::default(),
+                },
+                2,
+            ),
+        }
+    }
+
+    fn cancel(order_id: u64) -> BasicTraderToBroker<u8, u8, &'static str, SpotSettlement> {
+        BasicTraderToBroker {
+            broker_id: 1,
+            account: None,
+            content: BasicTraderRequest::CancelLimitOrder(
+                LimitOrderCancelRequest { traded_pair: pair(), order_id: OrderID(order_id) },
+                2,
+            ),
+        }
+    }
+
+    fn reply(
+        content: BasicBrokerReply<&'static str, SpotSettlement>,
+    ) -> BasicBrokerToTrader<u8, u8, &'static str, SpotSettlement> {
+        BasicBrokerToTrader { trader_id: 0, exchange_id: 2, event_dt: dt(0), content }
+    }
+
+    fn accepted(order_id: u64) -> BasicBrokerToTrader<u8, u8, &'static str, SpotSettlement> {
+        reply(
+            BasicBrokerReply::OrderAccepted(
+                OrderAccepted {
+                    traded_pair: pair(),
+                    order_id: OrderID(order_id),
+                    broker_order_id: OrderID(order_id),
+                    exchange_order_id: Some(OrderID(order_id)),
+                }
+            )
+        )
+    }
+
+    fn cancelled(order_id: u64) -> BasicBrokerToTrader<u8, u8, &'static str, SpotSettlement> {
+        reply(
+            BasicBrokerReply::OrderCancelled(
+                OrderCancelled {
+                    traded_pair: pair(),
+                    order_id: OrderID(order_id),
+                    reason: CancellationReason::TraderRequested,
+                }
+            )
+        )
+    }
+
+    #[test]
+    fn test_track() {
+        let mut tracker = Tracker::new(NanoDuration(10));
+        let actions = tracker.track::<RequestTimeout>(place(5), dt(0));
+        let [send, check] = actions.as_slice() else {
+            panic!("Tracked request should be sent along with the timeout check")
+        };
+        assert!(
+            matches!(
+                send,
+                TraderAction {
+                    delay: NanoDuration::ZERO,
+                    content: TraderActionKind::TraderToBroker(_),
+                }
+            )
+        );
+        assert!(
+            matches!(
+                check,
+                TraderAction {
+                    delay: NanoDuration(10),
+                    content: TraderActionKind::TraderToItself(
+                        RequestTimeout {
+                            order_id: OrderID(5),
+                            kind: RequestKind::PlaceLimitOrder,
+                            request_no: 0,
+                        }
+                    ),
+                }
+            )
+        );
+        let pending = tracker.get_pending(OrderID(5), RequestKind::PlaceLimitOrder).unwrap();
+        assert_eq!((pending.broker_id, pending.exchange_id, pending.sent_dt), (1, 2, dt(0)));
+        assert!(tracker.is_pending(OrderID(5)));
+        assert!(!tracker.is_pending(OrderID(6)))
+    }
+
+    #[test]
+    fn test_acknowledgement() {
+        let mut tracker = Tracker::new(NanoDuration(10));
+        tracker.track::<RequestTimeout>(place(5), dt(0));
+        tracker.track::<RequestTimeout>(cancel(5), dt(0));
+        assert_eq!(tracker.num_pending(), 2);
+
+        // Placement is acknowledged by any reply concerning the order, but the cancel is not
+        let acknowledged = tracker.process_reply(&accepted(5)).unwrap();
+        assert_eq!(acknowledged.kind, RequestKind::PlaceLimitOrder);
+        assert_eq!(tracker.process_reply(&accepted(5)), None);
+        assert!(tracker.get_pending(OrderID(5), RequestKind::CancelLimitOrder).is_some());
+
+        let acknowledged = tracker.process_reply(&cancelled(5)).unwrap();
+        assert_eq!(acknowledged.kind, RequestKind::CancelLimitOrder);
+        assert_eq!(tracker.num_pending(), 0);
+        assert_eq!(tracker.process_reply(&cancelled(5)), None)
+    }
+
+    #[test]
+    fn test_timeout() {
+        let mut tracker = Tracker::new(NanoDuration(10));
+        let timeout = RequestTimeout {
+            order_id: OrderID(5),
+            kind: RequestKind::PlaceLimitOrder,
+            request_no: 0,
+        };
+        tracker.track::<RequestTimeout>(place(5), dt(0));
+        // Request has been re-sent, so the check of the first one is stale
+        tracker.track::<RequestTimeout>(place(5), dt(0));
+        tracker.handle_timeout(timeout, |_, _| panic!("Stale timeout should be ignored"));
+        assert_eq!(tracker.num_pending(), 1);
+
+        let mut timed_out = None;
+        tracker.handle_timeout(
+            RequestTimeout { request_no: 1, ..timeout },
+            |order_id, request| timed_out = Some((order_id, request.request_no)),
+        );
+        assert_eq!(timed_out, Some((OrderID(5), 1)));
+        assert_eq!(tracker.num_pending(), 0);
+
+        // Acknowledged request does not time out
+        tracker.track::<RequestTimeout>(place(6), dt(0));
+        tracker.process_reply(&accepted(6));
+        tracker.handle_timeout(
+            RequestTimeout { order_id: OrderID(6), request_no: 2, ..timeout },
+            |_, _| panic!("Acknowledged request should not time out"),
+        )
+    }
+
+    #[test]
+    fn test_untrackable_request() {
+        let mut tracker = Tracker::new(NanoDuration(10));
+        let heartbeat = BasicTraderToBroker {
+            broker_id: 1,
+            account: None,
+            content: BasicTraderRequest::Heartbeat(Heartbeat { sent_dt: dt(0) }, 2),
+        };
+        let actions = tracker.track::<RequestTimeout>(heartbeat, dt(0));
+        assert!(
+            matches!(
+                actions.as_slice(),
+                [
+                    TraderAction {
+                        delay: NanoDuration::ZERO,
+                        content: TraderActionKind::TraderToBroker(sent),
+                    }
+                ] if *sent == heartbeat
+            )
+        );
+        assert_eq!(tracker.num_pending(), 0)
+    }
+}
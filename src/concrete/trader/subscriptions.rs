@@ -5,6 +5,7 @@ use {
 
 bitflags! {
     /// Bitflag containing information about the types of subscriptions to order book events.
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct SubscriptionList: u8 {
         /// Subscription to trades.
         const TRADES                  = 0b00000001;
@@ -14,6 +15,12 @@ bitflags! {
         const CANCELLED_LIMIT_ORDERS  = 0b00000100;
         /// Subscription to order book snapshots.
         const OB_SNAPSHOTS            = 0b00001000;
+        /// Subscription to order event counts published over the regular intervals.
+        const THROUGHPUT_STATISTICS   = 0b00010000;
+        /// Subscription to per-order changes of the order book.
+        const OB_DIFFS                = 0b00100000;
+        /// Subscription to realized volatility estimates published over the regular intervals.
+        const VOLATILITY_ESTIMATES    = 0b01000000;
     }
 }
 
@@ -32,6 +39,18 @@ pub struct SubscriptionConfig<ExchangeID, Symbol, Settlement>
     pub subscription: SubscriptionList,
 }
 
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Request to subscribe to the order book events of the traded pair during the simulation.
+/// The [`BasicBroker`](crate::concrete::broker::BasicBroker) first sends the
+/// [`RecoverySnapshot`](crate::concrete::message_protocol::exchange::reply::RecoverySnapshot)
+/// of the traded pair and only then starts forwarding the events.
+pub struct SubscriptionRequest<Symbol: Id, Settlement: GetSettlementLag> {
+    pub traded_pair: TradedPair<Symbol, Settlement>,
+    /// Subscriptions to add to the ones the trader already has.
+    pub subscription: SubscriptionList,
+}
+
 impl SubscriptionList {
     #[inline]
     /// Creates empty `SubscriptionList`.
@@ -73,6 +92,24 @@ impl SubscriptionList {
         self |= SubscriptionList::OB_SNAPSHOTS;
         self
     }
+    #[inline]
+    /// Adds subscription to throughput statistics.
+    pub fn to_throughput_statistics(mut self) -> Self {
+        self |= SubscriptionList::THROUGHPUT_STATISTICS;
+        self
+    }
+    #[inline]
+    /// Adds subscription to per-order changes of the order book.
+    pub fn to_ob_diffs(mut self) -> Self {
+        self |= SubscriptionList::OB_DIFFS;
+        self
+    }
+    #[inline]
+    /// Adds subscription to realized volatility estimates.
+    pub fn to_volatility_estimates(mut self) -> Self {
+        self |= SubscriptionList::VOLATILITY_ESTIMATES;
+        self
+    }
 }
 
 impl<ExchangeID, Symbol, Settlement>
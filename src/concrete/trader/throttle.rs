@@ -0,0 +1,552 @@
+use {
+    crate::{
+        concrete::{
+            message_protocol::trader::request::{BasicTraderRequest, BasicTraderToBroker},
+            traded_pair::settlement::GetSettlementLag,
+            types::OrderID,
+        },
+        interface::{
+            message::TraderToItself,
+            trader::{TraderAction, TraderActionKind},
+        },
+        types::{DateTime, Duration, Id, NanoDuration},
+    },
+    std::collections::VecDeque,
+};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+/// [`Trader`](crate::interface::trader::Trader)-to-itself message
+/// scheduled by the [`OrderThrottle`] to release the queued requests.
+pub struct ThrottleWakeup {
+    /// Sequence number of the wakeup.
+    pub wakeup_no: u64,
+}
+
+impl TraderToItself for ThrottleWakeup {}
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+/// Counters of the [`OrderThrottle`].
+pub struct ThrottleStats {
+    /// Number of requests submitted to the throttle.
+    pub num_submitted: usize,
+    /// Number of requests sent to the broker.
+    pub num_sent: usize,
+    /// Number of requests dropped since they were merged with other queued requests.
+    pub num_coalesced: usize,
+}
+
+/// Enforces internal order entry rate limit of the trader,
+/// queueing the requests that exceed it.
+///
+/// Requests are held in the queue for the coalescing window before being sent.
+/// A cancel request of the order which placing request is still queued
+/// annihilates with it, so that repeated cancel-replace intents are merged
+/// into the latest one. Duplicate cancel requests are dropped.
+/// Size reductions of the order are merged into its queued placing request
+/// and are superseded by its cancel request.
+pub struct OrderThrottle<BrokerID, ExchangeID, Symbol, Settlement>
+    where BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    max_requests: usize,
+    window: NanoDuration,
+    coalescing_window: NanoDuration,
+    sent_dts: VecDeque<DateTime>,
+    queue: VecDeque<(DateTime, BasicTraderToBroker<BrokerID, ExchangeID, Symbol, Settlement>)>,
+    scheduled_wakeup: Option<(DateTime, u64)>,
+    next_wakeup_no: u64,
+    stats: ThrottleStats,
+}
+
+impl<BrokerID, ExchangeID, Symbol, Settlement>
+OrderThrottle<BrokerID, ExchangeID, Symbol, Settlement>
+    where BrokerID: Id,
+          ExchangeID: Id,
+          Symbol: Id,
+          Settlement: GetSettlementLag
+{
+    /// Creates a new instance of the `OrderThrottle`.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_requests` — Maximum number of requests to send within the sliding window.
+    /// * `window` — Duration of the sliding window.
+    pub fn new(max_requests: usize, window: NanoDuration) -> Self {
+        if max_requests == 0 {
+            panic!("Maximum number of requests per window should be positive")
+        }
+        Self {
+            max_requests,
+            window,
+            coalescing_window: NanoDuration::ZERO,
+            sent_dts: Default::default(),
+            queue: Default::default(),
+            scheduled_wakeup: None,
+            next_wakeup_no: 0,
+            stats: Default::default(),
+        }
+    }
+
+    /// Sets the time to hold every request in the queue
+    /// so that it can be merged with the subsequent ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `coalescing_window` — Holding time.
+    pub fn with_coalescing_window(mut self, coalescing_window: NanoDuration) -> Self {
+        self.coalescing_window = coalescing_window;
+        self
+    }
+
+    /// Submits the request. Returns the actions sending the requests that can be sent now
+    /// and, if some requests remain queued, the action scheduling the [`ThrottleWakeup`].
+    ///
+    /// # Arguments
+    ///
+    /// * `request` — Request to send.
+    /// * `current_dt` — Current datetime of the trader.
+    pub fn submit<T2T>(
+        &mut self,
+        request: BasicTraderToBroker<BrokerID, ExchangeID, Symbol, Settlement>,
+        current_dt: DateTime,
+    ) -> Vec<TraderAction<BasicTraderToBroker<BrokerID, ExchangeID, Symbol, Settlement>, T2T>>
+        where T2T: TraderToItself + From<ThrottleWakeup>
+    {
+        self.stats.num_submitted += 1;
+        if let BasicTraderRequest::ReduceOrderSize(reduction, _) = request.content {
+            let queued = self.find_queued(
+                |content| matches!(
+                    content,
+                    BasicTraderRequest::PlaceLimitOrder(..)
+                    | BasicTraderRequest::PlaceIcebergOrder(..)
+                ) && Self::get_order_id(content) == Some(reduction.order_id)
+            );
+            if let Some(i) = queued {
+                let size = match &mut self.queue[i].1.content {
+                    BasicTraderRequest::PlaceLimitOrder(placing, _) => &mut placing.size,
+                    BasicTraderRequest::PlaceIcebergOrder(placing, _) => &mut placing.size,
+                    _ => unreachable!()
+                };
+                if reduction.size < *size {
+                    *size -= reduction.size
+                } else {
+                    self.queue.remove(i);
+                    self.stats.num_coalesced += 1
+                }
+                self.stats.num_coalesced += 1;
+                return self.release(current_dt);
+            }
+        }
+        if let BasicTraderRequest::CancelLimitOrder(cancel, _) = request.content {
+            let num_queued = self.queue.len();
+            self.queue.retain(
+                |(_, queued)| !matches!(
+                    queued.content,
+                    BasicTraderRequest::ReduceOrderSize(reduction, _)
+                    if reduction.order_id == cancel.order_id
+                )
+            );
+            self.stats.num_coalesced += num_queued - self.queue.len();
+            let queued = self.find_queued(
+                |content| matches!(
+                    content,
+                    BasicTraderRequest::PlaceLimitOrder(..)
+                    | BasicTraderRequest::PlaceIcebergOrder(..)
+                    | BasicTraderRequest::PlaceStopOrder(..)
+                    | BasicTraderRequest::PlaceStopLimitOrder(..)
+                    | BasicTraderRequest::CancelLimitOrder(..)
+                ) && Self::get_order_id(content) == Some(cancel.order_id)
+            );
+            if let Some(i) = queued {
+                let placing = matches!(
+                    self.queue[i].1.content,
+                    BasicTraderRequest::PlaceLimitOrder(..)
+                    | BasicTraderRequest::PlaceIcebergOrder(..)
+                    | BasicTraderRequest::PlaceStopOrder(..)
+                    | BasicTraderRequest::PlaceStopLimitOrder(..)
+                );
+                if placing {
+                    self.queue.remove(i);
+                    self.stats.num_coalesced += 1
+                }
+                self.stats.num_coalesced += 1;
+                return self.release(current_dt);
+            }
+        }
+        let ready_dt = current_dt + self.coalescing_window;
+        self.queue.push_back((ready_dt, request));
+        self.release(current_dt)
+    }
+
+    /// Releases the queued requests that can be sent now.
+    /// Returns the actions sending them and, if some requests remain queued,
+    /// the action scheduling the next [`ThrottleWakeup`].
+    ///
+    /// # Arguments
+    ///
+    /// * `wakeup` — Scheduled wakeup.
+    /// * `current_dt` — Current datetime of the trader.
+    pub fn handle_wakeup<T2T>(
+        &mut self,
+        wakeup: ThrottleWakeup,
+        current_dt: DateTime,
+    ) -> Vec<TraderAction<BasicTraderToBroker<BrokerID, ExchangeID, Symbol, Settlement>, T2T>>
+        where T2T: TraderToItself + From<ThrottleWakeup>
+    {
+        if matches!(self.scheduled_wakeup, Some((_, wakeup_no)) if wakeup_no == wakeup.wakeup_no) {
+            self.scheduled_wakeup = None
+        }
+        self.release(current_dt)
+    }
+
+    /// Returns the number of queued requests.
+    pub fn num_queued(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Checks whether there is any queued request referring to the order.
+    ///
+    /// # Arguments
+    ///
+    /// * `order_id` — ID of the order.
+    pub fn is_queued(&self, order_id: OrderID) -> bool {
+        self.find_queued(|content| Self::get_order_id(content) == Some(order_id)).is_some()
+    }
+
+    /// Returns the throttle counters.
+    pub fn get_stats(&self) -> ThrottleStats {
+        self.stats
+    }
+
+    /// Drops all queued requests.
+    pub fn clear(&mut self) {
+        self.queue.clear()
+    }
+
+    fn release<T2T>(
+        &mut self,
+        current_dt: DateTime,
+    ) -> Vec<TraderAction<BasicTraderToBroker<BrokerID, ExchangeID, Symbol, Settlement>, T2T>>
+        where T2T: TraderToItself + From<ThrottleWakeup>
+    {
+        let window_start = current_dt - self.window;
+        while matches!(self.sent_dts.front(), Some(sent_dt) if *sent_dt <= window_start) {
+            self.sent_dts.pop_front();
+        }
+        let mut actions = Vec::new();
+        while let Some((ready_dt, _)) = self.queue.front() {
+            if *ready_dt > current_dt || self.sent_dts.len() >= self.max_requests {
+                break;
+            }
+            let (_, request) = self.queue.pop_front().unwrap_or_else(
+                || unreachable!("Queue should not be empty")
+            );
+            self.sent_dts.push_back(current_dt);
+            self.stats.num_sent += 1;
+            actions.push(
+                TraderAction {
+                    delay: NanoDuration::ZERO,
+                    content: TraderActionKind::TraderToBroker(request),
+                }
+            )
+        }
+        if let Some((ready_dt, _)) = self.queue.front() {
+            let mut wakeup_dt = *ready_dt;
+            if self.sent_dts.len() >= self.max_requests {
+                if let Some(sent_dt) = self.sent_dts.front() {
+                    wakeup_dt = wakeup_dt.max(*sent_dt + self.window)
+                }
+            }
+            let already_scheduled = matches!(
+                self.scheduled_wakeup,
+                Some((scheduled_dt, _)) if scheduled_dt <= wakeup_dt
+            );
+            if !already_scheduled {
+                let wakeup_no = self.next_wakeup_no;
+                self.next_wakeup_no += 1;
+                self.scheduled_wakeup = Some((wakeup_dt, wakeup_no));
+                let delay = NanoDuration::from_duration(
+                    (wakeup_dt - current_dt).max(Duration::zero())
+                ).unwrap_or_else(
+                    || panic!("Cannot express the delay until {wakeup_dt} in nanoseconds")
+                );
+                actions.push(
+                    TraderAction {
+                        delay,
+                        content: TraderActionKind::TraderToItself(
+                            ThrottleWakeup { wakeup_no }.into()
+                        ),
+                    }
+                )
+            }
+        }
+        actions
+    }
+
+    fn find_queued(
+        &self,
+        predicate: impl Fn(&BasicTraderRequest<ExchangeID, Symbol, Settlement>) -> bool,
+    ) -> Option<usize> {
+        self.queue.iter().position(|(_, request)| predicate(&request.content))
+    }
+
+    fn get_order_id(
+        content: &BasicTraderRequest<ExchangeID, Symbol, Settlement>) -> Option<OrderID>
+    {
+        match content {
+            BasicTraderRequest::CancelLimitOrder(request, _) => Some(request.order_id),
+            BasicTraderRequest::ReduceOrderSize(request, _) => Some(request.order_id),
+            BasicTraderRequest::PlaceLimitOrder(request, _) => Some(request.order_id),
+            BasicTraderRequest::PlaceIcebergOrder(request, _) => Some(request.order_id),
+            BasicTraderRequest::PlaceMarketOrder(request, _) => Some(request.order_id),
+            BasicTraderRequest::PlaceStopOrder(request, _) => Some(request.order_id),
+            BasicTraderRequest::PlaceStopLimitOrder(request, _) => Some(request.order_id),
+            BasicTraderRequest::QueryOrderStatus(request, _) => Some(request.order_id),
+            // Request IDs of the quotes do not share the space of the order IDs
+            BasicTraderRequest::RequestQuote(..)
+            | BasicTraderRequest::AcceptQuote(..)
+            | BasicTraderRequest::Subscribe(..)
+            | BasicTraderRequest::Heartbeat(..)
+            | BasicTraderRequest::CloseOutPosition(..) => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{
+            concrete::{
+                order::{
+                    LimitOrderCancelRequest,
+                    LimitOrderPlacingRequest,
+                    LimitOrderReductionRequest,
+                    OrderStatusRequest,
+                    QuoteRequest,
+                },
+                traded_pair::settlement::concrete::SpotSettlement,
+                types::{Direction, Lots, Tick},
+            },
+            test_utils::{dt_ns, pair},
+        },
+        super::*,
+    };
+
+    type Throttle = OrderThrottle<u8, u8, &'static str, SpotSettlement>;
+    type Request = BasicTraderToBroker<u8, u8, &'static str, SpotSettlement>;
+    type Action = TraderAction<Request, ThrottleWakeup>;
+
+    fn request(content: BasicTraderRequest<u8, &'static str, SpotSettlement>) -> Request {
+        BasicTraderToBroker { broker_id: 1, account: None, content }
+    }
+
+    fn place(order_id: u64, size: i64) -> Request {
+        request(
+            BasicTraderRequest::PlaceLimitOrder(
+                LimitOrderPlacingRequest {
+                    traded_pair: pair(),
+                    order_id: OrderID(order_id),
+                    direction: Direction::Buy,
+                    price: Tick(100),
+                    size: Lots(size),
+                    dummy: false,
+                    time_in_force: Default::default(),
+                },
+                2,
+            )
+        )
+    }
+
+    fn cancel(order_id: u64) -> Request {
+        request(
+            BasicTraderRequest::CancelLimitOrder(
+                LimitOrderCancelRequest { traded_pair: pair(), order_id: OrderID(order_id) },
+                2,
+            )
+        )
+    }
+
+    fn reduce(order_id: u64, size: i64) -> Request {
+        request(
+            BasicTraderRequest::ReduceOrderSize(
+                LimitOrderReductionRequest {
+                    traded_pair: pair(),
+                    order_id: OrderID(order_id),
+                    size: Lots(size),
+                },
+                2,
+            )
+        )
+    }
+
+    fn query(order_id: u64) -> Request {
+        request(
+            BasicTraderRequest::QueryOrderStatus(
+                OrderStatusRequest { traded_pair: pair(), order_id: OrderID(order_id) },
+                2,
+            )
+        )
+    }
+
+    fn request_quote(request_id: u64) -> Request {
+        request(
+            BasicTraderRequest::RequestQuote(
+                QuoteRequest {
+                    traded_pair: pair(),
+                    request_id: OrderID(request_id),
+                    direction: Direction::Sell,
+                    size: Lots(1),
+                },
+                2,
+            )
+        )
+    }
+
+    fn sent(actions: &[Action]) -> Vec<Request> {
+        actions.iter()
+            .filter_map(
+                |action| match action.content {
+                    TraderActionKind::TraderToBroker(request) => Some(request),
+                    _ => None
+                }
+            )
+            .collect()
+    }
+
+    fn wakeups(actions: &[Action]) -> Vec<(NanoDuration, ThrottleWakeup)> {
+        actions.iter()
+            .filter_map(
+                |action| match action.content {
+                    TraderActionKind::TraderToItself(wakeup) => Some((action.delay, wakeup)),
+                    _ => None
+                }
+            )
+            .collect()
+    }
+
+    #[test]
+    fn test_cancel_after_status_query() {
+        let mut throttle = Throttle::new(1, NanoDuration(100));
+        assert_eq!(sent(&throttle.submit(place(5, 1), dt_ns(0))), [place(5, 1)]);
+        assert!(sent(&throttle.submit(query(5), dt_ns(1))).is_empty());
+
+        // Cancel should not be merged with the queued status query of the same order
+        assert!(sent(&throttle.submit(cancel(5), dt_ns(2))).is_empty());
+        assert_eq!(throttle.num_queued(), 2);
+        assert_eq!(throttle.get_stats().num_coalesced, 0);
+
+        let actions = throttle.handle_wakeup(ThrottleWakeup { wakeup_no: 0 }, dt_ns(100));
+        assert_eq!(sent(&actions), [query(5)]);
+        let actions = throttle.handle_wakeup(ThrottleWakeup { wakeup_no: 1 }, dt_ns(200));
+        assert_eq!(sent(&actions), [cancel(5)]);
+        assert!(wakeups(&actions).is_empty())
+    }
+
+    #[test]
+    fn test_cancel_after_quote_request() {
+        let mut throttle = Throttle::new(1, NanoDuration(100));
+        assert_eq!(sent(&throttle.submit(place(5, 1), dt_ns(0))), [place(5, 1)]);
+        assert!(sent(&throttle.submit(request_quote(5), dt_ns(1))).is_empty());
+        assert!(!throttle.is_queued(OrderID(5)));
+
+        // Cancel should not be merged with the queued quote request having the same ID
+        assert!(sent(&throttle.submit(cancel(5), dt_ns(2))).is_empty());
+        assert!(throttle.is_queued(OrderID(5)));
+        assert_eq!(throttle.num_queued(), 2);
+        assert_eq!(throttle.get_stats().num_coalesced, 0)
+    }
+
+    #[test]
+    fn test_window_enforcement() {
+        let mut throttle = Throttle::new(2, NanoDuration(100));
+        assert_eq!(sent(&throttle.submit(place(1, 1), dt_ns(0))), [place(1, 1)]);
+        assert_eq!(sent(&throttle.submit(place(2, 1), dt_ns(10))), [place(2, 1)]);
+        let actions = throttle.submit(place(3, 1), dt_ns(20));
+        assert!(sent(&actions).is_empty());
+        // Slot is freed when the first request leaves the window
+        assert_eq!(wakeups(&actions), [(NanoDuration(80), ThrottleWakeup { wakeup_no: 0 })]);
+
+        let actions = throttle.handle_wakeup(ThrottleWakeup { wakeup_no: 0 }, dt_ns(100));
+        assert_eq!(sent(&actions), [place(3, 1)]);
+        assert!(wakeups(&actions).is_empty());
+        assert_eq!(
+            throttle.get_stats(),
+            ThrottleStats { num_submitted: 3, num_sent: 3, num_coalesced: 0 }
+        )
+    }
+
+    #[test]
+    fn test_place_cancel_annihilation() {
+        let mut throttle = Throttle::new(10, NanoDuration(100)).with_coalescing_window(NanoDuration(10));
+        let actions = throttle.submit(place(5, 1), dt_ns(0));
+        assert!(sent(&actions).is_empty());
+        assert_eq!(wakeups(&actions), [(NanoDuration(10), ThrottleWakeup { wakeup_no: 0 })]);
+
+        assert!(throttle.submit::<ThrottleWakeup>(cancel(5), dt_ns(5)).is_empty());
+        assert_eq!(throttle.num_queued(), 0);
+        assert!(!throttle.is_queued(OrderID(5)));
+
+        // Duplicate cancel is dropped
+        assert!(sent(&throttle.submit(cancel(6), dt_ns(6))).is_empty());
+        assert!(sent(&throttle.submit(cancel(6), dt_ns(7))).is_empty());
+        assert_eq!(throttle.num_queued(), 1);
+
+        let actions = throttle.handle_wakeup(ThrottleWakeup { wakeup_no: 0 }, dt_ns(10));
+        assert!(sent(&actions).is_empty());
+        assert_eq!(wakeups(&actions), [(NanoDuration(6), ThrottleWakeup { wakeup_no: 1 })]);
+        let actions = throttle.handle_wakeup(ThrottleWakeup { wakeup_no: 1 }, dt_ns(16));
+        assert_eq!(sent(&actions), [cancel(6)]);
+        assert_eq!(
+            throttle.get_stats(),
+            ThrottleStats { num_submitted: 4, num_sent: 1, num_coalesced: 3 }
+        )
+    }
+
+    #[test]
+    fn test_reduce_merging() {
+        let mut throttle = Throttle::new(10, NanoDuration(100)).with_coalescing_window(NanoDuration(10));
+        throttle.submit::<ThrottleWakeup>(place(5, 3), dt_ns(0));
+        assert!(throttle.submit::<ThrottleWakeup>(reduce(5, 1), dt_ns(1)).is_empty());
+        assert_eq!(throttle.num_queued(), 1);
+
+        let actions = throttle.handle_wakeup(ThrottleWakeup { wakeup_no: 0 }, dt_ns(10));
+        assert_eq!(sent(&actions), [place(5, 2)]);
+
+        // Reduction of the sent order is queued and then superseded by its cancel
+        throttle.submit::<ThrottleWakeup>(reduce(5, 1), dt_ns(11));
+        assert!(throttle.is_queued(OrderID(5)));
+        throttle.submit::<ThrottleWakeup>(cancel(5), dt_ns(12));
+        assert_eq!(throttle.num_queued(), 1);
+
+        // Reduction by the whole size removes the queued placing request
+        throttle.submit::<ThrottleWakeup>(place(6, 2), dt_ns(13));
+        throttle.submit::<ThrottleWakeup>(reduce(6, 2), dt_ns(14));
+        assert!(!throttle.is_queued(OrderID(6)));
+        assert_eq!(
+            throttle.get_stats(),
+            ThrottleStats { num_submitted: 6, num_sent: 1, num_coalesced: 4 }
+        )
+    }
+
+    #[test]
+    fn test_stale_wakeup() {
+        let mut throttle = Throttle::new(1, NanoDuration(100));
+        throttle.submit::<ThrottleWakeup>(place(1, 1), dt_ns(0));
+        let actions = throttle.submit(place(2, 1), dt_ns(10));
+        assert_eq!(wakeups(&actions), [(NanoDuration(90), ThrottleWakeup { wakeup_no: 0 })]);
+
+        // Stale wakeup does not send anything and does not reschedule the pending one
+        let actions = throttle.handle_wakeup::<ThrottleWakeup>(
+            ThrottleWakeup { wakeup_no: 7 }, dt_ns(50),
+        );
+        assert!(actions.is_empty());
+        let actions = throttle.submit::<ThrottleWakeup>(place(3, 1), dt_ns(60));
+        assert!(actions.is_empty());
+
+        let actions = throttle.handle_wakeup(ThrottleWakeup { wakeup_no: 0 }, dt_ns(100));
+        assert_eq!(sent(&actions), [place(2, 1)]);
+        assert_eq!(wakeups(&actions), [(NanoDuration(100), ThrottleWakeup { wakeup_no: 1 })])
+    }
+}
@@ -0,0 +1,101 @@
+use {
+    crate::{
+        concrete::input::one_tick::HistoryEntry,
+        types::DateTime,
+        utils::collections::HashMap,
+    },
+    std::sync::Arc,
+};
+
+/// Values of the indicator along with the datetimes of the ticks they were computed at.
+pub type IndicatorSeries = Vec<(DateTime, f64)>;
+
+/// Trader which indicators can be warmed up with the [`WarmStart`] series
+/// instead of the long in-simulation warm-up period.
+pub trait WarmStartable {
+    /// Called once upon the trader creation, before it is registered at the brokers.
+    ///
+    /// # Arguments
+    ///
+    /// * `warm_start` — Indicator series precomputed from the history before the start.
+    fn warm_start(&mut self, warm_start: &WarmStart);
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Named indicator series precomputed from the history strictly before the simulation start.
+///
+/// Ticks at or after the `start_dt` are never passed to the indicators,
+/// so the series cannot look ahead of the simulation.
+/// Is handed to the traders with the
+/// [`with_warm_start`](crate::concrete::simulation::SimulationBuilder::with_warm_start).
+pub struct WarmStart {
+    start_dt: DateTime,
+    series: HashMap<String, Arc<IndicatorSeries>>,
+}
+
+impl WarmStart {
+    /// Creates a new instance of the `WarmStart` without any series.
+    ///
+    /// # Arguments
+    ///
+    /// * `start_dt` — Start of the simulation. Exclusive upper bound of the tick datetimes.
+    pub fn new(start_dt: DateTime) -> Self {
+        Self { start_dt, series: Default::default() }
+    }
+
+    /// Computes the indicator series over the ticks strictly before the `start_dt`,
+    /// e.g. over the ones iterated with the
+    /// [`iter_trd_before`](crate::concrete::input::one_tick::OneTickEventTable::iter_trd_before).
+    /// Ticks should be sorted in ascending order by time, so the iteration stops
+    /// at the first one at or after the `start_dt`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` — Name of the indicator.
+    /// * `ticks` — Historical ticks.
+    /// * `indicator` — Stateful indicator called with every tick.
+    ///   Returns `None` if the value is not defined yet.
+    pub fn with_indicator<'a>(
+        mut self,
+        name: impl Into<String>,
+        ticks: impl IntoIterator<Item=&'a HistoryEntry>,
+        mut indicator: impl FnMut(&HistoryEntry) -> Option<f64>) -> Self
+    {
+        let name = name.into();
+        if self.series.contains_key(&name) {
+            panic!("Indicator \"{name}\" is already computed")
+        }
+        let start_dt = self.start_dt;
+        let series = ticks.into_iter()
+            .take_while(|tick| tick.datetime < start_dt)
+            .filter_map(|tick| Some((tick.datetime, indicator(tick)?)))
+            .collect();
+        self.series.insert(name, Arc::new(series));
+        self
+    }
+
+    /// Returns the start of the simulation the series are computed for.
+    pub fn get_start_dt(&self) -> DateTime {
+        self.start_dt
+    }
+
+    /// Returns the series of the indicator or `None` if there is no such indicator.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` — Name of the indicator.
+    pub fn get_series(&self, name: &str) -> Option<Arc<IndicatorSeries>> {
+        self.series.get(name).cloned()
+    }
+
+    /// Returns the last value of the indicator before the start of the simulation
+    /// or `None` if the indicator is not defined.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` — Name of the indicator.
+    pub fn get_last_value(&self, name: &str) -> Option<f64> {
+        let (_, value) = self.series.get(name)?.last()?;
+        Some(*value)
+    }
+}
@@ -1,30 +1,96 @@
 use {
     chrono::NaiveDateTime as DateTime,
     derive_more::{Add, AddAssign, From, FromStr, Into, Sub, SubAssign, Sum},
-    std::{cmp::Ordering, str::FromStr},
+    std::{cmp::Ordering, fmt::{Display, Formatter}, str::FromStr},
 };
 
 #[derive(Debug, Default, PartialOrd, PartialEq, Ord, Eq, Hash, Clone, Copy)]
 #[derive(derive_more::Display, FromStr, Add, Sub, AddAssign, SubAssign, From, Into)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Order ID newtype.
 pub struct OrderID(pub u64);
 
+impl OrderID {
+    /// Lowest order ID reserved for the IDs generated by the simulation itself
+    /// when historical order IDs are preserved. Historical order IDs should be less than it.
+    pub const FIRST_SYNTHETIC: OrderID = OrderID(1 << 63);
+}
+
+#[derive(Debug, Default, PartialOrd, PartialEq, Ord, Eq, Hash, Clone, Copy)]
+#[derive(derive_more::Display, FromStr, From, Into)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Account ID newtype. Distinguishes accounts of the single trader.
+pub struct AccountID(pub u64);
+
+#[derive(Debug, Default, PartialOrd, PartialEq, Ord, Eq, Hash, Clone, Copy)]
+#[derive(derive_more::Display, FromStr, From, Into)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Execution ID newtype. Identifies the single fill of the single order at the venue.
+pub struct ExecID(pub u64);
+
+#[derive(Debug, Default, PartialOrd, PartialEq, Ord, Eq, Hash, Clone, Copy)]
+#[derive(derive_more::Display, FromStr, From, Into)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Trade ID newtype. Identifies the trade printed to the tape.
+/// Fills of all orders matched in the trade share it.
+pub struct TradeID(pub u64);
+
 #[derive(Debug, PartialOrd, PartialEq, Ord, Eq, Hash, Clone, Copy)]
 #[derive(derive_more::Display, Add, Sub, AddAssign, SubAssign, From, Into)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Quotation tick newtype. Is equivalent to the [`i64`] due to the fact that
 /// exchanges quote prices with a certain constant step.
 pub struct Tick(pub i64);
 
 #[derive(derive_more::Display, FromStr, Debug, PartialOrd, Clone, Copy, From, Into)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Tick size newtype. Price quotation step.
 pub struct TickSize(pub f64);
 
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+/// Tick size in the exact decimal representation: `mantissa * 10^(-scale)`.
+/// Used to convert decimal strings to [`Tick`]s without floating-point errors.
+pub struct DecimalTickSize {
+    mantissa: i128,
+    scale: u32,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+/// Error that can occur during the exact conversion of a decimal string to [`Tick`].
+pub enum ParsePriceError {
+    /// String is not a decimal number.
+    InvalidFormat,
+    /// Number does not fit into the supported range.
+    Overflow,
+    /// Price is not a multiple of the price step.
+    OffTickGrid,
+}
+
+impl Display for ParsePriceError {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParsePriceError::InvalidFormat => write!(f, "String is not a decimal number"),
+            ParsePriceError::Overflow => write!(f, "Number is out of the supported range"),
+            ParsePriceError::OffTickGrid => write!(f, "Price is not a multiple of the price step"),
+        }
+    }
+}
+
 #[derive(Debug, PartialOrd, PartialEq, Ord, Eq, Hash, Clone, Copy)]
 #[derive(derive_more::Display, FromStr, Add, Sub, AddAssign, SubAssign, Sum, From, Into)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Order size newtype.
 pub struct Lots(pub i64);
 
+#[derive(Debug, Default, PartialOrd, PartialEq, Ord, Eq, Hash, Clone, Copy)]
+#[derive(derive_more::Display, Add, Sub, AddAssign, SubAssign, Sum, From, Into)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Fee newtype, in millionths of the tick times lots. Negative fees are rebates.
+pub struct Fee(pub i64);
+
 #[derive(derive_more::Display, Debug, PartialEq, PartialOrd, Eq, Ord, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Order Direction.
 pub enum Direction {
     /// Buy direction.
@@ -34,18 +100,169 @@ pub enum Direction {
 }
 
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Order book state.
 pub struct ObState {
     pub bids: Vec<(Tick, Vec<(Lots, DateTime)>)>,
     pub asks: Vec<(Tick, Vec<(Lots, DateTime)>)>,
 }
 
+impl ObState {
+    /// Returns the size of the state, in bytes, transmitted as 8-byte price levels
+    /// each followed by 16-byte orders, i.e. their sizes and datetimes.
+    pub fn get_size_in_bytes(&self) -> u64 {
+        self.bids.iter()
+            .chain(&self.asks)
+            .map(|(_, orders)| 8 + 16 * orders.len() as u64)
+            .sum()
+    }
+
+    /// Returns the microprice, in ticks, i.e. the average of the best prices
+    /// weighted by the sizes of the opposite best levels.
+    /// Returns `None` if either side is empty.
+    pub fn get_microprice(&self) -> Option<f64> {
+        get_microprice(Self::iter_level_sizes(&self.bids), Self::iter_level_sizes(&self.asks))
+    }
+
+    /// Returns the imbalance of the best levels, i.e. the difference of the bid and ask sizes
+    /// divided by their sum. Lies within `[-1, 1]` and is positive if bids prevail.
+    /// Returns `None` if either side is empty.
+    pub fn get_top_imbalance(&self) -> Option<f64> {
+        self.get_depth_imbalance(0.0, 1)
+    }
+
+    /// Returns the imbalance of the sides, where the size of the `i`-th non-empty level
+    /// of each side is weighted by `decay^i`, the best level being the zeroth one.
+    /// Lies within `[-1, 1]` and is positive if bids prevail.
+    /// Returns `None` if either side is empty.
+    ///
+    /// # Arguments
+    ///
+    /// * `decay` — Weight decay per level. Should be within `[0, 1]`.
+    /// * `max_levels` — Maximum number of non-empty price levels per side to take into account.
+    ///   If zero, the number of levels is considered unlimited.
+    pub fn get_depth_imbalance(&self, decay: f64, max_levels: usize) -> Option<f64> {
+        get_depth_imbalance(
+            Self::iter_level_sizes(&self.bids),
+            Self::iter_level_sizes(&self.asks),
+            decay,
+            max_levels,
+        )
+    }
+
+    fn iter_level_sizes(
+        side: &[(Tick, Vec<(Lots, DateTime)>)]) -> impl Iterator<Item=(Tick, Lots)> + '_
+    {
+        side.iter().map(|(price, orders)| (*price, orders.iter().map(|(size, _)| *size).sum()))
+    }
+}
+
+/// Computes the microprice, in ticks, from the non-empty levels of the sides,
+/// the best ones first. Returns `None` if either side is empty.
+pub(crate) fn get_microprice(
+    mut bids: impl Iterator<Item=(Tick, Lots)>,
+    mut asks: impl Iterator<Item=(Tick, Lots)>) -> Option<f64>
+{
+    let (bid, bid_size) = bids.next()?;
+    let (ask, ask_size) = asks.next()?;
+    let (bid_size, ask_size) = (bid_size.0 as f64, ask_size.0 as f64);
+    Some((bid.0 as f64 * ask_size + ask.0 as f64 * bid_size) / (bid_size + ask_size))
+}
+
+/// Computes the weighted depth imbalance from the non-empty levels of the sides,
+/// the best ones first. Returns `None` if either side is empty.
+pub(crate) fn get_depth_imbalance(
+    bids: impl Iterator<Item=(Tick, Lots)>,
+    asks: impl Iterator<Item=(Tick, Lots)>,
+    decay: f64,
+    max_levels: usize) -> Option<f64>
+{
+    if !(0.0..=1.0).contains(&decay) {
+        panic!("Imbalance decay should be within [0, 1]. Got {decay}")
+    }
+    let max_levels = if max_levels != 0 { max_levels } else { usize::MAX };
+    let weigh = |side: &mut dyn Iterator<Item=(Tick, Lots)>| side.take(max_levels)
+        .zip(0..)
+        .map(|((_, size), i)| size.0 as f64 * decay.powi(i))
+        .sum::<f64>();
+    let (bid_size, ask_size) = (weigh(&mut { bids }), weigh(&mut { asks }));
+    if bid_size > 0.0 && ask_size > 0.0 {
+        Some((bid_size - ask_size) / (bid_size + ask_size))
+    } else {
+        None
+    }
+}
+
 /// Acceptable precision error during conversions between [`f64`] and [`Price`].
-const ACCEPTABLE_PRECISION_ERROR: f64 = 1e-11;
+pub(crate) const ACCEPTABLE_PRECISION_ERROR: f64 = 1e-11;
+
+/// Maximum number of fractional digits supported by the exact decimal parsing.
+const MAX_DECIMAL_SCALE: u32 = 18;
+
+/// Parses decimal string of the form `[+-]digits[.digits]` to the `(mantissa, scale)` pair.
+fn parse_decimal(string: &str) -> Result<(i128, u32), ParsePriceError>
+{
+    let (negative, digits) = match string.as_bytes().first() {
+        Some(b'-') => (true, &string[1..]),
+        Some(b'+') => (false, &string[1..]),
+        _ => (false, string)
+    };
+    let (integer_part, fractional_part) = digits.split_once('.').unwrap_or((digits, ""));
+    if integer_part.is_empty() && fractional_part.is_empty() {
+        return Err(ParsePriceError::InvalidFormat);
+    }
+    // Trailing zeros do not affect the value but may exceed the maximum scale
+    let fractional_part = fractional_part.trim_end_matches('0');
+    let scale = fractional_part.len() as u32;
+    if scale > MAX_DECIMAL_SCALE {
+        return Err(ParsePriceError::Overflow);
+    }
+    let mut mantissa: i128 = 0;
+    for digit in integer_part.bytes().chain(fractional_part.bytes()) {
+        if !digit.is_ascii_digit() {
+            return Err(ParsePriceError::InvalidFormat);
+        }
+        mantissa = mantissa.checked_mul(10)
+            .and_then(|mantissa| mantissa.checked_add((digit - b'0') as i128))
+            .ok_or(ParsePriceError::Overflow)?;
+    }
+    Ok((if negative { -mantissa } else { mantissa }, scale))
+}
+
+impl DecimalTickSize
+{
+    /// Parses decimal string to `DecimalTickSize`.
+    ///
+    /// # Arguments
+    ///
+    /// * `string` — String to parse.
+    pub fn from_decimal_str(string: impl AsRef<str>) -> Result<Self, ParsePriceError> {
+        let (mantissa, scale) = parse_decimal(string.as_ref())?;
+        if mantissa <= 0 {
+            return Err(ParsePriceError::InvalidFormat);
+        }
+        Ok(Self { mantissa, scale })
+    }
+}
+
+impl TryFrom<TickSize> for DecimalTickSize {
+    type Error = ParsePriceError;
+
+    /// Converts [`TickSize`] using the shortest decimal representation
+    /// that round-trips to the same [`f64`].
+    /// Fails if the price step is not positive or not finite.
+    #[inline]
+    fn try_from(price_step: TickSize) -> Result<Self, Self::Error> {
+        Self::from_decimal_str(price_step.0.to_string())
+    }
+}
 
 impl Tick
 {
     /// Converts string to [`Price`].
+    /// The string is parsed as [`f64`], so the exponent notation is accepted
+    /// and the value is rounded to the price step within the [`f64`] precision.
+    /// See [`try_from_decimal_str`](Self::try_from_decimal_str) for the exact conversion.
     ///
     /// # Arguments
     ///
@@ -60,6 +277,34 @@ impl Tick
         Self::from_f64(parsed_f64, price_step)
     }
 
+    /// Converts string to [`Price`] exactly, using integer arithmetic only.
+    ///
+    /// # Arguments
+    ///
+    /// * `string` — String to convert.
+    /// * `price_step` — Price quotation step.
+    pub fn try_from_decimal_str(
+        string: impl AsRef<str>,
+        price_step: DecimalTickSize) -> Result<Self, ParsePriceError>
+    {
+        let (mut price, price_scale) = parse_decimal(string.as_ref())?;
+        let DecimalTickSize { mantissa: mut step, scale: step_scale } = price_step;
+        // Bring both numbers to the same scale
+        if price_scale > step_scale {
+            step = step.checked_mul(10_i128.pow(price_scale - step_scale))
+                .ok_or(ParsePriceError::Overflow)?
+        } else {
+            price = price.checked_mul(10_i128.pow(step_scale - price_scale))
+                .ok_or(ParsePriceError::Overflow)?
+        }
+        if price % step != 0 {
+            return Err(ParsePriceError::OffTickGrid);
+        }
+        i64::try_from(price / step)
+            .map(Tick)
+            .map_err(|_| ParsePriceError::Overflow)
+    }
+
     #[inline]
     /// Converts [`f64`] to [`Price`].
     ///
@@ -91,6 +336,28 @@ impl Tick
     }
 }
 
+impl Fee
+{
+    /// Number of the fee units in the tick times lot.
+    pub const UNITS_PER_TICK: i64 = 1_000_000;
+
+    #[inline]
+    /// Converts the amount in ticks times lots to [`Fee`], rounding to the nearest unit.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` — Amount in ticks times lots.
+    pub fn from_ticks(value: f64) -> Self {
+        Fee((value * Self::UNITS_PER_TICK as f64).round() as i64)
+    }
+
+    #[inline]
+    /// Converts [`Fee`] to the amount in ticks times lots.
+    pub fn to_ticks(&self) -> f64 {
+        self.0 as f64 / Self::UNITS_PER_TICK as f64
+    }
+}
+
 impl From<Tick> for isize {
     fn from(price: Tick) -> Self {
         price.0 as isize
@@ -116,4 +383,85 @@ impl Ord for TickSize {
             Ordering::Greater
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(string: &str, price_step: &str) -> Result<Tick, ParsePriceError> {
+        Tick::try_from_decimal_str(string, DecimalTickSize::from_decimal_str(price_step).unwrap())
+    }
+
+    #[test]
+    fn test_decimal_sign() {
+        assert_eq!(parse("+1.5", "0.5"), Ok(Tick(3)));
+        assert_eq!(parse("-1.5", "0.5"), Ok(Tick(-3)));
+        assert_eq!(parse("-0", "0.5"), Ok(Tick(0)));
+        for string in ["-", "+", "--1", "+-1", "1-"] {
+            assert_eq!(parse(string, "0.5"), Err(ParsePriceError::InvalidFormat), "{string}")
+        }
+        assert_eq!(DecimalTickSize::from_decimal_str("-0.5"), Err(ParsePriceError::InvalidFormat));
+        assert_eq!(DecimalTickSize::from_decimal_str("0"), Err(ParsePriceError::InvalidFormat))
+    }
+
+    #[test]
+    fn test_decimal_dots() {
+        assert_eq!(parse(".5", "0.5"), Ok(Tick(1)));
+        assert_eq!(parse("-.5", "0.5"), Ok(Tick(-1)));
+        assert_eq!(parse("2.", "0.5"), Ok(Tick(4)));
+        for string in [".", "-.", "", "1.2.3", "1e3", " 1", "1,5"] {
+            assert_eq!(parse(string, "0.5"), Err(ParsePriceError::InvalidFormat), "{string}")
+        }
+    }
+
+    #[test]
+    fn test_decimal_fraction_digits() {
+        assert_eq!(parse("1.000000000000000000000", "0.5"), Ok(Tick(2)));
+        assert_eq!(parse("0.000000000000000001", "0.000000000000000001"), Ok(Tick(1)));
+        assert_eq!(parse("0.0000000000000000001", "0.5"), Err(ParsePriceError::Overflow));
+        assert_eq!(
+            DecimalTickSize::from_decimal_str("0.0000000000000000001"),
+            Err(ParsePriceError::Overflow)
+        );
+        assert_eq!(parse("1.03", "0.05"), Err(ParsePriceError::OffTickGrid));
+        assert_eq!(parse("1.05", "0.05"), Ok(Tick(21)))
+    }
+
+    #[test]
+    fn test_decimal_overflow() {
+        assert_eq!(parse("9223372036854775807", "1"), Ok(Tick(i64::MAX)));
+        assert_eq!(parse("-9223372036854775808", "1"), Ok(Tick(i64::MIN)));
+        assert_eq!(parse("9223372036854775808", "1"), Err(ParsePriceError::Overflow));
+        assert_eq!(parse(&"9".repeat(40), "1"), Err(ParsePriceError::Overflow));
+        // Scaling the price up to the scale of the price step overflows
+        assert_eq!(
+            parse(&"9".repeat(30), "0.000000000000000001"),
+            Err(ParsePriceError::Overflow)
+        )
+    }
+
+    #[test]
+    fn test_decimal_tick_size_from_f64() {
+        assert_eq!(
+            DecimalTickSize::try_from(TickSize(0.01)),
+            DecimalTickSize::from_decimal_str("0.01")
+        );
+        assert_eq!(
+            DecimalTickSize::try_from(TickSize(0.0000001)),
+            DecimalTickSize::from_decimal_str("0.0000001")
+        );
+        for price_step in [0.0, -0.01, f64::NAN, f64::INFINITY] {
+            assert_eq!(
+                DecimalTickSize::try_from(TickSize(price_step)),
+                Err(ParsePriceError::InvalidFormat)
+            )
+        }
+    }
+
+    #[test]
+    fn test_from_decimal_str_rounds_through_f64() {
+        assert_eq!(Tick::from_decimal_str("1e1", TickSize(0.5)), Tick(20));
+        assert_eq!(Tick::from_decimal_str("0.30000000000000004", TickSize(0.1)), Tick(3))
+    }
+}
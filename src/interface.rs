@@ -1,3 +1,5 @@
+/// Everything related to the [`Auxiliary`](auxiliary::Auxiliary) agents of the custom roles.
+pub mod auxiliary;
 /// Everything related to the [`Broker`](broker::Broker).
 pub mod broker;
 /// Everything related to the [`Exchange`](exchange::Exchange).
@@ -9,4 +11,5 @@ pub mod replay;
 /// Everything related to the [`Trader`](trader::Trader).
 pub mod trader;
 /// Inter-agent message protocol traits.
-pub mod message;
\ No newline at end of file
+pub mod message;/// Tunable parameters of the agent configs.
+pub mod tunable;
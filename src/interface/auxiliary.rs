@@ -0,0 +1,185 @@
+use {
+    crate::{
+        interface::{
+            broker::Broker,
+            message::{AuxiliaryToAuxiliary, BrokerToItself, TraderToItself},
+            trader::Trader,
+        },
+        types::{Agent, DateTime, Id, NanoDuration, Named, NeverType, TimeSync},
+        utils::queue::MessageReceiver,
+    },
+    core::{convert::Infallible, marker::PhantomData},
+    rand::Rng,
+};
+
+/// Defines [`Auxiliary`] reaction to anything.
+/// Supposed to be processed by `process_action` closures from the [`Auxiliary`] method signatures
+/// before pushing into the [`Kernel`](crate::kernel::Kernel) queue.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub struct AuxiliaryAction<
+    A2A: AuxiliaryToAuxiliary,
+    TraderID: Id,
+    T2T: TraderToItself,
+    BrokerID: Id,
+    B2B: BrokerToItself
+> {
+    /// Delay between the current datetime of the [`Auxiliary`]
+    /// and the datetime of popping this action
+    /// out of the [`Kernel`](crate::kernel::Kernel) queue.
+    pub delay: NanoDuration,
+    /// [`Auxiliary`] action content.
+    pub content: AuxiliaryActionKind<A2A, TraderID, T2T, BrokerID, B2B>,
+}
+
+/// [`Auxiliary`] action content.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum AuxiliaryActionKind<
+    A2A: AuxiliaryToAuxiliary,
+    TraderID: Id,
+    T2T: TraderToItself,
+    BrokerID: Id,
+    B2B: BrokerToItself
+> {
+    /// Message to the [`Auxiliary`] agent, possibly to itself.
+    AuxiliaryToAuxiliary(A2A),
+    /// Wakeup of the [`Trader`], delivered as if the trader has scheduled it itself.
+    AuxiliaryToTrader {
+        /// Recipient.
+        trader_id: TraderID,
+        /// Wakeup message.
+        t2t: T2T,
+    },
+    /// Wakeup of the [`Broker`], delivered as if the broker has scheduled it itself.
+    AuxiliaryToBroker {
+        /// Recipient.
+        broker_id: BrokerID,
+        /// Wakeup message.
+        b2b: B2B,
+    },
+}
+
+/// Provides custom interface of the agent which role is none of the four built-in ones,
+/// e.g. the news feed or the risk server.
+///
+/// Auxiliary agents exchange their own messages routed by the
+/// [`Kernel`](crate::kernel::Kernel) by time, without latencies.
+/// They affect the other agents by waking up the [traders](Trader) and the [brokers](Broker)
+/// with the messages of their own wakeup formats, e.g. carrying the news item,
+/// so the traders and the brokers need not know about the auxiliary agents at all.
+/// Auxiliary agents are added with the
+/// [`with_auxiliary_agents`](crate::kernel::KernelBuilder::with_auxiliary_agents).
+pub trait Auxiliary
+    where Self: TimeSync,
+          Self: Named<Self::AuxiliaryID>,
+          Self: Agent<Action=AuxiliaryAction<
+              Self::A2A, Self::TraderID, Self::T2T, Self::BrokerID, Self::B2B
+          >>
+{
+    /// [`Auxiliary`] identifier type.
+    type AuxiliaryID: Id;
+    /// [`Trader`] identifier type.
+    type TraderID: Id;
+    /// [`Broker`] identifier type.
+    type BrokerID: Id;
+
+    /// Message format of the [`Auxiliary`] agents.
+    type A2A: AuxiliaryToAuxiliary<AuxiliaryID=Self::AuxiliaryID>;
+    /// [`Trader`]-to-itself query format.
+    type T2T: TraderToItself;
+    /// [`Broker`]-to-itself query format.
+    type B2B: BrokerToItself;
+
+    /// Called once when the [`Kernel`](crate::kernel::Kernel) is built,
+    /// e.g. to schedule the first messages.
+    /// The current datetime of the [`Auxiliary`] is already set to the start of the simulation.
+    ///
+    /// # Arguments
+    ///
+    /// * `message_receiver` — Proxy providing pushing access
+    ///   to the [`Kernel`](crate::kernel::Kernel) event queue.
+    /// * `process_action` — Closure needed to preprocess the [`Auxiliary`]'s `Self::Action`
+    ///   into a format suitable for pushing
+    ///   into the [`Kernel`](crate::kernel::Kernel) event queue.
+    /// * `rng` — Thread-unique [`Kernel`](crate::kernel::Kernel) random number generator.
+    fn initialize<KerMsg: Ord, RNG: Rng>(
+        &mut self,
+        message_receiver: MessageReceiver<KerMsg>,
+        process_action: impl FnMut(Self::Action, &mut RNG) -> KerMsg,
+        rng: &mut RNG,
+    );
+
+    /// Defines the [`Auxiliary`] reaction to an incoming message
+    /// from the [`Auxiliary`] agent, possibly from itself.
+    /// Called whenever the [`Kernel`](crate::kernel::Kernel)
+    /// pops a [`Self::A2A`] message out of its event queue.
+    ///
+    /// # Arguments
+    ///
+    /// * `message_receiver` — Proxy providing pushing access
+    ///   to the [`Kernel`](crate::kernel::Kernel) event queue.
+    /// * `process_action` — Closure needed to preprocess the [`Auxiliary`]'s `Self::Action`
+    ///   into a format suitable for pushing
+    ///   into the [`Kernel`](crate::kernel::Kernel) event queue.
+    /// * `message` — Received message to react to.
+    /// * `sender_id` — Unique id of the [`Auxiliary`] agent that sent the message received.
+    /// * `rng` — Thread-unique [`Kernel`](crate::kernel::Kernel) random number generator.
+    fn process_auxiliary_message<KerMsg: Ord, RNG: Rng>(
+        &mut self,
+        message_receiver: MessageReceiver<KerMsg>,
+        process_action: impl FnMut(Self::Action, &mut RNG) -> KerMsg,
+        message: Self::A2A,
+        sender_id: Self::AuxiliaryID,
+        rng: &mut RNG,
+    );
+}
+
+/// Placeholder [`Auxiliary`] of the [`Kernel`](crate::kernel::Kernel)
+/// without the auxiliary agents. Cannot be instantiated.
+pub struct NoAuxiliary<T: Trader, B: Broker>(Infallible, PhantomData<fn() -> (T, B)>);
+
+impl<T: Trader, B: Broker> TimeSync for NoAuxiliary<T, B> {
+    fn current_datetime_mut(&mut self) -> &mut DateTime {
+        match self.0 {}
+    }
+}
+
+impl<T: Trader, B: Broker> Named<NeverType<()>> for NoAuxiliary<T, B> {
+    fn get_name(&self) -> NeverType<()> {
+        match self.0 {}
+    }
+}
+
+impl<T: Trader, B: Broker> Agent for NoAuxiliary<T, B> {
+    type Action = AuxiliaryAction<
+        NeverType<NeverType<()>>, T::TraderID, T::T2T, B::BrokerID, B::B2B
+    >;
+}
+
+impl<T: Trader, B: Broker> Auxiliary for NoAuxiliary<T, B> {
+    type AuxiliaryID = NeverType<()>;
+    type TraderID = T::TraderID;
+    type BrokerID = B::BrokerID;
+    type A2A = NeverType<NeverType<()>>;
+    type T2T = T::T2T;
+    type B2B = B::B2B;
+
+    fn initialize<KerMsg: Ord, RNG: Rng>(
+        &mut self,
+        _: MessageReceiver<KerMsg>,
+        _: impl FnMut(Self::Action, &mut RNG) -> KerMsg,
+        _: &mut RNG)
+    {
+        match self.0 {}
+    }
+
+    fn process_auxiliary_message<KerMsg: Ord, RNG: Rng>(
+        &mut self,
+        _: MessageReceiver<KerMsg>,
+        _: impl FnMut(Self::Action, &mut RNG) -> KerMsg,
+        _: Self::A2A,
+        _: Self::AuxiliaryID,
+        _: &mut RNG)
+    {
+        match self.0 {}
+    }
+}
@@ -13,7 +13,7 @@ use {
             },
         },
         kernel::LatentActionProcessor,
-        types::{Agent, Id, Named, TimeSync},
+        types::{Agent, Id, NanoDuration, Named, TimeSync},
         utils::queue::MessageReceiver,
     },
     rand::Rng,
@@ -29,11 +29,11 @@ pub struct BrokerAction<B2R, B2E, B2T, B2B>
           B2T: BrokerToTrader,
           B2B: BrokerToItself
 {
-    /// Constant part of the delay, in nanoseconds, between the current datetime of the [`Broker`]
+    /// Constant part of the delay between the current datetime of the [`Broker`]
     /// and the datetime of popping this action
     /// out of the [`Kernel`](crate::kernel::Kernel) queue.
     /// The final delay is the sum of this delay and the latency.
-    pub delay: u64,
+    pub delay: NanoDuration,
     /// [`Broker`] action content.
     pub content: BrokerActionKind<B2R, B2E, B2T, B2B>,
 }
@@ -48,6 +48,10 @@ pub enum BrokerActionKind<B2R, B2E, B2T, B2B>
 {
     /// [`Broker`]-to-itself message.
     BrokerToItself(B2B),
+    /// [`Broker`]-to-[`Broker`] message.
+    /// Is delivered to the [`Broker`] returned by the [`Broker::get_b2b_recipient`].
+    /// Latency is not applied, so the `delay` should account for it.
+    BrokerToBroker(B2B),
     /// [`Broker`]-to-[`Replay`](crate::interface::replay::Replay) message.
     BrokerToReplay(B2R),
     /// [`Broker`]-to-[`Exchange`](crate::interface::exchange::Exchange) message.
@@ -181,6 +185,47 @@ pub trait Broker
         rng: &mut impl Rng,
     );
 
+    /// Defines the [`Broker`] reaction to an incoming message from another [`Broker`].
+    /// Called whenever the [`Kernel`](crate::kernel::Kernel)
+    /// pops a [`BrokerActionKind::BrokerToBroker`] message out of its event queue.
+    /// Panics by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `message_receiver` — Proxy providing pushing access
+    ///                        to the [`Kernel`](crate::kernel::Kernel) event queue.
+    /// * `action_processor` — Structure needed to preprocess the [`Broker`]'s `Self::Action`
+    ///                        into a format suitable for pushing
+    ///                        into the [`Kernel`](crate::kernel::Kernel) event queue.
+    /// * `message` — Received message to react to.
+    /// * `broker_id` — Unique id of the [`Broker`] that sent the message received.
+    /// * `rng` — Thread-unique [`Kernel`](crate::kernel::Kernel) random number generator.
+    fn process_broker_message<KerMsg: Ord>(
+        &mut self,
+        _message_receiver: MessageReceiver<KerMsg>,
+        _action_processor: impl LatentActionProcessor<Self::Action, Self::ExchangeID, KerMsg=KerMsg>,
+        _message: Self::B2B,
+        broker_id: Self::BrokerID,
+        _rng: &mut impl Rng,
+    ) {
+        unreachable!(
+            "Broker {} does not expect messages from other brokers. Got one from {broker_id}",
+            self.get_name()
+        )
+    }
+
+    /// Returns the unique id of the [`Broker`]
+    /// the [`BrokerActionKind::BrokerToBroker`] message sent by this [`Broker`] is addressed to.
+    /// Called by the [`Kernel`](crate::kernel::Kernel) when it pops the message
+    /// out of its event queue. Panics by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` — Message sent by this [`Broker`].
+    fn get_b2b_recipient(&self, _message: &Self::B2B) -> Self::BrokerID {
+        unreachable!("Broker {} does not send messages to other brokers", self.get_name())
+    }
+
     /// Called whenever the [`Broker`]
     /// is being connected to an [`Exchange`](crate::interface::exchange::Exchange).
     ///
@@ -7,7 +7,7 @@ use {
             ExchangeToReplay,
             ReplayToExchange,
         },
-        types::{Agent, Id, Named, TimeSync},
+        types::{Agent, Id, NanoDuration, Named, TimeSync},
         utils::queue::MessageReceiver,
     },
     rand::Rng,
@@ -22,10 +22,10 @@ pub struct ExchangeAction<
     E2B: ExchangeToBroker,
     E2E: ExchangeToItself
 > {
-    /// Delay, in nanoseconds, between the current datetime of the [`Exchange`]
+    /// Delay between the current datetime of the [`Exchange`]
     /// and the datetime of popping this action
     /// out of the [`Kernel`](crate::kernel::Kernel) queue.
-    pub delay: u64,
+    pub delay: NanoDuration,
     /// [`Exchange`] action content.
     pub content: ExchangeActionKind<E2R, E2B, E2E>,
 }
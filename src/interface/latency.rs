@@ -1,4 +1,9 @@
-use {crate::types::{DateTime, Id}, rand::Rng};
+use {
+    crate::types::{DateTime, Id, NanoDuration},
+    alloc::vec::Vec,
+    core::num::NonZeroU64,
+    rand::Rng,
+};
 
 /// Is implemented for latent agents:
 /// that is, for those who may have an unintentional delay
@@ -15,6 +20,21 @@ pub trait Latent
     /// Returns [`LatencyGenerator`] describing a probabilistic model of latency
     /// against agents to which the `Self` agent connects.
     fn get_latency_generator(&self) -> Self::LatencyGenerator;
+
+    /// Takes back the [`LatencyGenerator`] after the latent delays have been sampled with it:
+    /// by the [`Kernel`](crate::kernel::Kernel) for the incoming messages
+    /// or by [`LatentActionProcessor`](crate::kernel::LatentActionProcessor)
+    /// for the outgoing ones, in which case the agent should call it itself. Since
+    /// [`get_latency_generator`](Latent::get_latency_generator) returns copies, this is
+    /// the way for the stateful generators to keep their state between the samplings.
+    /// Does nothing by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `latency_generator` — Latency generator with the updated state.
+    fn update_latency_generator(&mut self, latency_generator: Self::LatencyGenerator) {
+        let _ = latency_generator;
+    }
 }
 
 /// Describes a probabilistic model of latency.
@@ -39,7 +59,7 @@ pub trait LatencyGenerator: Copy
         &mut self,
         outer_id: Self::OuterID,
         event_dt: DateTime,
-        rng: &mut impl Rng) -> u64;
+        rng: &mut impl Rng) -> NanoDuration;
 
     /// Samples latent delay that is incoming for the generating agent
     /// and is outgoing for the anchor one.
@@ -57,5 +77,50 @@ pub trait LatencyGenerator: Copy
         &mut self,
         outer_id: Self::OuterID,
         event_dt: DateTime,
-        rng: &mut impl Rng) -> u64;
+        rng: &mut impl Rng) -> NanoDuration;
+
+    /// Returns the throughput, in bytes per second, of the link
+    /// that is outgoing for the generating agent and is incoming for the anchor one.
+    ///
+    /// Messages sent over the link are transmitted one by one,
+    /// each for its [size](crate::interface::message::TraderToBroker::get_size_in_bytes)
+    /// divided by the throughput. Thus, bursts of messages are queued before the latent delay
+    /// is sampled. `None` stands for the unlimited throughput, which is the default.
+    ///
+    /// # Arguments
+    ///
+    /// * `outer_id` — ID of the anchor agent.
+    fn outgoing_bandwidth(&self, outer_id: Self::OuterID) -> Option<NonZeroU64> {
+        let _ = outer_id;
+        None
+    }
+
+    /// Returns the throughput, in bytes per second, of the link
+    /// that is incoming for the generating agent and is outgoing for the anchor one.
+    /// See [`outgoing_bandwidth`](LatencyGenerator::outgoing_bandwidth) for details.
+    ///
+    /// # Arguments
+    ///
+    /// * `outer_id` — ID of the anchor agent.
+    fn incoming_bandwidth(&self, outer_id: Self::OuterID) -> Option<NonZeroU64> {
+        let _ = outer_id;
+        None
+    }
+
+    /// Returns the serialized stochastic state of the generator
+    /// so that it can be persisted and the sampling resumed exactly.
+    /// `None` stands for the stateless generator, which is the default.
+    fn save_state(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Restores the state previously returned by [`save_state`](LatencyGenerator::save_state).
+    /// Does nothing by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` — Serialized state of the generator.
+    fn restore_state(&mut self, state: &[u8]) {
+        let _ = state;
+    }
 }
\ No newline at end of file
@@ -1,7 +1,13 @@
-use crate::types::{Id, NeverType, Nothing};
+use crate::{types::{DateTime, Id, NeverType, Nothing}, utils::queue::ScheduleHandle};
 
 /// Indicates that the type is the [`Trader`](crate::interface::trader::Trader)-to-itself message.
-pub trait TraderToItself: Ord {}
+pub trait TraderToItself: Ord {
+    /// Returns the handle the message can be revoked by before it is delivered.
+    /// `None` if the message is not revocable.
+    fn get_schedule_handle(&self) -> Option<ScheduleHandle> {
+        None
+    }
+}
 
 /// Indicates that the type is the
 /// [`Trader`](crate::interface::trader::Trader)-to-[`Broker`](crate::interface::broker::Broker)
@@ -9,10 +15,22 @@ pub trait TraderToItself: Ord {}
 pub trait TraderToBroker: Ord {
     type BrokerID: Id;
     fn get_broker_id(&self) -> Self::BrokerID;
+
+    /// Returns the size of the message, in bytes, transmitted over the bandwidth-constrained link.
+    /// Zero-sized messages are not delayed by the bandwidth limits, which is the default.
+    fn get_size_in_bytes(&self) -> u64 {
+        0
+    }
 }
 
 /// Indicates that the type is the [`Broker`](crate::interface::broker::Broker)-to-itself message.
-pub trait BrokerToItself: Ord {}
+pub trait BrokerToItself: Ord {
+    /// Returns the handle the message can be revoked by before it is delivered.
+    /// `None` if the message is not revocable.
+    fn get_schedule_handle(&self) -> Option<ScheduleHandle> {
+        None
+    }
+}
 
 /// Indicates that the type is the
 /// [`Broker`](crate::interface::broker::Broker)-to-[`Replay`](crate::interface::replay::Replay)
@@ -25,6 +43,12 @@ pub trait BrokerToReplay: Ord {}
 pub trait BrokerToExchange: Ord {
     type ExchangeID: Id;
     fn get_exchange_id(&self) -> Self::ExchangeID;
+
+    /// Returns the size of the message, in bytes, transmitted over the bandwidth-constrained link.
+    /// Zero-sized messages are not delayed by the bandwidth limits, which is the default.
+    fn get_size_in_bytes(&self) -> u64 {
+        0
+    }
 }
 
 /// Indicates that the type is the
@@ -33,11 +57,30 @@ pub trait BrokerToExchange: Ord {
 pub trait BrokerToTrader: Ord {
     type TraderID: Id;
     fn get_trader_id(&self) -> Self::TraderID;
+
+    /// Returns the size of the message, in bytes, transmitted over the bandwidth-constrained link.
+    /// Zero-sized messages are not delayed by the bandwidth limits, which is the default.
+    fn get_size_in_bytes(&self) -> u64 {
+        0
+    }
+
+    /// Returns the datetime of the event the message informs about, if any,
+    /// e.g. to detect the strategies that read the market data before it has happened.
+    /// `None` by default.
+    fn get_event_dt(&self) -> Option<DateTime> {
+        None
+    }
 }
 
 /// Indicates that the type is the
 /// [`Exchange`](crate::interface::exchange::Exchange)-to-itself message.
-pub trait ExchangeToItself: Ord {}
+pub trait ExchangeToItself: Ord {
+    /// Returns the handle the message can be revoked by before it is delivered.
+    /// `None` if the message is not revocable.
+    fn get_schedule_handle(&self) -> Option<ScheduleHandle> {
+        None
+    }
+}
 
 /// Indicates that the type is the
 /// [`Exchange`](crate::interface::exchange::Exchange)-to-[`Replay`](
@@ -50,6 +93,12 @@ pub trait ExchangeToReplay: Ord {}
 pub trait ExchangeToBroker: Ord {
     type BrokerID: Id;
     fn get_broker_id(&self) -> Self::BrokerID;
+
+    /// Returns the size of the message, in bytes, transmitted over the bandwidth-constrained link.
+    /// Zero-sized messages are not delayed by the bandwidth limits, which is the default.
+    fn get_size_in_bytes(&self) -> u64 {
+        0
+    }
 }
 
 /// Indicates that the type is the
@@ -72,6 +121,21 @@ pub trait ReplayToBroker: Ord {
     fn get_broker_id(&self) -> Self::BrokerID;
 }
 
+/// Indicates that the type is the message between the
+/// [auxiliary agents](crate::interface::auxiliary::Auxiliary),
+/// including the ones the agent sends to itself.
+pub trait AuxiliaryToAuxiliary: Ord {
+    type AuxiliaryID: Id;
+    /// Returns the ID of the recipient.
+    fn get_auxiliary_id(&self) -> Self::AuxiliaryID;
+
+    /// Returns the handle the message can be revoked by before it is delivered.
+    /// `None` if the message is not revocable.
+    fn get_schedule_handle(&self) -> Option<ScheduleHandle> {
+        None
+    }
+}
+
 
 impl TraderToItself for Nothing {}
 
@@ -126,4 +190,11 @@ impl<BrokerID: Id> ReplayToBroker for NeverType<BrokerID> {
     fn get_broker_id(&self) -> BrokerID {
         unreachable!("Does not contain BrokerID")
     }
-}
\ No newline at end of file
+}
+
+impl<AuxiliaryID: Id> AuxiliaryToAuxiliary for NeverType<AuxiliaryID> {
+    type AuxiliaryID = AuxiliaryID;
+    fn get_auxiliary_id(&self) -> Self::AuxiliaryID {
+        unreachable!("Does not contain AuxiliaryID")
+    }
+}
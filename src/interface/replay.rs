@@ -7,10 +7,10 @@ use {
             ReplayToExchange,
             ReplayToItself,
         },
-        types::{DateTime, Id, TimeSync},
+        types::{DateTime, Id, IdAllocator, TimeSync},
     },
+    core::fmt::Debug,
     rand::Rng,
-    std::fmt::Debug,
 };
 
 /// Defines [`Replay`] reaction to anything.
@@ -99,4 +99,13 @@ pub trait Replay
         broker_id: Self::BrokerID,
         rng: &mut impl Rng,
     );
+
+    /// Returns the allocator the [`Replay`] takes the IDs of its orders from, if any.
+    /// The [`KernelBuilder`](crate::kernel::KernelBuilder) shares it with the
+    /// [traders](crate::interface::trader::Trader), so that their order IDs
+    /// never collide with the ones of the [`Replay`].
+    /// Returns `None` by default, in which case the new allocator is shared.
+    fn get_id_allocator(&self) -> Option<&IdAllocator> {
+        None
+    }
 }
\ No newline at end of file
@@ -2,22 +2,24 @@ use {
     crate::{
         interface::{latency::Latent, message::{BrokerToTrader, TraderToBroker, TraderToItself}},
         kernel::LatentActionProcessor,
-        types::{Agent, Id, Named, TimeSync},
+        types::{Agent, DateTime, Id, IdAllocator, NanoDuration, Named, TimeSync},
         utils::queue::MessageReceiver,
     },
     rand::Rng,
 };
+#[cfg(feature = "lookahead_guard")]
+use crate::kernel::lookahead::Delivered;
 
 /// Defines [`Trader`] reaction to anything.
 /// Supposed to be processed by [`LatentActionProcessor`]
 /// before pushing into the [`Kernel`](crate::kernel::Kernel) queue.
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd)]
 pub struct TraderAction<T2B: TraderToBroker, T2T: TraderToItself> {
-    /// Constant part of the delay, in nanoseconds, between the current datetime of the [`Trader`]
+    /// Constant part of the delay between the current datetime of the [`Trader`]
     /// and the datetime of popping this action
     /// out of the [`Kernel`](crate::kernel::Kernel) queue.
     /// The final delay is the sum of this delay and the latency.
-    pub delay: u64,
+    pub delay: NanoDuration,
     /// [`Trader`] action content.
     pub content: TraderActionKind<T2B, T2T>,
 }
@@ -97,6 +99,39 @@ pub trait Trader
         rng: &mut impl Rng,
     );
 
+    #[cfg(feature = "lookahead_guard")]
+    /// Same as the [`process_broker_reply`](Trader::process_broker_reply),
+    /// but receives the reply wrapped by the [`Kernel`](crate::kernel::Kernel)
+    /// into the [`Delivered`], which reports the comparisons of the reply
+    /// with the datetimes in the future of the simulation clock.
+    /// Called by the [`Kernel`](crate::kernel::Kernel) instead of the
+    /// [`process_broker_reply`](Trader::process_broker_reply) when the `lookahead_guard`
+    /// feature is enabled. Unwraps the reply and passes it
+    /// to the [`process_broker_reply`](Trader::process_broker_reply) by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `message_receiver` — Proxy providing pushing access
+    ///   to the [`Kernel`](crate::kernel::Kernel) event queue.
+    /// * `action_processor` — Structure needed to preprocess the [`Trader`]'s `Self::Action`
+    ///   into a format suitable for pushing into the [`Kernel`](crate::kernel::Kernel) event queue.
+    /// * `reply` — Received message to react to, along with its delivery datetime.
+    /// * `broker_id` — Unique id of the [`Broker`](crate::interface::broker::Broker)
+    ///   who sent the message received.
+    /// * `rng` — Thread-unique [`Kernel`](crate::kernel::Kernel) random number generator.
+    fn process_delivered_broker_reply<KerMsg: Ord>(
+        &mut self,
+        message_receiver: MessageReceiver<KerMsg>,
+        action_processor: impl LatentActionProcessor<Self::Action, Self::BrokerID, KerMsg=KerMsg>,
+        reply: Delivered<Self::B2T>,
+        broker_id: Self::BrokerID,
+        rng: &mut impl Rng,
+    ) {
+        self.process_broker_reply(
+            message_receiver, action_processor, reply.into_inner(), broker_id, rng,
+        )
+    }
+
     /// Called whenever the [`Trader`] registers at [`Broker`](crate::interface::broker::Broker).
     ///
     /// # Arguments
@@ -104,4 +139,60 @@ pub trait Trader
     /// * `broker_id` — Unique id of the [`Broker`](crate::interface::broker::Broker)
     ///                 to register at.
     fn upon_register_at_broker(&mut self, broker_id: Self::BrokerID);
+
+    /// Samples the time the [`Trader`] spends computing its next decision,
+    /// i.e. its reaction to a single wakeup, a single broker reply or a single idle period.
+    /// Called by the [`Kernel`](crate::kernel::Kernel) right before the reaction.
+    /// All the actions of the reaction are emitted after this delay,
+    /// which is added to their own delays before the latency is sampled.
+    /// Returns zero by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `rng` — Thread-unique [`Kernel`](crate::kernel::Kernel) random number generator.
+    fn sample_decision_latency(&mut self, rng: &mut impl Rng) -> NanoDuration {
+        let _ = rng;
+        NanoDuration::ZERO
+    }
+
+    /// Called whenever the [`Kernel`](crate::kernel::Kernel) fast-forwards over a period
+    /// with no events that is not shorter than the idle period threshold,
+    /// e.g. the overnight session break.
+    /// The current datetime of the [`Trader`] is already set to the end of the period,
+    /// so that the actions, e.g. the orders of the new session, are scheduled after it.
+    /// Traders are notified in the ascending order of their IDs.
+    /// Does nothing by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `message_receiver` — Proxy providing pushing access
+    ///   to the [`Kernel`](crate::kernel::Kernel) event queue.
+    /// * `action_processor` — Structure needed to preprocess the [`Trader`]'s `Self::Action`
+    ///   into a format suitable for pushing into the [`Kernel`](crate::kernel::Kernel) event queue.
+    /// * `idle_start` — Datetime of the last event before the period.
+    /// * `idle_end` — Datetime of the first event after the period.
+    /// * `rng` — Thread-unique [`Kernel`](crate::kernel::Kernel) random number generator.
+    fn on_idle_period<KerMsg: Ord>(
+        &mut self,
+        message_receiver: MessageReceiver<KerMsg>,
+        action_processor: impl LatentActionProcessor<Self::Action, Self::BrokerID, KerMsg=KerMsg>,
+        idle_start: DateTime,
+        idle_end: DateTime,
+        rng: &mut impl Rng,
+    ) {
+        let _ = (message_receiver, action_processor, idle_start, idle_end, rng);
+    }
+
+    /// Called by the [`KernelBuilder`](crate::kernel::KernelBuilder) once the [`Trader`]
+    /// joins the simulation, providing the allocator of the simulation-wide unique IDs,
+    /// e.g. to generate the order IDs that never collide with the ones
+    /// of the [`Replay`](crate::interface::replay::Replay) and of the other traders.
+    /// Ignores the allocator by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `id_allocator` — Allocator shared by the agents of the simulation.
+    fn set_id_allocator(&mut self, id_allocator: IdAllocator) {
+        let _ = id_allocator;
+    }
 }
\ No newline at end of file
@@ -0,0 +1,208 @@
+use {
+    alloc::{string::String, vec::Vec},
+    core::fmt::{Display, Formatter},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+/// Value of the tunable parameter.
+pub enum ParamValue {
+    /// Value of the integer parameter.
+    Integer(i64),
+    /// Value of the floating-point parameter.
+    Real(f64),
+    /// Value of the string parameter.
+    String(String),
+}
+
+impl Display for ParamValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Integer(value) => Display::fmt(value, f),
+            Self::Real(value) => Display::fmt(value, f),
+            Self::String(value) => Display::fmt(value, f)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Admissible values of the tunable parameter. Bounds are inclusive.
+pub enum ParamRange {
+    /// Integer parameter.
+    Integer {
+        /// Lower bound. `None` if unbounded.
+        min: Option<i64>,
+        /// Upper bound. `None` if unbounded.
+        max: Option<i64>,
+    },
+    /// Floating-point parameter.
+    Real {
+        /// Lower bound. `None` if unbounded.
+        min: Option<f64>,
+        /// Upper bound. `None` if unbounded.
+        max: Option<f64>,
+    },
+    /// String parameter.
+    String {
+        /// Admissible values. Any string is admissible if empty.
+        choices: Vec<&'static str>,
+    },
+}
+
+impl ParamRange {
+    /// Returns whether the value is admissible.
+    /// Integer values are admissible for the floating-point parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` — Value to check.
+    pub fn contains(&self, value: &ParamValue) -> bool {
+        match (self, value) {
+            (Self::Integer { min, max }, ParamValue::Integer(value)) => {
+                min.is_none_or(|min| min <= *value) && max.is_none_or(|max| *value <= max)
+            }
+            (Self::Real { min, max }, ParamValue::Real(value)) => {
+                !value.is_nan()
+                    && min.is_none_or(|min| min <= *value)
+                    && max.is_none_or(|max| *value <= max)
+            }
+            (Self::Real { .. }, ParamValue::Integer(value)) => {
+                self.contains(&ParamValue::Real(*value as f64))
+            }
+            (Self::String { choices }, ParamValue::String(value)) => {
+                choices.is_empty() || choices.contains(&value.as_str())
+            }
+            _ => false
+        }
+    }
+
+    /// Returns the evenly spaced values spanning the range, e.g. for the grid search.
+    /// Integer values are deduplicated, whereas all the choices of the string parameter
+    /// are returned regardless of the `num_points`.
+    /// Returns `None` if the range is unbounded.
+    ///
+    /// # Arguments
+    ///
+    /// * `num_points` — Number of the values.
+    pub fn grid(&self, num_points: usize) -> Option<Vec<ParamValue>> {
+        let last = num_points.saturating_sub(1).max(1);
+        match self {
+            Self::Integer { min: Some(min), max: Some(max) } => {
+                let span = (*max as i128 - *min as i128).max(0);
+                let mut values: Vec<_> = (0..num_points)
+                    .map(|i| (*min as i128 + span * i as i128 / last as i128) as i64)
+                    .map(ParamValue::Integer)
+                    .collect();
+                values.dedup();
+                Some(values)
+            }
+            Self::Real { min: Some(min), max: Some(max) } => Some(
+                (0..num_points)
+                    .map(|i| ParamValue::Real(min + (max - min) * i as f64 / last as f64))
+                    .collect()
+            ),
+            Self::String { choices } if !choices.is_empty() => Some(
+                choices.iter().map(|choice| ParamValue::String((*choice).into())).collect()
+            ),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Description of the tunable parameter.
+pub struct ParamSpec {
+    /// Name of the parameter.
+    pub name: &'static str,
+    /// Admissible values of the parameter.
+    pub range: ParamRange,
+}
+
+impl ParamSpec {
+    /// Checks that the value is admissible for the parameter.
+    /// Returns the value converted to the type of the parameter.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` — Value to check.
+    pub fn check(&self, value: ParamValue) -> Result<ParamValue, ParamError> {
+        let name = self.name;
+        let value = match (&self.range, value) {
+            (ParamRange::Real { .. }, ParamValue::Integer(value)) => ParamValue::Real(value as f64),
+            (ParamRange::Integer { .. }, value @ ParamValue::Integer(_))
+            | (ParamRange::Real { .. }, value @ ParamValue::Real(_))
+            | (ParamRange::String { .. }, value @ ParamValue::String(_)) => value,
+            (_, value) => return Err(ParamError::TypeMismatch { name, value })
+        };
+        if self.range.contains(&value) {
+            Ok(value)
+        } else {
+            Err(ParamError::OutOfRange { name, value })
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Error of setting the tunable parameter.
+pub enum ParamError {
+    /// There is no parameter with the given name.
+    Unknown(String),
+    /// Value is of the type other than the one of the parameter.
+    TypeMismatch {
+        /// Name of the parameter.
+        name: &'static str,
+        /// Rejected value.
+        value: ParamValue,
+    },
+    /// Value is out of the range of the parameter.
+    OutOfRange {
+        /// Name of the parameter.
+        name: &'static str,
+        /// Rejected value.
+        value: ParamValue,
+    },
+}
+
+impl Display for ParamError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Unknown(name) => write!(f, "Unknown parameter: {name}"),
+            Self::TypeMismatch { name, value } => {
+                write!(f, "Value {value} is of the wrong type for the parameter {name}")
+            }
+            Self::OutOfRange { name, value } => {
+                write!(f, "Value {value} is out of the range of the parameter {name}")
+            }
+        }
+    }
+}
+
+/// Config of the agent, e.g. of the strategy, exposing its named parameters,
+/// so that the optimizers and the sweep drivers could discover and mutate them generically.
+///
+/// Is usually derived with the `#[derive(Tunable)]` macro of the `enum_dispatch` feature,
+/// which exposes the struct fields marked with the `#[tunable]` attribute:
+///
+/// * `#[tunable]` — Unbounded integer, floating-point or [`String`] parameter.
+/// * `#[tunable(min = 1, max = 10)]` — Bounded numeric parameter. Either bound may be omitted.
+///   Negative bounds are given as strings, e.g. `min = "-1.5"`.
+/// * `#[tunable(choices("fast", "slow"))]` — String parameter with the admissible values.
+pub trait Tunable
+{
+    /// Returns the descriptions of the parameters.
+    fn get_param_specs() -> Vec<ParamSpec>;
+
+    /// Returns the current value of the parameter or `None` if there is no such parameter.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` — Name of the parameter.
+    fn get_param(&self, name: &str) -> Option<ParamValue>;
+
+    /// Sets the parameter if the value is admissible.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` — Name of the parameter.
+    /// * `value` — New value of the parameter.
+    fn set_param(&mut self, name: &str, value: ParamValue) -> Result<(), ParamError>;
+}
@@ -1,6 +1,7 @@
 use {
     crate::{
         interface::{
+            auxiliary::{Auxiliary, AuxiliaryActionKind, NoAuxiliary},
             broker::Broker,
             exchange::{Exchange, ExchangeActionKind},
             latency::LatencyGenerator,
@@ -8,15 +9,101 @@ use {
             replay::{Replay, ReplayActionKind},
             trader::Trader,
         },
-        kernel::action_processors::{BrokerActionProcessor, TraderActionProcessor},
-        types::{DateTime, Duration, Id},
-        utils::queue::{LessElementBinaryHeap, MessageReceiver},
+        kernel::{
+            action_processors::{BrokerActionProcessor, TraderActionProcessor},
+            bandwidth::LinkQueues,
+            tracer::{NoTracer, TraceEndpoint, TraceRecord, Tracer, WithTracer},
+        },
+        types::{DateTime, Duration, Id, IdAllocator, NanoDuration},
+        utils::{
+            collections::HashMap,
+            queue::{LessElementBinaryHeap, MessageReceiver, ScheduleHandle, ScheduleRegistry},
+        },
     },
+    alloc::{boxed::Box, vec::Vec},
+    core::{fmt::Debug, marker::PhantomData},
     rand::{Rng, rngs::StdRng, SeedableRng},
-    std::{collections::HashMap, marker::PhantomData},
 };
+#[cfg(feature = "lookahead_guard")]
+use crate::kernel::lookahead::{LookaheadGuard, LookaheadPolicy};
 
 mod action_processors;
+mod bandwidth;
+#[cfg(feature = "lookahead_guard")]
+/// Debug wrappers detecting the use of the market data ahead of its delivery.
+pub mod lookahead;
+#[cfg(feature = "concrete")]
+#[cfg(test)]
+mod tests;
+/// Hooks receiving every message delivered by the [`Kernel`].
+pub mod tracer;
+
+/// Number of the processed messages between the wall-time limit checks.
+#[cfg(all(feature = "std", not(feature = "deterministic")))]
+const WALL_TIME_CHECK_PERIOD: usize = 1024;
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+/// Resource limits of the single simulation run enforced by the [`Kernel`].
+pub struct ResourceLimits {
+    /// Maximum wall-clock time of the run.
+    /// Requires the `std` feature and is not available with the `deterministic` one,
+    /// in which case the run panics if it is set.
+    pub max_wall_time: Option<core::time::Duration>,
+    /// Maximum memory, in bytes, held by the pending messages of the event queue.
+    /// Best-effort, since the memory allocated by the agents themselves is not accounted for,
+    /// though the runaway event queue is what usually exhausts the memory.
+    pub max_queue_memory: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Adjustment the [`Kernel`] applies to every latent delay sampled
+/// by the [latency generators](LatencyGenerator) of the agents,
+/// e.g. to analyse the sensitivity of the strategies to the latency
+/// without changing the agents themselves.
+pub struct LatencyAdjustment {
+    /// Factor the sampled delays are multiplied by.
+    pub scale: f64,
+    /// Delay added to the scaled ones.
+    pub extra: NanoDuration,
+}
+
+impl Default for LatencyAdjustment {
+    fn default() -> Self {
+        Self { scale: 1.0, extra: NanoDuration::ZERO }
+    }
+}
+
+impl LatencyAdjustment {
+    /// Returns the adjusted delay. Scaled delay is rounded to the nearest nanosecond.
+    ///
+    /// # Arguments
+    ///
+    /// * `latency` — Sampled delay.
+    pub fn apply(self, latency: NanoDuration) -> NanoDuration {
+        // Float-to-integer cast saturates
+        let scaled = NanoDuration((latency.0 as f64 * self.scale + 0.5) as u64);
+        scaled.saturating_add(self.extra)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+/// Resource limit exceeded by the simulation run.
+pub enum LimitViolation {
+    /// Run has taken longer than the [`ResourceLimits::max_wall_time`].
+    WallTime {
+        /// Wall-clock time elapsed since the run has started.
+        elapsed: core::time::Duration,
+        /// Simulated datetime the run has been stopped at.
+        simulated_dt: DateTime,
+    },
+    /// Event queue has held more than the [`ResourceLimits::max_queue_memory`].
+    QueueMemory {
+        /// Memory held by the pending messages.
+        bytes: usize,
+        /// Simulated datetime the run has been stopped at.
+        simulated_dt: DateTime,
+    },
+}
 
 /// Agent action processor needed for latent agents
 /// (i.e. [traders](crate::interface::trader) and [brokers](crate::interface::broker))
@@ -34,55 +121,86 @@ pub trait LatentActionProcessor<Action, OuterID: Id>
     ///
     /// * `action` — Agent action to be processed.
     /// * `latency_generator` — Latency generator of the agent.
+    ///                         Sampling the outgoing delays may update its state,
+    ///                         so the [agent](crate::interface::latency::Latent)
+    ///                         should take it back with `update_latency_generator` afterwards.
     /// * `rng` — Random number generator needed for `latency_generator`.
     fn process_action(
         &mut self,
         action: Action,
-        latency_generator: impl LatencyGenerator<OuterID=OuterID>,
+        latency_generator: &mut impl LatencyGenerator<OuterID=OuterID>,
         rng: &mut impl Rng) -> Self::KerMsg;
 }
 
 /// Runs and controls the simulation process for a single thread.
-pub struct Kernel<T, B, E, R, RNG>
+pub struct Kernel<T, B, E, R, RNG, A = NoAuxiliary<T, B>>
     where
         T: Trader,
         B: Broker,
         E: Exchange,
         R: Replay,
-        RNG: SeedableRng + Rng
+        RNG: SeedableRng + Rng,
+        A: Auxiliary
 {
     traders: HashMap<T::TraderID, T>,
     brokers: HashMap<B::BrokerID, B>,
     exchanges: HashMap<E::ExchangeID, E>,
     replay: R,
+    auxiliaries: HashMap<A::AuxiliaryID, A>,
 
     message_queue: LessElementBinaryHeap<Message<<Self as InnerMessage>::MessageContent>>,
+    schedule_registry: ScheduleRegistry,
+    link_queues: LinkQueues<T::TraderID, B::BrokerID, E::ExchangeID>,
 
     end_dt: DateTime,
     current_dt: DateTime,
 
     rng: RNG,
     num_replay_messages: usize,
+
+    idle_threshold: Option<Duration>,
+
+    tracer: Option<KernelTracerOf<T, B, E, R, RNG, A>>,
+
+    #[cfg(feature = "lookahead_guard")]
+    lookahead_guard: LookaheadGuard,
+}
+
+/// [`Tracer`] along with the function exposing the kernel messages to it.
+struct KernelTracer<ExchangeID: Id, BrokerID: Id, TraderID: Id, AuxiliaryID: Id, MessageContent> {
+    tracer: Box<dyn Tracer<ExchangeID, BrokerID, TraderID, AuxiliaryID>>,
+    get_payload: for<'a> fn(&'a MessageContent) -> &'a dyn Debug,
 }
 
+/// [`KernelTracer`] of the [`Kernel`] with the given agents.
+type KernelTracerOf<T, B, E, R, RNG, A> = KernelTracer<
+    <E as Exchange>::ExchangeID,
+    <B as Broker>::BrokerID,
+    <T as Trader>::TraderID,
+    <A as Auxiliary>::AuxiliaryID,
+    <Kernel<T, B, E, R, RNG, A> as InnerMessage>::MessageContent
+>;
+
 trait InnerMessage {
     type MessageContent: Ord;
 }
 
-impl<T, B, E, R, RNG> InnerMessage for Kernel<T, B, E, R, RNG>
+impl<T, B, E, R, RNG, A> InnerMessage for Kernel<T, B, E, R, RNG, A>
     where
         T: Trader,
         B: Broker,
         E: Exchange,
         R: Replay,
-        RNG: SeedableRng + Rng
+        RNG: SeedableRng + Rng,
+        A: Auxiliary
 {
     type MessageContent = MessageContent<
-        E::ExchangeID, B::BrokerID, T::TraderID,
+        E::ExchangeID, B::BrokerID, T::TraderID, A::AuxiliaryID,
         R::R2R, R::R2E, R::R2B,
         B::B2R, B::B2E, B::B2T, B::B2B,
         T::T2B, T::T2T,
-        E::E2R, E::E2B, E::E2E
+        E::E2R, E::E2B, E::E2E,
+        A::A2A
     >;
 }
 
@@ -97,6 +215,7 @@ enum MessageContent<
     ExchangeID: Id,
     BrokerID: Id,
     TraderID: Id,
+    AuxiliaryID: Id,
     R2R: ReplayToItself,
     R2E: ReplayToExchange,
     R2B: ReplayToBroker,
@@ -108,7 +227,8 @@ enum MessageContent<
     T2T: TraderToItself,
     E2R: ExchangeToReplay,
     E2B: ExchangeToBroker,
-    E2E: ExchangeToItself
+    E2E: ExchangeToItself,
+    A2A: AuxiliaryToAuxiliary<AuxiliaryID=AuxiliaryID>
 > {
     ReplayWakeUp(R2R),
 
@@ -124,6 +244,8 @@ enum MessageContent<
 
     BrokerWakeUp { broker_id: BrokerID, b2b: B2B },
 
+    BrokerToBroker { broker_id: BrokerID, b2b: B2B },
+
     BrokerToReplay { broker_id: BrokerID, b2r: B2R },
 
     BrokerToExchange { broker_id: BrokerID, b2e: B2E },
@@ -133,26 +255,141 @@ enum MessageContent<
     TraderWakeUp { trader_id: TraderID, t2t: T2T },
 
     TraderToBroker { trader_id: TraderID, t2b: T2B },
+
+    AuxiliaryToAuxiliary { auxiliary_id: AuxiliaryID, a2a: A2A },
+
+    AuxiliaryToTrader { auxiliary_id: AuxiliaryID, trader_id: TraderID, t2t: T2T },
+
+    AuxiliaryToBroker { auxiliary_id: AuxiliaryID, broker_id: BrokerID, b2b: B2B },
+}
+
+impl<
+    ExchangeID: Id,
+    BrokerID: Id,
+    TraderID: Id,
+    AuxiliaryID: Id,
+    R2R: ReplayToItself,
+    R2E: ReplayToExchange,
+    R2B: ReplayToBroker,
+    B2R: BrokerToReplay,
+    B2E: BrokerToExchange,
+    B2T: BrokerToTrader,
+    B2B: BrokerToItself,
+    T2B: TraderToBroker,
+    T2T: TraderToItself,
+    E2R: ExchangeToReplay,
+    E2B: ExchangeToBroker,
+    E2E: ExchangeToItself,
+    A2A: AuxiliaryToAuxiliary<AuxiliaryID=AuxiliaryID>
+>
+MessageContent<
+    ExchangeID, BrokerID, TraderID, AuxiliaryID,
+    R2R, R2E, R2B,
+    B2R, B2E, B2T, B2B,
+    T2B, T2T,
+    E2R, E2B, E2E,
+    A2A
+>
+{
+    fn get_schedule_handle(&self) -> Option<ScheduleHandle> {
+        match self {
+            Self::ExchangeWakeUp { e2e, .. } => e2e.get_schedule_handle(),
+            Self::BrokerWakeUp { b2b, .. } => b2b.get_schedule_handle(),
+            Self::TraderWakeUp { t2t, .. } => t2t.get_schedule_handle(),
+            Self::AuxiliaryToAuxiliary { a2a, .. } => a2a.get_schedule_handle(),
+            _ => None
+        }
+    }
+}
+
+impl<
+    ExchangeID: Id,
+    BrokerID: Id,
+    TraderID: Id,
+    AuxiliaryID: Id,
+    R2R: ReplayToItself + Debug,
+    R2E: ReplayToExchange + Debug,
+    R2B: ReplayToBroker + Debug,
+    B2R: BrokerToReplay + Debug,
+    B2E: BrokerToExchange + Debug,
+    B2T: BrokerToTrader + Debug,
+    B2B: BrokerToItself + Debug,
+    T2B: TraderToBroker + Debug,
+    T2T: TraderToItself + Debug,
+    E2R: ExchangeToReplay + Debug,
+    E2B: ExchangeToBroker + Debug,
+    E2E: ExchangeToItself + Debug,
+    A2A: AuxiliaryToAuxiliary<AuxiliaryID=AuxiliaryID> + Debug
+>
+MessageContent<
+    ExchangeID, BrokerID, TraderID, AuxiliaryID,
+    R2R, R2E, R2B,
+    B2R, B2E, B2T, B2B,
+    T2B, T2T,
+    E2R, E2B, E2E,
+    A2A
+>
+{
+    fn get_payload(&self) -> &dyn Debug {
+        match self {
+            Self::ReplayWakeUp(r2r) => r2r,
+            Self::ReplayToExchange(r2e) => r2e,
+            Self::ReplayToBroker(r2b) => r2b,
+            Self::ExchangeWakeUp { e2e, .. } => e2e,
+            Self::ExchangeToReplay { e2r, .. } => e2r,
+            Self::ExchangeToBroker { e2b, .. } => e2b,
+            Self::BrokerWakeUp { b2b, .. } => b2b,
+            Self::BrokerToBroker { b2b, .. } => b2b,
+            Self::BrokerToReplay { b2r, .. } => b2r,
+            Self::BrokerToExchange { b2e, .. } => b2e,
+            Self::BrokerToTrader { b2t, .. } => b2t,
+            Self::TraderWakeUp { t2t, .. } => t2t,
+            Self::TraderToBroker { t2b, .. } => t2b,
+            Self::AuxiliaryToAuxiliary { a2a, .. } => a2a,
+            Self::AuxiliaryToTrader { t2t, .. } => t2t,
+            Self::AuxiliaryToBroker { b2b, .. } => b2b
+        }
+    }
+}
+
+/// Agents of the finished [`Kernel`] simulation in their final state.
+pub struct KernelAgents<T: Trader, B: Broker, E: Exchange, A: Auxiliary = NoAuxiliary<T, B>> {
+    /// Traders by their IDs.
+    pub traders: HashMap<T::TraderID, T>,
+    /// Brokers by their IDs.
+    pub brokers: HashMap<B::BrokerID, B>,
+    /// Exchanges by their IDs.
+    pub exchanges: HashMap<E::ExchangeID, E>,
+    /// Auxiliary agents by their IDs.
+    pub auxiliaries: HashMap<A::AuxiliaryID, A>,
 }
 
 /// Builder of the [`Kernel`].
-pub struct KernelBuilder<T, B, E, R, RNG>
+pub struct KernelBuilder<T, B, E, R, RNG, A = NoAuxiliary<T, B>, TR = NoTracer>
     where
         T: Trader,
         B: Broker,
         E: Exchange,
         R: Replay,
-        RNG: SeedableRng + Rng
+        RNG: SeedableRng + Rng,
+        A: Auxiliary
 {
     traders: HashMap<T::TraderID, T>,
     brokers: HashMap<B::BrokerID, B>,
     exchanges: HashMap<E::ExchangeID, E>,
     replay: R,
+    auxiliaries: HashMap<A::AuxiliaryID, A>,
 
     start_dt: DateTime,
     end_dt: DateTime,
 
     seed: Option<u64>,
+    idle_threshold: Option<Duration>,
+    latency_adjustment: LatencyAdjustment,
+    tracer: TR,
+    id_allocator: IdAllocator,
+    #[cfg(feature = "lookahead_guard")]
+    lookahead_policy: LookaheadPolicy,
 
     phantoms: PhantomData<RNG>,
 }
@@ -171,22 +408,22 @@ KernelBuilder<T, B, E, R, StdRng>
     /// # Arguments
     ///
     /// * `exchanges` — [`exchanges`](crate::interface::exchange::Exchange)
-    /// to initialize [`Kernel`].
+    ///   to initialize [`Kernel`].
     /// * `brokers` — Iterable of pairs consisting of the
-    /// [`broker`](crate::interface::broker::Broker)
-    /// and the names of the exchanges it will connect to.
+    ///   [`broker`](crate::interface::broker::Broker)
+    ///   and the names of the exchanges it will connect to.
     ///
     /// Informal syntax is like the following:
     ///
     /// `[(Broker1, [ExchangeID_1, ExchangeID_2]), (Broker2, [...]), ...]`.
     ///
     /// * `traders` — Iterable of pairs consisting of the
-    /// [`trader`](crate::interface::trader::Trader)
-    /// and the iterable of pairs of the broker names it will connect to
-    /// as well as the iterable
-    /// of subscription configs that could potentially define
-    /// [`trader`](crate::interface::trader::Trader)-to-[`broker`](crate::interface::broker::Broker)
-    /// interaction rules.
+    ///   [`trader`](crate::interface::trader::Trader)
+    ///   and the iterable of pairs of the broker names it will connect to
+    ///   as well as the iterable
+    ///   of subscription configs that could potentially define
+    ///   [`trader`](Trader)-to-[`broker`](Broker)
+    ///   interaction rules.
     ///
     /// Informal syntax is like the following:
     ///
@@ -212,9 +449,176 @@ KernelBuilder<T, B, E, R, StdRng>
         if end_dt < start_dt {
             panic!("start_dt ({start_dt}) is less than end_dt ({end_dt})")
         }
+        let mut exchanges = Self::collect_exchanges(exchanges, start_dt);
+        let mut brokers = Self::connect_brokers(brokers, &mut exchanges, start_dt);
+
+        let id_allocator = Self::get_replay_id_allocator(&replay);
+
+        let traders: Vec<_> = traders.into_iter().collect();
+        let n_traders = traders.len();
+        let traders: HashMap<T::TraderID, T> = traders.into_iter()
+            .map(
+                |(mut trader, brokers_to_register)| {
+                    *trader.current_datetime_mut() = start_dt;
+                    trader.set_id_allocator(id_allocator.clone());
+                    let trader_id = trader.get_name();
+                    for (broker_id, subscription_config) in brokers_to_register {
+                        if let Some(broker) = brokers.get_mut(&broker_id) {
+                            broker.register_trader(trader_id, subscription_config);
+                            trader.upon_register_at_broker(broker_id)
+                        } else {
+                            panic!("Cannot register Trader {trader_id} at the Broker: {broker_id}")
+                        }
+                    }
+                    (trader_id, trader)
+                }
+            )
+            .collect();
+        if traders.len() != n_traders {
+            panic!("traders contain entries with duplicate names")
+        }
+
+        KernelBuilder {
+            traders,
+            brokers,
+            exchanges,
+            replay,
+            auxiliaries: Default::default(),
+            end_dt,
+            start_dt,
+            seed: None,
+            idle_threshold: None,
+            latency_adjustment: Default::default(),
+            tracer: NoTracer,
+            id_allocator,
+            #[cfg(feature = "lookahead_guard")]
+            lookahead_policy: Default::default(),
+            phantoms: Default::default(),
+        }
+    }
+}
+
+impl<T, B, E, R, A>
+KernelBuilder<T, B, E, R, StdRng, A>
+    where
+        T: Trader<TraderID=B::TraderID, BrokerID=B::BrokerID, T2B=B::T2B, B2T=B::B2T>,
+        B: Broker<BrokerID=E::BrokerID, ExchangeID=E::ExchangeID, B2E=E::B2E, E2B=E::E2B>,
+        E: Exchange<BrokerID=R::BrokerID, ExchangeID=R::ExchangeID, E2R=R::E2R, R2E=R::R2E>,
+        R: Replay,
+        A: Auxiliary<TraderID=T::TraderID, BrokerID=B::BrokerID, T2T=T::T2T, B2B=B::B2B>,
+{
+    #[inline]
+    /// Creates a new instance of the [`KernelBuilder`] continuing the previous simulation,
+    /// e.g. to run the long-horizon simulation month by month.
+    ///
+    /// [Brokers](crate::interface::broker::Broker), [traders](crate::interface::trader::Trader)
+    /// and [auxiliary agents](crate::interface::auxiliary::Auxiliary)
+    /// keep their state, including the registrations of the traders at the brokers,
+    /// whereas the [exchanges](crate::interface::exchange::Exchange)
+    /// and the [replay](crate::interface::replay::Replay) are replaced,
+    /// so that the market data and the sessions may change.
+    /// Messages that have not been delivered by the end of the previous simulation are dropped,
+    /// so the simulations should be chained when the exchanges are closed.
+    ///
+    /// # Arguments
+    ///
+    /// * `agents` — Agents of the previous simulation. Its exchanges are dropped.
+    /// * `exchanges` — [`exchanges`](crate::interface::exchange::Exchange)
+    ///   to initialize [`Kernel`].
+    /// * `connections` — Iterable of pairs consisting of the broker name
+    ///   and the names of the exchanges it will connect to.
+    /// * `replay` — [`replay`](crate::interface::replay::Replay) to initialize [`Kernel`].
+    /// * `date_range` — Tuple of start and stop [`DateTimes`](crate::types::DateTime).
+    ///   Should not precede the current datetimes of the agents.
+    pub fn from_previous_run<CE>(agents: KernelAgents<T, B, E, A>,
+                                 exchanges: impl IntoIterator<Item=E>,
+                                 connections: impl IntoIterator<Item=(B::BrokerID, CE)>,
+                                 replay: R,
+                                 date_range: (DateTime, DateTime)) -> Self
+        where CE: IntoIterator<Item=E::ExchangeID>
+    {
+        let (start_dt, end_dt) = date_range;
+        if end_dt < start_dt {
+            panic!("start_dt ({start_dt}) is less than end_dt ({end_dt})")
+        }
+        let KernelAgents {
+            traders: mut prev_traders,
+            brokers: mut prev_brokers,
+            auxiliaries: mut prev_auxiliaries,
+            ..
+        } = agents;
+        let mut exchanges = Self::collect_exchanges(exchanges, start_dt);
+        let connections: Vec<_> = connections.into_iter().collect();
+        let n_connections = connections.len();
+        let brokers = connections.into_iter().map(
+            |(broker_id, exchanges_to_connect)| {
+                let mut broker = prev_brokers.remove(&broker_id).unwrap_or_else(
+                    || panic!("Broker {broker_id} did not take part in the previous simulation")
+                );
+                if *broker.current_datetime_mut() > start_dt {
+                    panic!(
+                        "start_dt ({start_dt}) precedes the current datetime of Broker {broker_id}"
+                    )
+                }
+                (broker, exchanges_to_connect)
+            }
+        ).collect::<Vec<_>>();
+        if brokers.len() != n_connections || !prev_brokers.is_empty() {
+            panic!("Every broker of the previous simulation should be connected exactly once")
+        }
+        let brokers = Self::connect_brokers(brokers, &mut exchanges, start_dt);
+        let id_allocator = Self::get_replay_id_allocator(&replay);
+        for (trader_id, trader) in &mut prev_traders {
+            let trader_dt = trader.current_datetime_mut();
+            if *trader_dt > start_dt {
+                panic!(
+                    "start_dt ({start_dt}) precedes the current datetime of Trader {trader_id}"
+                )
+            }
+            *trader_dt = start_dt;
+            trader.set_id_allocator(id_allocator.clone())
+        }
+        for (auxiliary_id, auxiliary) in &mut prev_auxiliaries {
+            let auxiliary_dt = auxiliary.current_datetime_mut();
+            if *auxiliary_dt > start_dt {
+                panic!(
+                    "start_dt ({start_dt}) precedes the current datetime \
+                    of Auxiliary {auxiliary_id}"
+                )
+            }
+            *auxiliary_dt = start_dt
+        }
+        KernelBuilder {
+            traders: prev_traders,
+            brokers,
+            exchanges,
+            replay,
+            auxiliaries: prev_auxiliaries,
+            end_dt,
+            start_dt,
+            seed: None,
+            idle_threshold: None,
+            latency_adjustment: Default::default(),
+            tracer: NoTracer,
+            id_allocator,
+            #[cfg(feature = "lookahead_guard")]
+            lookahead_policy: Default::default(),
+            phantoms: Default::default(),
+        }
+    }
+
+    /// Returns the allocator of the replay IDs or the new one if the replay has none.
+    fn get_replay_id_allocator(replay: &R) -> IdAllocator {
+        replay.get_id_allocator().cloned().unwrap_or_default()
+    }
+
+    fn collect_exchanges(
+        exchanges: impl IntoIterator<Item=E>,
+        start_dt: DateTime) -> HashMap<E::ExchangeID, E>
+    {
         let exchanges: Vec<_> = exchanges.into_iter().collect();
         let n_exchanges = exchanges.len();
-        let mut exchanges: HashMap<E::ExchangeID, E> = exchanges.into_iter()
+        let exchanges: HashMap<E::ExchangeID, E> = exchanges.into_iter()
             .map(
                 |mut exchange| {
                     *exchange.current_datetime_mut() = start_dt;
@@ -225,10 +629,18 @@ KernelBuilder<T, B, E, R, StdRng>
         if exchanges.len() != n_exchanges {
             panic!("exchanges contain entries with duplicate names")
         }
+        exchanges
+    }
 
+    fn connect_brokers<CE>(
+        brokers: impl IntoIterator<Item=(B, CE)>,
+        exchanges: &mut HashMap<E::ExchangeID, E>,
+        start_dt: DateTime) -> HashMap<B::BrokerID, B>
+        where CE: IntoIterator<Item=E::ExchangeID>
+    {
         let brokers: Vec<_> = brokers.into_iter().collect();
         let n_brokers = brokers.len();
-        let mut brokers: HashMap<B::BrokerID, B> = brokers.into_iter()
+        let brokers: HashMap<B::BrokerID, B> = brokers.into_iter()
             .map(
                 |(mut broker, exchanges_to_connect)| {
                     *broker.current_datetime_mut() = start_dt;
@@ -250,84 +662,245 @@ KernelBuilder<T, B, E, R, StdRng>
         if brokers.len() != n_brokers {
             panic!("brokers contain entries with duplicate names")
         }
+        brokers
+    }
+}
 
-        let traders: Vec<_> = traders.into_iter().collect();
-        let n_traders = traders.len();
-        let traders: HashMap<T::TraderID, T> = traders.into_iter()
-            .map(
-                |(mut trader, brokers_to_register)| {
-                    *trader.current_datetime_mut() = start_dt;
-                    let trader_id = trader.get_name();
-                    for (broker_id, subscription_config) in brokers_to_register {
-                        if let Some(broker) = brokers.get_mut(&broker_id) {
-                            broker.register_trader(trader_id, subscription_config);
-                            trader.upon_register_at_broker(broker_id)
-                        } else {
-                            panic!("Cannot register Trader {trader_id} at the Broker: {broker_id}")
-                        }
-                    }
-                    (trader_id, trader)
-                }
-            )
-            .collect();
-        if traders.len() != n_traders {
-            panic!("traders contain entries with duplicate names")
-        }
-
+impl<T, B, E, R, A, TR>
+KernelBuilder<T, B, E, R, StdRng, A, TR>
+    where
+        T: Trader<TraderID=B::TraderID, BrokerID=B::BrokerID, T2B=B::T2B, B2T=B::B2T>,
+        B: Broker<BrokerID=E::BrokerID, ExchangeID=E::ExchangeID, B2E=E::B2E, E2B=E::E2B>,
+        E: Exchange<BrokerID=R::BrokerID, ExchangeID=R::ExchangeID, E2R=R::E2R, R2E=R::R2E>,
+        R: Replay,
+        A: Auxiliary,
+{
+    #[inline]
+    /// Sets non-default ([`StdRng`]) random number generator.
+    pub fn with_rng<RNG>(self) -> KernelBuilder<T, B, E, R, RNG, A, TR>
+        where RNG: Rng + SeedableRng
+    {
+        let KernelBuilder {
+            traders, brokers, exchanges, replay, auxiliaries, end_dt, start_dt, seed,
+            idle_threshold, latency_adjustment, tracer, id_allocator,
+            #[cfg(feature = "lookahead_guard")]
+            lookahead_policy,
+            ..
+        } = self;
         KernelBuilder {
             traders,
             brokers,
             exchanges,
             replay,
+            auxiliaries,
             end_dt,
             start_dt,
-            seed: None,
+            seed,
+            idle_threshold,
+            latency_adjustment,
+            tracer,
+            id_allocator,
+            #[cfg(feature = "lookahead_guard")]
+            lookahead_policy,
             phantoms: Default::default(),
         }
     }
+}
 
+impl<T, B, E, R, RNG, PrevA, TR>
+KernelBuilder<T, B, E, R, RNG, PrevA, TR>
+    where
+        T: Trader<TraderID=B::TraderID, BrokerID=B::BrokerID, T2B=B::T2B, B2T=B::B2T>,
+        B: Broker<BrokerID=E::BrokerID, ExchangeID=E::ExchangeID, B2R=R::B2R, B2E=E::B2E, R2B=R::R2B, E2B=E::E2B>,
+        E: Exchange<BrokerID=R::BrokerID, ExchangeID=R::ExchangeID, E2R=R::E2R, R2E=R::R2E>,
+        R: Replay,
+        RNG: Rng + SeedableRng,
+        PrevA: Auxiliary,
+{
     #[inline]
-    /// Sets non-default ([`StdRng`]) random number generator.
-    pub fn with_rng<RNG: Rng + SeedableRng>(self) -> KernelBuilder<T, B, E, R, RNG>
+    /// Adds the [auxiliary agents](crate::interface::auxiliary::Auxiliary),
+    /// i.e. the agents of the custom roles, e.g. the news feed or the risk server.
+    /// Panics if the builder already has the auxiliary agents,
+    /// e.g. the ones carried over by the [`from_previous_run`](KernelBuilder::from_previous_run).
+    ///
+    /// # Arguments
+    ///
+    /// * `auxiliaries` — [Auxiliary agents](crate::interface::auxiliary::Auxiliary)
+    ///   to initialize [`Kernel`].
+    pub fn with_auxiliary_agents<A>(
+        self,
+        auxiliaries: impl IntoIterator<Item=A>) -> KernelBuilder<T, B, E, R, RNG, A, TR>
+        where A: Auxiliary<TraderID=T::TraderID, BrokerID=B::BrokerID, T2T=T::T2T, B2B=B::B2B>
     {
         let KernelBuilder {
-            traders, brokers, exchanges, replay, end_dt, start_dt, seed, ..
+            traders, brokers, exchanges, replay, auxiliaries: prev_auxiliaries, end_dt, start_dt,
+            seed, idle_threshold, latency_adjustment, tracer, id_allocator,
+            #[cfg(feature = "lookahead_guard")]
+            lookahead_policy,
+            ..
         } = self;
+        if !prev_auxiliaries.is_empty() {
+            panic!("KernelBuilder already has the auxiliary agents")
+        }
+        let auxiliaries: Vec<_> = auxiliaries.into_iter().collect();
+        let n_auxiliaries = auxiliaries.len();
+        let auxiliaries: HashMap<A::AuxiliaryID, A> = auxiliaries.into_iter()
+            .map(
+                |mut auxiliary| {
+                    *auxiliary.current_datetime_mut() = start_dt;
+                    (auxiliary.get_name(), auxiliary)
+                }
+            )
+            .collect();
+        if auxiliaries.len() != n_auxiliaries {
+            panic!("auxiliaries contain entries with duplicate names")
+        }
         KernelBuilder {
             traders,
             brokers,
             exchanges,
             replay,
+            auxiliaries,
             end_dt,
             start_dt,
             seed,
+            idle_threshold,
+            latency_adjustment,
+            tracer,
+            id_allocator,
+            #[cfg(feature = "lookahead_guard")]
+            lookahead_policy,
             phantoms: Default::default(),
         }
     }
 }
 
-impl<T, B, E, R, RNG>
-KernelBuilder<T, B, E, R, RNG>
+impl<T, B, E, R, RNG, A, TR>
+KernelBuilder<T, B, E, R, RNG, A, TR>
     where
         T: Trader<TraderID=B::TraderID, BrokerID=B::BrokerID, T2B=B::T2B, B2T=B::B2T>,
         B: Broker<BrokerID=E::BrokerID, ExchangeID=E::ExchangeID, B2R=R::B2R, B2E=E::B2E, R2B=R::R2B, E2B=E::E2B>,
         E: Exchange<BrokerID=R::BrokerID, ExchangeID=R::ExchangeID, E2R=R::E2R, R2E=R::R2E>,
         R: Replay,
         RNG: Rng + SeedableRng,
+        A: Auxiliary<TraderID=T::TraderID, BrokerID=B::BrokerID, T2T=T::T2T, B2B=B::B2B>,
 {
+    #[inline]
+    /// Returns the allocator of the simulation-wide unique IDs shared by the
+    /// [replay](crate::interface::replay::Replay) and the
+    /// [traders](crate::interface::trader::Trader),
+    /// e.g. to pass it to the [auxiliary agents](crate::interface::auxiliary::Auxiliary).
+    pub fn get_id_allocator(&self) -> &IdAllocator {
+        &self.id_allocator
+    }
+
     #[inline]
     /// Sets seed for the [`Kernel`] random number generator.
+    /// Mandatory without the `std` feature, since there is no source of entropy then,
+    /// as well as with the `deterministic` one, since the OS entropy is not used then.
     pub fn with_seed(mut self, seed: u64) -> Self {
         self.seed = Some(seed);
         self
     }
 
     #[inline]
-    /// Builds the [`Kernel`].
-    pub fn build(self) -> Kernel<T, B, E, R, RNG>
+    /// Sets the minimum duration of the period with no events
+    /// the [`Kernel`] reports to the [traders](crate::interface::trader::Trader)
+    /// via [`on_idle_period`](crate::interface::trader::Trader::on_idle_period)
+    /// when fast-forwarding over it.
+    /// By default, idle periods are not reported.
+    ///
+    /// # Arguments
+    ///
+    /// * `idle_threshold` — Minimum duration of the idle period.
+    pub fn with_idle_period_threshold(mut self, idle_threshold: Duration) -> Self {
+        if idle_threshold <= Duration::zero() {
+            panic!("Idle period threshold should be positive. Got: {idle_threshold}")
+        }
+        self.idle_threshold = Some(idle_threshold);
+        self
+    }
+
+    #[cfg(feature = "lookahead_guard")]
+    #[inline]
+    /// Sets the reaction to the lookahead violations detected in the broker replies
+    /// the [`Kernel`] delivers to the traders. Panics by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `lookahead_policy` — Reaction to the lookahead violations.
+    pub fn with_lookahead_policy(mut self, lookahead_policy: LookaheadPolicy) -> Self {
+        self.lookahead_policy = lookahead_policy;
+        self
+    }
+
+    #[inline]
+    /// Sets the adjustment applied to every latent delay sampled by the agents.
+    /// By default, delays are not adjusted.
+    ///
+    /// # Arguments
+    ///
+    /// * `latency_adjustment` — Scale and extra delay of the latent delays.
+    pub fn with_latency_adjustment(mut self, latency_adjustment: LatencyAdjustment) -> Self {
+        let scale = latency_adjustment.scale;
+        if !scale.is_finite() || scale < 0.0 {
+            panic!("Latency scale should be finite and non-negative. Got: {scale}")
+        }
+        self.latency_adjustment = latency_adjustment;
+        self
+    }
+
+    #[inline]
+    /// Sets the [`Tracer`] receiving every message the [`Kernel`] delivers,
+    /// with its datetime, sender and recipient, right before the delivery.
+    /// Requires all the messages to implement `Debug` by the time of the
+    /// [`build`](KernelBuilder::build), as well as the tracer to trace the messages
+    /// of the agents set by then, including the auxiliary ones.
+    /// By default, messages are not traced.
+    ///
+    /// # Arguments
+    ///
+    /// * `tracer` — Tracer of the messages, e.g. the [`WriterTracer`](tracer::WriterTracer)
+    ///   or the [`SharedTraceBuffer`](tracer::SharedTraceBuffer).
+    pub fn with_tracer<X>(self, tracer: X) -> KernelBuilder<T, B, E, R, RNG, A, WithTracer<X>>
+    {
+        let KernelBuilder {
+            traders, brokers, exchanges, replay, auxiliaries, end_dt, start_dt, seed,
+            idle_threshold, latency_adjustment, id_allocator,
+            #[cfg(feature = "lookahead_guard")]
+            lookahead_policy,
+            ..
+        } = self;
+        KernelBuilder {
+            traders,
+            brokers,
+            exchanges,
+            replay,
+            auxiliaries,
+            end_dt,
+            start_dt,
+            seed,
+            idle_threshold,
+            latency_adjustment,
+            tracer: WithTracer(tracer),
+            id_allocator,
+            #[cfg(feature = "lookahead_guard")]
+            lookahead_policy,
+            phantoms: Default::default(),
+        }
+    }
+
+    fn build_with_tracer(
+        self,
+        into_tracer: impl FnOnce(TR) -> Option<KernelTracerOf<T, B, E, R, RNG, A>>,
+    ) -> Kernel<T, B, E, R, RNG, A>
     {
         let KernelBuilder {
-            traders, brokers, exchanges, mut replay, end_dt, start_dt, seed, ..
+            traders, brokers, exchanges, mut replay, auxiliaries, end_dt, start_dt, seed,
+            idle_threshold, latency_adjustment, tracer,
+            #[cfg(feature = "lookahead_guard")]
+            lookahead_policy,
+            ..
         } = self;
 
         *replay.current_datetime_mut() = start_dt;
@@ -336,46 +909,365 @@ KernelBuilder<T, B, E, R, RNG>
             brokers,
             exchanges,
             replay,
+            auxiliaries,
             message_queue: LessElementBinaryHeap([].into()),
+            schedule_registry: Default::default(),
+            link_queues: LinkQueues::new(latency_adjustment),
             end_dt,
             current_dt: start_dt,
-            rng: if let Some(seed) = seed {
-                RNG::seed_from_u64(seed)
-            } else {
-                RNG::from_entropy()
+            rng: match seed {
+                Some(seed) => RNG::seed_from_u64(seed),
+                #[cfg(all(feature = "std", not(feature = "deterministic")))]
+                #[allow(clippy::disallowed_methods)]
+                None => RNG::from_entropy(),
+                #[cfg(not(feature = "std"))]
+                None => panic!("Kernel seed should be set when built without the standard library"),
+                #[cfg(feature = "deterministic")]
+                None => panic!("Kernel seed should be set in the deterministic mode")
             },
             num_replay_messages: 0,
+            idle_threshold,
+            tracer: into_tracer(tracer),
+            #[cfg(feature = "lookahead_guard")]
+            lookahead_guard: LookaheadGuard::new(start_dt, lookahead_policy),
         };
         kernel.pop_next_replay_message();
         if kernel.message_queue.len() == 0 {
             panic!("Replay does not contain any entries")
         };
+        kernel.initialize_auxiliaries();
         kernel
     }
 }
 
-impl<T, B, E, R, RNG> Kernel<T, B, E, R, RNG>
+impl<T, B, E, R, RNG, A>
+KernelBuilder<T, B, E, R, RNG, A, NoTracer>
+    where
+        T: Trader<TraderID=B::TraderID, BrokerID=B::BrokerID, T2B=B::T2B, B2T=B::B2T>,
+        B: Broker<BrokerID=E::BrokerID, ExchangeID=E::ExchangeID, B2R=R::B2R, B2E=E::B2E, R2B=R::R2B, E2B=E::E2B>,
+        E: Exchange<BrokerID=R::BrokerID, ExchangeID=R::ExchangeID, E2R=R::E2R, R2E=R::R2E>,
+        R: Replay,
+        RNG: Rng + SeedableRng,
+        A: Auxiliary<TraderID=T::TraderID, BrokerID=B::BrokerID, T2T=T::T2T, B2B=B::B2B>,
+{
+    #[inline]
+    /// Builds the [`Kernel`].
+    /// Panics if the seed has not been set while it is mandatory,
+    /// i.e. without the `std` feature or with the `deterministic` one,
+    /// as well as if the replay does not contain any entries.
+    pub fn build(self) -> Kernel<T, B, E, R, RNG, A>
+    {
+        self.build_with_tracer(|NoTracer| None)
+    }
+}
+
+impl<T, B, E, R, RNG, A, X>
+KernelBuilder<T, B, E, R, RNG, A, WithTracer<X>>
+    where
+        T: Trader<TraderID=B::TraderID, BrokerID=B::BrokerID, T2B=B::T2B, B2T=B::B2T>,
+        B: Broker<BrokerID=E::BrokerID, ExchangeID=E::ExchangeID, B2R=R::B2R, B2E=E::B2E, R2B=R::R2B, E2B=E::E2B>,
+        E: Exchange<BrokerID=R::BrokerID, ExchangeID=R::ExchangeID, E2R=R::E2R, R2E=R::R2E>,
+        R: Replay,
+        RNG: Rng + SeedableRng,
+        A: Auxiliary<TraderID=T::TraderID, BrokerID=B::BrokerID, T2T=T::T2T, B2B=B::B2B>,
+        X: Tracer<E::ExchangeID, B::BrokerID, T::TraderID, A::AuxiliaryID> + 'static,
+        R::R2R: Debug,
+        R::R2E: Debug,
+        R::R2B: Debug,
+        B::B2R: Debug,
+        B::B2E: Debug,
+        B::B2T: Debug,
+        B::B2B: Debug,
+        T::T2B: Debug,
+        T::T2T: Debug,
+        E::E2R: Debug,
+        E::E2B: Debug,
+        E::E2E: Debug,
+        A::A2A: Debug,
+{
+    #[inline]
+    /// Builds the [`Kernel`] tracing the messages by the [`Tracer`] set.
+    /// Panics if the seed has not been set while it is mandatory,
+    /// i.e. without the `std` feature or with the `deterministic` one,
+    /// as well as if the replay does not contain any entries.
+    pub fn build(self) -> Kernel<T, B, E, R, RNG, A>
+    {
+        self.build_with_tracer(
+            |WithTracer(tracer)| Some(
+                KernelTracer { tracer: Box::new(tracer), get_payload: MessageContent::get_payload }
+            )
+        )
+    }
+}
+
+impl<T, B, E, R, RNG, A> Kernel<T, B, E, R, RNG, A>
     where
         T: Trader<TraderID=B::TraderID, BrokerID=B::BrokerID, T2B=B::T2B, B2T=B::B2T>,
         B: Broker<BrokerID=E::BrokerID, ExchangeID=E::ExchangeID, B2R=R::B2R, B2E=E::B2E, R2B=R::R2B, E2B=E::E2B>,
         E: Exchange<BrokerID=R::BrokerID, ExchangeID=R::ExchangeID, E2R=R::E2R, R2E=R::R2E>,
         R: Replay,
-        RNG: SeedableRng + Rng
+        RNG: SeedableRng + Rng,
+        A: Auxiliary<TraderID=T::TraderID, BrokerID=B::BrokerID, T2T=T::T2T, B2B=B::B2B>,
 {
     #[inline]
     /// Runs final simulation.
     pub fn run_simulation(mut self)
+    {
+        self.process_messages()
+    }
+
+    #[inline]
+    /// Runs final simulation and returns the agents in their final state,
+    /// so that the analytics they have recorded during the simulation can be inspected.
+    pub fn run_simulation_into_agents(mut self) -> KernelAgents<T, B, E, A>
+    {
+        self.process_messages();
+        let Self { traders, brokers, exchanges, auxiliaries, .. } = self;
+        KernelAgents { traders, brokers, exchanges, auxiliaries }
+    }
+
+    /// Runs final simulation, stopping it once any of the resource limits is exceeded.
+    /// The wall time is checked every 1024 messages
+    /// and the memory of the event queue is checked after every message.
+    /// Panics if the `max_wall_time` is set without the `std` feature
+    /// or with the `deterministic` one, since the wall clock is not available then.
+    ///
+    /// # Arguments
+    ///
+    /// * `limits` — Resource limits of the run.
+    pub fn run_simulation_with_limits(mut self, limits: ResourceLimits) -> Result<(), LimitViolation>
+    {
+        #[cfg(all(feature = "std", not(feature = "deterministic")))]
+        #[allow(clippy::disallowed_methods)]
+        let started = std::time::Instant::now();
+        #[cfg(not(all(feature = "std", not(feature = "deterministic"))))]
+        if limits.max_wall_time.is_some() {
+            panic!(
+                "Wall-time limit requires the std feature \
+                and is not available in the deterministic mode"
+            )
+        }
+        let message_size = core::mem::size_of::<Message<<Self as InnerMessage>::MessageContent>>();
+        let mut num_processed = 0;
+        self.process_messages_until(
+            |kernel| {
+                if let Some(max_queue_memory) = limits.max_queue_memory {
+                    let bytes = kernel.message_queue.len() * message_size;
+                    if bytes > max_queue_memory {
+                        return Some(
+                            LimitViolation::QueueMemory { bytes, simulated_dt: kernel.current_dt }
+                        );
+                    }
+                }
+                num_processed += 1;
+                #[cfg(all(feature = "std", not(feature = "deterministic")))]
+                if let Some(max_wall_time) = limits.max_wall_time {
+                    if num_processed % WALL_TIME_CHECK_PERIOD == 0 {
+                        let elapsed = started.elapsed();
+                        if elapsed > max_wall_time {
+                            return Some(
+                                LimitViolation::WallTime {
+                                    elapsed,
+                                    simulated_dt: kernel.current_dt,
+                                }
+                            );
+                        }
+                    }
+                }
+                None
+            }
+        )
+    }
+
+    #[inline]
+    fn process_messages(&mut self)
+    {
+        let Ok(()) = self.process_messages_until(|_| None::<core::convert::Infallible>);
+    }
+
+    /// Processes the messages until the queue is exhausted, the end datetime is reached
+    /// or the `check` called after every message returns the reason to stop.
+    #[inline]
+    fn process_messages_until<Stop>(
+        &mut self,
+        mut check: impl FnMut(&Self) -> Option<Stop>) -> Result<(), Stop>
     {
         while let Some(message) = self.message_queue.pop()
         {
-            self.current_dt = message.datetime;
-            if self.current_dt > self.end_dt {
+            if let Some(handle) = message.body.get_schedule_handle() {
+                if self.schedule_registry.take_revoked(handle) {
+                    continue;
+                }
+            }
+            if message.datetime > self.end_dt {
+                self.current_dt = message.datetime;
                 break;
             }
-            self.handle_message(message.body)
+            match self.idle_threshold {
+                Some(threshold) if message.datetime - self.current_dt >= threshold => {
+                    self.fast_forward(message.datetime)
+                }
+                _ => self.current_dt = message.datetime
+            }
+            #[cfg(feature = "lookahead_guard")]
+            self.lookahead_guard.sync(self.current_dt);
+            self.trace_message(&message.body);
+            self.handle_message(message.body);
+            self.purge_revoked_messages();
+            if let Some(stop) = check(self) {
+                return Err(stop);
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn fast_forward(&mut self, idle_end: DateTime)
+    {
+        let idle_start = self.current_dt;
+        self.current_dt = idle_end;
+        #[cfg(feature = "lookahead_guard")]
+        self.lookahead_guard.sync(idle_end);
+        // Sorted, so that the random number generator is used in the same order every run
+        let mut trader_ids: Vec<_> = self.traders.keys().copied().collect();
+        trader_ids.sort_unstable();
+        for trader_id in trader_ids {
+            let trader = self.traders.get_mut(&trader_id).unwrap_or_else(
+                || unreachable!("Kernel does not know such a Trader: {trader_id}")
+            );
+            *trader.current_datetime_mut() = idle_end;
+            let decision_latency = trader.sample_decision_latency(&mut self.rng);
+            let trader_action_processor = TraderActionProcessor::<
+                T::TraderID, T::Action, B, E, R, A
+            >::new(
+                idle_end,
+                decision_latency,
+                trader_id,
+                &mut self.link_queues,
+            );
+            trader.on_idle_period(
+                MessageReceiver::with_schedule_registry(
+                    &mut self.message_queue,
+                    &mut self.schedule_registry,
+                ),
+                trader_action_processor,
+                idle_start,
+                idle_end,
+                &mut self.rng,
+            )
         }
     }
 
+    #[inline]
+    fn purge_revoked_messages(&mut self)
+    {
+        self.schedule_registry.purge_revoked(
+            &mut self.message_queue,
+            |message| message.body.get_schedule_handle(),
+        );
+    }
+
+    #[inline]
+    fn trace_message(&mut self, message: &<Self as InnerMessage>::MessageContent)
+    {
+        let tracer = if let Some(tracer) = &mut self.tracer {
+            tracer
+        } else {
+            return;
+        };
+        let (kind, source, destination) = match message
+        {
+            MessageContent::ReplayWakeUp(_) => {
+                ("ReplayWakeUp", TraceEndpoint::Replay, TraceEndpoint::Replay)
+            }
+            MessageContent::ReplayToExchange(r2e) => (
+                "ReplayToExchange",
+                TraceEndpoint::Replay,
+                TraceEndpoint::Exchange(r2e.get_exchange_id()),
+            ),
+            MessageContent::ReplayToBroker(r2b) => (
+                "ReplayToBroker",
+                TraceEndpoint::Replay,
+                TraceEndpoint::Broker(r2b.get_broker_id()),
+            ),
+            MessageContent::ExchangeWakeUp { exchange_id, .. } => (
+                "ExchangeWakeUp",
+                TraceEndpoint::Exchange(*exchange_id),
+                TraceEndpoint::Exchange(*exchange_id),
+            ),
+            MessageContent::ExchangeToReplay { exchange_id, .. } => {
+                ("ExchangeToReplay", TraceEndpoint::Exchange(*exchange_id), TraceEndpoint::Replay)
+            }
+            MessageContent::ExchangeToBroker { exchange_id, e2b } => (
+                "ExchangeToBroker",
+                TraceEndpoint::Exchange(*exchange_id),
+                TraceEndpoint::Broker(e2b.get_broker_id()),
+            ),
+            MessageContent::BrokerWakeUp { broker_id, .. } => (
+                "BrokerWakeUp",
+                TraceEndpoint::Broker(*broker_id),
+                TraceEndpoint::Broker(*broker_id),
+            ),
+            MessageContent::BrokerToBroker { broker_id, b2b } => {
+                let recipient_id = self.brokers.get(broker_id)
+                    .unwrap_or_else(|| panic!("Kernel does not know such a Broker: {broker_id}"))
+                    .get_b2b_recipient(b2b);
+                (
+                    "BrokerToBroker",
+                    TraceEndpoint::Broker(*broker_id),
+                    TraceEndpoint::Broker(recipient_id),
+                )
+            }
+            MessageContent::BrokerToReplay { broker_id, .. } => {
+                ("BrokerToReplay", TraceEndpoint::Broker(*broker_id), TraceEndpoint::Replay)
+            }
+            MessageContent::BrokerToExchange { broker_id, b2e } => (
+                "BrokerToExchange",
+                TraceEndpoint::Broker(*broker_id),
+                TraceEndpoint::Exchange(b2e.get_exchange_id()),
+            ),
+            MessageContent::BrokerToTrader { broker_id, b2t } => (
+                "BrokerToTrader",
+                TraceEndpoint::Broker(*broker_id),
+                TraceEndpoint::Trader(b2t.get_trader_id()),
+            ),
+            MessageContent::TraderWakeUp { trader_id, .. } => (
+                "TraderWakeUp",
+                TraceEndpoint::Trader(*trader_id),
+                TraceEndpoint::Trader(*trader_id),
+            ),
+            MessageContent::TraderToBroker { trader_id, t2b } => (
+                "TraderToBroker",
+                TraceEndpoint::Trader(*trader_id),
+                TraceEndpoint::Broker(t2b.get_broker_id()),
+            ),
+            MessageContent::AuxiliaryToAuxiliary { auxiliary_id, a2a } => (
+                "AuxiliaryToAuxiliary",
+                TraceEndpoint::Auxiliary(*auxiliary_id),
+                TraceEndpoint::Auxiliary(a2a.get_auxiliary_id()),
+            ),
+            MessageContent::AuxiliaryToTrader { auxiliary_id, trader_id, .. } => (
+                "AuxiliaryToTrader",
+                TraceEndpoint::Auxiliary(*auxiliary_id),
+                TraceEndpoint::Trader(*trader_id),
+            ),
+            MessageContent::AuxiliaryToBroker { auxiliary_id, broker_id, .. } => (
+                "AuxiliaryToBroker",
+                TraceEndpoint::Auxiliary(*auxiliary_id),
+                TraceEndpoint::Broker(*broker_id),
+            ),
+        };
+        tracer.tracer.trace(
+            TraceRecord {
+                datetime: self.current_dt,
+                kind,
+                source,
+                destination,
+                message: (tracer.get_payload)(message),
+            }
+        )
+    }
+
     #[inline]
     fn handle_message(&mut self, message: <Self as InnerMessage>::MessageContent)
     {
@@ -415,6 +1307,9 @@ impl<T, B, E, R, RNG> Kernel<T, B, E, R, RNG>
             MessageContent::BrokerWakeUp { broker_id, b2b } => {
                 self.handle_broker_wakeup(broker_id, b2b)
             }
+            MessageContent::BrokerToBroker { broker_id, b2b } => {
+                self.handle_broker_to_broker(broker_id, b2b)
+            }
             MessageContent::BrokerToReplay { broker_id, b2r } => {
                 self.handle_broker_to_replay(broker_id, b2r);
                 self.pop_next_replay_message()
@@ -431,6 +1326,40 @@ impl<T, B, E, R, RNG> Kernel<T, B, E, R, RNG>
             MessageContent::TraderToBroker { trader_id, t2b } => {
                 self.handle_trader_to_broker(trader_id, t2b)
             }
+            MessageContent::AuxiliaryToAuxiliary { auxiliary_id, a2a } => {
+                self.handle_auxiliary_to_auxiliary(auxiliary_id, a2a)
+            }
+            // Traders and brokers need not know about the auxiliary agents,
+            // so the messages are delivered as their wakeups
+            MessageContent::AuxiliaryToTrader { trader_id, t2t, .. } => {
+                self.handle_trader_wakeup(trader_id, t2t)
+            }
+            MessageContent::AuxiliaryToBroker { broker_id, b2b, .. } => {
+                self.handle_broker_wakeup(broker_id, b2b)
+            }
+        }
+    }
+
+    #[inline]
+    fn initialize_auxiliaries(&mut self) {
+        // Sorted, so that the random number generator is used in the same order every run
+        let mut auxiliary_ids: Vec<_> = self.auxiliaries.keys().copied().collect();
+        auxiliary_ids.sort_unstable();
+        for auxiliary_id in auxiliary_ids {
+            let auxiliary = self.auxiliaries.get_mut(&auxiliary_id).unwrap_or_else(
+                || unreachable!("Kernel does not know such an Auxiliary: {auxiliary_id}")
+            );
+            let current_dt = self.current_dt;
+            auxiliary.initialize(
+                MessageReceiver::with_schedule_registry(
+                    &mut self.message_queue,
+                    &mut self.schedule_registry,
+                ),
+                |action, _: &mut RNG| Self::process_auxiliary_action(
+                    current_dt, action, auxiliary_id,
+                ),
+                &mut self.rng,
+            )
         }
     }
 
@@ -461,12 +1390,16 @@ impl<T, B, E, R, RNG> Kernel<T, B, E, R, RNG>
             Self::process_exchange_action(
                 self.current_dt,
                 &mut self.brokers,
+                &mut self.link_queues,
                 rng,
                 action,
                 exchange_id,
             );
         exchange.process_replay_request(
-            MessageReceiver::new(&mut self.message_queue),
+            MessageReceiver::with_schedule_registry(
+                &mut self.message_queue,
+                &mut self.schedule_registry,
+            ),
             process_exchange_action,
             request,
             &mut self.rng,
@@ -481,13 +1414,17 @@ impl<T, B, E, R, RNG> Kernel<T, B, E, R, RNG>
             || panic!("Kernel does not know such a Broker: {broker_id}")
         );
         *broker.current_datetime_mut() = self.current_dt;
-        let broker_action_processor = BrokerActionProcessor::<B::BrokerID, B::Action, T, E, R>::new(
+        let broker_action_processor = BrokerActionProcessor::<B::BrokerID, B::Action, T, E, R, A>::new(
             self.current_dt,
             broker_id,
             &mut self.traders,
+            &mut self.link_queues,
         );
         broker.process_replay_request(
-            MessageReceiver::new(&mut self.message_queue),
+            MessageReceiver::with_schedule_registry(
+                &mut self.message_queue,
+                &mut self.schedule_registry,
+            ),
             broker_action_processor,
             request,
             &mut self.rng,
@@ -505,12 +1442,16 @@ impl<T, B, E, R, RNG> Kernel<T, B, E, R, RNG>
             Self::process_exchange_action(
                 self.current_dt,
                 &mut self.brokers,
+                &mut self.link_queues,
                 rng,
                 action,
                 exchange_id,
             );
         exchange.wakeup(
-            MessageReceiver::new(&mut self.message_queue),
+            MessageReceiver::with_schedule_registry(
+                &mut self.message_queue,
+                &mut self.schedule_registry,
+            ),
             process_exchange_action,
             scheduled_action,
             &mut self.rng,
@@ -536,13 +1477,17 @@ impl<T, B, E, R, RNG> Kernel<T, B, E, R, RNG>
             || panic!("Kernel does not know such a Broker: {broker_id}")
         );
         *broker.current_datetime_mut() = self.current_dt;
-        let broker_action_processor = BrokerActionProcessor::<B::BrokerID, B::Action, T, E, R>::new(
+        let broker_action_processor = BrokerActionProcessor::<B::BrokerID, B::Action, T, E, R, A>::new(
             self.current_dt,
             broker_id,
             &mut self.traders,
+            &mut self.link_queues,
         );
         broker.process_exchange_reply(
-            MessageReceiver::new(&mut self.message_queue),
+            MessageReceiver::with_schedule_registry(
+                &mut self.message_queue,
+                &mut self.schedule_registry,
+            ),
             broker_action_processor,
             reply,
             exchange_id,
@@ -557,19 +1502,51 @@ impl<T, B, E, R, RNG> Kernel<T, B, E, R, RNG>
             || panic!("Kernel does not know such a Broker: {broker_id}")
         );
         *broker.current_datetime_mut() = self.current_dt;
-        let broker_action_processor = BrokerActionProcessor::<B::BrokerID, B::Action, T, E, R>::new(
+        let broker_action_processor = BrokerActionProcessor::<B::BrokerID, B::Action, T, E, R, A>::new(
             self.current_dt,
             broker_id,
             &mut self.traders,
+            &mut self.link_queues,
         );
         broker.wakeup(
-            MessageReceiver::new(&mut self.message_queue),
+            MessageReceiver::with_schedule_registry(
+                &mut self.message_queue,
+                &mut self.schedule_registry,
+            ),
             broker_action_processor,
             scheduled_action,
             &mut self.rng,
         )
     }
 
+    #[inline]
+    fn handle_broker_to_broker(&mut self, sender_id: B::BrokerID, message: B::B2B)
+    {
+        let broker_id = self.brokers.get(&sender_id)
+            .unwrap_or_else(|| panic!("Kernel does not know such a Broker: {sender_id}"))
+            .get_b2b_recipient(&message);
+        let broker = self.brokers.get_mut(&broker_id).unwrap_or_else(
+            || panic!("Kernel does not know such a Broker: {broker_id}")
+        );
+        *broker.current_datetime_mut() = self.current_dt;
+        let broker_action_processor = BrokerActionProcessor::<B::BrokerID, B::Action, T, E, R, A>::new(
+            self.current_dt,
+            broker_id,
+            &mut self.traders,
+            &mut self.link_queues,
+        );
+        broker.process_broker_message(
+            MessageReceiver::with_schedule_registry(
+                &mut self.message_queue,
+                &mut self.schedule_registry,
+            ),
+            broker_action_processor,
+            message,
+            sender_id,
+            &mut self.rng,
+        )
+    }
+
     #[inline]
     fn handle_broker_to_replay(&mut self, broker_id: B::BrokerID, reply: B::B2R)
     {
@@ -593,12 +1570,16 @@ impl<T, B, E, R, RNG> Kernel<T, B, E, R, RNG>
             Self::process_exchange_action(
                 self.current_dt,
                 &mut self.brokers,
+                &mut self.link_queues,
                 rng,
                 action,
                 exchange_id,
             );
         exchange.process_broker_request(
-            MessageReceiver::new(&mut self.message_queue),
+            MessageReceiver::with_schedule_registry(
+                &mut self.message_queue,
+                &mut self.schedule_registry,
+            ),
             process_exchange_action,
             request,
             broker_id,
@@ -614,12 +1595,28 @@ impl<T, B, E, R, RNG> Kernel<T, B, E, R, RNG>
             || panic!("Kernel does not know such a Trader: {trader_id}")
         );
         *trader.current_datetime_mut() = self.current_dt;
-        let trader_action_processor = TraderActionProcessor::<T::TraderID, T::Action, B, E, R>::new(
+        let decision_latency = trader.sample_decision_latency(&mut self.rng);
+        let trader_action_processor = TraderActionProcessor::<T::TraderID, T::Action, B, E, R, A>::new(
             self.current_dt,
+            decision_latency,
             trader_id,
+            &mut self.link_queues,
+        );
+        let message_receiver = MessageReceiver::with_schedule_registry(
+            &mut self.message_queue,
+            &mut self.schedule_registry,
+        );
+        #[cfg(feature = "lookahead_guard")]
+        trader.process_delivered_broker_reply(
+            message_receiver,
+            trader_action_processor,
+            self.lookahead_guard.wrap_reply(reply),
+            broker_id,
+            &mut self.rng,
         );
+        #[cfg(not(feature = "lookahead_guard"))]
         trader.process_broker_reply(
-            MessageReceiver::new(&mut self.message_queue),
+            message_receiver,
             trader_action_processor,
             reply,
             broker_id,
@@ -634,12 +1631,18 @@ impl<T, B, E, R, RNG> Kernel<T, B, E, R, RNG>
             || panic!("Kernel does not know such a Trader: {trader_id}")
         );
         *trader.current_datetime_mut() = self.current_dt;
-        let trader_action_processor = TraderActionProcessor::<T::TraderID, T::Action, B, E, R>::new(
+        let decision_latency = trader.sample_decision_latency(&mut self.rng);
+        let trader_action_processor = TraderActionProcessor::<T::TraderID, T::Action, B, E, R, A>::new(
             self.current_dt,
+            decision_latency,
             trader_id,
+            &mut self.link_queues,
         );
         trader.wakeup(
-            MessageReceiver::new(&mut self.message_queue),
+            MessageReceiver::with_schedule_registry(
+                &mut self.message_queue,
+                &mut self.schedule_registry,
+            ),
             trader_action_processor,
             scheduled_action,
             &mut self.rng,
@@ -654,13 +1657,17 @@ impl<T, B, E, R, RNG> Kernel<T, B, E, R, RNG>
             || panic!("Kernel does not know such an Broker: {broker_id}")
         );
         *broker.current_datetime_mut() = self.current_dt;
-        let broker_action_processor = BrokerActionProcessor::<B::BrokerID, B::Action, T, E, R>::new(
+        let broker_action_processor = BrokerActionProcessor::<B::BrokerID, B::Action, T, E, R, A>::new(
             self.current_dt,
             broker_id,
             &mut self.traders,
+            &mut self.link_queues,
         );
         broker.process_trader_request(
-            MessageReceiver::new(&mut self.message_queue),
+            MessageReceiver::with_schedule_registry(
+                &mut self.message_queue,
+                &mut self.schedule_registry,
+            ),
             broker_action_processor,
             request,
             trader_id,
@@ -668,6 +1675,29 @@ impl<T, B, E, R, RNG> Kernel<T, B, E, R, RNG>
         )
     }
 
+    #[inline]
+    fn handle_auxiliary_to_auxiliary(&mut self, sender_id: A::AuxiliaryID, message: A::A2A)
+    {
+        let auxiliary_id = message.get_auxiliary_id();
+        let auxiliary = self.auxiliaries.get_mut(&auxiliary_id).unwrap_or_else(
+            || panic!("Kernel does not know such an Auxiliary: {auxiliary_id}")
+        );
+        *auxiliary.current_datetime_mut() = self.current_dt;
+        let current_dt = self.current_dt;
+        auxiliary.process_auxiliary_message(
+            MessageReceiver::with_schedule_registry(
+                &mut self.message_queue,
+                &mut self.schedule_registry,
+            ),
+            |action, _: &mut RNG| Self::process_auxiliary_action(
+                current_dt, action, auxiliary_id,
+            ),
+            message,
+            sender_id,
+            &mut self.rng,
+        )
+    }
+
     #[inline]
     fn process_replay_action(
         &mut self,
@@ -701,11 +1731,12 @@ impl<T, B, E, R, RNG> Kernel<T, B, E, R, RNG>
     fn process_exchange_action(
         current_dt: DateTime,
         brokers: &mut HashMap<B::BrokerID, B>,
+        link_queues: &mut LinkQueues<T::TraderID, B::BrokerID, E::ExchangeID>,
         rng: &mut RNG,
         action: E::Action,
         exchange_id: E::ExchangeID) -> Message<<Self as InnerMessage>::MessageContent>
     {
-        let delayed_dt = current_dt + Duration::nanoseconds(action.delay as i64);
+        let delayed_dt = current_dt + action.delay;
         let (datetime, body) = match action.content
         {
             ExchangeActionKind::ExchangeToBroker(reply) => {
@@ -714,11 +1745,21 @@ impl<T, B, E, R, RNG> Kernel<T, B, E, R, RNG>
                     || panic!("Kernel does not know such a Broker: {broker_id}")
                 );
                 *broker.current_datetime_mut() = current_dt;
-                let latency = broker
-                    .get_latency_generator()
-                    .incoming_latency(exchange_id, delayed_dt, rng);
+                let mut latency_generator = broker.get_latency_generator();
+                let sent_dt = link_queues.transmit_broker_message(
+                    broker_id,
+                    exchange_id,
+                    false,
+                    delayed_dt,
+                    reply.get_size_in_bytes(),
+                    latency_generator.incoming_bandwidth(exchange_id),
+                );
+                let latency = link_queues.adjust_latency(
+                    latency_generator.incoming_latency(exchange_id, sent_dt, rng)
+                );
+                broker.update_latency_generator(latency_generator);
                 (
-                    delayed_dt + Duration::nanoseconds(latency as i64),
+                    sent_dt + latency,
                     MessageContent::ExchangeToBroker { exchange_id, e2b: reply }
                 )
             }
@@ -737,4 +1778,25 @@ impl<T, B, E, R, RNG> Kernel<T, B, E, R, RNG>
         };
         Message { datetime, body }
     }
-}
\ No newline at end of file
+
+    #[inline]
+    fn process_auxiliary_action(
+        current_dt: DateTime,
+        action: A::Action,
+        auxiliary_id: A::AuxiliaryID) -> Message<<Self as InnerMessage>::MessageContent>
+    {
+        let body = match action.content
+        {
+            AuxiliaryActionKind::AuxiliaryToAuxiliary(a2a) => {
+                MessageContent::AuxiliaryToAuxiliary { auxiliary_id, a2a }
+            }
+            AuxiliaryActionKind::AuxiliaryToTrader { trader_id, t2t } => {
+                MessageContent::AuxiliaryToTrader { auxiliary_id, trader_id, t2t }
+            }
+            AuxiliaryActionKind::AuxiliaryToBroker { broker_id, b2b } => {
+                MessageContent::AuxiliaryToBroker { auxiliary_id, broker_id, b2b }
+            }
+        };
+        Message { datetime: current_dt + action.delay, body }
+    }
+}
@@ -1,6 +1,7 @@
 use {
     crate::{
         interface::{
+            auxiliary::Auxiliary,
             broker::{Broker, BrokerAction, BrokerActionKind},
             exchange::Exchange,
             latency::LatencyGenerator,
@@ -15,49 +16,57 @@ use {
             replay::Replay,
             trader::{Trader, TraderAction, TraderActionKind},
         },
-        kernel::{LatentActionProcessor, Message, MessageContent},
-        types::{DateTime, Duration, Id},
+        kernel::{bandwidth::LinkQueues, LatentActionProcessor, Message, MessageContent},
+        types::{DateTime, Id, NanoDuration},
+        utils::collections::HashMap,
     },
+    core::marker::PhantomData,
     rand::Rng,
-    std::{collections::HashMap, marker::PhantomData},
 };
 
 pub(in crate::kernel) struct BrokerActionProcessor<
     'a,
     BrokerID: Id, BrokerAction,
-    T: Trader, E: Exchange, R: Replay
+    T: Trader, E: Exchange, R: Replay, A: Auxiliary
 > {
     current_dt: DateTime,
     traders: &'a mut HashMap<T::TraderID, T>,
+    link_queues: &'a mut LinkQueues<T::TraderID, BrokerID, E::ExchangeID>,
     broker_id: BrokerID,
-    phantom: PhantomData<(BrokerAction, E, R)>,
+    phantom: PhantomData<(BrokerAction, E, R, A)>,
 }
 
 pub(in crate::kernel) struct TraderActionProcessor<
+    'a,
     TraderID: Id, TraderAction,
-    B: Broker, E: Exchange, R: Replay
+    B: Broker, E: Exchange, R: Replay, A: Auxiliary
 > {
     current_dt: DateTime,
+    /// Time the trader spends computing the decision the processed actions result from.
+    decision_latency: NanoDuration,
     trader_id: TraderID,
-    phantom: PhantomData<(TraderAction, B, E, R)>,
+    link_queues: &'a mut LinkQueues<TraderID, B::BrokerID, B::ExchangeID>,
+    phantom: PhantomData<(TraderAction, B, E, R, A)>,
 }
 
 impl<
     'a,
     BrokerID: Id, BrokerAction,
-    T: Trader, E: Exchange, R: Replay
+    T: Trader, E: Exchange, R: Replay, A: Auxiliary
 >
-BrokerActionProcessor<'a, BrokerID, BrokerAction, T, E, R>
+BrokerActionProcessor<'a, BrokerID, BrokerAction, T, E, R, A>
 {
     #[inline]
     pub fn new(
         current_dt: DateTime,
         broker_id: BrokerID,
-        traders: &'a mut HashMap<T::TraderID, T>) -> Self
+        traders: &'a mut HashMap<T::TraderID, T>,
+        link_queues: &'a mut LinkQueues<T::TraderID, BrokerID, E::ExchangeID>) -> Self
     {
         Self {
             current_dt,
             traders,
+            link_queues,
             broker_id,
             phantom: Default::default(),
         }
@@ -65,16 +74,24 @@ BrokerActionProcessor<'a, BrokerID, BrokerAction, T, E, R>
 }
 
 impl<
+    'a,
     TraderID: Id, TraderAction,
-    B: Broker, E: Exchange, R: Replay
+    B: Broker, E: Exchange, R: Replay, A: Auxiliary
 >
-TraderActionProcessor<TraderID, TraderAction, B, E, R>
+TraderActionProcessor<'a, TraderID, TraderAction, B, E, R, A>
 {
     #[inline]
-    pub fn new(current_dt: DateTime, trader_id: TraderID) -> Self {
+    pub fn new(
+        current_dt: DateTime,
+        decision_latency: NanoDuration,
+        trader_id: TraderID,
+        link_queues: &'a mut LinkQueues<TraderID, B::BrokerID, B::ExchangeID>) -> Self
+    {
         Self {
             current_dt,
+            decision_latency,
             trader_id,
+            link_queues,
             phantom: Default::default(),
         }
     }
@@ -90,17 +107,19 @@ impl<
     T: Trader<BrokerID=BrokerID, B2T=B2T>,
     E: Exchange<BrokerID=BrokerID, ExchangeID=R::ExchangeID, B2E=B2E, E2R=R::E2R, R2E=R::R2E>,
     R: Replay<BrokerID=BrokerID, B2R=B2R>,
+    A: Auxiliary,
 >
 LatentActionProcessor<BrokerAction<B2R, B2E, B2T, B2B>, E::ExchangeID>
-for BrokerActionProcessor<'a, BrokerID, BrokerAction<B2R, B2E, B2T, B2B>, T, E, R>
+for BrokerActionProcessor<'a, BrokerID, BrokerAction<B2R, B2E, B2T, B2B>, T, E, R, A>
 {
     type KerMsg = Message<
         MessageContent<
-            E::ExchangeID, BrokerID, T::TraderID,
+            E::ExchangeID, BrokerID, T::TraderID, A::AuxiliaryID,
             R::R2R, R::R2E, R::R2B,
             B2R, B2E, B2T, B2B,
             T::T2B, T::T2T,
-            E::E2R, E::E2B, E::E2E
+            E::E2R, E::E2B, E::E2E,
+            A::A2A
         >
     >;
 
@@ -108,10 +127,10 @@ for BrokerActionProcessor<'a, BrokerID, BrokerAction<B2R, B2E, B2T, B2B>, T, E,
     fn process_action(
         &mut self,
         action: BrokerAction<B2R, B2E, B2T, B2B>,
-        mut latency_generator: impl LatencyGenerator<OuterID=E::ExchangeID>,
+        latency_generator: &mut impl LatencyGenerator<OuterID=E::ExchangeID>,
         rng: &mut impl Rng) -> Self::KerMsg
     {
-        let delayed_dt = self.current_dt + Duration::nanoseconds(action.delay as i64);
+        let delayed_dt = self.current_dt + action.delay;
         let (datetime, body) = match action.content
         {
             BrokerActionKind::BrokerToReplay(reply) => {
@@ -126,19 +145,39 @@ for BrokerActionProcessor<'a, BrokerID, BrokerAction<B2R, B2E, B2T, B2B>, T, E,
                     || panic!("Kernel does not know such a Trader: {trader_id}")
                 );
                 *trader.current_datetime_mut() = self.current_dt;
-                let latency = trader
-                    .get_latency_generator()
-                    .incoming_latency(self.broker_id, delayed_dt, rng);
+                let mut latency_generator = trader.get_latency_generator();
+                let sent_dt = self.link_queues.transmit_trader_message(
+                    trader_id,
+                    self.broker_id,
+                    false,
+                    delayed_dt,
+                    reply.get_size_in_bytes(),
+                    latency_generator.incoming_bandwidth(self.broker_id),
+                );
+                let latency = self.link_queues.adjust_latency(
+                    latency_generator.incoming_latency(self.broker_id, sent_dt, rng)
+                );
+                trader.update_latency_generator(latency_generator);
                 (
-                    delayed_dt + Duration::nanoseconds(latency as i64),
+                    sent_dt + latency,
                     MessageContent::BrokerToTrader { broker_id: self.broker_id, b2t: reply }
                 )
             }
             BrokerActionKind::BrokerToExchange(request) => {
                 let exchange_id = request.get_exchange_id();
-                let latency = latency_generator.outgoing_latency(exchange_id, delayed_dt, rng);
+                let sent_dt = self.link_queues.transmit_broker_message(
+                    self.broker_id,
+                    exchange_id,
+                    true,
+                    delayed_dt,
+                    request.get_size_in_bytes(),
+                    latency_generator.outgoing_bandwidth(exchange_id),
+                );
+                let latency = self.link_queues.adjust_latency(
+                    latency_generator.outgoing_latency(exchange_id, sent_dt, rng)
+                );
                 (
-                    delayed_dt + Duration::nanoseconds(latency as i64),
+                    sent_dt + latency,
                     MessageContent::BrokerToExchange { broker_id: self.broker_id, b2e: request }
                 )
             }
@@ -148,6 +187,12 @@ for BrokerActionProcessor<'a, BrokerID, BrokerAction<B2R, B2E, B2T, B2B>, T, E,
                     MessageContent::BrokerWakeUp { broker_id: self.broker_id, b2b: wakeup }
                 )
             }
+            BrokerActionKind::BrokerToBroker(message) => {
+                (
+                    delayed_dt,
+                    MessageContent::BrokerToBroker { broker_id: self.broker_id, b2b: message }
+                )
+            }
         };
         Message { datetime, body }
     }
@@ -159,18 +204,20 @@ impl<
     T2T: TraderToItself,
     B: Broker<T2B=T2B, ExchangeID=R::ExchangeID, TraderID=TraderID, BrokerID=R::BrokerID>,
     E: Exchange<BrokerID=B::BrokerID, ExchangeID=R::ExchangeID, B2E=B::B2E, E2R=R::E2R, R2E=R::R2E>,
-    R: Replay
+    R: Replay,
+    A: Auxiliary,
 >
 LatentActionProcessor<TraderAction<T2B, T2T>, B::BrokerID>
-for TraderActionProcessor<TraderID, TraderAction<T2B, T2T>, B, E, R>
+for TraderActionProcessor<'_, TraderID, TraderAction<T2B, T2T>, B, E, R, A>
 {
     type KerMsg = Message<
         MessageContent<
-            E::ExchangeID, B::BrokerID, TraderID,
+            E::ExchangeID, B::BrokerID, TraderID, A::AuxiliaryID,
             R::R2R, R::R2E, R::R2B,
             B::B2R, B::B2E, B::B2T, B::B2B,
             B::T2B, T2T,
-            E::E2R, E::E2B, E::E2E
+            E::E2R, E::E2B, E::E2E,
+            A::A2A
         >
     >;
 
@@ -178,17 +225,27 @@ for TraderActionProcessor<TraderID, TraderAction<T2B, T2T>, B, E, R>
     fn process_action(
         &mut self,
         action: TraderAction<T2B, T2T>,
-        mut latency_generator: impl LatencyGenerator<OuterID=B::BrokerID>,
+        latency_generator: &mut impl LatencyGenerator<OuterID=B::BrokerID>,
         rng: &mut impl Rng) -> Self::KerMsg
     {
-        let delayed_dt = self.current_dt + Duration::nanoseconds(action.delay as i64);
+        let delayed_dt = self.current_dt + self.decision_latency + action.delay;
         let (datetime, body) = match action.content
         {
             TraderActionKind::TraderToBroker(request) => {
                 let broker_id = request.get_broker_id();
-                let latency = latency_generator.outgoing_latency(broker_id, delayed_dt, rng);
+                let sent_dt = self.link_queues.transmit_trader_message(
+                    self.trader_id,
+                    broker_id,
+                    true,
+                    delayed_dt,
+                    request.get_size_in_bytes(),
+                    latency_generator.outgoing_bandwidth(broker_id),
+                );
+                let latency = self.link_queues.adjust_latency(
+                    latency_generator.outgoing_latency(broker_id, sent_dt, rng)
+                );
                 (
-                    delayed_dt + Duration::nanoseconds(latency as i64),
+                    sent_dt + latency,
                     MessageContent::TraderToBroker { trader_id: self.trader_id, t2b: request }
                 )
             }
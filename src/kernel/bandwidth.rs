@@ -0,0 +1,157 @@
+use {
+    crate::{
+        kernel::LatencyAdjustment,
+        types::{DateTime, Duration, Id, NanoDuration},
+        utils::collections::HashMap,
+    },
+    core::{hash::Hash, num::NonZeroU64},
+};
+
+/// Transmission queues of the bandwidth-constrained links between the agents.
+/// Every direction of the link is a separate queue.
+pub(in crate::kernel) struct LinkQueues<TraderID: Id, BrokerID: Id, ExchangeID: Id> {
+    trader_links: HashMap<(TraderID, BrokerID, bool), DateTime>,
+    broker_links: HashMap<(BrokerID, ExchangeID, bool), DateTime>,
+    /// Adjustment of the latent delays of all the links.
+    latency_adjustment: LatencyAdjustment,
+}
+
+impl<TraderID: Id, BrokerID: Id, ExchangeID: Id> LinkQueues<TraderID, BrokerID, ExchangeID>
+{
+    /// Creates a new instance of the `LinkQueues`.
+    pub fn new(latency_adjustment: LatencyAdjustment) -> Self {
+        Self {
+            trader_links: Default::default(),
+            broker_links: Default::default(),
+            latency_adjustment,
+        }
+    }
+
+    /// Returns the latent delay of the message leaving the link queue
+    /// adjusted by the [`LatencyAdjustment`].
+    #[inline]
+    pub fn adjust_latency(&self, latency: NanoDuration) -> NanoDuration {
+        self.latency_adjustment.apply(latency)
+    }
+
+    /// Returns the datetime the message leaves the trader-broker link queue at.
+    #[inline]
+    pub fn transmit_trader_message(
+        &mut self,
+        trader_id: TraderID,
+        broker_id: BrokerID,
+        outgoing: bool,
+        send_dt: DateTime,
+        size_in_bytes: u64,
+        bandwidth: Option<NonZeroU64>) -> DateTime
+    {
+        transmit(
+            &mut self.trader_links,
+            (trader_id, broker_id, outgoing),
+            send_dt,
+            size_in_bytes,
+            bandwidth,
+        )
+    }
+
+    /// Returns the datetime the message leaves the broker-exchange link queue at.
+    #[inline]
+    pub fn transmit_broker_message(
+        &mut self,
+        broker_id: BrokerID,
+        exchange_id: ExchangeID,
+        outgoing: bool,
+        send_dt: DateTime,
+        size_in_bytes: u64,
+        bandwidth: Option<NonZeroU64>) -> DateTime
+    {
+        transmit(
+            &mut self.broker_links,
+            (broker_id, exchange_id, outgoing),
+            send_dt,
+            size_in_bytes,
+            bandwidth,
+        )
+    }
+}
+
+#[inline]
+fn transmit<Link: Hash + Eq>(
+    busy_until: &mut HashMap<Link, DateTime>,
+    link: Link,
+    send_dt: DateTime,
+    size_in_bytes: u64,
+    bandwidth: Option<NonZeroU64>) -> DateTime
+{
+    let bandwidth = match bandwidth {
+        Some(bandwidth) if size_in_bytes != 0 => bandwidth.get() as u128,
+        _ => return send_dt
+    };
+    // Rounded up so that no message is transmitted instantly
+    let transmission_ns = (size_in_bytes as u128 * 1_000_000_000).div_ceil(bandwidth);
+    let busy_until = busy_until.entry(link).or_insert(send_dt);
+    *busy_until = send_dt.max(*busy_until) + Duration::nanoseconds(transmission_ns as i64);
+    *busy_until
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::test_utils::dt_ns,
+        super::*,
+    };
+
+    #[test]
+    fn test_unconstrained_link() {
+        let mut queues = LinkQueues::<u8, u8, u8>::new(LatencyAdjustment::default());
+        assert_eq!(queues.transmit_trader_message(0, 0, true, dt_ns(0), 1000, None), dt_ns(0));
+        // Empty messages are not queued
+        let bandwidth = NonZeroU64::new(1);
+        assert_eq!(queues.transmit_trader_message(0, 0, true, dt_ns(5), 0, bandwidth), dt_ns(5));
+        assert_eq!(queues.transmit_broker_message(0, 0, true, dt_ns(5), 0, bandwidth), dt_ns(5));
+    }
+
+    #[test]
+    fn test_link_queueing() {
+        let mut queues = LinkQueues::<u8, u8, u8>::new(LatencyAdjustment::default());
+        // 1000 bytes per second, i.e. 1 millisecond per byte
+        let bandwidth = NonZeroU64::new(1000);
+        let ms = 1_000_000;
+
+        // Burst is transmitted message by message
+        assert_eq!(
+            queues.transmit_broker_message(0, 1, false, dt_ns(0), 2, bandwidth),
+            dt_ns(2 * ms)
+        );
+        assert_eq!(
+            queues.transmit_broker_message(0, 1, false, dt_ns(0), 3, bandwidth),
+            dt_ns(5 * ms)
+        );
+        assert_eq!(
+            queues.transmit_broker_message(0, 1, false, dt_ns(ms), 1, bandwidth),
+            dt_ns(6 * ms)
+        );
+        // Other direction and other links have their own queues
+        assert_eq!(queues.transmit_broker_message(0, 1, true, dt_ns(0), 1, bandwidth), dt_ns(ms));
+        assert_eq!(queues.transmit_broker_message(1, 1, false, dt_ns(0), 1, bandwidth), dt_ns(ms));
+        assert_eq!(queues.transmit_trader_message(0, 1, false, dt_ns(0), 1, bandwidth), dt_ns(ms));
+        // Idle link starts transmitting at once
+        assert_eq!(
+            queues.transmit_broker_message(0, 1, false, dt_ns(10 * ms), 1, bandwidth),
+            dt_ns(11 * ms)
+        );
+    }
+
+    #[test]
+    fn test_transmission_time_rounding() {
+        let mut queues = LinkQueues::<u8, u8, u8>::new(LatencyAdjustment::default());
+        let bandwidth = NonZeroU64::new(3);
+        assert_eq!(
+            queues.transmit_trader_message(0, 0, true, dt_ns(0), 1, bandwidth),
+            dt_ns(333_333_334)
+        );
+        // Even the fastest link takes at least a nanosecond
+        let bandwidth = NonZeroU64::new(u64::MAX);
+        assert_eq!(queues.transmit_trader_message(0, 0, false, dt_ns(0), 1, bandwidth), dt_ns(1));
+    }
+}
@@ -0,0 +1,239 @@
+use {
+    crate::{interface::message::BrokerToTrader, types::DateTime},
+    alloc::rc::Rc,
+    core::{cell::Cell, cmp::Ordering, fmt::Arguments},
+};
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+/// Reaction of the [`LookaheadGuard`] to the lookahead violation.
+pub enum LookaheadPolicy {
+    /// Panic with the description of the violation.
+    #[default]
+    Panic,
+    /// Print the description of the violation to the standard error and proceed.
+    Log,
+}
+
+/// Detects the strategies that use the market data before they have received it
+/// or compare it with the timestamps in the future of the trader clock.
+///
+/// The [`Kernel`](crate::kernel::Kernel) keeps its own guard in sync with the simulation clock
+/// and wraps every broker reply it delivers to the trader,
+/// see [`Trader::process_delivered_broker_reply`](crate::interface::trader::Trader).
+/// Standalone guards are for the data the trader obtains elsewhere, e.g. the preloaded history,
+/// in which case the trader should call [`sync`](LookaheadGuard::sync) whenever its clock advances
+/// and [`wrap`](LookaheadGuard::wrap) every such data object.
+pub struct LookaheadGuard {
+    clock: Rc<Cell<DateTime>>,
+    policy: LookaheadPolicy,
+}
+
+impl LookaheadGuard {
+    /// Creates a new instance of the `LookaheadGuard`.
+    ///
+    /// # Arguments
+    ///
+    /// * `current_dt` — Current datetime of the trader.
+    /// * `policy` — Reaction to the lookahead violations.
+    pub fn new(current_dt: DateTime, policy: LookaheadPolicy) -> Self {
+        Self {
+            clock: Rc::new(Cell::new(current_dt)),
+            policy,
+        }
+    }
+
+    /// Advances the clock of the guard.
+    ///
+    /// # Arguments
+    ///
+    /// * `current_dt` — Current datetime of the trader.
+    pub fn sync(&self, current_dt: DateTime) {
+        if current_dt < self.clock.get() {
+            panic!(
+                "Trader clock cannot go backwards. Got {current_dt} after {}",
+                self.clock.get()
+            )
+        }
+        self.clock.set(current_dt)
+    }
+
+    /// Wraps the market data object delivered to the trader at the current datetime.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` — Market data object.
+    /// * `data_dt` — Datetime the data refers to.
+    pub fn wrap<T>(&self, value: T, data_dt: DateTime) -> Delivered<T> {
+        Delivered {
+            value,
+            data_dt,
+            delivered_dt: self.clock.get(),
+            clock: Rc::clone(&self.clock),
+            policy: self.policy,
+        }
+    }
+
+    /// Wraps the broker reply delivered to the trader at the current datetime.
+    /// Replies that do not inform about any event refer to the current datetime.
+    ///
+    /// # Arguments
+    ///
+    /// * `reply` — Broker reply.
+    pub fn wrap_reply<B2T: BrokerToTrader>(&self, reply: B2T) -> Delivered<B2T> {
+        let data_dt = reply.get_event_dt().unwrap_or_else(|| self.clock.get());
+        self.wrap(reply, data_dt)
+    }
+}
+
+/// Market data object along with the datetime it was delivered to the trader at.
+///
+/// Reading the data from the future or comparing its datetime with the ones
+/// in the future of the trader clock is reported according to the [`LookaheadPolicy`].
+pub struct Delivered<T> {
+    value: T,
+    data_dt: DateTime,
+    delivered_dt: DateTime,
+    clock: Rc<Cell<DateTime>>,
+    policy: LookaheadPolicy,
+}
+
+impl<T> Delivered<T> {
+    /// Returns the wrapped object.
+    /// Reading the data that refers to the future of the trader clock is a violation,
+    /// e.g. the history wrapped before it has happened.
+    pub fn get(&self) -> &T {
+        let now = self.clock.get();
+        if self.data_dt > now {
+            self.report(
+                format_args!(
+                    "{now} :: Market data of {} delivered at {} is read before it happened",
+                    self.data_dt, self.delivered_dt
+                )
+            )
+        }
+        &self.value
+    }
+
+    /// Returns the wrapped object without any check.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    /// Returns the datetime the data refers to.
+    pub fn get_data_dt(&self) -> DateTime {
+        self.data_dt
+    }
+
+    /// Returns the datetime the data was delivered to the trader at.
+    pub fn get_delivered_dt(&self) -> DateTime {
+        self.delivered_dt
+    }
+
+    /// Compares the datetime of the data with the other one.
+    ///
+    /// # Arguments
+    ///
+    /// * `other_dt` — Datetime to compare with. Should not be in the future of the trader clock.
+    pub fn cmp_dt(&self, other_dt: DateTime) -> Ordering {
+        let now = self.clock.get();
+        if other_dt > now {
+            self.report(
+                format_args!(
+                    "{now} :: Market data timestamp {} is compared \
+                    with the timestamp {other_dt} from the future",
+                    self.data_dt
+                )
+            )
+        }
+        self.data_dt.cmp(&other_dt)
+    }
+
+    fn report(&self, message: Arguments) {
+        match self.policy {
+            LookaheadPolicy::Panic => panic!("Lookahead bias detected. {message}"),
+            LookaheadPolicy::Log => eprintln!("Lookahead bias detected. {message}")
+        }
+    }
+}
+
+impl<T> PartialEq<DateTime> for Delivered<T> {
+    fn eq(&self, other: &DateTime) -> bool {
+        self.cmp_dt(*other) == Ordering::Equal
+    }
+}
+
+impl<T> PartialOrd<DateTime> for Delivered<T> {
+    fn partial_cmp(&self, other: &DateTime) -> Option<Ordering> {
+        Some(self.cmp_dt(*other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::test_utils::dt,
+        super::*,
+    };
+    #[cfg(feature = "concrete")]
+    use crate::concrete::{
+        message_protocol::{
+            broker::reply::{BasicBrokerReply, BasicBrokerToTrader},
+            exchange::reply::ExchangeEventNotification,
+        },
+        traded_pair::settlement::concrete::SpotSettlement,
+    };
+
+    #[cfg(feature = "concrete")]
+    #[test]
+    fn test_delivered_reply() {
+        let guard = LookaheadGuard::new(dt(2), LookaheadPolicy::Panic);
+        let reply: BasicBrokerToTrader<u8, &str, &str, SpotSettlement> = BasicBrokerToTrader {
+            trader_id: 0,
+            exchange_id: "MOEX",
+            event_dt: dt(1),
+            content: BasicBrokerReply::ExchangeEventNotification(
+                ExchangeEventNotification::ExchangeOpen
+            ),
+        };
+        let reply = guard.wrap_reply(reply);
+        assert_eq!((reply.get_data_dt(), reply.get_delivered_dt()), (dt(1), dt(2)));
+        assert!(reply < dt(2));
+        guard.sync(dt(3));
+        assert!(reply == dt(1));
+        assert_eq!(reply.get().event_dt, dt(1))
+    }
+
+    #[test]
+    #[should_panic(expected = "is compared with the timestamp")]
+    fn test_comparison_with_future() {
+        let guard = LookaheadGuard::new(dt(2), LookaheadPolicy::Panic);
+        let value = guard.wrap(100, dt(1));
+        let _ = value < dt(3);
+    }
+
+    #[test]
+    #[should_panic(expected = "is read before it happened")]
+    fn test_reading_history_ahead() {
+        let guard = LookaheadGuard::new(dt(2), LookaheadPolicy::Panic);
+        let history = [guard.wrap(100, dt(1)), guard.wrap(101, dt(3))];
+        assert_eq!(*history[0].get(), 100);
+        history[1].get();
+    }
+
+    #[test]
+    fn test_log_policy() {
+        let guard = LookaheadGuard::new(dt(2), LookaheadPolicy::Log);
+        let value = guard.wrap(100, dt(3));
+        assert_eq!(*value.get(), 100);
+        assert!(value > dt(2));
+        guard.sync(dt(3));
+        assert_eq!(value.into_inner(), 100)
+    }
+
+    #[test]
+    #[should_panic(expected = "Trader clock cannot go backwards")]
+    fn test_clock_going_backwards() {
+        LookaheadGuard::new(dt(2), LookaheadPolicy::Panic).sync(dt(1))
+    }
+}
+
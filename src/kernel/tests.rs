@@ -0,0 +1,858 @@
+use {
+    crate::{
+        concrete::{
+            broker::BasicVoidBroker,
+            exchange::BasicVoidExchange,
+            latency::ConstantLatency,
+            message_protocol::{
+                broker::{
+                    reply::{BasicBrokerReply, BasicBrokerToTrader},
+                    request::BasicBrokerToExchange,
+                    wakeup::{BasicBrokerToItself, GiveUp},
+                },
+                exchange::reply::{
+                    BasicExchangeToBroker,
+                    BasicExchangeToReplay,
+                    ExchangeEventNotification,
+                },
+                replay::request::{BasicReplayToBroker, BasicReplayToExchange},
+                trader::request::{BasicTraderRequest, BasicTraderToBroker},
+            },
+            order::LimitOrderCancelRequest,
+            traded_pair::settlement::concrete::SpotSettlement,
+            types::{Direction, Lots, OrderID, Tick as Price},
+        },
+        interface::{
+            auxiliary::{Auxiliary, AuxiliaryAction, AuxiliaryActionKind},
+            broker::{Broker as BrokerInterface, BrokerAction, BrokerActionKind},
+            latency::{Latent, LatencyGenerator},
+            message::{AuxiliaryToAuxiliary, ReplayToItself, TraderToItself},
+            replay::{Replay, ReplayAction, ReplayActionKind},
+            trader::{Trader, TraderAction, TraderActionKind},
+        },
+        kernel::{
+            KernelBuilder,
+            LatentActionProcessor,
+            tracer::{SharedTraceBuffer, TraceBuffer, TraceEndpoint},
+        },
+        test_utils::{hms, pair},
+        types::{Agent, DateTime, Duration, NanoDuration, Named, Nothing, TimeSync},
+        utils::queue::MessageReceiver,
+    },
+    rand::{Rng, rngs::StdRng},
+    std::{cell::RefCell, rc::Rc, vec::IntoIter},
+};
+#[cfg(feature = "lookahead_guard")]
+use crate::kernel::lookahead::{Delivered, LookaheadPolicy};
+
+type Exchange = BasicVoidExchange<&'static str, u8, &'static str, SpotSettlement>;
+type Broker = BasicVoidBroker<u8, u8, &'static str, &'static str, SpotSettlement>;
+type BrokerMessage = BasicBrokerToItself<u8, u8, &'static str, &'static str, SpotSettlement>;
+type T2B = BasicTraderToBroker<u8, &'static str, &'static str, SpotSettlement>;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+struct Tick;
+
+impl ReplayToItself for Tick {}
+
+impl TraderToItself for Tick {}
+
+/// Replay that only wakes itself up at the given datetimes.
+struct TickReplay {
+    current_dt: DateTime,
+    ticks: IntoIter<DateTime>,
+}
+
+impl TimeSync for TickReplay {
+    fn current_datetime_mut(&mut self) -> &mut DateTime {
+        &mut self.current_dt
+    }
+}
+
+impl Iterator for TickReplay {
+    type Item = ReplayAction<
+        Tick,
+        BasicReplayToExchange<&'static str, &'static str, SpotSettlement>,
+        BasicReplayToBroker<u8, &'static str, &'static str, SpotSettlement>
+    >;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.ticks.next().map(
+            |datetime| ReplayAction { datetime, content: ReplayActionKind::ReplayToItself(Tick) }
+        )
+    }
+}
+
+impl Replay for TickReplay {
+    type ExchangeID = &'static str;
+    type BrokerID = u8;
+
+    type E2R = BasicExchangeToReplay<&'static str, SpotSettlement>;
+    type B2R = Nothing;
+    type R2R = Tick;
+    type R2E = BasicReplayToExchange<&'static str, &'static str, SpotSettlement>;
+    type R2B = BasicReplayToBroker<u8, &'static str, &'static str, SpotSettlement>;
+
+    fn wakeup(&mut self, _: Self::R2R, _: &mut impl Rng) {}
+
+    fn handle_exchange_reply(&mut self, _: Self::E2R, _: Self::ExchangeID, _: &mut impl Rng) {}
+
+    fn handle_broker_reply(&mut self, _: Self::B2R, _: Self::BrokerID, _: &mut impl Rng) {}
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Event {
+    IdlePeriod { trader_id: u8, idle_start: DateTime, idle_end: DateTime },
+    WakeUp { trader_id: u8, datetime: DateTime },
+}
+
+/// Trader that wakes itself up after every idle period.
+struct IdleTrader {
+    name: u8,
+    current_dt: DateTime,
+    events: Rc<RefCell<Vec<Event>>>,
+}
+
+impl TimeSync for IdleTrader {
+    fn current_datetime_mut(&mut self) -> &mut DateTime {
+        &mut self.current_dt
+    }
+}
+
+impl Named<u8> for IdleTrader {
+    fn get_name(&self) -> u8 {
+        self.name
+    }
+}
+
+impl Agent for IdleTrader {
+    type Action = TraderAction<T2B, Tick>;
+}
+
+impl Latent for IdleTrader {
+    type OuterID = u8;
+    type LatencyGenerator = ConstantLatency<u8, 0, 0>;
+
+    fn get_latency_generator(&self) -> Self::LatencyGenerator {
+        ConstantLatency::new()
+    }
+}
+
+impl Trader for IdleTrader {
+    type TraderID = u8;
+    type BrokerID = u8;
+
+    type B2T = BasicBrokerToTrader<u8, &'static str, &'static str, SpotSettlement>;
+    type T2T = Tick;
+    type T2B = T2B;
+
+    fn wakeup<KerMsg: Ord>(
+        &mut self,
+        _: MessageReceiver<KerMsg>,
+        _: impl LatentActionProcessor<Self::Action, Self::BrokerID, KerMsg=KerMsg>,
+        _: Self::T2T,
+        _: &mut impl Rng,
+    ) {
+        let event = Event::WakeUp { trader_id: self.name, datetime: self.current_dt };
+        self.events.borrow_mut().push(event)
+    }
+
+    fn process_broker_reply<KerMsg: Ord>(
+        &mut self,
+        _: MessageReceiver<KerMsg>,
+        _: impl LatentActionProcessor<Self::Action, Self::BrokerID, KerMsg=KerMsg>,
+        _: Self::B2T,
+        _: Self::BrokerID,
+        _: &mut impl Rng,
+    ) {}
+
+    fn upon_register_at_broker(&mut self, _: Self::BrokerID) {}
+
+    fn on_idle_period<KerMsg: Ord>(
+        &mut self,
+        mut message_receiver: MessageReceiver<KerMsg>,
+        action_processor: impl LatentActionProcessor<Self::Action, Self::BrokerID, KerMsg=KerMsg>,
+        idle_start: DateTime,
+        idle_end: DateTime,
+        rng: &mut impl Rng,
+    ) {
+        let event = Event::IdlePeriod { trader_id: self.name, idle_start, idle_end };
+        self.events.borrow_mut().push(event);
+        let mut action_processor = action_processor;
+        let action = TraderAction {
+            delay: NanoDuration(self.name as u64),
+            content: TraderActionKind::TraderToItself(Tick),
+        };
+        message_receiver.push(
+            action_processor.process_action(action, &mut self.get_latency_generator(), rng)
+        )
+    }
+}
+
+#[test]
+fn test_idle_period()
+{
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let traders = [3, 1, 2].map(
+        |name| (
+            IdleTrader { name, current_dt: hms(0, 0, 0), events: events.clone() },
+            [(1, [])]
+        )
+    );
+    let replay = TickReplay {
+        current_dt: hms(0, 0, 0),
+        ticks: vec![hms(10, 0, 0), hms(10, 0, 1), hms(12, 0, 0), hms(14, 0, 0)].into_iter(),
+    };
+    KernelBuilder::new(
+        [Exchange::new("MOEX")],
+        [(Broker::new(1), ["MOEX"])],
+        traders,
+        replay,
+        (hms(10, 0, 0), hms(12, 0, 1)),
+    )
+        .with_seed(0)
+        .with_idle_period_threshold(Duration::hours(1))
+        .build()
+        .run_simulation();
+
+    // Traders are notified in the order of their IDs and may act upon the idle period.
+    // Period before the event past the end datetime is not reported
+    let (idle_start, idle_end) = (hms(10, 0, 1), hms(12, 0, 0));
+    let idle_end_after = |nanoseconds| idle_end + Duration::nanoseconds(nanoseconds);
+    assert_eq!(
+        *events.borrow(),
+        [
+            Event::IdlePeriod { trader_id: 1, idle_start, idle_end },
+            Event::IdlePeriod { trader_id: 2, idle_start, idle_end },
+            Event::IdlePeriod { trader_id: 3, idle_start, idle_end },
+            Event::WakeUp { trader_id: 1, datetime: idle_end_after(1) },
+            Event::WakeUp { trader_id: 2, datetime: idle_end_after(2) },
+            Event::WakeUp { trader_id: 3, datetime: idle_end_after(3) },
+        ]
+    );
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+struct Ping(&'static str);
+
+impl AuxiliaryToAuxiliary for Ping {
+    type AuxiliaryID = &'static str;
+
+    fn get_auxiliary_id(&self) -> Self::AuxiliaryID {
+        self.0
+    }
+}
+
+/// Auxiliary agent that pings itself once and sends the given wakeups.
+struct Clock {
+    current_dt: DateTime,
+    wakeups: Vec<AuxiliaryActionKind<Ping, u8, Tick, u8, BrokerMessage>>,
+}
+
+impl TimeSync for Clock {
+    fn current_datetime_mut(&mut self) -> &mut DateTime {
+        &mut self.current_dt
+    }
+}
+
+impl Named<&'static str> for Clock {
+    fn get_name(&self) -> &'static str {
+        "Clock"
+    }
+}
+
+impl Agent for Clock {
+    type Action = AuxiliaryAction<Ping, u8, Tick, u8, BrokerMessage>;
+}
+
+impl Auxiliary for Clock {
+    type AuxiliaryID = &'static str;
+    type TraderID = u8;
+    type BrokerID = u8;
+    type A2A = Ping;
+    type T2T = Tick;
+    type B2B = BrokerMessage;
+
+    fn initialize<KerMsg: Ord, RNG: Rng>(
+        &mut self,
+        mut message_receiver: MessageReceiver<KerMsg>,
+        mut process_action: impl FnMut(Self::Action, &mut RNG) -> KerMsg,
+        rng: &mut RNG,
+    ) {
+        let action = AuxiliaryAction {
+            delay: NanoDuration(1),
+            content: AuxiliaryActionKind::AuxiliaryToAuxiliary(Ping("Clock")),
+        };
+        message_receiver.push(process_action(action, rng));
+        for content in self.wakeups.drain(..) {
+            let action = AuxiliaryAction { delay: NanoDuration(1), content };
+            message_receiver.push(process_action(action, rng))
+        }
+    }
+
+    fn process_auxiliary_message<KerMsg: Ord, RNG: Rng>(
+        &mut self,
+        _: MessageReceiver<KerMsg>,
+        _: impl FnMut(Self::Action, &mut RNG) -> KerMsg,
+        _: Self::A2A,
+        _: Self::AuxiliaryID,
+        _: &mut RNG,
+    ) {}
+}
+
+#[test]
+fn test_tracer_before_auxiliary_agents()
+{
+    let buffer: SharedTraceBuffer<_, _, _, _> = Rc::new(RefCell::new(TraceBuffer::new(16)));
+    let replay = TickReplay { current_dt: hms(0, 0, 0), ticks: vec![hms(10, 0, 1)].into_iter() };
+    KernelBuilder::new(
+        [Exchange::new("MOEX")],
+        [(Broker::new(1), ["MOEX"])],
+        [(IdleTrader { name: 1, current_dt: hms(0, 0, 0), events: Default::default() }, [(1, [])])],
+        replay,
+        (hms(10, 0, 0), hms(10, 0, 2)),
+    )
+        .with_seed(0)
+        .with_tracer(buffer.clone())
+        .with_auxiliary_agents([Clock { current_dt: hms(0, 0, 0), wakeups: vec![] }])
+        .build()
+        .run_simulation();
+
+    let buffer = buffer.borrow();
+    let traced: Vec<_> = buffer.get_records()
+        .map(|record| (record.datetime, record.kind, record.source, record.message.as_str()))
+        .collect();
+    assert_eq!(
+        traced,
+        [
+            (
+                hms(10, 0, 0) + Duration::nanoseconds(1),
+                "AuxiliaryToAuxiliary",
+                TraceEndpoint::Auxiliary("Clock"),
+                "Ping(\"Clock\")",
+            ),
+            (hms(10, 0, 1), "ReplayWakeUp", TraceEndpoint::Replay, "Tick"),
+        ]
+    )
+}
+
+#[test]
+fn test_auxiliary_agents_carried_over()
+{
+    let buffer: SharedTraceBuffer<_, _, _, _> = Rc::new(RefCell::new(TraceBuffer::new(16)));
+    let events = Rc::new(RefCell::new(Vec::new()));
+    let trader = IdleTrader { name: 1, current_dt: hms(0, 0, 0), events: events.clone() };
+    let replay = |tick| TickReplay { current_dt: hms(0, 0, 0), ticks: vec![tick].into_iter() };
+    let wakeup = AuxiliaryActionKind::AuxiliaryToTrader { trader_id: 1, t2t: Tick };
+    let agents = KernelBuilder::new(
+        [Exchange::new("MOEX")],
+        [(Broker::new(1), ["MOEX"])],
+        [(trader, [(1, [])])],
+        replay(hms(10, 0, 1)),
+        (hms(10, 0, 0), hms(10, 0, 2)),
+    )
+        .with_seed(0)
+        .with_tracer(buffer.clone())
+        .with_auxiliary_agents([Clock { current_dt: hms(0, 0, 0), wakeups: vec![wakeup] }])
+        .with_rng::<StdRng>()
+        .build()
+        .run_simulation_into_agents();
+    assert_eq!(agents.auxiliaries.len(), 1);
+    KernelBuilder::from_previous_run(
+        agents,
+        [Exchange::new("MOEX")],
+        [(1, ["MOEX"])],
+        replay(hms(11, 0, 1)),
+        (hms(11, 0, 0), hms(11, 0, 2)),
+    )
+        .with_seed(0)
+        .with_tracer(buffer.clone())
+        .build()
+        .run_simulation();
+
+    // Wakeup of the trader is traced as the message of the auxiliary agent,
+    // which keeps running in the next simulation
+    let buffer = buffer.borrow();
+    let traced: Vec<_> = buffer.get_records()
+        .filter(|record| record.kind != "ReplayWakeUp")
+        .map(|record| (record.datetime, record.kind, record.source, record.destination))
+        .collect();
+    let clock = TraceEndpoint::Auxiliary("Clock");
+    let sent_dt = |start_dt: DateTime| start_dt + Duration::nanoseconds(1);
+    assert_eq!(
+        traced,
+        [
+            (sent_dt(hms(10, 0, 0)), "AuxiliaryToAuxiliary", clock, clock),
+            (sent_dt(hms(10, 0, 0)), "AuxiliaryToTrader", clock, TraceEndpoint::Trader(1)),
+            (sent_dt(hms(11, 0, 0)), "AuxiliaryToAuxiliary", clock, clock),
+        ]
+    );
+    let wakeup_dt = sent_dt(hms(10, 0, 0));
+    assert_eq!(*events.borrow(), [Event::WakeUp { trader_id: 1, datetime: wakeup_dt }]);
+}
+
+/// Broker that forwards its wakeups to the other brokers, records the messages it receives
+/// and answers every trader request with the exchange opening notification.
+struct RelayBroker {
+    name: u8,
+    current_dt: DateTime,
+    received: Rc<RefCell<Vec<(u8, u8, DateTime, BrokerMessage)>>>,
+}
+
+impl TimeSync for RelayBroker {
+    fn current_datetime_mut(&mut self) -> &mut DateTime {
+        &mut self.current_dt
+    }
+}
+
+impl Named<u8> for RelayBroker {
+    fn get_name(&self) -> u8 {
+        self.name
+    }
+}
+
+impl Agent for RelayBroker {
+    type Action = BrokerAction<
+        Nothing,
+        BasicBrokerToExchange<&'static str, &'static str, SpotSettlement>,
+        BasicBrokerToTrader<u8, &'static str, &'static str, SpotSettlement>,
+        BrokerMessage
+    >;
+}
+
+impl Latent for RelayBroker {
+    type OuterID = &'static str;
+    type LatencyGenerator = ConstantLatency<&'static str, 0, 0>;
+
+    fn get_latency_generator(&self) -> Self::LatencyGenerator {
+        ConstantLatency::new()
+    }
+}
+
+impl BrokerInterface for RelayBroker {
+    type BrokerID = u8;
+    type TraderID = u8;
+    type ExchangeID = &'static str;
+
+    type R2B = BasicReplayToBroker<u8, &'static str, &'static str, SpotSettlement>;
+    type E2B = BasicExchangeToBroker<u8, &'static str, SpotSettlement>;
+    type T2B = T2B;
+    type B2R = Nothing;
+    type B2E = BasicBrokerToExchange<&'static str, &'static str, SpotSettlement>;
+    type B2T = BasicBrokerToTrader<u8, &'static str, &'static str, SpotSettlement>;
+    type B2B = BrokerMessage;
+    type SubCfg = Nothing;
+
+    fn wakeup<KerMsg: Ord>(
+        &mut self,
+        mut message_receiver: MessageReceiver<KerMsg>,
+        mut action_processor: impl LatentActionProcessor<Self::Action, Self::ExchangeID, KerMsg=KerMsg>,
+        scheduled_action: Self::B2B,
+        rng: &mut impl Rng,
+    ) {
+        let action = BrokerAction {
+            delay: NanoDuration(1),
+            content: BrokerActionKind::BrokerToBroker(scheduled_action),
+        };
+        message_receiver.push(
+            action_processor.process_action(action, &mut self.get_latency_generator(), rng)
+        )
+    }
+
+    fn process_trader_request<KerMsg: Ord>(
+        &mut self,
+        mut message_receiver: MessageReceiver<KerMsg>,
+        mut action_processor: impl LatentActionProcessor<Self::Action, Self::ExchangeID, KerMsg=KerMsg>,
+        _: Self::T2B,
+        trader_id: Self::TraderID,
+        rng: &mut impl Rng,
+    ) {
+        let action = BrokerAction {
+            delay: NanoDuration(5),
+            content: BrokerActionKind::BrokerToTrader(
+                BasicBrokerToTrader {
+                    trader_id,
+                    exchange_id: "MOEX",
+                    event_dt: self.current_dt,
+                    content: BasicBrokerReply::ExchangeEventNotification(
+                        ExchangeEventNotification::ExchangeOpen
+                    ),
+                }
+            ),
+        };
+        message_receiver.push(
+            action_processor.process_action(action, &mut self.get_latency_generator(), rng)
+        )
+    }
+
+    fn process_exchange_reply<KerMsg: Ord>(
+        &mut self,
+        _: MessageReceiver<KerMsg>,
+        _: impl LatentActionProcessor<Self::Action, Self::ExchangeID, KerMsg=KerMsg>,
+        _: Self::E2B,
+        _: Self::ExchangeID,
+        _: &mut impl Rng,
+    ) {}
+
+    fn process_replay_request<KerMsg: Ord>(
+        &mut self,
+        _: MessageReceiver<KerMsg>,
+        _: impl LatentActionProcessor<Self::Action, Self::ExchangeID, KerMsg=KerMsg>,
+        _: Self::R2B,
+        _: &mut impl Rng,
+    ) {}
+
+    fn process_broker_message<KerMsg: Ord>(
+        &mut self,
+        _: MessageReceiver<KerMsg>,
+        _: impl LatentActionProcessor<Self::Action, Self::ExchangeID, KerMsg=KerMsg>,
+        message: Self::B2B,
+        broker_id: Self::BrokerID,
+        _: &mut impl Rng,
+    ) {
+        self.received.borrow_mut().push((self.name, broker_id, self.current_dt, message))
+    }
+
+    fn get_b2b_recipient(&self, message: &Self::B2B) -> Self::BrokerID {
+        match message {
+            BasicBrokerToItself::GiveUp(give_up) => give_up.clearing_broker_id,
+            message => panic!("Unexpected message: {message:?}")
+        }
+    }
+
+    fn upon_connection_to_exchange(&mut self, _: Self::ExchangeID) {}
+
+    fn register_trader(&mut self, _: Self::TraderID, _: impl IntoIterator<Item=Self::SubCfg>) {}
+}
+
+#[test]
+fn test_broker_to_broker_routing()
+{
+    let received = Rc::new(RefCell::new(Vec::new()));
+    let brokers = [1, 2].map(
+        |name| {
+            let broker = RelayBroker { name, current_dt: hms(0, 0, 0), received: received.clone() };
+            (broker, ["MOEX"])
+        }
+    );
+    let give_up = GiveUp {
+        clearing_broker_id: 2,
+        trader_id: 5,
+        account: None,
+        exchange_id: "MOEX",
+        traded_pair: pair(),
+        direction: Direction::Buy,
+        price: Price(100),
+        size: Lots(1),
+        executed_dt: hms(10, 0, 0),
+    };
+    let replay = TickReplay { current_dt: hms(0, 0, 0), ticks: vec![hms(10, 0, 1)].into_iter() };
+    KernelBuilder::new(
+        [Exchange::new("MOEX")],
+        brokers,
+        [(IdleTrader { name: 1, current_dt: hms(0, 0, 0), events: Default::default() }, [(1, [])])],
+        replay,
+        (hms(10, 0, 0), hms(10, 0, 2)),
+    )
+        .with_seed(0)
+        .with_auxiliary_agents(
+            [
+                Clock {
+                    current_dt: hms(0, 0, 0),
+                    wakeups: vec![
+                        AuxiliaryActionKind::AuxiliaryToBroker { broker_id: 1, b2b: give_up.into() }
+                    ],
+                }
+            ]
+        )
+        .build()
+        .run_simulation();
+
+    // Message is delivered to the recipient with the sender ID and without latency
+    let received = received.borrow();
+    assert_eq!(*received, [(2, 1, hms(10, 0, 0) + Duration::nanoseconds(2), give_up.into())])
+}
+
+/// Latency that grows by a nanosecond with each outgoing message.
+#[derive(Debug, Clone, Copy, Default)]
+struct GrowingLatency {
+    n_sent: u64,
+}
+
+impl LatencyGenerator for GrowingLatency {
+    type OuterID = u8;
+
+    fn outgoing_latency(&mut self, _: u8, _: DateTime, _: &mut impl Rng) -> NanoDuration {
+        self.n_sent += 1;
+        NanoDuration(self.n_sent)
+    }
+
+    fn incoming_latency(&mut self, _: u8, _: DateTime, _: &mut impl Rng) -> NanoDuration {
+        NanoDuration::ZERO
+    }
+}
+
+/// Trader that sends a request to the broker each time it wakes up.
+struct ChattyTrader {
+    current_dt: DateTime,
+    latency_generator: GrowingLatency,
+}
+
+impl TimeSync for ChattyTrader {
+    fn current_datetime_mut(&mut self) -> &mut DateTime {
+        &mut self.current_dt
+    }
+}
+
+impl Named<u8> for ChattyTrader {
+    fn get_name(&self) -> u8 {
+        1
+    }
+}
+
+impl Agent for ChattyTrader {
+    type Action = TraderAction<T2B, Tick>;
+}
+
+impl Latent for ChattyTrader {
+    type OuterID = u8;
+    type LatencyGenerator = GrowingLatency;
+
+    fn get_latency_generator(&self) -> Self::LatencyGenerator {
+        self.latency_generator
+    }
+
+    fn update_latency_generator(&mut self, latency_generator: Self::LatencyGenerator) {
+        self.latency_generator = latency_generator
+    }
+}
+
+impl Trader for ChattyTrader {
+    type TraderID = u8;
+    type BrokerID = u8;
+
+    type B2T = BasicBrokerToTrader<u8, &'static str, &'static str, SpotSettlement>;
+    type T2T = Tick;
+    type T2B = T2B;
+
+    fn wakeup<KerMsg: Ord>(
+        &mut self,
+        mut message_receiver: MessageReceiver<KerMsg>,
+        mut action_processor: impl LatentActionProcessor<Self::Action, Self::BrokerID, KerMsg=KerMsg>,
+        _: Self::T2T,
+        rng: &mut impl Rng,
+    ) {
+        let request = BasicTraderRequest::CancelLimitOrder(
+            LimitOrderCancelRequest {
+                traded_pair: pair(),
+                order_id: OrderID(0),
+            },
+            "MOEX",
+        );
+        let action = TraderAction {
+            delay: NanoDuration::ZERO,
+            content: TraderActionKind::TraderToBroker(
+                BasicTraderToBroker { broker_id: 1, account: None, content: request }
+            ),
+        };
+        let mut latency_generator = self.get_latency_generator();
+        message_receiver.push(
+            action_processor.process_action(action, &mut latency_generator, rng)
+        );
+        self.update_latency_generator(latency_generator)
+    }
+
+    fn process_broker_reply<KerMsg: Ord>(
+        &mut self,
+        _: MessageReceiver<KerMsg>,
+        _: impl LatentActionProcessor<Self::Action, Self::BrokerID, KerMsg=KerMsg>,
+        _: Self::B2T,
+        _: Self::BrokerID,
+        _: &mut impl Rng,
+    ) {}
+
+    fn upon_register_at_broker(&mut self, _: Self::BrokerID) {}
+}
+
+#[test]
+fn test_outgoing_latency_state()
+{
+    let buffer: SharedTraceBuffer<_, _, _, _> = Rc::new(RefCell::new(TraceBuffer::new(16)));
+    let replay = TickReplay { current_dt: hms(0, 0, 0), ticks: vec![hms(10, 0, 1)].into_iter() };
+    let trader = ChattyTrader { current_dt: hms(0, 0, 0), latency_generator: Default::default() };
+    let wakeup = || AuxiliaryActionKind::AuxiliaryToTrader { trader_id: 1, t2t: Tick };
+    KernelBuilder::new(
+        [Exchange::new("MOEX")],
+        [(Broker::new(1), ["MOEX"])],
+        [(trader, [(1, [])])],
+        replay,
+        (hms(10, 0, 0), hms(10, 0, 2)),
+    )
+        .with_seed(0)
+        .with_tracer(buffer.clone())
+        .with_auxiliary_agents(
+            [Clock { current_dt: hms(0, 0, 0), wakeups: vec![wakeup(), wakeup()] }]
+        )
+        .build()
+        .run_simulation();
+
+    // Each request is delayed more than the previous one
+    let buffer = buffer.borrow();
+    let delivered: Vec<_> = buffer.get_records()
+        .filter(|record| record.kind == "TraderToBroker")
+        .map(|record| record.datetime)
+        .collect();
+    let sent_dt = hms(10, 0, 0) + Duration::nanoseconds(1);
+    assert_eq!(
+        delivered,
+        [sent_dt + Duration::nanoseconds(1), sent_dt + Duration::nanoseconds(2)]
+    )
+}
+
+#[cfg(feature = "lookahead_guard")]
+/// Trader that requests the broker on every wakeup and records the delivered replies,
+/// comparing them with the datetime `peek_ahead` nanoseconds after the current one.
+struct GuardedTrader {
+    current_dt: DateTime,
+    peek_ahead: i64,
+    delivered: Rc<RefCell<Vec<(DateTime, DateTime)>>>,
+}
+
+#[cfg(feature = "lookahead_guard")]
+impl TimeSync for GuardedTrader {
+    fn current_datetime_mut(&mut self) -> &mut DateTime {
+        &mut self.current_dt
+    }
+}
+
+#[cfg(feature = "lookahead_guard")]
+impl Named<u8> for GuardedTrader {
+    fn get_name(&self) -> u8 {
+        1
+    }
+}
+
+#[cfg(feature = "lookahead_guard")]
+impl Agent for GuardedTrader {
+    type Action = TraderAction<T2B, Tick>;
+}
+
+#[cfg(feature = "lookahead_guard")]
+impl Latent for GuardedTrader {
+    type OuterID = u8;
+    type LatencyGenerator = ConstantLatency<u8, 0, 0>;
+
+    fn get_latency_generator(&self) -> Self::LatencyGenerator {
+        ConstantLatency::new()
+    }
+}
+
+#[cfg(feature = "lookahead_guard")]
+impl Trader for GuardedTrader {
+    type TraderID = u8;
+    type BrokerID = u8;
+
+    type B2T = BasicBrokerToTrader<u8, &'static str, &'static str, SpotSettlement>;
+    type T2T = Tick;
+    type T2B = T2B;
+
+    fn wakeup<KerMsg: Ord>(
+        &mut self,
+        mut message_receiver: MessageReceiver<KerMsg>,
+        mut action_processor: impl LatentActionProcessor<Self::Action, Self::BrokerID, KerMsg=KerMsg>,
+        _: Self::T2T,
+        rng: &mut impl Rng,
+    ) {
+        let request = BasicTraderRequest::CancelLimitOrder(
+            LimitOrderCancelRequest {
+                traded_pair: pair(),
+                order_id: OrderID(0),
+            },
+            "MOEX",
+        );
+        let action = TraderAction {
+            delay: NanoDuration::ZERO,
+            content: TraderActionKind::TraderToBroker(
+                BasicTraderToBroker { broker_id: 1, account: None, content: request }
+            ),
+        };
+        message_receiver.push(
+            action_processor.process_action(action, &mut self.get_latency_generator(), rng)
+        )
+    }
+
+    fn process_broker_reply<KerMsg: Ord>(
+        &mut self,
+        _: MessageReceiver<KerMsg>,
+        _: impl LatentActionProcessor<Self::Action, Self::BrokerID, KerMsg=KerMsg>,
+        _: Self::B2T,
+        _: Self::BrokerID,
+        _: &mut impl Rng,
+    ) {
+        unreachable!("Kernel should deliver the replies wrapped")
+    }
+
+    fn process_delivered_broker_reply<KerMsg: Ord>(
+        &mut self,
+        _: MessageReceiver<KerMsg>,
+        _: impl LatentActionProcessor<Self::Action, Self::BrokerID, KerMsg=KerMsg>,
+        reply: Delivered<Self::B2T>,
+        _: Self::BrokerID,
+        _: &mut impl Rng,
+    ) {
+        assert_eq!(reply.get().event_dt, reply.get_data_dt());
+        let _ = reply.cmp_dt(self.current_dt + Duration::nanoseconds(self.peek_ahead));
+        self.delivered.borrow_mut().push((reply.get_data_dt(), reply.get_delivered_dt()))
+    }
+
+    fn upon_register_at_broker(&mut self, _: Self::BrokerID) {}
+}
+
+#[cfg(feature = "lookahead_guard")]
+fn run_guarded_trader(peek_ahead: i64, policy: LookaheadPolicy) -> Vec<(DateTime, DateTime)>
+{
+    let delivered = Rc::new(RefCell::new(Vec::new()));
+    let trader = GuardedTrader {
+        current_dt: hms(0, 0, 0),
+        peek_ahead,
+        delivered: delivered.clone(),
+    };
+    let broker = RelayBroker { name: 1, current_dt: hms(0, 0, 0), received: Default::default() };
+    let replay = TickReplay { current_dt: hms(0, 0, 0), ticks: vec![hms(10, 0, 1)].into_iter() };
+    let wakeup = AuxiliaryActionKind::AuxiliaryToTrader { trader_id: 1, t2t: Tick };
+    KernelBuilder::new(
+        [Exchange::new("MOEX")],
+        [(broker, ["MOEX"])],
+        [(trader, [(1, [])])],
+        replay,
+        (hms(10, 0, 0), hms(10, 0, 2)),
+    )
+        .with_seed(0)
+        .with_lookahead_policy(policy)
+        .with_auxiliary_agents([Clock { current_dt: hms(0, 0, 0), wakeups: vec![wakeup] }])
+        .build()
+        .run_simulation();
+    delivered.take()
+}
+
+#[cfg(feature = "lookahead_guard")]
+#[test]
+fn test_lookahead_guard_wraps_delivered_replies()
+{
+    // Reply is stamped with the datetime of the event and the one of the delivery
+    let request_dt = hms(10, 0, 0) + Duration::nanoseconds(1);
+    assert_eq!(
+        run_guarded_trader(0, LookaheadPolicy::Panic),
+        [(request_dt, request_dt + Duration::nanoseconds(5))]
+    );
+    // Violations are only logged if requested
+    assert_eq!(run_guarded_trader(1, LookaheadPolicy::Log).len(), 1)
+}
+
+#[cfg(feature = "lookahead_guard")]
+#[test]
+#[should_panic(expected = "Lookahead bias detected")]
+fn test_lookahead_guard_detects_peeking_ahead()
+{
+    run_guarded_trader(1, LookaheadPolicy::Panic);
+}
@@ -0,0 +1,395 @@
+use {
+    crate::types::{DateTime, Id},
+    alloc::{collections::VecDeque, format, rc::Rc, string::String},
+    core::{
+        cell::RefCell,
+        fmt::{Debug, Display, Formatter},
+    },
+};
+#[cfg(feature = "std")]
+use std::io::Write;
+#[cfg(all(feature = "std", not(feature = "serde")))]
+use crate::utils::metadata::escape_json;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+/// Agent that sends or receives the message traced by the [`Tracer`].
+pub enum TraceEndpoint<ExchangeID: Id, BrokerID: Id, TraderID: Id, AuxiliaryID: Id> {
+    /// [`Replay`](crate::interface::replay::Replay).
+    Replay,
+    /// [`Exchange`](crate::interface::exchange::Exchange) with the given ID.
+    Exchange(ExchangeID),
+    /// [`Broker`](crate::interface::broker::Broker) with the given ID.
+    Broker(BrokerID),
+    /// [`Trader`](crate::interface::trader::Trader) with the given ID.
+    Trader(TraderID),
+    /// [`Auxiliary`](crate::interface::auxiliary::Auxiliary) agent with the given ID.
+    Auxiliary(AuxiliaryID),
+}
+
+impl<ExchangeID: Id, BrokerID: Id, TraderID: Id, AuxiliaryID: Id> Display
+for TraceEndpoint<ExchangeID, BrokerID, TraderID, AuxiliaryID>
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Replay => write!(f, "Replay"),
+            Self::Exchange(exchange_id) => write!(f, "Exchange({exchange_id})"),
+            Self::Broker(broker_id) => write!(f, "Broker({broker_id})"),
+            Self::Trader(trader_id) => write!(f, "Trader({trader_id})"),
+            Self::Auxiliary(auxiliary_id) => write!(f, "Auxiliary({auxiliary_id})")
+        }
+    }
+}
+
+/// Kernel message passed to the [`Tracer`] before it is delivered.
+pub struct TraceRecord<'a, ExchangeID: Id, BrokerID: Id, TraderID: Id, AuxiliaryID: Id> {
+    /// Datetime the message is delivered at.
+    pub datetime: DateTime,
+    /// Kind of the message, e.g. `BrokerToExchange` or `TraderWakeUp`.
+    pub kind: &'static str,
+    /// Sender of the message.
+    pub source: TraceEndpoint<ExchangeID, BrokerID, TraderID, AuxiliaryID>,
+    /// Recipient of the message. Same as the `source` for the wakeups.
+    pub destination: TraceEndpoint<ExchangeID, BrokerID, TraderID, AuxiliaryID>,
+    /// Message itself.
+    pub message: &'a dyn Debug,
+}
+
+/// Receives every message the [`Kernel`](crate::kernel::Kernel) delivers,
+/// in the order of the delivery, e.g. to audit the simulation run
+/// without instrumenting the agents.
+/// Is set by the [`KernelBuilder::with_tracer`](crate::kernel::KernelBuilder::with_tracer).
+pub trait Tracer<ExchangeID: Id, BrokerID: Id, TraderID: Id, AuxiliaryID: Id> {
+    /// Traces the message.
+    ///
+    /// # Arguments
+    ///
+    /// * `record` — Message along with its datetime, sender and recipient.
+    fn trace(&mut self, record: TraceRecord<'_, ExchangeID, BrokerID, TraderID, AuxiliaryID>);
+}
+
+/// Shared [`Tracer`] remains accessible after it is passed to the
+/// [`KernelBuilder`](crate::kernel::KernelBuilder), e.g. to inspect the [`TraceBuffer`].
+impl<ExchangeID, BrokerID, TraderID, AuxiliaryID, T>
+Tracer<ExchangeID, BrokerID, TraderID, AuxiliaryID>
+for Rc<RefCell<T>>
+    where ExchangeID: Id,
+          BrokerID: Id,
+          TraderID: Id,
+          AuxiliaryID: Id,
+          T: Tracer<ExchangeID, BrokerID, TraderID, AuxiliaryID>
+{
+    fn trace(&mut self, record: TraceRecord<'_, ExchangeID, BrokerID, TraderID, AuxiliaryID>) {
+        self.borrow_mut().trace(record)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+/// State of the [`KernelBuilder`](crate::kernel::KernelBuilder) with no [`Tracer`] set.
+pub struct NoTracer;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+/// State of the [`KernelBuilder`](crate::kernel::KernelBuilder) with the [`Tracer`] set.
+/// Is checked to trace the messages of the agents when the kernel is built,
+/// so the tracer may be set before the auxiliary agents.
+pub struct WithTracer<X>(pub(crate) X);
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+/// [`TraceRecord`] kept by the [`TraceBuffer`], with the message formatted by its `Debug`.
+pub struct TracedMessage<ExchangeID: Id, BrokerID: Id, TraderID: Id, AuxiliaryID: Id> {
+    /// Datetime the message has been delivered at.
+    pub datetime: DateTime,
+    /// Kind of the message.
+    pub kind: &'static str,
+    /// Sender of the message.
+    pub source: TraceEndpoint<ExchangeID, BrokerID, TraderID, AuxiliaryID>,
+    /// Recipient of the message.
+    pub destination: TraceEndpoint<ExchangeID, BrokerID, TraderID, AuxiliaryID>,
+    /// Formatted message.
+    pub message: String,
+}
+
+/// [`TraceBuffer`] shared between the [`Kernel`](crate::kernel::Kernel)
+/// and the code inspecting it after the run.
+pub type SharedTraceBuffer<ExchangeID, BrokerID, TraderID, AuxiliaryID> = Rc<
+    RefCell<TraceBuffer<ExchangeID, BrokerID, TraderID, AuxiliaryID>>
+>;
+
+/// In-memory [`Tracer`] keeping the given number of the most recent messages,
+/// e.g. to find out what has led to the failure at the end of the run.
+pub struct TraceBuffer<ExchangeID: Id, BrokerID: Id, TraderID: Id, AuxiliaryID: Id> {
+    capacity: usize,
+    records: VecDeque<TracedMessage<ExchangeID, BrokerID, TraderID, AuxiliaryID>>,
+    num_traced: usize,
+}
+
+impl<ExchangeID: Id, BrokerID: Id, TraderID: Id, AuxiliaryID: Id>
+TraceBuffer<ExchangeID, BrokerID, TraderID, AuxiliaryID>
+{
+    /// Creates a new instance of the `TraceBuffer`.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` — Maximum number of the messages to keep. Should be positive.
+    pub fn new(capacity: usize) -> Self {
+        if capacity == 0 {
+            panic!("TraceBuffer capacity should be positive")
+        }
+        Self { capacity, records: VecDeque::with_capacity(capacity), num_traced: 0 }
+    }
+
+    /// Returns the kept messages, from the oldest to the most recent one.
+    pub fn get_records(
+        &self,
+    ) -> impl Iterator<Item=&TracedMessage<ExchangeID, BrokerID, TraderID, AuxiliaryID>> {
+        self.records.iter()
+    }
+
+    /// Returns the number of the messages traced so far, including the evicted ones.
+    pub fn get_num_traced(&self) -> usize {
+        self.num_traced
+    }
+}
+
+impl<ExchangeID: Id, BrokerID: Id, TraderID: Id, AuxiliaryID: Id>
+Tracer<ExchangeID, BrokerID, TraderID, AuxiliaryID>
+for TraceBuffer<ExchangeID, BrokerID, TraderID, AuxiliaryID>
+{
+    fn trace(&mut self, record: TraceRecord<'_, ExchangeID, BrokerID, TraderID, AuxiliaryID>) {
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(
+            TracedMessage {
+                datetime: record.datetime,
+                kind: record.kind,
+                source: record.source,
+                destination: record.destination,
+                message: format!("{:?}", record.message),
+            }
+        );
+        self.num_traced += 1
+    }
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+/// Output format of the [`WriterTracer`].
+pub enum TraceFormat {
+    /// Comma-separated values with the `DATETIME,KIND,SOURCE,DESTINATION,MESSAGE` header,
+    /// readable by the [`TraceDiffer`](crate::utils::trace_diff::TraceDiffer).
+    Csv,
+    /// JSON object per line with the `datetime`, `kind`, `source`, `destination`
+    /// and `message` keys.
+    JsonLines,
+}
+
+#[cfg(feature = "std")]
+/// [`Tracer`] writing every message as a line to the writer, e.g. to the file.
+/// Messages are formatted by their `Debug`.
+///
+/// # Examples
+///
+/// ```
+/// use trading_backtester::{
+///     kernel::tracer::{TraceEndpoint, TraceFormat, TraceRecord, Tracer, WriterTracer},
+///     types::Date,
+/// };
+///
+/// let mut tracer = WriterTracer::new(Vec::new(), TraceFormat::Csv);
+/// Tracer::<u8, u8, u8, u8>::trace(
+///     &mut tracer,
+///     TraceRecord {
+///         datetime: Date::from_ymd_opt(2022, 1, 3).unwrap().and_hms_opt(10, 0, 0).unwrap(),
+///         kind: "BrokerToExchange",
+///         source: TraceEndpoint::Broker(1),
+///         destination: TraceEndpoint::Exchange(2),
+///         message: &("Cancel", 3),
+///     },
+/// );
+/// assert_eq!(
+///     String::from_utf8(tracer.into_inner()).unwrap(),
+///     "DATETIME,KIND,SOURCE,DESTINATION,MESSAGE\n\
+///     2022-01-03 10:00:00,BrokerToExchange,Broker(1),Exchange(2),\"(\"\"Cancel\"\", 3)\"\n"
+/// );
+/// ```
+pub struct WriterTracer<W: Write> {
+    writer: W,
+    format: TraceFormat,
+}
+
+#[cfg(feature = "std")]
+impl<W: Write> WriterTracer<W> {
+    /// Creates a new instance of the `WriterTracer`. Writes the header if the format has any.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` — Writer to write the lines to, e.g. the buffered file.
+    /// * `format` — Output format.
+    pub fn new(mut writer: W, format: TraceFormat) -> Self {
+        if format == TraceFormat::Csv {
+            writeln!(writer, "DATETIME,KIND,SOURCE,DESTINATION,MESSAGE")
+                .unwrap_or_else(|err| panic!("Cannot write the trace. Error: {err}"))
+        }
+        Self { writer, format }
+    }
+
+    /// Returns the writer, e.g. to flush it after the run.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W, ExchangeID, BrokerID, TraderID, AuxiliaryID>
+Tracer<ExchangeID, BrokerID, TraderID, AuxiliaryID>
+for WriterTracer<W>
+    where W: Write,
+          ExchangeID: Id,
+          BrokerID: Id,
+          TraderID: Id,
+          AuxiliaryID: Id
+{
+    fn trace(&mut self, record: TraceRecord<'_, ExchangeID, BrokerID, TraderID, AuxiliaryID>) {
+        let TraceRecord { datetime, kind, source, destination, message } = record;
+        match self.format {
+            TraceFormat::Csv => writeln!(
+                self.writer,
+                "{datetime},{kind},{},{},{}",
+                escape_csv(&source.to_string()),
+                escape_csv(&destination.to_string()),
+                escape_csv(&format!("{message:?}")),
+            ),
+            #[cfg(feature = "serde")]
+            TraceFormat::JsonLines => {
+                let line = JsonTraceLine {
+                    datetime: &datetime.to_string(),
+                    kind,
+                    source: &source.to_string(),
+                    destination: &destination.to_string(),
+                    message: &format!("{message:?}"),
+                };
+                serde_json::to_writer(&mut self.writer, &line)
+                    .map_err(std::io::Error::from)
+                    .and_then(|()| writeln!(self.writer))
+            }
+            #[cfg(not(feature = "serde"))]
+            TraceFormat::JsonLines => writeln!(
+                self.writer,
+                "{{\"datetime\":\"{datetime}\",\"kind\":\"{kind}\",\"source\":\"{}\",\
+                \"destination\":\"{}\",\"message\":\"{}\"}}",
+                escape_json(&source.to_string()),
+                escape_json(&destination.to_string()),
+                escape_json(&format!("{message:?}")),
+            )
+        }.unwrap_or_else(|err| panic!("Cannot write the trace. Error: {err}"))
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+/// Line of the [`TraceFormat::JsonLines`] output.
+struct JsonTraceLine<'a> {
+    datetime: &'a str,
+    kind: &'a str,
+    source: &'a str,
+    destination: &'a str,
+    message: &'a str,
+}
+
+#[cfg(feature = "std")]
+/// Quotes the field if it contains the separators or the quotes.
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.into()
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{test_utils::hms, types::NeverType},
+    };
+
+    type Endpoint = TraceEndpoint<&'static str, u8, u8, NeverType<()>>;
+
+    /// Message formatted by its `Debug` as is.
+    struct Raw(&'static str);
+
+    impl Debug for Raw {
+        fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+            f.write_str(self.0)
+        }
+    }
+
+    fn trace(tracer: &mut impl Tracer<&'static str, u8, u8, NeverType<()>>,
+             hour: u32,
+             source: Endpoint,
+             message: &'static str)
+    {
+        tracer.trace(
+            TraceRecord {
+                datetime: hms(hour, 0, 0),
+                kind: "ExchangeToBroker",
+                source,
+                destination: TraceEndpoint::Broker(1),
+                message: &Raw(message),
+            }
+        )
+    }
+
+    fn write_trace(format: TraceFormat, source: Endpoint, message: &'static str) -> String {
+        let mut tracer = WriterTracer::new(Vec::new(), format);
+        trace(&mut tracer, 10, source, message);
+        String::from_utf8(tracer.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn test_csv_escaping()
+    {
+        assert_eq!(escape_csv("plain text"), "plain text");
+        assert_eq!(escape_csv("a,b"), "\"a,b\"");
+        assert_eq!(escape_csv("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(escape_csv("line\nbreak"), "\"line\nbreak\"");
+        assert_eq!(escape_csv("carriage\rreturn"), "\"carriage\rreturn\"");
+        assert_eq!(
+            write_trace(TraceFormat::Csv, TraceEndpoint::Exchange("A,B"), "Fill { \"id\": 1 }\n"),
+            "DATETIME,KIND,SOURCE,DESTINATION,MESSAGE\n\
+            2022-01-03 10:00:00,ExchangeToBroker,\"Exchange(A,B)\",Broker(1),\
+            \"Fill { \"\"id\"\": 1 }\n\"\n"
+        )
+    }
+
+    #[test]
+    fn test_json_escaping()
+    {
+        assert_eq!(
+            write_trace(
+                TraceFormat::JsonLines,
+                TraceEndpoint::Exchange("\"MOEX\""),
+                "Fill { path: \"C:\\data\" }\r\n\t\u{1}",
+            ),
+            "{\"datetime\":\"2022-01-03 10:00:00\",\"kind\":\"ExchangeToBroker\",\
+            \"source\":\"Exchange(\\\"MOEX\\\")\",\"destination\":\"Broker(1)\",\
+            \"message\":\"Fill { path: \\\"C:\\\\data\\\" }\\r\\n\\t\\u0001\"}\n"
+        )
+    }
+
+    #[test]
+    fn test_trace_buffer_wrap_around()
+    {
+        let mut buffer = TraceBuffer::new(2);
+        for hour in 10..13 {
+            trace(&mut buffer, hour, TraceEndpoint::Replay, "Tick")
+        }
+        assert_eq!(buffer.get_num_traced(), 3);
+        let hours: Vec<_> = buffer.get_records()
+            .map(|record| record.datetime.format("%H").to_string())
+            .collect();
+        assert_eq!(hours, ["11", "12"]);
+        assert!(buffer.get_records().all(|record| record.message == "Tick"))
+    }
+}
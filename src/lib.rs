@@ -35,10 +35,37 @@
 //!
 //! The following features are available for enabling. Each of them provides access to:
 //!
+//! * __`std`__ (enabled by default)
+//!
+//!   Support of the standard library. Required by all other features except `alloc`
+//!   as well as by the I/O utilities (`utils::metadata` and `utils::trace_diff`).
+//!
+//! * __`alloc`__
+//!
+//!   Build of the discrete-event core (the `interface`, `kernel`, `types` and `utils::queue`
+//!   modules) without the standard library, using only `core` and `alloc`.
+//!   Should be enabled together with `default-features = false`.
+//!
+//! * __`cli`__
+//!
+//!   The `backtester-cli` binary running, validating and sweeping the simulations
+//!   configured by the YAML-configs.
+//!
 //! * __`concrete`__
 //!
 //!   Concrete examples of entities that implement traits from the `interface` module.
 //!
+//! * __`deterministic`__
+//!
+//!   Determinism mode of the simulation. The kernel seed becomes mandatory,
+//!   since the kernel random number generator is no longer seeded from the OS entropy,
+//!   and the hash-based collections of the crate use the fixed hasher keys,
+//!   so that the runs with the same seed and inputs are byte-identical.
+//!   Types of the collections stay the same, since their hasher builder is always
+//!   the [`HashState`](utils::collections::HashState).
+//!   Calls to the wall clock and to the OS entropy listed in the `clippy.toml`
+//!   are denied by `clippy` throughout the crate.
+//!
 //! * __`enum_def`__
 //!
 //!   The macro that generates an `enum` that can contain each
@@ -49,10 +76,41 @@
 //!
 //!   Derive macros for statically dispatched trait objects from the `interface` module.
 //!   Convenient to use with the `enum_def`.
+//!   Also provides the `Tunable` derive macro exposing the parameters of the strategy configs.
+//!
+//! * __`lookahead_guard`__
+//!
+//!   Detection of the lookahead bias in the strategies (`kernel::lookahead`).
+//!   The `Kernel` wraps every broker reply it delivers to the traders,
+//!   reporting the reads of the data before it has happened
+//!   and its comparisons with the timestamps from the future.
+//!
+//! * __`mmap`__
+//!
+//!   Memory-mapped binary cache of the parsed OneTick history
+//!   (`concrete::input::one_tick::cache`), letting the repeated runs skip the csv parsing.
 //!
 //! * __`multithread`__
 //!
 //!   Utilities for running backtesters in multiple threads.
+//!
+//! * __`optimizer`__
+//!
+//!   Constrained portfolio optimization computing the rebalancing trades of the traders
+//!   (`concrete::trader::optimizer`).
+//!
+//! * __`serde`__
+//!
+//!   `Serialize` and `Deserialize` implementations for the message protocol
+//!   of the `concrete` module, as well as for the orders, trades and other types it contains.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(feature = "deterministic", deny(clippy::disallowed_methods))]
+
+#[cfg(not(any(feature = "std", feature = "alloc")))]
+compile_error!("Either the `std` or the `alloc` feature should be enabled");
+
+extern crate alloc;
 
 #[cfg(feature = "concrete")]
 /// Concrete examples of entities that implement traits from the [`interface`] module.
@@ -74,11 +132,17 @@ pub mod types;
 /// Other auxiliary utilities.
 pub mod utils;
 
+#[cfg(test)]
+/// Fixtures shared by the unit tests.
+pub(crate) mod test_utils;
+
 /// The Rust Prelude
 pub mod prelude {
     pub use crate::{
-        interface::{broker::*, exchange::*, latency::*, message::*, replay::*, trader::*},
-        kernel::{Kernel, KernelBuilder, LatentActionProcessor},
+        interface::{
+            broker::*, exchange::*, latency::*, message::*, replay::*, trader::*, tunable::*,
+        },
+        kernel::{Kernel, KernelAgents, KernelBuilder, LatentActionProcessor},
         types::*,
         utils::{
             chrono,
@@ -94,11 +158,12 @@ pub mod prelude {
         input::{
             config::{from_structs::*, from_yaml::*},
             one_tick::OneTickTradedPairReader,
+            synthetic_book::SyntheticBookConfig,
         },
         latency as latency_examples,
         message_protocol::{
             broker::{reply as broker_reply, request as broker_request},
-            exchange::reply as exchange_reply,
+            exchange::{reply as exchange_reply, wakeup as exchange_wakeup},
             replay::request as replay_request,
             trader::request as trader_request,
         },
@@ -106,9 +171,13 @@ pub mod prelude {
             LimitOrderCancelRequest,
             LimitOrderPlacingRequest,
             MarketOrderPlacingRequest,
+            StopLimitOrderPlacingRequest,
+            StopOrderPlacingRequest,
+            TimeInForce,
         },
         order_book::{LimitOrder, OrderBook, OrderBookEvent, OrderBookEventKind},
         replay as replay_examples,
+        simulation::{SimulationBuilder, SimulationSummary},
         traded_pair::{
             Asset,
             Base,
@@ -120,13 +189,13 @@ pub mod prelude {
             TradedPair,
         },
         trader as trader_examples,
-        trader::subscriptions::{SubscriptionConfig, SubscriptionList},
+        trader::subscriptions::{SubscriptionConfig, SubscriptionList, SubscriptionRequest},
         types as misc_types,
     };
     #[cfg(feature = "enum_def")]
     pub use crate::enum_def;
     #[cfg(feature = "multithread")]
-    pub use crate::parallel::{ParallelBacktester, ThreadConfig};
+    pub use crate::parallel::{ParallelBacktester, RngKind, RunFailure, RunOutcome, ThreadConfig};
     #[cfg(feature = "derive")]
     pub use crate::utils::derive;
     #[cfg(feature = "derive_more")]
@@ -140,7 +209,6 @@ mod tests {
         broker_examples::BasicBroker,
         crate::prelude::*,
         exchange_example::BasicExchange,
-        misc_types::TickSize,
         rand::{Rng, rngs::StdRng},
         replay_examples::{GetNextObSnapshotDelay, OneTickReplay},
         settlement_examples::SpotSettlement,
@@ -256,6 +324,134 @@ mod tests {
             .run_simulation()
     }
 
+    #[test]
+    fn test_parse_traders_yaml()
+    {
+        type Trader = SpreadWriter<u8, BrokerName, ExchangeName, SymbolName, SpotSettlement>;
+
+        let test_files = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+        let trader_configs = parse_traders_yaml::<u8>(test_files.join("example_01.yml"));
+        assert_eq!(trader_configs.len(), 1);
+
+        let output_dir = test_files.join("example_01");
+        let registry = TraderRegistry::<u8, Trader>::new().with_factory(
+            "SpreadWriter",
+            move |name, params: &yaml_rust::Yaml| SpreadWriter::new(
+                name,
+                params["price_step"].as_f64().unwrap(),
+                output_dir.join(params["file"].as_str().unwrap()),
+            ),
+        );
+        let traders: Vec<_> = trader_configs.iter()
+            .map(|config| registry.create_trader(config))
+            .collect();
+        assert_eq!(traders.len(), 1)
+    }
+
+    #[test]
+    fn test_parse_traders_yaml_with_overrides()
+    {
+        let test_files = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+        let overrides = [
+            "Traders.0.params.price_step=0.001".parse().unwrap(),
+            "Traders.0.tags=[cohort=B]".parse().unwrap(),
+        ];
+        let trader_configs = parse_traders_yaml_with_overrides::<u8>(
+            test_files.join("example_01.yml"),
+            &overrides,
+        );
+        assert_eq!(trader_configs[0].params["price_step"].as_f64(), Some(0.001));
+        assert_eq!(trader_configs[0].params["file"].as_str(), Some("simulated_spread_registry.csv"));
+        assert_eq!(trader_configs[0].tags, ["cohort=B"])
+    }
+
+    #[test]
+    fn test_parse_broker_yaml()
+    {
+        let test_files = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+        let broker_config = parse_broker_yaml::<u8>(test_files.join("example_01.yml")).unwrap();
+        assert_eq!(broker_config.name, 0);
+        assert_eq!(broker_config.params["ob_snapshot_period"].as_f64(), Some(1.0));
+
+        let overrides = ["Broker.name=7".parse().unwrap()];
+        let broker_config = parse_broker_yaml_with_overrides::<u8>(
+            test_files.join("example_01.yml"),
+            &overrides,
+        );
+        assert_eq!(broker_config.unwrap().name, 7);
+        assert!(parse_broker_yaml::<u8>(test_files.join("example_03.yml")).is_none())
+    }
+
+    #[test]
+    fn test_parse_yaml_preserve_order_ids()
+    {
+        let test_files = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+        let overrides = ["Traded Pairs.0.preserve_order_ids=true".parse().unwrap()];
+        let (_, replay_config, _, _) = parse_yaml_with_overrides::<_, SymbolName, _, _, _>(
+            test_files.join("example_01.yml"),
+            &overrides,
+            SpotBaseTradedPairParser,
+            DelayScheduler,
+        );
+        assert!(replay_config.preserves_order_ids(ExchangeName::MOEX));
+        assert!(!replay_config.preserves_order_ids(ExchangeName::NYSE))
+    }
+
+    #[test]
+    fn test_parse_yaml_synthetic_book()
+    {
+        let test_files = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+        let (_, replay_config, _, _) = parse_yaml::<ExchangeName, SymbolName, _, _, _>(
+            test_files.join("example_03.yml"),
+            SpotBaseTradedPairParser,
+            DelayScheduler,
+        );
+        assert_eq!(
+            replay_config.traded_pair_configs[0].synthetic_book,
+            Some(
+                SyntheticBookConfig {
+                    num_levels: 5,
+                    mean_order_size: 10.0,
+                    mean_lifetime: NanoDuration(60_000_000_000),
+                    seed: 42,
+                }
+            )
+        )
+    }
+
+    #[test]
+    fn test_simulation_builder()
+    {
+        type Trader = SpreadWriter<u8, BrokerName, ExchangeName, SymbolName, SpotSettlement>;
+
+        let test_files = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+        let output_file = test_files.join("example_01").join("simulated_spread_builder.csv");
+        let sidecar_file = output_file.clone();
+
+        let summary = SimulationBuilder::<_, _, _, SymbolName, _, _, Trader>::from_yaml(
+            test_files.join("example_01.yml"),
+            SpotBaseTradedPairParser,
+            DelayScheduler,
+            BrokerName::Broker1,
+        )
+            .with_factory(
+                "SpreadWriter",
+                move |name, params: &yaml_rust::Yaml| SpreadWriter::new(
+                    name,
+                    params["price_step"].as_f64().unwrap(),
+                    &output_file,
+                ),
+            )
+            .with_seed(3344)
+            .with_sink(move |summary| summary.metadata.write_sidecar(sidecar_file))
+            .run();
+
+        assert_eq!(summary.traders.len(), 1);
+        assert_eq!(summary.exchanges.len(), 2);
+        assert_eq!(summary.metadata.seed, Some(3344));
+        assert!(summary.metadata.config_hash.is_some())
+    }
+
     #[test]
     fn test_parse_yaml_2()
     {
@@ -308,6 +504,8 @@ mod tests {
     #[test]
     fn test_parse_yaml_in_parallel()
     {
+        use {crate::kernel::LatencyAdjustment, misc_types::TickSize};
+
         let usd_rub = TradedPair {
             quoted_asset: Base::new(SymbolName::USD).into(),
             settlement_asset: Base::new(SymbolName::RUB).into(),
@@ -377,25 +575,37 @@ mod tests {
             BrokerName, ExchangeName, SymbolName, DelayScheduler, SpotSettlement
         >;
 
-        ParallelBacktester::new(
+        let outcomes = ParallelBacktester::new(
             per_thread_configs,
             (start_dt, end_dt),
         )
             .run_simulation::<Trader, Broker, Exchange, Replay>();
+        for outcome in outcomes {
+            assert_eq!(outcome.result, Ok(()), "Run {} has failed", outcome.config_index)
+        }
 
         let per_thread_configs = [
             first_thread_config,
             second_thread_config,
             second_thread_config
+                .with_rng_kind(RngKind::Small)
+                .with_latency_adjustment(
+                    LatencyAdjustment { scale: 2.0, extra: NanoDuration(1_000) }
+                )
         ];
 
-        ParallelBacktester::new(
+        let outcomes = ParallelBacktester::new(
             per_thread_configs,
             (start_dt, end_dt),
         )
             .with_rng::<StdRng>()
             .with_num_threads(2)
-            .run_simulation::<Trader, Broker, Exchange, Replay>()
+            .with_max_retries(1)
+            .run_simulation::<Trader, Broker, Exchange, Replay>();
+        for outcome in outcomes {
+            assert_eq!(outcome.result, Ok(()), "Run {} has failed", outcome.config_index);
+            assert_eq!(outcome.attempts, 1)
+        }
     }
 
     #[cfg(feature = "derive")]
@@ -404,7 +614,7 @@ mod tests {
         use {
             broker_examples::{BasicBroker, BasicVoidBroker},
             crate::prelude::*,
-            derive::{Broker, Exchange, GetSettlementLag, LatencyGenerator, Replay, Trader},
+            derive::{Broker, Exchange, GetSettlementLag, LatencyGenerator, Replay, Trader, Tunable},
             exchange_example::{BasicExchange, BasicVoidExchange},
             latency_examples::ConstantLatency,
             rand::Rng,
@@ -528,5 +738,38 @@ mod tests {
             Var1(VoidSettlement),
             Var2(SpotSettlement),
         }
+
+        #[derive(Tunable, Default)]
+        struct StrategyConfig {
+            #[tunable(min = 0.001, max = 0.1)]
+            spread: f64,
+            #[tunable(min = 1)]
+            depth: u32,
+            #[tunable(choices("fast", "slow"))]
+            mode: String,
+            name: String,
+        }
+
+        #[test]
+        fn test_tunable() {
+            let specs = StrategyConfig::get_param_specs();
+            assert_eq!(
+                specs.iter().map(|spec| spec.name).collect::<Vec<_>>(),
+                ["spread", "depth", "mode"]
+            );
+            assert_eq!(specs[1].range, ParamRange::Integer { min: Some(1), max: None });
+
+            let mut config = StrategyConfig::default();
+            config.set_param("spread", ParamValue::Real(0.01)).unwrap();
+            config.set_param("depth", ParamValue::Integer(5)).unwrap();
+            config.set_param("mode", ParamValue::String("slow".into())).unwrap();
+            assert_eq!(config.get_param("spread"), Some(ParamValue::Real(0.01)));
+            assert_eq!(config.depth, 5);
+            assert_eq!(config.mode, "slow");
+            assert!(config.set_param("depth", ParamValue::Integer(0)).is_err());
+            assert!(config.set_param("mode", ParamValue::Real(1.0)).is_err());
+            assert!(config.set_param("name", ParamValue::String("x".into())).is_err());
+            assert_eq!(specs[0].range.grid(3).map(|grid| grid.len()), Some(3))
+        }
     }
 }
\ No newline at end of file
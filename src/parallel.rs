@@ -1,20 +1,38 @@
 use {
     crate::{
         interface::{broker::Broker, exchange::Exchange, replay::Replay, trader::Trader},
-        kernel::KernelBuilder,
+        kernel::{KernelBuilder, LatencyAdjustment, LimitViolation, ResourceLimits},
         types::{DateTime, Id},
     },
-    rand::{Rng, rngs::StdRng, SeedableRng},
+    rand::{Rng, rngs::{SmallRng, StdRng}, SeedableRng},
     rayon::{iter::{IntoParallelIterator, ParallelIterator}, ThreadPoolBuilder},
-    std::marker::PhantomData,
+    std::{any::Any, marker::PhantomData, panic::{self, AssertUnwindSafe}},
 };
 
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Hash)]
+/// Random number generator of the single run of the [`ParallelBacktester`].
+pub enum RngKind {
+    #[default]
+    /// RNG of the [`ParallelBacktester`], i.e. [`StdRng`] unless it is set by the
+    /// [`with_rng`](ParallelBacktester::with_rng).
+    Inherited,
+    /// [`StdRng`].
+    Std,
+    /// [`SmallRng`]. Faster than the [`StdRng`], but not portable across the platforms
+    /// and the versions of the `rand` crate.
+    Small,
+}
+
 #[derive(Clone, Copy)]
 /// Initializer struct that contain thread-unique information.
 /// Here it is the RNG seed and the initializer configs for building possibly thread-unique
-/// entities.
+/// entities, as well as the RNG kind and the latency adjustment of the thread,
+/// so that the heterogeneous experiments, e.g. the latency sensitivity analysis,
+/// could run in one batch.
 pub struct ThreadConfig<ReplayConfig, ExchangeConfigs, BrokerConfigs, TraderConfigs> {
     rng_seed: u64,
+    rng_kind: RngKind,
+    latency_adjustment: LatencyAdjustment,
     replay_config: ReplayConfig,
     exchange_configs: ExchangeConfigs,
     broker_configs: BrokerConfigs,
@@ -43,12 +61,65 @@ ThreadConfig<ReplayConfig, ExchangeConfigs, BrokerConfigs, TraderConfigs>
     {
         Self {
             rng_seed,
+            rng_kind: Default::default(),
+            latency_adjustment: Default::default(),
             replay_config,
             exchange_configs,
             broker_configs,
             trader_configs,
         }
     }
+
+    #[inline]
+    /// Sets the random number generator of the thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `rng_kind` — RNG kind.
+    pub fn with_rng_kind(mut self, rng_kind: RngKind) -> Self {
+        self.rng_kind = rng_kind;
+        self
+    }
+
+    #[inline]
+    /// Sets the adjustment applied to every latent delay sampled by the agents of the thread.
+    /// See [`KernelBuilder::with_latency_adjustment`] for details.
+    ///
+    /// # Arguments
+    ///
+    /// * `latency_adjustment` — Scale and extra delay of the latent delays.
+    pub fn with_latency_adjustment(mut self, latency_adjustment: LatencyAdjustment) -> Self {
+        self.latency_adjustment = latency_adjustment;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[must_use]
+/// Reason the simulation run of the [`ParallelBacktester`] has failed.
+pub enum RunFailure {
+    /// Run has exceeded the resource limits and has been stopped.
+    LimitExceeded(LimitViolation),
+    /// Run has panicked. Reported only if the retry policy is set
+    /// by the [`with_max_retries`](ParallelBacktester::with_max_retries).
+    Panicked {
+        /// Panic message.
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[must_use]
+/// Result of the single simulation run of the [`ParallelBacktester`].
+pub struct RunOutcome {
+    /// Index of the [`ThreadConfig`] of the run.
+    pub config_index: usize,
+    /// RNG seed of the run.
+    pub rng_seed: u64,
+    /// Number of the attempts made, including the retries.
+    pub attempts: usize,
+    /// `Err` with the failure of the last attempt if all the attempts have failed.
+    pub result: Result<(), RunFailure>,
 }
 
 /// Parallels simultaneous runs of multiple [`Kernels`](crate::kernel::Kernel).
@@ -58,6 +129,8 @@ pub struct ParallelBacktester<PerThreadConfs, RNG>
     date_range: (DateTime, DateTime),
 
     num_threads: usize,
+    limits: ResourceLimits,
+    max_retries: Option<usize>,
     phantom: PhantomData<RNG>,
 }
 
@@ -79,6 +152,8 @@ impl<T> ParallelBacktester<T, StdRng>
             per_thread_configs,
             date_range,
             num_threads: 0,
+            limits: Default::default(),
+            max_retries: None,
             phantom: Default::default(),
         }
     }
@@ -90,12 +165,16 @@ impl<T> ParallelBacktester<T, StdRng>
             per_thread_configs,
             date_range,
             num_threads,
+            limits,
+            max_retries,
             ..
         } = self;
         ParallelBacktester {
             per_thread_configs,
             date_range,
             num_threads,
+            limits,
+            max_retries,
             phantom: Default::default(),
         }
     }
@@ -116,6 +195,39 @@ ParallelBacktester<PerThreadConfigs, RNG>
         self.num_threads = num_threads;
         self
     }
+
+    #[inline]
+    /// Sets the resource limits of every run, so that the pathological configs
+    /// do not hang or exhaust the memory of the whole batch.
+    /// Runs exceeding the limits are stopped and reported as failed,
+    /// whereas the other runs keep going.
+    ///
+    /// # Arguments
+    ///
+    /// * `limits` — Resource limits of the single run.
+    pub fn with_resource_limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    #[inline]
+    /// Sets the number of times the failed run is retried with the same RNG seed
+    /// before it is reported as failed. Retries only help if the failure depends
+    /// on something else than the seed and the configs, e.g. on the wall time or the I/O.
+    ///
+    /// Once the retry policy is set, panics of the runs are caught and reported
+    /// as the [`RunFailure::Panicked`], so that the failed run does not stop the others.
+    /// This requires the panics to unwind, i.e. does not work with `panic = "abort"`.
+    /// Otherwise, panics propagate to the caller of the
+    /// [`run_simulation`](ParallelBacktester::run_simulation).
+    ///
+    /// # Arguments
+    ///
+    /// * `max_retries` — Maximum number of the retries of the single run.
+    pub fn with_max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = Some(max_retries);
+        self
+    }
 }
 
 impl<
@@ -126,22 +238,27 @@ impl<
 ParallelBacktester<PerThreadConfigs, RNG>
     where BrokerID: Id,
           ExchangeID: Id,
-          TraderConfig: Send,
-          BrokerConfig: Send,
-          ExchangeConfig: Send,
-          ReplayConfig: Send,
+          TraderConfig: Send + Clone,
+          BrokerConfig: Send + Clone,
+          ExchangeConfig: Send + Clone,
+          ReplayConfig: Send + Clone,
           TraderConfigs: IntoIterator<Item=(TraderConfig, ConnectedBrokers)>,
           BrokerConfigs: IntoIterator<Item=(BrokerConfig, ConnectedExchanges)>,
           ExchangeConfigs: IntoIterator<Item=ExchangeConfig>,
           PerThreadConfigs: IntoIterator<Item=ThreadConfig<ReplayConfig, ExchangeConfigs, BrokerConfigs, TraderConfigs>>,
-          ConnectedBrokers: Send + IntoIterator<Item=(BrokerID, SubscriptionConfigs)>,
-          ConnectedExchanges: Send + IntoIterator<Item=ExchangeID>,
+          ConnectedBrokers: Send + Clone + IntoIterator<Item=(BrokerID, SubscriptionConfigs)>,
+          ConnectedExchanges: Send + Clone + IntoIterator<Item=ExchangeID>,
           SubscriptionConfigs: IntoIterator<Item=SubCfg>,
           RNG: Rng + SeedableRng
 {
     #[inline]
+    #[must_use]
     /// Runs final simulation.
-    pub fn run_simulation<T, B, E, R>(self)
+    /// Returns the outcomes of the runs in the order of the thread configs.
+    ///
+    /// Panics of the runs propagate unless the retry policy is set
+    /// by the [`with_max_retries`](ParallelBacktester::with_max_retries).
+    pub fn run_simulation<T, B, E, R>(self) -> Vec<RunOutcome>
         where
             T: From<TraderConfig>,
             B: From<BrokerConfig>,
@@ -152,42 +269,64 @@ ParallelBacktester<PerThreadConfigs, RNG>
             E: Exchange<BrokerID=BrokerID, ExchangeID=ExchangeID, E2R=R::E2R, R2E=R::R2E, B2E=B::B2E, E2B=B::E2B>,
             R: Replay<BrokerID=BrokerID, ExchangeID=ExchangeID>
     {
-        let Self { num_threads, per_thread_configs, date_range, .. } = self;
-        let per_thread_configs: Vec<(_, _, Vec<_>, Vec<_>, Vec<_>)> = per_thread_configs.into_iter()
+        let Self { num_threads, per_thread_configs, date_range, limits, max_retries, .. } = self;
+        let per_thread_configs: Vec<(_, ThreadConfig<_, Vec<_>, Vec<_>, Vec<_>>)> = per_thread_configs
+            .into_iter()
             .map(
                 |ThreadConfig {
-                     rng_seed, replay_config, trader_configs,
+                     rng_seed, rng_kind, latency_adjustment, replay_config, trader_configs,
                      broker_configs, exchange_configs
                  }|
-                    (
+                    ThreadConfig {
                         rng_seed,
+                        rng_kind,
+                        latency_adjustment,
                         replay_config,
-                        exchange_configs.into_iter().collect(),
-                        broker_configs.into_iter().collect(),
-                        trader_configs.into_iter().collect()
-                    )
+                        exchange_configs: exchange_configs.into_iter().collect(),
+                        broker_configs: broker_configs.into_iter().collect(),
+                        trader_configs: trader_configs.into_iter().collect(),
+                    }
             )
+            .enumerate()
             .collect();
 
-        let job = || per_thread_configs.into_par_iter().for_each(
-            |(rng_seed, replay_config, exchange_configs, broker_configs, trader_configs)| {
-                let exchanges = exchange_configs.into_iter().map(E::from);
-                let brokers = broker_configs.into_iter().map(
-                    |(broker_cfg, connected_exchanges)|
-                        (B::from(broker_cfg), connected_exchanges)
-                );
-                let traders = trader_configs.into_iter().map(
-                    |(trader_config, connected_brokers)|
-                        (T::from(trader_config), connected_brokers)
-                );
-                let replay = R::from(replay_config);
-                KernelBuilder::new(exchanges, brokers, traders, replay, date_range)
-                    .with_rng::<RNG>()
-                    .with_seed(rng_seed)
-                    .build()
-                    .run_simulation()
+        let job = || per_thread_configs.into_par_iter().map(
+            |(config_index, thread_config)| {
+                let ThreadConfig {
+                    rng_seed, rng_kind, latency_adjustment, replay_config,
+                    exchange_configs, broker_configs, trader_configs
+                } = &thread_config;
+                let rng_seed = *rng_seed;
+                let run = || {
+                    let exchanges = exchange_configs.iter().cloned().map(E::from);
+                    let brokers = broker_configs.iter().cloned().map(
+                        |(broker_cfg, connected_exchanges)|
+                            (B::from(broker_cfg), connected_exchanges)
+                    );
+                    let traders = trader_configs.iter().cloned().map(
+                        |(trader_config, connected_brokers)|
+                            (T::from(trader_config), connected_brokers)
+                    );
+                    let replay = R::from(replay_config.clone());
+                    let builder = KernelBuilder::new(exchanges, brokers, traders, replay, date_range)
+                        .with_seed(rng_seed)
+                        .with_latency_adjustment(*latency_adjustment);
+                    match rng_kind {
+                        RngKind::Inherited => builder.with_rng::<RNG>()
+                            .build()
+                            .run_simulation_with_limits(limits),
+                        RngKind::Std => builder.with_rng::<StdRng>()
+                            .build()
+                            .run_simulation_with_limits(limits),
+                        RngKind::Small => builder.with_rng::<SmallRng>()
+                            .build()
+                            .run_simulation_with_limits(limits),
+                    }.map_err(RunFailure::LimitExceeded)
+                };
+                let (attempts, result) = run_with_retries(run, max_retries);
+                RunOutcome { config_index, rng_seed, attempts, result }
             }
-        );
+        ).collect();
         if num_threads == 0 {
             job()
         } else {
@@ -204,4 +343,100 @@ ParallelBacktester<PerThreadConfigs, RNG>
                 .install(job)
         }
     }
-}
\ No newline at end of file
+}
+
+/// Runs the simulation until it succeeds or the retries are exhausted.
+/// Returns the number of the attempts made and the result of the last one.
+/// Panics propagate if there is no retry policy.
+fn run_with_retries(
+    run: impl Fn() -> Result<(), RunFailure>,
+    max_retries: Option<usize>) -> (usize, Result<(), RunFailure>)
+{
+    let Some(max_retries) = max_retries else {
+        return (1, run());
+    };
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let result = catch_run_panic(&run);
+        if result.is_ok() || attempts > max_retries {
+            break (attempts, result);
+        }
+    }
+}
+
+/// Runs the simulation, converting its panic into the [`RunFailure::Panicked`]
+/// with the message taken from the panic payload.
+fn catch_run_panic(run: impl FnOnce() -> Result<(), RunFailure>) -> Result<(), RunFailure>
+{
+    panic::catch_unwind(AssertUnwindSafe(run)).unwrap_or_else(
+        |payload| Err(RunFailure::Panicked { message: panic_message(payload.as_ref()) })
+    )
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::test_utils::dt, std::cell::Cell};
+
+    fn violation() -> RunFailure {
+        RunFailure::LimitExceeded(
+            LimitViolation::QueueMemory {
+                bytes: 1024,
+                simulated_dt: dt(0),
+            }
+        )
+    }
+
+    #[test]
+    fn test_panicking_run() {
+        let num_runs = Cell::new(0);
+        let (attempts, result) = run_with_retries(
+            || {
+                num_runs.set(num_runs.get() + 1);
+                panic!("Run {} has failed", num_runs.get())
+            },
+            Some(2),
+        );
+        assert_eq!((attempts, num_runs.get()), (3, 3));
+        assert_eq!(result, Err(RunFailure::Panicked { message: "Run 3 has failed".to_string() }))
+    }
+
+    #[test]
+    #[should_panic(expected = "Run has failed")]
+    fn test_panic_propagation_without_retries() {
+        let _ = run_with_retries(|| panic!("Run has failed"), None);
+    }
+
+    #[test]
+    fn test_retry_until_success() {
+        let num_runs = Cell::new(0);
+        let (attempts, result) = run_with_retries(
+            || {
+                num_runs.set(num_runs.get() + 1);
+                if num_runs.get() == 1 {
+                    Err(violation())
+                } else {
+                    Ok(())
+                }
+            },
+            Some(5),
+        );
+        assert_eq!((attempts, result), (2, Ok(())));
+
+        let (attempts, result) = run_with_retries(|| Err(violation()), Some(0));
+        assert_eq!((attempts, result), (1, Err(violation())));
+
+        let (attempts, result) = run_with_retries(|| Err(violation()), None);
+        assert_eq!((attempts, result), (1, Err(violation())))
+    }
+}
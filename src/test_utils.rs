@@ -0,0 +1,34 @@
+use crate::types::{Date, DateTime, Duration};
+#[cfg(feature = "concrete")]
+use crate::concrete::traded_pair::{Base, settlement::concrete::SpotSettlement, TradedPair};
+
+/// Returns the datetime of the test trading day.
+pub(crate) fn hms(hour: u32, minute: u32, second: u32) -> DateTime {
+    Date::from_ymd_opt(2022, 1, 3).unwrap().and_hms_opt(hour, minute, second).unwrap()
+}
+
+/// Returns the datetime the given number of seconds after 10:00 of the test trading day.
+pub(crate) fn dt(seconds: u32) -> DateTime {
+    hms(10, 0, seconds)
+}
+
+/// Returns the datetime the given number of nanoseconds after 10:00 of the test trading day.
+pub(crate) fn dt_ns(nanos: i64) -> DateTime {
+    dt(0) + Duration::nanoseconds(nanos)
+}
+
+#[cfg(feature = "concrete")]
+/// Returns the spot traded pair of the symbol settled in `RUB`.
+pub(crate) fn symbol_pair(symbol: &'static str) -> TradedPair<&'static str, SpotSettlement> {
+    TradedPair {
+        quoted_asset: Base::new(symbol).into(),
+        settlement_asset: Base::new("RUB").into(),
+        settlement_determinant: SpotSettlement,
+    }
+}
+
+#[cfg(feature = "concrete")]
+/// Returns the `USD/RUB` spot traded pair.
+pub(crate) fn pair() -> TradedPair<&'static str, SpotSettlement> {
+    symbol_pair("USD")
+}
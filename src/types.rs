@@ -1,4 +1,14 @@
-use std::{convert::Infallible, fmt::{Debug, Display, Formatter}, hash::Hash, marker::PhantomData};
+use {
+    alloc::sync::Arc,
+    core::{
+        convert::Infallible,
+        fmt::{Debug, Display, Formatter},
+        hash::Hash,
+        marker::PhantomData,
+        ops::{Add, AddAssign, Mul, Range, Sub, SubAssign},
+        sync::atomic::{AtomicU64, Ordering},
+    },
+};
 
 pub use chrono::{
     Duration,
@@ -37,8 +47,8 @@ pub trait Agent {
 /// to indicate that the given message bridge between agents is not used.
 pub struct NeverType<K>((Infallible, PhantomData<K>));
 
-impl<K> std::fmt::Display for NeverType<K> {
-    fn fmt(&self, _: &mut Formatter<'_>) -> std::fmt::Result {
+impl<K> core::fmt::Display for NeverType<K> {
+    fn fmt(&self, _: &mut Formatter<'_>) -> core::fmt::Result {
         unreachable!("Cannot be called for the NeverType")
     }
 }
@@ -49,8 +59,287 @@ impl<K> std::fmt::Display for NeverType<K> {
 /// to indicate that the given message bridge between agents is not used.
 pub struct Nothing(Infallible);
 
-impl std::fmt::Display for Nothing {
-    fn fmt(&self, _: &mut Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for Nothing {
+    fn fmt(&self, _: &mut Formatter<'_>) -> core::fmt::Result {
         unreachable!("Cannot be called for the Nothing type")
     }
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Non-negative time span in nanoseconds, e.g. the message delay or the settlement lag.
+///
+/// Plain operators panic on overflow, while the `checked_*` and `saturating_*` methods
+/// let the caller handle it.
+pub struct NanoDuration(pub u64);
+
+impl NanoDuration {
+    /// Zero duration.
+    pub const ZERO: Self = Self(0);
+    /// Largest representable duration.
+    pub const MAX: Self = Self(u64::MAX);
+
+    /// Creates a new instance of the `NanoDuration`.
+    ///
+    /// # Arguments
+    ///
+    /// * `nanos` — Number of nanoseconds.
+    pub const fn from_nanos(nanos: u64) -> Self {
+        Self(nanos)
+    }
+
+    /// Returns the number of nanoseconds.
+    pub const fn as_nanos(self) -> u64 {
+        self.0
+    }
+
+    /// Returns whether the duration is zero.
+    pub const fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns the sum or `None` on overflow.
+    pub const fn checked_add(self, rhs: Self) -> Option<Self> {
+        match self.0.checked_add(rhs.0) {
+            Some(nanos) => Some(Self(nanos)),
+            None => None
+        }
+    }
+
+    /// Returns the difference or `None` if `rhs` is greater than `self`.
+    pub const fn checked_sub(self, rhs: Self) -> Option<Self> {
+        match self.0.checked_sub(rhs.0) {
+            Some(nanos) => Some(Self(nanos)),
+            None => None
+        }
+    }
+
+    /// Returns the duration multiplied by `rhs` or `None` on overflow.
+    pub const fn checked_mul(self, rhs: u64) -> Option<Self> {
+        match self.0.checked_mul(rhs) {
+            Some(nanos) => Some(Self(nanos)),
+            None => None
+        }
+    }
+
+    /// Returns the sum, saturating at [`NanoDuration::MAX`].
+    pub const fn saturating_add(self, rhs: Self) -> Self {
+        Self(self.0.saturating_add(rhs.0))
+    }
+
+    /// Returns the difference, saturating at [`NanoDuration::ZERO`].
+    pub const fn saturating_sub(self, rhs: Self) -> Self {
+        Self(self.0.saturating_sub(rhs.0))
+    }
+
+    /// Returns the duration multiplied by `rhs`, saturating at [`NanoDuration::MAX`].
+    pub const fn saturating_mul(self, rhs: u64) -> Self {
+        Self(self.0.saturating_mul(rhs))
+    }
+
+    /// Converts to the [`Duration`] or returns `None` if it does not fit.
+    pub fn to_duration(self) -> Option<Duration> {
+        i64::try_from(self.0).ok().map(Duration::nanoseconds)
+    }
+
+    /// Converts from the [`Duration`] or returns `None`
+    /// if it is negative or does not fit.
+    ///
+    /// # Arguments
+    ///
+    /// * `duration` — Duration to convert.
+    pub fn from_duration(duration: Duration) -> Option<Self> {
+        u64::try_from(duration.num_nanoseconds()?).ok().map(Self)
+    }
+}
+
+impl Display for NanoDuration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}ns", self.0)
+    }
+}
+
+impl From<u64> for NanoDuration {
+    fn from(nanos: u64) -> Self {
+        Self(nanos)
+    }
+}
+
+impl From<NanoDuration> for u64 {
+    fn from(duration: NanoDuration) -> Self {
+        duration.0
+    }
+}
+
+impl Add for NanoDuration {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        self.checked_add(rhs).unwrap_or_else(
+            || panic!("NanoDuration overflow: {self} + {rhs}")
+        )
+    }
+}
+
+impl AddAssign for NanoDuration {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs
+    }
+}
+
+impl Sub for NanoDuration {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        self.checked_sub(rhs).unwrap_or_else(
+            || panic!("NanoDuration underflow: {self} - {rhs}")
+        )
+    }
+}
+
+impl SubAssign for NanoDuration {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs
+    }
+}
+
+impl Mul<u64> for NanoDuration {
+    type Output = Self;
+
+    fn mul(self, rhs: u64) -> Self {
+        self.checked_mul(rhs).unwrap_or_else(
+            || panic!("NanoDuration overflow: {self} * {rhs}")
+        )
+    }
+}
+
+impl Add<NanoDuration> for DateTime {
+    type Output = DateTime;
+
+    fn add(self, rhs: NanoDuration) -> DateTime {
+        rhs.to_duration()
+            .and_then(|duration| self.checked_add_signed(duration))
+            .unwrap_or_else(|| panic!("DateTime overflow: {self} + {rhs}"))
+    }
+}
+
+impl Sub<NanoDuration> for DateTime {
+    type Output = DateTime;
+
+    fn sub(self, rhs: NanoDuration) -> DateTime {
+        rhs.to_duration()
+            .and_then(|duration| self.checked_sub_signed(duration))
+            .unwrap_or_else(|| panic!("DateTime overflow: {self} - {rhs}"))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// Simulation-scoped allocator of the unique IDs, e.g. the order IDs.
+/// Clones share the same counter, so the IDs obtained from any of them never collide.
+///
+/// Agents can either allocate the IDs one by one with [`next_id`](IdAllocator::next_id)
+/// or reserve ranges of them with [`reserve`](IdAllocator::reserve)
+/// and generate the IDs within their own ranges.
+pub struct IdAllocator(Arc<AtomicU64>);
+
+impl IdAllocator {
+    /// Creates a new instance of the `IdAllocator`.
+    ///
+    /// # Arguments
+    ///
+    /// * `first_id` — First ID to allocate.
+    pub fn new(first_id: u64) -> Self {
+        Self(Arc::new(AtomicU64::new(first_id)))
+    }
+
+    /// Allocates the next ID.
+    pub fn next_id(&self) -> u64 {
+        self.reserve(1).start
+    }
+
+    /// Reserves the range of consecutive IDs for the exclusive use of the caller.
+    ///
+    /// # Arguments
+    ///
+    /// * `len` — Number of IDs to reserve.
+    pub fn reserve(&self, len: u64) -> Range<u64> {
+        let start = self.0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |next| next.checked_add(len))
+            .unwrap_or_else(|next| panic!("Cannot reserve {len} IDs starting from {next}"));
+        start..start + len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::test_utils::dt};
+
+    #[test]
+    fn test_nano_duration_checked_arithmetic() {
+        let one = NanoDuration(1);
+        assert_eq!(NanoDuration(2).checked_add(one), Some(NanoDuration(3)));
+        assert_eq!(NanoDuration::MAX.checked_add(one), None);
+        assert_eq!(NanoDuration(2).checked_sub(one), Some(one));
+        assert_eq!(NanoDuration::ZERO.checked_sub(one), None);
+        assert_eq!(NanoDuration(2).checked_mul(3), Some(NanoDuration(6)));
+        assert_eq!(NanoDuration::MAX.checked_mul(2), None);
+    }
+
+    #[test]
+    fn test_nano_duration_saturating_arithmetic() {
+        let one = NanoDuration(1);
+        assert_eq!(NanoDuration(2).saturating_add(one), NanoDuration(3));
+        assert_eq!(NanoDuration::MAX.saturating_add(one), NanoDuration::MAX);
+        assert_eq!(NanoDuration(2).saturating_sub(one), one);
+        assert_eq!(one.saturating_sub(NanoDuration(2)), NanoDuration::ZERO);
+        assert_eq!(NanoDuration(2).saturating_mul(3), NanoDuration(6));
+        assert_eq!(NanoDuration::MAX.saturating_mul(2), NanoDuration::MAX);
+    }
+
+    #[test]
+    #[should_panic(expected = "NanoDuration overflow")]
+    fn test_nano_duration_add_overflow() {
+        let _ = NanoDuration::MAX + NanoDuration(1);
+    }
+
+    #[test]
+    #[should_panic(expected = "NanoDuration underflow")]
+    fn test_nano_duration_sub_underflow() {
+        let mut duration = NanoDuration::ZERO;
+        duration -= NanoDuration(1)
+    }
+
+    #[test]
+    #[should_panic(expected = "NanoDuration overflow")]
+    fn test_nano_duration_mul_overflow() {
+        let _ = NanoDuration::MAX * 2;
+    }
+
+    #[test]
+    fn test_nano_duration_conversions() {
+        assert_eq!(NanoDuration(5).to_duration(), Some(Duration::nanoseconds(5)));
+        assert_eq!(NanoDuration::MAX.to_duration(), None);
+        assert_eq!(NanoDuration::from_duration(Duration::nanoseconds(5)), Some(NanoDuration(5)));
+        assert_eq!(NanoDuration::from_duration(Duration::nanoseconds(-1)), None);
+
+        let dt = dt(0);
+        assert_eq!(dt + NanoDuration(5), dt + Duration::nanoseconds(5));
+    }
+
+    #[test]
+    #[should_panic(expected = "DateTime overflow")]
+    fn test_date_time_overflow() {
+        let dt = dt(0);
+        let _ = dt + NanoDuration::MAX;
+    }
+
+    #[test]
+    fn test_id_allocator_clones_share_counter() {
+        let allocator = IdAllocator::new(10);
+        let clone = allocator.clone();
+        assert_eq!(allocator.next_id(), 10);
+        assert_eq!(clone.reserve(3), 11..14);
+        assert_eq!(allocator.next_id(), 14);
+        assert_eq!(IdAllocator::default().next_id(), 0);
+    }
+}
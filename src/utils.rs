@@ -1,14 +1,25 @@
 #[cfg(feature = "derive_more")]
 pub use derive_more;
 
-pub use {chrono, rand};
+pub use rand;
 #[cfg(feature = "derive")]
 pub use derive;
 
+/// Re-export of the [`chrono`](::chrono) crate along with the trading calendar,
+/// the bar alignment and the time zone aware recurring schedules.
+pub mod chrono;
+/// Hash-based collections available both with and without the standard library.
+pub mod collections;
 /// Useful constants.
 pub mod constants;
+#[cfg(feature = "std")]
+/// Simulation metadata stamped into the output files for reproducibility.
+pub mod metadata;
 /// Useful queue structures.
 pub mod queue;
+#[cfg(feature = "std")]
+/// Comparison of two simulation traces.
+pub mod trace_diff;
 
 #[cfg(feature = "enum_def")]
 #[macro_export]
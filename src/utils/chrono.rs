@@ -0,0 +1,272 @@
+pub use ::chrono::*;
+
+use {alloc::collections::BTreeSet, core::ops::Range};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Trading days and the daily session of the exchange, in the simulation time.
+///
+/// Session-relative times and bar boundaries are measured from the session open,
+/// so that the bars are aligned to the calendar rather than to the midnight.
+///
+/// # Examples
+///
+/// ```
+/// use trading_backtester::{types::{Date, Duration, Time}, utils::chrono::TradingCalendar};
+///
+/// let calendar = TradingCalendar::new(
+///     Time::from_hms_opt(9, 30, 0).unwrap(),
+///     Time::from_hms_opt(16, 0, 0).unwrap(),
+/// );
+/// let friday = Date::from_ymd_opt(2022, 1, 7).unwrap();
+/// let dt = friday.and_hms_opt(15, 50, 0).unwrap();
+/// let bar = Duration::minutes(45);
+///
+/// assert_eq!(calendar.align_to_bar(dt, bar), friday.and_hms_opt(15, 30, 0));
+/// assert_eq!(calendar.get_next_bar_boundary(dt, bar), friday.and_hms_opt(16, 0, 0).unwrap());
+/// assert_eq!(
+///     calendar.get_next_bar_boundary(friday.and_hms_opt(16, 0, 0).unwrap(), bar),
+///     Date::from_ymd_opt(2022, 1, 10).unwrap().and_hms_opt(9, 30, 0).unwrap()
+/// );
+/// ```
+pub struct TradingCalendar {
+    open: NaiveTime,
+    close: NaiveTime,
+    /// Whether the day of the week is non-trading, starting from Monday.
+    weekend: [bool; 7],
+    holidays: BTreeSet<NaiveDate>,
+}
+
+impl TradingCalendar {
+    /// Creates a new instance of the `TradingCalendar` with the Saturday and Sunday weekend
+    /// and no holidays.
+    ///
+    /// # Arguments
+    ///
+    /// * `open` — Time of the session open.
+    /// * `close` — Time of the session close. Should be after the `open`.
+    pub fn new(open: NaiveTime, close: NaiveTime) -> Self {
+        if close <= open {
+            panic!("Session close {close} should be after the session open {open}")
+        }
+        Self {
+            open,
+            close,
+            weekend: [false, false, false, false, false, true, true],
+            holidays: Default::default(),
+        }
+    }
+
+    /// Replaces the weekend days. At least one day of the week should be a trading one.
+    ///
+    /// # Arguments
+    ///
+    /// * `days` — Non-trading days of the week.
+    pub fn with_weekend(mut self, days: impl IntoIterator<Item=Weekday>) -> Self {
+        self.weekend = [false; 7];
+        for day in days {
+            self.weekend[day.num_days_from_monday() as usize] = true
+        }
+        if self.weekend.iter().all(|is_weekend| *is_weekend) {
+            panic!("Weekend cannot last the whole week")
+        }
+        self
+    }
+
+    /// Adds the holidays.
+    ///
+    /// # Arguments
+    ///
+    /// * `dates` — Non-trading dates.
+    pub fn with_holidays(mut self, dates: impl IntoIterator<Item=NaiveDate>) -> Self {
+        self.holidays.extend(dates);
+        self
+    }
+
+    /// Whether there is a session on the date.
+    ///
+    /// # Arguments
+    ///
+    /// * `date` — Date to check.
+    pub fn is_trading_day(&self, date: NaiveDate) -> bool {
+        !self.weekend[date.weekday().num_days_from_monday() as usize]
+            && !self.holidays.contains(&date)
+    }
+
+    /// Returns the first trading day strictly after the date.
+    ///
+    /// # Arguments
+    ///
+    /// * `date` — Date to start from.
+    pub fn get_next_trading_day(&self, date: NaiveDate) -> NaiveDate {
+        let mut date = date.succ_opt().unwrap_or_else(|| panic!("No date after {date}"));
+        while !self.is_trading_day(date) {
+            date = date.succ_opt().unwrap_or_else(|| panic!("No date after {date}"))
+        }
+        date
+    }
+
+    /// Returns the session of the date or `None` if it is not a trading day.
+    ///
+    /// # Arguments
+    ///
+    /// * `date` — Date of the session.
+    pub fn get_session(&self, date: NaiveDate) -> Option<Range<NaiveDateTime>> {
+        if self.is_trading_day(date) {
+            Some(date.and_time(self.open)..date.and_time(self.close))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the open of the first session starting at or after the datetime.
+    ///
+    /// # Arguments
+    ///
+    /// * `dt` — Datetime to start from.
+    pub fn get_next_open(&self, dt: NaiveDateTime) -> NaiveDateTime {
+        let date = dt.date();
+        match self.get_session(date) {
+            Some(session) if dt <= session.start => session.start,
+            _ => self.get_next_trading_day(date).and_time(self.open)
+        }
+    }
+
+    /// Returns the time elapsed since the session open
+    /// or `None` if the datetime is outside the session.
+    ///
+    /// # Arguments
+    ///
+    /// * `dt` — Datetime within the session.
+    pub fn get_time_since_open(&self, dt: NaiveDateTime) -> Option<Duration> {
+        let session = self.get_session(dt.date())?;
+        session.contains(&dt).then(|| dt - session.start)
+    }
+
+    /// Returns the time left until the session close
+    /// or `None` if the datetime is outside the session.
+    ///
+    /// # Arguments
+    ///
+    /// * `dt` — Datetime within the session.
+    pub fn get_time_until_close(&self, dt: NaiveDateTime) -> Option<Duration> {
+        let session = self.get_session(dt.date())?;
+        session.contains(&dt).then(|| session.end - dt)
+    }
+
+    /// Returns the start of the bar containing the datetime
+    /// or `None` if the datetime is outside the session.
+    /// Bars start at the session open and the last bar of the session is cut by the close.
+    ///
+    /// # Arguments
+    ///
+    /// * `dt` — Datetime within the session.
+    /// * `bar` — Length of the bar. Should be positive.
+    pub fn align_to_bar(&self, dt: NaiveDateTime, bar: Duration) -> Option<NaiveDateTime> {
+        let elapsed = get_nanos(self.get_time_since_open(dt)?);
+        let bar = get_bar_nanos(bar);
+        Some(dt - Duration::nanoseconds(elapsed % bar))
+    }
+
+    /// Returns the first bar boundary strictly after the datetime,
+    /// that is the start of the next bar or the session close,
+    /// e.g. to schedule the wakeup completing the bar.
+    /// Outside the session it is the next session open.
+    ///
+    /// # Arguments
+    ///
+    /// * `dt` — Datetime to start from.
+    /// * `bar` — Length of the bar. Should be positive.
+    pub fn get_next_bar_boundary(&self, dt: NaiveDateTime, bar: Duration) -> NaiveDateTime {
+        let bar_start = if let Some(bar_start) = self.align_to_bar(dt, bar) {
+            bar_start
+        } else {
+            return self.get_next_open(dt + Duration::nanoseconds(1));
+        };
+        let close = dt.date().and_time(self.close);
+        (bar_start + Duration::nanoseconds(get_bar_nanos(bar))).min(close)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Daily event at the fixed wall-clock time of the time zone, e.g. the end-of-day rollover,
+/// that stays at the same local time across the daylight saving time transitions.
+///
+/// The simulation time is assumed to be UTC.
+/// If the local time is skipped by the transition, the event occurs
+/// as if the clocks have not been changed yet, i.e. later by the length of the gap.
+/// If the local time is repeated, the event occurs at the earlier one.
+pub struct RecurringSchedule<Tz: TimeZone> {
+    time: NaiveTime,
+    tz: Tz,
+    calendar: Option<TradingCalendar>,
+}
+
+impl<Tz: TimeZone> RecurringSchedule<Tz> {
+    /// Creates a new instance of the `RecurringSchedule` occurring every day.
+    ///
+    /// # Arguments
+    ///
+    /// * `time` — Local time of the event.
+    /// * `tz` — Time zone of the local time, e.g. the one of the exchange.
+    pub fn new(time: NaiveTime, tz: Tz) -> Self {
+        Self { time, tz, calendar: None }
+    }
+
+    /// Restricts the event to the trading days of the calendar, in local dates.
+    ///
+    /// # Arguments
+    ///
+    /// * `calendar` — Trading calendar.
+    pub fn with_calendar(mut self, calendar: TradingCalendar) -> Self {
+        self.calendar = Some(calendar);
+        self
+    }
+
+    /// Returns the first occurrence strictly after the datetime.
+    ///
+    /// # Arguments
+    ///
+    /// * `dt` — Datetime to start from.
+    pub fn get_next(&self, dt: NaiveDateTime) -> NaiveDateTime {
+        // Start from the previous local date, since the occurrence of the current UTC date
+        // may belong to it
+        let mut date = self.tz.from_utc_datetime(&dt).naive_local().date();
+        date = date.pred_opt().unwrap_or(date);
+        loop {
+            let is_scheduled = self.calendar.as_ref()
+                .is_none_or(|calendar| calendar.is_trading_day(date));
+            if is_scheduled {
+                let occurrence = self.to_utc(date.and_time(self.time));
+                if occurrence > dt {
+                    return occurrence;
+                }
+            }
+            date = date.succ_opt().unwrap_or_else(|| panic!("No date after {date}"))
+        }
+    }
+
+    fn to_utc(&self, local: NaiveDateTime) -> NaiveDateTime {
+        match self.tz.from_local_datetime(&local) {
+            LocalResult::Single(dt) | LocalResult::Ambiguous(dt, _) => dt.naive_utc(),
+            LocalResult::None => {
+                // Offset of the day before lasts until the transition
+                let offset = self.tz.offset_from_utc_datetime(&(local - Duration::days(1)));
+                local - Duration::seconds(offset.fix().local_minus_utc() as i64)
+            }
+        }
+    }
+}
+
+fn get_nanos(duration: Duration) -> i64 {
+    duration.num_nanoseconds().unwrap_or_else(
+        || panic!("Duration {duration} cannot be expressed in nanoseconds")
+    )
+}
+
+fn get_bar_nanos(bar: Duration) -> i64 {
+    let nanos = get_nanos(bar);
+    if nanos <= 0 {
+        panic!("Bar length should be positive. Got {bar}")
+    }
+    nanos
+}
@@ -0,0 +1,51 @@
+#[cfg(not(feature = "std"))]
+pub use hashbrown::{HashMap, HashSet};
+#[cfg(feature = "std")]
+pub use hash_state::{HashMap, HashSet, HashState};
+
+#[cfg(feature = "std")]
+mod hash_state {
+    #[cfg(not(feature = "deterministic"))]
+    use std::collections::hash_map::RandomState;
+    use std::{collections::hash_map::DefaultHasher, hash::BuildHasher};
+
+    #[derive(Debug, Clone)]
+    #[cfg_attr(feature = "deterministic", derive(Default))]
+    /// Hasher builder of the hash-based collections of the crate.
+    /// Its keys are taken from the OS entropy unless the `deterministic` feature is enabled,
+    /// in which case they are fixed, so that the iteration order of the collections
+    /// is the same across the runs.
+    /// Is the same type either way, so the feature does not change the types of the public items.
+    pub struct HashState {
+        #[cfg(not(feature = "deterministic"))]
+        random_state: RandomState,
+    }
+
+    #[cfg(not(feature = "deterministic"))]
+    impl Default for HashState {
+        #[allow(clippy::disallowed_methods)]
+        fn default() -> Self {
+            Self { random_state: RandomState::new() }
+        }
+    }
+
+    impl BuildHasher for HashState {
+        type Hasher = DefaultHasher;
+
+        #[cfg(not(feature = "deterministic"))]
+        fn build_hasher(&self) -> DefaultHasher {
+            self.random_state.build_hasher()
+        }
+
+        #[cfg(feature = "deterministic")]
+        fn build_hasher(&self) -> DefaultHasher {
+            DefaultHasher::new()
+        }
+    }
+
+    /// [`HashMap`](std::collections::HashMap) with the [`HashState`] hasher.
+    pub type HashMap<K, V> = std::collections::HashMap<K, V, HashState>;
+
+    /// [`HashSet`](std::collections::HashSet) with the [`HashState`] hasher.
+    pub type HashSet<T> = std::collections::HashSet<T, HashState>;
+}
@@ -0,0 +1,231 @@
+use std::{
+    fmt::Write as _,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// Version of the `trading_backtester` crate the simulation is run with.
+pub const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+/// Metadata that makes simulation outputs traceable:
+/// crate version, config hash, seed, git commit of the user binary and agent tags.
+///
+/// # Examples
+///
+/// ```
+/// use trading_backtester::utils::metadata::SimulationMetadata;
+///
+/// let metadata = SimulationMetadata::new()
+///     .with_config_hash(0xbeef)
+///     .with_seed(3344)
+///     .with_git_commit("9a89459");
+///
+/// let mut header = Vec::new();
+/// metadata.write_header(&mut header).unwrap();
+/// assert!(String::from_utf8(header).unwrap().ends_with(
+///     "# config_hash: 000000000000beef\n# seed: 3344\n# git_commit: 9a89459\n"
+/// ));
+/// assert!(metadata.to_json().contains("\"seed\": 3344"));
+/// ```
+pub struct SimulationMetadata {
+    /// Version of the `trading_backtester` crate.
+    pub crate_version: &'static str,
+    /// Hash of the simulation config.
+    pub config_hash: Option<u64>,
+    /// Seed of the random number generator.
+    pub seed: Option<u64>,
+    /// Git commit of the user binary.
+    pub git_commit: Option<String>,
+    /// Tags of the agents the output belongs to, e.g. `cohort=A`.
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+/// Where to place the [`SimulationMetadata`] of the output file.
+pub enum MetadataPlacement {
+    /// Comment lines starting with `#` at the beginning of the output file.
+    Header,
+    /// JSON file next to the output file, named `<output file>.meta.json`.
+    Sidecar,
+}
+
+impl Default for SimulationMetadata {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimulationMetadata {
+    /// Creates a new instance of the `SimulationMetadata`
+    /// containing only the crate version.
+    pub fn new() -> Self {
+        Self {
+            crate_version: CRATE_VERSION,
+            config_hash: None,
+            seed: None,
+            git_commit: None,
+            tags: vec![],
+        }
+    }
+
+    /// Sets the config hash.
+    ///
+    /// # Arguments
+    ///
+    /// * `config_hash` — Hash of the simulation config.
+    pub fn with_config_hash(mut self, config_hash: u64) -> Self {
+        self.config_hash = Some(config_hash);
+        self
+    }
+
+    /// Sets the config hash to the hash of the config file contents.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` — Path to the config file.
+    pub fn with_config_file(self, path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let contents = std::fs::read(path).unwrap_or_else(
+            |err| panic!("Cannot read config file {path:?}. Error: {err}")
+        );
+        self.with_config_hash(stable_hash(&contents))
+    }
+
+    /// Sets the seed.
+    ///
+    /// # Arguments
+    ///
+    /// * `seed` — Seed of the random number generator.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Sets the git commit of the user binary.
+    /// Typically taken from the build script of the user binary, e.g. `env!("GIT_COMMIT")`.
+    ///
+    /// # Arguments
+    ///
+    /// * `git_commit` — Git commit hash.
+    pub fn with_git_commit(mut self, git_commit: impl Into<String>) -> Self {
+        self.git_commit = Some(git_commit.into());
+        self
+    }
+
+    /// Appends the tags.
+    ///
+    /// # Arguments
+    ///
+    /// * `tags` — Tags of the agents the output belongs to.
+    pub fn with_tags(mut self, tags: impl IntoIterator<Item=impl Into<String>>) -> Self {
+        self.tags.extend(tags.into_iter().map(Into::into));
+        self
+    }
+
+    /// Writes the metadata as comment lines starting with `#`.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` — Writer to write the header to.
+    pub fn write_header(&self, mut writer: impl Write) -> std::io::Result<()> {
+        writeln!(writer, "# crate_version: {}", self.crate_version)?;
+        if let Some(config_hash) = self.config_hash {
+            writeln!(writer, "# config_hash: {config_hash:016x}")?
+        }
+        if let Some(seed) = self.seed {
+            writeln!(writer, "# seed: {seed}")?
+        }
+        if let Some(git_commit) = &self.git_commit {
+            writeln!(writer, "# git_commit: {git_commit}")?
+        }
+        if !self.tags.is_empty() {
+            writeln!(writer, "# tags: {}", self.tags.join(","))?
+        }
+        Ok(())
+    }
+
+    /// Returns the metadata serialized into a JSON object.
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\n");
+        write!(json, "  \"crate_version\": \"{}\"", escape_json(self.crate_version)).unwrap();
+        match self.config_hash {
+            Some(config_hash) => write!(json, ",\n  \"config_hash\": \"{config_hash:016x}\""),
+            None => write!(json, ",\n  \"config_hash\": null")
+        }.unwrap();
+        match self.seed {
+            Some(seed) => write!(json, ",\n  \"seed\": {seed}"),
+            None => write!(json, ",\n  \"seed\": null")
+        }.unwrap();
+        match &self.git_commit {
+            Some(git_commit) => write!(
+                json, ",\n  \"git_commit\": \"{}\"", escape_json(git_commit)
+            ),
+            None => write!(json, ",\n  \"git_commit\": null")
+        }.unwrap();
+        let tags: Vec<_> = self.tags.iter()
+            .map(|tag| format!("\"{}\"", escape_json(tag)))
+            .collect();
+        write!(json, ",\n  \"tags\": [{}]", tags.join(", ")).unwrap();
+        json.push_str("\n}\n");
+        json
+    }
+
+    /// Returns the path to the sidecar JSON file of the output file.
+    ///
+    /// # Arguments
+    ///
+    /// * `output_file` — Path to the output file.
+    pub fn sidecar_path(output_file: impl AsRef<Path>) -> PathBuf {
+        let mut path = output_file.as_ref().as_os_str().to_owned();
+        path.push(".meta.json");
+        path.into()
+    }
+
+    /// Writes the metadata to the sidecar JSON file of the output file.
+    ///
+    /// # Arguments
+    ///
+    /// * `output_file` — Path to the output file.
+    pub fn write_sidecar(&self, output_file: impl AsRef<Path>) {
+        let path = Self::sidecar_path(output_file);
+        let mut file = File::create(&path).unwrap_or_else(
+            |err| panic!("Cannot create file {path:?}. Error: {err}")
+        );
+        file.write_all(self.to_json().as_bytes())
+            .unwrap_or_else(|err| panic!("Cannot write to file {path:?}. Error: {err}"))
+    }
+}
+
+/// Computes the 64-bit FNV-1a hash of the `bytes`.
+/// Unlike the [`DefaultHasher`](std::collections::hash_map::DefaultHasher),
+/// it is stable across Rust releases and platforms.
+///
+/// # Arguments
+///
+/// * `bytes` — Bytes to hash.
+pub fn stable_hash(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    bytes.iter().fold(
+        OFFSET_BASIS,
+        |hash, byte| (hash ^ *byte as u64).wrapping_mul(PRIME),
+    )
+}
+
+pub(crate) fn escape_json(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len());
+    for c in string.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c)
+        }
+    }
+    escaped
+}
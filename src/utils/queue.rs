@@ -1,4 +1,8 @@
-use std::{cmp::Reverse, collections::BinaryHeap};
+use {
+    crate::utils::collections::HashSet,
+    alloc::collections::BinaryHeap,
+    core::cmp::Reverse,
+};
 
 #[derive(Default)]
 /// A priority queue implemented with a binary heap.
@@ -26,6 +30,11 @@ impl<T: Ord> LessElementBinaryHeap<T>
     pub fn len(&self) -> usize {
         self.0.len()
     }
+
+    /// Retains only the items specified by the predicate.
+    pub fn retain(&mut self, mut f: impl FnMut(&T) -> bool) {
+        self.0.retain(|Reverse(item)| f(item))
+    }
 }
 
 impl<T: Ord> Extend<T> for LessElementBinaryHeap<T>
@@ -35,23 +44,221 @@ impl<T: Ord> Extend<T> for LessElementBinaryHeap<T>
     }
 }
 
+/// Minimum number of the revoked scheduled messages to purge them from the queue.
+const MIN_REVOKED_TO_PURGE: usize = 1024;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+/// Handle of the scheduled self-message that allows to revoke it before it is delivered.
+pub struct ScheduleHandle(pub u64);
+
+#[derive(Debug, Default)]
+/// Allocates [`ScheduleHandle`]s and keeps track of the revoked ones.
+///
+/// Revoked messages are not removed from the queue immediately.
+/// They are dropped either when popped or when the queue is purged.
+pub struct ScheduleRegistry {
+    next_handle: u64,
+    revoked: HashSet<ScheduleHandle>,
+}
+
+impl ScheduleRegistry {
+    /// Allocates a new unique [`ScheduleHandle`].
+    pub fn allocate(&mut self) -> ScheduleHandle {
+        let handle = ScheduleHandle(self.next_handle);
+        self.next_handle += 1;
+        handle
+    }
+
+    /// Marks the scheduled message as revoked.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` — Handle of the message to revoke.
+    pub fn revoke(&mut self, handle: ScheduleHandle) {
+        self.revoked.insert(handle);
+    }
+
+    /// Forgets the revoked handle. Returns whether the handle has been revoked.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` — Handle of the message being delivered.
+    pub fn take_revoked(&mut self, handle: ScheduleHandle) -> bool {
+        self.revoked.remove(&handle)
+    }
+
+    /// Checks whether the handle has been revoked.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` — Handle of the scheduled message.
+    pub fn is_revoked(&self, handle: ScheduleHandle) -> bool {
+        self.revoked.contains(&handle)
+    }
+
+    /// Returns the number of the revoked handles not yet forgotten.
+    pub fn num_revoked(&self) -> usize {
+        self.revoked.len()
+    }
+
+    /// Forgets all the revoked handles.
+    pub fn clear_revoked(&mut self) {
+        self.revoked.clear()
+    }
+
+    /// Drops the revoked messages from the queue once there are enough of them
+    /// and they may make up at least half of the queue.
+    /// Returns whether the queue has been purged.
+    ///
+    /// # Arguments
+    ///
+    /// * `queue` — Message queue.
+    /// * `get_handle` — Returns the [`ScheduleHandle`] of the message, if any.
+    pub fn purge_revoked<T: Ord>(
+        &mut self,
+        queue: &mut LessElementBinaryHeap<T>,
+        get_handle: impl Fn(&T) -> Option<ScheduleHandle>) -> bool
+    {
+        let num_revoked = self.num_revoked();
+        if num_revoked < MIN_REVOKED_TO_PURGE || num_revoked * 2 < queue.len() {
+            return false;
+        }
+        queue.retain(
+            |message| !matches!(get_handle(message), Some(handle) if self.is_revoked(handle))
+        );
+        // Remaining handles refer to the messages that have already been delivered
+        self.clear_revoked();
+        true
+    }
+}
+
 /// Structure to provide push-only access for the inner [`LessElementBinaryHeap`].
-pub struct MessageReceiver<'a, T: Ord> (&'a mut LessElementBinaryHeap<T>);
+pub struct MessageReceiver<'a, T: Ord> {
+    queue: &'a mut LessElementBinaryHeap<T>,
+    schedule_registry: Option<&'a mut ScheduleRegistry>,
+}
 
 impl<'a, T: Ord> MessageReceiver<'a, T> {
     /// Creates a new instance of the [`MessageReceiver`].
     pub fn new(queue: &'a mut LessElementBinaryHeap<T>) -> Self {
-        Self(queue)
+        Self { queue, schedule_registry: None }
+    }
+
+    /// Creates a new instance of the [`MessageReceiver`]
+    /// that allows to schedule revocable messages.
+    ///
+    /// # Arguments
+    ///
+    /// * `queue` — Message queue.
+    /// * `schedule_registry` — Registry of the [`ScheduleHandle`]s of the `queue`.
+    pub fn with_schedule_registry(
+        queue: &'a mut LessElementBinaryHeap<T>,
+        schedule_registry: &'a mut ScheduleRegistry) -> Self
+    {
+        Self { queue, schedule_registry: Some(schedule_registry) }
+    }
+
+    /// Returns the [`MessageReceiver`] pushing into the same queue for the shorter lifetime,
+    /// e.g. to pass it into the callee and keep pushing afterwards.
+    pub fn reborrow(&mut self) -> MessageReceiver<'_, T> {
+        MessageReceiver {
+            queue: self.queue,
+            schedule_registry: self.schedule_registry.as_deref_mut(),
+        }
     }
 
     /// Pushes an item onto the binary heap.
     pub fn push(&mut self, item: T) {
-        self.0.push(item)
+        self.queue.push(item)
+    }
+
+    /// Allocates a new [`ScheduleHandle`] to be carried by the scheduled self-message.
+    pub fn new_schedule_handle(&mut self) -> ScheduleHandle {
+        self.get_schedule_registry().allocate()
+    }
+
+    /// Revokes the scheduled self-message so that it is never delivered.
+    /// Revoking the already delivered message has no effect.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` — Handle carried by the message to revoke.
+    pub fn revoke(&mut self, handle: ScheduleHandle) {
+        self.get_schedule_registry().revoke(handle)
+    }
+
+    fn get_schedule_registry(&mut self) -> &mut ScheduleRegistry {
+        self.schedule_registry.as_deref_mut().unwrap_or_else(
+            || panic!("MessageReceiver does not support revocable messages")
+        )
     }
 }
 
 impl<'a, T: Ord> Extend<T> for MessageReceiver<'a, T> {
     fn extend<I: IntoIterator<Item=T>>(&mut self, iter: I) {
-        self.0.extend(iter)
+        self.queue.extend(iter)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_revocation() {
+        let mut registry = ScheduleRegistry::default();
+        let first = registry.allocate();
+        let second = registry.allocate();
+        assert_ne!(first, second);
+
+        registry.revoke(first);
+        assert!(registry.is_revoked(first));
+        assert!(!registry.is_revoked(second));
+        assert_eq!(registry.num_revoked(), 1);
+
+        // Revoked message is dropped once when popped
+        assert!(registry.take_revoked(first));
+        assert!(!registry.take_revoked(first));
+        assert!(!registry.take_revoked(second));
+        assert_eq!(registry.num_revoked(), 0)
+    }
+
+    #[test]
+    fn test_lazy_purge() {
+        let mut registry = ScheduleRegistry::default();
+        let mut queue = LessElementBinaryHeap::default();
+        let get_handle = |(_, handle): &(usize, Option<ScheduleHandle>)| *handle;
+
+        let handles: Vec<_> = (0..MIN_REVOKED_TO_PURGE).map(|_| registry.allocate()).collect();
+        queue.extend(handles.iter().enumerate().map(|(i, handle)| (i, Some(*handle))));
+        queue.extend((0..MIN_REVOKED_TO_PURGE).map(|i| (i, None)));
+
+        // Too few revoked messages
+        handles[1..].iter().for_each(|handle| registry.revoke(*handle));
+        assert!(!registry.purge_revoked(&mut queue, get_handle));
+        assert_eq!(queue.len(), 2 * MIN_REVOKED_TO_PURGE);
+
+        // Revoked messages make up half of the queue
+        registry.revoke(handles[0]);
+        assert!(registry.purge_revoked(&mut queue, get_handle));
+        assert_eq!(queue.len(), MIN_REVOKED_TO_PURGE);
+        assert_eq!(registry.num_revoked(), 0);
+        assert!(queue.0.iter().all(|message| message.0.1.is_none()))
+    }
+
+    #[test]
+    fn test_no_purge_of_mostly_live_queue() {
+        let mut registry = ScheduleRegistry::default();
+        let mut queue = LessElementBinaryHeap::default();
+        let get_handle = |(_, handle): &(usize, Option<ScheduleHandle>)| *handle;
+
+        let handles: Vec<_> = (0..MIN_REVOKED_TO_PURGE).map(|_| registry.allocate()).collect();
+        queue.extend(handles.iter().enumerate().map(|(i, handle)| (i, Some(*handle))));
+        queue.extend((0..MIN_REVOKED_TO_PURGE + 1).map(|i| (i, None)));
+        handles.iter().for_each(|handle| registry.revoke(*handle));
+
+        assert!(!registry.purge_revoked(&mut queue, get_handle));
+        assert_eq!(queue.len(), 2 * MIN_REVOKED_TO_PURGE + 1);
+        assert_eq!(registry.num_revoked(), MIN_REVOKED_TO_PURGE)
+    }
+}
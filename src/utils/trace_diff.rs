@@ -0,0 +1,281 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::{Display, Formatter},
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+/// First pair of the aligned trace events that differ.
+pub struct Divergence {
+    /// Number of the event in both traces, starting from zero.
+    pub event_no: usize,
+    /// Event of the left trace. Is `None` if the left trace has ended.
+    pub left: Option<String>,
+    /// Event of the right trace. Is `None` if the right trace has ended.
+    pub right: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+/// Summary statistics of the single trace.
+pub struct TraceSummary {
+    /// Number of events.
+    pub num_events: usize,
+    /// Number of events per event kind.
+    pub num_events_by_kind: BTreeMap<String, usize>,
+    /// Number of fills.
+    pub num_fills: usize,
+    /// Total bought size.
+    pub bought: i64,
+    /// Total sold size.
+    pub sold: i64,
+    /// Sum of the sold notionals minus the sum of the bought ones.
+    pub cash_flow: f64,
+    /// Profit and loss, i.e. the cash flow plus the net position marked at the last fill price.
+    pub pnl: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Result of the comparison of two simulation traces.
+pub struct TraceDiff {
+    /// First divergence of the aligned event streams. Is `None` if the traces are identical.
+    pub first_divergence: Option<Divergence>,
+    /// Summary of the left trace.
+    pub left: TraceSummary,
+    /// Summary of the right trace.
+    pub right: TraceSummary,
+}
+
+/// Compares two simulation traces, e.g. obtained before and after a strategy change.
+///
+/// Trace is a csv-file which first non-comment line is a header.
+/// Lines starting with `#` are comments, e.g. the
+/// [`SimulationMetadata`](crate::utils::metadata::SimulationMetadata) header.
+/// The header should contain the `KIND` column. Fills are the events of the fill kinds
+/// and should have the `DIRECTION` (`Buy` or `Sell`), `PRICE` and `SIZE` columns filled.
+///
+/// Events are aligned by their order in the traces.
+///
+/// # Examples
+///
+/// ```
+/// use trading_backtester::utils::trace_diff::TraceDiffer;
+///
+/// let before = "DATETIME,KIND,DIRECTION,PRICE,SIZE\n\
+///               2022-01-03 10:00:00,OrderAccepted,,,\n\
+///               2022-01-03 10:00:01,OrderExecuted,Buy,100.5,2\n\
+///               2022-01-03 10:00:02,OrderExecuted,Sell,101,2\n";
+/// let after = "# seed: 3344\n\
+///              DATETIME,KIND,DIRECTION,PRICE,SIZE\n\
+///              2022-01-03 10:00:00,OrderAccepted,,,\n\
+///              2022-01-03 10:00:01,OrderExecuted,Buy,100.5,2\n";
+///
+/// let diff = TraceDiffer::new().compare(before.as_bytes(), after.as_bytes());
+/// let divergence = diff.first_divergence.unwrap();
+/// assert_eq!(divergence.event_no, 2);
+/// assert_eq!(divergence.right, None);
+/// assert_eq!(diff.left.num_fills, 2);
+/// assert_eq!(diff.left.pnl, 1.0);
+/// assert_eq!(diff.right.pnl, 0.0);
+/// ```
+pub struct TraceDiffer {
+    fill_kinds: Vec<String>,
+    ignored_columns: Vec<String>,
+}
+
+impl Default for TraceDiffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TraceDiffer {
+    /// Creates a new instance of the `TraceDiffer`
+    /// treating `OrderExecuted`, `OrderPartiallyExecuted` and `QuoteTraded` events as fills.
+    pub fn new() -> Self {
+        Self {
+            fill_kinds: ["OrderExecuted", "OrderPartiallyExecuted", "QuoteTraded"]
+                .map(String::from)
+                .into(),
+            ignored_columns: vec![],
+        }
+    }
+
+    /// Sets the kinds of the events that are fills.
+    ///
+    /// # Arguments
+    ///
+    /// * `fill_kinds` — Event kinds.
+    pub fn with_fill_kinds(
+        mut self,
+        fill_kinds: impl IntoIterator<Item=impl Into<String>>) -> Self
+    {
+        self.fill_kinds = fill_kinds.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Sets the columns to exclude when looking for the divergence,
+    /// e.g. the ones containing wall-clock times.
+    ///
+    /// # Arguments
+    ///
+    /// * `ignored_columns` — Column names.
+    pub fn with_ignored_columns(
+        mut self,
+        ignored_columns: impl IntoIterator<Item=impl Into<String>>) -> Self
+    {
+        self.ignored_columns = ignored_columns.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Compares the traces stored in the files.
+    ///
+    /// # Arguments
+    ///
+    /// * `left` — Path to the left trace.
+    /// * `right` — Path to the right trace.
+    pub fn compare_files(&self, left: impl AsRef<Path>, right: impl AsRef<Path>) -> TraceDiff {
+        let open = |path: &Path| BufReader::new(
+            File::open(path).unwrap_or_else(
+                |err| panic!("Cannot read the following file: {path:?}. Error: {err}")
+            )
+        );
+        self.compare(open(left.as_ref()), open(right.as_ref()))
+    }
+
+    /// Compares the traces.
+    ///
+    /// # Arguments
+    ///
+    /// * `left` — Left trace.
+    /// * `right` — Right trace.
+    pub fn compare(&self, left: impl BufRead, right: impl BufRead) -> TraceDiff {
+        let left = self.parse(left, "left");
+        let right = self.parse(right, "right");
+        let num_events = left.events.len().max(right.events.len());
+        let first_divergence = (0..num_events)
+            .find(|i| {
+                let left_key = left.events.get(*i).map(|(key, _)| key);
+                left_key != right.events.get(*i).map(|(key, _)| key)
+            })
+            .map(
+                |event_no| Divergence {
+                    event_no,
+                    left: left.events.get(event_no).map(|(_, line)| line.clone()),
+                    right: right.events.get(event_no).map(|(_, line)| line.clone()),
+                }
+            );
+        TraceDiff {
+            first_divergence,
+            left: left.summary,
+            right: right.summary,
+        }
+    }
+
+    fn parse(&self, trace: impl BufRead, side: &str) -> ParsedTrace {
+        let mut lines = trace.lines()
+            .map(
+                |line| line.unwrap_or_else(
+                    |err| panic!("Cannot read the {side} trace. Error: {err}")
+                )
+            )
+            .filter(|line| !line.starts_with('#') && !line.is_empty());
+        let header: Vec<String> = if let Some(header) = lines.next() {
+            header.split(',').map(|name| name.trim().to_string()).collect()
+        } else {
+            return ParsedTrace { events: vec![], summary: Default::default() };
+        };
+        let get_column = |name: &str| header.iter().position(|column| column == name);
+        let kind_col = get_column("KIND").unwrap_or_else(
+            || panic!("The {side} trace header does not contain the KIND column: {header:?}")
+        );
+        let fill_cols = (get_column("DIRECTION"), get_column("PRICE"), get_column("SIZE"));
+        let compared_cols: Vec<_> = (0..header.len())
+            .filter(|i| !self.ignored_columns.contains(&header[*i]))
+            .collect();
+
+        let mut summary = TraceSummary::default();
+        let mut last_price = None;
+        let mut events = Vec::new();
+        for (line_no, line) in lines.enumerate() {
+            let fields: Vec<_> = line.split(',').map(str::trim).collect();
+            let kind = fields.get(kind_col).copied().unwrap_or_default();
+            summary.num_events += 1;
+            *summary.num_events_by_kind.entry(kind.to_string()).or_default() += 1;
+            if self.fill_kinds.iter().any(|fill_kind| fill_kind == kind) {
+                let (direction, price, size) = if let (Some(d), Some(p), Some(s)) = fill_cols {
+                    (fields.get(d), fields.get(p), fields.get(s))
+                } else {
+                    panic!("The {side} trace contains fills but lacks DIRECTION, PRICE or SIZE")
+                };
+                let parse_fill = || -> Option<(bool, f64, i64)> {
+                    let is_buy = match *direction? {
+                        "Buy" => true,
+                        "Sell" => false,
+                        _ => return None
+                    };
+                    Some((is_buy, price?.parse().ok()?, size?.parse().ok()?))
+                };
+                let (is_buy, price, size) = parse_fill().unwrap_or_else(
+                    || panic!("Cannot parse event #{line_no} of the {side} trace: {line}")
+                );
+                if is_buy {
+                    summary.bought += size;
+                    summary.cash_flow -= price * size as f64
+                } else {
+                    summary.sold += size;
+                    summary.cash_flow += price * size as f64
+                }
+                summary.num_fills += 1;
+                last_price = Some(price)
+            }
+            let key: Vec<_> = compared_cols.iter()
+                .map(|i| fields.get(*i).copied().unwrap_or_default().to_string())
+                .collect();
+            events.push((key, line))
+        }
+        let position = (summary.bought - summary.sold) as f64;
+        summary.pnl = summary.cash_flow + last_price.map_or(0.0, |price| position * price);
+        ParsedTrace { events, summary }
+    }
+}
+
+struct ParsedTrace {
+    events: Vec<(Vec<String>, String)>,
+    summary: TraceSummary,
+}
+
+impl Display for TraceDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if let Some(Divergence { event_no, left, right }) = &self.first_divergence {
+            let or_end = |event: &Option<String>| event.clone()
+                .unwrap_or_else(|| "<end of trace>".into());
+            writeln!(f, "First divergence at event #{event_no}:")?;
+            writeln!(f, "  left:  {}", or_end(left))?;
+            writeln!(f, "  right: {}", or_end(right))?
+        } else {
+            writeln!(f, "Traces are identical")?
+        }
+        writeln!(f, "{:<24}{:>16}{:>16}{:>16}", "", "left", "right", "diff")?;
+        let mut write_row = |name: &str, left: f64, right: f64| writeln!(
+            f, "{name:<24}{left:>16.4}{right:>16.4}{:>16.4}", right - left
+        );
+        write_row("events", self.left.num_events as f64, self.right.num_events as f64)?;
+        write_row("fills", self.left.num_fills as f64, self.right.num_fills as f64)?;
+        write_row("bought", self.left.bought as f64, self.right.bought as f64)?;
+        write_row("sold", self.left.sold as f64, self.right.sold as f64)?;
+        write_row("cash flow", self.left.cash_flow, self.right.cash_flow)?;
+        write_row("pnl", self.left.pnl, self.right.pnl)?;
+        let kinds: BTreeSet<_> = self.left.num_events_by_kind.keys()
+            .chain(self.right.num_events_by_kind.keys())
+            .collect();
+        for kind in kinds {
+            let get_count = |summary: &TraceSummary| summary.num_events_by_kind.get(kind)
+                .copied()
+                .unwrap_or_default() as f64;
+            write_row(kind, get_count(&self.left), get_count(&self.right))?
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,160 @@
+use std::{
+    env::temp_dir,
+    fs::{create_dir_all, read_to_string, remove_dir_all, write},
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+const CONFIG: &str = r#"
+Defaults:
+
+  datetime_format: "%Y-%m-%d %H:%M:%S%.f"
+  csv_sep:         ','
+  open_colname:    OPEN
+  close_colname:   CLOSE
+  start_colname:   BEGIN
+  stop_colname:    STOP
+
+
+Simulation Time:
+
+  start: 2022-01-03 10:00:00
+  end:   2022-01-03 10:10:00
+
+
+Exchanges:
+
+  - name: MOEX
+    sessions:
+      path: sessions.csv
+
+
+Traded Pairs:
+
+  - exchange: MOEX
+    kind:     "Base :: Spot"
+    quoted:   USD
+    base:     RUB
+    price_step: 0.01
+    err_log_file: err_log.txt
+    start_stop_datetimes:
+      path: start_stop.csv
+    trd:
+      path_list: trd_list.txt
+      datetime_colname: Timestamp
+      reference_order_id_colname: ORDER_ID
+      price_colname: PRICE
+      size_colname: SIZE
+      buy_sell_flag_colname: BUY_SELL_FLAG
+    synthetic_book:
+      num_levels:      3
+      mean_order_size: 10.0
+      mean_lifetime:   60000000000
+      seed:            42
+
+
+Traders:
+
+  - name: 0
+    type: SpreadWriter
+    params:
+      price_step: 0.01
+      file: spread.csv
+
+  - name: 1
+    type: QuoteRecorder
+    params:
+      price_step: 0.01
+      file: quotes_{exchange}_{pair}.tsv
+      fields: [MID_PRICE, SPREAD]
+      format: tsv
+
+
+Broker:
+
+  name: TestBroker
+  params:
+    ob_snapshot_period: 30
+"#;
+
+const TRD: &str = "\
+Timestamp,ORDER_ID,PRICE,SIZE,BUY_SELL_FLAG
+2022-01-03 10:00:10.0,1,100.01,5,B
+2022-01-03 10:01:00.0,2,100.02,3,S
+2022-01-03 10:02:30.0,3,100.00,7,B
+2022-01-03 10:04:00.0,4,100.03,2,S
+2022-01-03 10:06:00.0,5,100.01,4,B
+2022-01-03 10:08:00.0,6,100.02,6,S
+";
+
+fn create_config(name: &str) -> PathBuf
+{
+    let dir = temp_dir().join(format!("trading_backtester_{name}_{}", std::process::id()));
+    let _ = remove_dir_all(&dir);
+    create_dir_all(&dir).unwrap();
+    write(dir.join("sessions.csv"), "OPEN,CLOSE\n2022-01-03 10:00:01.0,2022-01-03 10:09:00.0\n")
+        .unwrap();
+    write(dir.join("start_stop.csv"), "BEGIN,STOP\n2022-01-03 10:00:01.0,2022-01-03 10:09:00.0\n")
+        .unwrap();
+    write(dir.join("trd_list.txt"), "trd.csv").unwrap();
+    write(dir.join("trd.csv"), TRD).unwrap();
+    let config = dir.join("config.yml");
+    write(&config, CONFIG).unwrap();
+    config
+}
+
+fn run_cli(args: &[&str], config: &Path) -> Output
+{
+    Command::new(env!("CARGO_BIN_EXE_backtester-cli"))
+        .args(&args[..1])
+        .arg(config)
+        .args(&args[1..])
+        .env_remove("BACKTESTER_OVERRIDES")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_validate()
+{
+    let config = create_config("cli_validate");
+    let output = run_cli(&["validate"], &config);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(stdout.contains("Broker: TestBroker :: OB-snapshot period 30s"), "{stdout}");
+    assert!(stdout.contains("0 :: SpreadWriter"), "{stdout}");
+    assert!(stdout.contains("1 :: QuoteRecorder"), "{stdout}");
+    assert!(stdout.contains("Config is valid"), "{stdout}");
+
+    let output = run_cli(&["validate", "--set", "Traders.1.type=Unknown"], &config);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unsupported type \"Unknown\""));
+
+    remove_dir_all(config.parent().unwrap()).unwrap()
+}
+
+#[test]
+fn test_run()
+{
+    let config = create_config("cli_run");
+    let dir = config.parent().unwrap();
+    let output = run_cli(&["run", "--seed", "1"], &config);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let spreads = read_to_string(dir.join("spread.csv")).unwrap();
+    let quotes = read_to_string(dir.join("quotes_MOEX_USD_RUB.tsv")).unwrap();
+    assert_eq!(spreads.lines().next(), Some("Timestamp,BID_PRICE,BID_SIZE,ASK_PRICE,ASK_SIZE"));
+    assert_eq!(quotes.lines().next(), Some("Timestamp\tMID_PRICE\tSPREAD"));
+
+    let num_rows = spreads.lines().count() - 1;
+    assert!(num_rows > 0);
+    assert_eq!(quotes.lines().count() - 1, num_rows);
+    for trader_id in [0, 1] {
+        let rows = format!("{:<32}{:>16.4}", format!("{trader_id} :: rows"), num_rows as f64);
+        assert!(stdout.contains(&rows), "{stdout}");
+        assert!(stdout.contains(&format!("{trader_id} :: mean spread")), "{stdout}")
+    }
+
+    remove_dir_all(dir).unwrap()
+}